@@ -2,6 +2,7 @@
 use std::os::unix::ffi::OsStrExt;
 use std::{
     any,
+    collections::BTreeMap,
     convert::Infallible,
     fmt,
     marker::PhantomData,
@@ -14,7 +15,7 @@ use base64::{Engine, engine::general_purpose};
 use k8s_openapi::api::core::v1::EnvVar;
 use thiserror::Error;
 
-use crate::steal_tls::StealPortTlsConfig;
+use crate::{probes::HttpProbe, steal_tls::StealPortTlsConfig};
 
 /// Type of an environment variable value.
 pub trait EnvValue: Sized {
@@ -206,9 +207,48 @@ impl EnvValue for Vec<IpAddr> {
     }
 }
 
-/// Errors that can occur when parsing [`STEAL_TLS_CONFIG`](crate::envs::STEAL_TLS_CONFIG) value.
+/// Errors that can occur when parsing [`POD_CONTAINERS`](crate::envs::POD_CONTAINERS) value.
 #[derive(Error, Debug)]
-pub enum ParseStealTlsConfigError {
+pub enum ParsePodContainersError {
+    #[error(transparent)]
+    Utf8Error(#[from] Utf8Error),
+    #[error("expected a `name=id` pair, got `{0}`")]
+    MissingSeparator(String),
+}
+
+/// For [`POD_CONTAINERS`](crate::envs::POD_CONTAINERS) variable.
+///
+/// The value is stored as comma-separated `name=id` pairs.
+impl EnvValue for BTreeMap<String, String> {
+    type IntoReprError = Infallible;
+    type FromReprError = ParsePodContainersError;
+
+    fn as_repr(&self) -> Result<String, Self::IntoReprError> {
+        Ok(self
+            .iter()
+            .map(|(name, id)| format!("{name}={id}"))
+            .collect::<Vec<_>>()
+            .join(","))
+    }
+
+    fn from_repr(repr: &[u8]) -> Result<Self, Self::FromReprError> {
+        let as_str = std::str::from_utf8(repr)?;
+
+        as_str
+            .split_terminator(',')
+            .map(|pair| {
+                pair.split_once('=')
+                    .map(|(name, id)| (name.to_owned(), id.to_owned()))
+                    .ok_or_else(|| ParsePodContainersError::MissingSeparator(pair.to_owned()))
+            })
+            .collect()
+    }
+}
+
+/// Errors that can occur when parsing a value stored as JSON encoded with base64, e.g.
+/// [`STEAL_TLS_CONFIG`](crate::envs::STEAL_TLS_CONFIG).
+#[derive(Error, Debug)]
+pub enum ParseJsonEnvError {
     #[error("failed to decode as base64: {0}")]
     DecodeBase64Error(#[from] base64::DecodeError),
     #[error("failed to deserialize as JSON: {0}")]
@@ -220,7 +260,29 @@ pub enum ParseStealTlsConfigError {
 /// The value is stored as JSON encoded with base64.
 impl EnvValue for Vec<StealPortTlsConfig> {
     type IntoReprError = Infallible;
-    type FromReprError = ParseStealTlsConfigError;
+    type FromReprError = ParseJsonEnvError;
+
+    fn as_repr(&self) -> Result<String, Self::IntoReprError> {
+        let as_bytes = serde_json::to_vec(self).expect("serializing to memory should not fail");
+        let encoded = general_purpose::STANDARD_NO_PAD.encode(as_bytes);
+
+        Ok(encoded)
+    }
+
+    fn from_repr(repr: &[u8]) -> Result<Self, Self::FromReprError> {
+        let decoded = general_purpose::STANDARD_NO_PAD.decode(repr)?;
+        let deserialized = serde_json::from_slice(&decoded)?;
+
+        Ok(deserialized)
+    }
+}
+
+/// For [`STEAL_IGNORE_PROBES`](crate::envs::STEAL_IGNORE_PROBES) variable.
+///
+/// The value is stored as JSON encoded with base64, as the paths may contain any characters.
+impl EnvValue for Vec<HttpProbe> {
+    type IntoReprError = Infallible;
+    type FromReprError = ParseJsonEnvError;
 
     fn as_repr(&self) -> Result<String, Self::IntoReprError> {
         let as_bytes = serde_json::to_vec(self).expect("serializing to memory should not fail");
@@ -2,9 +2,12 @@
 //!
 //! If you want to add some more, please do it here.
 
-use std::net::{IpAddr, SocketAddr};
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, SocketAddr},
+};
 
-use crate::{checked_env::CheckedEnv, steal_tls::StealPortTlsConfig};
+use crate::{checked_env::CheckedEnv, probes::HttpProbe, steal_tls::StealPortTlsConfig};
 
 /// Used to pass operator's x509 certificate to the agent.
 ///
@@ -53,6 +56,16 @@ pub const LOG_LEVEL: CheckedEnv<String> = CheckedEnv::new("RUST_LOG");
 pub const STEAL_TLS_CONFIG: CheckedEnv<Vec<StealPortTlsConfig>> =
     CheckedEnv::new("MIRRORD_AGENT_STEAL_TLS_CONFIG");
 
+/// When set, the agent passes stolen connections made from within the target pod through to
+/// their original destination.
+pub const STEAL_IGNORE_LOCAL: CheckedEnv<bool> =
+    CheckedEnv::new("MIRRORD_AGENT_STEAL_IGNORE_LOCAL");
+
+/// Health check probes of the target pod, the agent passes their stolen requests through to their
+/// original destination.
+pub const STEAL_IGNORE_PROBES: CheckedEnv<Vec<HttpProbe>> =
+    CheckedEnv::new("MIRRORD_AGENT_STEAL_IGNORE_PROBES");
+
 /// Container id of the target we're attaching to, e.g. `mirrord exec -t
 /// pod/glorious-cat/container/[cat-container]`, this is the id of `cat-container` that you
 /// can retrieve with `kubectl describe glorious-cat`.
@@ -65,6 +78,13 @@ pub const STEAL_TLS_CONFIG: CheckedEnv<Vec<StealPortTlsConfig>> =
 pub const EPHEMERAL_TARGET_CONTAINER_ID: CheckedEnv<String> =
     CheckedEnv::new("MIRRORD_AGENT_EPHEMERAL_TARGET_CONTAINER_ID");
 
+/// Names and ids of all containers in the target pod, e.g. `app=4f5a...,istio-proxy=9c1d...`.
+///
+/// Used to read the environment of a container other than the target, see
+/// `feature.env.container`.
+pub const POD_CONTAINERS: CheckedEnv<BTreeMap<String, String>> =
+    CheckedEnv::new("MIRRORD_AGENT_POD_CONTAINERS");
+
 /// Exclude agent's port from service mesh sidecar proxy.
 pub const EXCLUDE_FROM_MESH: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_EXCLUDE_FROM_MESH");
 
@@ -87,6 +107,9 @@ pub const INJECT_HEADERS: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_INJE
 /// Sets the max size (in bytes) for bodies buffered for body filters.
 pub const MAX_BODY_BUFFER_SIZE: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_MAX_BODY_BUFFER_SIZE");
 
+/// Sets the max size (in bytes) of all bodies buffered for body filters at the same time.
+pub const MAX_BODY_BUFFER_TOTAL: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_MAX_BODY_BUFFER_TOTAL");
+
 /// Sets how long to wait (in milliseconds) to receive the entire body for body filters.
 pub const MAX_BODY_BUFFER_TIMEOUT: CheckedEnv<u32> =
     CheckedEnv::new("MIRRORD_MAX_BODY_BUFFER_TIMEOUT");
@@ -95,5 +118,25 @@ pub const MAX_BODY_BUFFER_TIMEOUT: CheckedEnv<u32> =
 pub const CLEAN_IPTABLES_ON_START: CheckedEnv<bool> =
     CheckedEnv::new("MIRRORD_AGENT_CLEAN_IPTABLES_ON_START");
 
-/// Jaq process time limit (ms)
-pub const JAQ_TIME_LIMIT: CheckedEnv<u64> = CheckedEnv::new("MIRRORD_JAQ_TIME_LIMIT");
+/// Time limit (in milliseconds) for evaluating jq expressions of HTTP filters.
+pub const JAQ_TIME_LIMIT_MS: CheckedEnv<u64> = CheckedEnv::new("MIRRORD_AGENT_JAQ_TIME_LIMIT_MS");
+
+/// Memory limit (in bytes) for evaluating jq expressions of HTTP filters.
+pub const JAQ_MEMORY_LIMIT_BYTES: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_JAQ_MEMORY_LIMIT_BYTES");
+
+/// Limit (in bytes per second) of the mirrored traffic sent to the clients.
+pub const MIRROR_BANDWIDTH_LIMIT: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_MIRROR_BANDWIDTH_LIMIT_BPS");
+
+/// Burst size (in bytes) of the limit set with [`MIRROR_BANDWIDTH_LIMIT`].
+pub const MIRROR_BANDWIDTH_BURST: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_MIRROR_BANDWIDTH_BURST_BYTES");
+
+/// Number of evaluator processes used for evaluating jq expressions of HTTP filters. When not set,
+/// the expressions are evaluated in the agent process.
+pub const JAQ_POOL_SIZE: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_AGENT_JAQ_POOL_SIZE");
+
+/// Number of evaluations after which the processes set with [`JAQ_POOL_SIZE`] are replaced.
+pub const JAQ_POOL_MAX_EVALUATIONS: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_JAQ_POOL_MAX_EVALUATIONS");
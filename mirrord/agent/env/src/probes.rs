@@ -0,0 +1,16 @@
+//! This module contains definition of the target pod's health check probes, which the agent can
+//! leave alone when stealing traffic.
+//!
+//! As with all definitions in this crate, keep this backwards compatible.
+
+use serde::{Deserialize, Serialize};
+
+/// An HTTP `GET` probe declared in the target pod spec.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpProbe {
+    /// Port probed by the kubelet.
+    pub port: u16,
+    /// Path of the probe requests, without the query.
+    pub path: String,
+}
@@ -4,7 +4,7 @@
 //!
 //! As with all definitions in this crate, keep this backwards compatible.
 
-use std::{ops::Not, path::PathBuf};
+use std::{fmt, ops::Not, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -26,6 +26,61 @@ pub struct TlsAuthentication {
     pub key_pem: PathBuf,
 }
 
+/// A certificate chain and a matching private key given directly in PEM format.
+///
+/// Allows for using TLS setup that is not present in the target container filesystem,
+/// e.g. a certificate provided by the user.
+#[derive(Deserialize, Serialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct InlineTlsAuthentication {
+    /// PEM data containing a certificate chain to use.
+    ///
+    /// Must contain at least one certificate.
+    /// It can contain entries of other types, e.g private keys, which are ignored.
+    pub cert_pem_data: String,
+    /// PEM data containing a private key matching the certificate chain found in
+    /// `cert_pem_data`.
+    ///
+    /// Must contain exactly one private key.
+    /// It can contain entries of other types, e.g certificates, which are ignored.
+    pub key_pem_data: String,
+}
+
+/// Does not print the PEM data, as it contains a private key.
+impl fmt::Debug for InlineTlsAuthentication {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InlineTlsAuthentication")
+            .field("cert_pem_data_len", &self.cert_pem_data.len())
+            .field("key_pem_data_len", &self.key_pem_data.len())
+            .finish()
+    }
+}
+
+/// Configures how mirrord-agent's TLS server should authenticate itself.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum AgentServerAuthentication {
+    /// Certificate chain and private key are read from PEM files in the target container
+    /// filesystem.
+    Files(TlsAuthentication),
+    /// Certificate chain and private key are given directly.
+    Inline(InlineTlsAuthentication),
+}
+
+impl From<TlsAuthentication> for AgentServerAuthentication {
+    fn from(value: TlsAuthentication) -> Self {
+        Self::Files(value)
+    }
+}
+
+impl From<InlineTlsAuthentication> for AgentServerAuthentication {
+    fn from(value: InlineTlsAuthentication) -> Self {
+        Self::Inline(value)
+    }
+}
+
 /// Configures how a TLS client should be verified.
 #[derive(Deserialize, Serialize, Clone, Debug)]
 #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
@@ -84,7 +139,7 @@ pub struct TlsServerVerification {
 #[serde(rename_all = "camelCase")]
 pub struct AgentServerConfig {
     /// Configures how the server authenticates itself to the clients.
-    pub authentication: TlsAuthentication,
+    pub authentication: AgentServerAuthentication,
     /// ALPN protocols supported by the server, in order of preference.
     ///
     /// If empty, ALPN is disabled.
@@ -1,10 +1,15 @@
 #![deny(missing_docs)]
 
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Duration};
 
 use clap::{Parser, Subcommand};
 use mirrord_agent_env::envs;
 
+use crate::{
+    http::safe_jaq::{JaqLimits, pool::JaqPoolConfig},
+    mirror::bandwidth::MirrorBandwidthLimiter,
+};
+
 const DEFAULT_RUNTIME: &str = "containerd";
 
 /// **Heads-up**: Order of arguments passed to this matter, so if you add a new arg after something
@@ -64,6 +69,86 @@ pub struct Args {
     /// are existing mirrord rules in the target's iptables.
     #[arg(long, default_value_t = false, env = envs::CLEAN_IPTABLES_ON_START.name)]
     pub clean_iptables_on_start: bool,
+
+    /// Time limit for evaluating jq expressions of HTTP filters, in milliseconds.
+    #[arg(
+        long,
+        default_value_t = JaqLimits::DEFAULT_TIME_LIMIT_MS,
+        env = envs::JAQ_TIME_LIMIT_MS.name,
+        value_parser = clap::value_parser!(u64).range(JaqLimits::TIME_LIMIT_MS_RANGE),
+    )]
+    pub jaq_time_limit_ms: u64,
+
+    /// Memory limit for evaluating jq expressions of HTTP filters, in bytes.
+    #[arg(
+        long,
+        default_value_t = JaqLimits::DEFAULT_MEMORY_LIMIT_BYTES,
+        env = envs::JAQ_MEMORY_LIMIT_BYTES.name,
+        value_parser = clap::value_parser!(u64).range(JaqLimits::MEMORY_LIMIT_BYTES_RANGE),
+    )]
+    pub jaq_memory_limit_bytes: u64,
+
+    /// Number of sandboxed processes that evaluate the jq expressions of HTTP filters. When not
+    /// set, the expressions are evaluated in the agent process.
+    #[arg(
+        long,
+        env = envs::JAQ_POOL_SIZE.name,
+        value_parser = clap::value_parser!(u32).range(JaqPoolConfig::SIZE_RANGE),
+    )]
+    pub jaq_pool_size: Option<u32>,
+
+    /// Number of evaluations after which the processes of [`Args::jaq_pool_size`] are replaced.
+    #[arg(
+        long,
+        default_value_t = JaqPoolConfig::DEFAULT_MAX_EVALUATIONS,
+        env = envs::JAQ_POOL_MAX_EVALUATIONS.name,
+        value_parser = clap::value_parser!(u64).range(1..),
+    )]
+    pub jaq_pool_max_evaluations: u64,
+
+    /// Limit of the mirrored traffic sent to the clients, in bytes per second.
+    #[arg(
+        long,
+        env = envs::MIRROR_BANDWIDTH_LIMIT.name,
+        value_parser = clap::value_parser!(u64).range(1..),
+    )]
+    pub mirror_bandwidth_limit: Option<u64>,
+
+    /// Amount of mirrored traffic that can be sent at once when under
+    /// [`Args::mirror_bandwidth_limit`], in bytes. Defaults to the traffic of one second.
+    #[arg(
+        long,
+        env = envs::MIRROR_BANDWIDTH_BURST.name,
+        value_parser = clap::value_parser!(u64).range(1..),
+    )]
+    pub mirror_bandwidth_burst: Option<u64>,
+}
+
+impl Args {
+    /// [`JaqLimits`] for the [`SafeJaq`](crate::http::safe_jaq::SafeJaq) used by the HTTP
+    /// filters.
+    pub fn jaq_limits(&self) -> JaqLimits {
+        JaqLimits {
+            time_limit: Duration::from_millis(self.jaq_time_limit_ms),
+            memory_limit: self.jaq_memory_limit_bytes,
+        }
+    }
+
+    /// [`JaqPoolConfig`] for the [`SafeJaq`](crate::http::safe_jaq::SafeJaq) used by the HTTP
+    /// filters, if the expressions are evaluated in a pool of processes.
+    pub fn jaq_pool(&self) -> Option<JaqPoolConfig> {
+        Some(JaqPoolConfig {
+            size: self.jaq_pool_size?,
+            max_evaluations: self.jaq_pool_max_evaluations,
+        })
+    }
+
+    /// [`MirrorBandwidthLimiter`] for the mirrored traffic, if it is limited.
+    pub fn mirror_bandwidth_limiter(&self) -> Option<MirrorBandwidthLimiter> {
+        let rate = self.mirror_bandwidth_limit?;
+        let burst = self.mirror_bandwidth_burst.unwrap_or(rate);
+        Some(MirrorBandwidthLimiter::new(rate, burst))
+    }
 }
 
 #[derive(Clone, Debug, Default, Subcommand)]
@@ -13,10 +13,10 @@ use hickory_resolver::{
 };
 use mirrord_agent_env::envs;
 use mirrord_protocol::{
-    DnsLookupError, ResolveErrorKindInternal, ResponseError,
+    DaemonMessage, DnsLookupError, ResolveErrorKindInternal, ResponseError,
     dns::{
-        AddressFamily, DnsLookup, GetAddrInfoRequest, GetAddrInfoRequestV2, GetAddrInfoResponse,
-        LookupRecord,
+        ADDRINFO_TTL_VERSION, AddressFamily, DnsLookup, GetAddrInfoRequest, GetAddrInfoRequestV2,
+        GetAddrInfoResponse, GetAddrInfoResponseV2, LookupRecord,
     },
 };
 use thiserror::Error;
@@ -31,7 +31,10 @@ use tokio::{
 use tokio_util::sync::CancellationToken;
 use tracing::{Level, warn};
 
-use crate::{error::AgentResult, metrics::DNS_REQUEST_COUNT, task::status::BgTaskStatus};
+use crate::{
+    error::AgentResult, metrics::DNS_REQUEST_COUNT, task::status::BgTaskStatus,
+    util::protocol_version::ClientProtocolVersion,
+};
 
 #[derive(Debug)]
 pub(crate) enum ClientGetAddrInfoRequest {
@@ -48,11 +51,19 @@ impl ClientGetAddrInfoRequest {
     }
 }
 
+/// Successful result of [`DnsWorker::do_lookup`].
+#[derive(Debug)]
+pub(crate) struct TtlDnsLookup {
+    lookup: DnsLookup,
+    /// Lowest TTL of the resolved records, in seconds.
+    ttl: Option<u32>,
+}
+
 /// Sent from per-client [`DnsApi`] to the global [`DnsWorker`].
 #[derive(Debug)]
 pub(crate) struct DnsCommand {
     request: ClientGetAddrInfoRequest,
-    response_tx: oneshot::Sender<Result<DnsLookup, ResolveErrorKindInternal>>,
+    response_tx: oneshot::Sender<Result<TtlDnsLookup, ResolveErrorKindInternal>>,
 }
 
 /// Background task for resolving hostnames to IP addresses.
@@ -78,8 +89,8 @@ pub(crate) struct DnsWorker {
     /// Background tasks that handle the DNS requests.
     ///
     /// Each of these builds a new [`TokioAsyncResolver`] and performs one lookup.
-    tasks: JoinSet<Result<DnsLookup, InternalLookupError>>,
-    response_txs: HashMap<Id, oneshot::Sender<Result<DnsLookup, ResolveErrorKindInternal>>>,
+    tasks: JoinSet<Result<TtlDnsLookup, InternalLookupError>>,
+    response_txs: HashMap<Id, oneshot::Sender<Result<TtlDnsLookup, ResolveErrorKindInternal>>>,
 }
 
 impl DnsWorker {
@@ -141,7 +152,7 @@ impl DnsWorker {
         attempts: Option<usize>,
         timeout: Option<Duration>,
         support_ipv6: bool,
-    ) -> Result<DnsLookup, InternalLookupError> {
+    ) -> Result<TtlDnsLookup, InternalLookupError> {
         // Prepares the `Resolver` after reading some `/etc` DNS files.
         //
         // We care about logging these errors, at an `error!` level.
@@ -191,10 +202,19 @@ impl DnsWorker {
             .lookup_ip(request.node)
             .await
             .inspect(|lookup| tracing::trace!(?lookup, "DNS lookup finished"))
-            .inspect_err(|e| tracing::debug!(%e, "DNS lookup failed"))?
-            .convert();
+            .inspect_err(|e| tracing::debug!(%e, "DNS lookup failed"))?;
+
+        let ttl = lookup
+            .as_lookup()
+            .records()
+            .iter()
+            .map(|record| record.ttl())
+            .min();
 
-        Ok(lookup)
+        Ok(TtlDnsLookup {
+            lookup: lookup.convert(),
+            ttl,
+        })
     }
 
     /// Handles the given [`DnsCommand`] in a separate [`tokio::task`].
@@ -270,15 +290,22 @@ pub(crate) struct DnsApi {
     request_tx: Sender<DnsCommand>,
     /// [`DnsWorker`] processes all requests concurrently, so we use a combination of [`oneshot`]
     /// channels and [`FuturesOrdered`] to preserve order of responses.
-    responses: FuturesOrdered<oneshot::Receiver<Result<DnsLookup, ResolveErrorKindInternal>>>,
+    responses: FuturesOrdered<oneshot::Receiver<Result<TtlDnsLookup, ResolveErrorKindInternal>>>,
+    /// Determines whether we can respond with [`GetAddrInfoResponseV2`].
+    protocol_version: ClientProtocolVersion,
 }
 
 impl DnsApi {
-    pub(crate) fn new(task_status: BgTaskStatus, task_sender: Sender<DnsCommand>) -> Self {
+    pub(crate) fn new(
+        task_status: BgTaskStatus,
+        task_sender: Sender<DnsCommand>,
+        protocol_version: ClientProtocolVersion,
+    ) -> Self {
         Self {
             task_status,
             request_tx: task_sender,
             responses: Default::default(),
+            protocol_version,
         }
     }
 
@@ -307,20 +334,31 @@ impl DnsApi {
     /// Returns the result of the oldest outstanding DNS request issued with this struct (see
     /// [`Self::make_request`]).
     ///
+    /// The result is a [`DaemonMessage::GetAddrInfoResponseV2`] if the client supports
+    /// [`ADDRINFO_TTL_VERSION`], and a [`DaemonMessage::GetAddrInfoResponse`] otherwise.
+    ///
     /// If there is no outstanding DNS request, never returns.
     #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
-    pub(crate) async fn recv(&mut self) -> AgentResult<GetAddrInfoResponse> {
+    pub(crate) async fn recv(&mut self) -> AgentResult<DaemonMessage> {
         let Some(response) = self.responses.next().await else {
             return future::pending().await;
         };
 
-        match response {
-            Ok(response) => {
-                Ok(GetAddrInfoResponse(response.map_err(|kind| {
-                    ResponseError::DnsLookup(DnsLookupError { kind })
-                })))
-            }
-            Err(..) => Err(self.task_status.wait_assert_running().await),
+        let (response, ttl) = match response {
+            Ok(Ok(TtlDnsLookup { lookup, ttl })) => (GetAddrInfoResponse(Ok(lookup)), ttl),
+            Ok(Err(kind)) => (
+                GetAddrInfoResponse(Err(ResponseError::DnsLookup(DnsLookupError { kind }))),
+                None,
+            ),
+            Err(..) => return Err(self.task_status.wait_assert_running().await),
+        };
+
+        if self.protocol_version.matches(&ADDRINFO_TTL_VERSION) {
+            Ok(DaemonMessage::GetAddrInfoResponseV2(
+                GetAddrInfoResponseV2 { response, ttl },
+            ))
+        } else {
+            Ok(DaemonMessage::GetAddrInfoResponse(response))
         }
     }
 }
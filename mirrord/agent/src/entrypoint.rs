@@ -1,6 +1,7 @@
 use std::{
-    collections::HashMap,
-    mem,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    io, mem,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr},
     ops::Not,
     path::PathBuf,
@@ -14,13 +15,16 @@ use async_pidfd::AsyncPidFd;
 use client_connection::AgentTlsConnector;
 use dns::{ClientGetAddrInfoRequest, DnsCommand};
 use futures::{TryFutureExt, future::OptionFuture};
-use metrics::{CLIENT_COUNT, start_metrics};
+use metrics::{CLIENT_COUNT, CLIENT_SESSIONS, start_metrics};
 use mirrord_agent_env::envs;
 use mirrord_agent_iptables::{
     IPTablesWrapper, SafeIpTables,
     error::{IPTablesError, IPTablesResult},
 };
-use mirrord_protocol::{ClientMessage, DaemonMessage, GetEnvVarsRequest};
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, GetContainerEnvVarsRequest, GetEnvVarsRequest, RemoteResult,
+    ResponseError, compression::CompressionCodec,
+};
 use tokio::{
     net::{TcpListener, TcpSocket, TcpStream},
     process::Command,
@@ -42,9 +46,13 @@ use crate::{
     env,
     error::{AgentError, AgentResult},
     file::FileManager,
+    http::safe_jaq::{
+        SafeJaq,
+        pool::{EVALUATOR_ENV, run_evaluator},
+    },
     incoming::MirrorHandle,
     metrics,
-    mirror::TcpMirrorApi,
+    mirror::{TcpMirrorApi, bandwidth::MirrorBandwidthLimiter},
     namespace::NamespaceType,
     outgoing::{TcpOutgoingApi, UdpOutgoingApi},
     reverse_dns::ReverseDnsApi,
@@ -77,6 +85,17 @@ or the previous agent failed to clean up before exit. \
 The leftover rules were cleaned and the agent is starting. \
 To allow concurrent sessions, consider using the operator available in mirrord for Teams: https://app.metalbear.com/?utm_source=dirtyiptables&utm_medium=agent";
 
+/// Containers of the target pod, from [`envs::POD_CONTAINERS`].
+#[derive(Debug, Default)]
+struct PodContainers {
+    /// Id of the target container.
+    target_id: String,
+    /// Container runtime of the target pod.
+    runtime: String,
+    /// Names and ids of all containers in the target pod.
+    ids: BTreeMap<String, String>,
+}
+
 /// Keeps track of next client id.
 /// Stores common data used when serving client connections.
 /// Can be cheaply cloned and passed to per-client background tasks.
@@ -89,11 +108,16 @@ struct State {
     /// pausing. When those args are not passed, container is [`None`].
     container: Option<ContainerHandle>,
     env: Arc<HashMap<String, String>>,
+    /// Containers of the target pod, used to read the environment of a container other than the
+    /// target.
+    pod_containers: Arc<PodContainers>,
     ephemeral: bool,
     /// When present, it is used to secure incoming TCP connections.
     tls_connector: Option<AgentTlsConnector>,
     /// [`tokio::runtime`] that should be used for network operations ([`BackgroundTasks`]).
     network_runtime: Arc<BgTaskRuntime>,
+    /// Limits the mirrored traffic of all clients, see `agent.mirror_bandwidth_limit`.
+    mirror_bandwidth: Option<MirrorBandwidthLimiter>,
 }
 
 impl State {
@@ -107,6 +131,7 @@ impl State {
             .transpose()?;
 
         let mut env: HashMap<String, String> = HashMap::new();
+        let mut pod_containers = PodContainers::default();
 
         let (ephemeral, container) = match &args.mode {
             cli::Mode::Targeted {
@@ -114,6 +139,12 @@ impl State {
                 container_runtime,
                 ..
             } => {
+                pod_containers = PodContainers {
+                    target_id: container_id.clone(),
+                    runtime: container_runtime.clone(),
+                    ids: envs::POD_CONTAINERS.from_env_or_default(),
+                };
+
                 let container = get_container(container_id.clone(), container_runtime).await?;
 
                 let container_handle = ContainerHandle::new(container).await?;
@@ -167,9 +198,11 @@ impl State {
             next_client_id: Default::default(),
             container,
             env: Arc::new(env),
+            pod_containers: Arc::new(pod_containers),
             ephemeral,
             tls_connector,
             network_runtime: Arc::new(network_runtime),
+            mirror_bandwidth: args.mirror_bandwidth_limiter(),
         })
     }
 
@@ -178,6 +211,55 @@ impl State {
         self.container.as_ref().map(ContainerHandle::pid)
     }
 
+    /// Returns the environment of the target pod container with the given `name`.
+    ///
+    /// The environment of the target container is taken from [`State::env`], other containers
+    /// are inspected with the container runtime, and their environment is read from
+    /// `/proc/<pid>/environ` of their main process.
+    #[tracing::instrument(level = Level::TRACE, skip(self), err)]
+    async fn container_env(&self, name: &str) -> RemoteResult<Cow<'_, HashMap<String, String>>> {
+        let PodContainers {
+            target_id,
+            runtime,
+            ids,
+        } = self.pod_containers.as_ref();
+
+        let Some(id) = ids.get(name) else {
+            return Err(ResponseError::UnknownContainer {
+                name: name.to_owned(),
+                available: ids.keys().cloned().collect(),
+            });
+        };
+
+        if id == target_id {
+            return Ok(Cow::Borrowed(self.env.as_ref()));
+        }
+
+        let into_response_error = |error: AgentError| {
+            error!(%error, container = name, "Failed to read the container environment");
+            ResponseError::from(io::Error::other(error.to_string()))
+        };
+
+        let container = get_container(id.clone(), runtime)
+            .await
+            .map_err(|error| into_response_error(error.into()))?;
+        let container_handle = ContainerHandle::new(container)
+            .await
+            .map_err(into_response_error)?;
+
+        let mut env = container_handle.raw_env().clone();
+        let environ_path = PathBuf::from("/proc")
+            .join(container_handle.pid().to_string())
+            .join("environ");
+        env.extend(
+            env::get_proc_environ(environ_path)
+                .await
+                .map_err(into_response_error)?,
+        );
+
+        Ok(Cow::Owned(env))
+    }
+
     pub async fn serve_client_connection(
         self,
         stream: TcpStream,
@@ -278,6 +360,9 @@ struct ClientConnectionHandler {
     ready_for_logs: bool,
     /// Client's version of [`mirrord_protocol`].
     protocol_version: ClientProtocolVersion,
+    /// Codec used to compress bulk [`DaemonMessage`]s, negotiated with
+    /// [`ClientMessage::SwitchCompression`].
+    compression: CompressionCodec,
 }
 
 impl Drop for ClientConnectionHandler {
@@ -301,9 +386,13 @@ impl ClientConnectionHandler {
 
         let file_manager = FileManager::new(pid.or_else(|| state.ephemeral.then_some(1)));
 
-        let tcp_mirror_api = bg_tasks
-            .mirror_handle
-            .map(|mirror_handle| TcpMirrorApi::new(mirror_handle, protocol_version.clone()));
+        let tcp_mirror_api = bg_tasks.mirror_handle.map(|mirror_handle| {
+            TcpMirrorApi::new(
+                mirror_handle,
+                protocol_version.clone(),
+                state.mirror_bandwidth.clone(),
+            )
+        });
         let tcp_stealer_api = Self::create_stealer_api(
             id,
             protocol_version.clone(),
@@ -311,7 +400,7 @@ impl ClientConnectionHandler {
             &mut connection,
         )
         .await?;
-        let dns_api = Self::create_dns_api(bg_tasks.dns);
+        let dns_api = Self::create_dns_api(bg_tasks.dns, protocol_version.clone());
         let reverse_dns_api = ReverseDnsApi::new(&state.network_runtime);
         let tcp_outgoing_api = TcpOutgoingApi::new(&state.network_runtime);
         let udp_outgoing_api = UdpOutgoingApi::new(&state.network_runtime);
@@ -329,9 +418,11 @@ impl ClientConnectionHandler {
             state,
             ready_for_logs: false,
             protocol_version,
+            compression: CompressionCodec::None,
         };
 
         CLIENT_COUNT.fetch_add(1, Ordering::Relaxed);
+        CLIENT_SESSIONS.inc();
 
         Ok(client_handler)
     }
@@ -362,10 +453,13 @@ impl ClientConnectionHandler {
         }
     }
 
-    fn create_dns_api(task: BackgroundTask<DnsCommand>) -> DnsApi {
+    fn create_dns_api(
+        task: BackgroundTask<DnsCommand>,
+        protocol_version: ClientProtocolVersion,
+    ) -> DnsApi {
         match task {
             BackgroundTask::Running(task_status, task_sender) => {
-                DnsApi::new(task_status, task_sender)
+                DnsApi::new(task_status, task_sender, protocol_version)
             }
             BackgroundTask::Disabled => unreachable!("dns task is never disabled"),
         }
@@ -437,7 +531,7 @@ impl ClientConnectionHandler {
                     Err(e) => break e,
                 },
                 message = self.dns_api.recv() => match message {
-                    Ok(message) => self.respond(DaemonMessage::GetAddrInfoResponse(message)).await?,
+                    Ok(message) => self.respond(message).await?,
                     Err(e) => break e,
                 },
                 message = self.reverse_dns_api.recv() => match message {
@@ -462,6 +556,7 @@ impl ClientConnectionHandler {
             return Ok(());
         }
 
+        let response = response.compress(self.compression);
         self.connection.send(response).await.map_err(Into::into)
     }
 
@@ -504,6 +599,27 @@ impl ClientConnectionHandler {
                 self.respond(DaemonMessage::GetEnvVarsResponse(env_vars_result))
                     .await?
             }
+            ClientMessage::GetContainerEnvVarsRequest(GetContainerEnvVarsRequest {
+                container,
+                env_vars_filter,
+                env_vars_select,
+            }) => {
+                debug!(
+                    "ClientMessage::GetContainerEnvVarsRequest client id {:?} container {:?} filter {:?} select {:?}",
+                    self.id, container, env_vars_filter, env_vars_select
+                );
+
+                let env_vars_result =
+                    self.state
+                        .container_env(&container)
+                        .await
+                        .and_then(|container_env| {
+                            env::select_env_vars(&container_env, env_vars_filter, env_vars_select)
+                        });
+
+                self.respond(DaemonMessage::GetEnvVarsResponse(env_vars_result))
+                    .await?
+            }
             ClientMessage::GetAddrInfoRequest(request) => {
                 self.dns_api
                     .make_request(ClientGetAddrInfoRequest::V1(request))
@@ -570,6 +686,20 @@ impl ClientConnectionHandler {
             ClientMessage::ReadyForLogs => {
                 self.ready_for_logs = true;
             }
+            ClientMessage::SwitchCompression(client_codecs) => {
+                let codec = CompressionCodec::negotiate(&client_codecs);
+
+                self.respond(DaemonMessage::SwitchCompressionResponse(codec))
+                    .await?;
+                self.compression = codec;
+            }
+            ClientMessage::ClientIdentity(identity) => {
+                tracing::debug!(client_id = self.id, %identity, "Client identified");
+
+                if let Some(tcp_stealer_api) = self.tcp_stealer_api.as_mut() {
+                    tcp_stealer_api.set_identity(identity).await?;
+                }
+            }
             ClientMessage::Vpn(_message) => {
                 self.respond(DaemonMessage::Close("VPN is not supported".into()))
                     .await?;
@@ -727,6 +857,38 @@ async fn check_existing_rules(
 /// starts background tasks and listens for client connections.
 #[tracing::instrument(level = Level::TRACE, ret, err)]
 async fn start_agent(args: Args) -> AgentResult<()> {
+    let jaq_limits = args.jaq_limits();
+    tracing::info!(
+        ?jaq_limits,
+        "Limits of jq expression evaluation in HTTP filters."
+    );
+    metrics::JAQ_TIME_LIMIT_MS.set(
+        jaq_limits
+            .time_limit
+            .as_millis()
+            .try_into()
+            .unwrap_or(i64::MAX),
+    );
+    metrics::JAQ_MEMORY_LIMIT_BYTES.set(jaq_limits.memory_limit.try_into().unwrap_or(i64::MAX));
+
+    let mut safe_jaq = SafeJaq::new(jaq_limits);
+    if let Some(pool) = args.jaq_pool() {
+        tracing::info!(
+            ?pool,
+            "jq expressions of HTTP filters are evaluated in a pool of processes."
+        );
+        safe_jaq = safe_jaq.with_pool(pool);
+    }
+    safe_jaq.install();
+
+    if let Some(limit) = args.mirror_bandwidth_limit {
+        tracing::info!(
+            limit,
+            burst = args.mirror_bandwidth_burst.unwrap_or(limit),
+            "Mirrored traffic is limited (in bytes per second)."
+        );
+    }
+
     // Prepares a TCP listener for accepting client connections.
     let setup_listener = |ipv6: bool| -> AgentResult<TcpListener> {
         let (socket, ip) = if ipv6 {
@@ -1076,7 +1238,15 @@ async fn start_iptable_guard(args: Args) -> AgentResult<()> {
 /// This weird flow is a safety measure - should the real agent OOM (which means instant process
 /// termination) or be killed with a signal, the parent will a chance to clean iptables. If we leave
 /// the iptables dirty, the whole target pod is broken, probably forever.
+///
+/// When started with [`EVALUATOR_ENV`], the binary runs as a jq evaluator process instead, see
+/// [`run_evaluator`].
 pub async fn main() -> AgentResult<()> {
+    // Evaluator processes use stdout for the evaluation results, so they must not set up tracing.
+    if let Ok(memory_limit) = std::env::var(EVALUATOR_ENV) {
+        return Ok(run_evaluator(&memory_limit)?);
+    }
+
     rustls::crypto::CryptoProvider::install_default(rustls::crypto::aws_lc_rs::default_provider())
         .expect("Failed to install crypto provider");
 
@@ -10,7 +10,7 @@ use crate::{
         self, MirrorHandle, RedirectorTask, RedirectorTaskConfig, StealHandle,
         tls::StealTlsHandlerStore,
     },
-    steal::{StealerCommand, TcpStealerTask},
+    steal::{StealExclusions, StealerCommand, TcpStealerTask},
     task::{BgTaskRuntime, status::IntoStatus},
     util::path_resolver::InTargetPathResolver,
 };
@@ -67,9 +67,11 @@ pub(super) fn start_stealer(
 
     let (command_tx, command_rx) = mpsc::channel::<StealerCommand>(1000);
 
-    let task_status =
-        tokio::spawn(TcpStealerTask::new(command_rx, steal_handle).run(cancellation_token))
-            .into_status("TcpStealerTask");
+    let task_status = tokio::spawn(
+        TcpStealerTask::new(command_rx, steal_handle, StealExclusions::from_env())
+            .run(cancellation_token),
+    )
+    .into_status("TcpStealerTask");
 
     BackgroundTask::Running(task_status, command_tx)
 }
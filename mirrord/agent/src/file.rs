@@ -2,13 +2,18 @@ use std::{
     self,
     borrow::Cow,
     collections::{HashMap, VecDeque, hash_map::Entry},
+    ffi::CString,
     fs::{File, OpenOptions, ReadDir, read_link},
     io::{self, SeekFrom, prelude::*},
     iter::{Enumerate, Peekable},
-    ops::RangeInclusive,
+    ops::{Not, RangeInclusive},
     os::{
         fd::{AsRawFd, RawFd},
-        unix::{ffi::OsStrExt, fs::MetadataExt, prelude::FileExt},
+        unix::{
+            ffi::{OsStrExt, OsStringExt},
+            fs::MetadataExt,
+            prelude::FileExt,
+        },
     },
     path::{Path, PathBuf, StripPrefixError},
     ptr,
@@ -53,6 +58,41 @@ pub enum RemoteFile {
     Directory(PathBuf),
 }
 
+/// File that an extended attributes request refers to, see [`FileManager::xattr_target`].
+enum XattrTarget {
+    Fd(RawFd),
+    /// Resolved path, and whether symlinks should be followed.
+    Path(PathBuf, bool),
+}
+
+/// Calls one of the `*getxattr` or `*listxattr` functions, first to get the size of the value,
+/// then to read it.
+///
+/// Retries when the value grows between the calls.
+fn read_xattr<F>(mut call: F) -> io::Result<Vec<u8>>
+where
+    F: FnMut(*mut libc::c_void, usize) -> isize,
+{
+    loop {
+        let size = call(ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buffer = vec![0_u8; size as usize];
+        let read = call(buffer.as_mut_ptr().cast(), buffer.len());
+        if read >= 0 {
+            buffer.truncate(read as usize);
+            return Ok(buffer);
+        }
+
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::ERANGE) {
+            return Err(error);
+        }
+    }
+}
+
 fn log_err(entry_res: io::Result<DirEntryInternal>) -> io::Result<DirEntryInternal> {
     entry_res.inspect_err(|err| error!("Converting DirEntry failed with {err:?}"))
 }
@@ -264,6 +304,26 @@ impl FileManager {
             FileRequest::Fchmod(FchmodRequest { fd, mode }) => {
                 Some(FileResponse::Fchmod(self.fchmod(fd, mode)))
             }
+            FileRequest::GetXattr(GetXattrRequest {
+                path,
+                fd,
+                follow_symlink,
+                name,
+            }) => Some(FileResponse::GetXattr(self.getxattr(
+                path,
+                fd,
+                follow_symlink,
+                name,
+            ))),
+            FileRequest::ListXattr(ListXattrRequest {
+                path,
+                fd,
+                follow_symlink,
+            }) => Some(FileResponse::ListXattr(self.listxattr(
+                path,
+                fd,
+                follow_symlink,
+            ))),
         })
     }
 
@@ -811,6 +871,110 @@ impl FileManager {
         .map_err(ResponseError::from)
     }
 
+    /// Returns the file that the extended attributes requests refer to.
+    ///
+    /// Paths are resolved in the target's root, see [`InTargetPathResolver`].
+    fn xattr_target(
+        &self,
+        path: Option<PathBuf>,
+        fd: Option<u64>,
+        follow_symlink: bool,
+    ) -> RemoteResult<XattrTarget> {
+        let path = match (path, fd) {
+            (Some(path), None) => path,
+            (None, Some(fd)) => {
+                return match self
+                    .open_files
+                    .get(&fd)
+                    .ok_or(ResponseError::NotFound(fd))?
+                {
+                    RemoteFile::File(file) => Ok(XattrTarget::Fd(file.as_raw_fd())),
+                    RemoteFile::Directory(path) => Ok(XattrTarget::Path(path.clone(), true)),
+                };
+            }
+            _ => return Err(io::Error::from(io::ErrorKind::InvalidInput).into()),
+        };
+
+        let path = if follow_symlink {
+            self.resolve_path(&path)?.into_owned()
+        } else if let Some(resolver) = self.path_resolver.as_ref() {
+            let path = path.strip_prefix_root().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "couldn't strip prefix")
+            })?;
+            resolver.root_path().join(path)
+        } else {
+            path
+        };
+
+        Ok(XattrTarget::Path(path, follow_symlink))
+    }
+
+    #[tracing::instrument(level = Level::TRACE, skip(self), err(level = Level::DEBUG))]
+    pub(crate) fn getxattr(
+        &mut self,
+        path: Option<PathBuf>,
+        fd: Option<u64>,
+        follow_symlink: bool,
+        name: Vec<u8>,
+    ) -> RemoteResult<GetXattrResponse> {
+        let name = CString::new(name).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+
+        let value = match self.xattr_target(path, fd, follow_symlink)? {
+            XattrTarget::Fd(fd) => read_xattr(|buffer, size| unsafe {
+                libc::fgetxattr(fd, name.as_ptr(), buffer, size)
+            }),
+            XattrTarget::Path(path, follow_symlink) => {
+                let path = CString::new(path.into_os_string().into_vec())
+                    .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+                read_xattr(|buffer, size| unsafe {
+                    if follow_symlink {
+                        libc::getxattr(path.as_ptr(), name.as_ptr(), buffer, size)
+                    } else {
+                        libc::lgetxattr(path.as_ptr(), name.as_ptr(), buffer, size)
+                    }
+                })
+            }
+        }?;
+
+        Ok(GetXattrResponse {
+            value: value.into(),
+        })
+    }
+
+    #[tracing::instrument(level = Level::TRACE, skip(self), err(level = Level::DEBUG))]
+    pub(crate) fn listxattr(
+        &mut self,
+        path: Option<PathBuf>,
+        fd: Option<u64>,
+        follow_symlink: bool,
+    ) -> RemoteResult<ListXattrResponse> {
+        let list = match self.xattr_target(path, fd, follow_symlink)? {
+            XattrTarget::Fd(fd) => {
+                read_xattr(|buffer, size| unsafe { libc::flistxattr(fd, buffer.cast(), size) })
+            }
+            XattrTarget::Path(path, follow_symlink) => {
+                let path = CString::new(path.into_os_string().into_vec())
+                    .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+                read_xattr(|buffer, size| unsafe {
+                    if follow_symlink {
+                        libc::listxattr(path.as_ptr(), buffer.cast(), size)
+                    } else {
+                        libc::llistxattr(path.as_ptr(), buffer.cast(), size)
+                    }
+                })
+            }
+        }?;
+
+        // The list is a sequence of null-terminated names.
+        let names = list
+            .split(|byte| *byte == 0)
+            .filter(|name| name.is_empty().not())
+            .map(<[u8]>::to_vec)
+            .collect();
+
+        Ok(ListXattrResponse { names })
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     pub(crate) fn fstatfs(&mut self, fd: u64) -> RemoteResult<XstatFsResponseV2> {
         let target = self
@@ -1029,3 +1193,102 @@ impl FileManager {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    use mirrord_protocol::{ResponseError, file::*};
+
+    use super::FileManager;
+
+    /// Fields of [`FsMetadataInternalV2`] that don't change when the filesystem is in use.
+    fn stable_fields(metadata: FsMetadataInternalV2) -> (i64, i64, u64, u64, [i32; 2], i64, i64) {
+        (
+            metadata.filesystem_type,
+            metadata.block_size,
+            metadata.blocks,
+            metadata.files,
+            metadata.filesystem_id,
+            metadata.name_len,
+            metadata.fragment_size,
+        )
+    }
+
+    /// `statfs` and `fstatfs` results sent to the client match the agent's own `statfs`.
+    #[test]
+    fn statfs_matches_direct() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"mirrord").unwrap();
+
+        let direct: FsMetadataInternalV2 = nix::sys::statfs::statfs(dir.path()).unwrap().into();
+
+        let mut manager = FileManager::new(None);
+        let by_path = manager.statfs(dir.path().to_owned()).unwrap().metadata;
+        let OpenFileResponse { fd } = manager
+            .open(
+                file,
+                OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let by_fd = manager.fstatfs(fd).unwrap().metadata;
+
+        assert_eq!(stable_fields(by_path), stable_fields(direct));
+        assert_eq!(stable_fields(by_fd), stable_fields(direct));
+    }
+
+    #[test]
+    fn xattr() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"mirrord").unwrap();
+
+        let path = CString::new(file.as_os_str().as_bytes()).unwrap();
+        let result = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                c"user.mirrord".as_ptr(),
+                b"value".as_ptr().cast(),
+                5,
+                0,
+            )
+        };
+        if result != 0 {
+            // Filesystem of the temporary directory without user extended attributes.
+            return;
+        }
+
+        let mut manager = FileManager::new(None);
+        let OpenFileResponse { fd } = manager
+            .open(
+                file.clone(),
+                OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let by_path = manager
+            .getxattr(Some(file.clone()), None, true, b"user.mirrord".to_vec())
+            .unwrap();
+        let by_fd = manager
+            .getxattr(None, Some(fd), true, b"user.mirrord".to_vec())
+            .unwrap();
+        assert_eq!(&by_path.value[..], b"value");
+        assert_eq!(by_path, by_fd);
+
+        let missing = manager.getxattr(Some(file.clone()), None, false, b"user.missing".to_vec());
+        assert!(matches!(
+            missing,
+            Err(ResponseError::RemoteIO(error)) if error.raw_os_error == Some(libc::ENODATA)
+        ));
+
+        let names = manager.listxattr(None, Some(fd), true).unwrap().names;
+        assert!(names.contains(&b"user.mirrord".to_vec()));
+    }
+}
@@ -2,7 +2,7 @@ use std::{io, ops::Not, time::Duration};
 
 use bytes::{Bytes, BytesMut};
 use futures::future::OptionFuture;
-use http::Response;
+use http::{Method, Response, StatusCode};
 use http_body_util::combinators::BoxBody;
 use httparse::Status;
 use tokio::{
@@ -14,9 +14,13 @@ use tracing::Level;
 use crate::util::rolledback_stream::RolledBackStream;
 
 pub mod body;
+pub mod body_budget;
 pub mod error;
 pub mod extract_requests;
 pub mod filter;
+pub mod grpc;
+pub mod request_view;
+pub mod safe_jaq;
 pub mod sender;
 
 /// When the corresponding config flag is enabled, a header with this
@@ -27,6 +31,16 @@ pub(crate) const MIRRORD_AGENT_HTTP_HEADER_NAME: &str = "Mirrord-Agent";
 /// [`Response`] type with a boxed body.
 pub type BoxResponse = Response<BoxBody<Bytes, hyper::Error>>;
 
+/// Returns whether the response hands the connection over to another protocol, after which raw
+/// bytes should be copied in both directions.
+///
+/// This is the case for `101 Switching Protocols` responses (e.g. WebSockets), and for successful
+/// responses to `CONNECT` requests.
+pub fn is_upgrade(request_method: &Method, response_status: StatusCode) -> bool {
+    response_status == StatusCode::SWITCHING_PROTOCOLS
+        || (*request_method == Method::CONNECT && response_status.is_success())
+}
+
 /// Helper enum for representing HTTP/1.x and HTTP/2, which are handled very differently in some
 /// parts of the code.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
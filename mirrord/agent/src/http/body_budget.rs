@@ -0,0 +1,202 @@
+//! Memory accounting of the HTTP request bodies buffered for body filters, see
+//! [`BodyBufferBudget`].
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use mirrord_agent_env::envs;
+
+use crate::{metrics::BODY_BUFFER_BYTES, util::ClientId};
+
+/// Default of [`BodyBufferBudget::ceiling`], 64MiB.
+const DEFAULT_MAX_BODY_BUFFER_TOTAL: usize = 64 * 1024 * 1024;
+
+static MAX_BODY_BUFFER_TOTAL: LazyLock<usize> = LazyLock::new(|| {
+    match envs::MAX_BODY_BUFFER_TOTAL.try_from_env() {
+        Ok(Some(t)) => Some(t as usize),
+        Ok(None) => {
+            tracing::debug!(
+                "{} not set, using default",
+                envs::MAX_BODY_BUFFER_TOTAL.name
+            );
+            None
+        }
+        Err(error) => {
+            tracing::warn!(
+                ?error,
+                "failed to parse {}, using default",
+                envs::MAX_BODY_BUFFER_TOTAL.name
+            );
+            None
+        }
+    }
+    .unwrap_or(DEFAULT_MAX_BODY_BUFFER_TOTAL)
+});
+
+/// Bytes of HTTP request bodies buffered for body filters, accounted per agent client, with a
+/// global ceiling.
+///
+/// A body is accounted once towards the ceiling, and to every client whose filter waits for it.
+/// The memory is taken with [`BodyBufferBudget::reserve`], and given back when the returned
+/// [`BodyBufferReservation`] is dropped.
+#[derive(Clone, Debug)]
+pub struct BodyBufferBudget {
+    ceiling: usize,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    total: usize,
+    per_client: HashMap<ClientId, usize>,
+}
+
+impl BudgetState {
+    fn client_usage(&self, client_id: ClientId) -> usize {
+        self.per_client.get(&client_id).copied().unwrap_or_default()
+    }
+
+    /// Sets the usage of the client, and updates [`BODY_BUFFER_BYTES`].
+    fn set_client_usage(&mut self, client_id: ClientId, usage: usize) {
+        let label = client_id.to_string();
+
+        if usage == 0 {
+            self.per_client.remove(&client_id);
+            let _ = BODY_BUFFER_BYTES.remove_label_values(&[&label]);
+        } else {
+            self.per_client.insert(client_id, usage);
+            BODY_BUFFER_BYTES
+                .with_label_values(&[&label])
+                .set(usage.try_into().unwrap_or(i64::MAX));
+        }
+    }
+}
+
+impl Default for BodyBufferBudget {
+    /// Uses the ceiling from [`envs::MAX_BODY_BUFFER_TOTAL`].
+    fn default() -> Self {
+        Self::new(*MAX_BODY_BUFFER_TOTAL)
+    }
+}
+
+impl BodyBufferBudget {
+    pub fn new(ceiling: usize) -> Self {
+        Self {
+            ceiling,
+            state: Default::default(),
+        }
+    }
+
+    /// Max amount of bytes buffered by all clients at the same time.
+    pub fn ceiling(&self) -> usize {
+        self.ceiling
+    }
+
+    /// Amount of bytes buffered by all clients.
+    pub fn total(&self) -> usize {
+        self.state.lock().expect("budget lock poisoned").total
+    }
+
+    /// Amount of bytes buffered on behalf of the given client.
+    #[cfg(test)]
+    pub fn client_usage(&self, client_id: ClientId) -> usize {
+        self.state
+            .lock()
+            .expect("budget lock poisoned")
+            .client_usage(client_id)
+    }
+
+    /// Starts an empty reservation on behalf of the given clients, grow it with
+    /// [`BodyBufferReservation::try_grow`].
+    pub fn reserve(&self, clients: Vec<ClientId>) -> BodyBufferReservation {
+        BodyBufferReservation {
+            budget: self.clone(),
+            clients,
+            bytes: 0,
+        }
+    }
+}
+
+/// Memory taken from a [`BodyBufferBudget`] for a single request body.
+///
+/// Given back on drop.
+#[derive(Debug)]
+pub struct BodyBufferReservation {
+    budget: BodyBufferBudget,
+    clients: Vec<ClientId>,
+    bytes: usize,
+}
+
+impl BodyBufferReservation {
+    /// Takes `bytes` more from the budget.
+    ///
+    /// Returns `false` and takes nothing if that would exceed the ceiling of the budget.
+    pub fn try_grow(&mut self, bytes: usize) -> bool {
+        let mut state = self.budget.state.lock().expect("budget lock poisoned");
+
+        if state.total.saturating_add(bytes) > self.budget.ceiling {
+            return false;
+        }
+
+        state.total += bytes;
+        for &client_id in &self.clients {
+            let usage = state.client_usage(client_id) + bytes;
+            state.set_client_usage(client_id, usage);
+        }
+        self.bytes += bytes;
+
+        true
+    }
+}
+
+impl Drop for BodyBufferReservation {
+    fn drop(&mut self) {
+        if self.bytes == 0 {
+            return;
+        }
+
+        let Ok(mut state) = self.budget.state.lock() else {
+            return;
+        };
+
+        state.total = state.total.saturating_sub(self.bytes);
+        for &client_id in &self.clients {
+            let usage = state.client_usage(client_id).saturating_sub(self.bytes);
+            state.set_client_usage(client_id, usage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Not;
+
+    use super::BodyBufferBudget;
+
+    #[test]
+    fn accounts_per_client_with_ceiling() {
+        let budget = BodyBufferBudget::new(100);
+
+        let mut first = budget.reserve(vec![1, 2]);
+        assert!(first.try_grow(60));
+
+        let mut second = budget.reserve(vec![2]);
+        assert!(second.try_grow(50).not());
+        assert!(second.try_grow(40));
+
+        assert_eq!(budget.total(), 100);
+        assert_eq!(budget.client_usage(1), 60);
+        assert_eq!(budget.client_usage(2), 100);
+
+        drop(first);
+        assert_eq!(budget.total(), 40);
+        assert_eq!(budget.client_usage(1), 0);
+        assert_eq!(budget.client_usage(2), 40);
+
+        drop(second);
+        assert_eq!(budget.total(), 0);
+        assert_eq!(budget.client_usage(2), 0);
+    }
+}
@@ -5,14 +5,17 @@ use bytes::Bytes;
 use http_body_util::{BodyExt, Full};
 use hyper::http::{StatusCode, Version};
 
-use super::BoxResponse;
+use super::{BoxResponse, MIRRORD_AGENT_HTTP_HEADER_NAME};
 
 /// HTTP response produced by the agent when it fails to serve a redirected request.
 ///
-/// 1. Always uses [`StatusCode::BAD_GATEWAY`].
+/// 1. Uses [`StatusCode::BAD_GATEWAY`], unless created with [`MirrordErrorResponse::filter_error`].
 /// 2. Body always starts with `mirrord-agent: `.
 pub struct MirrordErrorResponse {
     version: Version,
+    status: StatusCode,
+    /// Value of the [`MIRRORD_AGENT_HTTP_HEADER_NAME`] header, if any.
+    agent_header: Option<&'static str>,
     body: Bytes,
 }
 
@@ -20,15 +23,38 @@ impl MirrordErrorResponse {
     pub fn new<M: fmt::Display>(version: Version, message: M) -> Self {
         let body = format!("mirrord-agent v{}: {message}\n", env!("CARGO_PKG_VERSION")).into();
 
-        Self { version, body }
+        Self {
+            version,
+            status: StatusCode::BAD_GATEWAY,
+            agent_header: None,
+            body,
+        }
+    }
+
+    /// Response to a request whose HTTP filter failed with the `close` error policy.
+    ///
+    /// Uses [`StatusCode::SERVICE_UNAVAILABLE`] and the `Mirrord-Agent: filter-error` header, so
+    /// that it can be told apart from the errors of the user's app.
+    pub fn filter_error<M: fmt::Display>(version: Version, message: M) -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            agent_header: Some("filter-error"),
+            ..Self::new(version, message)
+        }
     }
 }
 
 impl From<MirrordErrorResponse> for BoxResponse {
     fn from(value: MirrordErrorResponse) -> Self {
-        Response::builder()
-            .status(StatusCode::BAD_GATEWAY)
-            .version(value.version)
+        let mut builder = Response::builder()
+            .status(value.status)
+            .version(value.version);
+
+        if let Some(agent_header) = value.agent_header {
+            builder = builder.header(MIRRORD_AGENT_HTTP_HEADER_NAME, agent_header);
+        }
+
+        builder
             .body(Full::new(value.body).map_err(|_| unreachable!()).boxed())
             .unwrap()
     }
@@ -1,19 +1,32 @@
-use std::{fmt::Debug, io::Read, ops::Not, sync::LazyLock, time::Duration};
+use std::{
+    fmt::Debug,
+    io::Read,
+    ops::Not,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
 
 use fancy_regex::Regex;
 use http::HeaderMap;
 use hyper::http::request::Parts;
-use jaq_core::{
-    Ctx, RcIter,
-    load::{Arena, File, Loader},
-};
-use jaq_json::Val;
-use mirrord_agent_env::envs::JAQ_TIME_LIMIT;
-use mirrord_protocol::tcp::{HttpMethodFilter, JqQuery};
+use mirrord_protocol::tcp::{BodyFilterFallback, FilterErrorPolicy, HttpMethodFilter, JqQuery};
 use serde_json::Value;
 use serde_json_path::JsonPath;
-use tokio_retry::strategy::ExponentialBackoff;
-use tracing::{Instrument, Level};
+use tracing::Level;
+
+use super::{
+    grpc::{GrpcDecoder, GrpcDecoderError},
+    request_view::RequestView,
+    safe_jaq::{JaqError, SafeJaq},
+};
+use crate::metrics::{HTTP_FILTER_DECISIONS, HTTP_FILTER_DURATION, HTTP_FILTER_ERRORS};
+
+/// Min interval between the warnings about failed HTTP filter evaluations, so that a broken
+/// filter does not flood the agent logs.
+const FILTER_ERROR_WARNING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When the last warning about a failed HTTP filter evaluation was emitted.
+static LAST_FILTER_ERROR_WARNING: Mutex<Option<Instant>> = Mutex::new(None);
 
 /// Currently supported filtering criterias.
 #[derive(Debug, Clone)]
@@ -38,6 +51,39 @@ pub enum HttpFilter {
 
     /// Header based on header using jq
     HeaderJq(JqQuery),
+
+    /// Matches when the inner filter does not match.
+    Not(Box<HttpFilter>),
+
+    /// Decides what happens with the request when the inner filter fails, see
+    /// [`HttpFilter::decide`].
+    OnError {
+        filter: Box<HttpFilter>,
+        policy: FilterErrorPolicy,
+    },
+}
+
+/// Outcome of [`HttpFilter::decide`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// The request should be stolen.
+    Match,
+    /// The request should not be stolen.
+    NoMatch,
+    /// The filter failed with [`FilterErrorPolicy::Close`], the request should be answered with
+    /// [`MirrordErrorResponse::filter_error`](super::error::MirrordErrorResponse::filter_error).
+    Close,
+}
+
+impl FilterDecision {
+    /// Label of [`HTTP_FILTER_DECISIONS`] for this decision.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Match => "match",
+            Self::NoMatch => "miss",
+            Self::Close => "close",
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -50,6 +96,9 @@ pub enum FilterCreationError {
 
     #[error("error compiling jq expression: {0}")]
     Jq(String),
+
+    #[error("error loading gRPC decoder: {0}")]
+    Grpc(#[from] GrpcDecoderError),
 }
 
 impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
@@ -66,10 +115,14 @@ impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
             mirrord_protocol::tcp::HttpFilter::Method(method) => Ok(Self::Method(method.clone())),
             mirrord_protocol::tcp::HttpFilter::Composite { all, filters } => {
                 let all = *all;
-                let filters = filters
+                let mut filters = filters
                     .iter()
                     .map(HttpFilter::try_from)
                     .collect::<Result<Vec<_>, _>>()?;
+                // Body filters go last, so that `matches` can short-circuit on the cheaper
+                // filters before parsing the body. The sort is stable, so the order of the other
+                // filters is kept.
+                filters.sort_by_key(HttpFilter::needs_body);
                 Ok(Self::Composite { all, filters })
             }
             mirrord_protocol::tcp::HttpFilter::Body(http_body_filter) => {
@@ -81,13 +134,32 @@ impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
                     .map(HttpFilter::HeaderJq)
                     .map_err(FilterCreationError::Jq)
             }
+            mirrord_protocol::tcp::HttpFilter::Not(filter) => {
+                Ok(Self::Not(Box::new(filter.as_ref().try_into()?)))
+            }
+            mirrord_protocol::tcp::HttpFilter::OnError { filter, policy } => Ok(Self::OnError {
+                filter: Box::new(filter.as_ref().try_into()?),
+                policy: *policy,
+            }),
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum HttpBodyFilter {
-    Json { query: JsonPath, matches: Regex },
+    Json {
+        query: JsonPath,
+        matches: Regex,
+    },
+    /// Evaluated with [`SafeJaq`] against the [`RequestView`].
+    Jq {
+        query: JqQuery,
+        /// Used when the body is not JSON (or cannot be decoded with `grpc`) or was not fully
+        /// buffered.
+        fallback: BodyFilterFallback,
+        /// Decodes gRPC bodies, which are parsed as JSON when [`None`].
+        grpc: Option<GrpcDecoder>,
+    },
 }
 
 impl TryFrom<&mirrord_protocol::tcp::HttpBodyFilter> for HttpBodyFilter {
@@ -99,15 +171,93 @@ impl TryFrom<&mirrord_protocol::tcp::HttpBodyFilter> for HttpBodyFilter {
                 query: JsonPath::parse(query)?,
                 matches: Regex::new(matches)?,
             },
+            mirrord_protocol::tcp::HttpBodyFilter::Jq { query, fallback } => Self::Jq {
+                // Recompile to validate again
+                query: JqQuery::new(query).map_err(FilterCreationError::Jq)?,
+                fallback: *fallback,
+                grpc: None,
+            },
+            mirrord_protocol::tcp::HttpBodyFilter::GrpcJq {
+                query,
+                fallback,
+                decoder,
+            } => Self::Jq {
+                query: JqQuery::new(query).map_err(FilterCreationError::Jq)?,
+                fallback: *fallback,
+                grpc: Some(GrpcDecoder::new(decoder)?),
+            },
         })
     }
 }
 
 impl HttpFilter {
     /// Checks whether the given request [`Parts`] match this filter.
-    #[tracing::instrument(level = Level::DEBUG, skip_all, fields(has_body = body.is_some()), ret)]
+    ///
+    /// Failed evaluations are handled according to the [`FilterErrorPolicy`] of this filter, and
+    /// [`FilterDecision::Close`] does not match.
     pub async fn matches<T: Read + Copy>(&self, parts: &mut Parts, body: Option<T>) -> bool {
+        self.decide(parts, body).await == FilterDecision::Match
+    }
+
+    /// Checks whether the given request [`Parts`] match this filter, applying its
+    /// [`FilterErrorPolicy`] when the evaluation fails.
+    ///
+    /// Only the policy of the top level [`HttpFilter::OnError`] is used, the default is
+    /// [`FilterErrorPolicy::Pass`]. Failures are counted in [`HTTP_FILTER_ERRORS`], and logged
+    /// at most once per [`FILTER_ERROR_WARNING_INTERVAL`].
+    ///
+    /// Every decision is counted in [`HTTP_FILTER_DECISIONS`], and timed in
+    /// [`HTTP_FILTER_DURATION`].
+    pub async fn decide<T: Read + Copy>(
+        &self,
+        parts: &mut Parts,
+        body: Option<T>,
+    ) -> FilterDecision {
+        let policy = self.error_policy();
+        let timer = HTTP_FILTER_DURATION.start_timer();
+
+        let decision = match self.try_matches(parts, body).await {
+            Ok(true) => FilterDecision::Match,
+            Ok(false) => FilterDecision::NoMatch,
+            Err(error) => {
+                HTTP_FILTER_ERRORS.with_label_values(&[error.class()]).inc();
+                warn_filter_error(&error, policy);
+
+                match policy {
+                    FilterErrorPolicy::Pass => FilterDecision::NoMatch,
+                    FilterErrorPolicy::Steal => FilterDecision::Match,
+                    FilterErrorPolicy::Close => FilterDecision::Close,
+                }
+            }
+        };
+
+        timer.observe_duration();
+        HTTP_FILTER_DECISIONS
+            .with_label_values(&[decision.label()])
+            .inc();
+
+        decision
+    }
+
+    /// Policy applied by [`HttpFilter::decide`] when this filter fails.
+    pub fn error_policy(&self) -> FilterErrorPolicy {
         match self {
+            Self::OnError { policy, .. } => *policy,
+            _ => FilterErrorPolicy::Pass,
+        }
+    }
+
+    /// Checks whether the given request [`Parts`] match this filter.
+    ///
+    /// Fails when a jq expression cannot be evaluated. Composite filters fail only when the
+    /// filters that did not fail cannot decide on their own.
+    #[tracing::instrument(level = Level::DEBUG, skip_all, fields(has_body = body.is_some()), ret)]
+    async fn try_matches<T: Read + Copy>(
+        &self,
+        parts: &mut Parts,
+        body: Option<T>,
+    ) -> Result<bool, JaqError> {
+        Ok(match self {
             Self::Header(filter) => {
                 let headers = parts
                     .extensions
@@ -139,55 +289,101 @@ impl HttpFilter {
                     .unwrap_or(false)
             }),
 
-            Self::Method(filter) => parts.method.as_str().eq_ignore_ascii_case(filter.as_ref()),
+            Self::Method(filter) => {
+                // `as_ref` does not return the method of `Other`.
+                let method = match filter {
+                    HttpMethodFilter::Other(method) => method.as_str(),
+                    filter => filter.as_ref(),
+                };
+
+                parts.method.as_str().eq_ignore_ascii_case(method)
+            }
 
-            Self::Composite { all: true, filters } => {
+            Self::Composite { all, filters } => {
                 // Since we require `body` to be Clone + Copy, each
                 // iteration creates a new version that reads from the
                 // beginning. Need to make sure that we don't read
                 // anything from `body` before passing (copies of) it
                 // to other fns.
+                let mut error = None;
                 for filter in filters {
-                    if Box::pin(filter.matches(parts, body)).await.not() {
-                        return false;
+                    match Box::pin(filter.try_matches(parts, body)).await {
+                        // `false` decides an `all` composite, `true` decides an `any` one.
+                        Ok(matched) if matched != *all => return Ok(matched),
+                        Ok(_) => {}
+                        Err(e) => {
+                            error.get_or_insert(e);
+                        }
                     }
                 }
-                true
+
+                match error {
+                    Some(error) => return Err(error),
+                    None => *all,
+                }
             }
-            Self::Composite {
-                all: false,
-                filters,
-            } => {
-                // Same as above
-                for filter in filters {
-                    if Box::pin(filter.matches(parts, body)).await {
-                        return true;
+            Self::Not(filter) => Box::pin(filter.try_matches(parts, body)).await?.not(),
+            // Only the policy of the top level filter is used.
+            Self::OnError { filter, .. } => Box::pin(filter.try_matches(parts, body)).await?,
+            Self::Body(HttpBodyFilter::Json { query, matches }) => {
+                let Some(body) = body else { return Ok(false) };
+
+                let json = match serde_json::from_reader::<_, Value>(body) {
+                    Ok(json) => json,
+                    Err(error) => {
+                        tracing::debug!(?error, "json filter failed to parse body json");
+                        return Ok(false);
                     }
-                }
-                false
+                };
+
+                let results = query.query(&json);
+
+                results.iter().any(|v| {
+                    match v {
+                        Value::String(s) => matches.is_match(s),
+                        other => matches.is_match(&other.to_string()),
+                    }
+                    .is_ok_and(|t| t)
+                })
             }
-            Self::Body(filter) => {
-                let Some(body) = body else { return false };
-
-                match filter {
-                    HttpBodyFilter::Json { query, matches } => {
-                        let json = match serde_json::from_reader::<_, Value>(body) {
-                            Ok(json) => json,
-                            Err(error) => {
-                                tracing::debug!(?error, "json filter failed to parse body json");
-                                return false;
-                            }
-                        };
-
-                        let results = query.query(&json);
-
-                        results.iter().any(|v| {
-                            match v {
-                                Value::String(s) => matches.is_match(s),
-                                other => matches.is_match(&other.to_string()),
-                            }
-                            .is_ok_and(|t| t)
+            Self::Body(HttpBodyFilter::Jq {
+                query,
+                fallback,
+                grpc,
+            }) => {
+                let json = match (body, grpc) {
+                    (Some(body), None) => serde_json::from_reader::<_, Value>(body)
+                        .inspect_err(|error| {
+                            tracing::debug!(
+                                ?error,
+                                ?fallback,
+                                "jq filter failed to parse body json"
+                            );
                         })
+                        .ok(),
+                    (Some(body), Some(decoder)) => decoder
+                        .decode(parts.uri.path(), body)
+                        .inspect_err(|error| {
+                            tracing::debug!(
+                                ?error,
+                                ?fallback,
+                                "jq filter failed to decode gRPC body"
+                            );
+                        })
+                        .ok(),
+                    (None, _) => {
+                        tracing::debug!(?fallback, "jq filter did not get the full body");
+                        None
+                    }
+                };
+
+                match (json, fallback) {
+                    (None, BodyFilterFallback::NoMatch) => false,
+                    (None, BodyFilterFallback::Match) => true,
+                    (json, _) => {
+                        let view = RequestView::new(parts, json);
+
+                        SafeJaq::global().evaluate(query, view.into()).await?
                     }
                 }
             }
@@ -196,99 +392,234 @@ impl HttpFilter {
                     .extensions
                     .get_or_insert_with(|| NormalizedHeaders::from_headers(&parts.headers));
 
+                let mut error = None;
                 for header in headers.0.iter() {
-                    match eval_jaq(filter.clone(), header.clone()).await {
-                        Ok(true) => return true,
+                    match SafeJaq::global()
+                        .evaluate(filter, Value::String(header.clone()))
+                        .await
+                    {
+                        Ok(true) => return Ok(true),
                         Ok(false) => (),
-                        Err(err) => {
-                            tracing::error!(?err, ?header, ?filter, "failed to run jaq query");
+                        Err(e) => {
+                            tracing::debug!(error = %e, ?header, ?filter, "failed to run jaq query");
+                            error.get_or_insert(e);
                         }
                     }
                 }
 
-                false
+                match error {
+                    Some(error) => return Err(error),
+                    None => false,
+                }
+            }
+        })
+    }
+
+    /// Checks whether the given request [`Parts`] match this filter, before the body is
+    /// received.
+    ///
+    /// Returns [`None`] when the result depends on the body. Lets us stop buffering the body as
+    /// soon as the filter can decide without it, e.g. when a header filter in an `all`
+    /// composite does not match.
+    pub async fn matches_without_body(&self, parts: &mut Parts) -> Option<bool> {
+        match self {
+            Self::Body(_) => None,
+            Self::Composite { all, filters } => {
+                let mut decided = true;
+                for filter in filters {
+                    match Box::pin(filter.matches_without_body(parts)).await {
+                        // `false` decides an `all` composite, `true` decides an `any` one.
+                        Some(matched) if matched != *all => return Some(matched),
+                        Some(_) => {}
+                        None => decided = false,
+                    }
+                }
+                decided.then_some(*all)
             }
+            Self::Not(filter) => Box::pin(filter.matches_without_body(parts))
+                .await
+                .map(Not::not),
+            Self::OnError { filter, .. } => Box::pin(filter.matches_without_body(parts)).await,
+            // A failed filter is decided with the body, it might not fail then.
+            filter => filter.try_matches::<&[u8]>(parts, None).await.ok(),
         }
     }
 
     pub fn needs_body(&self) -> bool {
         match self {
             HttpFilter::Composite { filters, .. } => filters.iter().any(HttpFilter::needs_body),
+            HttpFilter::Not(filter) | HttpFilter::OnError { filter, .. } => filter.needs_body(),
             HttpFilter::Body(_) => true,
             _ => false,
         }
     }
+
+    /// Checks whether this filter is trivially identical to the `other` one, i.e. they would
+    /// always match the same requests.
+    ///
+    /// Conservative, body filters are never considered identical.
+    pub fn is_identical(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Header(a), Self::Header(b)) | (Self::Path(a), Self::Path(b)) => {
+                a.as_str() == b.as_str()
+            }
+            (Self::Method(a), Self::Method(b)) => a == b,
+            (Self::HeaderJq(a), Self::HeaderJq(b)) => a == b,
+            (
+                Self::Composite {
+                    all: all_a,
+                    filters: filters_a,
+                },
+                Self::Composite {
+                    all: all_b,
+                    filters: filters_b,
+                },
+            ) => {
+                all_a == all_b
+                    && filters_a.len() == filters_b.len()
+                    && filters_a
+                        .iter()
+                        .zip(filters_b)
+                        .all(|(a, b)| a.is_identical(b))
+            }
+            (Self::Not(a), Self::Not(b)) => a.is_identical(b),
+            (
+                Self::OnError {
+                    filter: filter_a,
+                    policy: policy_a,
+                },
+                Self::OnError {
+                    filter: filter_b,
+                    policy: policy_b,
+                },
+            ) => policy_a == policy_b && filter_a.is_identical(filter_b),
+            _ => false,
+        }
+    }
 }
 
-async fn eval_jaq(query: JqQuery, payload: String) -> Result<bool, String> {
-    static TIME_LIMIT: LazyLock<Duration> = LazyLock::new(|| {
-        Duration::from_secs(JAQ_TIME_LIMIT.try_from_env().ok().flatten().unwrap_or(500))
-    });
+/// [`HttpFilter`] of a filter dry-run, see
+/// [`LayerTcp::PortSubscribeDryRun`](mirrord_protocol::tcp::LayerTcp::PortSubscribeDryRun).
+///
+/// Keeps the descriptions of the filters, so that we can report which one matched.
+#[derive(Debug)]
+pub struct DryRunFilter {
+    filter: HttpFilter,
+    description: String,
+    /// Sub-filters of a top level `any_of` composite, with their descriptions.
+    any_of: Vec<(String, HttpFilter)>,
+}
 
-    let span = tracing::warn_span!("jaq eval", ?query);
+/// Result of [`DryRunFilter::evaluate`].
+#[derive(Debug)]
+pub struct FilterEvaluation {
+    /// Whether the request would have been stolen, according to the [`FilterErrorPolicy`] when
+    /// the evaluation failed.
+    pub matched: bool,
+    /// Description of the filter that matched, see [`DryRunFilter`].
+    pub matched_filter: Option<String>,
+    /// Why the evaluation failed.
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for DryRunFilter {
+    type Error = FilterCreationError;
 
-    let mut handle = tokio::task::spawn_blocking(move || {
-        let program = File {
-            code: query.as_str(),
-            path: (),
+    fn try_from(filter: &mirrord_protocol::tcp::HttpFilter) -> Result<Self, Self::Error> {
+        let inner = match filter {
+            mirrord_protocol::tcp::HttpFilter::OnError { filter, .. } => filter.as_ref(),
+            filter => filter,
+        };
+        let any_of = match inner {
+            mirrord_protocol::tcp::HttpFilter::Composite {
+                all: false,
+                filters,
+            } => filters
+                .iter()
+                .map(|filter| Ok((filter.to_string(), HttpFilter::try_from(filter)?)))
+                .collect::<Result<_, FilterCreationError>>()?,
+            _ => Default::default(),
         };
-        let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
-        let arena = Arena::default();
-        let modules = loader
-            .load(&arena, program)
-            .map_err(|errors| format!("failed to parse the filter: {errors:?}"))?;
-
-        let filter =
-            jaq_core::Compiler::default().with_funs(jaq_std::funs().chain(jaq_json::funs()));
-
-        let filter = filter
-            .compile(modules)
-            .map_err(|errors| format!("failed to compile the filter: {errors:?}"))?;
-
-        let inputs = RcIter::new(core::iter::empty());
-        let mut out = filter.run((Ctx::new([], &inputs), Val::from(payload)));
-
-        let found_match = out
-            .find_map(|item| {
-                if let Ok(Val::Bool(value)) = &item {
-                    Some(*value)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(false);
-        Ok(found_match)
-    });
-
-    tokio::select! {
-        result = &mut handle => {
-            match result {
-                Ok(r) => r,
-                Err(join) => {
-                    tracing::error!(?join, "panic in jaq evaluation task");
-                    Ok(false)
-                }
-            }
-        }
-        _ = tokio::time::sleep(*TIME_LIMIT) => {
-            tracing::warn!("jq expr evaluation took longer than max allowed time");
 
-            tokio::spawn(async move {
-                let backoff = ExponentialBackoff::from_millis(5)
-                    .factor(200)
-                    .max_delay(Duration::from_secs(25));
+        Ok(Self {
+            filter: filter.try_into()?,
+            description: inner.to_string(),
+            any_of,
+        })
+    }
+}
+
+impl DryRunFilter {
+    pub fn needs_body(&self) -> bool {
+        self.filter.needs_body()
+    }
+
+    /// Evaluates the filter on the given request, without acting on it.
+    ///
+    /// Unlike [`HttpFilter::decide`], does not count the evaluation in the metrics.
+    pub async fn evaluate<T: Read + Copy>(
+        &self,
+        parts: &mut Parts,
+        body: Option<T>,
+    ) -> FilterEvaluation {
+        let start = Instant::now();
+        let result = self.filter.try_matches(parts, body).await;
+        let duration = start.elapsed();
+
+        let (matched, error) = match result {
+            Ok(matched) => (matched, None),
+            Err(error) => (
+                self.filter.error_policy() == FilterErrorPolicy::Steal,
+                Some(error.to_string()),
+            ),
+        };
 
-                for delay in backoff {
-                    tokio::time::sleep(delay).await;
-                    tracing::warn!("jq evaluation has not completed");
+        let matched_filter = match (matched, &error) {
+            (true, None) if self.any_of.is_empty().not() => {
+                let mut matched_filter = None;
+                for (description, filter) in &self.any_of {
+                    if filter.try_matches(parts, body).await.unwrap_or(false) {
+                        matched_filter = Some(description.clone());
+                        break;
+                    }
                 }
-            }.instrument(span));
+                matched_filter
+            }
+            (true, None) => Some(self.description.clone()),
+            _ => None,
+        };
 
-            Ok(false)
+        FilterEvaluation {
+            matched,
+            matched_filter,
+            error,
+            duration,
         }
     }
 }
 
+/// Logs a failed HTTP filter evaluation, at most once per [`FILTER_ERROR_WARNING_INTERVAL`].
+fn warn_filter_error(error: &JaqError, policy: FilterErrorPolicy) {
+    let mut last = LAST_FILTER_ERROR_WARNING
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    if last.is_some_and(|last| last.elapsed() < FILTER_ERROR_WARNING_INTERVAL) {
+        return;
+    }
+    *last = Some(Instant::now());
+
+    tracing::warn!(
+        %error,
+        class = error.class(),
+        on_filter_error = %policy,
+        "Failed to evaluate an HTTP filter, the request is handled according to the \
+        `on_filter_error` policy. Next failures are not logged for {}s.",
+        FILTER_ERROR_WARNING_INTERVAL.as_secs(),
+    );
+}
+
 /// [`HeaderMap`] entries formatted like `k: v` (format expected by
 /// [`HttpFilter::Header`]). Computed and cached in [`Parts::extensions`] the first time
 /// [`HttpFilter::matches`] is called on [`Parts`].
@@ -328,9 +659,12 @@ mod test {
     use std::{ops::Not, str::FromStr};
 
     use hyper::Request;
-    use mirrord_protocol::tcp::{self, Filter, HttpMethodFilter};
+    use mirrord_protocol::tcp::{
+        self, BodyFilterFallback, Filter, FilterErrorPolicy, HttpMethodFilter, JqQuery,
+    };
+    use rstest::rstest;
 
-    use super::HttpFilter;
+    use super::{DryRunFilter, FilterDecision, HttpFilter};
 
     #[tokio::test]
     async fn matching_all_filter() {
@@ -377,9 +711,21 @@ mod test {
                 tcp::HttpFilter::Path(Filter::new("path/to/v1".to_string()).unwrap()),
                 tcp::HttpFilter::Path(Filter::new("path/for/v8".to_string()).unwrap()),
                 tcp::HttpFilter::Method(HttpMethodFilter::from_str("get").unwrap()),
+                tcp::HttpFilter::Method(HttpMethodFilter::Other("PURGE".to_string())),
             ],
         };
 
+        // should match
+        let mut input = Request::builder()
+            .method("purge")
+            .uri("https://www.balconia.gov/api/path/to/v3")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+        assert!(filter.matches::<&[u8]>(&mut input, None).await);
+
         // should match
         let mut input = Request::builder()
             .method("GET")
@@ -404,4 +750,398 @@ mod test {
         let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
         assert!(!filter.matches::<&[u8]>(&mut input, None).await);
     }
+
+    #[tokio::test]
+    async fn matching_nested_filter() {
+        let tcp_filter = tcp::HttpFilter::Composite {
+            all: true,
+            filters: vec![
+                tcp::HttpFilter::Body(tcp::HttpBodyFilter::Jq {
+                    query: JqQuery::new(r#".body.user.id == "123""#).unwrap(),
+                    fallback: BodyFilterFallback::NoMatch,
+                }),
+                tcp::HttpFilter::Composite {
+                    all: false,
+                    filters: vec![
+                        tcp::HttpFilter::Path(Filter::new("path/to/v1".to_string()).unwrap()),
+                        tcp::HttpFilter::Method(HttpMethodFilter::from_str("get").unwrap()),
+                    ],
+                },
+            ],
+        };
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        let HttpFilter::Composite { filters, .. } = &filter else {
+            panic!("expected a composite filter, got {filter:?}");
+        };
+        assert!(matches!(
+            filters.as_slice(),
+            [HttpFilter::Composite { .. }, HttpFilter::Body(..)]
+        ));
+
+        // should match
+        let mut input = Request::builder()
+            .method("POST")
+            .uri("https://www.balconia.gov/api/path/to/v1")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        let body = br#"{"user": {"id": "123"}}"#.as_slice();
+        assert!(filter.matches(&mut input, Some(body)).await);
+
+        // should fail
+        let mut input = Request::builder()
+            .method("POST")
+            .uri("https://www.balconia.gov/api/path/to/v3")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches(&mut input, Some(body)).await.not());
+    }
+
+    #[rstest]
+    #[case::user("GET", "/api/users", "x-user: foo", true)]
+    #[case::team("GET", "/api/users", "x-team: foo", true)]
+    #[case::team_admin("GET", "/api/admin", "x-team: foo", false)]
+    #[case::options("OPTIONS", "/api/users", "x-user: foo", false)]
+    #[case::other_path("GET", "/health", "x-user: foo", false)]
+    #[case::other_user("GET", "/api/users", "x-user: bar", false)]
+    #[tokio::test]
+    async fn matching_deeply_nested_filter(
+        #[case] method: &str,
+        #[case] path: &str,
+        #[case] header: &str,
+        #[case] expected: bool,
+    ) {
+        // path starts with /api AND (x-user is foo OR (x-team is foo AND path is not /api/admin))
+        // AND method is NOT OPTIONS
+        let tcp_filter = tcp::HttpFilter::Composite {
+            all: true,
+            filters: vec![
+                tcp::HttpFilter::Path(Filter::new("^/api".to_string()).unwrap()),
+                tcp::HttpFilter::Composite {
+                    all: false,
+                    filters: vec![
+                        tcp::HttpFilter::Header(Filter::new("^x-user: foo$".to_string()).unwrap()),
+                        tcp::HttpFilter::Composite {
+                            all: true,
+                            filters: vec![
+                                tcp::HttpFilter::Header(
+                                    Filter::new("^x-team: foo$".to_string()).unwrap(),
+                                ),
+                                tcp::HttpFilter::Not(Box::new(tcp::HttpFilter::Path(
+                                    Filter::new("^/api/admin".to_string()).unwrap(),
+                                ))),
+                            ],
+                        },
+                    ],
+                },
+                tcp::HttpFilter::Not(Box::new(tcp::HttpFilter::Method(
+                    HttpMethodFilter::from_str("options").unwrap(),
+                ))),
+            ],
+        };
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        let (name, value) = header.split_once(": ").unwrap();
+        let mut input = Request::builder()
+            .method(method)
+            .uri(format!("https://www.balconia.gov{path}"))
+            .header(name, value)
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert_eq!(filter.matches::<&[u8]>(&mut input, None).await, expected);
+    }
+
+    #[tokio::test]
+    async fn matching_not_filter() {
+        let tcp_filter = tcp::HttpFilter::Composite {
+            all: false,
+            filters: vec![
+                tcp::HttpFilter::Not(Box::new(tcp::HttpFilter::Header(
+                    Filter::new("x-synthetic-probe: true".to_string()).unwrap(),
+                ))),
+                tcp::HttpFilter::Path(Filter::new("path/to/v1".to_string()).unwrap()),
+            ],
+        };
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        // should match
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api/path/to/v3")
+            .header("x-synthetic-probe", "false")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).await);
+
+        // should match
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api/path/to/v1")
+            .header("x-synthetic-probe", "true")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).await);
+
+        // should fail
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api/path/to/v3")
+            .header("x-synthetic-probe", "true")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).await.not());
+    }
+
+    #[rstest]
+    #[case::header_fails_all(true, "x-tenant: other", "/api/users", Some(false))]
+    #[case::header_passes_all(true, "x-tenant: acme", "/api/other", None)]
+    #[case::header_passes_any(false, "x-tenant: acme", "/api/other", Some(true))]
+    #[case::header_fails_any(false, "x-tenant: other", "/api/users", None)]
+    #[tokio::test]
+    async fn matching_without_body(
+        #[case] all: bool,
+        #[case] header: &str,
+        #[case] path: &str,
+        #[case] expected: Option<bool>,
+    ) {
+        let tcp_filter = tcp::HttpFilter::Composite {
+            all,
+            filters: vec![
+                tcp::HttpFilter::Header(Filter::new(header.to_string()).unwrap()),
+                tcp::HttpFilter::Not(Box::new(tcp::HttpFilter::Path(
+                    Filter::new(path.to_string()).unwrap(),
+                ))),
+                tcp::HttpFilter::Body(tcp::HttpBodyFilter::Jq {
+                    query: JqQuery::new(r#".body.user.id == "123""#).unwrap(),
+                    fallback: BodyFilterFallback::NoMatch,
+                }),
+            ],
+        };
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        let mut input = Request::builder()
+            .method("POST")
+            .uri("https://www.balconia.gov/api/users")
+            .header("X-Tenant", "acme")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+
+        assert_eq!(filter.matches_without_body(&mut input).await, expected);
+    }
+
+    #[rstest]
+    #[case::matches(Some(r#"{"user": {"id": "123"}}"#), BodyFilterFallback::NoMatch, true)]
+    #[case::does_not_match(Some(r#"{"user": {"id": "456"}}"#), BodyFilterFallback::Match, false)]
+    #[case::not_json(Some("user=123"), BodyFilterFallback::NoMatch, false)]
+    #[case::not_json_fallback_match(Some("user=123"), BodyFilterFallback::Match, true)]
+    #[case::not_json_fallback_evaluate(Some("user=123"), BodyFilterFallback::Evaluate, true)]
+    #[case::too_big(None, BodyFilterFallback::NoMatch, false)]
+    #[case::too_big_fallback_match(None, BodyFilterFallback::Match, true)]
+    #[case::too_big_fallback_evaluate(None, BodyFilterFallback::Evaluate, true)]
+    #[tokio::test]
+    async fn matching_jq_body_filter(
+        #[case] body: Option<&str>,
+        #[case] fallback: BodyFilterFallback,
+        #[case] expected: bool,
+    ) {
+        let tcp_filter = tcp::HttpFilter::Body(tcp::HttpBodyFilter::Jq {
+            query: JqQuery::new(
+                r#".method == "POST" and .headers["x-tenant"] == "acme"
+                    and (.body == null or .body.user.id == "123")"#,
+            )
+            .unwrap(),
+            fallback,
+        });
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        let mut input = Request::builder()
+            .method("POST")
+            .uri("https://www.balconia.gov/api/users")
+            .header("X-Tenant", "acme")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+
+        assert_eq!(
+            filter.matches(&mut input, body.map(str::as_bytes)).await,
+            expected
+        );
+    }
+
+    /// The `body_filter` shorthand of the config, a jq expression evaluated against the body.
+    #[rstest]
+    #[case::matches(Some(r#"{"tenant_id": "my-team"}"#), true)]
+    #[case::does_not_match(Some(r#"{"tenant_id": "other-team"}"#), false)]
+    #[case::not_object(Some("[1, 2]"), false)]
+    #[case::not_json(Some("tenant_id=my-team"), false)]
+    #[tokio::test]
+    async fn matching_body_query(#[case] body: Option<&str>, #[case] expected: bool) {
+        let tcp_filter = tcp::HttpFilter::Body(tcp::HttpBodyFilter::Jq {
+            query: JqQuery::new(".body | (\n.tenant_id == \"my-team\"\n)").unwrap(),
+            fallback: BodyFilterFallback::NoMatch,
+        });
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        let mut input = Request::builder()
+            .method("POST")
+            .uri("https://www.balconia.gov/api/orders")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+
+        assert_eq!(
+            filter.matches(&mut input, body.map(str::as_bytes)).await,
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::pass(FilterErrorPolicy::Pass, FilterDecision::NoMatch)]
+    #[case::steal(FilterErrorPolicy::Steal, FilterDecision::Match)]
+    #[case::close(FilterErrorPolicy::Close, FilterDecision::Close)]
+    #[tokio::test]
+    async fn deciding_on_filter_error(
+        #[case] policy: FilterErrorPolicy,
+        #[case] expected: FilterDecision,
+    ) {
+        // Fails for non-numeric user ids.
+        let failing = tcp::HttpFilter::Body(tcp::HttpBodyFilter::Jq {
+            query: JqQuery::new(".body.user | tonumber > 100").unwrap(),
+            fallback: BodyFilterFallback::NoMatch,
+        });
+        let tcp_filter = tcp::HttpFilter::OnError {
+            filter: Box::new(tcp::HttpFilter::Composite {
+                all: false,
+                filters: vec![
+                    tcp::HttpFilter::Header(Filter::new("x-tenant: other".to_string()).unwrap()),
+                    failing,
+                ],
+            }),
+            policy,
+        };
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        let mut input = Request::builder()
+            .method("POST")
+            .uri("https://www.balconia.gov/api/users")
+            .header("x-tenant", "acme")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+
+        assert_eq!(
+            filter
+                .decide(&mut input, Some(br#"{"user": "alice"}"#.as_slice()))
+                .await,
+            expected
+        );
+        assert_eq!(
+            filter
+                .decide(&mut input, Some(br#"{"user": "123"}"#.as_slice()))
+                .await,
+            FilterDecision::Match
+        );
+
+        // The failed filter does not matter when the other one matches.
+        let mut input = Request::builder()
+            .method("POST")
+            .uri("https://www.balconia.gov/api/users")
+            .header("x-tenant", "other")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert_eq!(
+            filter
+                .decide(&mut input, Some(br#"{"user": "alice"}"#.as_slice()))
+                .await,
+            FilterDecision::Match
+        );
+    }
+
+    #[rstest]
+    #[case(tcp::HttpFilter::Path(Filter::new("/api".into()).unwrap()), tcp::HttpFilter::Path(Filter::new("/api".into()).unwrap()), true)]
+    #[case(tcp::HttpFilter::Path(Filter::new("/api".into()).unwrap()), tcp::HttpFilter::Path(Filter::new("/api/v1".into()).unwrap()), false)]
+    #[case(tcp::HttpFilter::Path(Filter::new("/api".into()).unwrap()), tcp::HttpFilter::Header(Filter::new("/api".into()).unwrap()), false)]
+    #[case(
+        tcp::HttpFilter::Not(Box::new(tcp::HttpFilter::Method(HttpMethodFilter::Get))),
+        tcp::HttpFilter::Not(Box::new(tcp::HttpFilter::Method(HttpMethodFilter::Get))),
+        true
+    )]
+    #[case(
+        tcp::HttpFilter::Composite { all: true, filters: vec![tcp::HttpFilter::Method(HttpMethodFilter::Get)] },
+        tcp::HttpFilter::Composite { all: false, filters: vec![tcp::HttpFilter::Method(HttpMethodFilter::Get)] },
+        false
+    )]
+    fn identical_filters(
+        #[case] a: tcp::HttpFilter,
+        #[case] b: tcp::HttpFilter,
+        #[case] expected: bool,
+    ) {
+        let a = HttpFilter::try_from(&a).unwrap();
+        let b = HttpFilter::try_from(&b).unwrap();
+
+        assert_eq!(a.is_identical(&b), expected);
+        assert_eq!(b.is_identical(&a), expected);
+    }
+
+    #[tokio::test]
+    async fn dry_run_reports_matched_filter() {
+        let path = tcp::HttpFilter::Path(Filter::new("^/api/v1".into()).unwrap());
+        let method = tcp::HttpFilter::Method(HttpMethodFilter::Post);
+        let filter = DryRunFilter::try_from(&tcp::HttpFilter::Composite {
+            all: false,
+            filters: vec![path.clone(), method.clone()],
+        })
+        .unwrap();
+
+        let mut input = Request::builder()
+            .method("POST")
+            .uri("https://www.balconia.gov/api/v2")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        let evaluation = filter.evaluate::<&[u8]>(&mut input, None).await;
+        assert!(evaluation.matched);
+        assert_eq!(evaluation.matched_filter, Some(method.to_string()));
+        assert!(evaluation.error.is_none());
+
+        let mut input = Request::builder()
+            .method("GET")
+            .uri("https://www.balconia.gov/api/v2")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        let evaluation = filter.evaluate::<&[u8]>(&mut input, None).await;
+        assert!(evaluation.matched.not());
+        assert!(evaluation.matched_filter.is_none());
+
+        let filter = DryRunFilter::try_from(&path).unwrap();
+        let mut input = Request::builder()
+            .method("GET")
+            .uri("https://www.balconia.gov/api/v1/users")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        let evaluation = filter.evaluate::<&[u8]>(&mut input, None).await;
+        assert!(evaluation.matched);
+        assert_eq!(evaluation.matched_filter, Some(path.to_string()));
+    }
 }
@@ -0,0 +1,182 @@
+//! Decoding of gRPC request bodies for the jq body filters, see [`GrpcDecoder`].
+
+use std::io::{self, Read};
+
+use mirrord_protocol::tcp::ProtoDescriptorSet;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde_json::Value;
+
+/// Length of the gRPC message prefix: 1 byte compression flag and 4 bytes message length.
+const GRPC_PREFIX_LEN: usize = 5;
+
+#[derive(thiserror::Error, Debug)]
+pub enum GrpcDecoderError {
+    #[error("failed to read the protobuf descriptor set from `{path}`: {error}")]
+    ReadDescriptorSet { path: String, error: io::Error },
+
+    #[error("invalid protobuf descriptor set: {0}")]
+    DescriptorSet(#[from] prost_reflect::DescriptorError),
+
+    #[error("protobuf message `{0}` was not found in the descriptor set")]
+    UnknownMessage(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GrpcDecodeError {
+    #[error("no protobuf message is configured for path `{0}`")]
+    NoMessage(String),
+
+    #[error("failed to read the gRPC message: {0}")]
+    Read(#[from] io::Error),
+
+    #[error("compressed gRPC messages are not supported")]
+    Compressed,
+
+    #[error("failed to decode the protobuf message: {0}")]
+    Decode(#[from] prost_reflect::prost::DecodeError),
+
+    #[error("failed to convert the protobuf message into JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Decodes the first protobuf message of a gRPC request body into JSON.
+///
+/// Created from [`mirrord_protocol::tcp::GrpcDecoder`], the request message is picked by the
+/// longest prefix of the request path.
+#[derive(Debug, Clone)]
+pub struct GrpcDecoder {
+    /// Sorted by the prefix length, longest first.
+    messages: Vec<(String, MessageDescriptor)>,
+}
+
+impl GrpcDecoder {
+    /// Decodes the descriptor set and looks up all of the messages in it.
+    pub fn new(decoder: &mirrord_protocol::tcp::GrpcDecoder) -> Result<Self, GrpcDecoderError> {
+        let pool = match &decoder.descriptor_set {
+            ProtoDescriptorSet::Inline(bytes) => DescriptorPool::decode(bytes.as_slice())?,
+            ProtoDescriptorSet::Path(path) => {
+                let bytes =
+                    std::fs::read(path).map_err(|error| GrpcDecoderError::ReadDescriptorSet {
+                        path: path.clone(),
+                        error,
+                    })?;
+                DescriptorPool::decode(bytes.as_slice())?
+            }
+        };
+
+        let mut messages = decoder
+            .messages
+            .iter()
+            .map(|(prefix, name)| {
+                pool.get_message_by_name(name)
+                    .map(|message| (prefix.clone(), message))
+                    .ok_or_else(|| GrpcDecoderError::UnknownMessage(name.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        messages.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        Ok(Self { messages })
+    }
+
+    /// Decodes the first message of the gRPC request `body` sent to the given `path`.
+    ///
+    /// Only the first message is read, the rest of the body (e.g. next messages of a streaming
+    /// RPC) is ignored.
+    pub fn decode<T: Read>(&self, path: &str, mut body: T) -> Result<Value, GrpcDecodeError> {
+        let (_, descriptor) = self
+            .messages
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .ok_or_else(|| GrpcDecodeError::NoMessage(path.to_owned()))?;
+
+        let mut prefix = [0; GRPC_PREFIX_LEN];
+        body.read_exact(&mut prefix)?;
+        if prefix[0] != 0 {
+            return Err(GrpcDecodeError::Compressed);
+        }
+
+        let len = u32::from_be_bytes([prefix[1], prefix[2], prefix[3], prefix[4]]);
+        let mut message = Vec::new();
+        body.take(len.into()).read_to_end(&mut message)?;
+        if message.len() < len as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        let message = DynamicMessage::decode(descriptor.clone(), message.as_slice())?;
+        Ok(serde_json::to_value(&message)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_protocol::tcp::{self, ProtoDescriptorSet};
+    use prost_reflect::{
+        prost::Message,
+        prost_types::{
+            DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+            field_descriptor_proto::{Label, Type},
+        },
+    };
+    use serde_json::json;
+
+    use super::{GrpcDecodeError, GrpcDecoder};
+
+    /// Descriptor set with a single `acme.GetUserRequest { string user_id = 1; }` message.
+    fn descriptor_set() -> Vec<u8> {
+        FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("acme.proto".into()),
+                package: Some("acme".into()),
+                message_type: vec![DescriptorProto {
+                    name: Some("GetUserRequest".into()),
+                    field: vec![FieldDescriptorProto {
+                        name: Some("user_id".into()),
+                        number: Some(1),
+                        label: Some(Label::Optional.into()),
+                        r#type: Some(Type::String.into()),
+                        json_name: Some("userId".into()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                syntax: Some("proto3".into()),
+                ..Default::default()
+            }],
+        }
+        .encode_to_vec()
+    }
+
+    #[test]
+    fn decodes_first_message() {
+        let decoder = GrpcDecoder::new(&tcp::GrpcDecoder {
+            descriptor_set: ProtoDescriptorSet::Inline(descriptor_set()),
+            messages: vec![("/acme.Users/".into(), "acme.GetUserRequest".into())],
+        })
+        .unwrap();
+
+        // Field 1 (length-delimited), "123".
+        let message = [0x0a, 0x03, b'1', b'2', b'3'];
+        let mut body = vec![0, 0, 0, 0, message.len() as u8];
+        body.extend_from_slice(&message);
+        // Second message of a streaming RPC, ignored.
+        body.extend_from_slice(&[0, 0, 0, 0, 2, 0x0a, 0x00]);
+
+        assert_eq!(
+            decoder
+                .decode("/acme.Users/GetUser", body.as_slice())
+                .unwrap(),
+            json!({ "userId": "123" })
+        );
+
+        assert!(matches!(
+            decoder.decode("/acme.Orders/GetOrder", body.as_slice()),
+            Err(GrpcDecodeError::NoMessage(..))
+        ));
+
+        body[0] = 1;
+        assert!(matches!(
+            decoder.decode("/acme.Users/GetUser", body.as_slice()),
+            Err(GrpcDecodeError::Compressed)
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+//! JSON view of an HTTP request, evaluated by the jq body filters, see [`RequestView`].
+
+use std::collections::BTreeMap;
+
+use hyper::http::request::Parts;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Max length (in bytes) of a header value in [`RequestView::headers`], longer values are
+/// truncated.
+///
+/// Keeps huge headers (e.g. cookies) from eating up the jq memory limit.
+pub const MAX_HEADER_VALUE_LEN: usize = 4096;
+
+/// JSON representation of an HTTP request, the input of
+/// [`HttpBodyFilter::Jq`](super::filter::HttpBodyFilter::Jq).
+///
+/// Users' jq expressions depend on these field names, **do not** change them:
+///
+/// ```json
+/// {
+///   "method": "POST",
+///   "path": "/api/v2/users",
+///   "query": { "page": ["1"], "tag": ["a", "b"] },
+///   "headers": { "content-type": "application/json", "x-tenant": "acme" },
+///   "body": { "user_id": "123" }
+/// }
+/// ```
+///
+/// - `method` is uppercase;
+/// - `path` does not include the query, and is not percent-decoded;
+/// - `query` maps the decoded parameter names to all of their decoded values, in order;
+/// - `headers` maps the lowercase header names to their values. Repeated headers are joined with
+///   `", "`, invalid UTF-8 is replaced with `U+FFFD`, and values are truncated to
+///   [`MAX_HEADER_VALUE_LEN`] bytes;
+/// - `body` is the parsed JSON body, or `null` when the body is not JSON.
+#[derive(Debug, Serialize)]
+pub struct RequestView {
+    method: String,
+    path: String,
+    query: BTreeMap<String, Vec<String>>,
+    headers: BTreeMap<String, String>,
+    body: Value,
+}
+
+impl RequestView {
+    pub fn new(parts: &Parts, body: Option<Value>) -> Self {
+        let query = parts
+            .uri
+            .query()
+            .map(|query| {
+                form_urlencoded::parse(query.as_bytes()).fold(
+                    BTreeMap::<String, Vec<String>>::new(),
+                    |mut params, (name, value)| {
+                        params
+                            .entry(name.into_owned())
+                            .or_default()
+                            .push(value.into_owned());
+                        params
+                    },
+                )
+            })
+            .unwrap_or_default();
+
+        let mut headers = BTreeMap::<String, String>::new();
+        for (name, value) in &parts.headers {
+            let value = String::from_utf8_lossy(value.as_bytes());
+
+            headers
+                .entry(name.as_str().to_owned())
+                .and_modify(|values| {
+                    values.push_str(", ");
+                    values.push_str(&value);
+                })
+                .or_insert_with(|| value.into_owned());
+        }
+        headers.values_mut().for_each(truncate_header_value);
+
+        Self {
+            method: parts.method.as_str().to_uppercase(),
+            path: parts.uri.path().to_owned(),
+            query,
+            headers,
+            body: body.unwrap_or(Value::Null),
+        }
+    }
+}
+
+impl From<RequestView> for Value {
+    fn from(view: RequestView) -> Self {
+        serde_json::to_value(view).expect("RequestView should always serialize into JSON")
+    }
+}
+
+/// Truncates the value to [`MAX_HEADER_VALUE_LEN`] bytes, at a char boundary.
+fn truncate_header_value(value: &mut String) {
+    if value.len() > MAX_HEADER_VALUE_LEN {
+        let end = value.floor_char_boundary(MAX_HEADER_VALUE_LEN);
+        value.truncate(end);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::Request;
+    use serde_json::{Value, json};
+
+    use super::{MAX_HEADER_VALUE_LEN, RequestView};
+
+    #[test]
+    fn serializes_stable_schema() {
+        let parts = Request::builder()
+            .method("post")
+            .uri("http://api.local/api/v2/users?tag=a&page=1&tag=b%20c&flag")
+            .header("X-Tenant", "acme")
+            .header("accept", "text/html")
+            .header("accept", "application/json")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+
+        let view = RequestView::new(&parts, Some(json!({ "user_id": "123" })));
+
+        assert_eq!(
+            Value::from(view),
+            json!({
+                "method": "POST",
+                "path": "/api/v2/users",
+                "query": { "flag": [""], "page": ["1"], "tag": ["a", "b c"] },
+                "headers": { "accept": "text/html, application/json", "x-tenant": "acme" },
+                "body": { "user_id": "123" }
+            })
+        );
+    }
+
+    #[test]
+    fn null_body_and_truncated_headers() {
+        let parts = Request::builder()
+            .uri("/")
+            .header("cookie", "é".repeat(MAX_HEADER_VALUE_LEN))
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+
+        let view = Value::from(RequestView::new(&parts, None));
+
+        assert_eq!(view["query"], json!({}));
+        assert_eq!(view["body"], Value::Null);
+        assert_eq!(
+            view["headers"]["cookie"].as_str().unwrap().len(),
+            MAX_HEADER_VALUE_LEN
+        );
+    }
+}
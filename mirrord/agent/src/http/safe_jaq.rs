@@ -0,0 +1,262 @@
+//! Evaluation of user supplied jq expressions, see [`SafeJaq`].
+
+use std::{
+    io::{self, Write},
+    ops::RangeInclusive,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use mirrord_protocol::tcp::JqQuery;
+use serde_json::Value;
+use tokio_retry::strategy::ExponentialBackoff;
+use tracing::Instrument;
+
+use self::pool::{JaqPool, JaqPoolConfig};
+
+pub mod pool;
+
+/// Limits of [`SafeJaq`], configured with the `MIRRORD_AGENT_JAQ_TIME_LIMIT_MS` and
+/// `MIRRORD_AGENT_JAQ_MEMORY_LIMIT_BYTES` environment variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JaqLimits {
+    /// Expressions that take longer than this do not match.
+    pub time_limit: Duration,
+    /// Expressions are not evaluated against payloads bigger than this (measured as serialized
+    /// JSON).
+    ///
+    /// This bounds the input of the evaluation. The memory allocated by the expression itself is
+    /// bounded only by [`JaqLimits::time_limit`], unless [`SafeJaq`] runs with a [`JaqPool`],
+    /// whose processes also get a memory limit based on this value.
+    pub memory_limit: u64,
+}
+
+impl JaqLimits {
+    /// Allowed values of [`JaqLimits::time_limit`], in milliseconds.
+    pub const TIME_LIMIT_MS_RANGE: RangeInclusive<u64> = 1..=60_000;
+
+    /// Allowed values of [`JaqLimits::memory_limit`].
+    pub const MEMORY_LIMIT_BYTES_RANGE: RangeInclusive<u64> = 1024 * 1024..=8 * 1024 * 1024 * 1024;
+
+    pub const DEFAULT_TIME_LIMIT_MS: u64 = 500;
+
+    pub const DEFAULT_MEMORY_LIMIT_BYTES: u64 = 512 * 1024 * 1024;
+}
+
+impl Default for JaqLimits {
+    fn default() -> Self {
+        Self {
+            time_limit: Duration::from_millis(Self::DEFAULT_TIME_LIMIT_MS),
+            memory_limit: Self::DEFAULT_MEMORY_LIMIT_BYTES,
+        }
+    }
+}
+
+/// Failed evaluation of a jq expression by [`SafeJaq`].
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum JaqError {
+    #[error("payload of {size} bytes exceeds the jq memory limit of {limit} bytes")]
+    MemoryLimit { size: u64, limit: u64 },
+
+    #[error("jq expression took longer than the time limit of {}ms", .0.as_millis())]
+    TimeLimit(Duration),
+
+    #[error("jq expression failed: {0}")]
+    Evaluation(String),
+
+    #[error("jq evaluation task failed: {0}")]
+    Unavailable(String),
+}
+
+impl JaqError {
+    /// Label of [`HTTP_FILTER_ERRORS`](crate::metrics::HTTP_FILTER_ERRORS) for this error.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Self::MemoryLimit { .. } | Self::TimeLimit(..) => "limit_exceeded",
+            Self::Evaluation(..) => "evaluation_error",
+            Self::Unavailable(..) => "evaluator_unavailable",
+        }
+    }
+}
+
+/// Evaluates user supplied jq expressions (used by the HTTP filters) off the async runtime,
+/// within the [`JaqLimits`].
+///
+/// An expression matches only when it returns `true`. Expressions that run over the time limit
+/// fail with [`JaqError::TimeLimit`].
+///
+/// By default, expressions are evaluated in blocking tasks of the agent process, and the ones that
+/// run over the time limit are left running in the background, as blocking tasks cannot be
+/// cancelled. With [`SafeJaq::with_pool`], they are evaluated in a pool of sandboxed evaluator
+/// processes instead, see [`JaqPool`].
+#[derive(Debug, Clone, Default)]
+pub struct SafeJaq {
+    limits: JaqLimits,
+    pool: Option<Arc<JaqPool>>,
+}
+
+/// Set with [`SafeJaq::install`] when the agent starts.
+static GLOBAL: OnceLock<SafeJaq> = OnceLock::new();
+
+impl SafeJaq {
+    pub fn new(limits: JaqLimits) -> Self {
+        Self { limits, pool: None }
+    }
+
+    /// Evaluates the expressions in a pool of evaluator processes.
+    pub fn with_pool(self, config: JaqPoolConfig) -> Self {
+        Self {
+            pool: Some(Arc::new(JaqPool::new(config, self.limits.memory_limit))),
+            ..self
+        }
+    }
+
+    pub fn limits(&self) -> JaqLimits {
+        self.limits
+    }
+
+    /// Makes this the instance returned from [`SafeJaq::global`].
+    ///
+    /// Should be called once, when the agent starts. Later calls have no effect.
+    pub fn install(self) {
+        if GLOBAL.set(self).is_err() {
+            tracing::warn!("SafeJaq was already installed, ignoring new limits");
+        }
+    }
+
+    /// Instance shared by all HTTP filters, the one passed to [`SafeJaq::install`], or one with
+    /// the default [`JaqLimits`].
+    pub fn global() -> &'static Self {
+        GLOBAL.get_or_init(Self::default)
+    }
+
+    /// Evaluates the `query` against the `payload`.
+    ///
+    /// Returns an error if the `payload` exceeds [`JaqLimits::memory_limit`], if the evaluation
+    /// runs over [`JaqLimits::time_limit`], or if the query fails without returning a boolean.
+    /// Failing to compile the query should not happen, as [`JqQuery`] is verified when created.
+    pub async fn evaluate(&self, query: &JqQuery, payload: Value) -> Result<bool, JaqError> {
+        let size = json_size(&payload);
+        if size > self.limits.memory_limit {
+            return Err(JaqError::MemoryLimit {
+                size,
+                limit: self.limits.memory_limit,
+            });
+        }
+
+        if let Some(pool) = &self.pool {
+            return pool.evaluate(query, payload, self.limits.time_limit).await;
+        }
+
+        let span = tracing::warn_span!("jaq eval", ?query);
+        let query = query.clone();
+
+        let mut handle = tokio::task::spawn_blocking(move || {
+            run_query(query.as_str(), payload).map_err(JaqError::Evaluation)
+        });
+
+        tokio::select! {
+            result = &mut handle => {
+                match result {
+                    Ok(r) => r,
+                    Err(join) => {
+                        tracing::error!(?join, "panic in jaq evaluation task");
+                        Err(JaqError::Unavailable(join.to_string()))
+                    }
+                }
+            }
+            _ = tokio::time::sleep(self.limits.time_limit) => {
+                tracing::warn!("jq expr evaluation took longer than max allowed time");
+
+                tokio::spawn(async move {
+                    let backoff = ExponentialBackoff::from_millis(5)
+                        .factor(200)
+                        .max_delay(Duration::from_secs(25));
+
+                    for delay in backoff {
+                        tokio::time::sleep(delay).await;
+                        tracing::warn!("jq evaluation has not completed");
+                    }
+                }.instrument(span));
+
+                Err(JaqError::TimeLimit(self.limits.time_limit))
+            }
+        }
+    }
+}
+
+/// Runs the `query` against the `payload` on the current thread.
+///
+/// Returns the error message of [`JaqError::Evaluation`] on failure.
+fn run_query(query: &str, payload: Value) -> Result<bool, String> {
+    let filter = mirrord_jaq::compile_jq(query).map_err(|error| error.to_string())?;
+    mirrord_jaq::run_jq(&filter, payload.into())
+}
+
+/// Length of the `value` serialized as JSON.
+fn json_size(value: &Value) -> u64 {
+    struct Counter(u64);
+
+    impl Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len() as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    // Writing to `Counter` cannot fail.
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use mirrord_protocol::tcp::JqQuery;
+    use serde_json::json;
+
+    use super::{JaqError, JaqLimits, SafeJaq};
+
+    #[tokio::test]
+    async fn rejects_payload_over_memory_limit() {
+        let safe_jaq = SafeJaq::new(JaqLimits {
+            time_limit: Duration::from_millis(500),
+            memory_limit: 16,
+        });
+        let query = JqQuery::new(".user == \"alice\"").unwrap();
+
+        assert_eq!(
+            safe_jaq.evaluate(&query, json!({ "user": "alice" })).await,
+            Ok(true)
+        );
+        assert!(matches!(
+            safe_jaq
+                .evaluate(&query, json!({ "user": "alice", "padding": "0123456789" }))
+                .await,
+            Err(JaqError::MemoryLimit { limit: 16, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn runtime_error_without_boolean() {
+        let safe_jaq = SafeJaq::default();
+
+        let query = JqQuery::new(".user | tonumber").unwrap();
+        assert!(matches!(
+            safe_jaq.evaluate(&query, json!({ "user": "alice" })).await,
+            Err(JaqError::Evaluation(..))
+        ));
+
+        let query = JqQuery::new("(.user | tonumber), true").unwrap();
+        assert_eq!(
+            safe_jaq.evaluate(&query, json!({ "user": "alice" })).await,
+            Ok(true)
+        );
+    }
+}
@@ -0,0 +1,466 @@
+//! Pool of evaluator processes used by [`SafeJaq`](super::SafeJaq) in the pooled mode, see
+//! [`JaqPool`].
+//!
+//! Evaluators are the agent binary started with [`EVALUATOR_ENV`]. They read requests from stdin
+//! and write responses to stdout, each frame prefixed with its length as an 8 byte big endian
+//! integer, see [`run_evaluator`].
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    io::{self, Read, Write},
+    ops::RangeInclusive,
+    process::Stdio,
+    sync::Mutex,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use mirrord_jaq::JqFilter;
+use mirrord_protocol::tcp::JqQuery;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::Semaphore,
+};
+use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
+
+use super::JaqError;
+
+/// When set, the agent binary runs as a jq evaluator, see [`run_evaluator`].
+///
+/// The value is the memory limit of the evaluator process, in bytes.
+pub const EVALUATOR_ENV: &str = "MIRRORD_AGENT_JAQ_EVALUATOR";
+
+/// Memory the evaluator processes can use on top of [`JaqLimits::memory_limit`](super::JaqLimits),
+/// as the payload is held both serialized and deserialized.
+const EVALUATOR_MEMORY_OVERHEAD: u64 = 64 * 1024 * 1024;
+
+/// Length of the prefix of the frames exchanged with the evaluator processes.
+const FRAME_LENGTH_PREFIX: usize = 8;
+
+/// Frames exchanged with the evaluator processes can be this much bigger than the payload limit
+/// ([`JaqLimits::memory_limit`](super::JaqLimits)), to fit the query and the error messages.
+const FRAME_OVERHEAD: usize = 1024 * 1024;
+
+/// User and group the evaluator processes run as (`nobody`).
+const EVALUATOR_ID: u32 = 65534;
+
+/// Configures the pooled mode of [`SafeJaq`](super::SafeJaq), with the
+/// `MIRRORD_AGENT_JAQ_POOL_SIZE` and `MIRRORD_AGENT_JAQ_POOL_MAX_EVALUATIONS` environment
+/// variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JaqPoolConfig {
+    /// How many evaluator processes can run at once.
+    pub size: u32,
+    /// Evaluator processes are replaced after this many evaluations.
+    pub max_evaluations: u64,
+}
+
+impl JaqPoolConfig {
+    /// Allowed values of [`JaqPoolConfig::size`].
+    pub const SIZE_RANGE: RangeInclusive<i64> = 1..=64;
+
+    pub const DEFAULT_MAX_EVALUATIONS: u64 = 1000;
+}
+
+/// Sent to the evaluator processes.
+#[derive(Serialize, Deserialize, Debug)]
+struct EvaluatorRequest {
+    query: String,
+    payload: Value,
+}
+
+/// Received from the evaluator processes, the error being a [`JaqError::Evaluation`].
+type EvaluatorResponse = Result<bool, String>;
+
+/// Evaluator processes kept alive between the evaluations of [`SafeJaq`](super::SafeJaq).
+///
+/// Evaluators are started when needed, and killed when they run over
+/// [`JaqLimits::time_limit`](super::JaqLimits::time_limit), when they fail (e.g. when they run
+/// out of memory), or after [`JaqPoolConfig::max_evaluations`]. Unlike the blocking tasks of the
+/// in-process mode, expressions that run over the time limit do not keep running in the
+/// background.
+#[derive(Debug)]
+pub struct JaqPool {
+    config: JaqPoolConfig,
+    /// Memory limit of the evaluator processes.
+    memory_limit: u64,
+    /// Frames bigger than this are not sent to nor read from the evaluator processes, so that a
+    /// misbehaving evaluator cannot make the agent allocate without limit.
+    max_frame_length: usize,
+    /// Evaluators waiting for the next request.
+    idle: Mutex<Vec<Evaluator>>,
+    /// Limits the number of evaluators to [`JaqPoolConfig::size`].
+    permits: Semaphore,
+}
+
+impl JaqPool {
+    pub fn new(config: JaqPoolConfig, memory_limit: u64) -> Self {
+        Self {
+            config,
+            memory_limit: memory_limit.saturating_add(EVALUATOR_MEMORY_OVERHEAD),
+            max_frame_length: usize::try_from(memory_limit)
+                .unwrap_or(usize::MAX)
+                .saturating_add(FRAME_OVERHEAD),
+            idle: Default::default(),
+            permits: Semaphore::new(config.size as usize),
+        }
+    }
+
+    /// Evaluates the `query` against the `payload` in one of the evaluator processes.
+    pub async fn evaluate(
+        &self,
+        query: &JqQuery,
+        payload: Value,
+        time_limit: Duration,
+    ) -> Result<bool, JaqError> {
+        let request = serde_json::to_vec(&EvaluatorRequest {
+            query: query.as_str().to_owned(),
+            payload,
+        })
+        .map_err(|error| JaqError::Unavailable(error.to_string()))?;
+
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|error| JaqError::Unavailable(error.to_string()))?;
+
+        // Unwrap is safe, the lock is only held to pop or push an evaluator, which can't panic.
+        let idle = self.idle.lock().unwrap().pop();
+        let mut evaluator = match idle {
+            Some(evaluator) => evaluator,
+            None => {
+                Evaluator::spawn(self.memory_limit, self.max_frame_length).map_err(|error| {
+                    JaqError::Unavailable(format!(
+                        "failed to start a jq evaluator process: {error}"
+                    ))
+                })?
+            }
+        };
+
+        let result = tokio::time::timeout(time_limit, evaluator.evaluate(request.into())).await;
+        match result {
+            Ok(Ok(response)) => {
+                evaluator.evaluations += 1;
+                if evaluator.evaluations < self.config.max_evaluations {
+                    // Unwrap is safe, see above.
+                    self.idle.lock().unwrap().push(evaluator);
+                }
+
+                response.map_err(JaqError::Evaluation)
+            }
+            Ok(Err(error)) => {
+                tracing::warn!(%error, "jq evaluator process failed, replacing it");
+                Err(JaqError::Unavailable(format!(
+                    "jq evaluator process failed: {error}"
+                )))
+            }
+            Err(..) => {
+                tracing::warn!("jq expr evaluation took longer than max allowed time");
+                Err(JaqError::TimeLimit(time_limit))
+            }
+        }
+    }
+}
+
+/// Evaluator process of [`JaqPool`], killed when dropped.
+#[derive(Debug)]
+struct Evaluator {
+    _child: Child,
+    requests: FramedWrite<ChildStdin, LengthDelimitedCodec>,
+    responses: FramedRead<ChildStdout, LengthDelimitedCodec>,
+    /// How many requests this evaluator has handled.
+    evaluations: u64,
+}
+
+impl Evaluator {
+    fn spawn(memory_limit: u64, max_frame_length: usize) -> io::Result<Self> {
+        // The executable is resolved by the kernel, regardless of the namespaces the agent is in.
+        let mut child = Command::new("/proc/self/exe")
+            .env_clear()
+            .env(EVALUATOR_ENV, memory_limit.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| io::Error::other("stdin of the evaluator is not piped"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| io::Error::other("stdout of the evaluator is not piped"))?;
+
+        Ok(Self {
+            _child: child,
+            requests: FramedWrite::new(stdin, codec(max_frame_length)),
+            responses: FramedRead::new(stdout, codec(max_frame_length)),
+            evaluations: 0,
+        })
+    }
+
+    async fn evaluate(&mut self, request: Bytes) -> io::Result<EvaluatorResponse> {
+        self.requests.send(request).await?;
+
+        let response = self.responses.next().await.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "evaluator exited before responding",
+            )
+        })??;
+
+        Ok(serde_json::from_slice(&response)?)
+    }
+}
+
+/// Codec of the frames exchanged with the evaluator processes, see [`JaqPool::max_frame_length`].
+fn codec(max_frame_length: usize) -> LengthDelimitedCodec {
+    LengthDelimitedCodec::builder()
+        .length_field_length(FRAME_LENGTH_PREFIX)
+        .max_frame_length(max_frame_length)
+        .new_codec()
+}
+
+/// Runs the agent binary as an evaluator process of [`JaqPool`], until stdin is closed.
+///
+/// `memory_limit` is the value of [`EVALUATOR_ENV`].
+pub fn run_evaluator(memory_limit: &str) -> io::Result<()> {
+    let memory_limit = memory_limit
+        .parse::<u64>()
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    sandbox(memory_limit)?;
+
+    evaluate_frames(io::stdin().lock(), io::stdout().lock())
+}
+
+/// Limits the memory of the evaluator process, drops its privileges (see [`drop_privileges`]),
+/// and makes sure that it does not outlive the agent.
+fn sandbox(memory_limit: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: memory_limit,
+        rlim_max: memory_limit,
+    };
+    if unsafe { libc::setrlimit(libc::RLIMIT_DATA, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    drop_privileges()?;
+
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Set after the credentials change, which resets it.
+    if unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Switches the evaluator process to [`EVALUATOR_ID`], with no supplementary groups and no
+/// capabilities.
+///
+/// The capability bounding set is cleared first, while the process still has `CAP_SETPCAP`, and
+/// the permitted and effective sets are cleared by the kernel when the process leaves uid `0`.
+fn drop_privileges() -> io::Result<()> {
+    for capability in 0.. {
+        if unsafe { libc::prctl(libc::PR_CAPBSET_DROP, capability, 0, 0, 0) } != 0 {
+            let error = io::Error::last_os_error();
+            // Past the last capability known to the kernel.
+            if error.raw_os_error() == Some(libc::EINVAL) {
+                break;
+            }
+
+            return Err(error);
+        }
+    }
+
+    if unsafe {
+        libc::prctl(
+            libc::PR_CAP_AMBIENT,
+            libc::PR_CAP_AMBIENT_CLEAR_ALL,
+            0,
+            0,
+            0,
+        )
+    } != 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+
+    if unsafe { libc::setgroups(0, std::ptr::null()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if unsafe { libc::setresgid(EVALUATOR_ID, EVALUATOR_ID, EVALUATOR_ID) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if unsafe { libc::setresuid(EVALUATOR_ID, EVALUATOR_ID, EVALUATOR_ID) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Filters compiled by an evaluator process, keyed by their queries.
+///
+/// The agent sends the same few queries (one per HTTP filter) over and over, so they are compiled
+/// only once.
+#[derive(Default)]
+struct FilterCache(HashMap<String, JqFilter>);
+
+impl FilterCache {
+    /// Maximum number of filters kept, the cache is emptied when it gets full.
+    const CAPACITY: usize = 64;
+
+    /// Returns the compiled `query`, or the error message of [`JaqError::Evaluation`] if it does
+    /// not compile.
+    fn get_or_compile(&mut self, query: String) -> Result<&JqFilter, String> {
+        if self.0.len() >= Self::CAPACITY && !self.0.contains_key(&query) {
+            self.0.clear();
+        }
+
+        match self.0.entry(query) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let filter =
+                    mirrord_jaq::compile_jq(entry.key()).map_err(|error| error.to_string())?;
+                Ok(entry.insert(filter))
+            }
+        }
+    }
+}
+
+/// Responds to the requests read from the `input` frames with frames written to the `output`.
+fn evaluate_frames<R: Read, W: Write>(mut input: R, mut output: W) -> io::Result<()> {
+    let mut filters = FilterCache::default();
+
+    while let Some(request) = read_frame(&mut input)? {
+        let EvaluatorRequest { query, payload } = serde_json::from_slice(&request)?;
+        let response: EvaluatorResponse = filters
+            .get_or_compile(query)
+            .and_then(|filter| mirrord_jaq::run_jq(filter, payload.into()));
+
+        let response = serde_json::to_vec(&response)?;
+        output.write_all(&(response.len() as u64).to_be_bytes())?;
+        output.write_all(&response)?;
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Reads one frame, returns [`None`] if the `input` is closed.
+fn read_frame<R: Read>(input: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0; FRAME_LENGTH_PREFIX];
+    match input.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+
+    let length = usize::try_from(u64::from_be_bytes(length))
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let mut frame = Vec::try_with_capacity(length)
+        .map_err(|error| io::Error::new(io::ErrorKind::OutOfMemory, error))?;
+    input.by_ref().take(length as u64).read_to_end(&mut frame)?;
+    if frame.len() != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(Some(frame))
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use serde_json::json;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use super::{EvaluatorRequest, EvaluatorResponse, FilterCache, codec, evaluate_frames};
+
+    const MAX_FRAME_LENGTH: usize = 1024;
+
+    /// Frames encoded by the agent are understood by the evaluator, and the other way around.
+    #[test]
+    fn evaluator_frames() {
+        let requests = [
+            (".user == \"alice\"", json!({ "user": "alice" })),
+            (".user == \"alice\"", json!({ "user": "bob" })),
+            (".user | tonumber", json!({ "user": "alice" })),
+            (".user ==", json!({ "user": "alice" })),
+        ];
+
+        let mut input = BytesMut::new();
+        for (query, payload) in requests {
+            let request = serde_json::to_vec(&EvaluatorRequest {
+                query: query.to_owned(),
+                payload,
+            })
+            .unwrap();
+            codec(MAX_FRAME_LENGTH)
+                .encode(request.as_slice(), &mut input)
+                .unwrap();
+        }
+
+        let mut output = Vec::new();
+        evaluate_frames(input.as_ref(), &mut output).unwrap();
+
+        let mut output = BytesMut::from(output.as_slice());
+        let mut codec = codec(MAX_FRAME_LENGTH);
+        let responses = std::iter::from_fn(|| codec.decode(&mut output).unwrap())
+            .map(|frame| serde_json::from_slice::<EvaluatorResponse>(&frame).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(output.is_empty());
+        assert!(
+            matches!(
+                responses.as_slice(),
+                [Ok(true), Ok(false), Err(..), Err(..)]
+            ),
+            "{responses:?}"
+        );
+    }
+
+    /// Queries are compiled once, and the cache does not grow past its capacity.
+    #[test]
+    fn filter_cache() {
+        let mut filters = FilterCache::default();
+
+        filters.get_or_compile(".user".to_owned()).unwrap();
+        filters.get_or_compile(".user".to_owned()).unwrap();
+        assert!(filters.get_or_compile(".user ==".to_owned()).is_err());
+        assert_eq!(filters.0.len(), 1);
+
+        for index in 0..FilterCache::CAPACITY {
+            filters.get_or_compile(format!(".user{index}")).unwrap();
+        }
+        assert_eq!(filters.0.len(), 1);
+    }
+
+    /// Frames over the limit are neither sent nor read.
+    #[test]
+    fn frames_over_the_limit() {
+        let frame = vec![b'x'; MAX_FRAME_LENGTH + 1];
+
+        let mut buffer = BytesMut::new();
+        assert!(
+            codec(MAX_FRAME_LENGTH)
+                .encode(frame.as_slice(), &mut buffer)
+                .is_err()
+        );
+
+        codec(usize::MAX)
+            .encode(frame.as_slice(), &mut buffer)
+            .unwrap();
+        assert!(codec(MAX_FRAME_LENGTH).decode(&mut buffer).is_err());
+    }
+}
@@ -1,3 +1,7 @@
+use std::{fmt, ops::Not, sync::Arc};
+
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
 use hyper::{
     Request, Response, Version,
     body::{Body, Incoming},
@@ -5,6 +9,7 @@ use hyper::{
     rt::{Read, Write},
 };
 use hyper_util::rt::TokioExecutor;
+use tokio::sync::Mutex;
 
 use super::HttpVersion;
 
@@ -66,3 +71,46 @@ where
         }
     }
 }
+
+/// HTTP/2 connection to the original destination, shared by all requests passed through from the
+/// same redirected connection.
+///
+/// HTTP/2 multiplexes streams over one connection, so there is no need to make a new connection
+/// for every unmatched stream (e.g. every gRPC call). The connection is made lazily with the first
+/// HTTP/2 request, and made again if it was closed.
+#[derive(Clone, Default)]
+pub struct Http2SenderPool(Arc<Mutex<Option<http2::SendRequest<BoxBody<Bytes, hyper::Error>>>>>);
+
+impl Http2SenderPool {
+    /// Returns a sender for the pooled connection, or makes a new connection with the given
+    /// future.
+    ///
+    /// Concurrent calls wait for the first one to make the connection.
+    pub async fn get_or_connect<F, E>(
+        &self,
+        connect: F,
+    ) -> Result<HttpSender<BoxBody<Bytes, hyper::Error>>, E>
+    where
+        F: Future<Output = Result<HttpSender<BoxBody<Bytes, hyper::Error>>, E>>,
+    {
+        let mut pooled = self.0.lock().await;
+
+        if let Some(sender) = pooled.as_ref().filter(|sender| sender.is_closed().not()) {
+            return Ok(HttpSender::V2(sender.clone()));
+        }
+
+        let sender = connect.await?;
+        *pooled = match &sender {
+            HttpSender::V1(..) => None,
+            HttpSender::V2(sender) => Some(sender.clone()),
+        };
+
+        Ok(sender)
+    }
+}
+
+impl fmt::Debug for Http2SenderPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Http2SenderPool").finish_non_exhaustive()
+    }
+}
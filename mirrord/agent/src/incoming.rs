@@ -19,7 +19,10 @@ use std::{
 use composed::ComposedRedirector;
 pub use connection::{
     IncomingStream, IncomingStreamItem,
-    http::{MirroredHttp, RedirectedHttp, ResponseBodyProvider, ResponseProvider, StolenHttp},
+    http::{
+        BufferBodyError, MirroredHttp, RedirectedHttp, ResponseBodyProvider, ResponseProvider,
+        StolenHttp,
+    },
     tcp::{RedirectedTcp, StolenTcp},
 };
 pub use error::{ConnError, RedirectorTaskError};
@@ -79,6 +82,25 @@ pub struct Redirected {
     destination: SocketAddr,
 }
 
+impl Redirected {
+    /// Creates a new instance, converting IPv4-mapped IPv6 addresses to plain IPv4 addresses.
+    ///
+    /// Listeners bound to the IPv6 wildcard address (`::`) also accept IPv4 connections, and see
+    /// their addresses as `::ffff:a.b.c.d`. The clients should see the original IPv4 addresses.
+    pub fn new(stream: TcpStream, source: SocketAddr, destination: SocketAddr) -> Self {
+        Self {
+            stream,
+            source: to_canonical(source),
+            destination: to_canonical(destination),
+        }
+    }
+}
+
+/// Converts an IPv4-mapped IPv6 address to a plain IPv4 address, see [`IpAddr::to_canonical`].
+fn to_canonical(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(addr.ip().to_canonical(), addr.port())
+}
+
 impl fmt::Debug for Redirected {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Redirected")
@@ -298,14 +320,37 @@ pub mod test {
             )
             .unwrap();
 
-            let redirected = Redirected {
-                stream: server_stream,
-                source: peer_addr,
-                destination: original_destination,
-            };
+            let redirected = Redirected::new(server_stream, peer_addr, original_destination);
             self.tx.send(redirected).await.unwrap();
 
             client_stream
         }
     }
+
+    /// Verifies that connections accepted on a dual-stack listener have plain IPv4 addresses.
+    #[tokio::test]
+    async fn v4_connection_on_v6_wildcard() {
+        let listener = TcpListener::bind(SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0))
+            .await
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let ((stream, source), _client) = tokio::try_join!(
+            listener.accept(),
+            TcpStream::connect(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port)),
+        )
+        .unwrap();
+        assert_eq!(source.ip(), Ipv4Addr::LOCALHOST.to_ipv6_mapped());
+
+        let redirected = Redirected::new(
+            stream,
+            source,
+            SocketAddr::new(Ipv4Addr::LOCALHOST.to_ipv6_mapped().into(), port),
+        );
+        assert_eq!(redirected.source.ip(), Ipv4Addr::LOCALHOST);
+        assert_eq!(
+            redirected.destination,
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port)
+        );
+    }
 }
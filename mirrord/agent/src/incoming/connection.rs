@@ -12,6 +12,7 @@ use futures::Stream;
 use mirrord_protocol::tcp::InternalHttpBodyFrame;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
     sync::mpsc,
 };
 use tokio_stream::wrappers::{BroadcastStream, errors::BroadcastStreamRecvError};
@@ -22,8 +23,8 @@ use super::{
     tls::{self, StealTlsHandlerStore, handler::PassThroughTlsConnector},
 };
 use crate::{
-    http::HttpVersion,
-    metrics::{MetricGuard, REDIRECTED_CONNECTIONS},
+    http::{HttpVersion, sender::Http2SenderPool},
+    metrics::{FORWARDED_BYTES, MetricGuard, REDIRECTED_CONNECTIONS},
 };
 
 mod body_utils;
@@ -52,6 +53,9 @@ pub struct ConnectionInfo {
     /// TLS connector that should be used when passing this connection
     /// through to its original destination.
     pub tls_connector: Option<PassThroughTlsConnector>,
+    /// HTTP/2 connection to the original destination, shared by the requests passed through from
+    /// this connection.
+    pub http2_pool: Http2SenderPool,
 }
 
 impl ConnectionInfo {
@@ -82,7 +86,8 @@ impl<T> IncomingIO for T where T: 'static + AsyncRead + AsyncWrite + Send + Sync
 
 /// Wrapper over an incoming IO stream.
 ///
-/// Automatically updates the [`REDIRECTED_CONNECTIONS`] metric with an internal [`MetricGuard`].
+/// Automatically updates the [`REDIRECTED_CONNECTIONS`] metric with an internal [`MetricGuard`],
+/// and counts the bytes read and written in the [`FORWARDED_BYTES`] metric.
 /// Transparently implements [`AsyncRead`] and [`AsyncWrite`].
 struct IncomingIoWrapper<T> {
     io: T,
@@ -99,7 +104,17 @@ where
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
         let this = self.get_mut();
-        Pin::new(&mut this.io).poll_read(cx, buf)
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.io).poll_read(cx, buf);
+
+        let read = buf.filled().len().saturating_sub(filled);
+        if read > 0 {
+            FORWARDED_BYTES
+                .with_label_values(&["inbound"])
+                .inc_by(read as u64);
+        }
+
+        result
     }
 }
 
@@ -127,7 +142,9 @@ where
         buf: &[u8],
     ) -> Poll<Result<usize, io::Error>> {
         let this = self.get_mut();
-        Pin::new(&mut this.io).poll_write(cx, buf)
+        Pin::new(&mut this.io)
+            .poll_write(cx, buf)
+            .map_ok(count_outbound_bytes)
     }
 
     fn poll_write_vectored(
@@ -136,10 +153,23 @@ where
         bufs: &[io::IoSlice<'_>],
     ) -> Poll<Result<usize, io::Error>> {
         let this = self.get_mut();
-        Pin::new(&mut this.io).poll_write_vectored(cx, bufs)
+        Pin::new(&mut this.io)
+            .poll_write_vectored(cx, bufs)
+            .map_ok(count_outbound_bytes)
     }
 }
 
+/// Counts the bytes written to an [`IncomingIoWrapper`] in the [`FORWARDED_BYTES`] metric.
+fn count_outbound_bytes(written: usize) -> usize {
+    FORWARDED_BYTES
+        .with_label_values(&["outbound"])
+        .inc_by(written as u64);
+    written
+}
+
+/// Content type of the TLS record that opens every TLS connection (handshake).
+const TLS_HANDSHAKE_CONTENT_TYPE: u8 = 0x16;
+
 /// A redirected connection that went through HTTP detection.
 ///
 /// # Metrics
@@ -172,8 +202,15 @@ impl MaybeHttp {
         let local_addr = redirected
             .stream
             .local_addr()
+            .map(super::to_canonical)
             .map_err(HttpDetectError::LocalAddr)?;
-        let tls_handler = tls_handlers.get(original_destination.port()).await?;
+        let tls_handler = match tls_handlers.get(original_destination.port()).await? {
+            Some(handler) => Self::starts_with_tls_handshake(&redirected.stream)
+                .await
+                .map_err(HttpDetectError::HttpDetect)?
+                .then_some(handler),
+            None => None,
+        };
 
         let Some(tls_handler) = tls_handler else {
             let (stream, http_version) =
@@ -192,6 +229,7 @@ impl MaybeHttp {
                     local_addr,
                     peer_addr,
                     tls_connector: None,
+                    http2_pool: Default::default(),
                 },
             });
         };
@@ -248,9 +286,26 @@ impl MaybeHttp {
                 local_addr,
                 peer_addr,
                 tls_connector: Some(tls_connector),
+                http2_pool: Default::default(),
             },
         })
     }
+    /// Checks whether the client starts the connection with a TLS handshake, without consuming any
+    /// data.
+    ///
+    /// Allows for passing non-TLS traffic on ports covered by the TLS steal config untouched.
+    /// If the client does not send anything within [`Self::HTTP_DETECTION_TIMEOUT`], the
+    /// connection is assumed to be non-TLS.
+    async fn starts_with_tls_handshake(stream: &TcpStream) -> io::Result<bool> {
+        let mut first_byte = [0_u8];
+
+        match tokio::time::timeout(Self::HTTP_DETECTION_TIMEOUT, stream.peek(&mut first_byte)).await
+        {
+            Ok(Ok(1)) => Ok(first_byte[0] == TLS_HANDSHAKE_CONTENT_TYPE),
+            Ok(Ok(..)) | Err(..) => Ok(false),
+            Ok(Err(error)) => Err(error),
+        }
+    }
 }
 
 impl fmt::Debug for MaybeHttp {
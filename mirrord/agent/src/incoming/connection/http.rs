@@ -1,5 +1,6 @@
 use std::{
     fmt::{self, Debug},
+    ops::Not,
     str::FromStr,
     sync::{Arc, LazyLock},
     time::Duration,
@@ -12,7 +13,7 @@ use http_body_util::{BodyExt, StreamBody, combinators::BoxBody};
 use hyper::{
     Response,
     body::Frame,
-    http::{StatusCode, request, response},
+    http::{Method, request, response},
 };
 use mirrord_agent_env::envs;
 use mirrord_protocol::tcp::InternalHttpBodyFrame;
@@ -30,7 +31,10 @@ use tracing::instrument;
 
 use super::{ConnectionInfo, IncomingStream, body_utils::FramesReader};
 use crate::{
-    http::{BoxResponse, body::RolledBackBody, extract_requests::ExtractedRequest},
+    http::{
+        BoxResponse, body::RolledBackBody, body_budget::BodyBufferReservation,
+        extract_requests::ExtractedRequest, is_upgrade,
+    },
     incoming::{
         ConnError, IncomingStreamItem, RedirectorTaskConfig,
         connection::{
@@ -38,6 +42,7 @@ use crate::{
             optional_broadcast::OptionalBroadcast,
         },
     },
+    metrics::INCOMING_TRAFFIC,
 };
 
 /// A redirected HTTP request.
@@ -67,6 +72,21 @@ pub enum BufferBodyError {
     BodyTooBig,
     #[error("receiving body took longer than the max configured timeout of {}ms", MAX_BODY_BUFFER_TIMEOUT.as_millis())]
     Timeout(#[from] Elapsed),
+    #[error("buffered bodies reached the max configured total size")]
+    CeilingReached,
+}
+
+impl BufferBodyError {
+    /// Label of [`BODY_FILTER_FALLBACKS`](crate::metrics::BODY_FILTER_FALLBACKS) for this error,
+    /// as the body filters fall back whenever the body could not be buffered.
+    pub fn fallback_reason(&self) -> &'static str {
+        match self {
+            Self::Conn(..) => "connection_error",
+            Self::BodyTooBig => "body_too_big",
+            Self::Timeout(..) => "timeout",
+            Self::CeilingReached => "buffer_ceiling",
+        }
+    }
 }
 
 impl RedirectedHttp {
@@ -100,6 +120,10 @@ impl RedirectedHttp {
     /// For the data to flow, you must start the request task with either [`Self::steal`] or
     /// [`Self::pass_through`].
     pub fn mirror(&mut self) -> MirroredHttp {
+        INCOMING_TRAFFIC
+            .with_label_values(&["http", "mirror"])
+            .inc();
+
         let rx = match &self.mirror_tx {
             Some(tx) => tx.subscribe(),
             None => {
@@ -143,6 +167,8 @@ impl RedirectedHttp {
     ///
     /// All data will be directed to this handle.
     pub fn steal(self) -> StolenHttp {
+        INCOMING_TRAFFIC.with_label_values(&["http", "steal"]).inc();
+
         let (tx, rx) = mpsc::channel(8);
         let (upgrade_tx, upgrade_rx) = oneshot::channel();
 
@@ -156,6 +182,7 @@ impl RedirectedHttp {
                 .collect(),
             body_finished: self.request.body_tail.is_none(),
         };
+        let request_method = request_head.parts.method.clone();
 
         let task = HttpTask {
             body_tail: self.request.body_tail,
@@ -173,6 +200,7 @@ impl RedirectedHttp {
             request_head,
             stream: IncomingStream::Steal(rx),
             response_provider: ResponseProvider {
+                request_method,
                 response_tx: self.request.response_tx,
                 upgrade_tx,
             },
@@ -193,6 +221,13 @@ impl RedirectedHttp {
         self.runtime_handle.spawn(task.run());
     }
 
+    /// Responds to the request in place, without passing it anywhere.
+    ///
+    /// The rest of the request body is dropped.
+    pub fn respond(self, response: BoxResponse) {
+        let _ = self.steal().response_provider.send_finished(response);
+    }
+
     pub fn parts_and_body(&mut self) -> (&mut Parts, Option<FramesReader<'_, Frame<Bytes>>>) {
         (
             &mut self.request.parts,
@@ -203,8 +238,17 @@ impl RedirectedHttp {
         )
     }
 
-    #[instrument(level = "trace", ret)]
-    pub async fn buffer_body(&mut self) -> Result<(), BufferBodyError> {
+    /// Buffers the request body for the body filters, up to the max configured size.
+    ///
+    /// The buffered bytes are taken from the given [`BodyBufferReservation`]. Buffering stops
+    /// with an error when the body is too big or the reservation cannot grow. The frames
+    /// buffered so far are kept, and the rest of the body is streamed without buffering once
+    /// the request is stolen or passed through.
+    #[instrument(level = "trace", skip(reservation), ret)]
+    pub async fn buffer_body(
+        &mut self,
+        reservation: &mut BodyBufferReservation,
+    ) -> Result<(), BufferBodyError> {
         let Some(tail) = self.request.body_tail.as_mut() else {
             return Ok(());
         };
@@ -228,6 +272,10 @@ impl RedirectedHttp {
             .map(|f| f.data_ref().map(Bytes::len).unwrap_or(0))
             .sum();
 
+        if reservation.try_grow(rxd).not() {
+            return Err(BufferBodyError::CeilingReached);
+        }
+
         let result = tokio::time::timeout(*MAX_BODY_BUFFER_TIMEOUT, async {
             let mut mirror = OptionalBroadcast::from(self.mirror_tx.clone());
             while rxd < *MAX_BODY_BUFFER_SIZE {
@@ -237,9 +285,14 @@ impl RedirectedHttp {
                         return Ok(());
                     }
                     Some(Ok(frame)) => {
-                        rxd += frame.data_ref().map(|f| f.len()).unwrap_or(0);
+                        let len = frame.data_ref().map(Bytes::len).unwrap_or(0);
+                        rxd += len;
                         mirror.send_frame(&frame);
                         self.request.body_head.push(frame);
+
+                        if reservation.try_grow(len).not() {
+                            return Err(BufferBodyError::CeilingReached);
+                        }
                     }
                     Some(Err(error)) => {
                         let error = ConnError::IncomingHttpError(Arc::new(error));
@@ -282,7 +335,7 @@ static MAX_BODY_BUFFER_SIZE: LazyLock<usize> = LazyLock::new(|| {
             None
         }
     }
-    .unwrap_or(64 * 1024)
+    .unwrap_or(256 * 1024)
 });
 
 static MAX_BODY_BUFFER_TIMEOUT: LazyLock<Duration> = LazyLock::new(|| {
@@ -338,6 +391,8 @@ pub struct RequestHead {
 
 /// Can be used by a stealing client to send an HTTP response for a stolen HTTP request.
 pub struct ResponseProvider {
+    /// Used to detect an HTTP upgrade, see [`is_upgrade`].
+    request_method: Method,
     response_tx: oneshot::Sender<BoxResponse>,
     upgrade_tx: oneshot::Sender<Option<UpgradeDataRx>>,
 }
@@ -349,7 +404,7 @@ impl ResponseProvider {
     ///
     /// Returns a [`ResponseBodyProvider`].
     pub fn send(self, parts: response::Parts) -> ResponseBodyProvider {
-        let has_upgrade = parts.status == StatusCode::SWITCHING_PROTOCOLS;
+        let has_upgrade = is_upgrade(&self.request_method, parts.status);
         let (frame_tx, frame_rx) = mpsc::channel::<Frame<Bytes>>(8);
         let body = RolledBackBody {
             head: Default::default(),
@@ -383,7 +438,7 @@ impl ResponseProvider {
         self,
         response: Response<BoxBody<Bytes, hyper::Error>>,
     ) -> Option<mpsc::Sender<Bytes>> {
-        let has_upgrade = response.status() == StatusCode::SWITCHING_PROTOCOLS;
+        let has_upgrade = is_upgrade(&self.request_method, response.status());
         let _ = self.response_tx.send(response);
         let (data_tx, data_rx) = has_upgrade.then(|| mpsc::channel(8)).unzip();
         let _ = self.upgrade_tx.send(data_rx);
@@ -6,7 +6,7 @@ use http_body_util::{BodyExt, StreamBody, combinators::BoxBody};
 use hyper::{
     Request, Response,
     body::{Body, Frame, Incoming},
-    http::{StatusCode, Version},
+    http::{Uri, Version},
     upgrade::{OnUpgrade, Upgraded},
 };
 use hyper_util::rt::TokioIo;
@@ -24,7 +24,8 @@ use tracing::instrument;
 use crate::{
     http::{
         HttpVersion, MIRRORD_AGENT_HTTP_HEADER_NAME, body::RolledBackBody,
-        error::MirrordErrorResponse, extract_requests::ExtractedRequest, sender::HttpSender,
+        error::MirrordErrorResponse, extract_requests::ExtractedRequest, is_upgrade,
+        sender::HttpSender,
     },
     incoming::{
         IncomingStreamItem, RedirectorTaskConfig,
@@ -126,6 +127,7 @@ impl HttpTask<PassthroughConnection> {
         let redirector_config_clone = redirector_config.clone();
         let upgrade = tokio::spawn(async move {
             let version = request.parts.version;
+            let method = request.parts.method.clone();
 
             let body_tail = request_frame_rx
                 .map(ReceiverStream::new)
@@ -154,8 +156,8 @@ impl HttpTask<PassthroughConnection> {
 
             Self::modify_response(&mut response, &redirector_config_clone);
 
-            let upgrade = (response.status() == StatusCode::SWITCHING_PROTOCOLS)
-                .then(|| hyper::upgrade::on(&mut response));
+            let upgrade =
+                is_upgrade(&method, response.status()).then(|| hyper::upgrade::on(&mut response));
             let _ = request.response_tx.send(response.map(BoxBody::new));
 
             match upgrade {
@@ -182,13 +184,39 @@ impl HttpTask<PassthroughConnection> {
         }
     }
 
+    /// Sends the request to its original destination.
+    ///
+    /// HTTP/2 requests are sent over the connection from [`ConnectionInfo::http2_pool`], HTTP/1
+    /// requests always get a new connection.
     async fn send_request<B>(
         info: &ConnectionInfo,
         request: Request<B>,
     ) -> Result<Response<Incoming>, ConnError>
     where
-        B: 'static + Body<Data = Bytes, Error = hyper::Error> + Send + Unpin,
+        B: 'static + Body<Data = Bytes, Error = hyper::Error> + Send + Sync + Unpin,
     {
+        let mut sender = match request.version() {
+            Version::HTTP_2 => {
+                info.http2_pool
+                    .get_or_connect(Self::connect(info, request.uri(), HttpVersion::V2))
+                    .await?
+            }
+            _ => Self::connect(info, request.uri(), HttpVersion::V1).await?,
+        };
+
+        sender
+            .send(request.map(BoxBody::new))
+            .await
+            .map_err(From::from)
+            .map_err(ConnError::PassthroughHttpError)
+    }
+
+    /// Makes a new HTTP connection to the original destination of the request.
+    async fn connect(
+        info: &ConnectionInfo,
+        uri: &Uri,
+        version: HttpVersion,
+    ) -> Result<HttpSender<BoxBody<Bytes, hyper::Error>>, ConnError> {
         let stream = TcpStream::connect(info.pass_through_address())
             .await
             .map_err(From::from)
@@ -197,7 +225,7 @@ impl HttpTask<PassthroughConnection> {
         let stream = match &info.tls_connector {
             Some(connector) => {
                 let stream = connector
-                    .connect(info.original_destination.ip(), Some(request.uri()), stream)
+                    .connect(info.original_destination.ip(), Some(uri), stream)
                     .await
                     .map_err(From::from)
                     .map_err(ConnError::TcpConnectError)?;
@@ -206,16 +234,7 @@ impl HttpTask<PassthroughConnection> {
             None => MaybeTls::NoTls(stream),
         };
 
-        let version = match request.version() {
-            Version::HTTP_2 => HttpVersion::V2,
-            _ => HttpVersion::V1,
-        };
-        let mut sender = HttpSender::new(TokioIo::new(stream), version)
-            .await
-            .map_err(From::from)
-            .map_err(ConnError::PassthroughHttpError)?;
-        sender
-            .send(request)
+        HttpSender::new(TokioIo::new(stream), version)
             .await
             .map_err(From::from)
             .map_err(ConnError::PassthroughHttpError)
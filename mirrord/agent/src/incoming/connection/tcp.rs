@@ -13,12 +13,15 @@ use tokio_stream::wrappers::BroadcastStream;
 use tokio_util::sync::CancellationToken;
 
 use super::{ConnectionInfo, IncomingIO, IncomingStream};
-use crate::incoming::{
-    ConnError, IncomingStreamItem,
-    connection::{
-        copy_bidirectional::{self, PassthroughConnection, StealingClient},
-        optional_broadcast::OptionalBroadcast,
+use crate::{
+    incoming::{
+        ConnError, IncomingStreamItem,
+        connection::{
+            copy_bidirectional::{self, PassthroughConnection, StealingClient},
+            optional_broadcast::OptionalBroadcast,
+        },
     },
+    metrics::INCOMING_TRAFFIC,
 };
 
 /// A redirected TCP connection.
@@ -59,6 +62,8 @@ impl RedirectedTcp {
     /// For the data to flow, you must start the connection task with either [`Self::steal`] or
     /// [`Self::pass_through`].
     pub fn mirror(&mut self) -> MirroredTcp {
+        INCOMING_TRAFFIC.with_label_values(&["tcp", "mirror"]).inc();
+
         let rx = match &self.mirror_tx {
             Some(tx) => tx.subscribe(),
             None => {
@@ -80,6 +85,8 @@ impl RedirectedTcp {
     /// All data will be directed to this handle.
     /// The returned [`JoinHandle`] is for the spawned IO task.
     pub fn steal(mut self, shutdown: CancellationToken) -> (StolenTcp, JoinHandle<()>) {
+        INCOMING_TRAFFIC.with_label_values(&["tcp", "steal"]).inc();
+
         let (incoming_tx, incoming_rx) = mpsc::channel(32);
         let (outgoing_tx, outgoing_rx) = mpsc::channel(32);
 
@@ -166,7 +166,9 @@ impl PortRedirector for IpTablesRedirector {
         loop {
             let (stream, source) = self.listener.accept().await?;
 
-            let destination = if source.is_ipv6() {
+            // IPv4 connections accepted on an IPv6 socket have IPv4-mapped addresses,
+            // but their original destination is still tracked as IPv4.
+            let destination = if source.ip().to_canonical().is_ipv6() {
                 socket::getsockopt(&stream, Ip6tOriginalDst)
                     .map(SockaddrIn6::from)
                     .map(|addr| SocketAddr::new(addr.ip().into(), addr.port()))
@@ -178,11 +180,7 @@ impl PortRedirector for IpTablesRedirector {
 
             match destination {
                 Ok(destination) => {
-                    break Ok(Redirected {
-                        stream,
-                        source,
-                        destination,
-                    });
+                    break Ok(Redirected::new(stream, source, destination));
                 }
                 Err(error) => {
                     // Resolving the original destination can fail,
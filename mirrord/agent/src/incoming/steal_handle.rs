@@ -12,6 +12,7 @@ use super::{
     connection::{ConnectionInfo, http::RedirectedHttp, tcp::RedirectedTcp},
     error::RedirectorTaskError,
     task::{RedirectRequest, TaskError},
+    tls::{StealTlsHandlerStore, error::StealTlsSetupError},
 };
 
 /// Handle to a running [`RedirectorTask`](super::task::RedirectorTask).
@@ -29,17 +30,34 @@ pub struct StealHandle {
     task_error: TaskError,
     /// For receiving stolen connections.
     stolen_ports: StreamMap<u16, StreamNotifyClose<ReceiverStream<StolenTraffic>>>,
+    /// Shared with the [`RedirectorTask`](super::RedirectorTask), allows for checking the TLS
+    /// setup before a port is stolen.
+    tls_store: StealTlsHandlerStore,
 }
 
 impl StealHandle {
-    pub(super) fn new(message_tx: mpsc::Sender<RedirectRequest>, task_error: TaskError) -> Self {
+    pub(super) fn new(
+        message_tx: mpsc::Sender<RedirectRequest>,
+        task_error: TaskError,
+        tls_store: StealTlsHandlerStore,
+    ) -> Self {
         Self {
             message_tx,
             task_error,
             stolen_ports: Default::default(),
+            tls_store,
         }
     }
 
+    /// Builds the [`StealTlsHandler`](super::tls::handler::StealTlsHandler) for the given port, if
+    /// the port is covered by the TLS steal config.
+    ///
+    /// Allows for failing a port subscription early when the TLS setup is invalid, e.g. when the
+    /// certificate cannot be parsed.
+    pub async fn check_tls_setup(&self, port: u16) -> Result<(), StealTlsSetupError> {
+        self.tls_store.get(port).await.map(drop)
+    }
+
     /// Issues a request to start stealing from the given port.
     ///
     /// If this port is already stolen, does nothing.
@@ -113,6 +131,22 @@ impl StolenTraffic {
             StolenTraffic::Http(conn) => conn.info(),
         }
     }
+
+    /// Passes this traffic through to its original destination.
+    pub fn pass_through(self) {
+        match self {
+            StolenTraffic::Tcp {
+                conn,
+                join_handle_tx,
+                shutdown,
+            } => {
+                join_handle_tx
+                    .send(conn.pass_through(shutdown))
+                    .expect("RedirectorTask dropped oneshot rx for receiving JoinHandle to IO task for TCP connection");
+            }
+            StolenTraffic::Http(http) => http.pass_through(),
+        }
+    }
 }
 
 impl fmt::Debug for StealHandle {
@@ -90,12 +90,12 @@ where
             ports: Default::default(),
             internal_rx,
             internal_tx,
-            tls_store,
+            tls_store: tls_store.clone(),
             config,
         };
 
         let task_error = TaskError(error_rx.shared());
-        let steal_handle = StealHandle::new(message_tx.clone(), task_error.clone());
+        let steal_handle = StealHandle::new(message_tx.clone(), task_error.clone(), tls_store);
         let mirror_handle = MirrorHandle::new(message_tx, task_error);
 
         (task, steal_handle, mirror_handle)
@@ -218,6 +218,7 @@ where
                 local_addr,
                 peer_addr: source,
                 tls_connector: None,
+                http2_pool: Default::default(),
             };
 
             let shutdown = state.shutdown.child_token();
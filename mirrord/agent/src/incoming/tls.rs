@@ -9,8 +9,8 @@ use std::{
 use error::{StealTlsSetupError, StealTlsSetupErrorInner};
 use handler::StealTlsHandler;
 use mirrord_agent_env::steal_tls::{
-    AgentClientConfig, AgentServerConfig, StealPortTlsConfig, TlsAuthentication,
-    TlsClientVerification, TlsServerVerification,
+    AgentClientConfig, AgentServerAuthentication, AgentServerConfig, InlineTlsAuthentication,
+    StealPortTlsConfig, TlsAuthentication, TlsClientVerification, TlsServerVerification,
 };
 use mirrord_tls_util::{
     DangerousNoVerifierClient, DangerousNoVerifierServer, best_effort_root_store,
@@ -161,14 +161,34 @@ impl StealTlsHandlerStore {
             None => Arc::new(NoClientAuth),
         };
 
-        let TlsAuthentication { cert_pem, key_pem } = config.authentication;
-        let cert_chain = {
-            let path = self.resolve_path(cert_pem)?;
-            mirrord_tls_util::read_cert_chain(path).await?
-        };
-        let key_der = {
-            let path = self.resolve_path(key_pem)?;
-            mirrord_tls_util::read_key_der(path).await?
+        let (cert_chain, key_der) = match config.authentication {
+            AgentServerAuthentication::Files(TlsAuthentication { cert_pem, key_pem }) => {
+                let cert_chain = {
+                    let path = self.resolve_path(cert_pem)?;
+                    mirrord_tls_util::read_cert_chain(path).await?
+                };
+                let key_der = {
+                    let path = self.resolve_path(key_pem)?;
+                    mirrord_tls_util::read_key_der(path).await?
+                };
+                (cert_chain, key_der)
+            }
+            AgentServerAuthentication::Inline(InlineTlsAuthentication {
+                cert_pem_data,
+                key_pem_data,
+            }) => {
+                let cert_chain = mirrord_tls_util::parse_cert_chain(
+                    cert_pem_data.into_bytes(),
+                    "inline certificate chain".into(),
+                )
+                .await?;
+                let key_der = mirrord_tls_util::parse_key_der(
+                    key_pem_data.into_bytes(),
+                    "inline private key".into(),
+                )
+                .await?;
+                (cert_chain, key_der)
+            }
         };
 
         let mut server_config = ServerConfig::builder()
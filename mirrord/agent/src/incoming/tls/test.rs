@@ -1,8 +1,8 @@
-use std::{fs, path::Path, sync::Arc};
+use std::{fs, ops::Not, path::Path, sync::Arc};
 
 use mirrord_agent_env::steal_tls::{
-    AgentClientConfig, AgentServerConfig, StealPortTlsConfig, TlsAuthentication,
-    TlsClientVerification, TlsServerVerification,
+    AgentClientConfig, AgentServerConfig, InlineTlsAuthentication, StealPortTlsConfig,
+    TlsAuthentication, TlsClientVerification, TlsServerVerification,
 };
 use mirrord_tls_util::generate_cert;
 use pem::{EncodeConfig, LineEnding, Pem};
@@ -52,7 +52,7 @@ impl CertChainWithKey {
         }
     }
 
-    pub fn to_file(&self, path: &Path) {
+    pub fn to_pem(&self) -> String {
         let mut pems = Vec::with_capacity(self.certs.len() + 1);
 
         pems.push(Pem::new("PRIVATE KEY", self.key.secret_der()));
@@ -60,9 +60,11 @@ impl CertChainWithKey {
             pems.push(Pem::new("CERTIFICATE", cert.as_ref()));
         }
 
-        let content =
-            pem::encode_many_config(&pems, EncodeConfig::new().set_line_ending(LineEnding::LF));
-        fs::write(path, content).unwrap();
+        pem::encode_many_config(&pems, EncodeConfig::new().set_line_ending(LineEnding::LF))
+    }
+
+    pub fn to_file(&self, path: &Path) {
+        fs::write(path, self.to_pem()).unwrap();
     }
 }
 
@@ -128,7 +130,8 @@ async fn server_authentication(
                 authentication: TlsAuthentication {
                     cert_pem: "/auth.pem".into(),
                     key_pem: "/auth.pem".into(),
-                },
+                }
+                .into(),
                 alpn_protocols: Default::default(),
                 verification: None,
             },
@@ -167,6 +170,64 @@ async fn server_authentication(
     }
 }
 
+/// Verifies that agent's TLS server can use a certificate chain and a key given inline, and that
+/// invalid PEM data fails the setup.
+#[rstest::rstest]
+#[case::valid(true)]
+#[case::invalid(false)]
+#[tokio::test]
+async fn server_authentication_inline(#[case] valid: bool) {
+    let _ = CryptoProvider::install_default(rustls::crypto::aws_lc_rs::default_provider());
+
+    let chain = CertChainWithKey::new("mirrord-agent", None);
+    let pem = chain.to_pem();
+
+    let store = StealTlsHandlerStore::new(
+        vec![StealPortTlsConfig {
+            port: 443,
+            agent_as_server: AgentServerConfig {
+                authentication: InlineTlsAuthentication {
+                    cert_pem_data: pem.clone(),
+                    key_pem_data: if valid { pem } else { "not a key".into() },
+                }
+                .into(),
+                alpn_protocols: Default::default(),
+                verification: None,
+            },
+            agent_as_client: AgentClientConfig {
+                authentication: None,
+                verification: TlsServerVerification {
+                    accept_any_cert: true,
+                    trust_roots: Default::default(),
+                },
+            },
+        }],
+        InTargetPathResolver::with_root_path(tempfile::tempdir().unwrap().path().to_path_buf()),
+    );
+
+    if valid.not() {
+        let error = store.get(443).await.unwrap_err();
+        assert!(
+            error.to_string().contains("no private key was found"),
+            "unexpected error: {error}"
+        );
+        return;
+    }
+
+    let handler = store.get(443).await.unwrap().unwrap();
+    let connector = {
+        let mut root_store = RootCertStore::empty();
+        root_store.add(chain.certs.last().unwrap().clone()).unwrap();
+
+        let client_config = ClientConfig::builder()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(client_config))
+    };
+
+    assert_can_talk(handler.acceptor(), connector, "mirrord-agent").await;
+}
+
 /// Verifies that agent's TLS server correctly verifies clients.
 #[rstest::rstest]
 #[case::known_root_accepted(false, true, false, false, true)]
@@ -201,7 +262,8 @@ async fn client_verification(
                 authentication: TlsAuthentication {
                     cert_pem: "/auth.pem".into(),
                     key_pem: "/auth.pem".into(),
-                },
+                }
+                .into(),
                 alpn_protocols: Default::default(),
                 verification: Some(TlsClientVerification {
                     allow_anonymous,
@@ -273,7 +335,8 @@ async fn client_authentication(
                 authentication: TlsAuthentication {
                     cert_pem: "/auth.pem".into(),
                     key_pem: "/auth.pem".into(),
-                },
+                }
+                .into(),
                 alpn_protocols: Default::default(),
                 verification: None,
             },
@@ -355,7 +418,8 @@ async fn server_verification(
                 authentication: TlsAuthentication {
                     cert_pem: "/auth.pem".into(),
                     key_pem: "/auth.pem".into(),
-                },
+                }
+                .into(),
                 alpn_protocols: Default::default(),
                 verification: None,
             },
@@ -484,7 +548,8 @@ impl SimpleStore {
                     authentication: TlsAuthentication {
                         cert_pem: "/auth.pem".into(),
                         key_pem: "/auth.pem".into(),
-                    },
+                    }
+                    .into(),
                     alpn_protocols: alpn_protocols
                         .iter()
                         .map(ToString::to_string)
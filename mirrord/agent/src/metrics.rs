@@ -8,7 +8,10 @@ use std::{
 
 use axum::{Router, extract::State, routing::get};
 use http::StatusCode;
-use prometheus::{GaugeVec, IntGauge, Registry, proto::MetricFamily};
+use prometheus::{
+    GaugeVec, Histogram, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Registry,
+    proto::MetricFamily,
+};
 use tokio::net::TcpListener;
 use tokio_util::sync::CancellationToken;
 use tracing::Level;
@@ -58,6 +61,138 @@ pub(crate) static BYPASSED_REQUESTS: LazyLock<prometheus::GaugeVec> = LazyLock::
     .expect("BYPASSED_REQUESTS should be valid")
 });
 
+/// Time limit (in milliseconds) of jq expression evaluation in HTTP filters, set once when the
+/// agent starts.
+pub(crate) static JAQ_TIME_LIMIT_MS: LazyLock<IntGauge> = LazyLock::new(|| {
+    prometheus::register_int_gauge!(
+        "mirrord_agent_jaq_time_limit_ms",
+        "time limit of jq expression evaluation in mirrord-agent http filters"
+    )
+    .expect("JAQ_TIME_LIMIT_MS should be valid")
+});
+
+/// Memory limit (in bytes) of jq expression evaluation in HTTP filters, set once when the agent
+/// starts.
+pub(crate) static JAQ_MEMORY_LIMIT_BYTES: LazyLock<IntGauge> = LazyLock::new(|| {
+    prometheus::register_int_gauge!(
+        "mirrord_agent_jaq_memory_limit_bytes",
+        "memory limit of jq expression evaluation in mirrord-agent http filters"
+    )
+    .expect("JAQ_MEMORY_LIMIT_BYTES should be valid")
+});
+
+/// Bytes of HTTP request bodies currently buffered for body filters, per agent client.
+///
+/// A body is accounted to every client whose filter waits for it, see
+/// [`BodyBufferBudget`](crate::http::body_budget::BodyBufferBudget).
+pub(crate) static BODY_BUFFER_BYTES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    prometheus::register_int_gauge_vec!(
+        "mirrord_agent_body_buffer_bytes",
+        "bytes of http request bodies currently buffered by mirrord-agent for body filters",
+        &["client_id"]
+    )
+    .expect("BODY_BUFFER_BYTES should be valid")
+});
+
+/// Decisions of HTTP body filters made without the full request body (the filter's fallback
+/// was used), by the reason why the body was not buffered.
+pub(crate) static BODY_FILTER_FALLBACKS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    prometheus::register_int_counter_vec!(
+        "mirrord_agent_body_filter_fallback_count",
+        "amount of http body filter decisions made by mirrord-agent without the full request body",
+        &["reason"]
+    )
+    .expect("BODY_FILTER_FALLBACKS should be valid")
+});
+
+/// Failed evaluations of HTTP filters, by the class of the error (see
+/// [`JaqError::class`](crate::http::safe_jaq::JaqError::class)).
+pub(crate) static HTTP_FILTER_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    prometheus::register_int_counter_vec!(
+        "mirrord_agent_http_filter_error_count",
+        "amount of failed http filter evaluations in mirrord-agent",
+        &["class"]
+    )
+    .expect("HTTP_FILTER_ERRORS should be valid")
+});
+
+/// Total amount of client sessions started in the agent, see [`CLIENT_COUNT`] for the ones
+/// currently connected.
+pub(crate) static CLIENT_SESSIONS: LazyLock<IntCounter> = LazyLock::new(|| {
+    prometheus::register_int_counter!(
+        "mirrord_agent_client_session_count",
+        "total amount of client sessions started in mirrord-agent"
+    )
+    .expect("CLIENT_SESSIONS should be valid")
+});
+
+/// Incoming TCP connections and HTTP requests handed to agent clients, by `protocol` (`tcp` or
+/// `http`) and `mode` (`steal` or `mirror`).
+///
+/// A connection mirrored by multiple clients is counted once per client.
+pub(crate) static INCOMING_TRAFFIC: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    prometheus::register_int_counter_vec!(
+        "mirrord_agent_incoming_traffic_count",
+        "amount of incoming tcp connections and http requests stolen or mirrored by mirrord-agent",
+        &["protocol", "mode"]
+    )
+    .expect("INCOMING_TRAFFIC should be valid")
+});
+
+/// Bytes forwarded on redirected incoming connections, by `direction`: `inbound` for the bytes
+/// received from the remote peer, `outbound` for the bytes sent back to it.
+pub(crate) static FORWARDED_BYTES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    prometheus::register_int_counter_vec!(
+        "mirrord_agent_forwarded_bytes",
+        "amount of bytes forwarded on incoming connections redirected by mirrord-agent",
+        &["direction"]
+    )
+    .expect("FORWARDED_BYTES should be valid")
+});
+
+/// Mirrored connections and HTTP requests dropped because of `agent.mirror_bandwidth_limit`, see
+/// [`MirrorBandwidthLimiter`](crate::mirror::bandwidth::MirrorBandwidthLimiter).
+pub(crate) static MIRROR_BANDWIDTH_DROPS: LazyLock<IntCounter> = LazyLock::new(|| {
+    prometheus::register_int_counter!(
+        "mirrord_agent_mirror_bandwidth_drop_count",
+        "amount of mirrored tcp connections and http requests dropped by mirrord-agent bandwidth limit"
+    )
+    .expect("MIRROR_BANDWIDTH_DROPS should be valid")
+});
+
+/// Decisions of HTTP filters, by `decision` (`match`, `miss` or `close`, see
+/// [`FilterDecision`](crate::http::filter::FilterDecision)).
+pub(crate) static HTTP_FILTER_DECISIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    prometheus::register_int_counter_vec!(
+        "mirrord_agent_http_filter_decision_count",
+        "amount of http filter decisions made by mirrord-agent",
+        &["decision"]
+    )
+    .expect("HTTP_FILTER_DECISIONS should be valid")
+});
+
+/// Stolen connections and HTTP requests passed through before evaluating the subscriptions, by
+/// `reason` (`local` or `health_check`, see
+/// [`StealExclusions`](crate::steal::StealExclusions)).
+pub(crate) static STEAL_EXCLUSIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    prometheus::register_int_counter_vec!(
+        "mirrord_agent_steal_exclusion_count",
+        "amount of stolen tcp connections and http requests passed through by mirrord-agent exclusions",
+        &["reason"]
+    )
+    .expect("STEAL_EXCLUSIONS should be valid")
+});
+
+/// Time it takes to evaluate an HTTP filter against a request, in seconds.
+pub(crate) static HTTP_FILTER_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+    prometheus::register_histogram!(
+        "mirrord_agent_http_filter_duration_seconds",
+        "time of http filter evaluations in mirrord-agent",
+        prometheus::exponential_buckets(0.0001, 4.0, 8).expect("buckets should be valid")
+    )
+    .expect("HTTP_FILTER_DURATION should be valid")
+});
+
 /// Convenience trait for static metrics variables.
 ///
 /// We store them as [`AtomicUsize`], which is the correct type (they're all counters).
@@ -384,10 +519,31 @@ impl Drop for MetricGuard {
 mod tests {
     use std::{sync::atomic::Ordering, time::Duration};
 
+    use futures::StreamExt;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
     use tokio_util::sync::CancellationToken;
 
     use super::OPEN_FD_COUNT;
-    use crate::metrics::start_metrics;
+    use crate::{
+        incoming::{
+            IncomingStreamItem, MirroredTraffic, RedirectorTask, RedirectorTaskConfig,
+            test::DummyRedirector,
+        },
+        metrics::start_metrics,
+    };
+
+    /// Finds the value of the metric `name` with the given `label` (`key="value"`) in the
+    /// prometheus text format.
+    fn metric_value(metrics: &str, name: &str, label: &str) -> f64 {
+        metrics
+            .lines()
+            .filter(|line| line.starts_with(&format!("{name}{{")) && line.contains(label))
+            .find_map(|line| line.rsplit_once(' ')?.1.parse().ok())
+            .unwrap_or_default()
+    }
 
     #[tokio::test]
     async fn test_metrics() {
@@ -419,4 +575,87 @@ mod tests {
 
         cancellation_token.drop_guard();
     }
+
+    /// Scrapes the metrics server during a small mirrored session, a TCP connection passed
+    /// through to its original destination while being mirrored.
+    #[tokio::test]
+    async fn test_mirrored_session_metrics() {
+        let metrics_address = "127.0.0.1:9001".parse().unwrap();
+        let cancellation_token = CancellationToken::new();
+
+        let metrics_cancellation = cancellation_token.child_token();
+        tokio::spawn(async move {
+            start_metrics(metrics_address, metrics_cancellation)
+                .await
+                .unwrap()
+        });
+
+        let (redirector, _state, mut conn_tx) = DummyRedirector::new();
+        let (task, _steal_handle, mut mirror_handle) = RedirectorTask::new(
+            redirector,
+            Default::default(),
+            RedirectorTaskConfig::from_env(),
+        );
+        tokio::spawn(task.run());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let destination = listener.local_addr().unwrap();
+        mirror_handle.mirror(destination.port()).await.unwrap();
+
+        let mut conn = conn_tx.make_connection(destination).await;
+        conn.write_all(b"def not http\r\n\r\n").await.unwrap();
+
+        let MirroredTraffic::Tcp(mut mirrored) = mirror_handle.next().await.unwrap().unwrap()
+        else {
+            panic!("falsely detected HTTP traffic");
+        };
+
+        let (mut original, _) = listener.accept().await.unwrap();
+        let mut buf = [0; 16];
+        original.read_exact(&mut buf).await.unwrap();
+        original.write_all(b"pong").await.unwrap();
+        conn.read_exact(&mut buf[..4]).await.unwrap();
+        assert_eq!(&buf[..4], b"pong");
+
+        let Some(IncomingStreamItem::Data(data)) = mirrored.stream.next().await else {
+            panic!("expected mirrored data");
+        };
+        assert_eq!(data.as_ref(), b"def not http\r\n\r\n");
+
+        // Give the server some time to start.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let get_all_metrics = reqwest::get("http://127.0.0.1:9001/metrics")
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        assert!(
+            metric_value(
+                &get_all_metrics,
+                "mirrord_agent_incoming_traffic_count",
+                r#"mode="mirror""#
+            ) > 0.0
+        );
+        assert!(
+            metric_value(
+                &get_all_metrics,
+                "mirrord_agent_forwarded_bytes",
+                r#"direction="inbound""#
+            ) >= 16.0
+        );
+        assert!(
+            metric_value(
+                &get_all_metrics,
+                "mirrord_agent_forwarded_bytes",
+                r#"direction="outbound""#
+            ) >= 4.0
+        );
+
+        cancellation_token.drop_guard();
+    }
 }
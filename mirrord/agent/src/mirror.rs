@@ -2,13 +2,14 @@ use std::{
     collections::{HashMap, VecDeque},
     error::Report,
     ops::{Not, RangeInclusive},
+    time::{Duration, Instant},
 };
 
 use futures::StreamExt;
 use mirrord_protocol::{
     ConnectionId, DaemonMessage, LogMessage, Port, RequestId,
     tcp::{
-        ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, DaemonTcp,
+        ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, DaemonTcp, HttpFilterReport,
         HttpRequestMetadata, IncomingTrafficTransportType, InternalHttpBodyNew,
         InternalHttpRequest, LayerTcp, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV1,
         NewTcpConnectionV2, TcpClose, TcpData,
@@ -21,14 +22,18 @@ use tracing::{Level, instrument};
 use crate::{
     AgentError,
     error::AgentResult,
-    http::filter::HttpFilter,
+    http::filter::{DryRunFilter, HttpFilter},
     incoming::{
         IncomingStream, IncomingStreamItem, MirrorHandle, MirroredHttp, MirroredTraffic,
         RedirectorTaskError,
     },
+    metrics::MIRROR_BANDWIDTH_DROPS,
+    mirror::bandwidth::MirrorBandwidthLimiter,
     util::protocol_version::ClientProtocolVersion,
 };
 
+pub mod bandwidth;
+
 /// Agent client's API for using the TCP mirror feature.
 ///
 /// Wrapper over a [`MirrorHandle`].
@@ -39,7 +44,23 @@ pub struct TcpMirrorApi {
     connection_ids_iter: RangeInclusive<ConnectionId>,
     queued_messages: VecDeque<DaemonTcp>,
     port_filters: HashMap<Port, HttpFilter>,
+    /// Filters of the [`LayerTcp::PortSubscribeDryRun`] subscriptions.
+    dry_run_filters: HashMap<Port, DryRunFilter>,
     ongoing_requests: JoinSet<MirroredHttp>,
+    /// Limits the mirrored traffic of all clients, see `agent.mirror_bandwidth_limit`.
+    bandwidth: Option<MirrorBandwidthLimiter>,
+    /// Connections and requests of this client dropped because of [`TcpMirrorApi::bandwidth`].
+    bandwidth_drops: u64,
+    /// When the client was last warned about [`TcpMirrorApi::bandwidth_drops`].
+    bandwidth_warned_at: Option<Instant>,
+}
+
+/// Item produced by [`TcpMirrorApi::next`].
+#[derive(Debug)]
+enum MirrorEvent {
+    Traffic(MirroredTraffic),
+    /// A request to a [`LayerTcp::PortSubscribeDryRun`] port was evaluated.
+    Report(HttpFilterReport),
 }
 
 impl TcpMirrorApi {
@@ -51,7 +72,15 @@ impl TcpMirrorApi {
     /// Since `mirrord-intproxy` processes requests independently, this is fine.
     const REQUEST_ID: RequestId = 0;
 
-    pub fn new(mirror_handle: MirrorHandle, protocol_version: ClientProtocolVersion) -> Self {
+    /// The client is warned about the connections dropped because of the bandwidth limit at most
+    /// this often.
+    const BANDWIDTH_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+    pub fn new(
+        mirror_handle: MirrorHandle,
+        protocol_version: ClientProtocolVersion,
+        bandwidth: Option<MirrorBandwidthLimiter>,
+    ) -> Self {
         Self {
             mirror_handle,
             incoming_streams: Default::default(),
@@ -59,10 +88,43 @@ impl TcpMirrorApi {
             connection_ids_iter: 0..=ConnectionId::MAX,
             queued_messages: Default::default(),
             port_filters: Default::default(),
+            dry_run_filters: Default::default(),
             ongoing_requests: Default::default(),
+            bandwidth,
+            bandwidth_drops: 0,
+            bandwidth_warned_at: None,
         }
     }
 
+    /// Takes `bytes` from the bandwidth limit, returns `false` if they exceed it.
+    fn take_bandwidth(&self, bytes: u64) -> bool {
+        self.bandwidth
+            .as_ref()
+            .is_none_or(|bandwidth| bandwidth.try_take(bytes))
+    }
+
+    /// Counts a connection or request dropped because of the bandwidth limit, returns a warning
+    /// for the client, unless one was sent in the last [`Self::BANDWIDTH_WARNING_INTERVAL`].
+    fn bandwidth_drop(&mut self) -> Option<DaemonMessage> {
+        self.bandwidth_drops += 1;
+        MIRROR_BANDWIDTH_DROPS.inc();
+
+        let now = Instant::now();
+        if self
+            .bandwidth_warned_at
+            .is_some_and(|warned_at| now - warned_at < Self::BANDWIDTH_WARNING_INTERVAL)
+        {
+            return None;
+        }
+        self.bandwidth_warned_at = Some(now);
+
+        Some(DaemonMessage::LogMessage(LogMessage::warn(format!(
+            "{} mirrored connections were dropped so far, because the mirrored traffic exceeded \
+            `agent.mirror_bandwidth_limit`",
+            self.bandwidth_drops
+        ))))
+    }
+
     pub async fn handle_client_message(&mut self, message: LayerTcp) -> AgentResult<()> {
         match message {
             LayerTcp::ConnectionUnsubscribe(id) => {
@@ -84,8 +146,19 @@ impl TcpMirrorApi {
                 self.queued_messages
                     .push_back(DaemonTcp::SubscribeResult(Ok(port)));
             }
+            LayerTcp::PortSubscribeDryRun(port, filter) => {
+                let agent_filter = DryRunFilter::try_from(&filter)
+                    .map_err(Box::new)
+                    .map_err(AgentError::InvalidHttpFilter)?;
+
+                self.mirror_handle.mirror(port).await?;
+                self.dry_run_filters.insert(port, agent_filter);
+                self.queued_messages
+                    .push_back(DaemonTcp::SubscribeResult(Ok(port)));
+            }
             LayerTcp::PortUnsubscribe(port) => {
                 self.port_filters.remove(&port);
+                self.dry_run_filters.remove(&port);
                 self.mirror_handle.stop_mirror(port);
             }
         }
@@ -93,13 +166,42 @@ impl TcpMirrorApi {
         Ok(())
     }
 
+    /// Evaluates the filter of a [`LayerTcp::PortSubscribeDryRun`] subscription on the given
+    /// request.
+    async fn report(filter: &DryRunFilter, http: &mut MirroredHttp) -> HttpFilterReport {
+        let port = http.info.original_destination.port();
+        let (parts, body) = http.parts_and_body();
+        let evaluation = filter.evaluate(parts, body).await;
+
+        HttpFilterReport {
+            port,
+            method: parts.method.to_string(),
+            path: parts
+                .uri
+                .path_and_query()
+                .map(|path| path.as_str())
+                .unwrap_or_else(|| parts.uri.path())
+                .to_owned(),
+            matched: evaluation.matched,
+            matched_filter: evaluation.matched_filter,
+            error: evaluation.error,
+            duration_us: evaluation
+                .duration
+                .as_micros()
+                .try_into()
+                .unwrap_or(u64::MAX),
+        }
+    }
+
     #[instrument(level = Level::TRACE, ret)]
     async fn next(
         handle: &mut MirrorHandle,
         ongoing: &mut JoinSet<MirroredHttp>,
         version: &ClientProtocolVersion,
         filters: &HashMap<Port, HttpFilter>,
-    ) -> Result<MirroredTraffic, RedirectorTaskError> {
+        dry_run_filters: &HashMap<Port, DryRunFilter>,
+    ) -> Result<MirrorEvent, RedirectorTaskError> {
+        use MirrorEvent as E;
         use MirroredTraffic as M;
         loop {
             return tokio::select! {
@@ -113,14 +215,18 @@ impl TcpMirrorApi {
                             }
 
                             let port = http.info.original_destination.port();
+                            if let Some(filter) = dry_run_filters.get(&port) {
+                                return Ok(E::Report(Self::report(filter, &mut http).await));
+                            }
+
                             let (parts, body) = http.parts_and_body();
                             let Some(filter) = filters.get(&port) else {
                                 tracing::warn!("have no filter for request with buffered body.");
-                                return Ok(M::Http(http));
+                                return Ok(E::Traffic(M::Http(http)));
                             };
 
                             if filter.matches(parts, body).await {
-                                Ok(M::Http(http))
+                                Ok(E::Traffic(M::Http(http)))
                             } else {
                                 continue
                             }
@@ -137,22 +243,41 @@ impl TcpMirrorApi {
                 Some(next) = handle.next() => {
                     match next? {
                         M::Tcp(tcp) => {
-                            if filters.contains_key(&tcp.info.original_destination.port()) {
+                            let port = tcp.info.original_destination.port();
+                            if filters.contains_key(&port) || dry_run_filters.contains_key(&port) {
                                 continue
                             } else {
-                                Ok(M::Tcp(tcp))
+                                Ok(E::Traffic(M::Tcp(tcp)))
                             }
                         }
 
+                        M::Http(mut http) if dry_run_filters.contains_key(&http.info.original_destination.port()) => {
+                            let Some(filter) = dry_run_filters.get(&http.info.original_destination.port()) else {
+                                continue
+                            };
+
+                            if filter.needs_body() && http.request_head.body_finished.not() {
+                                ongoing.spawn(async move {
+                                    if let Err(error) = http.buffer_body().await {
+                                        tracing::debug!(?error, "failed to buffer request body");
+                                    };
+                                    http
+                                });
+                                continue
+                            }
+
+                            Ok(E::Report(Self::report(filter, &mut http).await))
+                        }
+
                         M::Http(mut http) if version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
                             let port = http.info.original_destination.port();
                             let (parts, body) = http.parts_and_body();
                             let Some(filter) = filters.get(&port) else {
-                                return Ok(M::Http(http));
+                                return Ok(E::Traffic(M::Http(http)));
                             };
 
                             if filter.matches(parts, body).await {
-                                return Ok(M::Http(http));
+                                return Ok(E::Traffic(M::Http(http)));
                             }
 
                             if filter.needs_body() {
@@ -167,7 +292,7 @@ impl TcpMirrorApi {
                         }
 
                         M::Http(http) => {
-                            Ok(M::Http(http))
+                            Ok(E::Traffic(M::Http(http)))
                         }
                     }
                 }
@@ -181,142 +306,190 @@ impl TcpMirrorApi {
             return Ok(DaemonMessage::Tcp(message));
         }
 
-        let message = tokio::select! {
-            Some((id, item)) = self.incoming_streams.next() => match item {
-                IncomingStreamItem::Data(data) => DaemonTcp::Data(TcpData {
-                    connection_id: id,
-                    bytes: data.into(),
-                }),
-                IncomingStreamItem::NoMoreData => DaemonTcp::Data(TcpData {
-                    connection_id: id,
-                    bytes: Default::default(),
-                }),
-                IncomingStreamItem::Frame(frame) => {
-                    DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
-                        frames: vec![frame],
-                        is_last: false,
+        loop {
+            let message = tokio::select! {
+                Some((id, item)) = self.incoming_streams.next() => match item {
+                    // Whole connections are dropped, so that the client never misses bytes in the
+                    // middle of a stream.
+                    item if stream_item_len(&item).is_some_and(|bytes| self.take_bandwidth(bytes).not()) => {
+                        self.incoming_streams.remove(&id);
+                        let close = DaemonTcp::Close(TcpClose { connection_id: id });
+                        match self.bandwidth_drop() {
+                            Some(warning) => {
+                                self.queued_messages.push_back(close);
+                                return Ok(warning);
+                            }
+                            None => close,
+                        }
+                    }
+                    IncomingStreamItem::Data(data) => DaemonTcp::Data(TcpData {
                         connection_id: id,
-                        request_id: Self::REQUEST_ID,
-                    }))
-                }
-                IncomingStreamItem::NoMoreFrames => {
-                    DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
-                        frames: Default::default(),
-                        is_last: true,
+                        bytes: data.into(),
+                    }),
+                    IncomingStreamItem::NoMoreData => DaemonTcp::Data(TcpData {
                         connection_id: id,
-                        request_id: Self::REQUEST_ID,
-                    }))
-                }
-                IncomingStreamItem::Finished(Ok(())) => {
-                    DaemonTcp::Close(TcpClose { connection_id: id })
-                }
-                IncomingStreamItem::Finished(Err(error)) => {
-                    self.queued_messages.push_back(DaemonTcp::Close(TcpClose { connection_id: id }));
-                    return Ok(DaemonMessage::LogMessage(LogMessage::warn(format!(
-                        "Mirrored connection {id} failed: {}",
-                        Report::new(error)
-                    ))));
-                }
-            },
+                        bytes: Default::default(),
+                    }),
+                    IncomingStreamItem::Frame(frame) => {
+                        DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
+                            frames: vec![frame],
+                            is_last: false,
+                            connection_id: id,
+                            request_id: Self::REQUEST_ID,
+                        }))
+                    }
+                    IncomingStreamItem::NoMoreFrames => {
+                        DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
+                            frames: Default::default(),
+                            is_last: true,
+                            connection_id: id,
+                            request_id: Self::REQUEST_ID,
+                        }))
+                    }
+                    IncomingStreamItem::Finished(Ok(())) => {
+                        DaemonTcp::Close(TcpClose { connection_id: id })
+                    }
+                    IncomingStreamItem::Finished(Err(error)) => {
+                        self.queued_messages.push_back(DaemonTcp::Close(TcpClose { connection_id: id }));
+                        return Ok(DaemonMessage::LogMessage(LogMessage::warn(format!(
+                            "Mirrored connection {id} failed: {}",
+                            Report::new(error)
+                        ))));
+                    }
+                },
 
-            traffic = Self::next(&mut self.mirror_handle, &mut self.ongoing_requests, &self.protocol_version, &self.port_filters) => match traffic? {
-                MirroredTraffic::Tcp(tcp) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
-                    let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
-                    let connection = NewTcpConnectionV1 {
-                        connection_id: id,
-                        remote_address: tcp.info.peer_addr.ip(),
-                        destination_port: tcp.info.original_destination.port(),
-                        source_port: tcp.info.peer_addr.port(),
-                        local_address: tcp.info.local_addr.ip(),
-                    };
-                    let message = NewTcpConnectionV2 {
-                        connection,
-                        transport: tcp
-                            .info
-                            .tls_connector
-                            .map(|tls| IncomingTrafficTransportType::Tls {
-                                alpn_protocol: tls.alpn_protocol().map(From::from),
-                                server_name: tls.server_name().map(|s| s.to_str().into_owned()),
-                            })
-                            .unwrap_or(IncomingTrafficTransportType::Tcp),
-                    };
-                    self.incoming_streams.insert(id, tcp.stream);
-                    DaemonTcp::NewConnectionV2(message)
-                }
+                event = Self::next(&mut self.mirror_handle, &mut self.ongoing_requests, &self.protocol_version, &self.port_filters, &self.dry_run_filters) => match event? {
+                    MirrorEvent::Report(report) => DaemonTcp::HttpFilterReport(report),
 
-                MirroredTraffic::Tcp(tcp) => {
-                    if tcp.info.tls_connector.is_some() {
-                        return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
-                            "A TLS connection was not mirrored due to mirrord-protocol version requirement: {}",
-                            &*MODE_AGNOSTIC_HTTP_REQUESTS,
-                        ))));
+                    MirrorEvent::Traffic(traffic) if self.take_bandwidth(traffic_len(&traffic)).not() => {
+                        match self.bandwidth_drop() {
+                            Some(warning) => return Ok(warning),
+                            None => continue,
+                        }
                     }
 
-                    if self.port_filters.contains_key(&tcp.info.original_destination.port()) {
-                        return Ok(DaemonMessage::LogMessage(LogMessage::warn(
-                            "TCP traffic skipped due to HTTP filter on this port".to_string()
-                        )));
+                    MirrorEvent::Traffic(MirroredTraffic::Tcp(tcp)) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
+                        let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
+                        let connection = NewTcpConnectionV1 {
+                            connection_id: id,
+                            remote_address: tcp.info.peer_addr.ip(),
+                            destination_port: tcp.info.original_destination.port(),
+                            source_port: tcp.info.peer_addr.port(),
+                            local_address: tcp.info.local_addr.ip(),
+                        };
+                        let message = NewTcpConnectionV2 {
+                            connection,
+                            transport: tcp
+                                .info
+                                .tls_connector
+                                .map(|tls| IncomingTrafficTransportType::Tls {
+                                    alpn_protocol: tls.alpn_protocol().map(From::from),
+                                    server_name: tls.server_name().map(|s| s.to_str().into_owned()),
+                                })
+                                .unwrap_or(IncomingTrafficTransportType::Tcp),
+                        };
+                        self.incoming_streams.insert(id, tcp.stream);
+                        DaemonTcp::NewConnectionV2(message)
                     }
 
-                    let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
-                    self.incoming_streams.insert(id, tcp.stream);
+                    MirrorEvent::Traffic(MirroredTraffic::Tcp(tcp)) => {
+                        if tcp.info.tls_connector.is_some() {
+                            return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
+                                "A TLS connection was not mirrored due to mirrord-protocol version requirement: {}",
+                                &*MODE_AGNOSTIC_HTTP_REQUESTS,
+                            ))));
+                        }
 
-                    let message = NewTcpConnectionV1 {
-                        connection_id: id,
-                        remote_address: tcp.info.peer_addr.ip(),
-                        destination_port: tcp.info.original_destination.port(),
-                        source_port: tcp.info.peer_addr.port(),
-                        local_address: tcp.info.local_addr.ip(),
-                    };
-                    DaemonTcp::NewConnectionV1(message)
-                }
+                        if self.port_filters.contains_key(&tcp.info.original_destination.port()) {
+                            return Ok(DaemonMessage::LogMessage(LogMessage::warn(
+                                "TCP traffic skipped due to HTTP filter on this port".to_string()
+                            )));
+                        }
 
-                MirroredTraffic::Http(http) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
-                    let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
+                        let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
+                        self.incoming_streams.insert(id, tcp.stream);
 
-                    self.incoming_streams.insert(id, http.stream);
+                        let message = NewTcpConnectionV1 {
+                            connection_id: id,
+                            remote_address: tcp.info.peer_addr.ip(),
+                            destination_port: tcp.info.original_destination.port(),
+                            source_port: tcp.info.peer_addr.port(),
+                            local_address: tcp.info.local_addr.ip(),
+                        };
+                        DaemonTcp::NewConnectionV1(message)
+                    }
 
-                    let message = ChunkedRequestStartV2 {
-                        connection_id: id,
-                        request_id: Self::REQUEST_ID,
-                        metadata: HttpRequestMetadata::V1 {
-                            source: http.info.peer_addr,
-                            destination: http.info.original_destination,
-                        },
-                        transport: http
-                            .info
-                            .tls_connector
-                            .as_ref()
-                            .map(|tls| IncomingTrafficTransportType::Tls {
-                                alpn_protocol: tls.alpn_protocol().map(From::from),
-                                server_name: tls.server_name().map(|s| s.to_str().into_owned()),
-                            })
-                            .unwrap_or(IncomingTrafficTransportType::Tcp),
-                        request: InternalHttpRequest {
-                            method: http.request_head.parts.method,
-                            uri: http.request_head.parts.uri,
-                            headers: http.request_head.parts.headers,
-                            version: http.request_head.parts.version,
-                            body: InternalHttpBodyNew {
-                                frames: http.request_head.body_head,
-                                is_last: http.request_head.body_finished,
+                    MirrorEvent::Traffic(MirroredTraffic::Http(http)) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
+                        let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
+
+                        self.incoming_streams.insert(id, http.stream);
+
+                        let message = ChunkedRequestStartV2 {
+                            connection_id: id,
+                            request_id: Self::REQUEST_ID,
+                            metadata: HttpRequestMetadata::V1 {
+                                source: http.info.peer_addr,
+                                destination: http.info.original_destination,
                             },
-                        },
-                    };
-                    DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(message))
-                }
+                            transport: http
+                                .info
+                                .tls_connector
+                                .as_ref()
+                                .map(|tls| IncomingTrafficTransportType::Tls {
+                                    alpn_protocol: tls.alpn_protocol().map(From::from),
+                                    server_name: tls.server_name().map(|s| s.to_str().into_owned()),
+                                })
+                                .unwrap_or(IncomingTrafficTransportType::Tcp),
+                            request: InternalHttpRequest {
+                                method: http.request_head.parts.method,
+                                uri: http.request_head.parts.uri,
+                                headers: http.request_head.parts.headers,
+                                version: http.request_head.parts.version,
+                                body: InternalHttpBodyNew {
+                                    frames: http.request_head.body_head,
+                                    is_last: http.request_head.body_finished,
+                                },
+                            },
+                        };
+                        DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(message))
+                    }
 
-                MirroredTraffic::Http(..) => {
-                    return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
-                        "An HTTP request was not mirrored due to mirrord-protocol version requirement: {}",
-                        &*MODE_AGNOSTIC_HTTP_REQUESTS,
-                    ))));
-                }
-            },
+                    MirrorEvent::Traffic(MirroredTraffic::Http(..)) => {
+                        return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
+                            "An HTTP request was not mirrored due to mirrord-protocol version requirement: {}",
+                            &*MODE_AGNOSTIC_HTTP_REQUESTS,
+                        ))));
+                    }
+                },
 
-            else => std::future::pending().await,
-        };
+                else => std::future::pending().await,
+            };
+
+            return Ok(DaemonMessage::Tcp(message));
+        }
+    }
+}
+
+/// Bytes of the given item sent to the client, [`None`] if it carries no data.
+fn stream_item_len(item: &IncomingStreamItem) -> Option<u64> {
+    match item {
+        IncomingStreamItem::Data(data) => Some(data.len() as u64),
+        IncomingStreamItem::Frame(frame) => Some(bandwidth::frame_len(frame)),
+        IncomingStreamItem::NoMoreData
+        | IncomingStreamItem::NoMoreFrames
+        | IncomingStreamItem::Finished(..) => None,
+    }
+}
 
-        Ok(DaemonMessage::Tcp(message))
+/// Bytes of the body sent to the client together with the new connection or request.
+fn traffic_len(traffic: &MirroredTraffic) -> u64 {
+    match traffic {
+        MirroredTraffic::Tcp(..) => 0,
+        MirroredTraffic::Http(http) => http
+            .request_head
+            .body_head
+            .iter()
+            .map(bandwidth::frame_len)
+            .sum(),
     }
 }
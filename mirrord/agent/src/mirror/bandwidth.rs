@@ -0,0 +1,172 @@
+//! Bandwidth limit of the mirrored traffic sent to the clients, see [`MirrorBandwidthLimiter`].
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use mirrord_protocol::tcp::InternalHttpBodyFrame;
+
+/// Token bucket that refills with `rate` bytes per second, up to `burst` bytes.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: u64,
+    burst: u64,
+    tokens: u64,
+    /// Time up to which the tokens were added to the bucket.
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// Starts with a full bucket.
+    fn new(rate: u64, burst: u64, now: Instant) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            refilled_at: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        let new_tokens = elapsed.as_nanos() * u128::from(self.rate) / 1_000_000_000;
+
+        if self.tokens as u128 + new_tokens >= u128::from(self.burst) {
+            self.tokens = self.burst;
+            self.refilled_at = now;
+        } else if new_tokens > 0 {
+            self.tokens += new_tokens as u64;
+            // Only the time that produced whole tokens is consumed, so that frequent refills do
+            // not lose the fractions.
+            let nanos = new_tokens * 1_000_000_000 / u128::from(self.rate);
+            self.refilled_at += Duration::from_nanos(nanos as u64);
+        }
+    }
+
+    /// Takes `amount` tokens, if the bucket has that many.
+    ///
+    /// An empty bucket refuses everything, even empty amounts, so that no new connections are
+    /// mirrored when the limit is already exhausted.
+    fn try_take(&mut self, amount: u64, now: Instant) -> bool {
+        self.refill(now);
+
+        if self.tokens == 0 || self.tokens < amount {
+            return false;
+        }
+
+        self.tokens -= amount;
+        true
+    }
+}
+
+/// Bandwidth limit of the mirrored traffic, shared by all clients of the agent, see
+/// `agent.mirror_bandwidth_limit`.
+///
+/// Stolen traffic is never accounted here.
+#[derive(Clone, Debug)]
+pub struct MirrorBandwidthLimiter {
+    bucket: Arc<Mutex<TokenBucket>>,
+}
+
+impl MirrorBandwidthLimiter {
+    /// Limits the traffic to `rate` bytes per second, allowing `burst` bytes at once after being
+    /// idle.
+    pub fn new(rate: u64, burst: u64) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(TokenBucket::new(rate, burst, Instant::now()))),
+        }
+    }
+
+    /// Accounts `bytes` of mirrored traffic, returns `false` if they exceed the limit, in which
+    /// case the whole connection should be dropped.
+    pub fn try_take(&self, bytes: u64) -> bool {
+        self.bucket
+            .lock()
+            .expect("bandwidth lock poisoned")
+            .try_take(bytes, Instant::now())
+    }
+}
+
+/// Size of the given HTTP body frame, as accounted by the [`MirrorBandwidthLimiter`].
+pub fn frame_len(frame: &InternalHttpBodyFrame) -> u64 {
+    match frame {
+        InternalHttpBodyFrame::Data(data) => data.len() as u64,
+        InternalHttpBodyFrame::Trailers(trailers) => trailers
+            .iter()
+            .map(|(name, value)| (name.as_str().len() + value.len()) as u64)
+            .sum(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        ops::Not,
+        time::{Duration, Instant},
+    };
+
+    use super::TokenBucket;
+
+    #[test]
+    fn burst_then_rate() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000, 5000, start);
+
+        // The whole burst is available at once.
+        assert!(bucket.try_take(3000, start));
+        assert!(bucket.try_take(2000, start));
+        assert!(bucket.try_take(1, start).not());
+
+        // Then only what was refilled.
+        let later = start + Duration::from_millis(500);
+        assert!(bucket.try_take(501, later).not());
+        assert!(bucket.try_take(500, later));
+        assert!(bucket.try_take(1, later).not());
+    }
+
+    #[test]
+    fn refill_is_capped_at_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000, 2000, start);
+        assert!(bucket.try_take(2000, start));
+
+        let much_later = start + Duration::from_secs(60);
+        assert!(bucket.try_take(2001, much_later).not());
+        assert!(bucket.try_take(2000, much_later));
+    }
+
+    #[test]
+    fn bigger_than_burst_never_passes() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000, 1000, start);
+
+        assert!(bucket.try_take(1001, start).not());
+        // Refused amounts are not taken.
+        assert!(bucket.try_take(1000, start));
+    }
+
+    #[test]
+    fn empty_bucket_refuses_new_connections() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000, 1000, start);
+
+        assert!(bucket.try_take(0, start));
+        assert!(bucket.try_take(1000, start));
+        assert!(bucket.try_take(0, start).not());
+        assert!(bucket.try_take(0, start + Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn frequent_refills_keep_fractions() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(3, 10, start);
+        assert!(bucket.try_take(10, start));
+
+        // A third of a second produces one token, none is lost to rounding.
+        for i in 1..=30 {
+            bucket.refill(start + Duration::from_millis(100 * i));
+        }
+        assert!(bucket.try_take(9, start + Duration::from_secs(3)));
+    }
+}
@@ -1,4 +1,4 @@
-use mirrord_protocol::{LogMessage, Port};
+use mirrord_protocol::{ClientIdentity, LogMessage, Port, ResponseError};
 use tokio::sync::mpsc::Sender;
 
 use crate::{
@@ -8,12 +8,14 @@ use crate::{
 };
 
 mod api;
+mod exclusions;
 mod subscriptions;
 mod task;
 #[cfg(test)]
 mod test;
 
 pub use api::TcpStealerApi;
+pub use exclusions::StealExclusions;
 pub use task::TcpStealerTask;
 
 /// Commands from the agent that are passed down to the stealer worker, through [`TcpStealerApi`].
@@ -26,6 +28,10 @@ enum Command {
     /// [`TcpStealerApi`].
     NewClient(Sender<StealerMessage>, ClientProtocolVersion),
 
+    /// The client told us who is running it, used to report conflicting port subscriptions to
+    /// other clients.
+    ClientIdentity(ClientIdentity),
+
     /// The layer wants to subscribe to this [`Port`].
     ///
     /// The agent starts stealing traffic from this [`Port`].
@@ -52,4 +58,5 @@ enum StealerMessage {
     StolenHttp(StolenHttp),
     Log(LogMessage),
     PortSubscribed(Port),
+    PortSubscribeFailed(ResponseError),
 }
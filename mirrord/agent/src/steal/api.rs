@@ -11,7 +11,7 @@ use futures::{StreamExt, stream::FuturesUnordered};
 use http_body_util::{BodyExt, combinators::BoxBody};
 use hyper::{Response, body::Frame};
 use mirrord_protocol::{
-    ConnectionId, DaemonMessage, LogMessage, Payload, RequestId,
+    ClientIdentity, ConnectionId, DaemonMessage, LogMessage, Payload, RequestId,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, ChunkedResponse, DaemonTcp,
         HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_CHUNKED_REQUEST_VERSION, HTTP_FRAMED_VERSION,
@@ -143,6 +143,11 @@ impl TcpStealerApi {
         }
     }
 
+    /// Passes the [`ClientIdentity`] of the client to the stealer task.
+    pub(crate) async fn set_identity(&mut self, identity: ClientIdentity) -> AgentResult<()> {
+        self.send_command(Command::ClientIdentity(identity)).await
+    }
+
     /// Returns a [`DaemonMessage`] to be sent to the client.
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
     pub(crate) async fn recv(&mut self) -> AgentResult<DaemonMessage> {
@@ -166,6 +171,9 @@ impl TcpStealerApi {
                         StealerMessage::PortSubscribed(port) => {
                             break Ok(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Ok(port))));
                         },
+                        StealerMessage::PortSubscribeFailed(error) => {
+                            break Ok(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Err(error))));
+                        },
                         StealerMessage::StolenHttp(http) => self.handle_request(http)?,
                         StealerMessage::StolenTcp(tcp) => self.handle_connection(tcp)?,
                     }
@@ -0,0 +1,65 @@
+use std::collections::{HashMap, HashSet};
+
+use mirrord_agent_env::{envs, probes::HttpProbe};
+
+use crate::incoming::StolenTraffic;
+
+/// Stolen traffic that the [`TcpStealerTask`](super::TcpStealerTask) passes through to its
+/// original destination before looking at the clients' subscriptions.
+///
+/// Configured by the CLI in the agent environment, see [`envs::STEAL_IGNORE_LOCAL`] and
+/// [`envs::STEAL_IGNORE_PROBES`].
+#[derive(Clone, Debug, Default)]
+pub struct StealExclusions {
+    /// Whether to pass through connections made from within the target pod.
+    local: bool,
+    /// Paths of the target pod's HTTP probes, by port.
+    probes: HashMap<u16, HashSet<String>>,
+}
+
+impl StealExclusions {
+    pub fn from_env() -> Self {
+        Self::new(
+            envs::STEAL_IGNORE_LOCAL.from_env_or_default(),
+            envs::STEAL_IGNORE_PROBES.from_env_or_default(),
+        )
+    }
+
+    pub fn new(local: bool, probes: Vec<HttpProbe>) -> Self {
+        let mut paths: HashMap<u16, HashSet<String>> = HashMap::new();
+        for probe in probes {
+            paths.entry(probe.port).or_default().insert(probe.path);
+        }
+
+        Self {
+            local,
+            probes: paths,
+        }
+    }
+
+    /// Returns the reason for passing the given traffic through, if it is excluded.
+    ///
+    /// The reason is the label of [`STEAL_EXCLUSIONS`](crate::metrics::STEAL_EXCLUSIONS).
+    pub fn check(&self, traffic: &StolenTraffic) -> Option<&'static str> {
+        let info = traffic.info();
+
+        // Connections from the pod's own address are made from within the pod as well.
+        if self.local
+            && (info.peer_addr.ip().is_loopback()
+                || info.peer_addr.ip() == info.original_destination.ip())
+        {
+            return Some("local");
+        }
+
+        if let StolenTraffic::Http(http) = traffic
+            && self
+                .probes
+                .get(&info.original_destination.port())
+                .is_some_and(|paths| paths.contains(http.parts().uri.path()))
+        {
+            return Some("health_check");
+        }
+
+        None
+    }
+}
@@ -5,11 +5,12 @@ use std::{
     sync::atomic::Ordering,
 };
 
+use thiserror::Error;
 use tracing::Level;
 
 use crate::{
     http::filter::HttpFilter,
-    incoming::{RedirectorTaskError, StealHandle, StolenTraffic},
+    incoming::{RedirectorTaskError, StealHandle, StolenTraffic, tls::error::StealTlsSetupError},
     metrics::{STEAL_FILTERED_PORT_SUBSCRIPTION, STEAL_UNFILTERED_PORT_SUBSCRIPTION},
     util::ClientId,
 };
@@ -64,14 +65,23 @@ impl PortSubscriptions {
         }
     }
 
+    /// Checks the TLS setup for the given port, see [`StealHandle::check_tls_setup`].
+    pub async fn check_tls_setup(&self, port: u16) -> Result<(), StealTlsSetupError> {
+        self.handle.check_tls_setup(port).await
+    }
+
     /// Try adding a new subscription to this set.
     ///
     /// # Subscription clash rules
     ///
-    /// * A single client may have only one subscription for the given port
-    /// * A single port may have only one unfiltered subscription
+    /// * A single client may have only one subscription for the given port, a new one replaces the
+    ///   old one
+    /// * An unfiltered subscription clashes with any subscription of another client
+    /// * A filtered subscription clashes with a subscription of another client only when they
+    ///   trivially overlap, see [`HttpFilter::is_identical`]
     ///
-    /// When a new subscription clashes with an existing one, the old one is replaced.
+    /// When a new subscription clashes with another client's one, it is rejected with
+    /// [`PortSubscribeError::Conflict`].
     ///
     /// # Params
     ///
@@ -84,34 +94,51 @@ impl PortSubscriptions {
         client_id: ClientId,
         port: u16,
         filter: Option<HttpFilter>,
-    ) -> Result<(), RedirectorTaskError> {
+    ) -> Result<(), PortSubscribeError> {
         let replaced = match self.subscriptions.entry(port) {
             Entry::Occupied(mut e) => match (e.get_mut(), filter) {
+                (PortSubscription::Unfiltered(holder), _) if *holder != client_id => {
+                    return Err(PortSubscribeError::Conflict(*holder));
+                }
+
                 (PortSubscription::Unfiltered(..), Some(filter)) => {
                     STEAL_UNFILTERED_PORT_SUBSCRIPTION.fetch_sub(1, Ordering::Relaxed);
                     STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
-                    e.insert(PortSubscription::Filtered([(client_id, filter)].into()));
+                    e.insert(PortSubscription::Filtered(vec![(client_id, filter)]));
                     true
                 }
 
-                (PortSubscription::Unfiltered(..), None) => {
-                    e.insert(PortSubscription::Unfiltered(client_id));
-                    true
-                }
+                (PortSubscription::Unfiltered(..), None) => true,
 
                 (PortSubscription::Filtered(filters), Some(filter)) => {
-                    match filters.insert(client_id, filter) {
-                        Some(..) => true,
+                    if let Some((holder, _)) = filters
+                        .iter()
+                        .find(|(id, existing)| *id != client_id && existing.is_identical(&filter))
+                    {
+                        return Err(PortSubscribeError::Conflict(*holder));
+                    }
+
+                    match filters.iter_mut().find(|(id, _)| *id == client_id) {
+                        Some((_, existing)) => {
+                            *existing = filter;
+                            true
+                        }
                         None => {
                             STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+                            filters.push((client_id, filter));
                             false
                         }
                     }
                 }
 
                 (PortSubscription::Filtered(filters), None) => {
-                    STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_sub(filters.len(), Ordering::Relaxed);
-                    STEAL_UNFILTERED_PORT_SUBSCRIPTION.fetch_add(filters.len(), Ordering::Relaxed);
+                    if let Some((holder, _)) = filters.iter().find(|(id, _)| *id != client_id) {
+                        return Err(PortSubscribeError::Conflict(*holder));
+                    }
+
+                    // Only this client's own filter is there.
+                    STEAL_FILTERED_PORT_SUBSCRIPTION.fetch_sub(1, Ordering::Relaxed);
+                    STEAL_UNFILTERED_PORT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
                     e.insert(PortSubscription::Unfiltered(client_id));
                     true
                 }
@@ -131,7 +158,7 @@ impl PortSubscriptions {
 
         if replaced {
             // All info already be contained in the span.
-            tracing::debug!("An existing port subscription was evicted.");
+            tracing::debug!("The client's previous port subscription was replaced.");
         }
 
         Ok(())
@@ -159,10 +186,9 @@ impl PortSubscriptions {
             }
             PortSubscription::Unfiltered(..) => {}
             PortSubscription::Filtered(filters) => {
-                if filters.remove(&client_id).is_some() {
-                    STEAL_FILTERED_PORT_SUBSCRIPTION
-                        .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
-                }
+                let removed = remove_filter(filters, client_id);
+                STEAL_FILTERED_PORT_SUBSCRIPTION
+                    .fetch_sub(removed, std::sync::atomic::Ordering::Relaxed);
 
                 if filters.is_empty() {
                     e.remove();
@@ -193,10 +219,9 @@ impl PortSubscriptions {
                 }
                 PortSubscription::Unfiltered(..) => true,
                 PortSubscription::Filtered(filters) => {
-                    if filters.remove(&client_id).is_some() {
-                        STEAL_FILTERED_PORT_SUBSCRIPTION
-                            .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
-                    }
+                    let removed = remove_filter(filters, client_id);
+                    STEAL_FILTERED_PORT_SUBSCRIPTION
+                        .fetch_sub(removed, std::sync::atomic::Ordering::Relaxed);
 
                     if filters.is_empty() {
                         self.handle.stop_steal(*port);
@@ -244,26 +269,44 @@ pub enum PortSubscription {
     /// Only HTTP requests matching one of the [`HttpFilter`]s should be stolen (on behalf of the
     /// filter owner).
     ///
-    /// Can be shared by multiple clients.
-    Filtered(HashMap<ClientId, HttpFilter>),
+    /// Can be shared by multiple clients, at most one filter per client. The filters are kept in
+    /// the subscription order, and a request goes to the first one that matches.
+    Filtered(Vec<(ClientId, HttpFilter)>),
 }
 
 impl PortSubscription {
     /// Create a new instance. Variant is picked based on the optional `filter`.
     fn new(client_id: ClientId, filter: Option<HttpFilter>) -> Self {
         match filter {
-            Some(filter) => Self::Filtered(HashMap::from_iter([(client_id, filter)])),
+            Some(filter) => Self::Filtered(vec![(client_id, filter)]),
             None => Self::Unfiltered(client_id),
         }
     }
 }
 
+/// Removes the filter of the given client, returns how many filters were removed.
+fn remove_filter(filters: &mut Vec<(ClientId, HttpFilter)>, client_id: ClientId) -> usize {
+    let len = filters.len();
+    filters.retain(|(id, _)| *id != client_id);
+    len - filters.len()
+}
+
+/// Errors of [`PortSubscriptions::add`].
+#[derive(Error, Debug)]
+pub enum PortSubscribeError {
+    /// The new subscription clashes with a subscription of the contained client.
+    #[error("port is already stolen by client {0}")]
+    Conflict(ClientId),
+    #[error(transparent)]
+    Redirector(#[from] RedirectorTaskError),
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
         http::filter::HttpFilter,
         incoming::{RedirectorTask, RedirectorTaskConfig, test::DummyRedirector},
-        steal::subscriptions::{PortSubscription, PortSubscriptions},
+        steal::subscriptions::{PortSubscribeError, PortSubscription, PortSubscriptions},
         util::ClientId,
     };
 
@@ -271,7 +314,7 @@ mod test {
         /// Return whether this subscription belongs (possibly partially) to the given client.
         fn has_client(&self, client_id: ClientId) -> bool {
             match self {
-                Self::Filtered(filters) => filters.contains_key(&client_id),
+                Self::Filtered(filters) => filters.iter().any(|(id, _)| *id == client_id),
                 Self::Unfiltered(subscribed_client) => *subscribed_client == client_id,
             }
         }
@@ -281,6 +324,10 @@ mod test {
         HttpFilter::Header(".*".parse().unwrap())
     }
 
+    fn path_filter(path: &str) -> HttpFilter {
+        HttpFilter::Path(path.parse().unwrap())
+    }
+
     #[tokio::test]
     async fn multiple_subscriptions_one_port() {
         let (redirector, mut state, _tx) = DummyRedirector::new();
@@ -298,26 +345,29 @@ mod test {
         let sub = subscriptions.subscriptions.get(&80).unwrap();
         assert!(matches!(sub, PortSubscription::Unfiltered(0)), "{sub:?}");
 
-        // Another client's subscription should overwrite.
-        subscriptions.add(1, 80, None).await.unwrap();
-        assert!(state.borrow().has_redirections([80]));
+        // Another client's subscription should be rejected.
+        let error = subscriptions.add(1, 80, None).await.unwrap_err();
+        assert!(
+            matches!(error, PortSubscribeError::Conflict(0)),
+            "{error:?}"
+        );
         let sub = subscriptions.subscriptions.get(&80).unwrap();
-        assert!(matches!(sub, PortSubscription::Unfiltered(1)), "{sub:?}");
+        assert!(matches!(sub, PortSubscription::Unfiltered(0)), "{sub:?}");
 
         // Same client's next subscription should overwrite.
         subscriptions
-            .add(1, 80, Some(dummy_filter()))
+            .add(0, 80, Some(dummy_filter()))
             .await
             .unwrap();
         assert!(state.borrow().has_redirections([80]));
         let sub = subscriptions.subscriptions.get(&80).unwrap();
         assert!(
-            matches!(sub, PortSubscription::Filtered(filters) if filters.len() == 1 && sub.has_client(1)),
+            matches!(sub, PortSubscription::Filtered(filters) if filters.len() == 1 && sub.has_client(0)),
             "{sub:?}"
         );
 
         // Removing the subscription.
-        subscriptions.remove(1, 80);
+        subscriptions.remove(0, 80);
 
         // Checking if all is cleaned up.
         state
@@ -420,4 +470,74 @@ mod test {
         let sub = subscriptions.subscriptions.get(&81);
         assert!(sub.is_none(), "{sub:?}");
     }
+
+    #[tokio::test]
+    async fn conflicting_subscriptions() {
+        let (redirector, _state, _tx) = DummyRedirector::new();
+        let (redirector_task, steal_handle, _) = RedirectorTask::new(
+            redirector,
+            Default::default(),
+            RedirectorTaskConfig::from_env(),
+        );
+        tokio::spawn(redirector_task.run());
+        let mut subscriptions = PortSubscriptions::new(steal_handle);
+
+        subscriptions
+            .add(0, 80, Some(path_filter("/api")))
+            .await
+            .unwrap();
+
+        // Different filters are allowed, even if they could match the same requests.
+        subscriptions
+            .add(1, 80, Some(path_filter("/api/v1")))
+            .await
+            .unwrap();
+
+        // Identical filters are not.
+        let error = subscriptions
+            .add(2, 80, Some(path_filter("/api")))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, PortSubscribeError::Conflict(0)),
+            "{error:?}"
+        );
+
+        // Neither is an unfiltered subscription.
+        let error = subscriptions.add(2, 80, None).await.unwrap_err();
+        assert!(
+            matches!(error, PortSubscribeError::Conflict(0)),
+            "{error:?}"
+        );
+
+        // A client can replace its own filter, keeping its position.
+        subscriptions
+            .add(0, 80, Some(path_filter("/api/v2")))
+            .await
+            .unwrap();
+        let sub = subscriptions.subscriptions.get(&80).unwrap();
+        let PortSubscription::Filtered(filters) = sub else {
+            panic!("unexpected subscription: {sub:?}");
+        };
+        let order = filters
+            .iter()
+            .map(|(client_id, filter)| match filter {
+                HttpFilter::Path(path) => (*client_id, path.as_str()),
+                other => panic!("unexpected filter: {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(order, [(0, "/api/v2"), (1, "/api/v1")]);
+
+        // But cannot take over the port while another client is there.
+        let error = subscriptions.add(1, 80, None).await.unwrap_err();
+        assert!(
+            matches!(error, PortSubscribeError::Conflict(0)),
+            "{error:?}"
+        );
+
+        subscriptions.remove(0, 80);
+        subscriptions.add(1, 80, None).await.unwrap();
+        let sub = subscriptions.subscriptions.get(&80).unwrap();
+        assert!(matches!(sub, PortSubscription::Unfiltered(1)), "{sub:?}");
+    }
 }
@@ -8,7 +8,8 @@ use std::{
 use futures::{StreamExt, stream::FuturesUnordered};
 use http::header::UPGRADE;
 use mirrord_protocol::{
-    LogMessage,
+    ClientIdentity, LogMessage, ResponseError,
+    error::{PORT_STEAL_CONFLICT_VERSION, STEAL_TLS_SETUP_ERROR_VERSION},
     tcp::{
         HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_FILTERED_UPGRADE_VERSION, MODE_AGNOSTIC_HTTP_REQUESTS,
     },
@@ -18,12 +19,20 @@ use tokio_util::sync::CancellationToken;
 use tracing::Level;
 
 use super::{
-    Command, StealerCommand, StealerMessage,
-    subscriptions::{PortSubscription, PortSubscriptions},
+    Command, StealExclusions, StealerCommand, StealerMessage,
+    subscriptions::{PortSubscribeError, PortSubscription, PortSubscriptions},
 };
 use crate::{
-    http::filter::HttpFilter,
-    incoming::{RedirectedHttp, RedirectedTcp, RedirectorTaskError, StealHandle, StolenTraffic},
+    http::{
+        body_budget::{BodyBufferBudget, BodyBufferReservation},
+        error::MirrordErrorResponse,
+        filter::{FilterDecision, HttpFilter},
+    },
+    incoming::{
+        BufferBodyError, RedirectedHttp, RedirectedTcp, RedirectorTaskError, StealHandle,
+        StolenTraffic,
+    },
+    metrics::{BODY_FILTER_FALLBACKS, STEAL_EXCLUSIONS},
     util::{ChannelClosedFuture, ClientId, protocol_version::ClientProtocolVersion},
 };
 
@@ -41,17 +50,27 @@ pub struct TcpStealerTask {
     /// Futures that resolve when clients disconnect (drop their [`StealerMessage`] receivers).
     disconnected_clients: FuturesUnordered<ChannelClosedFuture>,
     /// For tracking http requests whose bodies are being buffered
-    ongoing_requests: JoinSet<RedirectedHttp>,
+    ongoing_requests: JoinSet<(RedirectedHttp, BodyBufferReservation)>,
+    /// Memory taken by the bodies in [`Self::ongoing_requests`].
+    body_budget: BodyBufferBudget,
+    /// Traffic passed through before looking at the subscriptions.
+    exclusions: StealExclusions,
 }
 
 impl TcpStealerTask {
-    pub fn new(command_rx: mpsc::Receiver<StealerCommand>, handle: StealHandle) -> Self {
+    pub fn new(
+        command_rx: mpsc::Receiver<StealerCommand>,
+        handle: StealHandle,
+        exclusions: StealExclusions,
+    ) -> Self {
         Self {
             subscriptions: PortSubscriptions::new(handle),
             command_rx,
             clients: Default::default(),
             disconnected_clients: Default::default(),
             ongoing_requests: Default::default(),
+            body_budget: Default::default(),
+            exclusions,
         }
     }
 
@@ -67,7 +86,14 @@ impl TcpStealerTask {
 
                 Some(result) = self.subscriptions.next() => {
                     let (traffic, subscription) = result?;
-                    Self::handle_stolen_traffic(&self.clients, traffic, subscription, &mut self.ongoing_requests).await;
+                    if let Some(reason) = self.exclusions.check(&traffic) {
+                        tracing::trace!(reason, info = ?traffic.info(), "passing excluded traffic through");
+                        STEAL_EXCLUSIONS.with_label_values(&[reason]).inc();
+                        traffic.pass_through();
+                        continue;
+                    }
+
+                    Self::handle_stolen_traffic(&self.clients, traffic, subscription, &mut self.ongoing_requests, &self.body_budget).await;
                 }
 
                 Some(client_id) = self.disconnected_clients.next() => {
@@ -76,8 +102,10 @@ impl TcpStealerTask {
 
                 Some(next) = self.ongoing_requests.join_next() => {
                     match next {
-                        Ok(http) => {
+                        Ok((http, reservation)) => {
                             self.handle_buffered_http(http).await;
+                            // The body is no longer needed once the filters are matched.
+                            drop(reservation);
                         },
                         Err(error) => {
                             tracing::error!(
@@ -146,7 +174,8 @@ impl TcpStealerTask {
         clients: &HashMap<ClientId, Client>,
         traffic: StolenTraffic,
         subscription: &PortSubscription,
-        ongoing: &mut JoinSet<RedirectedHttp>,
+        ongoing: &mut JoinSet<(RedirectedHttp, BodyBufferReservation)>,
+        budget: &BodyBufferBudget,
     ) {
         let protocol_version_req = match &traffic {
             StolenTraffic::Tcp { conn, .. } => Self::protocol_version_req_tcp(subscription, conn),
@@ -240,33 +269,72 @@ impl TcpStealerTask {
             }
         };
 
-        if filters.values().any(HttpFilter::needs_body) {
-            ongoing.spawn(async move {
-                if let Err(error) = http.buffer_body().await {
-                    tracing::debug!(?error, "failed to buffer request body");
-                };
-                http
-            });
-        } else {
-            Self::finish_stealing(clients, filters, http, protocol_version_req).await
+        if filters.iter().any(|(_, filter)| filter.needs_body()).not() {
+            Self::finish_stealing(clients, filters, http, protocol_version_req).await;
+            return;
+        }
+
+        // Clients whose filters cannot decide without the body, e.g. header filters failing in
+        // an `all` composite make the body irrelevant.
+        let mut undecided = vec![];
+        let (parts, _) = http.parts_and_body();
+        for (client_id, filter) in filters {
+            if filter.matches_without_body(parts).await.is_none() {
+                undecided.push(*client_id);
+            }
         }
+
+        if undecided.is_empty() {
+            Self::finish_stealing(clients, filters, http, protocol_version_req).await;
+            return;
+        }
+
+        let budget = budget.clone();
+        ongoing.spawn(async move {
+            let mut reservation = budget.reserve(undecided);
+
+            if let Err(error) = http.buffer_body(&mut reservation).await {
+                tracing::debug!(?error, "failed to buffer request body");
+
+                if matches!(error, BufferBodyError::CeilingReached) {
+                    tracing::warn!(
+                        total = budget.total(),
+                        ceiling = budget.ceiling(),
+                        "Buffered HTTP bodies reached the max total size, \
+                        body filters will use their fallback",
+                    );
+                }
+
+                BODY_FILTER_FALLBACKS
+                    .with_label_values(&[error.fallback_reason()])
+                    .inc();
+            };
+
+            (http, reservation)
+        });
     }
 
     async fn finish_stealing(
         clients: &HashMap<ClientId, Client>,
-        filters: &HashMap<ClientId, HttpFilter>,
+        filters: &[(ClientId, HttpFilter)],
         mut http: RedirectedHttp,
         protocol_version_req: Cow<'static, semver::VersionReq>,
     ) {
         let mut send_to = None; // the client that will receive the request
         let mut preempted = vec![]; // other clients that could receive the request as well
         let mut blocked_on_protocol = vec![]; // clients that cannot receive the request due to their protocol version
+        let mut close = false; // whether a filter failed with the `close` policy
 
         let (parts, body_reader) = http.parts_and_body();
 
         for (client_id, filter) in filters {
-            if filter.matches(parts, body_reader).await.not() {
-                continue;
+            match filter.decide(parts, body_reader).await {
+                FilterDecision::Match => {}
+                FilterDecision::NoMatch => continue,
+                FilterDecision::Close => {
+                    close = true;
+                    continue;
+                }
             }
 
             let Some(client) = clients.get(client_id) else {
@@ -318,6 +386,17 @@ impl TcpStealerTask {
                 .message_tx
                 .send(StealerMessage::StolenHttp(http.steal()))
                 .await;
+        } else if close {
+            // Another client can still steal the request, we only close it when no one does.
+            let version = http.parts().version;
+            http.respond(
+                MirrordErrorResponse::filter_error(
+                    version,
+                    "failed to evaluate the HTTP filter, closing the request \
+                    due to the `on_filter_error` policy",
+                )
+                .into(),
+            );
         } else {
             http.pass_through();
         }
@@ -339,23 +418,92 @@ impl TcpStealerTask {
                 e.insert(Client {
                     message_tx,
                     protocol_version,
+                    identity: None,
                 });
             }
 
+            Command::ClientIdentity(identity) => {
+                if let Some(client) = self.clients.get_mut(&command.client_id) {
+                    client.identity = Some(identity);
+                }
+            }
+
             Command::PortSubscribe(port, filter) => {
                 let Some(client) = self.clients.get(&command.client_id) else {
                     // The client disconnected after sending the message.
                     return Ok(());
                 };
 
-                self.subscriptions
+                if let Err(error) = self.subscriptions.check_tls_setup(port).await {
+                    if client
+                        .protocol_version
+                        .matches(&STEAL_TLS_SETUP_ERROR_VERSION)
+                    {
+                        let _ = client
+                            .message_tx
+                            .send(StealerMessage::PortSubscribeFailed(
+                                ResponseError::StealTlsSetup {
+                                    port,
+                                    reason: error.to_string(),
+                                },
+                            ))
+                            .await;
+                        return Ok(());
+                    }
+
+                    tracing::warn!(
+                        client_id = command.client_id,
+                        port,
+                        %error,
+                        "Client does not support TLS setup errors, \
+                        stolen TLS connections on this port will fail",
+                    );
+                }
+
+                if let Some(filter) = &filter {
+                    tracing::info!(
+                        client_id = command.client_id,
+                        port,
+                        on_filter_error = %filter.error_policy(),
+                        "Client subscribed to steal HTTP requests with a filter",
+                    );
+                }
+
+                let message = match self
+                    .subscriptions
                     .add(command.client_id, port, filter)
-                    .await?;
+                    .await
+                {
+                    Ok(()) => StealerMessage::PortSubscribed(port),
+                    Err(PortSubscribeError::Conflict(holder_id)) => {
+                        let holder = self
+                            .clients
+                            .get(&holder_id)
+                            .and_then(|holder| holder.identity.as_ref())
+                            .map(ClientIdentity::to_string);
+                        tracing::info!(
+                            client_id = command.client_id,
+                            holder_id,
+                            ?holder,
+                            port,
+                            "Rejected a port subscription conflicting with another client",
+                        );
+
+                        let error = if client
+                            .protocol_version
+                            .matches(&PORT_STEAL_CONFLICT_VERSION)
+                        {
+                            ResponseError::PortStealConflict { port, holder }
+                        } else {
+                            ResponseError::PortAlreadyStolen(port)
+                        };
+
+                        StealerMessage::PortSubscribeFailed(error)
+                    }
+                    Err(PortSubscribeError::Redirector(error)) => return Err(error),
+                };
 
-                let _ = client
-                    .message_tx
-                    .send(StealerMessage::PortSubscribed(port))
-                    .await;
+                let _ = client.message_tx.send(message).await;
             }
 
             Command::PortUnsubscribe(port) => {
@@ -413,4 +561,7 @@ impl fmt::Debug for TcpStealerTask {
 struct Client {
     message_tx: mpsc::Sender<StealerMessage>,
     protocol_version: ClientProtocolVersion,
+    /// Sent by the client with [`Command::ClientIdentity`], reported to other clients when their
+    /// port subscriptions conflict with this client's ones.
+    identity: Option<ClientIdentity>,
 }
@@ -4,17 +4,29 @@
 //! to the [`RedirectorTask`](crate::incoming::RedirectorTask).
 #![allow(clippy::indexing_slicing)]
 
-use std::time::Duration;
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    ops::Not,
+    time::Duration,
+};
 
 use bytes::{Buf, Bytes};
 use futures::StreamExt;
-use http_body_util::{BodyExt, Full, StreamBody};
-use hyper::body::SizeHint;
+use http_body_util::{BodyExt, Full, StreamBody, combinators::BoxBody};
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::{Frame, Incoming, SizeHint},
+    header::{HeaderMap, HeaderName, HeaderValue},
+    service::service_fn,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use mirrord_agent_env::probes::HttpProbe;
 use mirrord_protocol::{
-    DaemonMessage, LogLevel,
+    ClientIdentity, DaemonMessage, LogLevel, ResponseError,
     tcp::{
         DaemonTcp, Filter, HttpBodyFilter, HttpFilter, IncomingTrafficTransportType, JsonPathQuery,
-        StealType,
+        LayerTcpSteal, StealType,
     },
 };
 use mirrord_tls_util::MaybeTls;
@@ -26,9 +38,11 @@ use tokio::{net::TcpListener, sync::mpsc};
 use tokio_rustls::TlsStream;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::CancellationToken;
-use utils::{StealingClient, TestBody, TestHttpKind, TestRequest, TestTcpProtocol, WithSizeHint};
+use utils::{
+    StealingClient, TestBody, TestHttpKind, TestRequest, TestTcpProtocol, TestUpgrade, WithSizeHint,
+};
 
-use super::{StealerCommand, TcpStealerTask};
+use super::{StealExclusions, StealerCommand, TcpStealerApi, TcpStealerTask};
 use crate::{
     incoming::{
         RedirectorTask, RedirectorTaskConfig,
@@ -43,7 +57,7 @@ use crate::{
 
 mod utils;
 
-/// Verifies that redirected request upgrades are handled correctly.
+/// Verifies that redirected request upgrades, including `CONNECT` requests, are handled correctly.
 #[rstest]
 #[timeout(Duration::from_secs(5))]
 #[tokio::test]
@@ -61,6 +75,7 @@ async fn request_upgrade(
         TestTcpProtocol::ServerTalks
     )]
     upgraded_protocol: TestTcpProtocol,
+    #[values(false, true)] connect: bool,
 ) {
     let mut setup = TestSetup::new_http(http_kind, RedirectorTaskConfig::from_env()).await;
 
@@ -68,7 +83,11 @@ async fn request_upgrade(
         path: "/api/v1".into(),
         id_header: 0,
         user_header: if stolen { 0 } else { 1 },
-        upgrade: Some(upgraded_protocol),
+        upgrade: Some(if connect {
+            TestUpgrade::Connect(upgraded_protocol)
+        } else {
+            TestUpgrade::Header(upgraded_protocol)
+        }),
         kind: http_kind,
         connector: setup.tls.as_ref().map(|s| s.connector(http_kind.alpn())),
         acceptor: setup.tls.as_ref().map(SimpleStore::acceptor),
@@ -161,6 +180,7 @@ async fn http_with_unfiltered_subscription(
                 .await;
         },
     );
+    client_1.unsubscribe().await;
 
     let mut client_2 = StealingClient::new(
         1,
@@ -253,6 +273,107 @@ async fn tcp_stealing(#[values(false, true)] with_tls: bool, #[values(false, tru
     );
 }
 
+/// Verifies stealing and passthrough of IPv4 connections to an app listening on the IPv6
+/// wildcard address.
+#[rstest]
+#[timeout(Duration::from_secs(5))]
+#[tokio::test]
+async fn v4_traffic_on_v6_wildcard(#[values(false, true)] stolen: bool) {
+    let mut setup = TestSetup::new_tcp(false, RedirectorTaskConfig::from_env()).await;
+    setup.original_server = TcpListener::bind("[::]:0").await.unwrap();
+    let port = setup.original_server.local_addr().unwrap().port();
+
+    let steal_type = if stolen {
+        StealType::All(port)
+    } else {
+        StealType::FilteredHttpEx(
+            port,
+            HttpFilter::Header(Filter::new("whatever".into()).unwrap()),
+        )
+    };
+    let mut client = StealingClient::new(
+        0,
+        setup.stealer_tx.clone(),
+        "1.19.4",
+        steal_type,
+        setup.stealer_status.clone(),
+    )
+    .await;
+
+    let conn = setup
+        .conn_tx
+        .make_connection(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port))
+        .await;
+    tokio::join!(TestTcpProtocol::Echo.run(conn, false), async {
+        if stolen {
+            let conn = client.expect_connection().await;
+            assert_eq!(
+                conn.connection.local_address,
+                IpAddr::from(Ipv4Addr::LOCALHOST)
+            );
+            assert!(conn.connection.remote_address.is_ipv4());
+            client
+                .expect_tcp(conn.connection.connection_id, TestTcpProtocol::Echo)
+                .await;
+        } else {
+            let (conn, peer) = setup.original_server.accept().await.unwrap();
+            assert_eq!(peer.ip(), Ipv4Addr::LOCALHOST.to_ipv6_mapped());
+            TestTcpProtocol::Echo.run(conn, true).await;
+        }
+    },);
+}
+
+/// Verifies that the traffic excluded with [`StealExclusions`] is passed through, even when the
+/// port is stolen without a filter.
+#[rstest]
+#[timeout(Duration::from_secs(5))]
+#[tokio::test]
+async fn steal_exclusions(#[values(false, true)] ignore_local: bool) {
+    let mut setup = TestSetup::new_with_exclusions(ignore_local, &["/healthz"]).await;
+    let original_destination = setup.original_server.local_addr().unwrap();
+
+    let request = |path: &str| TestRequest {
+        path: path.into(),
+        id_header: 0,
+        user_header: 0,
+        upgrade: None,
+        kind: TestHttpKind::Http1,
+        connector: None,
+        acceptor: None,
+        body: None,
+    };
+
+    let mut client = StealingClient::new(
+        0,
+        setup.stealer_tx.clone(),
+        "1.19.4",
+        StealType::All(original_destination.port()),
+        setup.stealer_status.clone(),
+    )
+    .await;
+
+    // The test connections come from localhost.
+    for (request, stolen) in [
+        (request("/healthz"), false),
+        (request("/api/v1"), ignore_local.not()),
+    ] {
+        let conn = setup.conn_tx.make_connection(original_destination).await;
+        let mut sender = request.make_connection(conn).await;
+
+        if stolen {
+            tokio::join!(
+                request.send(&mut sender, 0),
+                client.expect_request(&request),
+            );
+        } else {
+            tokio::join!(request.send(&mut sender, 2137), async {
+                let (stream, _) = setup.original_server.accept().await.unwrap();
+                request.accept(stream, 2137).await;
+            });
+        }
+    }
+}
+
 /// Verifies scenario where the client cannot steal a TLS connection,
 /// because their mirrord-protocol version is too low.
 #[rstest]
@@ -321,15 +442,16 @@ async fn multiple_matching_filters(
         body: None,
     };
 
-    let clients = futures::stream::iter(0..3)
-        .then(|id| {
+    // Identical filters would conflict.
+    let clients = futures::stream::iter([(0, "/api/v1"), (1, "/api"), (2, "v1")])
+        .then(|(id, path)| {
             StealingClient::new(
                 id,
                 setup.stealer_tx.clone(),
                 "1.19.4",
                 StealType::FilteredHttpEx(
                     setup.original_server.local_addr().unwrap().port(),
-                    HttpFilter::Path(Filter::new("/api/v1".into()).unwrap()),
+                    HttpFilter::Path(Filter::new(path.into()).unwrap()),
                 ),
                 setup.stealer_status.clone(),
             )
@@ -346,25 +468,78 @@ async fn multiple_matching_filters(
         request.send(&mut sender, 0).await;
     });
 
-    let mut logs = 0;
-    let mut requests = 0;
-
-    for mut client in clients {
+    // The first subscription wins.
+    for (id, mut client) in clients.into_iter().enumerate() {
         match client.recv().await {
-            DaemonMessage::LogMessage(log) => {
+            DaemonMessage::LogMessage(log) if id > 0 => {
                 assert_eq!(log.level, LogLevel::Warn);
                 assert!(log.message.contains("stolen by another user"));
-                logs += 1;
-            }
-            DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(..)) => {
-                requests += 1;
             }
-            other => panic!("unexpected message: {other:?}"),
+            DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(..)) if id == 0 => {}
+            other => panic!("unexpected message for client {id}: {other:?}"),
         }
     }
+}
+
+/// Verifies that subscriptions conflicting with another client's one are rejected, naming the
+/// other client when the protocol version allows it.
+#[rstest]
+#[timeout(Duration::from_secs(5))]
+#[tokio::test]
+async fn conflicting_subscription(
+    #[values("1.19.4", "1.35.0")] protocol_version: &str,
+    #[values(false, true)] filtered: bool,
+) {
+    let setup = TestSetup::new_tcp(false, RedirectorTaskConfig::from_env()).await;
+    let port = setup.original_server.local_addr().unwrap().port();
+    let steal_type = if filtered {
+        StealType::FilteredHttpEx(
+            port,
+            HttpFilter::Header(Filter::new("x-user: alice".into()).unwrap()),
+        )
+    } else {
+        StealType::All(port)
+    };
+
+    let mut holder = StealingClient::new(
+        0,
+        setup.stealer_tx.clone(),
+        "1.35.0",
+        steal_type.clone(),
+        setup.stealer_status.clone(),
+    )
+    .await;
+    holder
+        .set_identity(ClientIdentity {
+            name: Some("alice".into()),
+            hostname: Some("laptop".into()),
+        })
+        .await;
 
-    assert_eq!(logs, 2);
-    assert_eq!(requests, 1);
+    let mut api = TcpStealerApi::new(
+        1,
+        protocol_version.parse().unwrap(),
+        setup.stealer_tx.clone(),
+        setup.stealer_status.clone(),
+    )
+    .await
+    .unwrap();
+    api.handle_client_message(LayerTcpSteal::PortSubscribe(steal_type))
+        .await
+        .unwrap();
+
+    let expected = if protocol_version == "1.35.0" {
+        ResponseError::PortStealConflict {
+            port,
+            holder: Some("alice@laptop".into()),
+        }
+    } else {
+        ResponseError::PortAlreadyStolen(port)
+    };
+    assert_eq!(
+        api.recv().await.unwrap(),
+        DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Err(expected))),
+    );
 }
 
 /// Verifies scenario where we have multiple filtered subscriptions.
@@ -537,6 +712,131 @@ async fn header_injection(
     );
 }
 
+/// Verifies that large HTTP/2 request and response bodies are streamed in both directions at the
+/// same time, with trailers intact.
+///
+/// Passed through requests must share one connection to the original destination, as the echo
+/// server accepts only one.
+#[rstest]
+#[timeout(Duration::from_secs(20))]
+#[tokio::test]
+async fn http2_streaming_echo(
+    #[values(false, true)] stolen: bool,
+    #[values(
+        TestHttpKind::Http2,
+        TestHttpKind::Http2Alpn,
+        TestHttpKind::Http2NoAlpn
+    )]
+    http_kind: TestHttpKind,
+) {
+    const REQUESTS: usize = 2;
+    const CHUNKS: usize = 32;
+    const CHUNK_SIZE: usize = 256 * 1024;
+
+    let grpc_status = HeaderName::from_static("grpc-status");
+    let make_body = || {
+        let chunks = (0..CHUNKS)
+            .map(|i| Ok::<_, hyper::Error>(Frame::data(Bytes::from(vec![i as u8; CHUNK_SIZE]))));
+        let trailers = HeaderMap::from_iter([(grpc_status.clone(), HeaderValue::from_static("0"))]);
+        let frames = chunks.chain(std::iter::once(Ok(Frame::trailers(trailers))));
+        BoxBody::new(StreamBody::new(futures::stream::iter(frames)))
+    };
+
+    let mut setup = TestSetup::new_http(http_kind, RedirectorTaskConfig::from_env()).await;
+    let original_destination = setup.original_server.local_addr().unwrap();
+
+    let request = TestRequest {
+        path: if stolen { "/stolen" } else { "/passthrough" }.into(),
+        id_header: 0,
+        user_header: 0,
+        upgrade: None,
+        kind: http_kind,
+        connector: setup.tls.as_ref().map(|s| s.connector(http_kind.alpn())),
+        acceptor: setup.tls.as_ref().map(SimpleStore::acceptor),
+        body: None,
+    };
+    let uri = format!(
+        "{}://server{}",
+        if http_kind.uses_tls() {
+            "https"
+        } else {
+            "http"
+        },
+        request.path,
+    );
+
+    let mut client = StealingClient::new(
+        0,
+        setup.stealer_tx.clone(),
+        "1.19.4",
+        StealType::FilteredHttpEx(
+            original_destination.port(),
+            HttpFilter::Path(Filter::new("/stolen".into()).unwrap()),
+        ),
+        setup.stealer_status.clone(),
+    )
+    .await;
+
+    tokio::join!(
+        async {
+            let conn = setup.conn_tx.make_connection(original_destination).await;
+            let mut sender = request.make_connection(conn).await;
+
+            for _ in 0..REQUESTS {
+                let hyper_request = Request::builder()
+                    .method(Method::POST)
+                    .uri(&uri)
+                    .body(make_body())
+                    .unwrap();
+                let response = sender.send(hyper_request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+
+                let body = response.into_body().collect().await.unwrap();
+                assert_eq!(
+                    body.trailers()
+                        .and_then(|trailers| trailers.get(&grpc_status)),
+                    Some(&HeaderValue::from_static("0")),
+                );
+                let body = body.to_bytes();
+                assert_eq!(body.len(), CHUNKS * CHUNK_SIZE);
+                assert!(
+                    body.chunks(CHUNK_SIZE)
+                        .enumerate()
+                        .all(|(i, chunk)| chunk.iter().all(|byte| *byte == i as u8)),
+                    "unexpected response body"
+                );
+            }
+        },
+        async {
+            if stolen {
+                for _ in 0..REQUESTS {
+                    client.expect_request_echo().await;
+                }
+                return;
+            }
+
+            let (stream, _) = setup.original_server.accept().await.unwrap();
+            let stream = match &request.acceptor {
+                Some(acceptor) => {
+                    let stream = acceptor.accept(stream).await.unwrap();
+                    MaybeTls::Tls(Box::new(TlsStream::Server(stream)))
+                }
+                None => MaybeTls::NoTls(stream),
+            };
+            // The agent drops the connection when the redirected connection is closed,
+            // so the result is not interesting here.
+            let _ = hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                .serve_connection(
+                    TokioIo::new(stream),
+                    service_fn(|request: Request<Incoming>| async move {
+                        Ok::<_, Infallible>(Response::new(request.into_body()))
+                    }),
+                )
+                .await;
+        },
+    );
+}
+
 #[derive(Clone, Copy)]
 enum FailReason {
     Timeout,
@@ -821,6 +1121,43 @@ impl TestSetup {
 
     async fn new_tcp(with_tls: bool, redirector_config: RedirectorTaskConfig) -> Self {
         let original_server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        Self::new_inner(
+            original_server,
+            with_tls,
+            redirector_config,
+            Default::default(),
+        )
+        .await
+    }
+
+    /// Plain HTTP setup, where the stealer passes through the local traffic if `ignore_local` is
+    /// set, and the requests to the given probe paths of the original server.
+    async fn new_with_exclusions(ignore_local: bool, probe_paths: &[&str]) -> Self {
+        let original_server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = original_server.local_addr().unwrap().port();
+        let probes = probe_paths
+            .iter()
+            .map(|path| HttpProbe {
+                port,
+                path: path.to_string(),
+            })
+            .collect();
+
+        Self::new_inner(
+            original_server,
+            false,
+            RedirectorTaskConfig::from_env(),
+            StealExclusions::new(ignore_local, probes),
+        )
+        .await
+    }
+
+    async fn new_inner(
+        original_server: TcpListener,
+        with_tls: bool,
+        redirector_config: RedirectorTaskConfig,
+        exclusions: StealExclusions,
+    ) -> Self {
         let original_destination = original_server.local_addr().unwrap();
         let tls_setup = if with_tls {
             Some(SimpleStore::new(original_destination.port(), &["h2", "http/1.1"]).await)
@@ -837,7 +1174,7 @@ impl TestSetup {
             redirector_config,
         );
         let (stealer_tx, stealer_rx) = mpsc::channel(8);
-        let stealer_task = TcpStealerTask::new(stealer_rx, handle);
+        let stealer_task = TcpStealerTask::new(stealer_rx, handle, exclusions);
         tokio::spawn(redirector.run());
 
         let local_bg_task_runtime = BgTaskRuntime::spawn(None).await.unwrap();
@@ -19,7 +19,7 @@ use hyper::{
 };
 use hyper_util::rt::TokioIo;
 use mirrord_protocol::{
-    ConnectionId, DaemonMessage, LogLevel,
+    ClientIdentity, ConnectionId, DaemonMessage, LogLevel,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, ChunkedResponse, DaemonTcp,
         HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_CHUNKED_RESPONSE_VERSION, HttpRequestMetadata,
@@ -135,6 +135,26 @@ impl TestTcpProtocol {
     }
 }
 
+/// HTTP upgrades used in steal tests.
+#[derive(Clone, Copy, Debug)]
+pub enum TestUpgrade {
+    /// `GET` request with the `Upgrade` header, and a `101 Switching Protocols` response.
+    Header(TestTcpProtocol),
+    /// `CONNECT` request, and a `200 OK` response.
+    Connect(TestTcpProtocol),
+}
+
+impl TestUpgrade {
+    /// Authority of the `CONNECT` requests.
+    const CONNECT_AUTHORITY: &str = "server:443";
+
+    pub fn protocol(self) -> TestTcpProtocol {
+        match self {
+            Self::Header(protocol) | Self::Connect(protocol) => protocol,
+        }
+    }
+}
+
 /// HTTP connections used in steal tests.
 #[derive(Clone, Copy, Debug)]
 pub enum TestHttpKind {
@@ -213,7 +233,7 @@ pub struct TestRequest {
     pub path: String,
     pub id_header: usize,
     pub user_header: ClientId,
-    pub upgrade: Option<TestTcpProtocol>,
+    pub upgrade: Option<TestUpgrade>,
     pub kind: TestHttpKind,
     pub connector: Option<TlsConnector>,
     pub acceptor: Option<TlsAcceptor>,
@@ -250,7 +270,14 @@ impl TestRequest {
             self.path,
         );
         match self.upgrade {
-            Some(protocol) => Request::builder()
+            Some(TestUpgrade::Connect(..)) => Request::builder()
+                .method(Method::CONNECT)
+                .uri(TestUpgrade::CONNECT_AUTHORITY)
+                .header(Self::REQUEST_ID_HEADER, self.id_header.to_string())
+                .header(Self::USER_ID_HEADER, self.user_header.to_string())
+                .body(Empty::<Bytes>::new().map_err(|_| unreachable!()).boxed())
+                .unwrap(),
+            Some(TestUpgrade::Header(protocol)) => Request::builder()
                 .method(Method::GET)
                 .uri(uri)
                 .header(header::CONNECTION, "upgrade")
@@ -295,7 +322,13 @@ impl TestRequest {
     /// assertions.
     fn as_hyper_response(&self, handled_by: ClientId) -> Response<BoxBody<Bytes, hyper::Error>> {
         match self.upgrade {
-            Some(protocol) => Response::builder()
+            Some(TestUpgrade::Connect(..)) => Response::builder()
+                .status(StatusCode::OK)
+                .header(Self::REQUEST_ID_HEADER, self.id_header.to_string())
+                .header(Self::HANDLED_BY_HEADER, handled_by.to_string())
+                .body(Empty::<Bytes>::new().map_err(|_| unreachable!()).boxed())
+                .unwrap(),
+            Some(TestUpgrade::Header(protocol)) => Response::builder()
                 .status(StatusCode::SWITCHING_PROTOCOLS)
                 .header(header::CONNECTION, "upgrade")
                 .header(header::UPGRADE, protocol.name())
@@ -347,10 +380,20 @@ impl TestRequest {
                 .unwrap(),
             self.user_header,
         );
-        assert_eq!(parts.uri.path(), self.path);
+        if matches!(self.upgrade, Some(TestUpgrade::Connect(..))).not() {
+            assert_eq!(parts.uri.path(), self.path);
+        }
 
         match self.upgrade {
-            Some(protocol) => {
+            Some(TestUpgrade::Connect(..)) => {
+                assert_eq!(parts.method, Method::CONNECT);
+                assert_eq!(
+                    parts.uri.authority().map(|authority| authority.as_str()),
+                    Some(TestUpgrade::CONNECT_AUTHORITY)
+                );
+                assert!(body.frame().await.is_none());
+            }
+            Some(TestUpgrade::Header(protocol)) => {
                 assert_eq!(parts.method, Method::GET);
                 assert!(body.frame().await.is_none());
                 assert_eq!(
@@ -414,7 +457,11 @@ impl TestRequest {
         );
 
         match self.upgrade {
-            Some(protocol) => {
+            Some(TestUpgrade::Connect(..)) => {
+                assert_eq!(response.status(), StatusCode::OK);
+                assert!(response.body_mut().frame().await.is_none());
+            }
+            Some(TestUpgrade::Header(protocol)) => {
                 assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
                 assert!(response.body_mut().frame().await.is_none());
                 assert_eq!(
@@ -494,7 +541,7 @@ impl TestRequest {
         let response = self.as_hyper_response(handled_by);
         request.response_tx.send(response).unwrap();
 
-        if let Some(protocol) = self.upgrade {
+        if let Some(protocol) = self.upgrade.map(TestUpgrade::protocol) {
             println!(
                 "[{}:{}] Processing upgrade to {protocol:?}",
                 file!(),
@@ -581,7 +628,7 @@ impl TestRequest {
         self.verify_response(response, expect_handled_by).await;
         println!("[{}:{}] Response ok", file!(), line!());
 
-        if let Some(protocol) = self.upgrade {
+        if let Some(protocol) = self.upgrade.map(TestUpgrade::protocol) {
             println!(
                 "[{}:{}] Processing upgrade to {protocol:?}",
                 file!(),
@@ -641,6 +688,18 @@ impl StealingClient {
         }
     }
 
+    /// Unsubscribes from the port of this client's [`StealType`].
+    pub async fn unsubscribe(&mut self) {
+        self.api
+            .handle_client_message(LayerTcpSteal::PortUnsubscribe(self.steal_type.get_port()))
+            .await
+            .unwrap();
+    }
+
+    pub async fn set_identity(&mut self, identity: ClientIdentity) {
+        self.api.set_identity(identity).await.unwrap();
+    }
+
     pub async fn expect_log(&mut self, level: LogLevel, containing: &str) {
         match self.api.recv().await.unwrap() {
             DaemonMessage::LogMessage(log) => {
@@ -834,7 +893,7 @@ impl StealingClient {
             .await
             .unwrap();
 
-        if let Some(protocol) = expected.upgrade {
+        if let Some(protocol) = expected.upgrade.map(TestUpgrade::protocol) {
             println!(
                 "[{}:{}] Processing upgrade to {protocol:?}",
                 file!(),
@@ -844,6 +903,74 @@ impl StealingClient {
         }
     }
 
+    /// Expects a request and echoes its body frames back in the response, as they arrive.
+    ///
+    /// Unlike [`Self::expect_request`], the response is started before the request body is
+    /// finished, so both bodies are streamed at the same time.
+    pub async fn expect_request_echo(&mut self) {
+        let request = match self.api.recv().await.unwrap() {
+            DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(
+                request,
+            ))) => request,
+            other => panic!(
+                "client {} received an unexpected message: {other:?}",
+                self.id
+            ),
+        };
+        println!(
+            "[{}:{}] Got request: {request:?}, echoing the body",
+            file!(),
+            line!(),
+        );
+
+        let response = ChunkedResponse::Start(HttpResponse {
+            port: self.steal_type.get_port(),
+            connection_id: request.connection_id,
+            request_id: request.request_id,
+            internal_response: InternalHttpResponse {
+                status: StatusCode::OK,
+                version: request.request.version,
+                headers: Default::default(),
+                body: request.request.body.frames,
+            },
+        });
+        self.api
+            .handle_client_message(LayerTcpSteal::HttpResponseChunked(response))
+            .await
+            .unwrap();
+
+        let mut frames = vec![];
+        let mut is_last = request.request.body.is_last;
+        loop {
+            self.api
+                .handle_client_message(LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Body(
+                    ChunkedRequestBodyV1 {
+                        frames,
+                        is_last,
+                        request_id: request.request_id,
+                        connection_id: request.connection_id,
+                    },
+                )))
+                .await
+                .unwrap();
+
+            if is_last {
+                break;
+            }
+
+            (frames, is_last) = match self.api.recv().await.unwrap() {
+                DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(
+                    body,
+                ))) => {
+                    assert_eq!(body.request_id, request.request_id);
+                    assert_eq!(body.connection_id, request.connection_id);
+                    (body.frames, body.is_last)
+                }
+                other => panic!("unexpected message: {other:?}"),
+            };
+        }
+    }
+
     pub async fn expect_tcp(
         &mut self,
         expect_connection_id: ConnectionId,
@@ -0,0 +1,445 @@
+//! Recording of the messages exchanged by the internal proxy with the agent, see
+//! `internal_proxy.protocol_capture`, and the `mirrord capture inspect` command that prints them.
+//!
+//! # Format
+//!
+//! A capture file is a sequence of records, each prefixed with its length (`u32`, little
+//! endian). A record is made of:
+//!
+//! 1. the format version (`u8`, [`FORMAT_VERSION`]);
+//! 2. the timestamp (`u64`, little endian, microseconds since the UNIX epoch);
+//! 3. the [`Direction`] (`u8`);
+//! 4. the flags (`u8`, see [`REDACTED_FLAG`]);
+//! 5. the message, encoded with bincode like on the wire.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read, Write},
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use mirrord_config::internal_proxy::InternalProxyConfig;
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse, Payload, RemoteEnvVars,
+};
+use mirrord_protocol_io::{Client, MessageTap};
+
+use crate::{
+    config::CaptureInspectArgs,
+    error::{CliError, CliResult},
+    logging::{
+        MEGABYTE,
+        rotation::{RotatingFile, RotationLimits},
+    },
+};
+
+/// Version of the capture format, bumped on incompatible changes.
+const FORMAT_VERSION: u8 = 1;
+
+/// Set when the sensitive contents of the message were removed, see
+/// `internal_proxy.protocol_capture_redact`.
+const REDACTED_FLAG: u8 = 1;
+
+/// Length of the fixed part of a record, before the message.
+const HEADER_LEN: usize = 1 + 8 + 1 + 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum Direction {
+    /// From the internal proxy to the agent.
+    Sent = 0,
+    /// From the agent to the internal proxy.
+    Received = 1,
+}
+
+/// [`MessageTap`] that appends the messages to a [`RotatingFile`].
+pub(crate) struct ProtocolCapture {
+    file: Mutex<RotatingFile>,
+    redact: bool,
+}
+
+impl ProtocolCapture {
+    /// Opens the capture file of this process, when `internal_proxy.protocol_capture` is set.
+    pub(crate) fn open(config: &InternalProxyConfig) -> io::Result<Option<Self>> {
+        let Some(prefix) = &config.protocol_capture else {
+            return Ok(None);
+        };
+
+        let mut path = prefix.clone().into_os_string();
+        path.push(format!("-{}.capture", std::process::id()));
+        let path = PathBuf::from(path);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = RotatingFile::open(
+            &path,
+            RotationLimits {
+                max_size: config.protocol_capture_max_size * MEGABYTE,
+                max_files: config.protocol_capture_max_files,
+            },
+        )?;
+        tracing::info!(path = %path.display(), "Capturing the messages exchanged with the agent");
+
+        Ok(Some(Self {
+            file: Mutex::new(file),
+            redact: config.protocol_capture_redact,
+        }))
+    }
+
+    fn record<M: bincode::Encode>(&self, direction: Direction, message: &M, redacted: bool) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let mut record = vec![0; 4];
+        record.push(FORMAT_VERSION);
+        record.extend_from_slice(&timestamp.to_le_bytes());
+        record.push(direction as u8);
+        record.push(if redacted { REDACTED_FLAG } else { 0 });
+
+        if let Err(error) =
+            bincode::encode_into_std_write(message, &mut record, bincode::config::standard())
+        {
+            tracing::warn!(%error, "Failed to encode a message for the protocol capture");
+            return;
+        }
+
+        let Ok(len) = u32::try_from(record.len() - 4) else {
+            tracing::warn!(
+                len = record.len(),
+                "Message too large for the protocol capture"
+            );
+            return;
+        };
+        record[..4].copy_from_slice(&len.to_le_bytes());
+
+        // Written at once, so that the rotation never splits a record.
+        let result = match self.file.lock() {
+            Ok(mut file) => file.write_all(&record),
+            Err(..) => return,
+        };
+        if let Err(error) = result {
+            tracing::warn!(%error, "Failed to write to the protocol capture");
+        }
+    }
+}
+
+impl MessageTap<Client> for ProtocolCapture {
+    fn sent(&self, message: &ClientMessage) {
+        let redacted = self
+            .redact
+            .then(|| redact_client_message(message))
+            .flatten();
+        match redacted {
+            Some(redacted) => self.record(Direction::Sent, &redacted, true),
+            None => self.record(Direction::Sent, message, false),
+        }
+    }
+
+    fn received(&self, message: &DaemonMessage) {
+        let redacted = self
+            .redact
+            .then(|| redact_daemon_message(message))
+            .flatten();
+        match redacted {
+            Some(redacted) => self.record(Direction::Received, &redacted, true),
+            None => self.record(Direction::Received, message, false),
+        }
+    }
+}
+
+/// Returns a copy of the message without the file contents, if it has any.
+fn redact_client_message(message: &ClientMessage) -> Option<ClientMessage> {
+    let mut message = message.clone();
+
+    match &mut message {
+        ClientMessage::FileRequest(FileRequest::Write(request)) => {
+            request.write_bytes = Payload::default();
+        }
+        ClientMessage::FileRequest(FileRequest::WriteLimited(request)) => {
+            request.write_bytes = Payload::default();
+        }
+        _ => return None,
+    }
+
+    Some(message)
+}
+
+/// Returns a copy of the message without the file contents or the environment variable values,
+/// if it has any.
+///
+/// Compressed messages are decompressed, so that their contents can be redacted as well.
+fn redact_daemon_message(message: &DaemonMessage) -> Option<DaemonMessage> {
+    let mut message = match message {
+        DaemonMessage::Compressed(compressed) => {
+            let decompressed = compressed.decompress().ok()?;
+            return redact_daemon_message(&decompressed);
+        }
+        message => message.clone(),
+    };
+
+    match &mut message {
+        DaemonMessage::File(FileResponse::Read(Ok(response)))
+        | DaemonMessage::File(FileResponse::ReadLimited(Ok(response))) => {
+            response.bytes = Payload::default();
+        }
+        DaemonMessage::GetEnvVarsResponse(Ok(RemoteEnvVars(vars))) => {
+            vars.values_mut().for_each(String::clear);
+        }
+        _ => return None,
+    }
+
+    Some(message)
+}
+
+/// Decoded message of a [`Record`].
+#[derive(Debug)]
+enum CapturedMessage {
+    Client(ClientMessage),
+    Daemon(DaemonMessage),
+}
+
+#[derive(Debug)]
+struct Record {
+    timestamp: SystemTime,
+    direction: Direction,
+    redacted: bool,
+    /// Length of the encoded message.
+    len: usize,
+    /// Failing to decode one message does not prevent reading the next ones.
+    message: Result<CapturedMessage, bincode::error::DecodeError>,
+}
+
+/// Reads the next [`Record`], returns [`None`] at the end of the capture.
+fn read_record<R: Read>(reader: &mut R) -> io::Result<Option<Record>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let len = u32::from_le_bytes(len) as usize;
+
+    let mut record = Vec::new();
+    reader.take(len as u64).read_to_end(&mut record)?;
+    if record.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the last record is truncated",
+        ));
+    }
+
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let (header, encoded) = record
+        .split_at_checked(HEADER_LEN)
+        .ok_or_else(|| invalid(format!("record of {len} bytes is too short")))?;
+    let [version, timestamp @ .., direction, flags] = header else {
+        unreachable!("the header has a fixed length");
+    };
+
+    if *version != FORMAT_VERSION {
+        return Err(invalid(format!(
+            "unsupported capture format version {version}"
+        )));
+    }
+
+    let timestamp = u64::from_le_bytes(timestamp.try_into().expect("timestamp is 8 bytes"));
+    let direction = match direction {
+        0 => Direction::Sent,
+        1 => Direction::Received,
+        other => return Err(invalid(format!("invalid direction {other}"))),
+    };
+
+    let config = bincode::config::standard();
+    let message = match direction {
+        Direction::Sent => bincode::decode_from_slice(encoded, config)
+            .map(|(message, _)| CapturedMessage::Client(message)),
+        Direction::Received => bincode::decode_from_slice(encoded, config)
+            .map(|(message, _)| CapturedMessage::Daemon(message)),
+    };
+
+    Ok(Some(Record {
+        timestamp: UNIX_EPOCH + Duration::from_micros(timestamp),
+        direction,
+        redacted: flags & REDACTED_FLAG != 0,
+        len: encoded.len(),
+        message,
+    }))
+}
+
+/// Cuts the text after `max_len` characters, noting how much was left out.
+fn truncate(text: String, max_len: usize) -> String {
+    match text.char_indices().nth(max_len) {
+        Some((index, _)) => format!("{}... ({} more bytes)", &text[..index], text.len() - index),
+        None => text,
+    }
+}
+
+fn format_record(record: &Record, max_len: usize) -> String {
+    let direction = match record.direction {
+        Direction::Sent => "->",
+        Direction::Received => "<-",
+    };
+    let redacted = if record.redacted { ", redacted" } else { "" };
+
+    let message = match &record.message {
+        Ok(CapturedMessage::Client(message)) => format!("{message:?}"),
+        Ok(CapturedMessage::Daemon(DaemonMessage::Compressed(compressed))) => {
+            match compressed.decompress() {
+                Ok(message) => format!("{message:?} (compressed)"),
+                Err(error) => format!("failed to decompress the message: {error}"),
+            }
+        }
+        Ok(CapturedMessage::Daemon(message)) => format!("{message:?}"),
+        Err(error) => format!("failed to decode the message: {error}"),
+    };
+
+    format!(
+        "{} {direction} [{} bytes{redacted}] {}",
+        humantime::format_rfc3339_micros(record.timestamp),
+        record.len,
+        truncate(message, max_len),
+    )
+}
+
+/// Prints the messages of a capture file, `->` marking the ones sent to the agent and `<-` the
+/// ones received from it.
+pub(crate) fn inspect_command(args: CaptureInspectArgs) -> CliResult<()> {
+    let read_error = |error| CliError::CaptureRead(args.path.clone(), error);
+
+    let mut reader = BufReader::new(File::open(&args.path).map_err(read_error)?);
+    let mut stdout = io::stdout().lock();
+
+    while let Some(record) = read_record(&mut reader).map_err(read_error)? {
+        // Stop quietly when the output is closed, e.g. piped to `head`.
+        if writeln!(stdout, "{}", format_record(&record, args.max_len)).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, io::Cursor, ops::Not};
+
+    use mirrord_config::{
+        config::{ConfigContext, MirrordConfig},
+        internal_proxy::InternalProxyFileConfig,
+    };
+    use mirrord_protocol::{
+        ClientMessage, DaemonMessage, FileRequest, FileResponse, Payload, RemoteEnvVars,
+        file::{ReadFileResponse, WriteFileRequest},
+    };
+    use mirrord_protocol_io::MessageTap;
+
+    use super::{CapturedMessage, Direction, ProtocolCapture};
+
+    fn capture(dir: &std::path::Path, redact: bool) -> ProtocolCapture {
+        let mut config = InternalProxyFileConfig::default()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+        config.protocol_capture = Some(dir.join("capture"));
+        config.protocol_capture_redact = redact;
+
+        ProtocolCapture::open(&config).unwrap().unwrap()
+    }
+
+    fn read_all(dir: &std::path::Path) -> Vec<super::Record> {
+        let path = dir.join(format!("capture-{}.capture", std::process::id()));
+        let mut reader = Cursor::new(std::fs::read(path).unwrap());
+
+        std::iter::from_fn(|| super::read_record(&mut reader).unwrap()).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let capture = capture(dir.path(), false);
+
+        capture.sent(&ClientMessage::Ping);
+        capture.received(&DaemonMessage::Pong);
+
+        let records = read_all(dir.path());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Sent);
+        assert!(matches!(
+            records[0].message,
+            Ok(CapturedMessage::Client(ClientMessage::Ping))
+        ));
+        assert_eq!(records[1].direction, Direction::Received);
+        assert!(matches!(
+            records[1].message,
+            Ok(CapturedMessage::Daemon(DaemonMessage::Pong))
+        ));
+        assert!(records.iter().all(|record| record.redacted.not()));
+    }
+
+    #[test]
+    fn redacts_sensitive_payloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let capture = capture(dir.path(), true);
+
+        capture.sent(&ClientMessage::FileRequest(FileRequest::Write(
+            WriteFileRequest {
+                fd: 3,
+                write_bytes: Payload::from(b"secret".to_vec()),
+            },
+        )));
+        capture.received(&DaemonMessage::File(FileResponse::Read(Ok(
+            ReadFileResponse {
+                bytes: Payload::from(b"secret".to_vec()),
+                read_amount: 6,
+            },
+        ))));
+        capture.received(&DaemonMessage::GetEnvVarsResponse(Ok(RemoteEnvVars(
+            HashMap::from([("TOKEN".to_string(), "secret".to_string())]),
+        ))));
+        capture.sent(&ClientMessage::Ping);
+
+        let records = read_all(dir.path());
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.redacted)
+                .collect::<Vec<_>>(),
+            [true, true, true, false]
+        );
+
+        match &records[0].message {
+            Ok(CapturedMessage::Client(ClientMessage::FileRequest(FileRequest::Write(
+                request,
+            )))) => {
+                assert!(request.write_bytes.is_empty())
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+        match &records[1].message {
+            Ok(CapturedMessage::Daemon(DaemonMessage::File(FileResponse::Read(Ok(response))))) => {
+                assert!(response.bytes.is_empty());
+                assert_eq!(response.read_amount, 6);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+        match &records[2].message {
+            Ok(CapturedMessage::Daemon(DaemonMessage::GetEnvVarsResponse(Ok(vars)))) => {
+                assert_eq!(vars.0.get("TOKEN").map(String::as_str), Some(""));
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn truncates_long_messages() {
+        assert_eq!(super::truncate("short".into(), 10), "short");
+        assert_eq!(
+            super::truncate("a".repeat(15), 10),
+            "aaaaaaaaaa... (5 more bytes)"
+        );
+    }
+}
@@ -7,6 +7,7 @@ use std::{
     collections::HashMap,
     ffi::{OsStr, OsString},
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    ops::Not,
     path::PathBuf,
     str::FromStr,
 };
@@ -15,7 +16,9 @@ use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum, ValueHint};
 use clap_complete::Shell;
 pub use mirrord_config::container::ContainerRuntime;
 use mirrord_config::{
-    LayerConfig, env_key,
+    LayerConfig,
+    config::expand::STRICT_VARS_ENV,
+    env_key,
     feature::{
         env::{
             MIRRORD_OVERRIDE_ENV_FILE_ENV, MIRRORD_OVERRIDE_ENV_VARS_EXCLUDE_ENV,
@@ -161,6 +164,12 @@ pub(super) enum Commands {
         _debug_args: Vec<OsString>,
     },
 
+    /// Spawned by [`Commands::Exec`] when `feature.target_logs` is enabled.
+    ///
+    /// Streams the target container's logs to stderr until the user application exits.
+    #[command(hide = true, name = "target-logs")]
+    TargetLogs,
+
     /// Forward local ports to hosts available from the cluster
     /// or intercept traffic and direct it to local ports (unstable).
     #[command(name = "port-forward")]
@@ -176,9 +185,16 @@ pub(super) enum Commands {
     #[command(hide = true)]
     VerifyConfig(VerifyConfigArgs),
 
+    /// Inspect the mirrord config.
+    Config(Box<ConfigArgs>),
+
+    /// Inspect the protocol captures of the internal proxy, see
+    /// `internal_proxy.protocol_capture`.
+    Capture(Box<CaptureArgs>),
+
     /// Try out mirrord for Teams.
     #[cfg_attr(target_os = "windows", command(hide = true))]
-    Teams,
+    Teams(TeamsArgs),
 
     /// Diagnose mirrord setup.
     Diagnose(Box<DiagnoseArgs>),
@@ -209,6 +225,10 @@ pub(super) enum Commands {
 
     /// Fix issues related to mirrord.
     Fix(FixArgs),
+
+    /// Replace this mirrord binary with the latest (or a specific) release from GitHub.
+    #[command(name = "self-update")]
+    SelfUpdate(SelfUpdateArgs),
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -296,7 +316,8 @@ pub(super) struct ExecParams {
 
     /// Load config from config file
     /// When using -f flag without a value, defaults to "./.mirrord/mirrord.json"
-    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+    /// (or "./.mirrord/mirrord.yaml", ".yml", ".toml", whichever exists)
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = DEFAULT_CONFIG_FILE, value_parser = config_file_path, num_args = 0..=1)]
     pub config_file: Option<PathBuf>,
 
     /// Kube context to use from Kubeconfig
@@ -317,6 +338,35 @@ pub(super) struct ExecParams {
     /// If not provided here or in the config file, a unique key is generated automatically.
     #[arg(long)]
     pub key: Option<String>,
+
+    /// Resolve the target and print the cluster operations mirrord would perform (agent
+    /// spec, or operator requests when the operator is used), then exit without creating
+    /// anything.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Stream the target container's logs to stderr for the duration of the session.
+    #[arg(long)]
+    pub follow_target_logs: bool,
+
+    /// Override a config value, e.g. `--set agent.namespace=staging`. Can be repeated.
+    ///
+    /// Keys are paths in the config printed by `mirrord config resolved`. Values are parsed as
+    /// JSON, and taken as strings when they are not valid JSON.
+    ///
+    /// Wins over the config file and environment variables.
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = config_override)]
+    pub set: Vec<String>,
+
+    /// Fail when a `${...}` reference in the config file cannot be resolved, instead of leaving
+    /// it as it is.
+    #[arg(long)]
+    pub strict_vars: bool,
+
+    /// Format of the HTTP filter dry run reports, see
+    /// `feature.network.incoming.http_filter.dry_run`. Defaults to a table.
+    #[arg(long = "output", value_name = "FORMAT", value_enum)]
+    pub output: Option<Format>,
 }
 
 impl ExecParams {
@@ -425,6 +475,27 @@ impl ExecParams {
                 Cow::Borrowed(key.as_ref()),
             );
         }
+        if self.follow_target_logs {
+            envs.insert(
+                "MIRRORD_TARGET_LOGS".as_ref(),
+                Cow::Borrowed("true".as_ref()),
+            );
+        }
+        if let Some(Format::Json) = self.output {
+            envs.insert(
+                "MIRRORD_HTTP_FILTER_DRY_RUN_OUTPUT".as_ref(),
+                Cow::Borrowed("json".as_ref()),
+            );
+        }
+        if self.set.is_empty().not() {
+            envs.insert(
+                LayerConfig::SET_ENV.as_ref(),
+                Cow::Owned(self.set.join("\n").into()),
+            );
+        }
+        if self.strict_vars {
+            envs.insert(STRICT_VARS_ENV.as_ref(), Cow::Borrowed("true".as_ref()));
+        }
 
         envs
     }
@@ -492,7 +563,7 @@ pub(super) struct TargetParams {
     /// - `rollout/{rollout-name}[/container/{container-name}]`
     /// - `job/{job-name}[/container/{container-name}]`
     /// - `cronjob/{cronjob-name}[/container/{container-name}]`
-    /// - `statefulset/{statefulset-name}[/container/{container-name}]`
+    /// - `statefulset/{statefulset-name}[/pod/{ordinal}][/container/{container-name}]`
     /// - `service/{service-name}[/container/{container-name}]`
     /// - `replicaset/{replicaset-name}[/container/{container-name}]`
     ///
@@ -505,6 +576,11 @@ pub(super) struct TargetParams {
     /// Defaults to the user default namespace.
     #[arg(short = 'n', long)]
     pub target_namespace: Option<String>,
+
+    /// When targeting a cron job, create a new job from its job template and target that,
+    /// instead of the most recent active job.
+    #[arg(long)]
+    pub create_job: bool,
 }
 
 impl TargetParams {
@@ -520,6 +596,9 @@ impl TargetParams {
         if let Some(namespace) = &self.target_namespace {
             envs.insert("MIRRORD_TARGET_NAMESPACE".as_ref(), namespace.as_ref());
         }
+        if self.create_job {
+            envs.insert("MIRRORD_TARGET_CREATE_JOB".as_ref(), "true".as_ref());
+        }
 
         envs
     }
@@ -548,6 +627,10 @@ pub(super) struct AgentParams {
     #[arg(long)]
     pub agent_startup_timeout: Option<u16>,
 
+    /// Wait for the agent startup without a timeout, useful when debugging a slow startup.
+    #[arg(long, conflicts_with = "agent_startup_timeout")]
+    pub wait_forever: bool,
+
     /// Spawn the agent in an ephemeral container.
     #[arg(short, long)]
     pub ephemeral_container: bool,
@@ -596,6 +679,12 @@ impl AgentParams {
                 Cow::Owned(agent_startup_timeout),
             );
         }
+        if self.wait_forever {
+            envs.insert(
+                "MIRRORD_AGENT_STARTUP_TIMEOUT".as_ref(),
+                Cow::Borrowed("0".as_ref()),
+            );
+        }
         if self.ephemeral_container {
             envs.insert(
                 "MIRRORD_EPHEMERAL_CONTAINER".as_ref(),
@@ -633,7 +722,8 @@ pub(super) struct PortForwardArgs {
     /// Load config from config file.
     ///
     /// When using this argument without a value, defaults to "./.mirrord/mirrord.json"
-    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+    /// (or "./.mirrord/mirrord.yaml", ".yml", ".toml", whichever exists)
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = DEFAULT_CONFIG_FILE, value_parser = config_file_path, num_args = 0..=1)]
     pub config_file: Option<PathBuf>,
 
     /// Kube context to use from the Kubeconfig.
@@ -801,8 +891,12 @@ pub(super) enum OperatorCommand {
     /// Print operator status
     Status {
         /// Specify config file to use
-        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = DEFAULT_CONFIG_FILE, value_parser = config_file_path, num_args = 0..=1)]
         config_file: Option<PathBuf>,
+
+        /// Kube context to use from the Kubeconfig.
+        #[arg(long)]
+        context: Option<String>,
     },
     /// Operator session management commands.
     ///
@@ -812,7 +906,8 @@ pub(super) enum OperatorCommand {
         command: SessionCommand,
         /// Load config from config file.
         /// When using -f flag without a value, defaults to "./.mirrord/mirrord.json"
-        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+        /// (or "./.mirrord/mirrord.yaml", ".yml", ".toml", whichever exists)
+        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = DEFAULT_CONFIG_FILE, value_parser = config_file_path, num_args = 0..=1)]
         config_file: Option<PathBuf>,
     },
 }
@@ -849,6 +944,39 @@ impl core::fmt::Display for SessionCommand {
     }
 }
 
+/// Default value of the `-f` flag, when it's used without a value.
+const DEFAULT_CONFIG_FILE: &str = "./.mirrord/mirrord.json";
+
+/// Parses the `-f` flag value.
+///
+/// When [`DEFAULT_CONFIG_FILE`] does not exist, falls back to the first existing
+/// `./.mirrord/mirrord.yaml`, `./.mirrord/mirrord.yml` or `./.mirrord/mirrord.toml`.
+fn config_file_path(raw: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(raw);
+    if raw != DEFAULT_CONFIG_FILE || path.exists() {
+        return Ok(path);
+    }
+
+    Ok(["yaml", "yml", "toml"]
+        .into_iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|path| path.exists())
+        .unwrap_or(path))
+}
+
+/// Parses the `--set` flag value.
+///
+/// The values are passed to the config in a single environment variable, one per line, see
+/// [`LayerConfig::SET_ENV`].
+fn config_override(raw: &str) -> Result<String, String> {
+    match raw.split_once('=') {
+        Some((key, _)) if key.is_empty() => Err("the key is empty".into()),
+        Some(..) if raw.contains('\n') => Err("the value cannot contain newlines".into()),
+        Some(..) => Ok(raw.to_owned()),
+        None => Err("must be in the `KEY=VALUE` format".into()),
+    }
+}
+
 /// Parses the operator session id from hex (without `0x` prefix) into `u64`.
 fn hex_id(raw: &str) -> Result<u64, String> {
     u64::from_str_radix(raw, 16)
@@ -858,6 +986,8 @@ fn hex_id(raw: &str) -> Result<u64, String> {
 #[derive(ValueEnum, Clone, Debug)]
 pub enum Format {
     Json,
+    /// Human readable, e.g. one target per line in `mirrord ls`.
+    Text,
 }
 
 #[derive(Args, Debug)]
@@ -884,6 +1014,10 @@ pub(super) struct ListTargetArgs {
     /// Can be used multiple times to specify multiple target types.
     #[arg(short = 't', long)]
     pub target_type: Option<Vec<TargetType>>,
+
+    /// Kube context to use from the Kubeconfig.
+    #[arg(long)]
+    pub context: Option<String>,
 }
 
 impl ListTargetArgs {
@@ -918,6 +1052,68 @@ pub(super) struct VerifyConfigArgs {
     pub(super) path: PathBuf,
 }
 
+/// Args for the `mirrord config` command.
+#[derive(Args, Debug)]
+pub(super) struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub(super) enum ConfigCommand {
+    /// Print the fully resolved config (file, environment variables and flags) as JSON.
+    ///
+    /// Secret values are never printed, only their presence.
+    Resolved(ConfigResolvedArgs),
+}
+
+/// Args for the [`mod@super::resolved_config`] mirrord-cli command.
+#[derive(Args, Debug)]
+pub(super) struct ConfigResolvedArgs {
+    #[clap(flatten)]
+    pub params: Box<ExecParams>,
+
+    /// Annotate each value with where it came from: default, file (with line), environment
+    /// variable or CLI flag.
+    #[arg(long)]
+    pub show_sources: bool,
+}
+
+/// Args for the `mirrord capture` command.
+#[derive(Args, Debug)]
+pub(super) struct CaptureArgs {
+    #[command(subcommand)]
+    pub command: CaptureCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub(super) enum CaptureCommand {
+    /// Print the messages of a capture file, `->` marking the ones sent to the agent and `<-` the
+    /// ones received from it.
+    Inspect(CaptureInspectArgs),
+}
+
+/// Args for the [`mod@super::capture`] mirrord-cli command.
+#[derive(Args, Debug)]
+pub(super) struct CaptureInspectArgs {
+    /// Path of the capture file.
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub path: PathBuf,
+
+    /// Truncate the printed messages after this many characters.
+    #[arg(long, default_value_t = 200)]
+    pub max_len: usize,
+}
+
+#[derive(Args, Debug)]
+pub(super) struct TeamsArgs {
+    /// Only print the link, without opening the browser.
+    ///
+    /// Implied when stdout is not a terminal.
+    #[arg(long)]
+    pub no_browser: bool,
+}
+
 #[derive(Args, Debug)]
 pub(super) struct CompletionsArgs {
     pub(super) shell: Shell,
@@ -935,7 +1131,7 @@ pub(super) enum DiagnoseCommand {
     /// Check network connectivity and provide RTT (latency) statistics.
     Latency {
         /// Specify config file to use
-        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = DEFAULT_CONFIG_FILE, value_parser = config_file_path, num_args = 0..=1)]
         config_file: Option<PathBuf>,
     },
 }
@@ -1047,7 +1243,8 @@ pub(super) struct VpnArgs {
 
     /// Load config from config file
     /// When using -f flag without a value, defaults to "./.mirrord/mirrord.json"
-    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+    /// (or "./.mirrord/mirrord.yaml", ".yml", ".toml", whichever exists)
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = DEFAULT_CONFIG_FILE, value_parser = config_file_path, num_args = 0..=1)]
     pub config_file: Option<PathBuf>,
 
     #[cfg(target_os = "macos")]
@@ -1070,7 +1267,7 @@ pub(super) enum CiCommand {
     /// `MIRRORD_CI_API_KEY`.
     ApiKey {
         /// Specify config file to use
-        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = DEFAULT_CONFIG_FILE, value_parser = config_file_path, num_args = 0..=1)]
         config_file: Option<PathBuf>,
     },
     /// Starts mirrord for ci. Takes the same arguments as `mirrord exec` plus ci specific options.
@@ -1096,7 +1293,8 @@ pub(super) struct DbBranchesArgs {
 
     /// Load config from config file
     /// When using -f flag without a value, defaults to "./.mirrord/mirrord.json"
-    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+    /// (or "./.mirrord/mirrord.yaml", ".yml", ".toml", whichever exists)
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = DEFAULT_CONFIG_FILE, value_parser = config_file_path, num_args = 0..=1)]
     pub config_file: Option<PathBuf>,
 
     #[command(subcommand)]
@@ -1172,6 +1370,23 @@ pub struct FixKubeconfig {
     pub dry_run: bool,
 }
 
+/// `mirrord self-update` args.
+#[derive(Args, Debug)]
+pub(super) struct SelfUpdateArgs {
+    /// Install this release instead of the latest one, e.g. `3.150.0`.
+    #[arg(long)]
+    pub version: Option<semver::Version>,
+
+    /// Only report what would be downloaded and replaced, without changing anything.
+    #[arg(long)]
+    pub check: bool,
+
+    /// Allow replacing a binary that lives in a path owned by root (or in Program Files on
+    /// Windows), which usually means running this command with elevated privileges.
+    #[arg(long)]
+    pub allow_elevated: bool,
+}
+
 /// Arguments for `mirrord preview` command.
 #[derive(Args, Debug)]
 pub(super) struct PreviewArgs {
@@ -1203,7 +1418,8 @@ pub(super) struct PreviewCommonArgs {
     /// Load config from config file.
     ///
     /// When using -f flag without a value, defaults to "./.mirrord/mirrord.json"
-    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+    /// (or "./.mirrord/mirrord.yaml", ".yml", ".toml", whichever exists)
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = DEFAULT_CONFIG_FILE, value_parser = config_file_path, num_args = 0..=1)]
     pub config_file: Option<PathBuf>,
 
     /// Kube context to use from Kubeconfig.
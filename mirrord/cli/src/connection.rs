@@ -3,17 +3,21 @@ use std::{collections::HashSet, time::Duration};
 use mirrord_analytics::Reporter;
 use mirrord_config::{
     LayerConfig,
-    target::{Target, TargetDisplay},
+    target::{Target, TargetDisplay, job::JobTarget},
 };
 use mirrord_intproxy::agent_conn::AgentConnectInfo;
 use mirrord_kube::{
-    api::{container::ContainerConfig, kubernetes::KubernetesAPI},
-    error::KubeApiError,
+    api::{
+        container::ContainerConfig,
+        kubernetes::{KubernetesAPI, selected_kube_context},
+        runtime::cron_job::create_job_from_cron_job,
+    },
     resolved::ResolvedTarget,
 };
 use mirrord_operator::{
     client::{OperatorApi, OperatorSessionConnection},
     crd::NewOperatorFeature,
+    types::{ownership_labels, resolve_isolation_marker},
 };
 use mirrord_progress::{
     IdeAction, IdeMessage, NotificationLevel, Progress,
@@ -22,7 +26,9 @@ use mirrord_progress::{
 use mirrord_protocol_io::{Client, Connection};
 use tracing::Level;
 
-use crate::{CliError, CliResult, MirrordCi, ci::error::CiError};
+use crate::{
+    CliError, CliResult, MirrordCi, ci::error::CiError, https, kube::kube_client_from_layer_config,
+};
 
 pub const AGENT_CONNECT_INFO_ENV_KEY: &str = "MIRRORD_AGENT_CONNECT_INFO";
 
@@ -148,6 +154,44 @@ where
     Ok(Some(connection))
 }
 
+/// When [`TargetConfig::create_job`](mirrord_config::target::TargetConfig::create_job) is set,
+/// creates a new job from the job template of the target cron job, and replaces the target with
+/// this job.
+///
+/// The job is labeled with [`ownership_labels`], so that it gets cleaned up after the session. It
+/// is also owned by the cron job, so it counts towards the cron job's history limits.
+async fn create_job_for_cron_job_target<P: Progress>(
+    config: &mut LayerConfig,
+    progress: &P,
+) -> CliResult<()> {
+    let cron_job = match config.target.path.as_ref() {
+        Some(Target::CronJob(cron_job)) if config.target.create_job => cron_job.clone(),
+        _ => return Ok(()),
+    };
+
+    let mut subtask = progress.subtask("creating job from cron job");
+    let client = kube_client_from_layer_config(config).await?;
+    let job = create_job_from_cron_job(
+        &client,
+        config.target.namespace.as_deref(),
+        &cron_job.cron_job,
+        ownership_labels(&resolve_isolation_marker()),
+    )
+    .await
+    .map_err(|error| CliError::friendlier_error_or_else(error, CliError::CreateJobFailed))?;
+
+    let job = job.metadata.name.unwrap_or_default();
+    subtask.success(Some(&format!("created job `{job}`")));
+
+    config.target.path = Some(Target::Job(JobTarget {
+        job,
+        container: cron_job.container,
+    }));
+    config.target.create_job = false;
+
+    Ok(())
+}
+
 /// 1. If mirrord-operator is explicitly enabled in the given [`LayerConfig`], makes a connection
 ///    with the target using the mirrord-operator.
 /// 2. If mirrord-operator is explicitly disabled in the given [`LayerConfig`], creates a
@@ -164,9 +208,24 @@ pub(crate) async fn create_and_connect<P: Progress, R: Reporter>(
     branch_name: Option<String>,
     mirrord_for_ci: Option<&MirrordCi>,
 ) -> CliResult<(AgentConnectInfo, Connection<Client>)> {
+    if let Some(context) =
+        selected_kube_context(config.kubeconfig.as_ref(), config.kube_context.as_deref())
+    {
+        progress.info(&format!("using kube context `{context}`"));
+    }
+
+    create_job_for_cron_job_target(config, progress).await?;
+
     if let Some(connection) =
         try_connect_using_operator(config, progress, analytics, branch_name, mirrord_for_ci).await?
     {
+        if config.feature.network.incoming.https.enabled {
+            progress.warning(
+                "`feature.network.incoming.https` is ignored when using the mirrord operator, \
+                stealing TLS traffic is configured with `MirrordTlsStealConfig` resources",
+            );
+        }
+
         return Ok((
             AgentConnectInfo::Operator(connection.session),
             connection.conn,
@@ -186,22 +245,16 @@ pub(crate) async fn create_and_connect<P: Progress, R: Reporter>(
         .inspect_err(|fail| tracing::debug!(?fail, "Failed to detect OpenShift!"))
         .ok();
 
-    let agent_container_config = ContainerConfig {
-        support_ipv6: config.feature.network.ipv6,
-        ..Default::default()
-    };
-    let agent_connect_info = tokio::time::timeout(
-        Duration::from_secs(config.agent.startup_timeout),
-        k8s_api.create_agent(
+    let agent_container_config = agent_container_config(config, &k8s_api).await?;
+    let agent_connect_info = k8s_api
+        .create_agent(
             progress,
             &config.target,
             Some(&mut config.feature.network),
             agent_container_config,
-        ),
-    )
-    .await
-    .unwrap_or(Err(KubeApiError::AgentReadyTimeout))
-    .map_err(|error| CliError::friendlier_error_or_else(error, CliError::CreateAgentFailed))?;
+        )
+        .await
+        .map_err(|error| CliError::friendlier_error_or_else(error, CliError::CreateAgentFailed))?;
 
     let conn = Connection::<Client>::from_stream(
         k8s_api
@@ -216,9 +269,39 @@ pub(crate) async fn create_and_connect<P: Progress, R: Reporter>(
     Ok((AgentConnectInfo::DirectKubernetes(agent_connect_info), conn))
 }
 
+/// Builds the [`ContainerConfig`] of the agent spawned without the operator.
+pub(crate) async fn agent_container_config(
+    config: &LayerConfig,
+    k8s_api: &KubernetesAPI,
+) -> CliResult<ContainerConfig> {
+    Ok(ContainerConfig {
+        support_ipv6: config.feature.network.ipv6,
+        steal_tls_config: https::steal_tls_config(
+            &config.feature.network.incoming,
+            k8s_api.client(),
+            config.target.namespace.as_deref(),
+        )
+        .await?,
+        ignore_local_traffic: config.feature.network.incoming.ignore_localhost,
+        ignore_health_checks: config.feature.network.incoming.ignore_health_checks,
+        // Keeps the agent alive while the internal proxy reconnects.
+        idle_ttl: if config.internal_proxy.reconnect_without_operator
+            && config.internal_proxy.reconnect_max_attempts > 0
+        {
+            Duration::from_secs(config.internal_proxy.reconnect_max_downtime)
+        } else {
+            Duration::ZERO
+        },
+        ..Default::default()
+    })
+}
+
 /// Verifies and adjusts the [`LayerConfig`] after we've determined that this run does not use the
 /// operator.
-fn process_config_oss<P: Progress>(config: &mut LayerConfig, progress: &mut P) -> CliResult<()> {
+pub(crate) fn process_config_oss<P: Progress>(
+    config: &mut LayerConfig,
+    progress: &mut P,
+) -> CliResult<()> {
     // operator is disabled, but target requires it.
     if let Some(target) = config.target.path.as_ref()
         && Target::requires_operator(target)
@@ -234,7 +317,7 @@ fn process_config_oss<P: Progress>(config: &mut LayerConfig, progress: &mut P) -
     }
 
     match (
-        // user in mutipod without operator
+        // user in mutipod without operator, and without mirroring all the replicas
         matches!(
             config.target,
             mirrord_config::target::TargetConfig {
@@ -244,9 +327,9 @@ fn process_config_oss<P: Progress>(config: &mut LayerConfig, progress: &mut P) -
                 ),
                 ..
             }
-        ),
+        ) && !config.feature.network.incoming.all_replicas,
         // user using http filter(s) without operator
-        config.feature.network.incoming.http_filter.is_filter_set(),
+        config.feature.network.incoming.uses_http_filter(),
     ) {
         (true, true) => {
             // only show user one of the two msgs - each user should always be shown same msg
@@ -337,7 +420,10 @@ mod tests {
     use mirrord_config::{
         LayerFileConfig,
         config::{ConfigContext, MirrordConfig},
-        target::{Target, TargetFileConfig, pod::PodTarget, service::ServiceTarget},
+        target::{
+            Target, TargetFileConfig, cron_job::CronJobTarget, pod::PodTarget,
+            service::ServiceTarget, stateful_set::StatefulSetTarget,
+        },
     };
     use mirrord_progress::NullProgress;
     use rstest::rstest;
@@ -348,6 +434,10 @@ mod tests {
     /// This occurs when `create_and_connect` fails to establish a connection with the operator.
     #[rstest]
     #[case(Target::Pod(PodTarget{pod: "my-pet-pod".into(),container: None}))]
+    #[case(Target::CronJob(CronJobTarget{cron_job: "nightly-cleanup".into(),container: None}))]
+    #[case(Target::StatefulSet(
+        StatefulSetTarget{stateful_set: "postgres".into(), ordinal: Some(1), container: None}
+    ))]
     #[case(Target::Service(
         ServiceTarget{service: "service-for-world-domination".into(),container: None}
     ))]
@@ -230,6 +230,11 @@ pub async fn container_command(
 
     adjust_container_config_for_wsl(runtime_args.runtime, &mut config);
 
+    if exec_params.dry_run {
+        crate::dry_run::dry_run(&mut config, &mut progress, &mut analytics).await?;
+        return Ok(0);
+    }
+
     let (runtime_command, _execution_info, _tls_setup) =
         prepare_proxies(&mut analytics, &progress, &mut config, runtime_args.runtime).await?;
 
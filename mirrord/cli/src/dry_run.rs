@@ -0,0 +1,241 @@
+//! Implementation of `--dry-run` for `mirrord exec` and `mirrord container`.
+//!
+//! Resolves the target and prints everything that would be created in (or requested from) the
+//! cluster, without creating anything.
+
+use std::fmt::Write;
+
+use mirrord_agent_env::steal_tls::AgentServerAuthentication;
+use mirrord_analytics::Reporter;
+use mirrord_config::LayerConfig;
+use mirrord_kube::api::{
+    container::ContainerConfig,
+    kubernetes::{KubernetesAPI, dry_run::AgentPlan},
+};
+use mirrord_operator::client::{OperatorApi, dry_run::PlannedOperatorRequest};
+use mirrord_progress::Progress;
+
+use crate::{
+    CliError, CliResult,
+    connection::{agent_container_config, process_config_oss},
+};
+
+/// Prints the cluster operations this run would perform, following the same operator/OSS
+/// decision as [`create_and_connect`](crate::connection::create_and_connect).
+pub(crate) async fn dry_run<P: Progress, R: Reporter>(
+    config: &mut LayerConfig,
+    progress: &mut P,
+    analytics: &mut R,
+) -> CliResult<()> {
+    let mut subtask = progress.subtask("planning cluster operations");
+
+    let operator_api = if config.operator == Some(false) {
+        None
+    } else {
+        OperatorApi::try_new(config, analytics, &subtask).await?
+    };
+
+    let output = match operator_api {
+        Some(api) => {
+            subtask.info("operator found, the session would be started by the operator");
+            render_operator_plan(&api.plan_session(config))
+        }
+        None if config.operator == Some(true) => return Err(CliError::OperatorNotInstalled),
+        None => {
+            process_config_oss(config, &mut subtask)?;
+
+            let k8s_api = KubernetesAPI::create(config, &subtask)
+                .await
+                .map_err(|error| {
+                    CliError::friendlier_error_or_else(error, CliError::DryRunFailed)
+                })?;
+            let mut container_config = agent_container_config(config, &k8s_api).await?;
+            redact_private_keys(&mut container_config);
+            let plan = k8s_api
+                .plan_agent(&subtask, &config.target, container_config)
+                .await
+                .map_err(|error| {
+                    CliError::friendlier_error_or_else(error, CliError::DryRunFailed)
+                })?;
+
+            render_agent_plan(&plan)?
+        }
+    };
+
+    subtask.success(Some("nothing was created"));
+    progress.success(None);
+    println!("{output}");
+
+    Ok(())
+}
+
+/// Printed in place of the private keys of the stolen TLS ports.
+const REDACTED: &str = "<redacted>";
+
+/// Replaces the inline private keys in the TLS steal config, which ends up in the printed agent
+/// spec.
+fn redact_private_keys(container_config: &mut ContainerConfig) {
+    for tls in &mut container_config.steal_tls_config {
+        if let AgentServerAuthentication::Inline(inline) = &mut tls.agent_as_server.authentication {
+            inline.key_pem_data = REDACTED.to_owned();
+        }
+    }
+}
+
+fn render_operator_plan(requests: &[PlannedOperatorRequest]) -> String {
+    let mut output = String::from("mirrord would make the following requests to the operator:\n");
+
+    for (index, request) in requests.iter().enumerate() {
+        let _ = writeln!(output, "\n{}. {} {}", index + 1, request.verb, request.url);
+        if let Some(note) = request.note {
+            let _ = writeln!(output, "   ({note})");
+        }
+        let _ = writeln!(output, "   headers: {}", request.headers.join(", "));
+    }
+
+    output
+}
+
+fn render_agent_plan(plan: &AgentPlan) -> CliResult<String> {
+    let mut output = format!(
+        "mirrord would spawn the agent as a {} with the following operations:\n",
+        plan.strategy
+    );
+
+    for (index, operation) in plan.operations.iter().enumerate() {
+        let _ = writeln!(
+            output,
+            "\n{}. {} {}",
+            index + 1,
+            operation.verb,
+            operation.path
+        );
+        if let Some(body) = &operation.body {
+            let yaml = serde_yaml::to_string(body)?;
+            for line in yaml.lines() {
+                let _ = writeln!(output, "   {line}");
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_agent_env::steal_tls::{
+        AgentClientConfig, AgentServerAuthentication, AgentServerConfig, InlineTlsAuthentication,
+        StealPortTlsConfig, TlsServerVerification,
+    };
+    use mirrord_kube::api::{
+        container::ContainerConfig,
+        kubernetes::dry_run::{AgentPlan, AgentStrategy, PlannedOperation},
+    };
+    use mirrord_operator::client::dry_run::PlannedOperatorRequest;
+
+    use super::{REDACTED, redact_private_keys, render_agent_plan, render_operator_plan};
+
+    #[test]
+    fn private_keys_are_redacted() {
+        let mut container_config = ContainerConfig {
+            steal_tls_config: vec![StealPortTlsConfig {
+                port: 443,
+                agent_as_server: AgentServerConfig {
+                    authentication: InlineTlsAuthentication {
+                        cert_pem_data: "certificate".to_owned(),
+                        key_pem_data: "private key".to_owned(),
+                    }
+                    .into(),
+                    alpn_protocols: Default::default(),
+                    verification: None,
+                },
+                agent_as_client: AgentClientConfig {
+                    authentication: None,
+                    verification: TlsServerVerification {
+                        accept_any_cert: true,
+                        trust_roots: Default::default(),
+                    },
+                },
+            }],
+            ..Default::default()
+        };
+
+        redact_private_keys(&mut container_config);
+
+        let authentication = container_config
+            .steal_tls_config
+            .first()
+            .map(|tls| &tls.agent_as_server.authentication);
+        assert!(matches!(
+            authentication,
+            Some(AgentServerAuthentication::Inline(InlineTlsAuthentication {
+                cert_pem_data,
+                key_pem_data,
+            })) if cert_pem_data == "certificate" && key_pem_data == REDACTED
+        ));
+    }
+
+    #[test]
+    fn agent_plan_renders_body_as_yaml() {
+        let plan = AgentPlan {
+            strategy: AgentStrategy::TargetlessJob,
+            operations: vec![
+                PlannedOperation {
+                    verb: "POST",
+                    path: "/apis/batch/v1/namespaces/default/jobs".into(),
+                    body: Some(serde_json::json!({ "metadata": { "name": "mirrord-agent" } })),
+                },
+                PlannedOperation {
+                    verb: "WATCH",
+                    path: "/api/v1/namespaces/default/pods".into(),
+                    body: None,
+                },
+            ],
+        };
+
+        let rendered = render_agent_plan(&plan).unwrap();
+
+        assert_eq!(
+            rendered,
+            "mirrord would spawn the agent as a targetless job with the following operations:\n\
+             \n\
+             1. POST /apis/batch/v1/namespaces/default/jobs\n   \
+             metadata:\n     \
+             name: mirrord-agent\n\
+             \n\
+             2. WATCH /api/v1/namespaces/default/pods\n"
+        );
+    }
+
+    #[test]
+    fn operator_plan_renders_notes_and_headers() {
+        let requests = [
+            PlannedOperatorRequest {
+                verb: "POST",
+                url: "/apis/operator.metalbear.co/v1/mirrordoperators/operator/certificate".into(),
+                headers: vec!["x-client-name", "x-client-hostname"],
+                note: Some("only when no valid user certificate is stored locally"),
+            },
+            PlannedOperatorRequest {
+                verb: "WEBSOCKET",
+                url: "/apis/operator.metalbear.co/v1/namespaces/default/targets/pod.app".into(),
+                headers: vec!["x-client-name", "x-client-hostname", "x-client-der"],
+                note: None,
+            },
+        ];
+
+        let rendered = render_operator_plan(&requests);
+
+        assert_eq!(
+            rendered,
+            "mirrord would make the following requests to the operator:\n\
+             \n\
+             1. POST /apis/operator.metalbear.co/v1/mirrordoperators/operator/certificate\n   \
+             (only when no valid user certificate is stored locally)\n   \
+             headers: x-client-name, x-client-hostname\n\
+             \n\
+             2. WEBSOCKET /apis/operator.metalbear.co/v1/namespaces/default/targets/pod.app\n   \
+             headers: x-client-name, x-client-hostname, x-client-der\n"
+        );
+    }
+}
@@ -60,7 +60,9 @@ pub async fn dump_command(
     );
 
     // Ensure a target was specified
-    let TargetConfig { path, namespace } = config.target.clone();
+    let TargetConfig {
+        path, namespace, ..
+    } = config.target.clone();
     let path: Target = match path {
         Some(Target::Targetless) | None => {
             return Err(CliError::MissingArg {
@@ -445,7 +447,7 @@ impl DumpSession {
                     }
                 );
             }
-            message @ DaemonTcp::SubscribeResult(..) => {
+            message @ (DaemonTcp::SubscribeResult(..) | DaemonTcp::HttpFilterReport(..)) => {
                 return Err(DumpSessionError::UnexpectedAgentMessage(Box::new(
                     DaemonMessage::Tcp(message),
                 )));
@@ -490,6 +492,7 @@ impl DumpSession {
                 },
                 message @ (DaemonMessage::File(..)
                 | DaemonMessage::GetAddrInfoResponse(..)
+                | DaemonMessage::GetAddrInfoResponseV2(..)
                 | DaemonMessage::GetEnvVarsResponse(..)
                 | DaemonMessage::PauseTarget(..)
                 | DaemonMessage::SwitchProtocolVersionResponse(..)
@@ -497,7 +500,9 @@ impl DumpSession {
                 | DaemonMessage::UdpOutgoing(..)
                 | DaemonMessage::Vpn(..)
                 | DaemonMessage::TcpSteal(..)
-                | DaemonMessage::ReverseDnsLookup(..)) => {
+                | DaemonMessage::ReverseDnsLookup(..)
+                | DaemonMessage::SwitchCompressionResponse(..)
+                | DaemonMessage::Compressed(..)) => {
                     return Err(DumpSessionError::UnexpectedAgentMessage(Box::new(message)));
                 }
             }
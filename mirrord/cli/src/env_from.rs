@@ -0,0 +1,139 @@
+//! Support for `feature.env.from`.
+//!
+//! The referenced ConfigMaps and Secrets are read with the user's Kubernetes credentials when the
+//! session starts. Their values are never logged, as they can hold credentials.
+
+use std::{collections::HashMap, ops::Not};
+
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+use kube::{Api, Client, Resource, core::ErrorResponse};
+use mirrord_config::{
+    LayerConfig,
+    feature::env::from::{EnvFromKind, EnvFromRef},
+};
+use mirrord_kube::error::KubeApiError;
+use serde::de::DeserializeOwned;
+use tracing::Level;
+
+use crate::{
+    error::{CliError, CliResult},
+    kube::kube_client_from_layer_config,
+};
+
+/// Reads the environment variables from the ConfigMaps and Secrets in `feature.env.from`.
+///
+/// When a variable is defined in more than one of them, the last one takes precedence.
+///
+/// Fails with [`CliError::EnvFromMissing`] listing all of the missing resources that are not
+/// `optional`.
+#[tracing::instrument(level = Level::DEBUG, skip_all, err)]
+pub(crate) async fn env_from(config: &LayerConfig) -> CliResult<HashMap<String, String>> {
+    let sources = config.feature.env.from.as_deref().unwrap_or_default();
+    if sources.is_empty() {
+        return Ok(Default::default());
+    }
+
+    let client = kube_client_from_layer_config(config).await?;
+    let target_namespace = config.target.namespace.as_deref();
+
+    let mut env_vars = HashMap::new();
+    let mut missing = Vec::new();
+
+    for source in sources {
+        let reference = source.reference().expect(
+            "Invalid feature.env.from entry, this should've been caught when verifying config!",
+        );
+        let namespace = reference
+            .namespace
+            .or(target_namespace)
+            .unwrap_or(client.default_namespace());
+        let display = format!("{} `{namespace}/{}`", kind_name(reference), reference.name);
+
+        match read_source(&client, namespace, reference, &display).await? {
+            Some(vars) => {
+                tracing::debug!(
+                    source = display,
+                    count = vars.len(),
+                    "Loaded environment variables from feature.env.from"
+                );
+                env_vars.extend(vars);
+            }
+            None if source.optional => {
+                tracing::debug!(
+                    source = display,
+                    "Skipped a missing optional feature.env.from"
+                );
+            }
+            None => missing.push(display),
+        }
+    }
+
+    if missing.is_empty().not() {
+        return Err(CliError::EnvFromMissing(missing.join(", ")));
+    }
+
+    Ok(env_vars)
+}
+
+/// Reads the variables from the referenced resource, [`None`] if it does not exist.
+async fn read_source(
+    client: &Client,
+    namespace: &str,
+    reference: EnvFromRef<'_>,
+    display: &str,
+) -> CliResult<Option<HashMap<String, String>>> {
+    let vars = match reference.kind {
+        EnvFromKind::ConfigMap => get::<ConfigMap>(client, namespace, reference.name, display)
+            .await?
+            .map(|config_map| config_map.data.unwrap_or_default().into_iter().collect()),
+        EnvFromKind::Secret => get::<Secret>(client, namespace, reference.name, display)
+            .await?
+            .map(|secret| {
+                secret
+                    .data
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|(key, value)| match String::from_utf8(value.0) {
+                        Ok(value) => Some((key, value)),
+                        Err(..) => {
+                            tracing::warn!(
+                                source = display,
+                                key,
+                                "Skipped a feature.env.from value that is not valid UTF-8"
+                            );
+                            None
+                        }
+                    })
+                    .collect()
+            }),
+    };
+
+    Ok(vars)
+}
+
+/// Gets the given resource, [`None`] if it does not exist.
+async fn get<R>(client: &Client, namespace: &str, name: &str, display: &str) -> CliResult<Option<R>>
+where
+    R: Resource<Scope = k8s_openapi::NamespaceResourceScope>
+        + Clone
+        + DeserializeOwned
+        + std::fmt::Debug,
+    R::DynamicType: Default,
+{
+    Api::<R>::namespaced(client.clone(), namespace)
+        .get_opt(name)
+        .await
+        .map_err(|error| match error {
+            kube::Error::Api(ErrorResponse { code: 403, .. }) => {
+                CliError::EnvFromForbidden(display.to_owned())
+            }
+            error => CliError::EnvFromFailed(display.to_owned(), KubeApiError::from(error)),
+        })
+}
+
+fn kind_name(reference: EnvFromRef<'_>) -> &'static str {
+    match reference.kind {
+        EnvFromKind::ConfigMap => "ConfigMap",
+        EnvFromKind::Secret => "Secret",
+    }
+}
@@ -1,4 +1,4 @@
-use std::{ffi::NulError, io, num::ParseIntError, path::PathBuf};
+use std::{ffi::NulError, io, path::PathBuf};
 
 #[cfg(feature = "wizard")]
 use axum::response::{IntoResponse, Response};
@@ -12,7 +12,10 @@ use mirrord_intproxy::{
     error::ProxyStartupError,
 };
 use mirrord_kube::error::KubeApiError;
-use mirrord_operator::client::error::{HttpError, OperatorApiError, OperatorOperation};
+use mirrord_operator::{
+    client::error::{HttpError, OperatorApiError, OperatorOperation},
+    types::{LicenseError, ReconnectError, SessionIdError, is_reconnect_not_possible},
+};
 use mirrord_protocol_io::ProtocolError;
 use mirrord_tls_util::SecureChannelError;
 use mirrord_vpn::error::VpnError;
@@ -143,6 +146,10 @@ pub(crate) enum InternalProxyError {
     #[diagnostic(help("{GENERAL_HELP}"))]
     OpenLogFile(String, std::io::Error),
 
+    #[error("Failed to open the protocol capture file: {0}")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    OpenCaptureFile(std::io::Error),
+
     #[error("Missing connect info environment variable")]
     MissingConnectInfo,
 
@@ -165,6 +172,65 @@ pub(crate) enum OperatorSetupError {
     Deleted,
 }
 
+/// Errors that can occur when executing the `mirrord self-update` command.
+///
+/// None of these leave the current binary modified.
+#[derive(Debug, Error, Diagnostic)]
+pub(crate) enum SelfUpdateError {
+    #[error("mirrord releases are not published for {os}/{arch}")]
+    UnsupportedPlatform { os: String, arch: String },
+
+    #[error("Failed to locate the current mirrord binary: {0}")]
+    CurrentExe(#[source] io::Error),
+
+    #[error("mirrord at `{}` was installed with Homebrew", .0.display())]
+    #[diagnostic(help("Run `brew upgrade metalbear-co/mirrord/mirrord` instead."))]
+    Homebrew(PathBuf),
+
+    #[error("mirrord at `{}` lives in a path owned by an administrator", .0.display())]
+    #[diagnostic(help(
+        "Pass `--allow-elevated` (and run the command with elevated privileges) to update it anyway."
+    ))]
+    ElevatedPath(PathBuf),
+
+    #[error("Failed to fetch the release: {0}")]
+    #[diagnostic(help(
+        "Check your connection to github.com and that the requested version exists."
+    ))]
+    Request(#[from] reqwest::Error),
+
+    #[error("Failed to parse the latest mirrord version: {0}")]
+    InvalidLatestVersion(#[source] semver::Error),
+
+    #[error("Checksum file `{0}` is malformed")]
+    #[diagnostic(help("{GENERAL_BUG}"))]
+    MalformedChecksum(String),
+
+    #[error("Checksum of `{asset}` does not match, expected {expected} but got {actual}")]
+    #[diagnostic(help(
+        "The download may have been corrupted or tampered with, please try again.{GENERAL_HELP}"
+    ))]
+    ChecksumMismatch {
+        asset: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Failed to extract mirrord from the release archive: {0}")]
+    #[diagnostic(help("{GENERAL_BUG}"))]
+    Archive(#[from] zip::result::ZipError),
+
+    #[error("Failed to replace `{}`: {error}", path.display())]
+    #[diagnostic(help(
+        "Make sure you can write to the directory containing mirrord.{GENERAL_HELP}"
+    ))]
+    Replace {
+        path: PathBuf,
+        #[source]
+        error: io::Error,
+    },
+}
+
 #[derive(Debug, Error, Diagnostic)]
 pub(crate) enum CliError {
     /// Do not construct this variant directly, use [`CliError::friendlier_error_or_else`] to allow
@@ -191,6 +257,29 @@ pub(crate) enum CliError {
     ))]
     CreateAgentFailed(KubeApiError),
 
+    #[error("Failed to create a job from the target cron job: {0}")]
+    #[diagnostic(help(
+        "Please check that the cron job exists, and that your Kubernetes user is allowed to create jobs in its namespace.{GENERAL_HELP}"
+    ))]
+    CreateJobFailed(KubeApiError),
+
+    /// Do not construct this variant directly, use [`CliError::friendlier_error_or_else`] to allow
+    /// for more granular error detection.
+    #[error("{0}")]
+    #[diagnostic(help(
+        "Pass one of the available contexts with `--context`, or set `kube_context` in the mirrord config.{GENERAL_HELP}"
+    ))]
+    UnknownKubeContext(KubeApiError),
+
+    /// Do not construct this variant directly, use [`CliError::friendlier_error_or_else`] to allow
+    /// for more granular error detection.
+    #[error("Failed to create mirrord-agent: {0}")]
+    #[diagnostic(help(
+        "If the agent image takes long to pull, increase `agent.startup_timeout` in the mirrord config, \
+        or run with `--wait-forever` to debug the agent startup.{GENERAL_HELP}"
+    ))]
+    AgentStartupTimeout(KubeApiError),
+
     /// Do not construct this variant directly, use [`CliError::friendlier_error_or_else`] to allow
     /// for more granular error detection.
     #[error("Failed to connect to the created mirrord-agent: {0}")]
@@ -210,6 +299,19 @@ pub(crate) enum CliError {
     ))]
     InvalidCertificate(KubeApiError),
 
+    #[error("Failed to fetch the TLS secret `{0}` for `feature.network.incoming.https`: {1}")]
+    #[diagnostic(help(
+        "Please check that the secret exists, and that your Kubernetes user is allowed to get secrets in its namespace.{GENERAL_HELP}"
+    ))]
+    HttpsCertSecretFailed(String, KubeApiError),
+
+    #[error("Invalid TLS certificate for `feature.network.incoming.https`: {0}")]
+    #[diagnostic(help(
+        "The certificate chain and the private key must be given in the PEM format, \
+        with exactly one private key matching the first certificate.{GENERAL_HELP}"
+    ))]
+    HttpsCertInvalid(String),
+
     #[error("Failed to communicate with the agent: {0}")]
     #[diagnostic(help("Please check agent status and logs.{GENERAL_HELP}"))]
     InitialAgentCommFailed(String),
@@ -255,6 +357,23 @@ pub(crate) enum CliError {
     ))]
     EnvFileAccessError(PathBuf, dotenvy::Error),
 
+    #[error("Resources referenced in `feature.env.from` were not found: {0}")]
+    #[diagnostic(help(
+        "Please check that the resources exist, or mark them as `optional` in `feature.env.from`.{GENERAL_HELP}"
+    ))]
+    EnvFromMissing(String),
+
+    #[error("Your Kubernetes user is not allowed to read the {0} referenced in `feature.env.from`")]
+    #[diagnostic(help(
+        "Please ask your cluster administrator for permission to get this resource, \
+        or remove it from `feature.env.from`.{GENERAL_HELP}"
+    ))]
+    EnvFromForbidden(String),
+
+    #[error("Failed to read the {0} referenced in `feature.env.from`: {1}")]
+    #[diagnostic(help("Please check your cluster connection and credentials.{GENERAL_HELP}"))]
+    EnvFromFailed(String, KubeApiError),
+
     #[cfg(target_os = "macos")]
     #[error("SIP Error: `{0:#?}`")]
     #[diagnostic(help(
@@ -354,11 +473,13 @@ pub(crate) enum CliError {
     ))]
     OperatorApiForbidden(OperatorOperation, String),
 
-    #[error(
-        "mirrord operator license expired. Visit https://app.metalbear.com to renew your license"
-    )]
+    #[error("{0}. {}", .0.remediation())]
     #[diagnostic(help("{GENERAL_HELP}"))]
-    OperatorLicenseExpired,
+    OperatorLicense(LicenseError),
+
+    #[error(transparent)]
+    #[diagnostic(help("Restart mirrord to start a new session."))]
+    OperatorSessionExpired(ReconnectError),
 
     #[error("Failed to build a websocket connect request: {0:#?}")]
     #[diagnostic(help("{GENERAL_BUG}"))]
@@ -433,7 +554,7 @@ pub(crate) enum CliError {
     BinaryWhichError(String, String),
 
     #[error(transparent)]
-    ParseInt(ParseIntError),
+    InvalidSessionId(SessionIdError),
 
     #[error(transparent)]
     ProfileError(#[from] ProfileError),
@@ -455,6 +576,17 @@ pub(crate) enum CliError {
     #[error("Failed to copy the session target: {}", message.as_deref().unwrap_or("unknown reason"))]
     OperatorCopyTargetFailed { message: Option<String> },
 
+    #[error(
+        "Refusing to scale down {target}, it is managed by the HorizontalPodAutoscaler \
+        `{autoscaler}`"
+    )]
+    #[diagnostic(help(
+        "The autoscaler would scale the target back up while the copied pod is alive. \
+        Set `feature.copy_target.force_scale_down` to scale it down anyway, \
+        or disable `feature.copy_target.scale_down`.{GENERAL_HELP}"
+    ))]
+    OperatorScaleDownWithHpa { target: String, autoscaler: String },
+
     #[error("operator operation timed out: {}", operation)]
     OperatorOperationTimeout { operation: String },
 
@@ -497,6 +629,11 @@ pub(crate) enum CliError {
     #[error("error while fixing kubeconfig")]
     FixKubeconfig(#[from] FixKubeconfigError),
 
+    /// Errors produced by `mirrord self-update` command.
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    SelfUpdate(#[from] SelfUpdateError),
+
     #[error("No image specified for preview environment")]
     #[diagnostic(help(
         "Specify the image using `-i <image>` or set `feature.preview.image` in your mirrord config file."
@@ -588,6 +725,24 @@ pub(crate) enum CliError {
         "Image must be a valid OCI image reference (e.g. 'myregistry.io/myimage:tag')."
     ))]
     PreviewInvalidImage(String),
+
+    #[error("Failed to plan the agent creation: {0}")]
+    #[diagnostic(help(
+        "mirrord was unable to resolve the target for the dry run. \
+        Please check that the target exists and that you can access it with your kubeconfig.{GENERAL_HELP}"
+    ))]
+    DryRunFailed(KubeApiError),
+
+    #[error("Failed to render the dry run output: {0}")]
+    #[diagnostic(help("{GENERAL_BUG}"))]
+    DryRunRender(#[from] serde_yaml::Error),
+
+    #[error("Failed to read the protocol capture `{}`: {1}", .0.display())]
+    #[diagnostic(help(
+        "Please check that the file was written by `internal_proxy.protocol_capture`, \
+        with the same mirrord version.{GENERAL_HELP}"
+    ))]
+    CaptureRead(PathBuf, io::Error),
 }
 
 impl CliError {
@@ -614,6 +769,8 @@ impl CliError {
                 Self::InvalidCertificate(error)
             }
             KubeApiError::AgentPodDeleted => Self::AgentPodDeleted,
+            error @ KubeApiError::UnknownKubeContext { .. } => Self::UnknownKubeContext(error),
+            error @ KubeApiError::AgentStartupTimeout { .. } => Self::AgentStartupTimeout(error),
             error => fallback(error),
         }
     }
@@ -642,6 +799,20 @@ impl From<OperatorApiError> for CliError {
                 error: Error::Api(ErrorResponse { message, code, .. }),
                 operation,
             } if code == StatusCode::FORBIDDEN => Self::OperatorApiForbidden(operation, message),
+            OperatorApiError::KubeError {
+                error:
+                    Error::Api(ErrorResponse {
+                        message,
+                        code,
+                        reason,
+                        ..
+                    }),
+                ..
+            } if StatusCode::from_u16(code)
+                .is_ok_and(|status| is_reconnect_not_possible(status, &reason)) =>
+            {
+                Self::OperatorSessionExpired(ReconnectError::new(message))
+            }
             OperatorApiError::KubeError {
                 error: Error::Auth(AuthError::AuthExecStart(error)),
                 ..
@@ -667,16 +838,19 @@ impl From<OperatorApiError> for CliError {
 
                 Self::OperatorApiFailed(operation, error)
             }
-            OperatorApiError::NoLicense => Self::OperatorLicenseExpired,
+            OperatorApiError::License(error) => Self::OperatorLicense(error),
             OperatorApiError::ClientCertError(error) => Self::OperatorClientCertError(error),
             OperatorApiError::FetchedUnknownTargetType(error) => {
                 Self::OperatorReturnedUnknownTargetType(error.0)
             }
             OperatorApiError::KubeApi(error) => Self::OperatorTargetResolution(error),
-            OperatorApiError::ParseInt(error) => Self::ParseInt(error),
+            OperatorApiError::InvalidSessionId(error) => Self::InvalidSessionId(error),
             OperatorApiError::CopiedTargetFailed { message } => {
                 Self::OperatorCopyTargetFailed { message }
             }
+            OperatorApiError::ScaleDownWithHpa { target, autoscaler } => {
+                Self::OperatorScaleDownWithHpa { target, autoscaler }
+            }
             OperatorApiError::OperationTimeout { operation } => Self::OperatorOperationTimeout {
                 operation: operation.to_string(),
             },
@@ -3,17 +3,25 @@ use std::ffi::OsString;
 use std::{
     collections::{HashMap, HashSet},
     net::SocketAddr,
+    ops::Not,
     time::Duration,
 };
 
 use mirrord_analytics::{AnalyticsError, AnalyticsReporter, Reporter};
 use mirrord_config::{
-    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR, MIRRORD_TEST_INTPROXY_ADDR, config::ConfigError,
-    external_proxy::MIRRORD_EXTPROXY_TLS_SETUP_PEM, feature::env::mapper::EnvVarsRemapper,
+    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR, MIRRORD_TEST_INTPROXY_ADDR,
+    config::ConfigError,
+    external_proxy::MIRRORD_EXTPROXY_TLS_SETUP_PEM,
+    feature::env::{filter::EnvVarsFilter, mapper::EnvVarsRemapper},
 };
 use mirrord_intproxy::agent_conn::AgentConnectInfo;
+use mirrord_kube::api::container::util::StartupDeadline;
 use mirrord_progress::Progress;
-use mirrord_protocol::{ClientMessage, DaemonMessage, EnvVars, GetEnvVarsRequest, LogLevel};
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, GET_CONTAINER_ENV_VARS_VERSION, GetContainerEnvVarsRequest,
+    GetEnvVarsRequest, LogLevel,
+    compression::{COMPRESSION_VERSION, CompressionCodec},
+};
 use mirrord_protocol_io::{Client, Connection};
 #[cfg(target_os = "macos")]
 use mirrord_sip::{SipError, SipPatchOptions, sip_patch};
@@ -36,6 +44,7 @@ use crate::util::reparent_to_init;
 use crate::{
     CliResult, MirrordCi,
     connection::{AGENT_CONNECT_INFO_ENV_KEY, create_and_connect},
+    env_from,
     error::CliError,
     extract::extract_library,
     util::{get_user_git_branch, remove_proxy_env},
@@ -320,14 +329,30 @@ impl MirrordExecution {
         })
     }
 
-    async fn get_agent_version(connection: &mut Connection<Client>) -> CliResult<Version> {
-        connection
-            .send(ClientMessage::SwitchProtocolVersion(
-                mirrord_protocol::VERSION.clone(),
+    /// Performs the first protocol handshake with the agent, bounded by the same
+    /// [`StartupDeadline`] as the agent startup.
+    async fn get_agent_version(
+        connection: &mut Connection<Client>,
+        deadline: StartupDeadline,
+    ) -> CliResult<Version> {
+        let handshake = async {
+            connection
+                .send(ClientMessage::SwitchProtocolVersion(
+                    mirrord_protocol::VERSION.clone(),
+                ))
+                .await;
+
+            connection.recv().await
+        };
+
+        let response = deadline.run(handshake).await.map_err(|timeout| {
+            CliError::InitialAgentCommFailed(format!(
+                "agent did not respond to the version check within the {}s startup timeout",
+                timeout.as_secs()
             ))
-            .await;
+        })?;
 
-        match connection.recv().await {
+        match response {
             Some(DaemonMessage::SwitchProtocolVersionResponse(version)) => Ok(version),
             Some(msg) => Err(CliError::InitialAgentCommFailed(format!(
                 "received unexpected message during agent version check: {msg:?}"
@@ -372,7 +397,7 @@ impl MirrordExecution {
         let env_vars = if config.feature.env.load_from_process.unwrap_or(false) {
             Default::default()
         } else {
-            Self::fetch_env_vars(config, &mut connection)
+            Self::fetch_env_vars(config, &mut connection, None)
                 .await
                 .inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?
         };
@@ -497,23 +522,24 @@ impl MirrordExecution {
 
         let agent_protocol_version = match &connect_info {
             AgentConnectInfo::Operator(session) => session.operator_protocol_version.clone(),
-            AgentConnectInfo::DirectKubernetes(_) => {
-                Some(MirrordExecution::get_agent_version(&mut connection).await?)
-            }
+            AgentConnectInfo::DirectKubernetes(connect_info) => Some(
+                MirrordExecution::get_agent_version(&mut connection, connect_info.startup_deadline)
+                    .await?,
+            ),
             _ => None,
         };
 
-        config
-            .feature
-            .network
-            .incoming
-            .http_filter
-            .ensure_usable_with(agent_protocol_version)?;
+        let incoming = &config.feature.network.incoming;
+        for http_filter in
+            std::iter::once(&incoming.http_filter).chain(incoming.port_http_filters.values())
+        {
+            http_filter.ensure_usable_with(agent_protocol_version.clone())?;
+        }
 
         let mut env_vars = if config.feature.env.load_from_process.unwrap_or(false) {
             Default::default()
         } else {
-            Self::fetch_env_vars(config, &mut connection)
+            Self::fetch_env_vars(config, &mut connection, agent_protocol_version.as_ref())
                 .await
                 .inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?
         };
@@ -549,7 +575,31 @@ impl MirrordExecution {
             proxy_command.pre_exec(|| reparent_to_init().map_err(Into::into));
         }
 
-        let mut proxy_process = proxy_command.spawn().map_err(|e| {
+        // The internal proxy prints the HTTP filter dry run reports to our stderr, see
+        // [`filter_report`](crate::filter_report). Unlike the fds opened by std, this one is not
+        // closed on exec.
+        #[cfg(not(target_os = "windows"))]
+        let filter_report_fd = config
+            .feature
+            .network
+            .incoming
+            .http_filter
+            .dry_run
+            .then(|| unsafe { nix::libc::dup(nix::libc::STDERR_FILENO) })
+            .filter(|fd| *fd >= 0)
+            .inspect(|fd| {
+                proxy_command.env(crate::filter_report::REPORT_FD_ENV, fd.to_string());
+            });
+
+        let proxy_process = proxy_command.spawn();
+
+        // It must not leak into the user application.
+        #[cfg(not(target_os = "windows"))]
+        if let Some(fd) = filter_report_fd {
+            unsafe { nix::libc::close(fd) };
+        }
+
+        let mut proxy_process = proxy_process.map_err(|e| {
             CliError::InternalProxySpawnError(format!("failed to spawn child process: {e}"))
         })?;
 
@@ -604,48 +654,55 @@ impl MirrordExecution {
 
     /// Construct filter and retrieve remote environment from the connected agent using
     /// `MirrordExecution::get_remote_env`.
+    ///
+    /// When `feature.env.container` is set, fails if the `agent_protocol_version` is known and
+    /// does not support [`GetContainerEnvVarsRequest`].
     async fn fetch_env_vars(
         config: &LayerConfig,
         connection: &mut Connection<Client>,
+        agent_protocol_version: Option<&Version>,
     ) -> CliResult<HashMap<String, String>> {
-        let (env_vars_exclude, env_vars_include) = match (
-            config
-                .feature
-                .env
-                .exclude
-                .clone()
-                .map(|exclude| exclude.join(";")),
-            config
-                .feature
-                .env
-                .include
-                .clone()
-                .map(|include| include.join(";")),
-        ) {
-            (Some(..), Some(..)) => {
-                return Err(CliError::ConfigError(ConfigError::Conflict(
-                    "cannot use both `include` and `exclude` filters for environment variables"
-                        .to_string(),
-                )));
-            }
-            (Some(exclude), None) => (HashSet::from(EnvVars(exclude)), HashSet::new()),
-            (None, Some(include)) => (HashSet::new(), HashSet::from(EnvVars(include))),
-            (None, None) => (HashSet::new(), HashSet::from(EnvVars("*".to_owned()))),
-        };
+        let env_vars_filter =
+            EnvVarsFilter::new(&config.feature.env).map_err(CliError::ConfigError)?;
+
+        let container = config.feature.env.container.as_deref();
+        if let (Some(container), Some(version)) = (container, agent_protocol_version)
+            && GET_CONTAINER_ENV_VARS_VERSION.matches(version).not()
+        {
+            return Err(CliError::ConfigError(ConfigError::Conflict(format!(
+                "`feature.env.container` is set to `{container}`, but the mirrord-agent \
+                (protocol version {version}) does not support reading the environment of a \
+                chosen container, it requires protocol version {}",
+                *GET_CONTAINER_ENV_VARS_VERSION
+            ))));
+        }
+        let (env_vars_exclude, env_vars_include) = env_vars_filter.remote_selectors();
+
+        // Variables from `feature.env.from` are overridden by the remote environment.
+        let mut env_vars = env_vars_filter.filter(env_from::env_from(config).await?);
 
-        let mut env_vars = if !env_vars_exclude.is_empty() || !env_vars_include.is_empty() {
+        let remote_env_vars = if !env_vars_exclude.is_empty() || !env_vars_include.is_empty() {
             let communication_timeout =
                 Duration::from_secs(config.agent.communication_timeout.unwrap_or(30).into());
 
-            tokio::time::timeout(
+            let remote_env = tokio::time::timeout(
                 communication_timeout,
-                Self::get_remote_env(connection, env_vars_exclude, env_vars_include),
+                Self::get_remote_env(
+                    connection,
+                    agent_protocol_version,
+                    container,
+                    env_vars_exclude,
+                    env_vars_include,
+                ),
             )
             .await
-            .map_err(|_| CliError::InitialAgentCommFailed("timeout".to_string()))??
+            .map_err(|_| CliError::InitialAgentCommFailed("timeout".to_string()))??;
+
+            env_vars_filter.filter(remote_env)
         } else {
             Default::default()
         };
+        env_vars.extend(remote_env_vars);
 
         if let Some(file) = &config.feature.env.env_file {
             let envs_from_file = dotenvy::from_path_iter(file)
@@ -669,21 +726,54 @@ impl MirrordExecution {
     }
 
     /// Retrieve remote environment from the connected agent.
+    ///
+    /// Reads the environment of the given `container` of the target pod, or of the target
+    /// container when [`None`].
+    ///
+    /// Negotiates compression first if the agent supports it, as the environment can be large.
     #[tracing::instrument(level = Level::TRACE, skip_all)]
     async fn get_remote_env(
         connection: &mut Connection<Client>,
+        agent_protocol_version: Option<&Version>,
+        container: Option<&str>,
         env_vars_filter: HashSet<String>,
         env_vars_select: HashSet<String>,
     ) -> CliResult<HashMap<String, String>> {
-        connection
-            .send(ClientMessage::GetEnvVarsRequest(GetEnvVarsRequest {
+        let request = match container {
+            Some(container) => {
+                ClientMessage::GetContainerEnvVarsRequest(GetContainerEnvVarsRequest {
+                    container: container.to_owned(),
+                    env_vars_filter,
+                    env_vars_select,
+                })
+            }
+            None => ClientMessage::GetEnvVarsRequest(GetEnvVarsRequest {
                 env_vars_filter,
                 env_vars_select,
-            }))
-            .await;
+            }),
+        };
+        if agent_protocol_version.is_some_and(|version| COMPRESSION_VERSION.matches(version)) {
+            connection
+                .send(ClientMessage::SwitchCompression(
+                    CompressionCodec::SUPPORTED.to_vec(),
+                ))
+                .await;
+        }
+        connection.send(request).await;
 
         loop {
-            let result = match connection.recv().await {
+            let message = match connection.recv().await {
+                Some(DaemonMessage::Compressed(compressed)) => {
+                    Some(compressed.decompress().map_err(|error| {
+                        CliError::InitialAgentCommFailed(format!(
+                            "failed to decompress agent message: {error}"
+                        ))
+                    })?)
+                }
+                message => message,
+            };
+
+            let result = match message {
                 Some(DaemonMessage::GetEnvVarsResponse(Ok(remote_env))) => {
                     tracing::trace!(?remote_env, "Agent responded with the remote env");
                     Ok(remote_env)
@@ -703,6 +793,10 @@ impl MirrordExecution {
 
                     continue;
                 }
+                Some(DaemonMessage::SwitchCompressionResponse(codec)) => {
+                    tracing::trace!(?codec, "Agent compression negotiated");
+                    continue;
+                }
                 Some(DaemonMessage::Close(msg)) => Err(CliError::InitialAgentCommFailed(format!(
                     "agent closed connection with message: {msg}"
                 ))),
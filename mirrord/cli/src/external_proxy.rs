@@ -218,10 +218,13 @@ pub async fn proxy(
                     | message @ Some(DaemonMessage::LogMessage(_))
                     | message @ Some(DaemonMessage::GetEnvVarsResponse(_))
                     | message @ Some(DaemonMessage::GetAddrInfoResponse(_))
+                    | message @ Some(DaemonMessage::GetAddrInfoResponseV2(_))
                     | message @ Some(DaemonMessage::PauseTarget(_))
                     | message @ Some(DaemonMessage::SwitchProtocolVersionResponse(_))
                     | message @ Some(DaemonMessage::Vpn(_))
-                    | message @ Some(DaemonMessage::ReverseDnsLookup(_)) => {
+                    | message @ Some(DaemonMessage::ReverseDnsLookup(_))
+                    | message @ Some(DaemonMessage::SwitchCompressionResponse(_))
+                    | message @ Some(DaemonMessage::Compressed(_)) => {
                         return Err(
                             ExternalProxyError::PingPongFailed(format!(
                                 "agent sent an unexpected message: {message:?}"
@@ -0,0 +1,196 @@
+//! Rendering of the reports produced by the HTTP filter dry run, see
+//! `feature.network.incoming.http_filter.dry_run`.
+//!
+//! The `mirrord exec` process is replaced with the user application soon after it spawns the
+//! internal proxy, so the reports are rendered by the internal proxy. It writes them to a
+//! duplicate of the `mirrord exec` stderr, which it inherits under the number given in
+//! [`REPORT_FD_ENV`].
+
+use std::{io::Write, time::Duration};
+
+use mirrord_config::feature::network::incoming::http_filter::DryRunOutput;
+use mirrord_protocol::tcp::HttpFilterReport;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Name of the environment variable with the number of the file descriptor where the internal
+/// proxy writes the HTTP filter dry run reports.
+pub(crate) const REPORT_FD_ENV: &str = "MIRRORD_FILTER_REPORT_FD";
+
+/// Counts of the reports, printed when the session ends.
+#[derive(Default, Debug)]
+struct Summary {
+    seen: u64,
+    matched: u64,
+    errored: u64,
+}
+
+impl Summary {
+    fn add(&mut self, report: &HttpFilterReport) {
+        self.seen += 1;
+        self.matched += u64::from(report.matched);
+        self.errored += u64::from(report.error.is_some());
+    }
+}
+
+fn table_row(report: &HttpFilterReport) -> String {
+    let detail = match (&report.error, &report.matched_filter) {
+        (Some(error), _) => format!("error: {error}"),
+        (None, Some(filter)) => filter.clone(),
+        (None, None) => String::new(),
+    };
+
+    let duration = format!("{:?}", Duration::from_micros(report.duration_us));
+
+    format!(
+        "{:<7} {:<5} {:<7} {duration:>10} {} {detail}",
+        report.method,
+        report.port,
+        if report.matched { "yes" } else { "no" },
+        report.path,
+    )
+    .trim_end()
+    .to_string()
+}
+
+fn json_row(report: &HttpFilterReport) -> String {
+    serde_json::json!({
+        "port": report.port,
+        "method": report.method,
+        "path": report.path,
+        "matched": report.matched,
+        "matched_filter": report.matched_filter,
+        "error": report.error,
+        "duration_us": report.duration_us,
+    })
+    .to_string()
+}
+
+/// Writes the reports received from the given channel to `out` in the given format, and the
+/// [`Summary`] once the channel is closed.
+///
+/// Blocks the current thread, meant to be run with [`tokio::task::spawn_blocking`].
+pub(crate) fn render<W: Write>(
+    mut reports: UnboundedReceiver<HttpFilterReport>,
+    output: DryRunOutput,
+    mut out: W,
+) {
+    let mut summary = Summary::default();
+
+    // Write errors are ignored, the terminal may be gone already.
+    if output == DryRunOutput::Table {
+        let _ = writeln!(
+            out,
+            "{:<7} {:<5} {:<7} {:>10} PATH FILTER",
+            "METHOD", "PORT", "MATCHED", "DURATION"
+        );
+    }
+
+    while let Some(report) = reports.blocking_recv() {
+        summary.add(&report);
+
+        let row = match output {
+            DryRunOutput::Table => table_row(&report),
+            DryRunOutput::Json => json_row(&report),
+        };
+        let _ = writeln!(out, "{row}");
+    }
+
+    let _ = match output {
+        DryRunOutput::Table => writeln!(
+            out,
+            "HTTP filter dry run: {} requests seen, {} matched, {} errored",
+            summary.seen, summary.matched, summary.errored
+        ),
+        DryRunOutput::Json => writeln!(
+            out,
+            "{}",
+            serde_json::json!({
+                "summary": {
+                    "seen": summary.seen,
+                    "matched": summary.matched,
+                    "errored": summary.errored,
+                }
+            })
+        ),
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_config::feature::network::incoming::http_filter::DryRunOutput;
+    use mirrord_protocol::tcp::HttpFilterReport;
+    use tokio::sync::mpsc;
+
+    fn reports() -> Vec<HttpFilterReport> {
+        vec![
+            HttpFilterReport {
+                port: 80,
+                method: "GET".into(),
+                path: "/api/users".into(),
+                matched: true,
+                matched_filter: Some("path: ^/api".into()),
+                error: None,
+                duration_us: 12,
+            },
+            HttpFilterReport {
+                port: 80,
+                method: "POST".into(),
+                path: "/health".into(),
+                matched: false,
+                matched_filter: None,
+                error: Some("body is not valid JSON".into()),
+                duration_us: 1500,
+            },
+        ]
+    }
+
+    fn rendered(output: DryRunOutput) -> String {
+        let (tx, rx) = mpsc::unbounded_channel();
+        reports()
+            .into_iter()
+            .for_each(|report| tx.send(report).unwrap());
+        drop(tx);
+
+        let mut out = Vec::new();
+        super::render(rx, output, &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn table() {
+        let rendered = rendered(DryRunOutput::Table);
+        let lines = rendered.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("METHOD"));
+        assert_eq!(
+            lines[1],
+            "GET     80    yes           12µs /api/users path: ^/api"
+        );
+        assert_eq!(
+            lines[2],
+            "POST    80    no           1.5ms /health error: body is not valid JSON"
+        );
+        assert_eq!(
+            lines[3],
+            "HTTP filter dry run: 2 requests seen, 1 matched, 1 errored"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let rendered = rendered(DryRunOutput::Json);
+        let lines = rendered
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["matched_filter"], "path: ^/api");
+        assert_eq!(lines[1]["error"], "body is not valid JSON");
+        assert_eq!(
+            lines[2],
+            serde_json::json!({ "summary": { "seen": 2, "matched": 1, "errored": 1 } })
+        );
+    }
+}
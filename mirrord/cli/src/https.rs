@@ -0,0 +1,160 @@
+//! Support for `feature.network.incoming.https` in OSS runs.
+//!
+//! The certificate chain and the private key are read from a TLS secret or from local PEM files,
+//! validated, and sent inline to the agent in its TLS steal config.
+
+use std::path::PathBuf;
+
+use k8s_openapi::api::core::v1::Secret;
+use kube::{Api, Client};
+use mirrord_agent_env::steal_tls::{
+    AgentClientConfig, AgentServerConfig, InlineTlsAuthentication, StealPortTlsConfig,
+    TlsServerVerification,
+};
+use mirrord_config::feature::network::incoming::{IncomingConfig, https::HttpsCertSource};
+use mirrord_kube::error::KubeApiError;
+use tracing::Level;
+
+use crate::error::{CliError, CliResult};
+
+/// Key of the certificate chain in a TLS secret.
+const TLS_SECRET_CERT_KEY: &str = "tls.crt";
+
+/// Key of the private key in a TLS secret.
+const TLS_SECRET_KEY_KEY: &str = "tls.key";
+
+/// ALPN protocols offered by the agent's TLS server.
+///
+/// The protocol negotiated with the client is then used when passing unmatched requests to the
+/// original destination.
+const ALPN_PROTOCOLS: [&str; 2] = ["h2", "http/1.1"];
+
+/// PEM data with a name describing where it comes from, used in errors.
+struct PemData {
+    source: PathBuf,
+    data: Vec<u8>,
+}
+
+/// Prepares the TLS steal config for [`IncomingConfig::https`].
+///
+/// Returns an empty list if the feature is not enabled.
+///
+/// Fails with [`CliError::HttpsCertInvalid`] when the certificate chain or the private key cannot
+/// be parsed, so that the run fails before the agent is created.
+#[tracing::instrument(level = Level::DEBUG, skip_all, err)]
+pub(crate) async fn steal_tls_config(
+    incoming: &IncomingConfig,
+    client: &Client,
+    target_namespace: Option<&str>,
+) -> CliResult<Vec<StealPortTlsConfig>> {
+    let ports = incoming.https_ports();
+    if ports.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (cert, key) = match incoming.https.cert_source() {
+        Some(HttpsCertSource::Secret { namespace, name }) => {
+            read_secret(client, namespace.or(target_namespace), name).await?
+        }
+        Some(HttpsCertSource::Files { cert_pem, key_pem }) => (
+            read_file(cert_pem.to_path_buf()).await?,
+            read_file(key_pem.to_path_buf()).await?,
+        ),
+        None => {
+            return Err(CliError::HttpsCertInvalid(
+                "either `cert_secret`, or `cert_pem` and `key_pem` must be set".into(),
+            ));
+        }
+    };
+
+    mirrord_tls_util::parse_cert_chain(cert.data.clone(), cert.source.clone())
+        .await
+        .map_err(|error| CliError::HttpsCertInvalid(error.to_string()))?;
+    mirrord_tls_util::parse_key_der(key.data.clone(), key.source.clone())
+        .await
+        .map_err(|error| CliError::HttpsCertInvalid(error.to_string()))?;
+
+    let authentication = InlineTlsAuthentication {
+        cert_pem_data: into_utf8(cert)?,
+        key_pem_data: into_utf8(key)?,
+    };
+
+    Ok(ports
+        .into_iter()
+        .map(|port| StealPortTlsConfig {
+            port,
+            agent_as_server: AgentServerConfig {
+                authentication: authentication.clone().into(),
+                alpn_protocols: ALPN_PROTOCOLS.map(String::from).into(),
+                verification: None,
+            },
+            agent_as_client: AgentClientConfig {
+                authentication: None,
+                // The original destination is the target's own server,
+                // which usually presents the same certificate.
+                verification: TlsServerVerification {
+                    accept_any_cert: true,
+                    trust_roots: Default::default(),
+                },
+            },
+        })
+        .collect())
+}
+
+/// Reads the certificate chain and the private key from the given TLS secret.
+async fn read_secret(
+    client: &Client,
+    namespace: Option<&str>,
+    name: &str,
+) -> CliResult<(PemData, PemData)> {
+    let api: Api<Secret> = match namespace {
+        Some(namespace) => Api::namespaced(client.clone(), namespace),
+        None => Api::default_namespaced(client.clone()),
+    };
+    let secret_name = format!("{}/{name}", namespace.unwrap_or(client.default_namespace()));
+
+    let mut data = api
+        .get(name)
+        .await
+        .map_err(|error| {
+            CliError::HttpsCertSecretFailed(secret_name.clone(), KubeApiError::from(error))
+        })?
+        .data
+        .unwrap_or_default();
+
+    let mut take = |key: &str| {
+        data.remove(key)
+            .map(|value| PemData {
+                source: format!("{secret_name}/{key}").into(),
+                data: value.0,
+            })
+            .ok_or_else(|| {
+                CliError::HttpsCertInvalid(format!(
+                    "secret `{secret_name}` has no `{key}` key, expected a TLS secret"
+                ))
+            })
+    };
+
+    Ok((take(TLS_SECRET_CERT_KEY)?, take(TLS_SECRET_KEY_KEY)?))
+}
+
+/// Reads a local PEM file.
+async fn read_file(path: PathBuf) -> CliResult<PemData> {
+    let data = tokio::fs::read(&path).await.map_err(|error| {
+        CliError::HttpsCertInvalid(format!(
+            "failed to read PEM file `{}`: {error}",
+            path.display()
+        ))
+    })?;
+
+    Ok(PemData { source: path, data })
+}
+
+fn into_utf8(pem: PemData) -> CliResult<String> {
+    String::from_utf8(pem.data).map_err(|_| {
+        CliError::HttpsCertInvalid(format!(
+            "PEM file `{}` is not valid UTF-8",
+            pem.source.display()
+        ))
+    })
+}
@@ -13,34 +13,45 @@
 use std::{
     env, io,
     net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
     time::Duration,
 };
 #[cfg(not(target_os = "windows"))]
-use std::{ops::Not, os::unix::ffi::OsStrExt};
+use std::{
+    fs::File,
+    ops::Not,
+    os::{fd::FromRawFd, unix::ffi::OsStrExt},
+};
 
 use mirrord_analytics::{AnalyticsReporter, CollectAnalytics, Reporter};
 use mirrord_config::LayerConfig;
 use mirrord_intproxy::{
-    IntProxy,
+    IntProxy, PingPongConfig,
     agent_conn::{AgentConnectInfo, AgentConnection},
+    replicas::ReplicaMirrors,
 };
+#[cfg(not(target_os = "windows"))]
+use mirrord_protocol::tcp::HttpFilterReport;
 use mirrord_protocol::{ClientMessage, DaemonMessage, LogLevel, LogMessage};
 #[cfg(not(target_os = "windows"))]
 use nix::sys::resource::{Resource, setrlimit};
 use tokio::net::TcpListener;
+#[cfg(not(target_os = "windows"))]
+use tokio::{sync::mpsc, task::JoinHandle};
 use tracing::Level;
 #[cfg(not(target_os = "windows"))]
 use tracing::warn;
 
-#[cfg(not(target_os = "windows"))]
-use crate::util::detach_io;
 use crate::{
+    capture::ProtocolCapture,
     connection::AGENT_CONNECT_INFO_ENV_KEY,
     error::{CliResult, InternalProxyError},
     execution::MIRRORD_EXECUTION_KIND_ENV,
     user_data::UserData,
     util::create_listen_socket,
 };
+#[cfg(not(target_os = "windows"))]
+use crate::{filter_report, util::detach_io};
 
 /// Print the address for the caller (mirrord cli execution flow) so it can pass it
 /// back to the layer instances via env var.
@@ -115,7 +126,13 @@ pub(crate) async fn proxy(
     // **before** this happens to ensure that the agent does not prematurely exit.
     // We also perform initial ping pong round to ensure that k8s runtime actually made connection
     // with the agent (it's a must, because port forwarding may be done lazily).
-    let agent_conn = connect_and_ping(&config, agent_connect_info, &mut analytics).await?;
+    let replica_mirrors = ReplicaMirrors::new(&config, &agent_connect_info);
+    let mut agent_conn = connect_and_ping(&config, agent_connect_info, &mut analytics).await?;
+    if let Some(capture) = ProtocolCapture::open(&config.internal_proxy)
+        .map_err(InternalProxyError::OpenCaptureFile)?
+    {
+        agent_conn = agent_conn.with_tap(Arc::new(capture));
+    }
 
     // Let it assign address for us then print it for the user.
     let listener = create_listen_socket(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), listen_port))
@@ -132,7 +149,7 @@ pub(crate) async fn proxy(
     let process_logging_interval =
         Duration::from_secs(config.internal_proxy.process_logging_interval);
 
-    IntProxy::new_with_connection(
+    let intproxy = IntProxy::new_with_connection(
         agent_conn,
         listener,
         config.feature.fs.readonly_file_buffer,
@@ -144,11 +161,62 @@ pub(crate) async fn proxy(
             .or(config.feature.network.incoming.https_delivery)
             .unwrap_or_default(),
         process_logging_interval,
+        PingPongConfig::from(&config.internal_proxy),
+        &config.feature.network.dns.cache,
         &config.experimental,
-    )
-    .run(first_connection_timeout, consecutive_connection_timeout)
-    .await
-    .map_err(From::from)
+        replica_mirrors,
+    );
+
+    #[cfg(not(target_os = "windows"))]
+    let mut filter_report_renderer = None;
+    #[cfg(not(target_os = "windows"))]
+    let intproxy = {
+        let mut intproxy = intproxy;
+        if config.feature.network.incoming.http_filter.dry_run
+            && let Some((tx, renderer)) = spawn_filter_report_renderer(&config)
+        {
+            intproxy = intproxy.with_filter_reports(tx);
+            filter_report_renderer = Some(renderer);
+        }
+        intproxy
+    };
+
+    let result = intproxy
+        .run(first_connection_timeout, consecutive_connection_timeout)
+        .await;
+
+    // The proxy is dropped by now, so the renderer prints the summary and exits.
+    #[cfg(not(target_os = "windows"))]
+    if let Some(renderer) = filter_report_renderer {
+        let _ = renderer.await;
+    }
+
+    result.map_err(From::from)
+}
+
+/// Spawns the task that renders the HTTP filter dry run reports, see [`filter_report`].
+///
+/// Returns [`None`] when the `mirrord exec` that spawned us did not pass its stderr, e.g. when
+/// running in a container.
+#[cfg(not(target_os = "windows"))]
+fn spawn_filter_report_renderer(
+    config: &LayerConfig,
+) -> Option<(mpsc::UnboundedSender<HttpFilterReport>, JoinHandle<()>)> {
+    let Some(fd) = env::var(filter_report::REPORT_FD_ENV)
+        .ok()
+        .and_then(|fd| fd.parse().ok())
+    else {
+        warn!("HTTP filter dry run is enabled, but there is no terminal to print the reports to");
+        return None;
+    };
+
+    // Safety: the fd was dup'ed for us by `mirrord exec`, and nothing else uses it.
+    let out = unsafe { File::from_raw_fd(fd) };
+    let output = config.feature.network.incoming.http_filter.dry_run_output;
+    let (tx, rx) = mpsc::unbounded_channel();
+    let renderer = tokio::task::spawn_blocking(move || filter_report::render(rx, output, out));
+
+    Some((tx, renderer))
 }
 
 /// Creates a connection with the agent and handles one round of ping pong.
@@ -197,10 +265,13 @@ pub(crate) async fn connect_and_ping(
             | message @ Some(DaemonMessage::LogMessage(_))
             | message @ Some(DaemonMessage::GetEnvVarsResponse(_))
             | message @ Some(DaemonMessage::GetAddrInfoResponse(_))
+            | message @ Some(DaemonMessage::GetAddrInfoResponseV2(_))
             | message @ Some(DaemonMessage::PauseTarget(_))
             | message @ Some(DaemonMessage::SwitchProtocolVersionResponse(_))
             | message @ Some(DaemonMessage::Vpn(_))
-            | message @ Some(DaemonMessage::ReverseDnsLookup(_)) => {
+            | message @ Some(DaemonMessage::ReverseDnsLookup(_))
+            | message @ Some(DaemonMessage::SwitchCompressionResponse(_))
+            | message @ Some(DaemonMessage::Compressed(_)) => {
                 break Err(InternalProxyError::InitialPingPongFailed(format!(
                     "agent sent an unexpected message: {message:?}"
                 )));
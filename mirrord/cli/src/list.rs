@@ -1,11 +1,14 @@
-use std::{sync::LazyLock, time::Instant};
+use std::{fmt, sync::LazyLock, time::Instant};
 
 use futures::TryStreamExt;
 use k8s_openapi::api::core::v1::Namespace;
 use mirrord_analytics::NullReporter;
 use mirrord_config::{LayerConfig, config::ConfigContext, target::TargetType};
-use mirrord_kube::{api::kubernetes::seeker::KubeResourceSeeker, error::KubeApiError};
-use mirrord_operator::client::OperatorApi;
+use mirrord_kube::{
+    api::kubernetes::{seeker::KubeResourceSeeker, selected_kube_context},
+    error::KubeApiError,
+};
+use mirrord_operator::{client::OperatorApi, crd::Session};
 use semver::VersionReq;
 use serde::{Serialize, Serializer, ser::SerializeSeq};
 use tracing::Level;
@@ -34,6 +37,122 @@ struct FoundTarget {
     /// This field is here for forward compatibility, because in the future we might want to return
     /// unavailable targets as well (along with some validation error message) to improve UX.
     available: bool,
+
+    /// Operator sessions that currently use this target.
+    ///
+    /// [`None`] when the operator is not used, or does not report its sessions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sessions: Option<Vec<TargetSession>>,
+}
+
+impl FoundTarget {
+    /// Compact summary of the [`FoundTarget::sessions`], e.g.
+    /// `(in use: alice, steal :8080, 12m)`.
+    fn usage_suffix(&self) -> Option<String> {
+        let sessions = self
+            .sessions
+            .as_deref()
+            .filter(|sessions| !sessions.is_empty())?;
+
+        let sessions = sessions
+            .iter()
+            .map(TargetSession::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        Some(format!("(in use: {sessions})"))
+    }
+}
+
+/// An operator session that uses a [`FoundTarget`], taken from the operator status.
+#[derive(Serialize, Debug, PartialEq)]
+struct TargetSession {
+    user: String,
+
+    /// How long the session has been running.
+    duration_secs: u64,
+
+    /// Ports locked by the session, which steals their traffic.
+    stolen_ports: Vec<u16>,
+
+    /// [`None`] when the operator does not report them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mirrored_ports: Option<Vec<u16>>,
+}
+
+impl TargetSession {
+    /// Picks the `sessions` that use the target at `path` in `namespace`.
+    ///
+    /// Sessions are matched on the target type and name only, so that a session on a container of
+    /// a pod is shown for the pod and vice versa.
+    fn for_target(path: &str, namespace: &str, sessions: &[Session]) -> Vec<Self> {
+        let type_and_name = |path: &str| path.splitn(3, '/').take(2).collect::<Vec<_>>();
+        let target = type_and_name(path);
+
+        sessions
+            .iter()
+            .filter(|session| {
+                session
+                    .namespace
+                    .as_deref()
+                    .is_none_or(|session_namespace| session_namespace == namespace)
+                    && type_and_name(&session.target) == target
+            })
+            .map(|session| {
+                let mut stolen_ports = session
+                    .locked_ports
+                    .iter()
+                    .flatten()
+                    .map(|locked_port| locked_port.to_locked_port().port)
+                    .collect::<Vec<_>>();
+                stolen_ports.sort_unstable();
+                stolen_ports.dedup();
+
+                Self {
+                    user: session.user.clone(),
+                    duration_secs: session.duration_secs,
+                    stolen_ports,
+                    mirrored_ports: session.mirrored_ports.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for TargetSession {
+    /// E.g. `alice, steal :8080, mirror :80 :9090, 12m`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ports = |ports: &[u16]| {
+            ports
+                .iter()
+                .map(|port| format!(":{port}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        write!(f, "{}", self.user)?;
+
+        if !self.stolen_ports.is_empty() {
+            write!(f, ", steal {}", ports(&self.stolen_ports))?;
+        }
+
+        if let Some(mirrored_ports) = self
+            .mirrored_ports
+            .as_deref()
+            .filter(|mirrored_ports| !mirrored_ports.is_empty())
+        {
+            write!(f, ", mirror {}", ports(mirrored_ports))?;
+        }
+
+        let duration = match self.duration_secs {
+            secs @ 0..60 => format!("{secs}s"),
+            secs @ 60..3600 => format!("{}m", secs / 60),
+            secs @ 3600..86400 => format!("{}h", secs / 3600),
+            secs => format!("{}d", secs / 86400),
+        };
+
+        write!(f, ", {duration}")
+    }
 }
 
 /// Result of mirrord targets lookup in the cluster.
@@ -55,6 +174,9 @@ struct FoundTargets {
 
     /// Available lookup namespaces.
     namespaces: Vec<String>,
+
+    /// Kube context used for the lookup, see [`selected_kube_context`].
+    kube_context: Option<String>,
 }
 
 impl FoundTargets {
@@ -96,6 +218,12 @@ impl FoundTargets {
             None
         };
 
+        // Older operators don't report their sessions, then the targets are listed without them.
+        let sessions = operator_api
+            .as_ref()
+            .and_then(|api| api.operator().status.as_ref())
+            .map(|status| status.sessions.clone());
+
         let seeker = KubeResourceSeeker {
             client: &client,
             namespace: layer_config
@@ -147,6 +275,9 @@ impl FoundTargets {
                 let targets = paths
                     .into_iter()
                     .map(|path| FoundTarget {
+                        sessions: sessions.as_deref().map(|sessions| {
+                            TargetSession::for_target(&path, seeker.namespace, sessions)
+                        }),
                         path,
                         available: true,
                     })
@@ -180,14 +311,34 @@ impl FoundTargets {
             .unwrap_or(client.default_namespace())
             .to_owned();
 
+        let kube_context = selected_kube_context(
+            layer_config.kubeconfig.as_ref(),
+            layer_config.kube_context.as_deref(),
+        );
+
         Ok(Self {
             targets,
             current_namespace,
             namespaces,
+            kube_context,
         })
     }
 }
 
+/// Renders the available targets one per line, followed by the
+/// [`FoundTarget::usage_suffix`] when someone uses them.
+fn render_text(targets: &FoundTargets) -> String {
+    targets
+        .targets
+        .iter()
+        .filter(|target| target.available)
+        .map(|target| match target.usage_suffix() {
+            Some(suffix) => format!("{} {suffix}\n", target.path),
+            None => format!("{}\n", target.path),
+        })
+        .collect()
+}
+
 /// Thin wrapper over [`FoundTargets`] that implements [`Serialize`].
 /// Its serialized format is a sequence of available target paths.
 ///
@@ -224,9 +375,12 @@ static ALL_TARGETS_SUPPORTED_OPERATOR_VERSION: LazyLock<VersionReq> =
 /// Otherwise:
 /// 1. targets are printed as a plain JSON array of strings (backward compatibility);
 /// 2. all available target types are fetched.
+///
+/// With [`Format::Text`], targets are printed one per line, see [`render_text`].
 pub(super) async fn print_targets(args: ListTargetArgs, rich_output: bool) -> CliResult<()> {
-    let mut cfg_config =
-        ConfigContext::default().override_env_opt(LayerConfig::FILE_PATH_ENV, args.config_file);
+    let mut cfg_config = ConfigContext::default()
+        .override_env_opt(LayerConfig::FILE_PATH_ENV, args.config_file)
+        .override_env_opt("MIRRORD_KUBE_CONTEXT", args.context);
 
     let mut layer_config = LayerConfig::resolve(&mut cfg_config)?;
 
@@ -264,7 +418,137 @@ pub(super) async fn print_targets(args: ListTargetArgs, rich_output: bool) -> Cl
 
             println!("{serialized}");
         }
+        Format::Text => print!("{}", render_text(&targets)),
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use mirrord_operator::crd::Session;
+
+    use super::{FoundTarget, FoundTargets, TargetSession, render_text};
+
+    /// Sessions as reported by the operator status, the second one by an operator that does not
+    /// report mirrored ports and uses the legacy format of locked ports.
+    const SESSIONS: &str = r#"[
+        {
+            "id": "A1", "duration_secs": 754, "user": "alice", "target": "deployment/checkout",
+            "namespace": "default", "user_id": "1", "sqs": null, "kafka": null,
+            "locked_ports": [
+                {"port": 8080, "kind": "steal", "filter": null},
+                {"port": 8080, "kind": "steal", "filter": "x-user: alice"}
+            ],
+            "mirrored_ports": [9090]
+        },
+        {
+            "id": "B2", "duration_secs": 30, "user": "bob",
+            "target": "pod/checkout-abc/container/app", "namespace": "default",
+            "user_id": "2", "sqs": null, "kafka": null,
+            "locked_ports": [[8080, "steal", null]]
+        },
+        {
+            "id": "C3", "duration_secs": 7200, "user": "carol", "target": "deployment/checkout",
+            "namespace": "staging", "user_id": "3", "sqs": null, "kafka": null,
+            "locked_ports": null, "mirrored_ports": []
+        }
+    ]"#;
+
+    fn target(path: &str, namespace: &str, sessions: Option<&[Session]>) -> FoundTarget {
+        FoundTarget {
+            path: path.to_owned(),
+            available: true,
+            sessions: sessions.map(|sessions| TargetSession::for_target(path, namespace, sessions)),
+        }
+    }
+
+    #[test]
+    fn sessions_for_target() {
+        let sessions = serde_json::from_str::<Vec<Session>>(SESSIONS).unwrap();
+
+        let found = TargetSession::for_target("deployment/checkout", "default", &sessions);
+        assert_eq!(
+            found,
+            vec![TargetSession {
+                user: "alice".to_owned(),
+                duration_secs: 754,
+                stolen_ports: vec![8080],
+                mirrored_ports: Some(vec![9090]),
+            }]
+        );
+
+        let found = TargetSession::for_target("pod/checkout-abc", "default", &sessions);
+        assert_eq!(
+            found,
+            vec![TargetSession {
+                user: "bob".to_owned(),
+                duration_secs: 30,
+                stolen_ports: vec![8080],
+                mirrored_ports: None,
+            }]
+        );
+
+        let found = TargetSession::for_target("deployment/checkout", "staging", &sessions);
+        assert_eq!(
+            found
+                .iter()
+                .map(|session| session.user.as_str())
+                .collect::<Vec<_>>(),
+            ["carol"]
+        );
+
+        assert!(TargetSession::for_target("deployment/cart", "default", &sessions).is_empty());
+    }
+
+    #[test]
+    fn text_output() {
+        let sessions = serde_json::from_str::<Vec<Session>>(SESSIONS).unwrap();
+
+        let targets = FoundTargets {
+            targets: vec![
+                target("deployment/checkout", "default", Some(&sessions)),
+                target("deployment/cart", "default", Some(&sessions)),
+                target("pod/checkout-abc/container/app", "default", Some(&sessions)),
+                target("deployment/checkout", "staging", Some(&sessions)),
+                target("deployment/checkout", "default", None),
+            ],
+            current_namespace: "default".to_owned(),
+            namespaces: vec![],
+            kube_context: None,
+        };
+
+        assert_eq!(
+            render_text(&targets),
+            "deployment/checkout (in use: alice, steal :8080, mirror :9090, 12m)\n\
+            deployment/cart\n\
+            pod/checkout-abc/container/app (in use: bob, steal :8080, 30s)\n\
+            deployment/checkout (in use: carol, 2h)\n\
+            deployment/checkout\n"
+        );
+    }
+
+    #[test]
+    fn json_output_without_sessions() {
+        let sessions = serde_json::from_str::<Vec<Session>>(SESSIONS).unwrap();
+
+        // The operator does not report its sessions.
+        let json = serde_json::to_value(target("deployment/checkout", "default", None)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"path": "deployment/checkout", "available": true})
+        );
+
+        // The operator does not report mirrored ports.
+        let json =
+            serde_json::to_value(target("pod/checkout-abc", "default", Some(&sessions))).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "path": "pod/checkout-abc",
+                "available": true,
+                "sessions": [{"user": "bob", "duration_secs": 30, "stolen_ports": [8080]}]
+            })
+        );
+    }
+}
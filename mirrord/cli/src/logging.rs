@@ -1,16 +1,23 @@
-use std::{fs::OpenOptions, future::Future, ops::Not, path::Path};
+use std::{future::Future, ops::Not, path::Path, sync::Mutex};
 
 use futures::StreamExt;
 use mirrord_config::LayerConfig;
 use tokio::io::AsyncWriteExt;
 use tokio_stream::Stream;
-use tracing_subscriber::{EnvFilter, prelude::*};
+use tracing_subscriber::{EnvFilter, filter::LevelFilter, prelude::*};
 
 use crate::{
     config::Commands,
     error::{CliError, ExternalProxyError, InternalProxyError},
+    logging::rotation::{RotatingFile, RotationLimits},
 };
 
+pub(crate) mod rotation;
+
+/// Size of a megabyte, used to convert `internal_proxy.log_max_size` and
+/// `internal_proxy.protocol_capture_max_size`.
+pub(crate) const MEGABYTE: u64 = 1024 * 1024;
+
 /// Tries to initialize tracing in the current process.
 pub async fn init_tracing_registry(
     command: &Commands,
@@ -40,6 +47,9 @@ pub async fn init_tracing_registry(
         // Proxies initialize tracing independently, after log file setup.
         Commands::InternalProxy { .. } | Commands::ExternalProxy { .. } => false,
 
+        // Shares the terminal with the user application.
+        Commands::TargetLogs => false,
+
         _ => true,
     };
 
@@ -64,6 +74,13 @@ pub async fn init_tracing_registry(
 ///
 /// Fails if the specified log file cannot be opened/created for writing.
 ///
+/// The log file is rotated according to the given [`RotationLimits`], see [`RotatingFile`].
+///
+/// Only warnings and errors are additionally written to stderr, so that they don't get mixed with
+/// the user application output. The proxies detach from the terminal once they're listening (see
+/// `detach_io`), which redirects stderr to `/dev/null`, so this covers only the startup, e.g.
+/// failing to connect to the agent.
+///
 /// # Log format
 ///
 /// Proxies output logs in JSON, which is not really human-readable.
@@ -72,6 +89,7 @@ async fn init_proxy_tracing_registry(
     log_destination: &Path,
     log_level: &str,
     json_log: bool,
+    rotation: RotationLimits,
 ) -> std::io::Result<()> {
     if std::env::var("MIRRORD_CONSOLE_ADDR").is_ok() {
         return Ok(());
@@ -81,10 +99,7 @@ async fn init_proxy_tracing_registry(
         tokio::fs::create_dir_all(parent).await?;
     }
 
-    let output_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(log_destination)?;
+    let output_file = RotatingFile::open(log_destination, rotation)?;
 
     let env_filter = EnvFilter::builder().parse_lossy(log_level);
 
@@ -92,16 +107,22 @@ async fn init_proxy_tracing_registry(
         .with_ansi(false)
         .with_file(true)
         .with_line_number(true)
-        .with_writer(output_file);
+        .with_writer(Mutex::new(output_file));
     let fmt_layer = if json_log {
         fmt_layer_base.json().boxed()
     } else {
         fmt_layer_base.pretty().boxed()
     };
 
+    let stderr_layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .with_filter(LevelFilter::WARN);
+
     tracing_subscriber::registry()
-        .with(fmt_layer)
-        .with(env_filter)
+        .with(fmt_layer.with_filter(env_filter))
+        .with(stderr_layer)
         .init();
 
     Ok(())
@@ -115,6 +136,10 @@ pub async fn init_intproxy_tracing_registry(
             &config.internal_proxy.log_destination,
             &config.internal_proxy.log_level,
             config.internal_proxy.json_log,
+            RotationLimits {
+                max_size: config.internal_proxy.log_max_size * MEGABYTE,
+                max_files: config.internal_proxy.log_max_files,
+            },
         )
         .await
         .map_err(|fail| {
@@ -161,6 +186,7 @@ pub async fn init_extproxy_tracing_registry(
         log_destination,
         &config.external_proxy.log_level,
         config.external_proxy.json_log,
+        RotationLimits::DISABLED,
     )
     .await
     .map_err(|fail| {
@@ -0,0 +1,152 @@
+//! Size-based rotation of the proxy log files.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// When a [`RotatingFile`] is rotated.
+#[derive(Clone, Copy, Debug)]
+pub struct RotationLimits {
+    /// Size in bytes after which the file is rotated, `0` disables the rotation.
+    pub max_size: u64,
+    /// How many rotated files are kept.
+    pub max_files: usize,
+}
+
+impl RotationLimits {
+    /// The file is never rotated.
+    pub const DISABLED: Self = Self {
+        max_size: 0,
+        max_files: 0,
+    };
+}
+
+/// Log file that is rotated once it grows past [`RotationLimits::max_size`] bytes.
+///
+/// Rotated files are named after the original one, with a numeric suffix (`.1` being the most
+/// recent). Only [`RotationLimits::max_files`] of them are kept, older ones are overwritten.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    limits: RotationLimits,
+    file: File,
+    /// How many bytes are in the current file.
+    written: u64,
+}
+
+impl RotatingFile {
+    /// Opens the log file at `path` in append mode.
+    pub fn open(path: &Path, limits: RotationLimits) -> io::Result<Self> {
+        let file = Self::open_file(path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            limits,
+            file,
+            written,
+        })
+    }
+
+    fn open_file(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    /// Shifts the rotated files by one, moves the current file to `.1` and starts a new one.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.limits.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.limits.max_files).rev() {
+                match fs::rename(self.rotated_path(index), self.rotated_path(index + 1)) {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                    _ => {}
+                }
+            }
+
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = Self::open_file(&self.path)?;
+        self.written = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.limits.max_size > 0
+            && self.written > 0
+            && self.written + buf.len() as u64 > self.limits.max_size
+        {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::{RotatingFile, RotationLimits};
+
+    #[test]
+    fn keeps_last_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("intproxy.log");
+        let mut file = RotatingFile::open(
+            &path,
+            RotationLimits {
+                max_size: 10,
+                max_files: 2,
+            },
+        )
+        .unwrap();
+
+        for line in ["first-line\n", "second-line\n", "third-line\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("intproxy.log"), "fourth\n");
+        assert_eq!(read("intproxy.log.1"), "third-line\n");
+        assert_eq!(read("intproxy.log.2"), "second-line\n");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn disabled_rotation_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("intproxy.log");
+        std::fs::write(&path, "existing\n").unwrap();
+
+        let mut file = RotatingFile::open(&path, RotationLimits::DISABLED).unwrap();
+        file.write_all(b"appended\n").unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "existing\nappended\n"
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -213,14 +213,16 @@
 //!
 //! Uses [`clap`] to generate completions for the mirrord CLI.
 //!
-//! ### `mirrord teams`
+//! ### `mirrord teams [--no-browser]`
 //!
 //! - [`teams::navigate_to_intro`]
+//! - [`teams::print_teams_intro`]
 //!
 //! > For users interested in getting mirrord for teams, which is a paid feature.
 //!
 //! Opens a browser window to our mirrord for teams intro page. If we fail to open it, then it
-//! prints a nice little message to stdout.
+//! prints a nice little message to stdout. With `--no-browser`, or when stdout is not a
+//! terminal, only prints the link.
 //!
 //! ### `mirrord wizard [OPTIONS]`
 //!
@@ -245,7 +247,9 @@
 #![cfg_attr(all(windows, feature = "windows_build"), feature(windows_change_time))]
 #![cfg_attr(all(windows, feature = "windows_build"), feature(windows_by_handle))]
 
-use std::{collections::HashMap, env::vars, net::SocketAddr, time::Duration};
+use std::{
+    collections::HashMap, env::vars, io::IsTerminal, net::SocketAddr, ops::Not, time::Duration,
+};
 #[cfg(not(target_os = "windows"))]
 use std::{ffi::CString, os::unix::ffi::OsStrExt};
 #[cfg(target_os = "macos")]
@@ -272,7 +276,7 @@ use mirrord_config::{
         database_branches::{DatabaseBranchConfig, RedisBranchLocation},
         fs::FsModeConfig,
         network::{
-            dns::{DnsConfig, DnsFilterConfig},
+            dns::{DnsConfig, DnsFilterConfig, DnsResolution},
             incoming::IncomingMode,
         },
     },
@@ -289,18 +293,24 @@ use tracing::{error, info, trace, warn};
 use which::which;
 
 mod browser;
+mod capture;
 mod ci;
 mod config;
 mod connection;
 mod container;
 mod db_branches;
 mod diagnose;
+mod dry_run;
 mod dump;
+mod env_from;
 mod error;
 mod execution;
 mod extension;
 mod external_proxy;
 mod extract;
+#[cfg(not(target_os = "windows"))]
+mod filter_report;
+mod https;
 mod internal_proxy;
 #[cfg(target_os = "linux")]
 mod is_static;
@@ -313,6 +323,9 @@ mod operator;
 mod port_forward;
 mod preview;
 mod profile;
+mod resolved_config;
+mod self_update;
+mod target_logs;
 mod teams;
 mod user_data;
 mod util;
@@ -328,6 +341,7 @@ mod fix;
 pub(crate) use error::{CliError, CliResult};
 #[cfg(target_os = "windows")]
 use mirrord_layer_lib::process::windows::{console, execution::LayerManagedProcess};
+use resolved_config::resolved_config_command;
 use verify_config::verify_config;
 
 use crate::{
@@ -442,6 +456,11 @@ where
 
     let sub_progress = progress.subtask("running process");
 
+    #[cfg(not(target_os = "windows"))]
+    if mirrord_for_ci.is_none() {
+        target_logs::spawn(&config, progress);
+    }
+
     run_process_with_mirrord(
         binary,
         binary_args,
@@ -637,14 +656,26 @@ pub(crate) fn print_config<P>(
     };
     progress.info(&format!("fs: file operations will default to {}", fs_info));
 
-    let incoming_info = match config.feature.network.incoming.mode {
+    let incoming_info = |mode: IncomingMode| match mode {
         IncomingMode::Mirror => "mirrored",
         IncomingMode::Steal => "stolen",
         IncomingMode::Off => "ignored",
     };
+    let mut port_modes = config
+        .feature
+        .network
+        .incoming
+        .port_modes
+        .iter()
+        .collect::<Vec<_>>();
+    port_modes.sort_by_key(|(port, _)| **port);
+    let port_modes_info = port_modes
+        .into_iter()
+        .map(|(port, mode)| format!(", on port {port} it will be {}", incoming_info(*mode)))
+        .collect::<String>();
     progress.info(&format!(
-        "incoming: incoming traffic will be {}",
-        incoming_info
+        "incoming: incoming traffic will be {}{port_modes_info}",
+        incoming_info(config.feature.network.incoming.mode)
     ));
 
     let outgoing_info = match (
@@ -663,23 +694,46 @@ pub(crate) fn print_config<P>(
         DnsConfig {
             enabled: true,
             filter: None,
+            ..
         } => "remotely",
         DnsConfig {
             enabled: true,
-            filter: Some(DnsFilterConfig::Remote(filters)),
-        } if filters.is_empty() => "locally",
+            filter: Some(DnsFilterConfig::Remote { remote }),
+            ..
+        } if remote.is_empty() => "locally",
         DnsConfig {
             enabled: true,
-            filter: Some(DnsFilterConfig::Local(filters)),
-        } if filters.is_empty() => "remotely",
+            filter: Some(DnsFilterConfig::Local { local }),
+            ..
+        } if local.is_empty() => "remotely",
         DnsConfig {
             enabled: true,
-            filter: Some(DnsFilterConfig::Remote(..)),
+            filter: Some(DnsFilterConfig::Remote { .. }),
+            ..
         } => "locally with exceptions",
         DnsConfig {
             enabled: true,
-            filter: Some(DnsFilterConfig::Local(..)),
+            filter: Some(DnsFilterConfig::Local { .. }),
+            ..
         } => "remotely with exceptions",
+        DnsConfig {
+            enabled: true,
+            filter:
+                Some(DnsFilterConfig::Rules {
+                    default: DnsResolution::Local,
+                    ..
+                }),
+            ..
+        } => "by rules, locally by default",
+        DnsConfig {
+            enabled: true,
+            filter:
+                Some(DnsFilterConfig::Rules {
+                    default: DnsResolution::Remote,
+                    ..
+                }),
+            ..
+        } => "by rules, remotely by default",
     };
     progress.info(&format!("dns: DNS will be resolved {}", dns_info));
 
@@ -781,6 +835,11 @@ async fn exec(
     }
     result?;
 
+    if args.params.dry_run {
+        return dry_run::dry_run(&mut config, progress, &mut analytics).await;
+    }
+
+    let intproxy_log_destination = config.internal_proxy.log_destination.path.clone();
     let res = exec_process(
         config,
         config_file_path.as_deref(),
@@ -792,8 +851,17 @@ async fn exec(
     )
     .await;
 
-    if res.is_err() && !analytics.has_error() {
-        analytics.set_error(AnalyticsError::Unknown);
+    if res.is_err() {
+        if !analytics.has_error() {
+            analytics.set_error(AnalyticsError::Unknown);
+        }
+
+        if intproxy_log_destination.exists() {
+            progress.warning(&format!(
+                "internal proxy logs for this session: {}",
+                intproxy_log_destination.display()
+            ));
+        }
     }
     res
 }
@@ -1012,14 +1080,30 @@ fn main() -> miette::Result<()> {
                 logging::init_intproxy_tracing_registry(&config).await?;
                 internal_proxy::proxy(config, port, watch, &user_data).await?
             }
+            Commands::TargetLogs => {
+                let config = mirrord_config::util::read_resolved_config()?;
+                target_logs::follow(config).await?
+            }
             Commands::VerifyConfig(args) => verify_config(args).await?,
+            Commands::Config(args) => match args.command {
+                ConfigCommand::Resolved(args) => resolved_config_command(args).await?,
+            },
+            Commands::Capture(args) => match args.command {
+                CaptureCommand::Inspect(args) => capture::inspect_command(args)?,
+            },
             Commands::Completions(args) => {
                 let mut cmd: clap::Command = Cli::command();
                 generate(args.shell, &mut cmd, "mirrord", &mut std::io::stdout());
             }
-            Commands::Teams => {
-                windows_unsupported!((), "teams", { teams::navigate_to_intro().await })
-            }
+            Commands::Teams(args) => windows_unsupported!(args, "teams", {
+                let context = teams::IntroContext::default();
+                if args.no_browser || std::io::stdout().is_terminal().not() {
+                    teams::print_teams_intro(&context);
+                } else if let Err(error) = teams::navigate_to_intro(&context, None).await {
+                    tracing::debug!(%error, "failed to open browser");
+                    teams::print_intro_link(&context);
+                }
+            }),
             Commands::Diagnose(args) => diagnose_command(*args).await?,
             Commands::Container(args) => windows_unsupported!(args, "container", {
                 let (runtime_args, exec_params) = args.into_parts();
@@ -1061,6 +1145,7 @@ fn main() -> miette::Result<()> {
                 .await?
             }
             Commands::Fix(args) => fix::fix_command(args).await?,
+            Commands::SelfUpdate(args) => self_update::self_update_command(args).await?,
         };
 
         Ok(())
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use mirrord_progress::Progress;
 use tracing::trace;
 
-use crate::user_data::UserData;
+use crate::{teams, user_data::UserData};
 
 /// Link to the mirrord newsletter signup page (with UTM query params)
 const NEWSLETTER_SIGNUP_URL: &str =
@@ -71,11 +71,10 @@ pub async fn suggest_newsletter_signup<P: Progress>(user_data: &mut UserData, pr
 pub async fn newsletter_command() {
     // open URL with param utm_source=newslettercmd
     let url = format!("{NEWSLETTER_SIGNUP_URL}cmd");
-    if let Err(error) = opener::open(&url) {
-        tracing::trace!("failed to open browser, command result: {error:?}");
-        println!(
-            "To sign up for the mirrord newsletter and get notified of new features as they come out, visit:\n\n\
-             {url}"
-        );
+    let prefix = "To sign up for the mirrord newsletter and get notified of new features as they \
+        come out, visit:\n\n";
+    if let Err(error) = teams::open_or_print(&url, prefix).await {
+        tracing::trace!(%error, "failed to open browser");
+        teams::print_link(&url, prefix);
     }
 }
@@ -20,8 +20,11 @@ async fn operator_setup() -> CliResult<(), OperatorSetupError> {
 pub(crate) async fn operator_command(args: OperatorArgs) -> CliResult<()> {
     match args.command {
         OperatorCommand::Setup => operator_setup().await.map_err(CliError::from),
-        OperatorCommand::Status { config_file } => {
-            StatusCommandHandler::new(config_file)
+        OperatorCommand::Status {
+            config_file,
+            context,
+        } => {
+            StatusCommandHandler::new(config_file, context)
                 .and_then(StatusCommandHandler::handle)
                 .await
         }
@@ -6,15 +6,16 @@ use std::{
     time::Duration,
 };
 
+use k8s_openapi::chrono::Utc;
 use mirrord_analytics::NullReporter;
 use mirrord_config::{LayerConfig, config::ConfigContext};
+use mirrord_kube::api::kubernetes::selected_kube_context;
 use mirrord_operator::{
     client::{NoClientCert, OperatorApi},
     crd::{
         MirrordOperatorSpec, MirrordSqsSession, QueueConsumer, QueueNameUpdate,
         kafka::MirrordKafkaEphemeralTopicSpec,
     },
-    types::LicenseInfoOwned,
 };
 use mirrord_progress::{Progress, ProgressTracker};
 use prettytable::{Row, Table, row};
@@ -26,17 +27,32 @@ use crate::{CliResult, error::CliError, util::remove_proxy_env};
 pub(super) struct StatusCommandHandler {
     /// Api to talk with session routes in the operator.
     operator_api: OperatorApi<NoClientCert>,
+
+    /// Kube context used to reach the operator, see [`selected_kube_context`].
+    kube_context: Option<String>,
 }
 
 impl StatusCommandHandler {
     #[tracing::instrument(level = Level::TRACE, err)]
-    pub(super) async fn new(config_file: Option<PathBuf>) -> CliResult<Self> {
+    pub(super) async fn new(
+        config_file: Option<PathBuf>,
+        context: Option<String>,
+    ) -> CliResult<Self> {
         let mut progress = ProgressTracker::from_env("Operator Status");
 
-        let mut cfg_context =
-            ConfigContext::default().override_env_opt(LayerConfig::FILE_PATH_ENV, config_file);
+        let mut cfg_context = ConfigContext::default()
+            .override_env_opt(LayerConfig::FILE_PATH_ENV, config_file)
+            .override_env_opt("MIRRORD_KUBE_CONTEXT", context);
         let layer_config = LayerConfig::resolve(&mut cfg_context)?;
 
+        let kube_context = selected_kube_context(
+            layer_config.kubeconfig.as_ref(),
+            layer_config.kube_context.as_deref(),
+        );
+        if let Some(kube_context) = &kube_context {
+            progress.info(&format!("using kube context `{kube_context}`"));
+        }
+
         if !layer_config.use_proxy {
             remove_proxy_env();
         }
@@ -53,7 +69,10 @@ impl StatusCommandHandler {
         status_progress.success(Some("fetched status"));
         progress.success(None);
 
-        Ok(Self { operator_api: api })
+        Ok(Self {
+            operator_api: api,
+            kube_context,
+        })
     }
 
     /// The Kafka information we want to display to the user is in the MirrordKafkaEphemeralTopic
@@ -202,31 +221,27 @@ impl StatusCommandHandler {
 
     #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
     pub(super) async fn handle(self) -> CliResult<()> {
-        let Self { operator_api: api } = self;
+        let Self {
+            operator_api: api,
+            kube_context,
+        } = self;
 
         let MirrordOperatorSpec {
             operator_version,
             default_namespace,
-            license:
-                LicenseInfoOwned {
-                    name,
-                    organization,
-                    expire_at,
-                    ..
-                },
+            license,
             ..
         } = &api.operator().spec;
 
-        let expire_at = expire_at.format("%e-%b-%Y");
+        let license = license.summary(Utc::now().date_naive());
+        let kube_context = kube_context.as_deref().unwrap_or("<in-cluster>");
 
         println!(
             r#"
+Kube context: {kube_context}
 Operator version: {operator_version}
 Operator default namespace: {default_namespace}
-Operator License
-    name: {name}
-    organization: {organization}
-    expire at: {expire_at}
+{license}
 "#
         );
 
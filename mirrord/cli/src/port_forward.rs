@@ -6,7 +6,9 @@ use std::{
 };
 
 use futures::StreamExt;
-use mirrord_config::feature::network::incoming::IncomingConfig;
+use mirrord_config::feature::network::incoming::{
+    IncomingConfig, IncomingMode as ConfigIncomingMode,
+};
 use mirrord_intproxy::{
     background_tasks::{BackgroundTasks, TaskError, TaskSender, TaskUpdate},
     main_tasks::{ProxyMessage, ToLayer},
@@ -18,7 +20,9 @@ use mirrord_intproxy_protocol::{
 };
 use mirrord_protocol::{
     CLIENT_READY_FOR_LOGS, ClientMessage, ConnectionId, DaemonMessage, LogLevel, Payload, Port,
-    dns::{DnsLookup, GetAddrInfoRequest, GetAddrInfoResponse, LookupRecord},
+    dns::{
+        DnsLookup, GetAddrInfoRequest, GetAddrInfoResponse, GetAddrInfoResponseV2, LookupRecord,
+    },
     outgoing::{
         LayerClose, LayerConnect, LayerWrite, SocketAddress,
         tcp::{DaemonTcpOutgoing, LayerTcpOutgoing},
@@ -284,7 +288,11 @@ impl PortForwarder {
                     );
                 }
             },
-            DaemonMessage::GetAddrInfoResponse(GetAddrInfoResponse(message)) => match message {
+            DaemonMessage::GetAddrInfoResponse(GetAddrInfoResponse(message))
+            | DaemonMessage::GetAddrInfoResponseV2(GetAddrInfoResponseV2 {
+                response: GetAddrInfoResponse(message),
+                ..
+            }) => match message {
                 Ok(DnsLookup(record)) if !record.is_empty() => {
                     // pop oneshot, send string
                     let resolved_ipv4: Vec<&LookupRecord> = record
@@ -352,7 +360,9 @@ impl PortForwarder {
             | DaemonMessage::UdpOutgoing(..)
             | DaemonMessage::Vpn(..)
             | DaemonMessage::TcpSteal(..)
-            | DaemonMessage::ReverseDnsLookup(..)) => {
+            | DaemonMessage::ReverseDnsLookup(..)
+            | DaemonMessage::SwitchCompressionResponse(..)
+            | DaemonMessage::Compressed(..)) => {
                 // includes unexpected DaemonMessage::Pong
                 return Err(PortForwardError::AgentError(format!(
                     "unexpected message from agent: {message:?}"
@@ -614,11 +624,14 @@ impl ReversePortForwarder {
             | message @ DaemonMessage::File(_)
             | message @ DaemonMessage::GetEnvVarsResponse(_)
             | message @ DaemonMessage::GetAddrInfoResponse(_)
+            | message @ DaemonMessage::GetAddrInfoResponseV2(_)
             | message @ DaemonMessage::PauseTarget(_)
             | message @ DaemonMessage::SwitchProtocolVersionResponse(_)
             | message @ DaemonMessage::Vpn(_)
             | message @ DaemonMessage::Pong
-            | message @ DaemonMessage::ReverseDnsLookup(_) => {
+            | message @ DaemonMessage::ReverseDnsLookup(_)
+            | message @ DaemonMessage::SwitchCompressionResponse(_)
+            | message @ DaemonMessage::Compressed(_) => {
                 return Err(PortForwardError::AgentError(format!(
                     "unexpected message from agent: {message:?}"
                 )));
@@ -885,6 +898,13 @@ impl LocalConnectionTask {
 pub struct IncomingMode {
     pub steal: bool,
     pub http_settings: Option<HttpSettings>,
+    /// Filters of the ports that have their own, see [`IncomingConfig::port_http_filters`].
+    ///
+    /// [`None`] means that the port is not filtered.
+    pub port_filters: HashMap<Port, Option<HttpFilter>>,
+    /// Whether to steal from the ports that have their own mode, see
+    /// [`IncomingConfig::port_modes`].
+    pub port_steal: HashMap<Port, bool>,
 }
 #[derive(Debug)]
 pub struct HttpSettings {
@@ -901,15 +921,10 @@ impl IncomingMode {
     ///
     /// * `config` - [`IncomingConfig`] is taken as `&mut` due to `add_probe_ports_to_http_ports`.
     fn new(config: &mut IncomingConfig, protocol_version: &Version) -> Self {
-        // Only create HttpSettings if there are actual filters configured.
-        if config.http_filter.is_filter_set().not() {
-            return Self {
-                steal: config.is_steal(),
-                http_settings: None,
-            };
-        }
-
-        if config.is_steal().not() && MIRROR_HTTP_FILTER_VERSION.matches(protocol_version).not() {
+        if config.uses_http_filter()
+            && config.is_steal().not()
+            && MIRROR_HTTP_FILTER_VERSION.matches(protocol_version).not()
+        {
             tracing::warn!(
                 %protocol_version,
                 "Negotiated mirrord-protocol does not support using an HTTP filter when mirroring traffic. \
@@ -917,56 +932,83 @@ impl IncomingMode {
             )
         }
 
-        let ports = config
-            .http_filter
-            .ports
-            .as_ref()
-            .cloned()
-            .map(HashSet::from);
+        // Only create HttpSettings if there are actual filters configured.
+        let http_settings = config.http_filter.is_filter_set().then(|| {
+            let ports = config
+                .http_filter
+                .ports
+                .as_ref()
+                .cloned()
+                .map(HashSet::from);
+
+            let filter = config
+                .http_filter
+                .as_protocol_http_filter()
+                .expect("invalid HTTP filter expression");
+
+            HttpSettings { filter, ports }
+        });
 
-        let filter = config
-            .http_filter
-            .as_protocol_http_filter()
+        let port_filters = config
+            .protocol_port_http_filters()
             .expect("invalid HTTP filter expression");
 
+        let port_steal = config
+            .port_modes
+            .iter()
+            .map(|(port, mode)| (*port, matches!(mode, ConfigIncomingMode::Steal)))
+            .collect();
+
         Self {
             steal: config.is_steal(),
-            http_settings: Some(HttpSettings { filter, ports }),
+            http_settings,
+            port_filters,
+            port_steal,
+        }
+    }
+
+    /// Returns whether traffic should be stolen from the given port, or mirrored.
+    ///
+    /// The port's own mode from [`IncomingConfig::port_modes`] takes precedence over the
+    /// top-level one.
+    fn steals(&self, port: Port) -> bool {
+        self.port_steal.get(&port).copied().unwrap_or(self.steal)
+    }
+
+    /// Returns the HTTP filter to be used for the given port, if any.
+    ///
+    /// The port's own filter from [`IncomingConfig::port_http_filters`] takes precedence over
+    /// the top-level one.
+    fn http_filter(&self, port: Port) -> Option<&HttpFilter> {
+        if let Some(filter) = self.port_filters.get(&port) {
+            return filter.as_ref();
         }
+
+        self.http_settings
+            .as_ref()
+            .filter(|settings| {
+                settings
+                    .ports
+                    .as_ref()
+                    .is_none_or(|ports| ports.contains(&port))
+            })
+            .map(|settings| &settings.filter)
     }
 
     /// Returns [`PortSubscription`] request to be used for the given port.
     pub fn subscription(&self, port: Port) -> PortSubscription {
-        if self.steal {
-            let steal_type = match &self.http_settings {
+        let filter = self.http_filter(port).cloned();
+
+        if self.steals(port) {
+            let steal_type = match filter {
+                Some(filter) => StealType::FilteredHttpEx(port, filter),
                 None => StealType::All(port),
-                Some(settings) => {
-                    if settings
-                        .ports
-                        .as_ref()
-                        .is_some_and(|p| p.contains(&port).not())
-                    {
-                        StealType::All(port)
-                    } else {
-                        StealType::FilteredHttpEx(port, settings.filter.clone())
-                    }
-                }
             };
             PortSubscription::Steal(steal_type)
         } else {
-            let mirror_type = match &self.http_settings {
+            let mirror_type = match filter {
+                Some(filter) => MirrorType::FilteredHttp(port, filter),
                 None => MirrorType::All(port),
-                Some(settings) => {
-                    if settings
-                        .ports
-                        .as_ref()
-                        .is_some_and(|p| p.contains(&port).not())
-                    {
-                        MirrorType::All(port)
-                    } else {
-                        MirrorType::FilteredHttp(port, settings.filter.clone())
-                    }
-                }
             };
             PortSubscription::Mirror(mirror_type)
         }
@@ -1022,6 +1064,7 @@ mod test {
     };
 
     use mirrord_config::feature::network::incoming::{IncomingConfig, IncomingMode};
+    use mirrord_intproxy_protocol::PortSubscription;
     use mirrord_protocol::{
         ClientMessage, DaemonMessage, ToPayload,
         outgoing::{
@@ -1030,8 +1073,8 @@ mod test {
         },
         tcp::{
             DaemonTcp, Filter, HttpRequest, HttpResponse, InternalHttpBody, InternalHttpBodyFrame,
-            InternalHttpRequest, InternalHttpResponse, LayerTcp, LayerTcpSteal, NewTcpConnectionV1,
-            StealType, TcpClose, TcpData,
+            InternalHttpRequest, InternalHttpResponse, LayerTcp, LayerTcpSteal, MirrorType,
+            NewTcpConnectionV1, StealType, TcpClose, TcpData,
         },
     };
     use mirrord_protocol_io::{Client, Connection, ConnectionOutput};
@@ -1048,6 +1091,26 @@ mod test {
         port_forward::{PortForwarder, ReversePortForwarder},
     };
 
+    /// Ports with their own mode are stolen from or mirrored regardless of the top-level mode.
+    #[test]
+    fn per_port_mode_subscription() {
+        let mut config = IncomingConfig {
+            mode: IncomingMode::Steal,
+            port_modes: HashMap::from([(9090, IncomingMode::Mirror)]),
+            ..Default::default()
+        };
+        let incoming_mode = super::IncomingMode::new(&mut config, &mirrord_protocol::VERSION);
+
+        assert_eq!(
+            incoming_mode.subscription(8080),
+            PortSubscription::Steal(StealType::All(8080))
+        );
+        assert_eq!(
+            incoming_mode.subscription(9090),
+            PortSubscription::Mirror(MirrorType::All(9090))
+        );
+    }
+
     /// Connects [`ReversePortForwarder`] with test code with [`ClientMessage`] and
     /// [`DaemonMessage`] channels. Runs a background [`tokio::task`] that auto responds to
     /// standard [`mirrord_protocol`] messages (e.g [`ClientMessage::Ping`]).
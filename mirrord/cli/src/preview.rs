@@ -37,7 +37,7 @@ use mirrord_operator::{
         preview::{PreviewIncomingConfig, PreviewSession, PreviewSessionPhase, PreviewSessionSpec},
         session::SessionTarget,
     },
-    types::OPERATOR_OWNERSHIP_LABEL,
+    types::{ownership_labels, resolve_isolation_marker},
 };
 use mirrord_progress::{Progress, ProgressTracker};
 use oci_spec::distribution::Reference;
@@ -167,18 +167,16 @@ async fn preview_start(args: PreviewStartArgs) -> CliResult<()> {
         format!("preview-session-{sanitized_target}-{uuid_short}")
     };
 
-    // Operators compiled with a custom OPERATOR_ISOLATION_MARKER only reconcile preview
-    // sessions labeled with their marker (see the label selector in the preview-env
-    // controller). The runtime env var check here allows developers to label the session
-    // so it gets picked up by an isolated operator instead of the production one.
+    // Operators only reconcile preview sessions labeled with their isolation marker (see the
+    // label selector in the preview-env controller). Setting `OPERATOR_ISOLATION_MARKER` allows
+    // developers to label the session so it gets picked up by an isolated operator instead of
+    // the production one.
     let session_labels = {
-        let mut labels = BTreeMap::from([(
+        let mut labels = ownership_labels(&resolve_isolation_marker());
+        labels.insert(
             PREVIEW_SESSION_KEY_LABEL.to_owned(),
             layer_config.key.as_str().to_owned(),
-        )]);
-        if let Ok(marker) = std::env::var("OPERATOR_ISOLATION_MARKER") {
-            labels.insert(OPERATOR_OWNERSHIP_LABEL.to_owned(), marker);
-        }
+        );
         labels
     };
 
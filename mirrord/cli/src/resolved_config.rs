@@ -0,0 +1,244 @@
+//! `mirrord config resolved [--show-sources]` prints the fully resolved [`LayerConfig`] as pretty
+//! JSON, optionally annotating each value with where it came from.
+//!
+//! Secret values (Redis credentials, env overrides) are never printed, only their presence.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+};
+
+use mirrord_config::{
+    LayerConfig,
+    config::{
+        ConfigContext,
+        provenance::{ConfigSources, ValueSource},
+    },
+    feature::database_branches::{DatabaseBranchConfig, RedisValueSource},
+};
+use serde_json::{Map, Value, json};
+
+use crate::{config::ConfigResolvedArgs, error::CliResult};
+
+/// Replaces secret values in the printed config.
+const REDACTED: &str = "<redacted>";
+
+/// Handles the `mirrord config resolved` command.
+pub(super) async fn resolved_config_command(args: ConfigResolvedArgs) -> CliResult<()> {
+    let env_overrides = args.params.as_env_vars();
+    let cli_envs = env_names(&env_overrides);
+
+    let mut context = ConfigContext::default().override_envs(&env_overrides);
+    let output = resolve(&mut context, &cli_envs, args.show_sources)?;
+
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
+
+/// Names of the environment variables set from the command line flags.
+fn env_names<V>(envs: &HashMap<&'static OsStr, V>) -> HashSet<&'static str> {
+    envs.keys().copied().filter_map(OsStr::to_str).collect()
+}
+
+/// Resolves the config and turns it into the printed JSON.
+///
+/// `cli_envs` are the environment variables set from the command line flags, used to tell flags
+/// apart from environment variables set by the user.
+fn resolve(
+    context: &mut ConfigContext,
+    cli_envs: &HashSet<&str>,
+    show_sources: bool,
+) -> CliResult<Value> {
+    let (mut config, sources) = LayerConfig::resolve_with_sources(context)?;
+    redact(&mut config);
+
+    let output = serde_json::to_value(&config)?;
+
+    if !show_sources {
+        return Ok(output);
+    }
+
+    let annotator = Annotator {
+        sources: &sources,
+        cli_envs,
+    };
+
+    Ok(annotator.annotate(output, &mut Vec::new()))
+}
+
+/// Replaces the secret values in the config with [`REDACTED`], leaving the unset ones in place,
+/// so that the user can still tell whether they are set.
+fn redact(config: &mut LayerConfig) {
+    for value in config
+        .feature
+        .env
+        .r#override
+        .iter_mut()
+        .flat_map(|overrides| overrides.values_mut())
+    {
+        *value = REDACTED.to_owned();
+    }
+
+    for branch in config.feature.db_branches.0.iter_mut() {
+        let DatabaseBranchConfig::Redis(redis) = branch else {
+            continue;
+        };
+
+        let connection = &mut redis.connection;
+        for source in [&mut connection.url, &mut connection.password]
+            .into_iter()
+            .flatten()
+        {
+            if let RedisValueSource::Direct(value) = source {
+                *value = REDACTED.to_owned();
+            }
+        }
+    }
+}
+
+/// Replaces each leaf of the config with `{"value": ..., "source": ...}`.
+struct Annotator<'a> {
+    sources: &'a ConfigSources,
+    cli_envs: &'a HashSet<&'a str>,
+}
+
+impl Annotator<'_> {
+    fn annotate(&self, value: Value, path: &mut Vec<String>) -> Value {
+        let key = path.join(".");
+
+        // Tracked fields are leaves, even if their values are objects (e.g. `target.path`).
+        match value {
+            Value::Object(fields) if self.sources.field(&key).is_none() && !fields.is_empty() => {
+                let fields = fields
+                    .into_iter()
+                    .map(|(name, field)| {
+                        path.push(name.clone());
+                        let field = self.annotate(field, path);
+                        path.pop();
+                        (name, field)
+                    })
+                    .collect::<Map<_, _>>();
+
+                Value::Object(fields)
+            }
+            value => {
+                let source = self.describe(self.sources.source_of(&key));
+                json!({ "value": value, "source": source })
+            }
+        }
+    }
+
+    fn describe(&self, source: &ValueSource) -> String {
+        match source {
+            ValueSource::Env(name) if self.cli_envs.contains(name) => format!("cli flag ({name})"),
+            other => other.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, ffi::OsStr, io::Write};
+
+    use mirrord_config::{LayerConfig, config::ConfigContext};
+    use serde_json::json;
+
+    use super::{env_names, resolve};
+
+    #[test]
+    fn annotates_winners_and_redacts_secrets() {
+        let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        file.write_all(
+            br#"{
+  "agent": {
+    "namespace": "from-file",
+    "startup_timeout": 120,
+    "ttl": 30
+  },
+  "feature": {
+    "env": {
+      "override": { "DB_PASSWORD": "hunter2" }
+    },
+    "db_branches": [
+      {
+        "type": "redis",
+        "location": "local",
+        "connection": { "host": "localhost", "password": "hunter2" }
+      }
+    ]
+  }
+}"#,
+        )
+        .unwrap();
+
+        // Mimics `--agent-namespace from-cli -f <file>`.
+        let cli: HashMap<&'static OsStr, &OsStr> = HashMap::from([
+            ("MIRRORD_AGENT_NAMESPACE".as_ref(), "from-cli".as_ref()),
+            (LayerConfig::FILE_PATH_ENV.as_ref(), file.path().as_os_str()),
+        ]);
+        let mut context = ConfigContext::default()
+            .override_envs(&cli)
+            .override_env("MIRRORD_AGENT_STARTUP_TIMEOUT", "15")
+            .strict_env(true);
+
+        let output = resolve(&mut context, &env_names(&cli), true).unwrap();
+        let agent = &output["agent"];
+
+        assert_eq!(
+            agent["namespace"],
+            json!({ "value": "from-cli", "source": "cli flag (MIRRORD_AGENT_NAMESPACE)" })
+        );
+        assert_eq!(
+            agent["startup_timeout"],
+            json!({ "value": 15, "source": "env MIRRORD_AGENT_STARTUP_TIMEOUT" })
+        );
+        assert_eq!(
+            agent["ttl"]["source"],
+            format!("file {}:5", file.path().display())
+        );
+        assert_eq!(agent["privileged"]["source"], "default");
+        assert_eq!(
+            output["feature"]["env"]["override"]["value"],
+            json!({ "DB_PASSWORD": "<redacted>" })
+        );
+        let redis = &output["feature"]["db_branches"]["value"][0]["connection"];
+        assert_eq!(redis["host"], "localhost");
+        assert_eq!(redis["password"], "<redacted>");
+    }
+
+    #[test]
+    fn shows_expanded_values() {
+        let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        file.write_all(
+            br#"{
+  "target": { "namespace": "${env:TEAM_NAMESPACE}" },
+  "feature": {
+    "env": {
+      "override": { "DB_PASSWORD": "${env:DB_PASSWORD}" }
+    }
+  }
+}"#,
+        )
+        .unwrap();
+
+        let mut context = ConfigContext::default()
+            .override_env(LayerConfig::FILE_PATH_ENV, file.path())
+            .override_env("TEAM_NAMESPACE", "payments")
+            .override_env("DB_PASSWORD", "hunter2")
+            .override_env(LayerConfig::SET_ENV, "agent.namespace=from-set")
+            .strict_env(true);
+
+        let output = resolve(&mut context, &Default::default(), true).unwrap();
+
+        assert_eq!(output["target"]["namespace"]["value"], "payments");
+        assert_eq!(
+            output["agent"]["namespace"],
+            json!({ "value": "from-set", "source": "cli flag (--set)" })
+        );
+        assert_eq!(
+            output["feature"]["env"]["override"]["value"],
+            json!({ "DB_PASSWORD": "<redacted>" })
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Implementation of `mirrord self-update`.
+//!
+//! Downloads a release artifact from GitHub, verifies it against the checksum published alongside
+//! it, and only then swaps it with the currently running executable. Nothing on disk is touched
+//! until the new binary is fully downloaded and verified.
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::Path,
+    time::Duration,
+};
+
+use mirrord_progress::{Progress, ProgressTracker};
+use semver::Version;
+use sha2::{Digest, Sha256};
+use tracing::Level;
+
+use crate::{CURRENT_VERSION, CliResult, config::SelfUpdateArgs, error::SelfUpdateError};
+
+/// Base URL of the mirrord GitHub releases.
+const RELEASES_URL: &str = "https://github.com/metalbear-co/mirrord/releases/download";
+
+/// How long we wait for each of the requests made when updating.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How the binary is packaged in a [`ReleaseAsset`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AssetKind {
+    /// The asset is the binary itself.
+    Raw,
+    /// The asset is a zip archive, and the binary is stored under the given name.
+    Zip { entry: &'static str },
+}
+
+/// A release artifact that contains the mirrord binary for a specific platform, as published by
+/// the release workflow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ReleaseAsset {
+    name: &'static str,
+    /// Name of the file containing the sha256 checksum of [`ReleaseAsset::name`].
+    checksum_name: &'static str,
+    kind: AssetKind,
+}
+
+impl ReleaseAsset {
+    /// Returns the asset for the given [`std::env::consts::OS`] and [`std::env::consts::ARCH`].
+    fn for_platform(os: &str, arch: &str) -> Option<Self> {
+        let zip = |name, checksum_name| Self {
+            name,
+            checksum_name,
+            kind: AssetKind::Zip { entry: "mirrord" },
+        };
+
+        match (os, arch) {
+            ("linux", "x86_64") => Some(zip(
+                "mirrord_linux_x86_64.zip",
+                "mirrord_linux_x86_64.shasum256",
+            )),
+            ("linux", "aarch64") => Some(zip(
+                "mirrord_linux_aarch64.zip",
+                "mirrord_linux_aarch64.shasum256",
+            )),
+            ("macos", _) => Some(zip(
+                "mirrord_mac_universal.zip",
+                "mirrord_mac_universal.shasum256",
+            )),
+            ("windows", "x86_64") => Some(Self {
+                name: "mirrord.exe",
+                checksum_name: "mirrord.exe.sha256",
+                kind: AssetKind::Raw,
+            }),
+            _ => None,
+        }
+    }
+
+    fn url(&self, version: &Version) -> String {
+        format!("{RELEASES_URL}/{version}/{}", self.name)
+    }
+
+    fn checksum_url(&self, version: &Version) -> String {
+        format!("{RELEASES_URL}/{version}/{}", self.checksum_name)
+    }
+
+    /// Extracts the binary from the downloaded (and already verified) asset.
+    fn extract(&self, asset: &[u8]) -> Result<Vec<u8>, SelfUpdateError> {
+        match self.kind {
+            AssetKind::Raw => Ok(asset.to_vec()),
+            AssetKind::Zip { entry } => {
+                let mut archive = zip::ZipArchive::new(Cursor::new(asset))?;
+                let mut file = archive.by_name(entry)?;
+                let mut binary = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut binary)
+                    .map_err(|error| SelfUpdateError::Archive(error.into()))?;
+
+                Ok(binary)
+            }
+        }
+    }
+}
+
+/// Parses a checksum file, which is either a bare hex digest (Windows artifacts), or the output
+/// of `shasum -a 256` (`<digest>  <file name>`).
+fn parse_checksum(contents: &str) -> Option<String> {
+    let digest = contents.split_whitespace().next()?;
+
+    (digest.len() == 64 && digest.bytes().all(|byte| byte.is_ascii_hexdigit()))
+        .then(|| digest.to_ascii_lowercase())
+}
+
+/// Makes sure the sha256 of `asset` is `expected`.
+fn verify_checksum(asset_name: &str, asset: &[u8], expected: &str) -> Result<(), SelfUpdateError> {
+    let actual = hex::encode(Sha256::digest(asset));
+
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(SelfUpdateError::ChecksumMismatch {
+            asset: asset_name.to_owned(),
+            expected: expected.to_owned(),
+            actual,
+        })
+    }
+}
+
+/// Whether replacing the binary at `path` requires elevated privileges.
+#[cfg(unix)]
+fn is_elevated_path(path: &Path) -> bool {
+    [Some(path), path.parent()]
+        .into_iter()
+        .flatten()
+        .any(|path| fs::metadata(path).is_ok_and(|metadata| metadata.uid() == 0))
+}
+
+/// Whether replacing the binary at `path` requires elevated privileges.
+#[cfg(windows)]
+fn is_elevated_path(path: &Path) -> bool {
+    ["ProgramFiles", "ProgramFiles(x86)", "ProgramW6432"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .any(|program_files| path.starts_with(program_files))
+}
+
+/// Path where the previous binary is moved on Windows, where a running executable can be renamed,
+/// but not overwritten or deleted.
+#[cfg(windows)]
+fn previous_binary_path(current: &Path) -> std::path::PathBuf {
+    current.with_extension("exe.old")
+}
+
+/// Atomically replaces the executable at `current` with `binary`.
+///
+/// The new binary is first written next to `current` (so that the final rename never crosses
+/// filesystems) and then renamed over it. On failure, the staged file is removed and `current` is
+/// left as it was.
+#[tracing::instrument(level = Level::TRACE, skip(binary), err)]
+fn replace_executable(current: &Path, binary: &[u8]) -> Result<(), SelfUpdateError> {
+    let replace_error = |error| SelfUpdateError::Replace {
+        path: current.to_path_buf(),
+        error,
+    };
+
+    let file_name = current
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "mirrord".to_owned());
+    let staged = current.with_file_name(format!(".{file_name}.update-{}", std::process::id()));
+
+    let result = stage_binary(current, &staged, binary).and_then(|()| swap(current, &staged));
+    if result.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+
+    result.map_err(replace_error)
+}
+
+/// Writes `binary` to `staged`, with the same permissions as the `current` executable.
+fn stage_binary(current: &Path, staged: &Path, binary: &[u8]) -> std::io::Result<()> {
+    fs::write(staged, binary)?;
+    fs::set_permissions(staged, fs::metadata(current)?.permissions())?;
+
+    // Gatekeeper refuses to run quarantined binaries. We never set the attribute ourselves, but
+    // some endpoint security tools add it to every new executable. Failing here most likely means
+    // that the attribute is not there.
+    #[cfg(target_os = "macos")]
+    let _ = std::process::Command::new("xattr")
+        .args(["-d", "com.apple.quarantine"])
+        .arg(staged)
+        .output();
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn swap(current: &Path, staged: &Path) -> std::io::Result<()> {
+    fs::rename(staged, current)
+}
+
+/// Windows does not allow overwriting the running executable, but allows renaming it. The previous
+/// binary is moved out of the way and deleted on the next update.
+#[cfg(windows)]
+fn swap(current: &Path, staged: &Path) -> std::io::Result<()> {
+    let previous = previous_binary_path(current);
+
+    let _ = fs::remove_file(&previous);
+    fs::rename(current, &previous)?;
+
+    fs::rename(staged, current).inspect_err(|_| {
+        let _ = fs::rename(&previous, current);
+    })
+}
+
+/// Asks the version service for the latest release, same as the update notice.
+async fn latest_version(client: &reqwest::Client) -> Result<Version, SelfUpdateError> {
+    let latest = client
+        .get(format!(
+            "https://version.mirrord.dev/get-latest-version?source=2&currentVersion={CURRENT_VERSION}&platform={}",
+            std::env::consts::OS,
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    Version::parse(latest.trim()).map_err(SelfUpdateError::InvalidLatestVersion)
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, SelfUpdateError> {
+    let bytes = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    Ok(bytes.to_vec())
+}
+
+/// Handles the `mirrord self-update` command.
+pub(crate) async fn self_update_command(args: SelfUpdateArgs) -> CliResult<()> {
+    let mut progress = ProgressTracker::from_env("mirrord self-update");
+
+    match self_update(args, &mut progress).await {
+        Ok(()) => Ok(()),
+        Err(error) => {
+            progress.failure(Some("update failed, the current binary was left untouched"));
+            Err(error.into())
+        }
+    }
+}
+
+async fn self_update<P: Progress>(
+    args: SelfUpdateArgs,
+    progress: &mut P,
+) -> Result<(), SelfUpdateError> {
+    let (os, arch) = (std::env::consts::OS, std::env::consts::ARCH);
+    let asset = ReleaseAsset::for_platform(os, arch).ok_or_else(|| {
+        SelfUpdateError::UnsupportedPlatform {
+            os: os.to_owned(),
+            arch: arch.to_owned(),
+        }
+    })?;
+
+    let current_exe = std::env::current_exe()
+        .and_then(fs::canonicalize)
+        .map_err(SelfUpdateError::CurrentExe)?;
+
+    #[cfg(windows)]
+    let _ = fs::remove_file(previous_binary_path(&current_exe));
+
+    let exe_path = current_exe.to_string_lossy();
+    if exe_path.contains("homebrew") || exe_path.contains("/Cellar/") {
+        return Err(SelfUpdateError::Homebrew(current_exe));
+    }
+
+    if is_elevated_path(&current_exe) && !args.allow_elevated {
+        return Err(SelfUpdateError::ElevatedPath(current_exe));
+    }
+
+    let client = reqwest::Client::builder()
+        .user_agent(format!("mirrord-cli/{CURRENT_VERSION}"))
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+
+    // Cargo rejects package versions that are not valid semver.
+    let current_version =
+        Version::parse(CURRENT_VERSION).expect("CARGO_PKG_VERSION should be a valid semver");
+    let version = match args.version {
+        Some(version) => version,
+        None => {
+            let mut subtask = progress.subtask("checking the latest release");
+            let version = latest_version(&client).await?;
+            subtask.success(Some(&format!("latest release is {version}")));
+            version
+        }
+    };
+
+    if version == current_version {
+        progress.success(Some(&format!("mirrord {version} is already installed")));
+        return Ok(());
+    }
+
+    if args.check {
+        progress.info(&format!(
+            "would download {} and verify it against {}",
+            asset.url(&version),
+            asset.checksum_url(&version),
+        ));
+        progress.info(&format!(
+            "would replace {} (mirrord {current_version}) with mirrord {version}",
+            current_exe.display(),
+        ));
+        progress.success(Some("nothing was changed"));
+        return Ok(());
+    }
+
+    let mut subtask = progress.subtask(&format!("downloading mirrord {version}"));
+    let checksum = download(&client, &asset.checksum_url(&version)).await?;
+    let checksum = parse_checksum(&String::from_utf8_lossy(&checksum))
+        .ok_or_else(|| SelfUpdateError::MalformedChecksum(asset.checksum_name.to_owned()))?;
+    let downloaded = download(&client, &asset.url(&version)).await?;
+    verify_checksum(asset.name, &downloaded, &checksum)?;
+    let binary = asset.extract(&downloaded)?;
+    subtask.success(Some("downloaded and verified checksum"));
+
+    let mut subtask = progress.subtask(&format!("replacing {}", current_exe.display()));
+    replace_executable(&current_exe, &binary)?;
+    subtask.success(None);
+
+    progress.success(Some(&format!(
+        "updated mirrord from {current_version} to {version}"
+    )));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    use rstest::rstest;
+    use sha2::{Digest, Sha256};
+
+    use super::{AssetKind, ReleaseAsset, parse_checksum, verify_checksum};
+
+    #[rstest]
+    #[case("linux", "x86_64", Some("mirrord_linux_x86_64.zip"))]
+    #[case("linux", "aarch64", Some("mirrord_linux_aarch64.zip"))]
+    #[case("macos", "aarch64", Some("mirrord_mac_universal.zip"))]
+    #[case("macos", "x86_64", Some("mirrord_mac_universal.zip"))]
+    #[case("windows", "x86_64", Some("mirrord.exe"))]
+    #[case("windows", "aarch64", None)]
+    #[case("freebsd", "x86_64", None)]
+    fn asset_for_platform(#[case] os: &str, #[case] arch: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            ReleaseAsset::for_platform(os, arch).map(|asset| asset.name),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::shasum(
+        "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08  mirrord_linux_x86_64.zip\n",
+        Some("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")
+    )]
+    #[case::bare(
+        "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+        Some("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")
+    )]
+    #[case::too_short("9f86d081884c7d659a2feaa0c55ad015", None)]
+    #[case::not_hex("<html>Not Found</html>", None)]
+    #[case::empty("", None)]
+    fn checksum_parsing(#[case] contents: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_checksum(contents).as_deref(), expected);
+    }
+
+    #[test]
+    fn checksum_mismatch_is_rejected() {
+        let expected = hex::encode(Sha256::digest(b"test"));
+
+        verify_checksum("asset", b"test", &expected).unwrap();
+        verify_checksum("asset", b"tampered", &expected).unwrap_err();
+    }
+
+    #[test]
+    fn binary_is_extracted_from_zip() {
+        let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        archive
+            .start_file("mirrord", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        archive.write_all(b"new mirrord").unwrap();
+        let archive = archive.finish().unwrap().into_inner();
+
+        let asset = ReleaseAsset::for_platform("linux", "x86_64").unwrap();
+        assert_eq!(asset.kind, AssetKind::Zip { entry: "mirrord" });
+        assert_eq!(asset.extract(&archive).unwrap(), b"new mirrord");
+
+        asset.extract(b"not a zip").unwrap_err();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_is_replaced_with_same_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let current = dir.path().join("mirrord");
+        std::fs::write(&current, b"old mirrord").unwrap();
+        std::fs::set_permissions(&current, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        super::replace_executable(&current, b"new mirrord").unwrap();
+
+        assert_eq!(std::fs::read(&current).unwrap(), b"new mirrord");
+        assert_eq!(
+            std::fs::metadata(&current).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_replace_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let current = dir.path().join("mirrord");
+
+        super::replace_executable(&current, b"new mirrord").unwrap_err();
+
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
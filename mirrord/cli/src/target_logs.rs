@@ -0,0 +1,206 @@
+//! Support for `feature.target_logs`.
+//!
+//! Right before `mirrord exec` replaces itself with the user application, it spawns the hidden
+//! `mirrord target-logs` command, which streams the target container's logs to the inherited
+//! stderr. The command exits once its parent (the user application) is gone.
+
+use std::{
+    io::{IsTerminal, Write},
+    ops::Not,
+    time::Duration,
+};
+
+use futures::{AsyncBufReadExt, TryStreamExt};
+use k8s_openapi::{
+    api::core::v1::Pod,
+    chrono::{DateTime, Utc},
+};
+use kube::{Api, Client, api::LogParams};
+use mirrord_config::{LayerConfig, target::Target};
+use mirrord_kube::{api::runtime::RuntimeDataProvider, error::KubeApiError};
+use thiserror::Error;
+
+use crate::{CliResult, kube::kube_client_from_layer_config};
+
+/// How long to wait before re-establishing the log stream.
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// How often to check whether the user application is still running.
+#[cfg(not(target_os = "windows"))]
+const PARENT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Errors that interrupt a single log stream, which is then re-established.
+#[derive(Debug, Error)]
+enum StreamError {
+    #[error("failed to resolve the target: {0}")]
+    Target(#[from] KubeApiError),
+    #[error("failed to open the log stream: {0}")]
+    Kube(#[from] kube::Error),
+    #[error("failed to read the log stream: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Spawns `mirrord target-logs` if `feature.target_logs` is enabled.
+///
+/// Failures are reported as warnings, the session starts regardless.
+#[cfg(not(target_os = "windows"))]
+pub(crate) fn spawn<P: mirrord_progress::Progress>(config: &LayerConfig, progress: &mut P) {
+    if config.feature.target_logs.not() {
+        return;
+    }
+
+    if matches!(config.target.path, None | Some(Target::Targetless)) {
+        progress.warning("feature.target_logs has no effect in the targetless mode");
+        return;
+    }
+
+    if config.feature.copy_target.enabled {
+        progress.warning("feature.target_logs has no effect with feature.copy_target");
+        return;
+    }
+
+    let result: Result<(), Box<dyn std::error::Error>> = try {
+        std::process::Command::new(std::env::current_exe()?)
+            .arg("target-logs")
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::inherit())
+            .env(LayerConfig::RESOLVED_CONFIG_ENV, config.encode()?)
+            .spawn()?;
+    };
+
+    if let Err(error) = result {
+        progress.warning(&format!("failed to follow the target logs: {error}"));
+    }
+}
+
+/// Entry point of `mirrord target-logs`.
+///
+/// Streams the logs until the parent process exits.
+pub(crate) async fn follow(config: LayerConfig) -> CliResult<()> {
+    let client = kube_client_from_layer_config(&config).await?;
+
+    tokio::select! {
+        _ = parent_exited() => {}
+        _ = stream_logs(&client, &config) => {}
+    }
+
+    Ok(())
+}
+
+/// Resolves when the process that spawned us exits.
+#[cfg(not(target_os = "windows"))]
+async fn parent_exited() {
+    let parent = std::os::unix::process::parent_id();
+
+    while std::os::unix::process::parent_id() == parent {
+        tokio::time::sleep(PARENT_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(target_os = "windows")]
+async fn parent_exited() {
+    std::future::pending().await
+}
+
+/// Streams the logs of the target container, re-establishing the stream whenever it ends, e.g.
+/// when the container restarts or the target pod is replaced.
+///
+/// Only lines logged after the session start are printed, each of them once.
+async fn stream_logs(client: &Client, config: &LayerConfig) {
+    let Some(target) = config.target.path.as_ref() else {
+        return;
+    };
+    let namespace = config.target.namespace.as_deref();
+    let colored = std::io::stderr().is_terminal();
+
+    let mut last_seen = Utc::now();
+    loop {
+        match stream_once(client, target, namespace, colored, &mut last_seen).await {
+            Ok(()) => tracing::debug!("Target log stream ended"),
+            Err(error) => tracing::debug!(%error, "Target log stream failed"),
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn stream_once(
+    client: &Client,
+    target: &Target,
+    namespace: Option<&str>,
+    colored: bool,
+    last_seen: &mut DateTime<Utc>,
+) -> Result<(), StreamError> {
+    let runtime_data = target.runtime_data(client, namespace).await?;
+    let prefix = prefix(
+        &runtime_data.pod_name,
+        &runtime_data.container_name,
+        colored,
+    );
+
+    // `since_time` has a precision of one second, so the lines logged during the same second
+    // are filtered out below.
+    let params = LogParams {
+        follow: true,
+        container: Some(runtime_data.container_name.clone()),
+        since_time: Some(*last_seen),
+        timestamps: true,
+        ..Default::default()
+    };
+    let logs = Api::<Pod>::namespaced(client.clone(), &runtime_data.pod_namespace)
+        .log_stream(&runtime_data.pod_name, &params)
+        .await?;
+
+    let mut lines = logs.lines();
+    while let Some(line) = lines.try_next().await? {
+        let Some((timestamp, message)) = parse_line(&line) else {
+            continue;
+        };
+        if timestamp <= *last_seen {
+            continue;
+        }
+        *last_seen = timestamp;
+
+        // Single write per line, so that it's not split by the user application output.
+        let _ = std::io::stderr().write_all(format!("{prefix}{message}\n").as_bytes());
+    }
+
+    Ok(())
+}
+
+/// Builds the `[pod/container] ` prefix of the printed lines, dimmed when `colored`.
+fn prefix(pod: &str, container: &str, colored: bool) -> String {
+    if colored {
+        format!("\x1b[2m[{pod}/{container}]\x1b[0m ")
+    } else {
+        format!("[{pod}/{container}] ")
+    }
+}
+
+/// Splits a log line requested with [`LogParams::timestamps`] into its timestamp and message.
+fn parse_line(line: &str) -> Option<(DateTime<Utc>, &str)> {
+    let (timestamp, message) = line.split_once(' ').unwrap_or((line, ""));
+    let timestamp = DateTime::parse_from_rfc3339(timestamp).ok()?;
+
+    Some((timestamp.to_utc(), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(
+        "2024-05-01T10:00:00.123456789Z GET /health 200",
+        Some("GET /health 200")
+    )]
+    #[case("2024-05-01T10:00:00Z ", Some(""))]
+    #[case("2024-05-01T10:00:00Z", Some(""))]
+    #[case("not a timestamp", None)]
+    fn parse(#[case] line: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_line(line).map(|(_, message)| message), expected);
+    }
+}
@@ -1,11 +1,398 @@
+#[cfg(feature = "qr")]
+use std::io::IsTerminal;
+use std::{
+    env, fs, io,
+    ops::Not,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use opener::OpenError;
+#[cfg(feature = "qr")]
+use qrcode::{QrCode, render::unicode::Dense1x2};
+use reqwest::Url;
+use tokio::{sync::oneshot, time};
+
+/// Landing page of mirrord for Teams, without the UTM parameters.
+const MIRRORD_FOR_TEAMS_BASE_URL: &str = "https://app.metalbear.com/";
+
+/// Overrides [`MIRRORD_FOR_TEAMS_BASE_URL`], e.g. with the portal of a self-hosted deployment.
+const MIRRORD_TEAMS_URL_ENV: &str = "MIRRORD_TEAMS_URL";
+
+/// Printed before the link to mirrord for Teams introduction.
+const INTRO_LINK_PREFIX: &str = "To try mirrord for Teams, visit ";
+
+/// How long [`open_url`] waits for the browser to launch.
+const OPEN_BROWSER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// When set, mirrord never tries to open the browser, and only prints the links.
+const MIRRORD_NO_BROWSER_ENV: &str = "MIRRORD_NO_BROWSER";
+
+/// Overrides the browser used to open the links, handled by [`opener::open_browser`].
+const BROWSER_ENV: &str = "BROWSER";
+
+/// Kernel version, used to detect WSL, see [`is_wsl`].
+const PROC_VERSION_PATH: &str = "/proc/version";
+
+/// Where the mirrord for Teams introduction is shown from, used for the UTM parameters of the
+/// link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntroContext {
+    pub utm_source: &'static str,
+    pub utm_medium: &'static str,
+    pub utm_campaign: Option<&'static str>,
+}
+
+impl IntroContext {
+    /// Builds the link to mirrord for Teams introduction, see [`intro_base_url`].
+    pub fn url(&self) -> String {
+        self.url_with_base(intro_base_url())
+    }
+
+    /// Appends the UTM parameters to the given base URL, after its own query parameters.
+    fn url_with_base(&self, mut base: Url) -> String {
+        {
+            let mut query = base.query_pairs_mut();
+            query
+                .append_pair("utm_source", self.utm_source)
+                .append_pair("utm_medium", self.utm_medium);
+            if let Some(campaign) = self.utm_campaign {
+                query.append_pair("utm_campaign", campaign);
+            }
+        }
+
+        base.into()
+    }
+}
+
+/// Returns the base URL of mirrord for Teams, taken from [`MIRRORD_TEAMS_URL_ENV`], or
+/// [`MIRRORD_FOR_TEAMS_BASE_URL`] when it's not set or not a valid URL.
+fn intro_base_url() -> Url {
+    let default = || Url::parse(MIRRORD_FOR_TEAMS_BASE_URL).expect("default URL is valid");
+
+    let Some(base) = env::var(MIRRORD_TEAMS_URL_ENV)
+        .ok()
+        .filter(|base| base.is_empty().not())
+    else {
+        return default();
+    };
+
+    parse_base_url(&base).unwrap_or_else(|error| {
+        tracing::warn!(%error, %base, "ignoring invalid {MIRRORD_TEAMS_URL_ENV}");
+        default()
+    })
+}
+
+/// Parses a base URL of mirrord for Teams, which must be an `http` or `https` URL.
+fn parse_base_url(base: &str) -> Result<Url, String> {
+    let url = Url::parse(base.trim()).map_err(|error| error.to_string())?;
+    match url.scheme() {
+        "http" | "https" if url.cannot_be_a_base().not() && url.host().is_some() => Ok(url),
+        _ => Err(format!("`{url}` is not an http(s) URL")),
+    }
+}
+
+/// Context of the `mirrord teams` command.
+impl Default for IntroContext {
+    fn default() -> Self {
+        Self {
+            utm_source: "teamscmd",
+            utm_medium: "cli",
+            utm_campaign: None,
+        }
+    }
+}
+
+/// Returns the link to mirrord for Teams introduction, with the UTM parameters of the given
+/// [`IntroContext`].
+///
+/// For callers that present the link themselves, and open it with [`open_url`] separately.
+pub fn teams_intro_url(context: &IntroContext) -> String {
+    context.url()
+}
+
 /// Attempts to open mirrord for Teams introduction in the default browser.
-/// In case of failure, prints the link.
-pub async fn navigate_to_intro() {
-    const MIRRORD_FOR_TEAMS_URL: &str =
-        "https://app.metalbear.com/?utm_source=teamscmd&utm_medium=cli";
-
-    if let Err(error) = opener::open(MIRRORD_FOR_TEAMS_URL) {
-        tracing::trace!("failed to open browser, command result: {error:?}");
-        println!("To try mirrord for Teams, visit {MIRRORD_FOR_TEAMS_URL}");
+/// In case no browser could be launched, prints the link.
+///
+/// The optional `on_outcome` hook is called with the [`IntroOutcome`], e.g. to record it in
+/// analytics. It is called on the current task, so it must not block.
+///
+/// Use [`open_url`] with [`teams_intro_url`] to skip the printing.
+pub async fn navigate_to_intro(
+    context: &IntroContext,
+    on_outcome: Option<&dyn Fn(IntroOutcome)>,
+) -> io::Result<OpenOutcome> {
+    let result = open_or_print(&teams_intro_url(context), INTRO_LINK_PREFIX).await;
+
+    if let Some(on_outcome) = on_outcome {
+        on_outcome(match &result {
+            Ok(OpenOutcome::Opened) => IntroOutcome::Opened,
+            Ok(OpenOutcome::NotOpened | OpenOutcome::TimedOut) => IntroOutcome::PrintedFallback,
+            Err(..) => IntroOutcome::Failed,
+        });
+    }
+
+    result
+}
+
+/// What happened when [`navigate_to_intro`] was called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntroOutcome {
+    /// The introduction was opened in the browser.
+    Opened,
+    /// The browser was not opened, and the link was printed instead.
+    PrintedFallback,
+    /// Opening the browser failed unexpectedly, the link was not printed.
+    Failed,
+}
+
+/// Outcome of [`open_url`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenOutcome {
+    /// The browser was launched.
+    Opened,
+    /// There is no browser to launch, or it failed to launch.
+    NotOpened,
+    /// Launching the browser did not finish within [`OPEN_BROWSER_TIMEOUT`].
+    TimedOut,
+}
+
+/// Attempts to open the given URL in the browser, see [`open_url`].
+/// In case no browser could be launched, prints the link after `fallback_prefix`, see
+/// [`print_link`].
+pub async fn open_or_print(url: &str, fallback_prefix: &str) -> io::Result<OpenOutcome> {
+    let outcome = open_url(url).await?;
+    if outcome != OpenOutcome::Opened {
+        print_link(url, fallback_prefix);
+    }
+
+    Ok(outcome)
+}
+
+/// Attempts to open the given URL in the browser from `$BROWSER`, or the default one, without
+/// printing anything.
+///
+/// On WSL, when `$BROWSER` is not set, the browser of the Windows host is tried first, see
+/// [`open_wsl_browser`].
+///
+/// Gives up after [`OPEN_BROWSER_TIMEOUT`], as some handlers block until the browser exits.
+/// Returns [`Err`] only on unexpected IO failures.
+pub async fn open_url(url: &str) -> io::Result<OpenOutcome> {
+    if !browser_available() {
+        tracing::trace!("no browser available, not opening");
+        return Ok(OpenOutcome::NotOpened);
+    }
+
+    let try_wsl_browser =
+        env::var_os(BROWSER_ENV).is_none_or(|browser| browser.is_empty()) && is_wsl();
+
+    // Not `spawn_blocking`, the runtime would wait for a stuck handler on shutdown.
+    let url = url.to_owned();
+    let (result_tx, result_rx) = oneshot::channel();
+    thread::spawn(move || {
+        let result = if try_wsl_browser && open_wsl_browser(&url) {
+            Ok(())
+        } else {
+            opener::open_browser(url)
+        };
+        let _ = result_tx.send(result);
+    });
+
+    match time::timeout(OPEN_BROWSER_TIMEOUT, result_rx).await {
+        Ok(Ok(Ok(()))) => Ok(OpenOutcome::Opened),
+        Ok(Ok(Err(OpenError::Io(error)))) => Err(error),
+        Ok(Ok(Err(error))) => {
+            tracing::trace!("failed to open browser, command result: {error:?}");
+            Ok(OpenOutcome::NotOpened)
+        }
+        Ok(Err(..)) => {
+            tracing::trace!("browser opener thread panicked");
+            Ok(OpenOutcome::NotOpened)
+        }
+        Err(..) => {
+            tracing::trace!("opening browser timed out");
+            Ok(OpenOutcome::TimedOut)
+        }
+    }
+}
+
+/// Checks whether we should try to open a browser at all.
+///
+/// Returns `false` when [`MIRRORD_NO_BROWSER_ENV`] is set, or, on Linux, when there is no
+/// display and [`BROWSER_ENV`] is not set, e.g. in CI or over SSH. On WSL, the browser of the
+/// Windows host is available without a display.
+fn browser_available() -> bool {
+    if env::var_os(MIRRORD_NO_BROWSER_ENV).is_some() {
+        return false;
+    }
+
+    if env::var_os(BROWSER_ENV).is_some_and(|browser| !browser.is_empty()) {
+        return true;
+    }
+
+    !cfg!(target_os = "linux")
+        || env::var_os("DISPLAY").is_some()
+        || env::var_os("WAYLAND_DISPLAY").is_some()
+        || is_wsl()
+}
+
+/// Checks whether we run on Windows Subsystem for Linux, where the Linux browser is usually not
+/// installed.
+fn is_wsl() -> bool {
+    cfg!(target_os = "linux")
+        && fs::read_to_string(PROC_VERSION_PATH).is_ok_and(|version| is_wsl_kernel(&version))
+}
+
+/// Checks whether the given `/proc/version` contents come from a WSL kernel.
+fn is_wsl_kernel(version: &str) -> bool {
+    version.to_ascii_lowercase().contains("microsoft")
+}
+
+/// Opens the given URL in the browser of the Windows host, using `wslview` (from `wslu`) when
+/// available, and `cmd.exe /c start` otherwise.
+///
+/// Returns whether any of them succeeded.
+fn open_wsl_browser(url: &str) -> bool {
+    let succeeded = |command: &mut Command| {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .inspect_err(|error| tracing::trace!(%error, ?command, "failed to run command"))
+            .is_ok_and(|status| status.success())
+    };
+
+    // `&` separates commands in `cmd.exe`, and the URL has them in its query.
+    succeeded(Command::new("wslview").arg(url))
+        || succeeded(Command::new("cmd.exe").args(["/c", "start", &url.replace('&', "^&")]))
+}
+
+/// Prints the link to mirrord for Teams introduction, see [`print_link`].
+pub fn print_intro_link(context: &IntroContext) {
+    print_link(&teams_intro_url(context), INTRO_LINK_PREFIX);
+}
+
+/// Prints the link to mirrord for Teams introduction, and nothing else.
+///
+/// Unlike [`navigate_to_intro`] and [`print_intro_link`], never opens the browser, prints a QR
+/// code or touches the clipboard, so it is safe to use in scripts and CI.
+pub fn print_teams_intro(context: &IntroContext) {
+    println!("{INTRO_LINK_PREFIX}{}", teams_intro_url(context));
+}
+
+/// Prints the given URL after `prefix`.
+///
+/// With the `qr` feature, the link is preceded by a QR code, so that it can be opened on a phone.
+/// With the `clipboard` feature, the link is also copied to the clipboard, if possible.
+pub fn print_link(url: &str, prefix: &str) {
+    print_qr_code(url);
+    if copy_to_clipboard(url) {
+        println!("{prefix}{url} (link copied to clipboard)");
+    } else {
+        println!("{prefix}{url}");
+    }
+}
+
+/// Prints the given text as a QR code made of Unicode half blocks, only when stdout is a terminal.
+#[cfg(feature = "qr")]
+fn print_qr_code(text: &str) {
+    if io::stdout().is_terminal().not() {
+        return;
+    }
+
+    match QrCode::new(text) {
+        // Inverted colors, most terminals have a dark background.
+        Ok(code) => println!(
+            "{}",
+            code.render::<Dense1x2>()
+                .dark_color(Dense1x2::Light)
+                .light_color(Dense1x2::Dark)
+                .build()
+        ),
+        Err(error) => tracing::trace!(%error, "failed to render the QR code"),
+    }
+}
+
+#[cfg(not(feature = "qr"))]
+fn print_qr_code(_: &str) {}
+
+/// Best-effort copy of the given text to the system clipboard.
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(text: &str) -> bool {
+    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)) {
+        Ok(()) => true,
+        Err(error) => {
+            tracing::trace!(%error, "failed to copy to the clipboard");
+            false
+        }
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_: &str) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::{IntroContext, is_wsl_kernel, parse_base_url, teams_intro_url};
+
+    #[test]
+    fn intro_url() {
+        assert_eq!(
+            IntroContext::default().url(),
+            "https://app.metalbear.com/?utm_source=teamscmd&utm_medium=cli"
+        );
+        assert_eq!(
+            teams_intro_url(&IntroContext {
+                utm_source: "session",
+                utm_medium: "cli",
+                utm_campaign: Some("upsell"),
+            }),
+            "https://app.metalbear.com/?utm_source=session&utm_medium=cli&utm_campaign=upsell"
+        );
+    }
+
+    #[rstest]
+    #[case::portal(
+        "https://teams.internal.example/",
+        Some("https://teams.internal.example/?utm_source=teamscmd&utm_medium=cli")
+    )]
+    #[case::with_query(
+        "https://teams.internal.example/intro?org=acme",
+        Some("https://teams.internal.example/intro?org=acme&utm_source=teamscmd&utm_medium=cli")
+    )]
+    #[case::no_scheme("teams.internal.example", None)]
+    #[case::other_scheme("mailto:admin@teams.internal.example", None)]
+    #[case::file("file:///tmp/intro.html", None)]
+    fn intro_url_override(#[case] base: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            parse_base_url(base)
+                .ok()
+                .map(|base| IntroContext::default().url_with_base(base))
+                .as_deref(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::wsl2(
+        "Linux version 5.15.153.1-microsoft-standard-WSL2 (root@941d701f84f1) (gcc (GCC) 11.2.0)",
+        true
+    )]
+    #[case::wsl1(
+        "Linux version 4.4.0-19041-Microsoft (Microsoft@Microsoft.com) (gcc version 5.4.0)",
+        true
+    )]
+    #[case::linux(
+        "Linux version 6.8.0-45-generic (buildd@lcy02-amd64-115) (x86_64-linux-gnu-gcc-13)",
+        false
+    )]
+    fn wsl_kernel(#[case] version: &str, #[case] expected: bool) {
+        assert_eq!(is_wsl_kernel(version), expected);
     }
 }
@@ -1,6 +1,6 @@
 use proc_macro2_diagnostics::Diagnostic;
 use quote::{ToTokens, quote};
-use syn::{Field, GenericArgument, Ident, PathArguments, Type, Visibility};
+use syn::{Field, GenericArgument, Ident, PathArguments, Type, Visibility, ext::IdentExt};
 
 use crate::config::flag::{ConfigFlags, ConfigFlagsType, EnvFlag};
 
@@ -124,7 +124,7 @@ impl ConfigField {
     /// ```
     /// Will output
     /// ```rust
-    /// test: crate::config::from_env::FromEnv::new("TEST").or(self.test)
+    /// test: crate::config::provenance::Tracked::new("test", crate::config::from_env::FromEnv::new("TEST").or(self.test))
     ///           .source_value().transpose()?
     ///           .ok_or(crate::config::ConfigError::ValueNotProvided("MyConfig", "test", Some("TEST")))?
     /// ```
@@ -136,9 +136,17 @@ impl ConfigField {
             ..
         } = &self;
 
+        // Name used for tracking the value sources, without the `r#` prefix.
+        let name = ident
+            .as_ref()
+            .map(|ident| ident.unraw().to_string())
+            .unwrap_or_default();
+
         // Rest of flow is irrelevant for nested config.
         if flags.nested {
-            return quote! { #ident: self.#ident.unwrap_or_default().generate_config(context)? };
+            return quote! {
+                #ident: context.section(#name, self.#ident.is_some(), |context| self.#ident.unwrap_or_default().generate_config(context))?
+            };
         }
 
         let mut impls = Vec::new();
@@ -181,10 +189,15 @@ impl ConfigField {
             .into_iter()
             .reduce(|acc, impl_| quote! { #acc.or(#impl_) });
 
-        if layers.is_empty() {
-            quote! { #ident: #impls .source_value(context).transpose()?#unwrapper }
+        let tracked = if layers.is_empty() {
+            quote! { #impls }
         } else {
-            quote! { #ident: #impls #(#layers),* .source_value(context).transpose()?#unwrapper }
+            quote! { #impls #(#layers),* }
+        };
+
+        quote! {
+            #ident: crate::config::provenance::Tracked::new(#name, #tracked)
+                .source_value(context).transpose()?#unwrapper
         }
     }
 }
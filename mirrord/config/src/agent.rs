@@ -1,14 +1,17 @@
-use std::{collections::HashMap, fmt, net::SocketAddr, path::Path};
+use std::{collections::HashMap, fmt, net::SocketAddr, path::Path, time::Duration};
 
-use k8s_openapi::api::core::v1::{ResourceRequirements, Toleration};
+use k8s_openapi::{
+    api::core::v1::{ResourceRequirements, Toleration},
+    apimachinery::pkg::api::resource::Quantity,
+};
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::config::{
-    self, ConfigContext, FromFileError, FromMirrordConfig, MirrordConfig, from_env::FromEnv,
-    source::MirrordConfigSource,
+    self, ConfigContext, ConfigError, FromFileError, FromMirrordConfig, MirrordConfig,
+    format::ConfigFileFormat, from_env::FromEnv, source::MirrordConfigSource,
 };
 
 /// Linux capabilities used by the mirrord-agent container.
@@ -64,8 +67,12 @@ impl fmt::Display for LinuxCapability {
 ///     "flush_connections": false,
 ///     "exclude_from_mesh": false
 ///     "inject_headers": false,
-///     "max_body_buffer_size": 65535,
-///     "max_body_buffer_timeout": 1000
+///     "max_body_buffer_size": 262144,
+///     "max_body_buffer_total": 67108864,
+///     "max_body_buffer_timeout": 1000,
+///     "jaq_time_limit": 500,
+///     "jaq_memory_limit": 536870912,
+///     "mirror_bandwidth_limit": "10MiB/s"
 ///   }
 /// }
 /// ```
@@ -214,9 +221,13 @@ pub struct AgentConfig {
 
     /// ### agent.startup_timeout {#agent-startup_timeout}
     ///
-    /// Controls how long to wait for the agent to finish initialization.
+    /// Controls how long to wait for the agent to finish initialization, in seconds.
+    ///
+    /// Covers scheduling of the agent pod, pulling the agent image and the first protocol
+    /// handshake. If initialization takes longer than this value, mirrord exits and reports the
+    /// state of the agent pod.
     ///
-    /// If initialization takes longer than this value, mirrord exits.
+    /// Set to `0` to wait forever.
     ///
     /// Defaults to `60`.
     #[config(env = "MIRRORD_AGENT_STARTUP_TIMEOUT", default = 60)]
@@ -270,7 +281,11 @@ pub struct AgentConfig {
 
     /// ### agent.resources {#agent-resources}
     ///
-    /// Set pod resource requirements. (not with ephemeral agents)
+    /// Set pod resource requirements. (not with ephemeral agents, as Kubernetes does not allow
+    /// resources on ephemeral containers)
+    ///
+    /// Quantities (e.g. `250m` or `1.5Gi`) are validated when the config is loaded.
+    ///
     /// Default is
     /// ```json
     /// {
@@ -444,9 +459,22 @@ pub struct AgentConfig {
     /// temporarily storing bodies of incoming HTTP requests to run
     /// body filters. HTTP body filters will not match any requests
     /// with bodies larger than this.
-    #[config(default = 65535)]
+    ///
+    /// Bodies are buffered only until the filters can decide, the rest of a bigger body is
+    /// streamed through without buffering. Defaults to 256KiB.
+    #[config(default = 262144)]
     pub max_body_buffer_size: u32,
 
+    /// ### agent.max_body_buffer_total {#agent-max_body_buffer_total}
+    ///
+    /// Maximum size, in bytes, of all HTTP request body buffers held by the agent at the same
+    /// time. Requests that would exceed it are treated like requests with bodies larger than
+    /// [`agent.max_body_buffer_size`](#agent-max_body_buffer_size).
+    ///
+    /// Defaults to 64MiB.
+    #[config(default = 67108864)]
+    pub max_body_buffer_total: u32,
+
     /// ### agent.max_body_buffer_timeout {#agent-max_body_buffer_timeout}
     ///
     /// Maximum timeout, in milliseconds, for receiving HTTP request
@@ -479,10 +507,67 @@ pub struct AgentConfig {
 
     /// ### agent.jaq_time_limit {#agent-jaq_time_limit}
     ///
-    /// Time limit for running jaq queries, in milliseconds. Defaults to 500ms.
-    #[config(default = 1)]
+    /// Time limit for evaluating the jq expressions of HTTP filters, in milliseconds. Expressions
+    /// that run longer do not match.
+    ///
+    /// Must be between 1 and 60000. Defaults to 500ms.
+    #[config(env = "MIRRORD_AGENT_JAQ_TIME_LIMIT_MS", default = 500)]
     pub jaq_time_limit: u64,
 
+    /// ### agent.jaq_memory_limit {#agent-jaq_memory_limit}
+    ///
+    /// Memory limit for evaluating the jq expressions of HTTP filters, in bytes. The agent does
+    /// not evaluate expressions against inputs (e.g. request bodies) bigger than this, and treats
+    /// them as not matching.
+    ///
+    /// Must be between 1MiB and 8GiB. Defaults to 512MiB.
+    #[config(env = "MIRRORD_AGENT_JAQ_MEMORY_LIMIT_BYTES", default = 536870912)]
+    pub jaq_memory_limit: u64,
+
+    /// ### agent.jaq_pool_size {#agent-jaq_pool_size}
+    ///
+    /// Number of sandboxed processes the agent keeps alive for evaluating the jq expressions of
+    /// HTTP filters. Their memory is limited by
+    /// [`agent.jaq_memory_limit`](#agent-jaq_memory_limit), and the ones that run over
+    /// [`agent.jaq_time_limit`](#agent-jaq_time_limit) are killed.
+    ///
+    /// Must be between 1 and 64. When not set, the expressions are evaluated in the agent
+    /// process.
+    #[config(env = "MIRRORD_AGENT_JAQ_POOL_SIZE")]
+    pub jaq_pool_size: Option<u32>,
+
+    /// ### agent.jaq_pool_max_evaluations {#agent-jaq_pool_max_evaluations}
+    ///
+    /// Number of evaluations after which the processes of
+    /// [`agent.jaq_pool_size`](#agent-jaq_pool_size) are replaced with new ones.
+    ///
+    /// Defaults to 1000.
+    #[config(env = "MIRRORD_AGENT_JAQ_POOL_MAX_EVALUATIONS")]
+    pub jaq_pool_max_evaluations: Option<u64>,
+
+    /// ### agent.mirror_bandwidth_limit {#agent-mirror_bandwidth_limit}
+    ///
+    /// Limits the bandwidth the agent uses to send mirrored traffic to mirrord, e.g. `"10MiB/s"`.
+    /// Supports the `B`, `KB`, `KiB`, `MB`, `MiB`, `GB` and `GiB` units.
+    ///
+    /// When the limit is exceeded, the agent drops whole mirrored connections (and requests),
+    /// so that the mirrored streams never miss bytes in the middle, and mirrord warns about
+    /// them. Stolen traffic is never limited.
+    ///
+    /// Not limited by default.
+    #[config(env = "MIRRORD_AGENT_MIRROR_BANDWIDTH_LIMIT")]
+    pub mirror_bandwidth_limit: Option<String>,
+
+    /// ### agent.mirror_bandwidth_burst {#agent-mirror_bandwidth_burst}
+    ///
+    /// How much mirrored traffic the agent can send at once after being idle, e.g. `"20MiB"`,
+    /// with [`agent.mirror_bandwidth_limit`](#agent-mirror_bandwidth_limit). Messages bigger
+    /// than this are never mirrored.
+    ///
+    /// Defaults to the traffic of one second.
+    #[config(env = "MIRRORD_AGENT_MIRROR_BANDWIDTH_BURST")]
+    pub mirror_bandwidth_burst: Option<String>,
+
     /// <!--${internal}-->
     /// Create an agent that returns an error after accepting the first client. For testing
     /// purposes. Only supported with job agents (not with ephemeral agents).
@@ -557,19 +642,39 @@ impl MirrordConfig for AgentImageFileConfig {
     /// Generates the [`AgentImageConfig`] from the `agent.image` config, or the
     /// `MIRRORD_AGENT_IMAGE` env var.
     fn generate_config(self, context: &mut ConfigContext) -> config::Result<Self::Generated> {
-        let env_registry = FromEnv::new("MIRRORD_AGENT_IMAGE_REGISTRY")
+        let env_registry: Option<String> = FromEnv::new("MIRRORD_AGENT_IMAGE_REGISTRY")
             .source_value(context)
             .transpose()
             .ok()
-            .flatten()
-            .unwrap_or_else(|| DEFAULT_AGENT_IMAGE_REGISTRY.to_string());
+            .flatten();
 
-        let env_tag = FromEnv::new("MIRRORD_AGENT_IMAGE_TAG")
+        let env_tag: Option<String> = FromEnv::new("MIRRORD_AGENT_IMAGE_TAG")
             .source_value(context)
             .transpose()
             .ok()
-            .flatten()
-            .unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_string());
+            .flatten();
+
+        // Environment variables used to fill the parts of the image that are not in the file.
+        let (from_file, registry_env, tag_env) = match &self {
+            AgentImageFileConfig::Simple(image) => {
+                (image.is_some(), image.is_none(), image.is_none())
+            }
+            AgentImageFileConfig::Advanced { registry, tag } => (
+                registry.is_some() || tag.is_some(),
+                registry.is_none(),
+                tag.is_none(),
+            ),
+        };
+        let used_env = [
+            (registry_env && env_registry.is_some()).then_some("MIRRORD_AGENT_IMAGE_REGISTRY"),
+            (tag_env && env_tag.is_some()).then_some("MIRRORD_AGENT_IMAGE_TAG"),
+        ]
+        .into_iter()
+        .flatten()
+        .next();
+
+        let env_registry = env_registry.unwrap_or_else(|| DEFAULT_AGENT_IMAGE_REGISTRY.to_string());
+        let env_tag = env_tag.unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_string());
 
         let agent_image = match self {
             AgentImageFileConfig::Simple(registry_and_tag) => {
@@ -585,7 +690,14 @@ impl MirrordConfig for AgentImageFileConfig {
         };
 
         // Env overrides configuration if both there.
-        let agent_image = Self::get_image_from_env(context)?.unwrap_or(agent_image);
+        let image_from_env = Self::get_image_from_env(context)?;
+        let used_env = image_from_env
+            .is_some()
+            .then_some("MIRRORD_AGENT_IMAGE")
+            .or(used_env);
+        context
+            .with_tracker(|tracker| tracker.note_value(used_env, from_file || used_env.is_some()));
+        let agent_image = image_from_env.unwrap_or(agent_image);
 
         Ok(AgentImageConfig(agent_image))
     }
@@ -601,6 +713,176 @@ impl AgentConfig {
     pub fn image(&self) -> &str {
         &self.image.0
     }
+
+    /// [`AgentConfig::startup_timeout`] as a [`Duration`], [`None`] if mirrord should wait
+    /// forever.
+    pub fn startup_timeout_duration(&self) -> Option<Duration> {
+        (self.startup_timeout != 0).then(|| Duration::from_secs(self.startup_timeout))
+    }
+
+    /// [`AgentConfig::mirror_bandwidth_limit`] in bytes per second, [`None`] if not limited.
+    pub fn mirror_bandwidth_limit_bytes(&self) -> Result<Option<u64>, ConfigError> {
+        self.mirror_bandwidth_limit
+            .as_deref()
+            .map(|limit| {
+                limit
+                    .strip_suffix("/s")
+                    .ok_or("must be a rate, e.g. `10MiB/s`")
+                    .and_then(parse_bytes)
+                    .map_err(|error| ConfigError::InvalidValue {
+                        name: "agent.mirror_bandwidth_limit",
+                        provided: limit.to_owned(),
+                        error: error.into(),
+                    })
+            })
+            .transpose()
+    }
+
+    /// [`AgentConfig::mirror_bandwidth_burst`] in bytes, [`None`] if not set.
+    pub fn mirror_bandwidth_burst_bytes(&self) -> Result<Option<u64>, ConfigError> {
+        self.mirror_bandwidth_burst
+            .as_deref()
+            .map(|burst| {
+                parse_bytes(burst).map_err(|error| ConfigError::InvalidValue {
+                    name: "agent.mirror_bandwidth_burst",
+                    provided: burst.to_owned(),
+                    error: error.into(),
+                })
+            })
+            .transpose()
+    }
+
+    /// Verifies the quantities in [`AgentConfig::resources`], so that they're not rejected by the
+    /// API server only when the agent is created, the mirror bandwidth and the jq pool settings.
+    ///
+    /// Warns about the pod settings that have no effect on ephemeral agents.
+    pub fn verify(&self, context: &mut ConfigContext) -> Result<(), ConfigError> {
+        let quantities = self.resources.iter().flat_map(|resources| {
+            let requests = resources.requests.iter().flatten();
+            let limits = resources.limits.iter().flatten();
+            requests.chain(limits)
+        });
+
+        for (resource, Quantity(quantity)) in quantities {
+            verify_quantity(quantity).map_err(|error| ConfigError::InvalidValue {
+                name: "agent.resources",
+                provided: quantity.clone(),
+                error: format!("`{resource}` {error}").into(),
+            })?;
+        }
+
+        let limit = self.mirror_bandwidth_limit_bytes()?;
+        if self.mirror_bandwidth_burst_bytes()?.is_some() && limit.is_none() {
+            context.add_warning(
+                "`agent.mirror_bandwidth_burst` is ignored without `agent.mirror_bandwidth_limit`."
+                    .to_string(),
+            );
+        }
+
+        if let Some(size) = self.jaq_pool_size.filter(|size| !(1..=64).contains(size)) {
+            Err(ConfigError::InvalidValue {
+                name: "agent.jaq_pool_size",
+                provided: size.to_string(),
+                error: "must be between 1 and 64".into(),
+            })?;
+        }
+
+        if self.jaq_pool_max_evaluations == Some(0) {
+            Err(ConfigError::InvalidValue {
+                name: "agent.jaq_pool_max_evaluations",
+                provided: "0".to_string(),
+                error: "must be greater than zero".into(),
+            })?;
+        }
+
+        if self.jaq_pool_max_evaluations.is_some() && self.jaq_pool_size.is_none() {
+            context.add_warning(
+                "`agent.jaq_pool_max_evaluations` is ignored without `agent.jaq_pool_size`."
+                    .to_string(),
+            );
+        }
+
+        if self.ephemeral {
+            let ignored = [
+                ("agent.resources", self.resources.is_some()),
+                ("agent.tolerations", self.tolerations.is_some()),
+                ("agent.node_selector", self.node_selector.is_some()),
+            ];
+
+            for (name, _) in ignored.into_iter().filter(|(_, set)| *set) {
+                context.add_warning(format!(
+                    "`{name}` is ignored when using an ephemeral container for the agent."
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a positive amount of bytes, e.g. `512`, `64KiB` or `10MB`.
+fn parse_bytes(size: &str) -> Result<u64, &'static str> {
+    let (number, unit) = size.split_at(
+        size.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(size.len()),
+    );
+
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| "must start with a whole number")?;
+    let unit: u64 = match unit.trim_start() {
+        "" | "B" => 1,
+        "KB" => 1_000,
+        "KiB" => 1 << 10,
+        "MB" => 1_000_000,
+        "MiB" => 1 << 20,
+        "GB" => 1_000_000_000,
+        "GiB" => 1 << 30,
+        _ => return Err("must use one of the `B`, `KB`, `KiB`, `MB`, `MiB`, `GB` or `GiB` units"),
+    };
+
+    match number.checked_mul(unit) {
+        Some(0) => Err("must be greater than zero"),
+        Some(bytes) => Ok(bytes),
+        None => Err("is too large"),
+    }
+}
+
+/// Checks that the given string is a valid, non-negative Kubernetes resource quantity, e.g.
+/// `100m`, `1.5Gi` or `2e3`.
+///
+/// See the [Kubernetes docs](https://kubernetes.io/docs/reference/kubernetes-api/common-definitions/quantity/)
+/// for the format.
+fn verify_quantity(quantity: &str) -> Result<(), &'static str> {
+    if quantity.starts_with('-') {
+        return Err("must not be negative");
+    }
+
+    let unsigned = quantity.strip_prefix('+').unwrap_or(quantity);
+    let (number, suffix) = unsigned.split_at(
+        unsigned
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(unsigned.len()),
+    );
+
+    if !number.contains(|c: char| c.is_ascii_digit()) || number.matches('.').count() > 1 {
+        return Err("must start with a number");
+    }
+
+    let valid_suffix = match suffix {
+        "" | "n" | "u" | "m" | "k" | "M" | "G" | "T" | "P" | "E" => true,
+        "Ki" | "Mi" | "Gi" | "Ti" | "Pi" | "Ei" => true,
+        exponent => exponent
+            .strip_prefix(['e', 'E'])
+            .map(|exponent| exponent.strip_prefix(['+', '-']).unwrap_or(exponent))
+            .is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())),
+    };
+
+    if valid_suffix {
+        Ok(())
+    } else {
+        Err("has an invalid suffix, expected e.g. `m`, `k`, `Mi`, `Gi` or an exponent")
+    }
 }
 
 impl AgentFileConfig {
@@ -610,12 +892,7 @@ impl AgentFileConfig {
     {
         let config = std::fs::read_to_string(path.as_ref())?;
 
-        match path.as_ref().extension().and_then(|os_val| os_val.to_str()) {
-            Some("json") => Ok(serde_json::from_str::<Self>(&config)?),
-            Some("toml") => Ok(toml::from_str::<Self>(&config)?),
-            Some("yaml" | "yml") => Ok(serde_yaml::from_str::<Self>(&config)?),
-            ext => Err(FromFileError::InvalidExtension(ext.map(String::from))),
-        }
+        ConfigFileFormat::detect(path.as_ref(), &config)?.parse(&config)
     }
 }
 
@@ -740,4 +1017,167 @@ mod tests {
         assert_eq!(agent.communication_timeout, communication_timeout.1);
         assert_eq!(agent.startup_timeout, startup_timeout.1);
     }
+
+    #[rstest]
+    #[case("100m", true)]
+    #[case("1.5Gi", true)]
+    #[case("+2", true)]
+    #[case(".5", true)]
+    #[case("2e3", true)]
+    #[case("1E-3", true)]
+    #[case("128MiB", false)]
+    #[case("-1", false)]
+    #[case("Gi", false)]
+    #[case("1.2.3", false)]
+    #[case("2e", false)]
+    #[case("1 cpu", false)]
+    fn verify_quantities(#[case] quantity: &str, #[case] valid: bool) {
+        assert_eq!(verify_quantity(quantity).is_ok(), valid, "{quantity}");
+    }
+
+    #[rstest]
+    #[case("512", Ok(512))]
+    #[case("64KiB", Ok(65536))]
+    #[case("10MB", Ok(10_000_000))]
+    #[case("1 GiB", Ok(1 << 30))]
+    #[case("0MiB", Err("must be greater than zero"))]
+    #[case(
+        "1.5MiB",
+        Err("must use one of the `B`, `KB`, `KiB`, `MB`, `MiB`, `GB` or `GiB` units")
+    )]
+    #[case("MiB", Err("must start with a whole number"))]
+    #[case("99999999999GiB", Err("is too large"))]
+    fn bytes(#[case] size: &str, #[case] expected: Result<u64, &'static str>) {
+        assert_eq!(parse_bytes(size), expected, "{size}");
+    }
+
+    #[test]
+    fn mirror_bandwidth() {
+        let mut context = ConfigContext::default().strict_env(true);
+        let agent = serde_json::from_value::<AgentFileConfig>(serde_json::json!({
+            "mirror_bandwidth_limit": "10MiB/s",
+            "mirror_bandwidth_burst": "20MiB",
+        }))
+        .unwrap()
+        .generate_config(&mut context)
+        .unwrap();
+
+        agent.verify(&mut context).unwrap();
+        assert_eq!(
+            agent.mirror_bandwidth_limit_bytes().unwrap(),
+            Some(10 << 20)
+        );
+        assert_eq!(
+            agent.mirror_bandwidth_burst_bytes().unwrap(),
+            Some(20 << 20)
+        );
+
+        let agent = AgentConfig {
+            mirror_bandwidth_limit: Some("10MiB".to_string()),
+            ..agent
+        };
+        let error = agent.verify(&mut context).unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                ConfigError::InvalidValue {
+                    name: "agent.mirror_bandwidth_limit",
+                    ..
+                }
+            ),
+            "{error}"
+        );
+
+        let agent = AgentConfig {
+            mirror_bandwidth_limit: None,
+            ..agent
+        };
+        agent.verify(&mut context).unwrap();
+        assert_eq!(
+            context.into_warnings(),
+            ["`agent.mirror_bandwidth_burst` is ignored without `agent.mirror_bandwidth_limit`."]
+        );
+    }
+
+    #[test]
+    fn jaq_pool() {
+        let mut context = ConfigContext::default().strict_env(true);
+        let agent = serde_json::from_value::<AgentFileConfig>(serde_json::json!({
+            "jaq_pool_max_evaluations": 100,
+        }))
+        .unwrap()
+        .generate_config(&mut context)
+        .unwrap();
+
+        agent.verify(&mut context).unwrap();
+        assert_eq!(
+            context.into_warnings(),
+            ["`agent.jaq_pool_max_evaluations` is ignored without `agent.jaq_pool_size`."]
+        );
+
+        let mut context = ConfigContext::default().strict_env(true);
+        let agent = AgentConfig {
+            jaq_pool_size: Some(4),
+            ..agent
+        };
+        agent.verify(&mut context).unwrap();
+        assert!(context.into_warnings().is_empty());
+    }
+
+    #[rstest]
+    #[case::size_zero(Some(0), None, Some("agent.jaq_pool_size"))]
+    #[case::size_too_big(Some(65), None, Some("agent.jaq_pool_size"))]
+    #[case::max_evaluations_zero(Some(4), Some(0), Some("agent.jaq_pool_max_evaluations"))]
+    #[case::valid(Some(64), Some(1), None)]
+    fn jaq_pool_ranges(
+        #[case] size: Option<u32>,
+        #[case] max_evaluations: Option<u64>,
+        #[case] invalid: Option<&str>,
+    ) {
+        let mut context = ConfigContext::default().strict_env(true);
+        let agent = serde_json::from_value::<AgentFileConfig>(serde_json::json!({
+            "jaq_pool_size": size,
+            "jaq_pool_max_evaluations": max_evaluations,
+        }))
+        .unwrap()
+        .generate_config(&mut context)
+        .unwrap();
+
+        match (agent.verify(&mut context), invalid) {
+            (Ok(()), None) => {}
+            (Err(ConfigError::InvalidValue { name, .. }), Some(invalid)) if name == invalid => {}
+            (result, _) => panic!("unexpected result {result:?}, expected {invalid:?}"),
+        }
+    }
+
+    #[test]
+    fn verify_resources() {
+        let mut context = ConfigContext::default().strict_env(true);
+        let agent = serde_json::from_value::<AgentFileConfig>(serde_json::json!({
+            "ephemeral": true,
+            "resources": { "requests": { "cpu": "250m", "memory": "128MiB" } },
+        }))
+        .unwrap()
+        .generate_config(&mut context)
+        .unwrap();
+
+        let error = agent.verify(&mut context).unwrap_err();
+        assert!(
+            matches!(&error, ConfigError::InvalidValue { provided, .. } if provided == "128MiB"),
+            "{error}"
+        );
+
+        let agent = AgentConfig {
+            resources: serde_json::from_value(serde_json::json!({
+                "limits": { "cpu": "1", "memory": "1Gi" },
+            }))
+            .unwrap(),
+            ..agent
+        };
+        agent.verify(&mut context).unwrap();
+        assert_eq!(
+            context.into_warnings(),
+            ["`agent.resources` is ignored when using an ephemeral container for the agent."]
+        );
+    }
 }
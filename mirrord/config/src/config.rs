@@ -1,6 +1,9 @@
 pub mod context;
 pub mod deprecated;
+pub mod expand;
+pub mod format;
 pub mod from_env;
+pub mod provenance;
 pub mod source;
 pub mod unstable;
 
@@ -51,14 +54,6 @@ pub enum ConfigError {
     )]
     TargetNamespaceWithoutTarget,
 
-    #[error(
-        "A Job or CronJob target has been specified, but the feature `copy_target` has not been enabled!
-
-        If you want to target a job or cronjob, please enable `copy_target` feature in the `feature` section.
-        "
-    )]
-    TargetJobWithoutCopyTarget,
-
     #[error(
         "Target type requires the mirrord-operator, but operator usage was explicitly disabled. Consider enabling mirrord-operator in your mirrord config."
     )]
@@ -96,6 +91,7 @@ pub enum FromFileError {
     ParseToml(#[from] toml::de::Error),
     ParseJson(#[from] serde_json::Error),
     ParseYaml(#[from] serde_yaml::Error),
+    Expand(#[from] expand::ExpandError),
 }
 
 impl From<tera::Error> for FromFileError {
@@ -141,6 +137,9 @@ impl fmt::Display for FromFileError {
                 f.write_str("failed to read the file")?;
                 error
             }
+            Self::Expand(error) => {
+                return write!(f, "failed to expand the variables: {error}");
+            }
         };
 
         let mut source = error.source();
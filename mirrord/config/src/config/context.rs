@@ -7,6 +7,8 @@ use std::{
     ops::Not,
 };
 
+use super::provenance::{ConfigSources, SourceTracker};
+
 /// Context for generating and verifying a [`MirrordConfig`](super::MirrordConfig).
 ///
 /// See:
@@ -30,6 +32,10 @@ pub struct ConfigContext {
 
     /// Warnings collected during config verification.
     warnings: Vec<String>,
+
+    /// Set only when resolving the config with
+    /// [`LayerConfig::resolve_with_sources`](crate::LayerConfig::resolve_with_sources).
+    sources: Option<SourceTracker>,
 }
 
 impl ConfigContext {
@@ -146,4 +152,59 @@ impl ConfigContext {
     pub fn has_warnings(&self) -> bool {
         self.warnings.is_empty().not()
     }
+
+    /// Starts recording where the generated values come from, see [`Self::take_sources`].
+    pub(crate) fn start_tracking(&mut self) {
+        self.sources = Some(SourceTracker::default());
+    }
+
+    /// Runs `f` on the source tracker, if tracking is enabled.
+    pub(crate) fn with_tracker<F: FnOnce(&mut SourceTracker)>(&mut self, f: F) {
+        if let Some(tracker) = self.sources.as_mut() {
+            f(tracker);
+        }
+    }
+
+    /// Generates a nested config section, so that the sources of its fields are recorded under
+    /// the right path.
+    ///
+    /// `in_file` tells whether the section is set in the config file, as the configs of the
+    /// sections that are not get generated from their defaults.
+    pub(crate) fn section<T, F: FnOnce(&mut Self) -> T>(
+        &mut self,
+        section: &'static str,
+        in_file: bool,
+        f: F,
+    ) -> T {
+        self.with_tracker(|tracker| tracker.enter(Some(section), in_file));
+        let generated = f(self);
+        self.with_tracker(SourceTracker::leave);
+        generated
+    }
+
+    /// Generates values from a default config, even if the current section is set in the config
+    /// file (e.g. with `"fs": true`).
+    pub(crate) fn defaults<T, F: FnOnce(&mut Self) -> T>(&mut self, f: F) -> T {
+        self.with_tracker(|tracker| tracker.enter(None, false));
+        let generated = f(self);
+        self.with_tracker(SourceTracker::leave);
+        generated
+    }
+
+    /// Records the source of a field of the current section that can only be set in the config
+    /// file, returning its value.
+    pub(crate) fn file_value<T>(&mut self, field: &'static str, value: Option<T>) -> Option<T> {
+        self.with_tracker(|tracker| tracker.note_file_value(field, value.is_some()));
+        value
+    }
+
+    /// Records that the value of the current field was taken from the given environment variable.
+    pub(crate) fn note_env_source(&mut self, name: &'static str) {
+        self.with_tracker(|tracker| tracker.note_env(name));
+    }
+
+    /// Returns the sources recorded since the tracking was started, disabling the tracking.
+    pub fn take_sources(&mut self) -> Option<ConfigSources> {
+        self.sources.take().map(SourceTracker::into_sources)
+    }
 }
@@ -0,0 +1,290 @@
+//! Expansion of the `${...}` references in the string values of config files, see [`Expander`].
+//!
+//! Supported references:
+//!
+//! - `${env:VAR}`, the value of the environment variable `VAR`;
+//! - `${env:VAR:-default}`, the same, but `default` when `VAR` is unset or empty;
+//! - `${user}`, the local username.
+//!
+//! `$${` is replaced with a literal `${`.
+
+use std::ops::Not;
+
+use serde_json::Value;
+use thiserror::Error;
+
+use super::ConfigContext;
+
+/// Name of the environment variable that enables the strict mode, where references that cannot
+/// be resolved are errors instead of warnings.
+pub const STRICT_VARS_ENV: &str = "MIRRORD_CONFIG_STRICT_VARS";
+
+/// A `${...}` reference that could not be resolved in the strict mode.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("`{reference}` in `{path}` could not be resolved: {reason}")]
+pub struct ExpandError {
+    /// Dot-separated path of the field, e.g. `target.namespace`.
+    pub path: String,
+    pub reference: String,
+    pub reason: &'static str,
+}
+
+/// Expands the `${...}` references, taking the environment variables from a [`ConfigContext`].
+pub struct Expander<'a> {
+    context: &'a ConfigContext,
+    /// Value of `${user}`, [`None`] if the username is not known.
+    user: Option<String>,
+    /// Whether references that cannot be resolved are errors, see [`STRICT_VARS_ENV`].
+    strict: bool,
+    /// Warnings about references that could not be resolved outside of the strict mode.
+    warnings: Vec<String>,
+}
+
+impl<'a> Expander<'a> {
+    pub fn new(context: &'a ConfigContext, user: Option<String>) -> Self {
+        let strict = context
+            .get_env(STRICT_VARS_ENV)
+            .is_ok_and(|value| value.eq_ignore_ascii_case("true"));
+
+        Self {
+            context,
+            user,
+            strict,
+            warnings: Default::default(),
+        }
+    }
+
+    /// Expands all string values nested in the given `value`, in place.
+    ///
+    /// Object keys are left as they are.
+    pub fn expand(&mut self, value: &mut Value) -> Result<(), ExpandError> {
+        self.expand_nested(value, &mut Vec::new())
+    }
+
+    /// Returns the warnings about references that could not be resolved, which are left in the
+    /// values as they are.
+    pub fn into_warnings(self) -> Vec<String> {
+        self.warnings
+    }
+
+    fn expand_nested(
+        &mut self,
+        value: &mut Value,
+        path: &mut Vec<String>,
+    ) -> Result<(), ExpandError> {
+        match value {
+            Value::String(string) if string.contains('$') => {
+                *string = self.expand_str(string, &path.join("."))?;
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    self.expand_nested(item, path)?;
+                    path.pop();
+                }
+            }
+            Value::Object(fields) => {
+                for (key, field) in fields {
+                    path.push(key.clone());
+                    self.expand_nested(field, path)?;
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn expand_str(&mut self, input: &str, path: &str) -> Result<String, ExpandError> {
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+
+        while let Some(start) = rest.find('$') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            if let Some(after) = rest.strip_prefix("$${") {
+                output.push_str("${");
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("${") {
+                let (reference, resolved, after) = match after.find('}') {
+                    Some(end) => {
+                        let reference = &rest[..end + 3];
+                        (reference, self.resolve(&after[..end]), &after[end + 1..])
+                    }
+                    None => (rest, Err("missing the closing `}`"), ""),
+                };
+
+                match resolved {
+                    Ok(value) => output.push_str(&value),
+                    Err(reason) => {
+                        let error = ExpandError {
+                            path: path.to_owned(),
+                            reference: reference.to_owned(),
+                            reason,
+                        };
+                        if self.strict {
+                            return Err(error);
+                        }
+
+                        self.warnings.push(format!(
+                            "{error}, the value was left as it is. Use `$${{` for a literal `${{`."
+                        ));
+                        output.push_str(reference);
+                    }
+                }
+
+                rest = after;
+            } else {
+                output.push('$');
+                rest = &rest[1..];
+            }
+        }
+
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    /// Resolves the content of a single `${...}` reference.
+    fn resolve(&self, reference: &str) -> Result<String, &'static str> {
+        if reference == "user" {
+            return self.user.clone().ok_or("the local username is not known");
+        }
+
+        let Some(variable) = reference.strip_prefix("env:") else {
+            return Err("only `${env:VAR}`, `${env:VAR:-default}` and `${user}` are supported");
+        };
+
+        let (name, default) = match variable.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (variable, None),
+        };
+        if name.is_empty() {
+            return Err("the variable name is empty");
+        }
+
+        match (self.context.get_env(name), default) {
+            (Ok(value), Some(default)) if value.is_empty() => Ok(default.to_owned()),
+            (Ok(value), _) => Ok(value),
+            (Err(..), Some(default)) => Ok(default.to_owned()),
+            (Err(..), None) => Err("the environment variable is not set"),
+        }
+    }
+}
+
+/// Returns the value of `${user}`.
+pub fn local_user() -> Option<String> {
+    whoami::fallible::username()
+        .ok()
+        .filter(|user| user.is_empty().not())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+
+    fn test_context(strict: bool) -> ConfigContext {
+        ConfigContext::default()
+            .override_env("TEAM_NAMESPACE", "payments")
+            .override_env("EMPTY", "")
+            .override_env_opt(STRICT_VARS_ENV, strict.then_some("true"))
+            .strict_env(true)
+    }
+
+    #[rstest]
+    #[case("${env:TEAM_NAMESPACE}", "payments")]
+    #[case("ns-${env:TEAM_NAMESPACE}-${user}", "ns-payments-alice")]
+    #[case("${env:MISSING:-default}", "default")]
+    #[case("${env:EMPTY:-default}", "default")]
+    #[case("${env:EMPTY}", "")]
+    #[case("${env:TEAM_NAMESPACE:-}", "payments")]
+    #[case("$${env:TEAM_NAMESPACE}", "${env:TEAM_NAMESPACE}")]
+    #[case("$${env:A} ${env:TEAM_NAMESPACE}", "${env:A} payments")]
+    #[case("^/home/.*$", "^/home/.*$")]
+    #[case("${HOME}", "${HOME}")]
+    #[case("${env:MISSING}", "${env:MISSING}")]
+    #[case("x-${env:TEAM", "x-${env:TEAM")]
+    fn expand_str(#[case] input: &str, #[case] expected: &str) {
+        let context = test_context(false);
+        let mut expander = Expander::new(&context, Some("alice".into()));
+
+        assert_eq!(expander.expand_str(input, "field").unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("${env:MISSING}")]
+    #[case("${user}")]
+    #[case("${HOME}")]
+    #[case("${env:}")]
+    #[case("${env:TEAM")]
+    fn strict_mode_rejects_unresolved(#[case] input: &str) {
+        let context = test_context(true);
+
+        let error = Expander::new(&context, None)
+            .expand_str(input, "target.namespace")
+            .unwrap_err();
+        assert_eq!(error.path, "target.namespace");
+        assert_eq!(error.reference, input);
+
+        let context = test_context(false);
+        let mut expander = Expander::new(&context, None);
+        assert_eq!(
+            expander.expand_str(input, "target.namespace").unwrap(),
+            input
+        );
+        assert_eq!(expander.into_warnings().len(), 1);
+    }
+
+    #[test]
+    fn expands_nested_string_values() {
+        let context = test_context(true);
+        let mut value = json!({
+            "target": { "namespace": "${env:TEAM_NAMESPACE}" },
+            "feature": {
+                "network": {
+                    "incoming": {
+                        "http_filter": { "header_filter": "x-user: ${user}" },
+                        "ports": [80, "${env:MISSING:-8080}"]
+                    }
+                },
+                "env": { "override": { "${env:TEAM_NAMESPACE}": true } }
+            }
+        });
+
+        Expander::new(&context, Some("alice".into()))
+            .expand(&mut value)
+            .unwrap();
+
+        assert_eq!(
+            value,
+            json!({
+                "target": { "namespace": "payments" },
+                "feature": {
+                    "network": {
+                        "incoming": {
+                            "http_filter": { "header_filter": "x-user: alice" },
+                            "ports": [80, "8080"]
+                        }
+                    },
+                    "env": { "override": { "${env:TEAM_NAMESPACE}": true } }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn error_points_to_the_field() {
+        let context = test_context(true);
+        let mut value = json!({ "agent": { "labels": ["ok", "${env:MISSING}"] } });
+
+        let error = Expander::new(&context, None)
+            .expand(&mut value)
+            .unwrap_err();
+
+        assert_eq!(error.path, "agent.labels.1");
+    }
+}
@@ -0,0 +1,300 @@
+//! Formats of the config files, see [`ConfigFileFormat`].
+
+use std::{ffi::OsStr, fmt, path::Path};
+
+use serde::{
+    Deserializer,
+    de::{self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, Visitor},
+};
+
+use super::FromFileError;
+
+/// Format of a config file.
+///
+/// All formats deserialize into the same config structs, so the JSON Schema describes each of
+/// them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFileFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFileFormat {
+    /// Detects the format of the file from its extension.
+    ///
+    /// Files without an extension are detected from their `content`, see
+    /// [`ConfigFileFormat::sniff`].
+    pub fn detect(path: &Path, content: &str) -> Result<Self, FromFileError> {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("json") => Ok(Self::Json),
+            Some("toml") => Ok(Self::Toml),
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            None => Ok(Self::sniff(content)),
+            Some(ext) => Err(FromFileError::InvalidExtension(Some(ext.to_owned()))),
+        }
+    }
+
+    /// Guesses the format from the file content.
+    ///
+    /// JSON configs are objects, so they start with `{`. Otherwise, the content is TOML if it
+    /// parses as TOML, and YAML if it does not.
+    pub fn sniff(content: &str) -> Self {
+        if content.trim_start().starts_with('{') {
+            Self::Json
+        } else if toml::from_str::<toml::Table>(content).is_ok() {
+            Self::Toml
+        } else {
+            Self::Yaml
+        }
+    }
+
+    /// Deserializes the content of a file in this format.
+    ///
+    /// Errors point to the line and column in the content.
+    pub fn parse<T: DeserializeOwned>(self, content: &str) -> Result<T, FromFileError> {
+        match self {
+            Self::Json => Ok(serde_json::from_str(content)?),
+            Self::Toml => Ok(toml::from_str(content)?),
+            Self::Yaml => {
+                // Maps in the config structs (e.g. `feature.env.override`) silently keep the last
+                // of the duplicate keys, while parsing into a `Value` rejects them.
+                serde_yaml::from_str::<serde_yaml::Value>(content)?;
+                Ok(serde_yaml::from_str(content)?)
+            }
+        }
+    }
+
+    /// Finds the line (1-based) of the key of the field with the given `path` in the content of
+    /// a file in this format, [`None`] if the field is not set.
+    ///
+    /// The content is deserialized following the `path`, and the key of the field fails with
+    /// [`FIELD_FOUND`], so that the parser tells where it is.
+    pub(crate) fn field_line(self, content: &str, path: &[&str]) -> Option<usize> {
+        let seek = SeekField { path };
+
+        match self {
+            Self::Json => {
+                let error = seek
+                    .deserialize(&mut serde_json::Deserializer::from_str(content))
+                    .err()?;
+                error
+                    .to_string()
+                    .starts_with(FIELD_FOUND)
+                    .then(|| error.line())
+            }
+            Self::Toml => {
+                let error = seek.deserialize(toml::Deserializer::new(content)).err()?;
+                let span = error.span().filter(|_| error.message() == FIELD_FOUND)?;
+                Some(content[..span.start].matches('\n').count() + 1)
+            }
+            Self::Yaml => {
+                let error = seek
+                    .deserialize(serde_yaml::Deserializer::from_str(content))
+                    .err()?;
+                // The message is prefixed with the path of the field.
+                let location = error
+                    .location()
+                    .filter(|_| error.to_string().contains(FIELD_FOUND))?;
+                Some(location.line())
+            }
+        }
+    }
+}
+
+/// Error message used by [`SeekField`] to stop at the key of the field.
+const FIELD_FOUND: &str = "mirrord config field found";
+
+/// Walks the maps of a config file along the `path` of a field, see
+/// [`ConfigFileFormat::field_line`].
+struct SeekField<'a> {
+    path: &'a [&'a str],
+}
+
+impl<'de> DeserializeSeed<'de> for SeekField<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SeekField<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let Some((name, rest)) = self.path.split_first() else {
+            return Ok(());
+        };
+
+        while let Some(found) = map.next_key_seed(SeekKey {
+            name,
+            last: rest.is_empty(),
+        })? {
+            if found {
+                return map.next_value_seed(SeekField { path: rest });
+            }
+
+            map.next_value::<IgnoredAny>()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Key in a map visited by [`SeekField`], fails with [`FIELD_FOUND`] when it is the `last`
+/// segment of the path.
+struct SeekKey<'a> {
+    name: &'a str,
+    last: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for SeekKey<'_> {
+    type Value = bool;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<bool, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SeekKey<'_> {
+    type Value = bool;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map key")
+    }
+
+    fn visit_str<E: de::Error>(self, key: &str) -> Result<bool, E> {
+        match key == self.name {
+            true if self.last => Err(E::custom(FIELD_FOUND)),
+            matches => Ok(matches),
+        }
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<bool, E> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rstest::rstest;
+
+    use super::ConfigFileFormat;
+    use crate::config::FromFileError;
+
+    #[rstest]
+    #[case::json("mirrord", r#"{ "target": "pod/app" }"#, ConfigFileFormat::Json)]
+    #[case::toml("mirrord", "target = \"pod/app\"\n", ConfigFileFormat::Toml)]
+    #[case::toml_table("mirrord", "[feature]\nenv = true\n", ConfigFileFormat::Toml)]
+    #[case::yaml("mirrord", "target: pod/app\n", ConfigFileFormat::Yaml)]
+    #[case::yaml_comment(
+        "mirrord",
+        "# staging\nfeature:\n  env: true\n",
+        ConfigFileFormat::Yaml
+    )]
+    #[case::extension("mirrord.yml", r#"{ "target": "pod/app" }"#, ConfigFileFormat::Yaml)]
+    fn detect(#[case] path: &str, #[case] content: &str, #[case] expected: ConfigFileFormat) {
+        assert_eq!(
+            ConfigFileFormat::detect(Path::new(path), content).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn invalid_extension() {
+        assert!(matches!(
+            ConfigFileFormat::detect(Path::new("mirrord.ini"), ""),
+            Err(FromFileError::InvalidExtension(Some(ext))) if ext == "ini"
+        ));
+    }
+
+    const NESTED_JSON: &str = r#"{
+  "agent": { "namespace": "default" },
+  "feature": {
+    "network": {
+      "incoming": {
+        "mode": "steal",
+        "http_filter": { "ports": [80] }
+      }
+    },
+    "fs": {
+      "mode": "read"
+    }
+  }
+}"#;
+
+    const NESTED_TOML: &str = r#"[agent]
+namespace = "default"
+
+[feature.network.incoming]
+mode = "steal"
+http_filter = { ports = [80] }
+
+[feature.fs]
+mode = "read"
+"#;
+
+    const NESTED_YAML: &str = r#"agent:
+  namespace: default
+feature:
+  network:
+    incoming:
+      mode: steal
+      http_filter:
+        ports: [80]
+  fs:
+    mode: read
+"#;
+
+    /// The same key (`mode`) is found in the right section.
+    #[rstest]
+    #[case::json(ConfigFileFormat::Json, NESTED_JSON, [Some(2), Some(6), Some(7), Some(11), None])]
+    #[case::toml(ConfigFileFormat::Toml, NESTED_TOML, [Some(2), Some(5), Some(6), Some(9), None])]
+    #[case::yaml(ConfigFileFormat::Yaml, NESTED_YAML, [Some(2), Some(6), Some(8), Some(10), None])]
+    fn field_line(
+        #[case] format: ConfigFileFormat,
+        #[case] content: &str,
+        #[case] expected: [Option<usize>; 5],
+    ) {
+        let lines = [
+            "agent.namespace",
+            "feature.network.incoming.mode",
+            "feature.network.incoming.http_filter.ports",
+            "feature.fs.mode",
+            "feature.fs.read_only",
+        ]
+        .map(|path| format.field_line(content, &path.split('.').collect::<Vec<_>>()));
+
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn yaml_duplicate_keys() {
+        let content = "feature:\n  env:\n    override:\n      A: one\n      A: two\n";
+
+        let error = ConfigFileFormat::Yaml
+            .parse::<crate::LayerFileConfig>(content)
+            .unwrap_err();
+
+        let FromFileError::ParseYaml(error) = error else {
+            panic!("unexpected error: {error:?}");
+        };
+        assert!(error.to_string().contains("duplicate entry"), "{error}");
+        assert!(error.location().is_some(), "{error}");
+    }
+}
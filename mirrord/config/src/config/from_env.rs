@@ -25,14 +25,17 @@ where
     /// - `Some(Err(ConfigError::InvalidValue{...}))` if the value of the env var cannot be parsed.
     /// - `Some(Ok(...))` if the env var exists and was parsed successfully.
     fn source_value(self, context: &mut ConfigContext) -> Option<Result<Self::Value>> {
-        context.get_env(self.0).ok().map(|var| {
+        let var = context.get_env(self.0).ok()?;
+        context.note_env_source(self.0);
+
+        Some(
             var.parse::<Self::Value>()
                 .map_err(|err| ConfigError::InvalidValue {
                     name: self.0,
                     provided: var,
                     error: Box::new(err),
-                })
-        })
+                }),
+        )
     }
 }
 
@@ -57,7 +60,10 @@ where
     type Value = T;
 
     fn source_value(self, context: &mut ConfigContext) -> Option<Result<Self::Value>> {
-        context.get_env(self.0).ok().map(|var| var.parse())
+        let var = context.get_env(self.0).ok()?;
+        context.note_env_source(self.0);
+
+        Some(var.parse())
     }
 }
 
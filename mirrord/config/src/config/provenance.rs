@@ -0,0 +1,390 @@
+//! Tracking where the values of the resolved config come from, used by `mirrord config resolved
+//! --show-sources`.
+//!
+//! Tracking is disabled by default, and enabled only by
+//! [`LayerConfig::resolve_with_sources`](crate::LayerConfig::resolve_with_sources).
+//!
+//! Values are recorded while the config is generated:
+//!
+//! 1. [`Tracked`] records the source of a single field;
+//! 2. [`ConfigContext::section`] marks the nested config sections, and whether they are set in the
+//!    config file. Sections with no tracked fields (e.g. `feature.copy_target`) are recorded as a
+//!    whole;
+//! 3. [`ToggleableConfig`](crate::util::ToggleableConfig) records the sections disabled with
+//!    `false`, as their values are decided by the toggle.
+
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use super::{ConfigContext, Result, format::ConfigFileFormat, source::MirrordConfigSource};
+
+/// Where the value of a single config field came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValueSource {
+    /// The field was not set anywhere.
+    Default,
+    /// The field was set in the config file.
+    File {
+        path: PathBuf,
+        /// [`None`] if the field could not be found in the file, e.g. when it comes from an
+        /// included template.
+        line: Option<usize>,
+    },
+    /// The field was set with an environment variable (this includes CLI flags, which are passed
+    /// to the config as environment variables).
+    Env(&'static str),
+    /// The field was set with `--set`, see [`LayerConfig::SET_ENV`](crate::LayerConfig::SET_ENV).
+    Set,
+    /// The field was changed by a [`feature.magic`](crate::feature::magic::MagicConfig) preset.
+    Magic(&'static str),
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File {
+                path,
+                line: Some(line),
+            } => write!(f, "file {}:{line}", path.display()),
+            Self::File { path, line: None } => write!(f, "file {}", path.display()),
+            Self::Env(name) => write!(f, "env {name}"),
+            Self::Set => f.write_str("cli flag (--set)"),
+            Self::Magic(preset) => write!(f, "feature.magic.{preset}"),
+        }
+    }
+}
+
+/// Sources of the resolved config, keyed by dot-separated paths, e.g. `agent.startup_timeout`.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigSources {
+    /// Sources of the tracked fields.
+    fields: BTreeMap<String, ValueSource>,
+    /// Sources of the sections recorded as a whole, see the [module docs](self).
+    sections: BTreeMap<String, ValueSource>,
+}
+
+impl ConfigSources {
+    /// Source of the tracked field with the given path, [`None`] if the field is not tracked
+    /// itself (e.g. it is a part of a section, or of a tracked field).
+    pub fn field(&self, path: &str) -> Option<&ValueSource> {
+        self.fields.get(path)
+    }
+
+    /// Source of the value with the given path, which can be nested in a tracked field or
+    /// section.
+    ///
+    /// Values in a section with tracked fields that are not tracked themselves were not read from
+    /// the file nor from the environment, so they are [`ValueSource::Default`].
+    pub fn source_of(&self, path: &str) -> &ValueSource {
+        let mut prefix = path;
+        loop {
+            if let Some(source) = self.fields.get(prefix).or(self.sections.get(prefix)) {
+                return source;
+            }
+
+            if self.has_tracked_fields(prefix) {
+                return &ValueSource::Default;
+            }
+
+            match prefix.rsplit_once('.') {
+                Some((parent, _)) => prefix = parent,
+                None => return &ValueSource::Default,
+            }
+        }
+    }
+
+    /// Whether any field in the section with the given path is tracked.
+    fn has_tracked_fields(&self, section: &str) -> bool {
+        let prefix = format!("{section}.");
+        self.fields
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|(path, _)| path.starts_with(&prefix))
+    }
+
+    fn len(&self) -> usize {
+        self.fields.len() + self.sections.len()
+    }
+
+    /// Fills [`ValueSource::File::line`] for all values that come from the config file.
+    fn resolve_file_lines(&mut self, file: &ConfigFile) {
+        for (path, source) in self.fields.iter_mut().chain(self.sections.iter_mut()) {
+            if let ValueSource::File { line, .. } = source {
+                *line = file.line_of(path);
+            }
+        }
+    }
+}
+
+/// Config file read by [`LayerFileConfig::from_path`](crate::LayerFileConfig::from_path).
+#[derive(Debug)]
+struct ConfigFile {
+    path: PathBuf,
+    format: ConfigFileFormat,
+    /// Rendered content of the file, so that the lines match what was parsed.
+    content: String,
+}
+
+impl ConfigFile {
+    /// Line of the value with the given path, or of its closest parent set in the file, e.g. the
+    /// line of `"fs": "read"` for `feature.fs.mode`.
+    fn line_of(&self, path: &str) -> Option<usize> {
+        let segments = path.split('.').collect::<Vec<_>>();
+
+        (1..=segments.len())
+            .rev()
+            .filter_map(|len| segments.get(..len))
+            .find_map(|path| self.format.field_line(&self.content, path))
+    }
+}
+
+/// Section of the config that is currently being generated, see [`ConfigContext::section`].
+#[derive(Debug)]
+struct Section {
+    /// [`None`] for the values generated by [`ConfigContext::defaults`].
+    name: Option<&'static str>,
+    /// Whether the section is set in the config file, if not, all of its values that are not
+    /// taken from the environment are defaults.
+    in_file: bool,
+    /// [`ConfigSources::len`] when the section was entered.
+    recorded: usize,
+}
+
+/// State of the source tracking, kept in the [`ConfigContext`].
+#[derive(Debug, Default)]
+pub(crate) struct SourceTracker {
+    /// Sections that are currently being generated, from the outermost.
+    sections: Vec<Section>,
+    /// Environment variable that provided the value of the field that is currently being
+    /// generated.
+    env_hit: Option<&'static str>,
+    file: Option<ConfigFile>,
+    sources: ConfigSources,
+}
+
+impl SourceTracker {
+    pub(crate) fn set_file(&mut self, path: PathBuf, format: ConfigFileFormat, content: String) {
+        self.file = Some(ConfigFile {
+            path,
+            format,
+            content,
+        });
+    }
+
+    /// Whether the values of the current section can come from the config file.
+    fn in_file(&self) -> bool {
+        self.file.is_some() && self.sections.iter().all(|section| section.in_file)
+    }
+
+    /// Dot-separated path of the given field of the current section, or of the section itself.
+    fn path_of(&self, field: Option<&'static str>) -> String {
+        self.sections
+            .iter()
+            .filter_map(|section| section.name)
+            .chain(field)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// [`ValueSource::File`] if the current section is set in the file, [`ValueSource::Default`]
+    /// otherwise.
+    fn file_or_default(&self) -> ValueSource {
+        match &self.file {
+            Some(file) if self.in_file() => ValueSource::File {
+                path: file.path.clone(),
+                line: None,
+            },
+            _ => ValueSource::Default,
+        }
+    }
+
+    pub(crate) fn enter(&mut self, name: Option<&'static str>, in_file: bool) {
+        self.sections.push(Section {
+            name,
+            in_file,
+            recorded: self.sources.len(),
+        });
+    }
+
+    pub(crate) fn leave(&mut self) {
+        let Some(section) = self.sections.last() else {
+            return;
+        };
+
+        // Hand-written configs that only copy the values from the file track nothing.
+        if section.name.is_some() && section.recorded == self.sources.len() {
+            let source = self.file_or_default();
+            self.sources.sections.insert(self.path_of(None), source);
+        }
+
+        self.sections.pop();
+    }
+
+    /// Records the current section as disabled in the config file, see
+    /// [`ToggleableConfig`](crate::util::ToggleableConfig).
+    pub(crate) fn note_disabled(&mut self) {
+        let source = self.file_or_default();
+        self.sources.sections.insert(self.path_of(None), source);
+    }
+
+    pub(crate) fn note_env(&mut self, name: &'static str) {
+        self.env_hit = Some(name);
+    }
+
+    /// Records that the field with the given dot-separated `path` was set with `--set`.
+    pub(crate) fn note_set(&mut self, path: &str) {
+        self.sources
+            .fields
+            .insert(path.to_owned(), ValueSource::Set);
+    }
+
+    /// Records that the field with the given dot-separated `path` was changed by a
+    /// `feature.magic` preset.
+    pub(crate) fn note_magic(&mut self, path: &str, preset: &'static str) {
+        self.sources
+            .fields
+            .insert(path.to_owned(), ValueSource::Magic(preset));
+    }
+
+    /// Records a field that can only be set in the file, see [`ConfigContext::file_value`].
+    pub(crate) fn note_file_value(&mut self, field: &'static str, set: bool) {
+        self.begin_field();
+        self.end_field(Some(field), set);
+    }
+
+    /// Records the source of the current section as a single value, for configs that pick the
+    /// value themselves (e.g. `key`). `env` is the environment variable that provided it.
+    pub(crate) fn note_value(&mut self, env: Option<&'static str>, found: bool) {
+        self.env_hit = env;
+        self.end_field(None, found);
+    }
+
+    fn begin_field(&mut self) {
+        self.env_hit = None;
+    }
+
+    fn end_field(&mut self, field: Option<&'static str>, found: bool) {
+        let source = match (found, self.env_hit.take()) {
+            (false, _) => ValueSource::Default,
+            (true, Some(name)) => ValueSource::Env(name),
+            (true, None) => self.file_or_default(),
+        };
+
+        self.sources.fields.insert(self.path_of(field), source);
+    }
+
+    pub(crate) fn into_sources(self) -> ConfigSources {
+        let mut sources = self.sources;
+        if let Some(file) = &self.file {
+            sources.resolve_file_lines(file);
+        }
+
+        sources
+    }
+}
+
+/// <!--${internal}-->
+/// Records where the value of the wrapped source came from, see [`ValueSource`].
+///
+/// Should be the outermost layer of the field's sources. A value that is found, but not in an
+/// environment variable, comes from the file when the current section is set in the file, and is
+/// a default otherwise.
+#[derive(Clone)]
+pub struct Tracked<T>(Option<&'static str>, T);
+
+impl<T> Tracked<T> {
+    pub fn new(field: &'static str, inner: T) -> Self {
+        Tracked(Some(field), inner)
+    }
+
+    /// Records the source of the current section as a single value, for configs that generate
+    /// one value out of a few sources (e.g. `agent.image`).
+    pub fn section(inner: T) -> Self {
+        Tracked(None, inner)
+    }
+}
+
+impl<T> MirrordConfigSource for Tracked<T>
+where
+    T: MirrordConfigSource,
+{
+    type Value = T::Value;
+
+    fn source_value(self, context: &mut ConfigContext) -> Option<Result<Self::Value>> {
+        context.with_tracker(SourceTracker::begin_field);
+        let value = self.1.source_value(context);
+        context.with_tracker(|tracker| tracker.end_field(self.0, value.is_some()));
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::config::from_env::FromEnv;
+
+    #[rstest]
+    #[case::env(Some("13"), Some(7), true, ValueSource::Env("TEST_VALUE"))]
+    #[case::file(None, Some(7), true, ValueSource::File { path: "mirrord.json".into(), line: None })]
+    #[case::default(None, None, true, ValueSource::Default)]
+    #[case::default_section(None, Some(7), false, ValueSource::Default)]
+    fn tracks_winner(
+        #[case] env: Option<&str>,
+        #[case] file: Option<i32>,
+        #[case] in_file: bool,
+        #[case] expected: ValueSource,
+    ) {
+        let mut context = ConfigContext::default()
+            .override_env_opt("TEST_VALUE", env)
+            .strict_env(true);
+        context.start_tracking();
+        context.with_tracker(|tracker| {
+            tracker.set_file("mirrord.json".into(), ConfigFileFormat::Json, "{}".into())
+        });
+
+        context.section("agent", in_file, |context| {
+            Tracked::new("value", FromEnv::<i32>::new("TEST_VALUE").or(file)).source_value(context)
+        });
+
+        let sources = context.take_sources().unwrap();
+        assert_eq!(sources.field("agent.value"), Some(&expected));
+    }
+
+    #[test]
+    fn untracked_values() {
+        let mut context = ConfigContext::default().strict_env(true);
+        context.start_tracking();
+        context.with_tracker(|tracker| {
+            tracker.set_file("mirrord.json".into(), ConfigFileFormat::Json, "{}".into())
+        });
+
+        context.section("feature", true, |context| {
+            Tracked::new("tracked", Some(1)).source_value(context);
+            context.section("copy_target", true, |_| ());
+            context.section("fs", true, |context| {
+                context.with_tracker(SourceTracker::note_disabled);
+                Tracked::new("read_only", None::<String>).source_value(context);
+            });
+        });
+
+        let sources = context.take_sources().unwrap();
+        let file = ValueSource::File {
+            path: "mirrord.json".into(),
+            line: None,
+        };
+        assert_eq!(sources.source_of("feature.tracked"), &file);
+        assert_eq!(sources.source_of("feature.copy_target.scale_down"), &file);
+        assert_eq!(sources.source_of("feature.fs.mode"), &file);
+        assert_eq!(
+            sources.source_of("feature.fs.read_only"),
+            &ValueSource::Default
+        );
+        assert_eq!(
+            sources.source_of("feature.untracked"),
+            &ValueSource::Default
+        );
+    }
+}
@@ -127,9 +127,17 @@ impl MirrordConfig for EnvKeyFileConfig {
                     EnvKey::Provided(key)
                 }
             })
-            .or(self.0.map(EnvKey::Provided))
+            .or(self.0.clone().map(EnvKey::Provided))
             .unwrap_or_else(|| EnvKey::Generated(EnvKey::autogenerated()));
 
+        // `LayerFileConfig::from_path` copies the key from the file to the environment.
+        let env = match &key {
+            EnvKey::Provided(key) if self.0.as_ref() != Some(key) => Some(MIRRORD_ENV_KEY),
+            _ => None,
+        };
+        let found = matches!(key, EnvKey::Provided(..));
+        context.with_tracker(|tracker| tracker.note_value(env, found));
+
         Ok(key)
     }
 }
@@ -138,6 +138,21 @@ pub struct FeatureConfig {
     /// Configuration for preview environments.
     #[config(nested, default)]
     pub preview: PreviewConfig,
+
+    /// ### feature.target_logs {#feature-target_logs}
+    ///
+    /// Streams the logs of the target container to stderr for the duration of the session,
+    /// starting from the session start. Each line is prefixed with the pod and container names.
+    ///
+    /// The stream is re-established when the container restarts or its log file is rotated.
+    /// Has no effect in the `targetless` mode and with
+    /// [`feature.copy_target`](#feature-copy_target).
+    ///
+    /// Can also be enabled with the `--follow-target-logs` flag of `mirrord exec`.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_TARGET_LOGS", default = false)]
+    pub target_logs: bool,
 }
 
 impl CollectAnalytics for &FeatureConfig {
@@ -151,5 +166,6 @@ impl CollectAnalytics for &FeatureConfig {
         analytics.add("db_branches", &self.db_branches);
         analytics.add("magic", &self.magic);
         analytics.add("preview", &self.preview);
+        analytics.add("target_logs", self.target_logs);
     }
 }
@@ -38,6 +38,7 @@ use crate::config::{ConfigContext, FromMirrordConfig, MirrordConfig, Result};
 ///     "copy_target": {
 ///       "enabled": true,
 ///       "scale_down": true,
+///       "force_scale_down": false,
 ///       "exclude_containers": ["my-container"],
 ///       "exclude_init_containers": ["my-init-container"]
 ///     }
@@ -57,6 +58,8 @@ pub enum CopyTargetFileConfig {
         enabled: Option<bool>,
         /// Scale down the target deployment to 0 for the time the copied pod is alive
         scale_down: Option<bool>,
+        /// Scale down the target even if it is managed by a HorizontalPodAutoscaler
+        force_scale_down: Option<bool>,
         /// List of containers to be ignored by copy_target
         exclude_containers: Option<Vec<String>>,
         /// List of init containers to be ignored by copy_target
@@ -78,17 +81,20 @@ impl MirrordConfig for CopyTargetFileConfig {
             Self::Simple(enabled) => Self::Generated {
                 enabled,
                 scale_down: false,
+                force_scale_down: false,
                 exclude_containers: vec![],
                 exclude_init_containers: vec![],
             },
             Self::Advanced {
                 enabled,
                 scale_down,
+                force_scale_down,
                 exclude_containers,
                 exclude_init_containers,
             } => Self::Generated {
                 enabled: enabled.unwrap_or(true),
                 scale_down: scale_down.unwrap_or_default(),
+                force_scale_down: force_scale_down.unwrap_or_default(),
                 exclude_containers: exclude_containers.unwrap_or_default(),
                 exclude_init_containers: exclude_init_containers.unwrap_or_default(),
             },
@@ -118,8 +124,20 @@ pub struct CopyTargetConfig {
     ///       "scale_down": true
     ///     }
     /// ```
+    ///
+    /// The original replica count is restored when the session ends. mirrord refuses to scale
+    /// down targets managed by a HorizontalPodAutoscaler, as it would scale them back up, unless
+    /// [`force_scale_down`](#feature-copy_target-force_scale_down) is set.
     pub scale_down: bool,
 
+    /// #### feature.copy_target.force_scale_down {#feature-copy_target-force_scale_down}
+    ///
+    /// Scale down the target with [`scale_down`](#feature-copy_target-scale_down), even if it is
+    /// managed by a HorizontalPodAutoscaler.
+    ///
+    /// Defaults to `false`.
+    pub force_scale_down: bool,
+
     /// #### feature.copy_target.exclude_containers {#feature-copy_target-exclude_containers}
     ///
     /// Set a list of containers to be ignored by copy_target
@@ -135,5 +153,6 @@ impl CollectAnalytics for &CopyTargetConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("enabled", self.enabled);
         analytics.add("scale_down", self.scale_down);
+        analytics.add("force_scale_down", self.force_scale_down);
     }
 }
@@ -6,10 +6,15 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    config::{ConfigContext, Result, from_env::FromEnv, source::MirrordConfigSource},
+    config::{
+        ConfigContext, Result, from_env::FromEnv, provenance::Tracked, source::MirrordConfigSource,
+    },
+    feature::env::from::EnvFromSource,
     util::{MirrordToggleableConfig, VecOrSingle},
 };
 
+pub mod filter;
+pub mod from;
 pub mod mapper;
 
 pub const MIRRORD_OVERRIDE_ENV_VARS_INCLUDE_ENV: &str = "MIRRORD_OVERRIDE_ENV_VARS_INCLUDE";
@@ -25,8 +30,15 @@ pub const MIRRORD_OVERRIDE_ENV_FILE_ENV: &str = "MIRRORD_OVERRIDE_ENV_VARS_FILE"
 /// 2. `true` - Enables the feature, will obtain remote environment variables.
 /// 3. object - see below (means `true` + additional configuration).
 ///
-/// Which environment variables to load from the remote pod are controlled by setting either
-/// [`include`](#feature-env-include) or [`exclude`](#feature-env-exclude).
+/// Which environment variables to load from the remote pod are controlled by setting
+/// [`include`](#feature-env-include) and/or [`exclude`](#feature-env-exclude). A variable is
+/// loaded only if it matches one of the `include` patterns (if set), and none of the `exclude`
+/// patterns, so `exclude` takes precedence over `include`.
+/// [`override`](#feature-env-override) is applied last, and takes precedence over both.
+///
+/// The variables are merged in the following order, each source overriding the previous ones:
+/// [`from`](#feature-env-from), the remote environment, [`env_file`](#feature-env-env-file),
+/// [`mapping`](#feature-env-mapping), and [`override`](#feature-env-override).
 ///
 /// See the environment variables [reference](https://metalbear.com/mirrord/docs/reference/env/) for more details.
 ///
@@ -57,6 +69,9 @@ pub struct EnvConfig {
     /// Variable names can be matched using `*` and `?` where `?` matches exactly one occurrence of
     /// any character and `*` matches arbitrary many (including zero) occurrences of any character.
     ///
+    /// Patterns containing any of the `.^$+()[]{}|\` characters are regexes instead, matched
+    /// against the whole variable name (e.g. `"APP_.*"`).
+    ///
     /// Can be passed as a list or as a semicolon-delimited string (e.g. `"VAR;OTHER_VAR"`).
     ///
     /// Some environment variables are excluded by default (`PATH` for example), including these
@@ -67,10 +82,13 @@ pub struct EnvConfig {
     /// #### feature.env.exclude {#feature-env-exclude}
     ///
     /// Include the remote environment variables in the local process that are **NOT** specified by
-    /// this option.
+    /// this option, takes precedence over [`include`](#feature-env-include).
     /// Variable names can be matched using `*` and `?` where `?` matches exactly one occurrence of
     /// any character and `*` matches arbitrary many (including zero) occurrences of any character.
     ///
+    /// Patterns containing any of the `.^$+()[]{}|\` characters are regexes instead, matched
+    /// against the whole variable name (e.g. `["AWS_.*", ".*_SECRET"]`).
+    ///
     /// Some of the variables that are excluded by default:
     /// `PATH`, `HOME`, `HOMEPATH`, `CLASSPATH`, `JAVA_EXE`, `JAVA_HOME`, `PYTHONPATH`.
     ///
@@ -85,7 +103,8 @@ pub struct EnvConfig {
     /// For example, if the remote pod has an environment variable `REGION=1`, but this is an
     /// undesirable value, it's possible to use `override` to set `REGION=2` (locally) instead.
     ///
-    /// Environment specified here will also override variables passed via the env file.
+    /// Environment specified here will also override variables passed via the env file, and is
+    /// set even if the variable is excluded with [`exclude`](#feature-env-exclude).
     pub r#override: Option<HashMap<String, String>>, // `r#`: `override` is a Rust keyword.
 
     /// #### feature.env.load_from_process {#feature-env-load_from_process}
@@ -141,25 +160,69 @@ pub struct EnvConfig {
     ///
     /// * `DATA_1234: common-value` => `DATA_1234: magic-value`
     pub mapping: Option<HashMap<String, String>>,
+
+    /// #### feature.env.container {#feature-env-container}
+    ///
+    /// Name of the target pod container to load the remote environment variables from.
+    ///
+    /// Useful when the target pod has multiple containers, and the environment the application
+    /// needs belongs to a different container than the one chosen as the target.
+    /// The session fails to start if the target pod has no container with this name.
+    ///
+    /// Defaults to the target container. Cannot be used with
+    /// [`load_from_process`](#feature-env-load_from_process).
+    pub container: Option<String>,
+
+    /// #### feature.env.from {#feature-env-from}
+    ///
+    /// Loads additional environment variables from remote ConfigMaps and Secrets, like the
+    /// `envFrom` field of a Kubernetes container.
+    ///
+    /// ```json
+    /// {
+    ///   "from": [
+    ///     { "configmap": "staging/app-config" },
+    ///     { "secret": "staging/app-credentials", "optional": true }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// Each entry sets exactly one of `configmap` and `secret`, in the `namespace/name` format, or
+    /// just `name` for the target's namespace. The resources are read with your Kubernetes
+    /// credentials when the session starts, so your user must be allowed to get them.
+    ///
+    /// When a variable is defined in more than one entry, the last one takes precedence. The
+    /// variables from the remote environment take precedence over all of them, and
+    /// [`include`](#feature-env-include) and [`exclude`](#feature-env-exclude) are applied to them
+    /// as well.
+    ///
+    /// The session fails to start when a resource that is not marked as `optional` does not
+    /// exist. Cannot be used with [`load_from_process`](#feature-env-load_from_process).
+    pub from: Option<Vec<EnvFromSource>>,
 }
 
 impl MirrordToggleableConfig for EnvFileConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated> {
         Ok(EnvConfig {
             include: FromEnv::new(MIRRORD_OVERRIDE_ENV_VARS_INCLUDE_ENV)
+                .layer(|layer| Tracked::new("include", layer))
                 .source_value(context)
                 .transpose()?,
             exclude: FromEnv::new(MIRRORD_OVERRIDE_ENV_VARS_EXCLUDE_ENV)
+                .or(Some(VecOrSingle::Single("*".to_owned())))
+                .layer(|layer| Tracked::new("exclude", layer))
                 .source_value(context)
-                .transpose()?
-                .or_else(|| Some(VecOrSingle::Single("*".to_owned()))),
+                .transpose()?,
             load_from_process: None,
             r#override: None,
             unset: None,
             env_file: FromEnv::new(MIRRORD_OVERRIDE_ENV_FILE_ENV)
+                .layer(|layer| Tracked::new("env_file", layer))
                 .source_value(context)
                 .transpose()?,
             mapping: None,
+            container: None,
+            from: None,
         })
     }
 }
@@ -195,6 +258,14 @@ impl CollectAnalytics for &EnvConfig {
                 .unwrap_or_default(),
         );
         analytics.add("env_file_used", self.env_file.is_some());
+        analytics.add("env_container_used", self.container.is_some());
+        analytics.add(
+            "env_from_count",
+            self.from
+                .as_ref()
+                .map(|v| v.len() as u32)
+                .unwrap_or_default(),
+        );
         analytics.add(
             "env_mapping_count",
             self.mapping
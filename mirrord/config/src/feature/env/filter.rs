@@ -0,0 +1,283 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Not,
+};
+
+use regex::Regex;
+
+use super::EnvConfig;
+use crate::config::ConfigError;
+
+/// Characters that make an entry of `feature.env.include` or `feature.env.exclude` a regex,
+/// entries without any of them are `*` and `?` wildcard patterns.
+const REGEX_META_CHARACTERS: &[char] =
+    &['.', '^', '$', '+', '(', ')', '[', ']', '{', '}', '|', '\\'];
+
+/// Single entry of `feature.env.include` or `feature.env.exclude`.
+#[derive(Debug)]
+struct EnvVarsPattern {
+    /// As given in the config.
+    pattern: String,
+    /// Matches the whole variable name.
+    regex: Regex,
+    /// Whether [`Self::pattern`] is a wildcard pattern that the agent understands.
+    is_wildcard: bool,
+}
+
+impl EnvVarsPattern {
+    fn new(field: &'static str, index: usize, pattern: &str) -> Result<Self, ConfigError> {
+        let is_wildcard = !pattern.contains(REGEX_META_CHARACTERS);
+
+        let regex = if is_wildcard {
+            let regex = pattern
+                .split('*')
+                .map(|part| {
+                    part.split('?')
+                        .map(regex::escape)
+                        .collect::<Vec<_>>()
+                        .join(".")
+                })
+                .collect::<Vec<_>>()
+                .join(".*");
+            Regex::new(&format!("^{regex}$"))
+        } else {
+            Regex::new(&format!("^(?:{pattern})$"))
+        };
+
+        let regex = regex.map_err(|error| ConfigError::InvalidValue {
+            name: field,
+            provided: pattern.to_owned(),
+            error: format!("entry #{index} is not a valid regex: {error}").into(),
+        })?;
+
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            regex,
+            is_wildcard,
+        })
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+/// Filters the remote environment variables with `feature.env.include` and
+/// `feature.env.exclude`.
+///
+/// A variable is kept if it matches any of the `include` entries (or there are none), and none of
+/// the `exclude` entries, so `exclude` takes precedence over `include`. `feature.env.override` is
+/// applied after filtering, so it takes precedence over both.
+///
+/// Wildcard entries are also sent to the agent with
+/// [`remote_selectors`](EnvVarsFilter::remote_selectors), regexes are only matched locally, after
+/// the variables are fetched.
+#[derive(Debug)]
+pub struct EnvVarsFilter {
+    include: Vec<EnvVarsPattern>,
+    exclude: Vec<EnvVarsPattern>,
+}
+
+impl EnvVarsFilter {
+    /// Compiles the `include` and `exclude` entries of the given [`EnvConfig`], entries are also
+    /// split on `;`.
+    ///
+    /// Fails on the first entry that is not a valid regex.
+    pub fn new(config: &EnvConfig) -> Result<Self, ConfigError> {
+        let compile = |field, patterns: Option<&[String]>| {
+            patterns
+                .into_iter()
+                .flatten()
+                .flat_map(|patterns| patterns.split_terminator(';'))
+                .enumerate()
+                .map(|(index, pattern)| EnvVarsPattern::new(field, index, pattern))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(Self {
+            include: compile("feature.env.include", config.include.as_deref())?,
+            exclude: compile("feature.env.exclude", config.exclude.as_deref())?,
+        })
+    }
+
+    /// Returns the exclude and include wildcard patterns that should be sent to the agent in the
+    /// `GetEnvVarsRequest`, so that the variables filtered out by them are never fetched.
+    ///
+    /// If any of the `include` entries is a regex, all of the variables are requested, and the
+    /// `include` entries are matched only locally.
+    pub fn remote_selectors(&self) -> (HashSet<String>, HashSet<String>) {
+        let exclude = self
+            .exclude
+            .iter()
+            .filter(|pattern| pattern.is_wildcard)
+            .map(|pattern| pattern.pattern.clone())
+            .collect();
+
+        let include =
+            if self.include.is_empty() || self.include.iter().any(|pattern| !pattern.is_wildcard) {
+                HashSet::from(["*".to_owned()])
+            } else {
+                self.include
+                    .iter()
+                    .map(|pattern| pattern.pattern.clone())
+                    .collect()
+            };
+
+        (exclude, include)
+    }
+
+    /// Whether the variable with the given `name` should be kept.
+    pub fn matches(&self, name: &str) -> bool {
+        self.dropped_by(name).is_none()
+    }
+
+    /// Returns the entry the variable with the given `name` is dropped by, [`None`] if it should
+    /// be kept.
+    fn dropped_by(&self, name: &str) -> Option<&str> {
+        if self.include.is_empty().not() && self.include.iter().any(|p| p.is_match(name)).not() {
+            return Some("<not included>");
+        }
+
+        self.exclude
+            .iter()
+            .find(|pattern| pattern.is_match(name))
+            .map(|pattern| pattern.pattern.as_str())
+    }
+
+    /// Drops the variables that don't pass the filter, and logs the number of variables dropped
+    /// by each entry.
+    pub fn filter(&self, env_vars: HashMap<String, String>) -> HashMap<String, String> {
+        let mut dropped: HashMap<&str, usize> = HashMap::new();
+
+        let env_vars = env_vars
+            .into_iter()
+            .filter(|(name, _)| match self.dropped_by(name) {
+                Some(pattern) => {
+                    *dropped.entry(pattern).or_default() += 1;
+                    false
+                }
+                None => true,
+            })
+            .collect();
+
+        for (pattern, count) in dropped {
+            tracing::debug!(
+                pattern,
+                count,
+                "Dropped remote environment variables with feature.env filters"
+            );
+        }
+
+        env_vars
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::util::VecOrSingle;
+
+    fn env_config(include: &[&str], exclude: &[&str]) -> EnvConfig {
+        let patterns = |patterns: &[&str]| {
+            patterns
+                .is_empty()
+                .not()
+                .then(|| VecOrSingle::Multiple(patterns.iter().map(ToString::to_string).collect()))
+        };
+
+        EnvConfig {
+            include: patterns(include),
+            exclude: patterns(exclude),
+            r#override: None,
+            load_from_process: None,
+            unset: None,
+            env_file: None,
+            mapping: None,
+            container: None,
+            from: None,
+        }
+    }
+
+    fn filter(include: &[&str], exclude: &[&str]) -> EnvVarsFilter {
+        EnvVarsFilter::new(&env_config(include, exclude)).unwrap()
+    }
+
+    #[rstest]
+    #[case::no_filters(&[], &[], "AWS_REGION", true)]
+    #[case::excluded_by_regex(&[], &["AWS_.*", ".*_SECRET"], "AWS_REGION", false)]
+    #[case::excluded_by_second_regex(&[], &["AWS_.*", ".*_SECRET"], "DB_SECRET", false)]
+    #[case::regex_is_anchored(&[], &["AWS_.*", ".*_SECRET"], "MY_AWS_KEY", true)]
+    #[case::regex_is_anchored_end(&[], &["AWS_.*", ".*_SECRET"], "DB_SECRET_FILE", true)]
+    #[case::wildcard(&[], &["DB_*"], "DB_PASSWORD", false)]
+    #[case::wildcard_single(&[], &["DB_?"], "DB_A", false)]
+    #[case::wildcard_not_regex(&[], &["DB_?"], "DB_", true)]
+    #[case::not_included(&["APP_.*"], &[], "DB_HOST", false)]
+    #[case::included(&["APP_.*"], &[], "APP_PORT", true)]
+    #[case::exclude_beats_include(&["APP_.*"], &[".*_SECRET"], "APP_SECRET", false)]
+    #[case::overlap_included(&["APP_.*"], &[".*_SECRET"], "APP_SECRET_FILE", true)]
+    #[case::exclude_beats_wildcard_include(&["APP_*"], &["APP_(TOKEN|KEY)"], "APP_TOKEN", false)]
+    #[case::overlap_wildcard_included(&["APP_*"], &["APP_(TOKEN|KEY)"], "APP_KEYS", true)]
+    #[case::semicolon_delimited(&["APP_*;DB_.*"], &[], "DB_HOST", true)]
+    fn overlapping_patterns(
+        #[case] include: &[&str],
+        #[case] exclude: &[&str],
+        #[case] name: &str,
+        #[case] kept: bool,
+    ) {
+        assert_eq!(filter(include, exclude).matches(name), kept);
+    }
+
+    #[test]
+    fn filter_drops_variables() {
+        let env_vars = [
+            ("APP_PORT", "80"),
+            ("APP_SECRET", "hunter2"),
+            ("AWS_REGION", "eu-west-1"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect();
+
+        let filtered = filter(&[], &["AWS_.*", ".*_SECRET"]).filter(env_vars);
+        assert_eq!(
+            filtered,
+            HashMap::from([("APP_PORT".to_owned(), "80".to_owned())])
+        );
+    }
+
+    #[rstest]
+    #[case::no_filters(&[], &[], &[], &["*"])]
+    #[case::wildcards(&["APP_*", "DB"], &["DB_?"], &["DB_?"], &["APP_*", "DB"])]
+    #[case::regex_include(&["APP_.*", "DB"], &["AWS_.*", "SECRET"], &["SECRET"], &["*"])]
+    fn remote_selectors(
+        #[case] include: &[&str],
+        #[case] exclude: &[&str],
+        #[case] expected_exclude: &[&str],
+        #[case] expected_include: &[&str],
+    ) {
+        let (remote_exclude, remote_include) = filter(include, exclude).remote_selectors();
+
+        let set = |patterns: &[&str]| patterns.iter().map(ToString::to_string).collect();
+        assert_eq!(remote_exclude, set(expected_exclude));
+        assert_eq!(remote_include, set(expected_include));
+    }
+
+    #[test]
+    fn invalid_regex() {
+        let error = EnvVarsFilter::new(&env_config(&[], &["AWS_.*", "GCP_(.*"])).unwrap_err();
+
+        let ConfigError::InvalidValue {
+            name,
+            provided,
+            error,
+        } = error
+        else {
+            panic!("unexpected error {error:?}");
+        };
+        assert_eq!(name, "feature.env.exclude");
+        assert_eq!(provided, "GCP_(.*");
+        assert!(error.to_string().starts_with("entry #1 "), "{error}");
+    }
+}
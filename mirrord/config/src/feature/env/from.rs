@@ -0,0 +1,134 @@
+use std::ops::Not;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfigError;
+
+/// A ConfigMap or a Secret to load additional environment variables from, like the `envFrom`
+/// field of a Kubernetes container.
+///
+/// Exactly one of `configmap` and `secret` must be set, in the `namespace/name` format. When the
+/// namespace is omitted, the target's namespace is used.
+///
+/// ```json
+/// [
+///   { "configmap": "staging/app-config" },
+///   { "secret": "staging/app-credentials", "optional": true }
+/// ]
+/// ```
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct EnvFromSource {
+    /// ConfigMap to load the variables from, in the `namespace/name` or `name` format.
+    pub configmap: Option<String>,
+
+    /// Secret to load the variables from, in the `namespace/name` or `name` format.
+    pub secret: Option<String>,
+
+    /// Whether the session can start when the ConfigMap or the Secret does not exist.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub optional: bool,
+}
+
+/// Kind of the resource referenced by an [`EnvFromSource`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnvFromKind {
+    ConfigMap,
+    Secret,
+}
+
+/// Resource referenced by an [`EnvFromSource`], see [`EnvFromSource::reference`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnvFromRef<'a> {
+    pub kind: EnvFromKind,
+    /// Defaults to the target's namespace.
+    pub namespace: Option<&'a str>,
+    pub name: &'a str,
+}
+
+impl EnvFromSource {
+    /// Returns the referenced ConfigMap or Secret.
+    ///
+    /// Returns [`None`] if the entry is invalid, see [`EnvFromSource::verify`].
+    pub fn reference(&self) -> Option<EnvFromRef<'_>> {
+        let (kind, reference) = match (self.configmap.as_deref(), self.secret.as_deref()) {
+            (Some(configmap), None) => (EnvFromKind::ConfigMap, configmap),
+            (None, Some(secret)) => (EnvFromKind::Secret, secret),
+            _ => return None,
+        };
+
+        let (namespace, name) = match reference.split_once('/') {
+            Some((namespace, name)) => (Some(namespace), name),
+            None => (None, reference),
+        };
+
+        (namespace.is_none_or(|namespace| namespace.is_empty().not())
+            && name.is_empty().not()
+            && name.contains('/').not())
+        .then_some(EnvFromRef {
+            kind,
+            namespace,
+            name,
+        })
+    }
+
+    /// Checks the entry at the given `index` of `feature.env.from`.
+    pub fn verify(&self, index: usize) -> Result<(), ConfigError> {
+        match (self.configmap.as_deref(), self.secret.as_deref()) {
+            (Some(reference), None) | (None, Some(reference)) if self.reference().is_none() => {
+                Err(ConfigError::InvalidValue {
+                    name: "feature.env.from",
+                    provided: reference.to_owned(),
+                    error: format!(
+                        "entry #{index} must be in the `namespace/name` or `name` format"
+                    )
+                    .into(),
+                })
+            }
+            (Some(..), None) | (None, Some(..)) => Ok(()),
+            _ => Err(ConfigError::Conflict(format!(
+                "entry #{index} of `feature.env.from` must set exactly one of `configmap` and \
+                `secret`"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(Some("app-config"), None, Some((EnvFromKind::ConfigMap, None, "app-config")))]
+    #[case(None, Some("staging/creds"), Some((EnvFromKind::Secret, Some("staging"), "creds")))]
+    #[case(Some("staging/"), None, None)]
+    #[case(None, Some("a/b/c"), None)]
+    #[case(Some("app-config"), Some("creds"), None)]
+    #[case(None, None, None)]
+    fn reference(
+        #[case] configmap: Option<&str>,
+        #[case] secret: Option<&str>,
+        #[case] expected: Option<(EnvFromKind, Option<&str>, &str)>,
+    ) {
+        let source = EnvFromSource {
+            configmap: configmap.map(From::from),
+            secret: secret.map(From::from),
+            optional: false,
+        };
+
+        assert_eq!(
+            source.reference(),
+            expected.map(|(kind, namespace, name)| EnvFromRef {
+                kind,
+                namespace,
+                name
+            })
+        );
+        assert_eq!(source.verify(0).is_ok(), expected.is_some());
+    }
+}
@@ -13,7 +13,8 @@ use serde::Deserialize;
 pub use self::{advanced::*, mode::*};
 use crate::{
     config::{
-        ConfigContext, ConfigError, MirrordConfig, from_env::FromEnv, source::MirrordConfigSource,
+        ConfigContext, ConfigError, MirrordConfig, from_env::FromEnv, provenance::Tracked,
+        source::MirrordConfigSource,
     },
     util::MirrordToggleableConfig,
 };
@@ -64,7 +65,7 @@ pub enum FsUserConfig {
     /// <!--${internal}-->
     /// Allows the user to specify both [`FsModeConfig`] (as above), and configuration for the
     /// overrides.
-    Advanced(AdvancedFsUserConfig),
+    Advanced(Box<AdvancedFsUserConfig>),
 }
 
 impl Default for FsUserConfig {
@@ -79,14 +80,18 @@ impl MirrordConfig for FsUserConfig {
     fn generate_config(self, context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
         let config = match self {
             FsUserConfig::Simple(mode) => FsConfig {
-                mode: mode.generate_config(context)?,
+                mode: context.section("mode", true, |context| mode.generate_config(context))?,
+                rules: Default::default(),
                 read_write: FromEnv::new("MIRRORD_FILE_READ_WRITE_PATTERN")
+                    .layer(|layer| Tracked::new("read_write", layer))
                     .source_value(context)
                     .transpose()?,
                 read_only: FromEnv::new("MIRRORD_FILE_READ_ONLY_PATTERN")
+                    .layer(|layer| Tracked::new("read_only", layer))
                     .source_value(context)
                     .transpose()?,
                 local: FromEnv::new("MIRRORD_FILE_LOCAL_PATTERN")
+                    .layer(|layer| Tracked::new("local", layer))
                     .source_value(context)
                     .transpose()?,
                 not_found: None,
@@ -102,19 +107,23 @@ impl MirrordConfig for FsUserConfig {
 
 impl MirrordToggleableConfig for FsUserConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
-        let mode = FsModeConfig::disabled_config(context)?;
+        let mode = context.section("mode", true, FsModeConfig::disabled_config)?;
         let read_write = FromEnv::new("MIRRORD_FILE_READ_WRITE_PATTERN")
+            .layer(|layer| Tracked::new("read_write", layer))
             .source_value(context)
             .transpose()?;
         let read_only = FromEnv::new("MIRRORD_FILE_READ_ONLY_PATTERN")
+            .layer(|layer| Tracked::new("read_only", layer))
             .source_value(context)
             .transpose()?;
         let local = FromEnv::new("MIRRORD_FILE_LOCAL_PATTERN")
+            .layer(|layer| Tracked::new("local", layer))
             .source_value(context)
             .transpose()?;
 
         Ok(FsConfig {
             mode,
+            rules: Default::default(),
             read_write,
             read_only,
             local,
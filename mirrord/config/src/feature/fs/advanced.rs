@@ -2,12 +2,16 @@ use std::collections::HashMap;
 
 use mirrord_analytics::{AnalyticValue, CollectAnalytics};
 use mirrord_config_derive::MirrordConfig;
+use regex::Regex;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::{FsModeConfig, FsUserConfig};
 use crate::{
-    config::{ConfigContext, ConfigError, from_env::FromEnv, source::MirrordConfigSource},
+    config::{
+        ConfigContext, ConfigError, from_env::FromEnv, provenance::Tracked,
+        source::MirrordConfigSource,
+    },
     util::{MirrordToggleableConfig, VecOrSingle},
 };
 
@@ -37,6 +41,12 @@ pub const READONLY_FILE_BUFFER_HARD_LIMIT: u64 = 15 * 1024 * 1024;
 /// Besides the default behavior, the user can specify behavior for specific regex patterns.
 /// Case insensitive.
 ///
+/// 1. `"rules"` - Ordered list of patterns and their modes, the first matching rule wins. See
+///    [`rules`](#feature-fs-rules).
+///
+/// The following lists are kept for compatibility, and are checked as if they were appended to
+/// `"rules"` in this order: `"not_found"`, `"read_write"`, `"read_only"`, `"local"`.
+///
 /// 1. `"read_write"` - List of patterns that should be read/write remotely.
 /// 2. `"read_only"` - List of patterns that should be read only remotely.
 /// 3. `"local"` - List of patterns that should be read locally.
@@ -49,12 +59,8 @@ pub const READONLY_FILE_BUFFER_HARD_LIMIT: u64 = 15 * 1024 * 1024;
 ///
 /// 1. Check against "mapping" if path needs to be replaced, if matched then continue to next step
 ///    with new path after replacements otherwise continue as usual.
-/// 2. Check if one of the patterns match the file path, do the corresponding action. There's no
-///    specified order if two lists match the same path, we will use the first one (and we do not
-///    guarantee what is first).
-///
-///    **Warning**: Specifying the same path in two lists is unsupported and can lead to undefined
-///    behaviour.
+/// 2. Check the rules in order, and do the action of the first one that matches the file path. The
+///    rules converted from the lists above come after the ones from `"rules"`.
 ///
 /// 3. There are pre-defined exceptions to the set FS mode.
 ///   1. Paths that match the pre-defined patterns [for Linux/MacOS](https://github.com/metalbear-co/mirrord/tree/latest/mirrord/layer-lib/src/file/unix/read_local_by_default.rs)
@@ -91,6 +97,25 @@ pub const READONLY_FILE_BUFFER_HARD_LIMIT: u64 = 15 * 1024 * 1024;
 ///   }
 /// }
 /// ```
+///
+/// The same with rules, where everything is read remotely, except `/var/app/uploads` that is
+/// written remotely, except `/tmp` and the Python virtual environment that are local:
+///
+/// ```json
+/// {
+///   "feature": {
+///     "fs": {
+///       "mode": "read",
+///       "rules": [
+///         { "pattern": "^/tmp(/|$)", "mode": "local" },
+///         { "pattern": "/\\.venv(/|$)", "mode": "local" },
+///         { "pattern": "^/var/app/uploads(/|$)", "mode": "write" },
+///         { "pattern": "^/etc/app/optional\\.yaml$", "mode": "not_found" }
+///       ]
+///     }
+///   }
+/// }
+/// ```
 #[derive(MirrordConfig, Default, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[config(
     map_to = "AdvancedFsUserConfig",
@@ -102,6 +127,31 @@ pub struct FsConfig {
     #[config(nested)]
     pub mode: FsModeConfig,
 
+    /// #### feature.fs.rules {#feature-fs-rules}
+    ///
+    /// Ordered list of rules, each with a case-insensitive regex `pattern` matched against the
+    /// absolute path, and the `mode` used for the matching paths:
+    ///
+    /// - `"read"` - read from the remote, opened locally when opened for writing;
+    /// - `"write"` - read and written to the remote;
+    /// - `"local"` - opened locally;
+    /// - `"not_found"` - treated as non-existent, e.g. to make the application skip optional
+    ///   configuration files that exist in the remote.
+    ///
+    /// The first matching rule wins. The rules are checked before
+    /// [`not_found`](#feature-fs-not_found), [`read_write`](#feature-fs-read_write),
+    /// [`read_only`](#feature-fs-read_only) and [`local`](#feature-fs-local), and before the
+    /// pre-defined exceptions.
+    ///
+    /// ```json
+    /// [
+    ///   { "pattern": "^/tmp(/|$)", "mode": "local" },
+    ///   { "pattern": "^/var/app/uploads(/|$)", "mode": "write" }
+    /// ]
+    /// ```
+    #[config(default)]
+    pub rules: Vec<FsRule>,
+
     /// #### feature.fs.read_write {#feature-fs-read_write}
     ///
     /// Specify file path patterns that if matched will be read and written to the remote.
@@ -127,24 +177,43 @@ pub struct FsConfig {
 
     /// #### feature.fs.mapping {#feature-fs-mapping}
     ///
-    /// Specify map of patterns that if matched will replace the path according to specification.
+    /// Specify map of path prefixes or patterns that if matched will replace the path according
+    /// to specification.
+    ///
+    /// The mapping is applied before deciding whether the file is accessed locally or remotely,
+    /// so the [`feature.fs.mode`](#feature-fs-mode) and the path filters apply to the replaced
+    /// path.
     ///
-    /// *Capture groups are allowed.*
+    /// - Path prefixes: keys without any of the `\^$*+?()[]{}|` characters. They match whole path
+    ///   components (`/var/data` matches `/var/data/file`, but not `/var/database`), are case
+    ///   sensitive, and the longest matching prefix wins.
+    /// - Patterns: other keys are case-insensitive regexes, *capture groups are allowed*. They are
+    ///   used only if no path prefix matches, and the pattern with the longest match wins.
     ///
     /// Example:
     /// ```json
     /// {
-    ///   "^/home/(?<user>\\S+)/dev/tomcat": "/etc/tomcat"
+    ///   "/etc/app/config.yaml": "./config/dev.yaml",
+    ///   "/var/data": "/tmp/mirrord-data",
+    ///   "^/home/(?<user>\\S+)/dev/tomcat": "/etc/tomcat",
     ///   "^/home/(?<user>\\S+)/dev/config/(?<app>\\S+)": "/mnt/configs/${user}-$app"
     /// }
     /// ```
     /// Will do the next replacements for any io operation
     ///
+    /// `/etc/app/config.yaml` => `./config/dev.yaml`
+    /// `/var/data/users/1.json` => `/tmp/mirrord-data/users/1.json`
     /// `/home/johndoe/dev/tomcat/context.xml` => `/etc/tomcat/context.xml`
     /// `/home/johndoe/dev/config/api/app.conf` => `/mnt/configs/johndoe-api/app.conf`
     ///
-    /// - Relative paths: this feature (currently) does not apply mappings to relative paths, e.g.
-    ///   `../dev`.
+    /// - Relative paths: mappings are not applied to relative paths, e.g. `../dev`. A path mapped
+    ///   to a relative path is always accessed locally, relative to the working directory of the
+    ///   application.
+    /// - Directories: listing a mapped directory lists the entries of the directory it's mapped
+    ///   to.
+    /// - Symlinks: the mapping is applied once, to the path as given by the application, before
+    ///   any symlinks are resolved. Symlinks in the replaced path are resolved where that path is
+    ///   accessed, and their targets are not mapped again.
     pub mapping: Option<HashMap<String, String>>,
 
     /// #### feature.fs.readonly_file_buffer {#feature-fs-readonly_file_buffer}
@@ -161,19 +230,23 @@ pub struct FsConfig {
 
 impl MirrordToggleableConfig for AdvancedFsUserConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
-        let mode = FsModeConfig::disabled_config(context)?;
+        let mode = context.section("mode", true, FsModeConfig::disabled_config)?;
         let read_write = FromEnv::new("MIRRORD_FILE_READ_WRITE_PATTERN")
+            .layer(|layer| Tracked::new("read_write", layer))
             .source_value(context)
             .transpose()?;
         let read_only = FromEnv::new("MIRRORD_FILE_READ_ONLY_PATTERN")
+            .layer(|layer| Tracked::new("read_only", layer))
             .source_value(context)
             .transpose()?;
         let local = FromEnv::new("MIRRORD_FILE_LOCAL_PATTERN")
+            .layer(|layer| Tracked::new("local", layer))
             .source_value(context)
             .transpose()?;
 
         Ok(Self::Generated {
             mode,
+            rules: Default::default(),
             read_write,
             read_only,
             local,
@@ -184,6 +257,40 @@ impl MirrordToggleableConfig for AdvancedFsUserConfig {
     }
 }
 
+/// A rule of [`FsConfig::rules`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FsRule {
+    /// Case-insensitive regex, matched against the absolute path.
+    pub pattern: String,
+
+    /// How the matching paths are accessed.
+    pub mode: FsRuleMode,
+}
+
+impl FsRule {
+    pub fn new<P: Into<String>>(pattern: P, mode: FsRuleMode) -> Self {
+        Self {
+            pattern: pattern.into(),
+            mode,
+        }
+    }
+}
+
+/// How the paths matching a [`FsRule`] are accessed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FsRuleMode {
+    /// Read from the remote, opened locally when opened for writing.
+    Read,
+    /// Read and written to the remote.
+    Write,
+    /// Opened locally.
+    Local,
+    /// Treated as non-existent.
+    NotFound,
+}
+
 impl FsConfig {
     pub fn is_read(&self) -> bool {
         self.mode.is_read()
@@ -197,6 +304,42 @@ impl FsConfig {
     pub fn is_active(&self) -> bool {
         !matches!(self.mode, FsModeConfig::Local)
     }
+
+    /// Returns [`FsConfig::rules`], followed by the rules converted from the legacy
+    /// [`FsConfig::not_found`], [`FsConfig::read_write`], [`FsConfig::read_only`] and
+    /// [`FsConfig::local`] lists, in this order.
+    pub fn effective_rules(&self) -> Vec<FsRule> {
+        let legacy = [
+            (&self.not_found, FsRuleMode::NotFound),
+            (&self.read_write, FsRuleMode::Write),
+            (&self.read_only, FsRuleMode::Read),
+            (&self.local, FsRuleMode::Local),
+        ]
+        .into_iter()
+        .flat_map(|(patterns, mode)| {
+            patterns
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .map(move |pattern| FsRule::new(pattern.as_str(), mode))
+        });
+
+        self.rules.iter().cloned().chain(legacy).collect()
+    }
+
+    pub fn verify(&self, _: &mut ConfigContext) -> Result<(), ConfigError> {
+        for rule in &self.rules {
+            if let Err(error) = Regex::new(&rule.pattern) {
+                return Err(ConfigError::InvalidValue {
+                    name: "feature.fs.rules",
+                    provided: rule.pattern.clone(),
+                    error: Box::new(error),
+                });
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl From<FsModeConfig> for AnalyticValue {
@@ -213,6 +356,7 @@ impl From<FsModeConfig> for AnalyticValue {
 impl CollectAnalytics for &FsConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("mode", self.mode);
+        analytics.add("rules", self.rules.len());
         analytics.add(
             "local_paths",
             self.local.as_deref().map(<[_]>::len).unwrap_or_default(),
@@ -265,4 +409,48 @@ mod tests {
 
         assert_eq!(fs_config, expect);
     }
+
+    #[test]
+    fn legacy_lists_converted_to_rules() {
+        let fs_config = AdvancedFsUserConfig {
+            rules: Some(
+                serde_json::from_str(r#"[{ "pattern": "^/tmp", "mode": "not_found" }]"#).unwrap(),
+            ),
+            read_write: Some(VecOrSingle::Single("rw".into())),
+            read_only: Some(VecOrSingle::Multiple(vec!["ro1".into(), "ro2".into()])),
+            local: Some(VecOrSingle::Single("local".into())),
+            not_found: Some(VecOrSingle::Single("nf".into())),
+            ..Default::default()
+        }
+        .generate_config(&mut ConfigContext::default())
+        .unwrap();
+
+        assert_eq!(
+            fs_config.effective_rules(),
+            vec![
+                FsRule::new("^/tmp", FsRuleMode::NotFound),
+                FsRule::new("nf", FsRuleMode::NotFound),
+                FsRule::new("rw", FsRuleMode::Write),
+                FsRule::new("ro1", FsRuleMode::Read),
+                FsRule::new("ro2", FsRuleMode::Read),
+                FsRule::new("local", FsRuleMode::Local),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_rule_pattern() {
+        let fs_config = FsConfig {
+            rules: vec![FsRule::new("^/tmp(", FsRuleMode::Local)],
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            fs_config.verify(&mut ConfigContext::default()),
+            Err(ConfigError::InvalidValue {
+                name: "feature.fs.rules",
+                ..
+            })
+        ));
+    }
 }
@@ -86,12 +86,9 @@ impl FsModeConfig {
             (None, None) => None,
         }
     }
-}
-
-impl MirrordConfig for FsModeConfig {
-    type Generated = FsModeConfig;
 
-    fn generate_config(self, context: &mut ConfigContext) -> Result<Self::Generated> {
+    /// Mode set with the environment variables, or the given `fallback`.
+    fn from_env_or(fallback: Self, context: &mut ConfigContext) -> Result<Self> {
         let fs = FromEnv::new("MIRRORD_FILE_OPS")
             .source_value(context)
             .transpose()?;
@@ -102,30 +99,33 @@ impl MirrordConfig for FsModeConfig {
             .source_value(context)
             .transpose()?;
 
-        if let Some(mode) = mode {
-            Ok(mode)
+        let legacy_env = if fs.is_some() {
+            "MIRRORD_FILE_OPS"
         } else {
-            Ok(Self::from_env_logic(fs, ro_fs).unwrap_or(self))
-        }
+            "MIRRORD_FILE_RO_OPS"
+        };
+        let (mode, env) = match (mode, Self::from_env_logic(fs, ro_fs)) {
+            (Some(mode), _) => (mode, Some("MIRRORD_FILE_MODE")),
+            (None, Some(mode)) => (mode, Some(legacy_env)),
+            (None, None) => (fallback, None),
+        };
+        context.with_tracker(|tracker| tracker.note_value(env, true));
+
+        Ok(mode)
+    }
+}
+
+impl MirrordConfig for FsModeConfig {
+    type Generated = FsModeConfig;
+
+    fn generate_config(self, context: &mut ConfigContext) -> Result<Self::Generated> {
+        Self::from_env_or(self, context)
     }
 }
 
 impl MirrordToggleableConfig for FsModeConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated> {
-        let fs = FromEnv::new("MIRRORD_FILE_OPS")
-            .source_value(context)
-            .transpose()?;
-        let ro_fs = FromEnv::new("MIRRORD_FILE_RO_OPS")
-            .source_value(context)
-            .transpose()?;
-        let mode = FromEnv::new("MIRRORD_FILE_MODE")
-            .source_value(context)
-            .transpose()?;
-        if let Some(mode) = mode {
-            Ok(mode)
-        } else {
-            Ok(Self::from_env_logic(fs, ro_fs).unwrap_or(FsModeConfig::Local))
-        }
+        Self::from_env_or(FsModeConfig::Local, context)
     }
 }
 
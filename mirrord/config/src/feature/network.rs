@@ -6,7 +6,10 @@ use serde::{Deserialize, Serialize};
 
 use self::{incoming::*, outgoing::*};
 use crate::{
-    config::{ConfigContext, ConfigError, from_env::FromEnv, source::MirrordConfigSource},
+    config::{
+        ConfigContext, ConfigError, from_env::FromEnv, provenance::Tracked,
+        source::MirrordConfigSource,
+    },
     util::MirrordToggleableConfig,
 };
 
@@ -81,14 +84,15 @@ pub struct NetworkConfig {
 impl MirrordToggleableConfig for NetworkFileConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
         let ipv6 = FromEnv::new(IPV6_ENV_VAR)
+            .layer(|layer| Tracked::new("ipv6", layer))
             .source_value(context)
             .transpose()?
             .unwrap_or_default();
 
         Ok(NetworkConfig {
-            incoming: IncomingFileConfig::disabled_config(context)?,
-            dns: DnsFileConfig::disabled_config(context)?,
-            outgoing: OutgoingFileConfig::disabled_config(context)?,
+            incoming: context.section("incoming", true, IncomingFileConfig::disabled_config)?,
+            dns: context.section("dns", true, DnsFileConfig::disabled_config)?,
+            outgoing: context.section("outgoing", true, OutgoingFileConfig::disabled_config)?,
             ipv6,
         })
     }
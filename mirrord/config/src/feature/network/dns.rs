@@ -2,17 +2,22 @@ use std::ops::Deref;
 
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
+use regex::{Regex, RegexBuilder};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::filter::AddressFilter;
 use crate::{
-    config::{ConfigContext, ConfigError, from_env::FromEnv, source::MirrordConfigSource},
+    config::{
+        ConfigContext, ConfigError, from_env::FromEnv, provenance::Tracked,
+        source::MirrordConfigSource,
+    },
     util::{MirrordToggleableConfig, VecOrSingle},
 };
 
 /// List of addresses/ports/subnets that should be resolved through either the remote pod or local
-/// app, depending how you set this up with either `remote` or `local`.
+/// app, depending how you set this up with either `remote` or `local`, or an ordered list of
+/// hostname `rules`.
 ///
 /// You may use this option to specify when DNS resolution is done from the remote pod (which
 /// is the default behavior when you enable remote DNS), or from the local app (default when
@@ -63,16 +68,132 @@ use crate::{
 /// ```
 ///
 /// Valid values follow this pattern: `[name|address|subnet/mask][:port]`.
+///
+/// - Cluster hostnames go through the remote pod, hostnames of the corporate VPN go through the
+///   local app, and so does everything else.
+///
+/// ```json
+/// {
+///   "rules": [
+///     { "pattern": "*.svc.cluster.local", "resolve": "remote" },
+///     { "regex": "\\.corp\\.example\\.com$", "resolve": "local" }
+///   ],
+///   "default": "local"
+/// }
+/// ```
+///
+/// The `rules` are checked in order, and the first one matching the hostname decides where it's
+/// resolved. Hostnames are first expanded with the search domains of the remote pod (from its
+/// `/etc/resolv.conf`), so `my-service` matches `*.svc.cluster.local`. IP addresses are never
+/// matched against the `rules` and are always resolved locally. Reverse lookups of addresses
+/// resolved through the remote pod follow the same `rules`.
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, JsonSchema)]
-#[serde(deny_unknown_fields, rename_all = "lowercase")]
+#[serde(untagged, deny_unknown_fields)]
 pub enum DnsFilterConfig {
     /// When filters are specified under `remote`, matching DNS queries will go through the remote
     /// pod, everything else will go through local.
-    Remote(VecOrSingle<String>),
+    Remote { remote: VecOrSingle<String> },
 
     /// When filters are specified under `local`, matching DNS queries will go through the local
     /// app , everything else will go through the remote pod.
-    Local(VecOrSingle<String>),
+    Local { local: VecOrSingle<String> },
+
+    /// Ordered list of hostname rules, the first matching rule decides where the query goes,
+    /// queries that don't match any rule go through `default`.
+    Rules {
+        rules: Vec<DnsFilterRule>,
+
+        /// Where DNS queries that don't match any of the `rules` go.
+        ///
+        /// Defaults to `"remote"`.
+        #[serde(default)]
+        default: DnsResolution,
+    },
+}
+
+/// Where a DNS query is resolved, see [`DnsFilterRule`].
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsResolution {
+    /// Through the local app.
+    Local,
+
+    /// Through the remote pod.
+    #[default]
+    Remote,
+}
+
+/// A single rule of the DNS filter, matches hostnames with either a glob `pattern` or a `regex`.
+///
+/// ```json
+/// { "pattern": "*.svc.cluster.local", "resolve": "remote" }
+/// ```
+///
+/// Hostnames are matched case-insensitively, without the trailing `.`.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DnsFilterRule {
+    /// Glob matched against the whole hostname, `*` matches any sequence of characters (dots
+    /// included), and `?` matches any single character.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+
+    /// Regex matched against the hostname, uses the
+    /// [`regex`](https://docs.rs/regex/latest/regex/) crate syntax.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+
+    /// Where the matching DNS queries go.
+    pub resolve: DnsResolution,
+}
+
+impl DnsFilterRule {
+    /// Builds the case-insensitive [`Regex`] matching the hostnames of this rule.
+    ///
+    /// A glob [`pattern`](Self::pattern) is anchored at both ends, a [`regex`](Self::regex) is
+    /// used as is.
+    pub fn to_regex(&self) -> Result<Regex, ConfigError> {
+        let regex = match (&self.pattern, &self.regex) {
+            (Some(pattern), None) => {
+                let regex = pattern
+                    .split('*')
+                    .map(|part| {
+                        part.split('?')
+                            .map(regex::escape)
+                            .collect::<Vec<_>>()
+                            .join(".")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(".*");
+
+                format!("^{regex}$")
+            }
+            (None, Some(regex)) => regex.clone(),
+            (Some(..), Some(..)) => {
+                return Err(ConfigError::Conflict(
+                    "a `feature.network.dns.filter` rule can have either a `pattern` or a `regex`, \
+                    not both"
+                        .to_string(),
+                ));
+            }
+            (None, None) => {
+                return Err(ConfigError::ValueNotProvided(
+                    "feature.network.dns.filter.rules",
+                    "pattern",
+                    None,
+                ));
+            }
+        };
+
+        RegexBuilder::new(&regex)
+            .case_insensitive(true)
+            .build()
+            .map_err(|error| ConfigError::InvalidValue {
+                name: "feature.network.dns.filter.rules",
+                provided: regex,
+                error: Box::new(error),
+            })
+    }
 }
 
 /// Resolve DNS via the remote pod.
@@ -99,6 +220,93 @@ pub struct DnsConfig {
     /// Unstable: the precise syntax of this config is subject to change.
     #[config(default, unstable)]
     pub filter: Option<DnsFilterConfig>,
+
+    /// ##### feature.network.dns.cache {#feature-network-dns-cache}
+    ///
+    /// See [`cache`](##cache) for details.
+    #[config(nested)]
+    pub cache: DnsCacheConfig,
+}
+
+/// Caching of the addresses resolved through the remote pod, done by the internal proxy.
+///
+/// Addresses are cached per hostname and address family, for the time to live returned by the
+/// cluster DNS, clamped to `max_ttl`. Hostnames that do not exist (`NXDOMAIN`) are cached for
+/// `negative_ttl`. When the cache is full, the least recently used entry is evicted.
+///
+/// ```json
+/// {
+///   "feature": {
+///     "network": {
+///       "dns": {
+///         "cache": {
+///           "enabled": true,
+///           "max_ttl": 30,
+///           "negative_ttl": 5,
+///           "max_entries": 1024
+///         }
+///       }
+///     }
+///   }
+/// }
+/// ```
+///
+/// Disable it when debugging DNS itself, so that every lookup reaches the cluster.
+#[derive(MirrordConfig, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[config(map_to = "DnsCacheFileConfig", derive = "JsonSchema")]
+#[cfg_attr(test, config(derive = "PartialEq, Eq"))]
+pub struct DnsCacheConfig {
+    /// ###### feature.network.dns.cache.enabled {#feature-network-dns-cache-enabled}
+    ///
+    /// Defaults to `true`.
+    #[config(env = "MIRRORD_DNS_CACHE", default = true)]
+    pub enabled: bool,
+
+    /// ###### feature.network.dns.cache.max_ttl {#feature-network-dns-cache-max_ttl}
+    ///
+    /// Maximum time, in seconds, for which resolved addresses are cached, even when the cluster
+    /// DNS returns a longer time to live.
+    ///
+    /// Defaults to `30`.
+    #[config(default = 30)]
+    pub max_ttl: u64,
+
+    /// ###### feature.network.dns.cache.negative_ttl {#feature-network-dns-cache-negative_ttl}
+    ///
+    /// Time, in seconds, for which hostnames that do not exist (`NXDOMAIN`) are cached. Set to
+    /// `0` to disable negative caching.
+    ///
+    /// Defaults to `5`.
+    #[config(default = 5)]
+    pub negative_ttl: u64,
+
+    /// ###### feature.network.dns.cache.max_entries {#feature-network-dns-cache-max_entries}
+    ///
+    /// Maximum number of cached lookups.
+    ///
+    /// Defaults to `1024`.
+    #[config(default = 1024)]
+    pub max_entries: usize,
+}
+
+impl Default for DnsCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_ttl: 30,
+            negative_ttl: 5,
+            max_entries: 1024,
+        }
+    }
+}
+
+impl CollectAnalytics for &DnsCacheConfig {
+    fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
+        analytics.add("enabled", self.enabled);
+        analytics.add("max_ttl", self.max_ttl);
+        analytics.add("negative_ttl", self.negative_ttl);
+        analytics.add("max_entries", self.max_entries);
+    }
 }
 
 impl DnsConfig {
@@ -112,22 +320,29 @@ impl DnsConfig {
                 return Ok(());
             }
             None => return Ok(()),
-            Some(DnsFilterConfig::Local(filters)) if filters.is_empty() => {
+            Some(DnsFilterConfig::Local { local }) if local.is_empty() => {
                 context.add_warning(
                     "Local DNS filter is empty, all DNS resolution will be done remotely"
                         .to_string(),
                 );
                 return Ok(());
             }
-            Some(DnsFilterConfig::Remote(filters)) if filters.is_empty() => {
+            Some(DnsFilterConfig::Remote { remote }) if remote.is_empty() => {
                 context.add_warning(
                     "Remote DNS filter is empty, all DNS resolution will be done locally"
                         .to_string(),
                 );
                 return Ok(());
             }
-            Some(DnsFilterConfig::Local(filters)) => filters.deref(),
-            Some(DnsFilterConfig::Remote(filters)) => filters.deref(),
+            Some(DnsFilterConfig::Local { local }) => local.deref(),
+            Some(DnsFilterConfig::Remote { remote }) => remote.deref(),
+            Some(DnsFilterConfig::Rules { rules, .. }) => {
+                for rule in rules {
+                    rule.to_regex()?;
+                }
+
+                return Ok(());
+            }
         };
 
         for filter in filters {
@@ -150,8 +365,11 @@ impl MirrordToggleableConfig for DnsFileConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
         Ok(DnsConfig {
             enabled: FromEnv::new("MIRRORD_REMOTE_DNS")
+                .or(Some(false))
+                .layer(|layer| Tracked::new("enabled", layer))
                 .source_value(context)
-                .unwrap_or(Ok(false))?,
+                .transpose()?
+                .unwrap_or_default(),
             ..Default::default()
         })
     }
@@ -160,12 +378,19 @@ impl MirrordToggleableConfig for DnsFileConfig {
 impl CollectAnalytics for &DnsConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("enabled", self.enabled);
+        analytics.add("cache", &self.cache);
 
         if let Some(filter) = self.filter.as_ref() {
             match filter {
-                DnsFilterConfig::Remote(value) => analytics.add("dns_filter_remote", value.len()),
+                DnsFilterConfig::Remote { remote } => {
+                    analytics.add("dns_filter_remote", remote.len())
+                }
+
+                DnsFilterConfig::Local { local } => analytics.add("dns_filter_local", local.len()),
 
-                DnsFilterConfig::Local(value) => analytics.add("dns_filter_local", value.len()),
+                DnsFilterConfig::Rules { rules, .. } => {
+                    analytics.add("dns_filter_rules", rules.len())
+                }
             }
         }
     }
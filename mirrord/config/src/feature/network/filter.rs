@@ -61,6 +61,9 @@ pub enum AddressFilter {
     /// We can only resolve such names on the mirrord layer `connect` call, as we have to check if
     /// the user enabled the DNS feature or not (and thus, resolve it through the remote pod, or
     /// the local app).
+    ///
+    /// The name can also be a pattern starting with `*.` (e.g. `*.internal.corp`), which is
+    /// never resolved, see [`AddressFilter::matches_name`].
     Name(String, u16),
 
     /// Just a plain old subnet and a port, specified as `a.b.c.d/e:f`.
@@ -76,6 +79,36 @@ impl AddressFilter {
             Self::Subnet(_, port) => *port,
         }
     }
+
+    /// Whether this is a [`AddressFilter::Name`] pattern, like `*.internal.corp`.
+    pub fn is_name_pattern(&self) -> bool {
+        matches!(self, Self::Name(name, _) if name.starts_with("*."))
+    }
+
+    /// Checks whether the given host `name` matches this [`AddressFilter::Name`], ignoring the
+    /// case and the trailing dot of fully qualified names.
+    ///
+    /// A pattern like `*.internal.corp` matches any subdomain of `internal.corp` (e.g.
+    /// `db.internal.corp` and `a.db.internal.corp`), but not `internal.corp` itself.
+    ///
+    /// Other variants never match.
+    pub fn matches_name(&self, name: &str) -> bool {
+        let Self::Name(filter_name, _) = self else {
+            return false;
+        };
+
+        let name = name.strip_suffix('.').unwrap_or(name);
+        let filter_name = filter_name.strip_suffix('.').unwrap_or(filter_name);
+
+        match filter_name.strip_prefix('*') {
+            Some(suffix) => {
+                name.len() > suffix.len()
+                    && name.is_char_boundary(name.len() - suffix.len())
+                    && name[name.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+            }
+            None => name.eq_ignore_ascii_case(filter_name),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -100,6 +133,9 @@ pub enum AddressFilterError {
 
     #[error("provided empty string")]
     Empty,
+
+    #[error("invalid host name pattern `{0}`, only a leading `*.` is supported")]
+    NamePattern(String),
 }
 
 impl From<nom::Err<nom::error::Error<&str>>> for AddressFilterError {
@@ -122,12 +158,17 @@ impl FromStr for AddressFilter {
         }
 
         match (address, subnet, port) {
-            // Only port specified.
+            // Only port specified, with or without the `:`.
             (None, None, Some(port)) => {
                 let port = port.parse::<u16>().map_err(AddressFilterError::ParsePort)?;
 
                 Ok(Self::Port(port))
             }
+            (Some(port), None, None) if port.bytes().all(|byte| byte.is_ascii_digit()) => {
+                let port = port.parse::<u16>().map_err(AddressFilterError::ParsePort)?;
+
+                Ok(Self::Port(port))
+            }
 
             // Subnet specified. Address must be IP.
             (Some(address), Some(subnet), port) => {
@@ -156,12 +197,16 @@ impl FromStr for AddressFilter {
                     .map_err(AddressFilterError::ParsePort)?
                     .unwrap_or(0);
 
-                let result = address
-                    .parse::<IpAddr>()
-                    .map(|ip| Self::Socket(SocketAddr::new(ip, port)))
-                    .unwrap_or(Self::Name(address, port));
+                if let Ok(ip) = address.parse::<IpAddr>() {
+                    return Ok(Self::Socket(SocketAddr::new(ip, port)));
+                }
 
-                Ok(result)
+                let pattern = address.strip_prefix("*.").unwrap_or(&address);
+                if pattern.is_empty() || pattern.contains('*') {
+                    return Err(AddressFilterError::NamePattern(address));
+                }
+
+                Ok(Self::Name(address, port))
             }
 
             // Subnet specified but address is missing, error.
@@ -173,6 +218,35 @@ impl FromStr for AddressFilter {
     }
 }
 
+/// <!--${internal}-->
+/// How specific a [`ProtocolAndAddressFilter`] is, see
+/// [`ProtocolAndAddressFilter::specificity`].
+///
+/// Compared by the address first, then by the port, and then by the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FilterSpecificity {
+    address: AddressSpecificity,
+    /// Whether the filter has a port other than `0`.
+    port: bool,
+    /// Whether the filter has a protocol other than [`ProtocolFilter::Any`].
+    protocol: bool,
+}
+
+/// <!--${internal}-->
+/// How specific the [`AddressFilter`] of a [`ProtocolAndAddressFilter`] is, from the least
+/// specific.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum AddressSpecificity {
+    /// [`AddressFilter::Port`] or an unspecified IP.
+    Any,
+    /// [`AddressFilter::Subnet`], by its prefix length.
+    Subnet(u8),
+    /// [`AddressFilter::Name`] pattern, by its number of labels.
+    NamePattern(usize),
+    /// [`AddressFilter::Name`] or a specified IP.
+    Host,
+}
+
 /// <!--${internal}-->
 /// The parsed filter with its [`ProtocolFilter`] and [`AddressFilter`].
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -192,6 +266,37 @@ pub enum ProtocolAndAddressFilterError {
     Protocol(#[from] ParseProtocolError),
 }
 
+impl ProtocolAndAddressFilter {
+    /// How specific this filter is, used to pick between the `local` and `remote` outgoing
+    /// filters matching the same connection.
+    ///
+    /// 1. The address is compared first: a single host (IP or name) is more specific than a name
+    ///    pattern (the one with more labels wins), which is more specific than a subnet (the one
+    ///    with the longer prefix wins), which is more specific than any address;
+    /// 2. then a specific port is more specific than any port;
+    /// 3. then a specific protocol is more specific than any protocol.
+    pub fn specificity(&self) -> FilterSpecificity {
+        let address = match &self.address {
+            AddressFilter::Port(..) => AddressSpecificity::Any,
+            AddressFilter::Socket(socket) if socket.ip().is_unspecified() => {
+                AddressSpecificity::Any
+            }
+            AddressFilter::Socket(..) => AddressSpecificity::Host,
+            AddressFilter::Subnet(subnet, _) => AddressSpecificity::Subnet(subnet.prefix_len()),
+            AddressFilter::Name(name, _) if self.address.is_name_pattern() => {
+                AddressSpecificity::NamePattern(name.trim_end_matches('.').split('.').count())
+            }
+            AddressFilter::Name(..) => AddressSpecificity::Host,
+        };
+
+        FilterSpecificity {
+            address,
+            port: self.address.port() != 0,
+            protocol: self.protocol != ProtocolFilter::Any,
+        }
+    }
+}
+
 impl From<nom::Err<nom::error::Error<&str>>> for ProtocolAndAddressFilterError {
     fn from(value: nom::Err<nom::error::Error<&str>>) -> Self {
         Self::Address(value.into())
@@ -242,7 +347,7 @@ fn address(input: &str) -> IResult<&str, Option<String>> {
     let ipv6 = many1(alt((alphanumeric1, tag(":"))));
     let ipv6_host = delimited(tag("["), ipv6, tag("]"));
 
-    let host_char = alt((alphanumeric1, tag("-"), tag("_"), tag(".")));
+    let host_char = alt((alphanumeric1, tag("-"), tag("_"), tag("."), tag("*")));
     let dotted_address = many1(host_char);
 
     let (rest, address) = opt(alt((dotted_address, ipv6_host)))(input)?;
@@ -414,6 +519,32 @@ mod tests {
         }
     }
 
+    #[fixture]
+    fn plain_port() -> &'static str {
+        "5432"
+    }
+
+    #[fixture]
+    fn plain_port_converted() -> ProtocolAndAddressFilter {
+        ProtocolAndAddressFilter {
+            protocol: ProtocolFilter::Any,
+            address: AddressFilter::Port(5432),
+        }
+    }
+
+    #[fixture]
+    fn name_pattern() -> &'static str {
+        "*.internal.corp:5432"
+    }
+
+    #[fixture]
+    fn name_pattern_converted() -> ProtocolAndAddressFilter {
+        ProtocolAndAddressFilter {
+            protocol: ProtocolFilter::Any,
+            address: AddressFilter::Name("*.internal.corp".to_string(), 5432),
+        }
+    }
+
     // Bad configs.
     #[fixture]
     fn name_with_subnet() -> &'static str {
@@ -430,6 +561,16 @@ mod tests {
         "meow://"
     }
 
+    #[fixture]
+    fn inner_wildcard() -> &'static str {
+        "db.*.corp"
+    }
+
+    #[fixture]
+    fn bare_wildcard() -> &'static str {
+        "*.:5432"
+    }
+
     #[rstest]
     #[case(full(), full_converted())]
     #[case(ipv6(), ipv6_converted())]
@@ -441,6 +582,8 @@ mod tests {
     #[case(subnet_only(), subnet_only_converted())]
     #[case(protocol_port(), protocol_port_converted())]
     #[case(port_only(), port_only_converted())]
+    #[case(plain_port(), plain_port_converted())]
+    #[case(name_pattern(), name_pattern_converted())]
     fn valid_filters(#[case] input: &'static str, #[case] converted: ProtocolAndAddressFilter) {
         assert_eq!(
             ProtocolAndAddressFilter::from_str(input).unwrap(),
@@ -452,8 +595,60 @@ mod tests {
     #[case(name_with_subnet())]
     #[case(port_protocol())]
     #[case(fake_protocol())]
+    #[case(inner_wildcard())]
+    #[case(bare_wildcard())]
     #[should_panic]
     fn invalid_filters(#[case] input: &'static str) {
         ProtocolAndAddressFilter::from_str(input).unwrap();
     }
+
+    #[rstest]
+    #[case("*.internal.corp", "db.internal.corp", true)]
+    #[case("*.internal.corp", "a.db.INTERNAL.corp.", true)]
+    #[case("*.internal.corp", "internal.corp", false)]
+    #[case("*.internal.corp", "dbinternal.corp", false)]
+    #[case("db.internal.corp", "DB.internal.corp.", true)]
+    #[case("db.internal.corp", "a.db.internal.corp", false)]
+    fn name_matching(#[case] filter: &str, #[case] name: &str, #[case] expected: bool) {
+        let filter = AddressFilter::from_str(filter).unwrap();
+        assert_eq!(filter.matches_name(name), expected);
+    }
+
+    /// Each filter is more specific than the previous one.
+    #[test]
+    fn specificity_order() {
+        let filters = [
+            ":0",
+            "udp://",
+            ":5432",
+            "udp://0.0.0.0:5432",
+            "10.0.0.0/8",
+            "10.0.0.0/8:5432",
+            "tcp://10.0.0.0/8:5432",
+            "10.1.0.0/16",
+            "*.corp",
+            "*.internal.corp",
+            "*.internal.corp:5432",
+            "db.internal.corp",
+            "10.1.2.3:5432",
+        ]
+        .map(|filter| {
+            ProtocolAndAddressFilter::from_str(filter)
+                .unwrap()
+                .specificity()
+        });
+
+        for (less, more) in filters.iter().zip(filters.iter().skip(1)) {
+            assert!(less < more, "{less:?} < {more:?}");
+        }
+
+        assert_eq!(
+            ProtocolAndAddressFilter::from_str("db.internal.corp:5432")
+                .unwrap()
+                .specificity(),
+            ProtocolAndAddressFilter::from_str("10.1.2.3:5432")
+                .unwrap()
+                .specificity(),
+        );
+    }
 }
@@ -1,6 +1,12 @@
-use std::{collections::HashSet, fmt, ops::Not, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Not,
+    str::FromStr,
+};
 
 use bimap::BiMap;
+use https::HttpsConfig;
 use mirrord_analytics::{AnalyticValue, Analytics, CollectAnalytics};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize, de, ser, ser::SerializeSeq as _};
@@ -10,12 +16,13 @@ use tls_delivery::LocalTlsDelivery;
 use crate::{
     config::{
         ConfigContext, ConfigError, FromMirrordConfig, MirrordConfig, Result, from_env::FromEnv,
-        source::MirrordConfigSource, unstable::Unstable,
+        provenance::Tracked, source::MirrordConfigSource, unstable::Unstable,
     },
     util::{MirrordToggleableConfig, ToggleableConfig},
 };
 
 pub mod http_filter;
+pub mod https;
 pub mod tls_delivery;
 
 use http_filter::*;
@@ -93,50 +100,102 @@ impl MirrordConfig for IncomingFileConfig {
             IncomingFileConfig::Simple(mode) => IncomingConfig {
                 mode: FromEnv::new("MIRRORD_AGENT_TCP_STEAL_TRAFFIC")
                     .or(mode)
+                    .layer(|layer| Tracked::new("mode", layer))
                     .source_value(context)
                     .transpose()?
                     .unwrap_or_default(),
-                http_filter: HttpFilterFileConfig::default().generate_config(context)?,
+                http_filter: context.section("http_filter", false, |context| {
+                    HttpFilterFileConfig::default().generate_config(context)
+                })?,
                 on_concurrent_steal: FromEnv::new("MIRRORD_OPERATOR_ON_CONCURRENT_STEAL")
                     .layer(|layer| Unstable::new("incoming", "on_concurrent_steal", layer))
+                    .layer(|layer| Tracked::new("on_concurrent_steal", layer))
                     .source_value(context)
                     .transpose()?
                     .unwrap_or_default(),
                 ..Default::default()
             },
-            IncomingFileConfig::Advanced(advanced) => IncomingConfig {
-                mode: FromEnv::new("MIRRORD_AGENT_TCP_STEAL_TRAFFIC")
-                    .or(advanced.mode)
-                    .source_value(context)
-                    .transpose()?
-                    .unwrap_or_default(),
-                http_filter: advanced
-                    .http_filter
-                    .unwrap_or_default()
-                    .generate_config(context)?,
-                port_mapping: advanced
-                    .port_mapping
-                    .map(|m| m.into_iter().collect())
-                    .unwrap_or_default(),
-                ignore_ports: advanced
-                    .ignore_ports
-                    .map(|m| m.into_iter().collect())
-                    .unwrap_or_default(),
-                ignore_localhost: advanced.ignore_localhost.unwrap_or_default(),
-                listen_ports: advanced
-                    .listen_ports
-                    .map(|m| m.into_iter().collect())
-                    .unwrap_or_default(),
-                on_concurrent_steal: FromEnv::new("MIRRORD_OPERATOR_ON_CONCURRENT_STEAL")
-                    .or(advanced.on_concurrent_steal)
-                    .layer(|layer| Unstable::new("incoming", "on_concurrent_steal", layer))
-                    .source_value(context)
-                    .transpose()?
-                    .unwrap_or_default(),
-                ports: advanced.ports.map(|ports| ports.into_iter().collect()),
-                https_delivery: advanced.https_delivery,
-                tls_delivery: advanced.tls_delivery,
-            },
+            IncomingFileConfig::Advanced(advanced) => {
+                let IncomingAdvancedFileConfig {
+                    mode,
+                    http_filter,
+                    port_mapping,
+                    ignore_localhost,
+                    ignore_ports,
+                    listen_ports,
+                    on_concurrent_steal,
+                    ports,
+                    ignore_health_checks,
+                    https_delivery,
+                    tls_delivery,
+                    https,
+                    all_replicas,
+                    max_replicas,
+                } = *advanced;
+
+                let (ports, port_modes, port_http_filters) =
+                    match context.file_value("ports", ports) {
+                        Some(entries) => {
+                            let (ports, modes, http_filters) =
+                                IncomingPortFileConfig::generate(entries)?;
+                            (Some(ports), modes, http_filters)
+                        }
+                        None => (None, Default::default(), Default::default()),
+                    };
+
+                IncomingConfig {
+                    mode: FromEnv::new("MIRRORD_AGENT_TCP_STEAL_TRAFFIC")
+                        .or(mode)
+                        .layer(|layer| Tracked::new("mode", layer))
+                        .source_value(context)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    http_filter: context.section(
+                        "http_filter",
+                        http_filter.is_some(),
+                        |context| http_filter.unwrap_or_default().generate_config(context),
+                    )?,
+                    port_mapping: generate_port_map(
+                        context
+                            .file_value("port_mapping", port_mapping)
+                            .unwrap_or_default(),
+                        "port_mapping",
+                    )?,
+                    ignore_ports: context
+                        .file_value("ignore_ports", ignore_ports)
+                        .map(|m| m.into_iter().collect())
+                        .unwrap_or_default(),
+                    ignore_localhost: context
+                        .file_value("ignore_localhost", ignore_localhost)
+                        .unwrap_or_default(),
+                    ignore_health_checks: context
+                        .file_value("ignore_health_checks", ignore_health_checks)
+                        .unwrap_or_default(),
+                    listen_ports: generate_port_map(
+                        context
+                            .file_value("listen_ports", listen_ports)
+                            .unwrap_or_default(),
+                        "listen_ports",
+                    )?,
+                    on_concurrent_steal: FromEnv::new("MIRRORD_OPERATOR_ON_CONCURRENT_STEAL")
+                        .or(on_concurrent_steal)
+                        .layer(|layer| Unstable::new("incoming", "on_concurrent_steal", layer))
+                        .layer(|layer| Tracked::new("on_concurrent_steal", layer))
+                        .source_value(context)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    ports,
+                    port_modes,
+                    port_http_filters,
+                    https_delivery: context.file_value("https_delivery", https_delivery),
+                    tls_delivery: context.file_value("tls_delivery", tls_delivery),
+                    https: context.file_value("https", https).unwrap_or_default(),
+                    all_replicas: context
+                        .file_value("all_replicas", all_replicas)
+                        .unwrap_or_default(),
+                    max_replicas: context.file_value("max_replicas", max_replicas),
+                }
+            }
         };
 
         Ok(config)
@@ -145,11 +204,15 @@ impl MirrordConfig for IncomingFileConfig {
 impl MirrordToggleableConfig for IncomingFileConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
         let mode = FromEnv::new("MIRRORD_AGENT_TCP_STEAL_TRAFFIC")
+            .or(Some(IncomingMode::Off))
+            .layer(|layer| Tracked::new("mode", layer))
             .source_value(context)
-            .unwrap_or_else(|| Ok(IncomingMode::Off))?;
+            .transpose()?
+            .unwrap_or_default();
 
         let on_concurrent_steal = FromEnv::new("MIRRORD_OPERATOR_ON_CONCURRENT_STEAL")
             .layer(|layer| Unstable::new("incoming", "on_concurrent_steal", layer))
+            .layer(|layer| Tracked::new("on_concurrent_steal", layer))
             .source_value(context)
             .transpose()?
             .unwrap_or_default();
@@ -157,7 +220,11 @@ impl MirrordToggleableConfig for IncomingFileConfig {
         Ok(IncomingConfig {
             mode,
             on_concurrent_steal,
-            http_filter: HttpFilterFileConfig::disabled_config(context)?,
+            http_filter: context.section(
+                "http_filter",
+                true,
+                HttpFilterFileConfig::disabled_config,
+            )?,
             ..Default::default()
         })
     }
@@ -265,13 +332,25 @@ pub struct IncomingAdvancedFileConfig {
     /// This is useful when you want to mirror/steal a port to a different port on the remote
     /// machine. For example, your local process listens on port `9333` and the container listens
     /// on port `80`. You'd use `[[9333, 80]]`
+    ///
+    /// See [`port_mapping`](#feature-network-incoming-port_mapping) for details.
     pub port_mapping: Option<Vec<(u16, u16)>>,
 
     /// ### ignore_localhost
     ///
-    /// Consider removing when adding <https://github.com/metalbear-co/mirrord/issues/702>
+    /// Ignores listeners bound to localhost, and leaves the stolen traffic that originates from
+    /// within the target pod alone.
+    ///
+    /// See [`ignore_localhost`](#feature-network-incoming-ignore_localhost) for details.
     pub ignore_localhost: Option<bool>,
 
+    /// ### ignore_health_checks
+    ///
+    /// Leaves the requests of the target pod's HTTP probes alone when stealing.
+    ///
+    /// See [`ignore_health_checks`](#feature-network-incoming-ignore_health_checks) for details.
+    pub ignore_health_checks: Option<bool>,
+
     /// ### ignore_ports
     ///
     /// Ports to ignore when mirroring/stealing traffic. Useful if you want specific ports to be
@@ -306,8 +385,21 @@ pub struct IncomingAdvancedFileConfig {
     ///
     /// List of ports to mirror/steal traffic from. Other ports will remain local.
     ///
+    /// Each entry is either a port number, or an object with the port and its own
+    /// [`mode`](###mode) and/or HTTP filter, which replace the top-level ones on that port. An
+    /// empty `http_filter` mirrors/steals all traffic on that port.
+    ///
+    /// ```json
+    /// "ports": [
+    ///   { "port": 8080, "http_filter": { "path_filter": "^/webhooks/" } },
+    ///   { "port": 9090, "mode": "mirror" },
+    ///   { "port": 5432, "mode": "off" },
+    ///   3000
+    /// ]
+    /// ```
+    ///
     /// Mutually exclusive with [`ignore_ports`](###ignore_ports).
-    pub ports: Option<Vec<u16>>,
+    pub ports: Option<Vec<IncomingPortFileConfig>>,
 
     /// ### https_delivery
     ///
@@ -319,6 +411,151 @@ pub struct IncomingAdvancedFileConfig {
     /// (Operator Only): configures how mirrord delivers stolen TLS traffic
     /// to the local application.
     pub tls_delivery: Option<LocalTlsDelivery>,
+
+    /// ### https
+    ///
+    /// Terminates TLS on the stolen connections with the given certificate, so that the HTTP
+    /// filter can be applied to HTTPS traffic.
+    ///
+    /// See [`https`](##https) for details.
+    pub https: Option<HttpsConfig>,
+
+    /// ### all_replicas
+    ///
+    /// Mirrors the traffic of all the ready pods of the target workload, not just the targeted
+    /// one.
+    ///
+    /// See [`all_replicas`](#feature-network-incoming-all_replicas) for details.
+    pub all_replicas: Option<bool>,
+
+    /// ### max_replicas
+    ///
+    /// Maximum number of pods mirrored at the same time with [`all_replicas`](###all_replicas).
+    ///
+    /// See [`max_replicas`](#feature-network-incoming-max_replicas) for details.
+    pub max_replicas: Option<usize>,
+}
+
+/// An entry of [`IncomingAdvancedFileConfig::ports`].
+#[derive(Deserialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+#[serde(untagged)]
+pub enum IncomingPortFileConfig {
+    /// Port that uses the top-level mode and HTTP filter.
+    Port(u16),
+    /// Port with its own mode and/or HTTP filter.
+    Filtered(Box<IncomingPortFilterFileConfig>),
+}
+
+/// A port with its own mode and/or HTTP filter, see [`IncomingPortFileConfig`].
+#[derive(Deserialize, Clone, Debug, JsonSchema)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+#[serde(deny_unknown_fields)]
+pub struct IncomingPortFilterFileConfig {
+    pub port: u16,
+
+    /// Replaces the top-level mode on this port.
+    ///
+    /// With `"off"`, the port remains local.
+    pub mode: Option<IncomingMode>,
+
+    /// Replaces the top-level HTTP filter on this port.
+    pub http_filter: Option<HttpFilterFileConfig>,
+}
+
+/// [`IncomingConfig::ports`], [`IncomingConfig::port_modes`] and
+/// [`IncomingConfig::port_http_filters`], generated from the entries of
+/// [`IncomingAdvancedFileConfig::ports`].
+type GeneratedPorts = (
+    HashSet<u16>,
+    HashMap<u16, IncomingMode>,
+    HashMap<u16, HttpFilterConfig>,
+);
+
+impl IncomingPortFileConfig {
+    /// Generates [`IncomingConfig::ports`], [`IncomingConfig::port_modes`] and
+    /// [`IncomingConfig::port_http_filters`].
+    ///
+    /// The per-port filters are generated without the environment, the `MIRRORD_HTTP_*`
+    /// variables apply only to the top-level filter.
+    ///
+    /// A port may be listed more than once only when none of its entries has a mode or an HTTP
+    /// filter.
+    fn generate(entries: Vec<Self>) -> Result<GeneratedPorts> {
+        let mut ports = HashSet::with_capacity(entries.len());
+        let mut modes = HashMap::new();
+        let mut http_filters = HashMap::new();
+
+        for entry in entries {
+            let (port, mode, http_filter) = match entry {
+                Self::Port(port) => (port, None, None),
+                Self::Filtered(filtered) => {
+                    let IncomingPortFilterFileConfig {
+                        port,
+                        mode,
+                        http_filter,
+                    } = *filtered;
+                    let http_filter = http_filter
+                        .map(|http_filter| {
+                            http_filter
+                                .generate_config(&mut ConfigContext::default().strict_env(true))
+                        })
+                        .transpose()?;
+                    (port, mode, http_filter)
+                }
+            };
+
+            let overridden = mode.is_some() || http_filter.is_some();
+            let duplicate = ports.insert(port).not();
+            if duplicate
+                && (overridden || modes.contains_key(&port) || http_filters.contains_key(&port))
+            {
+                return Err(ConfigError::Conflict(format!(
+                    "Port `{port}` is listed more than once in `incoming.ports`, \
+                    ports with their own mode or HTTP filter can be listed only once"
+                )));
+            }
+
+            if let Some(mode) = mode {
+                modes.insert(port, mode);
+            }
+
+            if let Some(http_filter) = http_filter {
+                http_filters.insert(port, http_filter);
+            }
+        }
+
+        Ok((ports, modes, http_filters))
+    }
+}
+
+/// Generates a one-to-one port mapping from the entries of the given `incoming` field.
+///
+/// Repeated entries are allowed, but a port cannot be mapped to two different ports, in either
+/// direction.
+fn generate_port_map(entries: Vec<(u16, u16)>, field: &str) -> Result<BiMap<u16, u16>> {
+    let mut map = BiMap::with_capacity(entries.len());
+
+    for (left, right) in entries {
+        let conflict = map
+            .get_by_left(&left)
+            .map(|other| (left, *other))
+            .or_else(|| map.get_by_right(&right).map(|other| (*other, right)))
+            .filter(|other| *other != (left, right));
+
+        if let Some(other) = conflict {
+            return Err(ConfigError::Conflict(format!(
+                "Entries `{:?}` and `{:?}` of `incoming.{field}` conflict, \
+                each port can be mapped only once",
+                [other.0, other.1],
+                [left, right],
+            )));
+        }
+
+        map.insert(left, right);
+    }
+
+    Ok(map)
 }
 
 fn serialize_bi_map<S>(map: &BiMap<u16, u16>, serializer: S) -> Result<S::Ok, S::Error>
@@ -426,6 +663,17 @@ pub struct IncomingConfig {
     /// This is useful when you want to mirror/steal a port to a different port on the remote
     /// machine. For example, your local process listens on port `9333` and the container listens
     /// on port `80`. You'd use `[[9333, 80]]`
+    ///
+    /// The local port is the one the application asks to listen on, before
+    /// [`listen_ports`](#feature-network-incoming-listen_ports) are applied, so the two can be
+    /// used together: with `"port_mapping": [[9333, 80]]` and `"listen_ports": [[9333, 4433]]`,
+    /// traffic from remote port `80` is delivered to the socket that the application binds to port
+    /// `9333`, which actually listens on port `4433`.
+    ///
+    /// The remote port is subscribed to only when the application starts listening on the local
+    /// port, until then the traffic is handled by the remote application as usual.
+    ///
+    /// Each port can be mapped only once, e.g. `[[8080, 80], [8080, 443]]` is rejected.
     #[serde(
         serialize_with = "serialize_bi_map",
         deserialize_with = "deserialize_bi_map"
@@ -433,8 +681,30 @@ pub struct IncomingConfig {
     pub port_mapping: BiMap<u16, u16>,
 
     /// ##### feature.network.incoming.ignore_localhost {#feature-network-incoming-ignore_localhost}
+    ///
+    /// Ignores the listeners bound to localhost, these will remain local.
+    ///
+    /// When stealing, also makes mirrord-agent pass the connections made from within the target
+    /// pod (e.g. by a sidecar) through to their original destination, before the
+    /// [`http_filter`](#feature-network-incoming-http_filter) is evaluated.
+    ///
+    /// Defaults to `false`.
     pub ignore_localhost: bool,
 
+    /// ##### feature.network.incoming.ignore_health_checks {#feature-network-incoming-ignore_health_checks}
+    ///
+    /// When stealing, makes mirrord-agent pass the requests of the target pod's HTTP readiness and
+    /// liveness probes through to their original destination, before the
+    /// [`http_filter`](#feature-network-incoming-http_filter) is evaluated. This keeps the pod
+    /// from being restarted when its probe port is stolen without a filter.
+    ///
+    /// The probes are read from the target pod spec, a request is matched by its port and path.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// Not used when mirrord runs with the operator.
+    pub ignore_health_checks: bool,
+
     /// ##### feature.network.incoming.ignore_ports {#feature-network-incoming-ignore_ports}
     ///
     /// Ports to ignore when mirroring/stealing traffic, these ports will remain local.
@@ -442,7 +712,8 @@ pub struct IncomingConfig {
     /// Can be especially useful when
     /// [`feature.network.incoming.mode`](#feature-network-incoming-mode) is set to `"steal"`,
     /// and you want to avoid redirecting traffic from some ports (for example, traffic from
-    /// a health probe, or other heartbeat-like traffic).
+    /// a health probe, or other heartbeat-like traffic). Since mirrord never subscribes to these
+    /// ports, their traffic does not reach mirrord-agent at all.
     ///
     /// Mutually exclusive with [`feature.network.incoming.ports`](#feature-network-ports).
     pub ignore_ports: HashSet<u16>,
@@ -467,6 +738,8 @@ pub struct IncomingConfig {
     /// you probably can't listen on `80` without sudo, so you can use `[[80, 4480]]`
     /// then access it on `4480` while getting traffic from remote `80`.
     /// The value of `port_mapping` doesn't affect this.
+    ///
+    /// Each port can be mapped only once.
     #[serde(
         serialize_with = "serialize_bi_map",
         deserialize_with = "deserialize_bi_map"
@@ -484,8 +757,48 @@ pub struct IncomingConfig {
     ///
     /// Mutually exclusive with
     /// [`feature.network.incoming.ignore_ports`](#feature-network-ignore_ports).
+    ///
+    /// Entries can also be objects with the port and its own
+    /// [`mode`](#feature-network-incoming-mode) and/or
+    /// [`http_filter`](#feature-network-incoming-http_filter), which replace the top-level ones
+    /// on that port. An empty `http_filter` mirrors/steals all traffic on that port, and a port
+    /// with the `"off"` mode remains local. Plain port numbers use the top-level mode and filter.
+    ///
+    /// The following steals the filtered HTTP traffic of port `8080`, only mirrors port `9090`,
+    /// and ignores port `5432`, in the same session:
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "ports": [
+    ///           { "port": 8080, "http_filter": { "path_filter": "^/webhooks/" } },
+    ///           { "port": 9090, "mode": "mirror" },
+    ///           { "port": 5432, "mode": "off" }
+    ///         ]
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Per-port modes cannot be used when the top-level mode is `"off"`.
     pub ports: Option<HashSet<u16>>,
 
+    /// <!--${internal}-->
+    /// Modes of the [`ports`](#feature-network-incoming-ports) entries that have their own,
+    /// these replace [`mode`](#feature-network-incoming-mode) on those ports.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub port_modes: HashMap<u16, IncomingMode>,
+
+    /// <!--${internal}-->
+    /// HTTP filters of the [`ports`](#feature-network-incoming-ports) entries that have their
+    /// own, these replace [`http_filter`](#feature-network-incoming-http_filter) on those ports.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub port_http_filters: HashMap<u16, HttpFilterConfig>,
+
     /// ##### feature.network.incoming.https_delivery {#feature-network-incoming-https_delivery}
     ///
     /// DEPRECATED: use `tls_delivery` instead.
@@ -496,9 +809,92 @@ pub struct IncomingConfig {
     /// (Operator Only): configures how mirrord delivers stolen TLS traffic
     /// to the local application.
     pub tls_delivery: Option<LocalTlsDelivery>,
+
+    /// ##### feature.network.incoming.https {#feature-network-incoming-https}
+    ///
+    /// Terminates TLS on the stolen connections with the given certificate, so that the
+    /// [`http_filter`](#feature-network-incoming-http_filter) can be applied to HTTPS traffic.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "http_filter": {
+    ///           "path_filter": "^/api/"
+    ///         },
+    ///         "https": {
+    ///           "enabled": true,
+    ///           "ports": [8443],
+    ///           "cert_secret": "ingress/api-tls"
+    ///         }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    #[serde(default)]
+    pub https: HttpsConfig,
+
+    /// ##### feature.network.incoming.all_replicas {#feature-network-incoming-all_replicas}
+    ///
+    /// Mirrors the traffic of all the ready pods of the targeted deployment, rollout or replica
+    /// set, instead of just the one picked as the target. A mirrord-agent is created for each of
+    /// the pods, and the connections mirrored by all of them are delivered to the local
+    /// application.
+    ///
+    /// The pods of the workload are watched during the session: agents are created for the pods
+    /// that become ready and removed with the pods that go away, up to
+    /// [`max_replicas`](#feature-network-incoming-max_replicas) at the same time.
+    ///
+    /// Works only with the `"mirror"` mode, stealing on any port is rejected when this is set.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// Not used when mirrord runs with the operator, which already mirrors the traffic of all the
+    /// pods of the target.
+    pub all_replicas: bool,
+
+    /// ##### feature.network.incoming.max_replicas {#feature-network-incoming-max_replicas}
+    ///
+    /// Maximum number of pods mirrored at the same time with
+    /// [`all_replicas`](#feature-network-incoming-all_replicas), including the targeted one.
+    /// The other ready pods are picked up only when some of the mirrored ones go away.
+    ///
+    /// Defaults to `10`.
+    pub max_replicas: Option<usize>,
 }
 
 impl IncomingConfig {
+    /// <!--${internal}-->
+    /// Default of [`IncomingConfig::max_replicas`].
+    pub const DEFAULT_MAX_REPLICAS: usize = 10;
+
+    /// <!--${internal}-->
+    /// Returns [`IncomingConfig::max_replicas`], or [`IncomingConfig::DEFAULT_MAX_REPLICAS`] if
+    /// it's not set.
+    pub fn max_replicas(&self) -> usize {
+        self.max_replicas.unwrap_or(Self::DEFAULT_MAX_REPLICAS)
+    }
+
+    /// <!--${internal}-->
+    /// Remote ports on which the TLS is terminated, taken from [`HttpsConfig::ports`] or
+    /// [`HttpFilterConfig::ports`].
+    ///
+    /// Empty if [`HttpsConfig::enabled`] is not set.
+    pub fn https_ports(&self) -> Vec<u16> {
+        if self.https.enabled.not() {
+            return Vec::new();
+        }
+
+        self.https
+            .ports
+            .clone()
+            .or_else(|| self.http_filter.ports.as_deref().map(<[u16]>::to_vec))
+            .unwrap_or_default()
+    }
+
     /// <!--${internal}-->
     /// Helper function.
     ///
@@ -507,15 +903,69 @@ impl IncomingConfig {
         matches!(self.mode, IncomingMode::Steal)
     }
 
+    /// <!--${internal}-->
+    /// Returns the mode used for the given remote port, its own from
+    /// [`IncomingConfig::port_modes`], or the top-level one.
+    pub fn port_mode(&self, port: u16) -> IncomingMode {
+        self.port_modes.get(&port).copied().unwrap_or(self.mode)
+    }
+
+    /// <!--${internal}-->
+    /// Checks whether traffic is stolen from any port, either with the top-level mode or with a
+    /// mode from [`IncomingConfig::port_modes`].
+    pub fn steals_any(&self) -> bool {
+        self.is_steal()
+            || self
+                .port_modes
+                .values()
+                .any(|mode| matches!(mode, IncomingMode::Steal))
+    }
+
+    /// <!--${internal}-->
+    /// Helper function.
+    ///
+    /// Checks whether any HTTP filter is set, either the top-level one or one from
+    /// [`IncomingConfig::port_http_filters`].
+    pub fn uses_http_filter(&self) -> bool {
+        self.http_filter.is_filter_set()
+            || self
+                .port_http_filters
+                .values()
+                .any(HttpFilterConfig::is_filter_set)
+    }
+
+    /// <!--${internal}-->
+    /// Converts [`IncomingConfig::port_http_filters`] into the protocol-level filters, with
+    /// [`None`] for the ports that should not be filtered.
+    pub fn protocol_port_http_filters(
+        &self,
+    ) -> Result<HashMap<u16, Option<mirrord_protocol::tcp::HttpFilter>>, HttpFilterParseError> {
+        self.port_http_filters
+            .iter()
+            .map(|(port, http_filter)| {
+                let filter = http_filter
+                    .is_filter_set()
+                    .then(|| http_filter.as_protocol_http_filter())
+                    .transpose()?;
+
+                Ok((*port, filter))
+            })
+            .collect()
+    }
+
     /// <!--${internal}-->
     /// Helper function
     ///
     /// Checks whether the given port can be stolen without an HTTP filter.
     pub fn steals_port_without_filter(&self, port: u16) -> bool {
-        if self.is_steal().not() {
+        if matches!(self.port_mode(port), IncomingMode::Steal).not() {
             return false;
         }
 
+        if let Some(http_filter) = self.port_http_filters.get(&port) {
+            return http_filter.is_filter_set().not();
+        }
+
         if self.http_filter.is_filter_set() {
             self.http_filter
                 .ports
@@ -689,6 +1139,11 @@ impl CollectAnalytics for &IncomingConfig {
         analytics.add("listen_ports_count", self.listen_ports.len());
         analytics.add("ignore_localhost", self.ignore_localhost);
         analytics.add("ignore_ports_count", self.ignore_ports.len());
+        analytics.add("ignore_health_checks", self.ignore_health_checks);
         analytics.add("http", &self.http_filter);
+        analytics.add("port_modes_count", self.port_modes.len());
+        analytics.add("port_http_filters_count", self.port_http_filters.len());
+        analytics.add("https", self.https.enabled);
+        analytics.add("all_replicas", self.all_replicas);
     }
 }
@@ -1,11 +1,14 @@
-use std::{ops::Not, str::FromStr, sync::LazyLock};
+use std::{collections::BTreeMap, ops::Not, str::FromStr, sync::LazyLock};
 
+use base64::{Engine, engine::general_purpose};
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
 use mirrord_protocol::tcp::{
-    Filter, HTTP_BODY_JSON_FILTER_VERSION, HTTP_COMPOSITE_FILTER_VERSION,
-    HTTP_HEADER_JQ_FILTER_VERSION, HTTP_METHOD_FILTER_VERSION, HttpBodyFilter, HttpFilter,
-    HttpMethodFilter, JqQuery, JsonPathQuery,
+    Filter, GrpcDecoder, HTTP_BODY_FILTER_EVALUATE_VERSION, HTTP_BODY_GRPC_FILTER_VERSION,
+    HTTP_BODY_JQ_FILTER_VERSION, HTTP_BODY_JSON_FILTER_VERSION, HTTP_COMPOSITE_FILTER_VERSION,
+    HTTP_FILTER_ERROR_POLICY_VERSION, HTTP_HEADER_JQ_FILTER_VERSION, HTTP_METHOD_FILTER_VERSION,
+    HTTP_NESTED_COMPOSITE_FILTER_VERSION, HTTP_NOT_FILTER_VERSION, HttpBodyFilter, HttpFilter,
+    HttpMethodFilter, JqQuery, JsonPathQuery, ProtoDescriptorSet,
 };
 use schemars::JsonSchema;
 use semver::{Version, VersionReq};
@@ -13,7 +16,10 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
-    config::{ConfigContext, ConfigError, from_env::FromEnv, source::MirrordConfigSource},
+    config::{
+        ConfigContext, ConfigError, from_env::FromEnv, provenance::Tracked,
+        source::MirrordConfigSource,
+    },
     util::{MirrordToggleableConfig, VecOrSingle},
 };
 
@@ -123,10 +129,39 @@ pub struct HttpFilterConfig {
     #[config(env = "MIRRORD_HTTP_METHOD_FILTER")]
     pub method_filter: Option<String>,
 
+    /// ##### feature.network.incoming.http_filter.methods {#feature-network-incoming-http_filter-methods}
+    ///
+    /// Steal only the requests with one of these HTTP methods, case-insensitive.
+    ///
+    /// Unlike the other filters, this can be used together with any of them, in which case the
+    /// requests must match both. The methods are checked before the other filters.
+    ///
+    /// Non-standard HTTP methods are allowed, but must be valid HTTP tokens.
+    ///
+    /// Example, steal only the mutating requests to `/api`:
+    /// ```json
+    /// {
+    ///   "methods": ["POST", "PUT", "PATCH", "DELETE"],
+    ///   "path_filter": "^/api"
+    /// }
+    /// ```
+    #[config(env = "MIRRORD_HTTP_METHODS_FILTER")]
+    pub methods: Option<VecOrSingle<String>>,
+
     /// ##### feature.network.incoming.http_filter.body_filter {#feature-network-incoming-http-body-filter}
     ///
-    /// Matches the request based on the contents of its body.
-    pub body_filter: Option<BodyFilter>,
+    /// Matches the request based on the contents of its body, see
+    /// [`body_filter.json`](#feature-network-incoming-inner-body-filter-json) and
+    /// [`body_filter.jq`](#feature-network-incoming-inner-body-filter-jq).
+    ///
+    /// Can also be just a jq expression, which is evaluated against the JSON body (instead of the
+    /// whole request), and never matches requests whose body is not JSON:
+    /// ```json
+    /// {
+    ///   "body_filter": ".tenant_id == \"my-team\""
+    /// }
+    /// ```
+    pub body_filter: Option<BodyFilterOrQuery>,
 
     /// ##### feature.network.incoming.http_filter.header_filter_jq {#feature-network-incoming-http-header-filter-jq}
     ///
@@ -140,10 +175,14 @@ pub struct HttpFilterConfig {
     ///
     /// An array of HTTP filters.
     ///
-    /// Each inner filter specifies either header or path regex.
+    /// Each inner filter is a header, path, method, body or jq header filter, or another
+    /// `all_of`/`any_of` list (nested up to 4 levels deep).
     /// Requests must match all of the filters to be stolen.
     ///
-    /// Cannot be an empty list.
+    /// Body filters are evaluated last, so that requests can be rejected by the cheaper filters
+    /// before their bodies are parsed.
+    ///
+    /// Cannot be an empty list, and cannot be used together with the other filter fields.
     ///
     /// Example:
     /// ```json
@@ -161,10 +200,14 @@ pub struct HttpFilterConfig {
     ///
     /// An array of HTTP filters.
     ///
-    /// Each inner filter specifies either header or path regex.
+    /// Each inner filter is a header, path, method, body or jq header filter, or another
+    /// `all_of`/`any_of` list (nested up to 4 levels deep).
     /// Requests must match at least one of the filters to be stolen.
     ///
-    /// Cannot be an empty list.
+    /// Body filters are evaluated last, so that requests can be rejected by the cheaper filters
+    /// before their bodies are parsed.
+    ///
+    /// Cannot be an empty list, and cannot be used together with the other filter fields.
     ///
     /// Example:
     /// ```json
@@ -178,12 +221,101 @@ pub struct HttpFilterConfig {
     /// ```
     pub any_of: Option<Vec<InnerFilter>>,
 
+    /// ##### feature.network.incoming.http_filter.not {#feature-network-incoming-http_filter-not}
+    ///
+    /// Steal the requests that do **not** match the inner filter.
+    ///
+    /// The inner filter has the same format as the filters in `all_of` and `any_of`, and `not`
+    /// can also be used inside of them. A `not` cannot directly contain another `not`.
+    ///
+    /// Example, steal all requests except the synthetic probes:
+    /// ```json
+    /// {
+    ///   "not": { "header": "^x-synthetic-probe: true$" }
+    /// }
+    /// ```
+    pub not: Option<Box<InnerFilter>>,
+
+    /// ##### feature.network.incoming.http_filter.grpc {#feature-network-incoming-http_filter-grpc}
+    ///
+    /// Decodes the protobuf messages of gRPC requests into JSON, so that
+    /// [`body_filter.jq`](#feature-network-incoming-inner-body-filter-jq) filters can match them
+    /// like JSON bodies.
+    ///
+    /// - `descriptor_set` is a compiled protobuf descriptor set (e.g. from `protoc
+    ///   --include_imports --descriptor_set_out`), in standard base64;
+    /// - `descriptor_set_path` is the path to such a descriptor set in the agent's file system
+    ///   (e.g. a volume mounted to the agent pod), used instead of `descriptor_set`;
+    /// - `messages` maps prefixes of the request path to the fully-qualified names of the request
+    ///   messages, the longest matching prefix wins.
+    ///
+    /// The gRPC length-prefixed framing is stripped, and the message is placed in `body`, with
+    /// the fields named in the protobuf JSON mapping (`lowerCamelCase` by default).
+    /// Requests whose message cannot be decoded (including compressed messages and paths that
+    /// match no prefix) follow the `fallback` of the jq filter, like bodies that are not JSON.
+    ///
+    /// Only the first message of the body is decoded, so streaming RPCs are matched based on
+    /// their first request message.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "body_filter": { "body": "jq", "query": ".body.userId == \"123\"" },
+    ///   "grpc": {
+    ///     "descriptor_set_path": "/protos/users.pb",
+    ///     "messages": { "/acme.users.v1.Users/GetUser": "acme.users.v1.GetUserRequest" }
+    ///   }
+    /// }
+    /// ```
+    pub grpc: Option<GrpcDecodeConfig>,
+
+    /// ##### feature.network.incoming.http_filter.on_filter_error {#feature-network-incoming-http_filter-on_filter_error}
+    ///
+    /// What the agent does with a request when evaluating the filter fails, e.g. when a jq
+    /// expression errors at runtime or runs over the agent's time or memory limits:
+    ///
+    /// - `"pass"` (default): the request is not stolen, and goes to its original destination;
+    /// - `"steal"`: the request is stolen;
+    /// - `"close"`: the agent responds with `503 Service Unavailable`, with the `Mirrord-Agent:
+    ///   filter-error` header, so that it can be told apart from the errors of your application.
+    #[config(default)]
+    pub on_filter_error: FilterErrorPolicy,
+
     /// ##### feature.network.incoming.http_filter.ports {#feature-network-incoming-http_filter-ports}
     ///
     /// Activate the HTTP traffic filter only for these ports. When
     /// absent, filtering will be done for all ports.
     #[config(env = "MIRRORD_HTTP_FILTER_PORTS")]
     pub ports: Option<VecOrSingle<u16>>,
+
+    /// ##### feature.network.incoming.http_filter.dry_run {#feature-network-incoming-http_filter-dry_run}
+    ///
+    /// Only report which requests the filter would match, without stealing (or mirroring)
+    /// anything.
+    ///
+    /// The agent evaluates the filter on mirrored copies of the requests to the filtered ports,
+    /// and mirrord prints a report for each request (method, path, whether it matched, which
+    /// filter of an `any_of` matched, how long the evaluation took), followed by a summary when
+    /// the session ends. Your application does not receive the requests to the filtered ports.
+    ///
+    /// Applies to all the filtered ports, including the ones with their own filters in
+    /// [`feature.network.incoming.ports`](#feature-network-incoming-ports). Can only be set in
+    /// `feature.network.incoming.http_filter`. Not supported on Windows.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_HTTP_FILTER_DRY_RUN", default = false)]
+    pub dry_run: bool,
+
+    /// ##### feature.network.incoming.http_filter.dry_run_output {#feature-network-incoming-http_filter-dry_run_output}
+    ///
+    /// Format of the [`dry_run`](#feature-network-incoming-http_filter-dry_run) reports:
+    ///
+    /// - `"table"` (default): a table with a row for each request;
+    /// - `"json"`: a JSON object per line for each request, and one with the `summary` at the end.
+    ///
+    /// Can also be set with `mirrord exec --output json`.
+    #[config(env = "MIRRORD_HTTP_FILTER_DRY_RUN_OUTPUT", default)]
+    pub dry_run_output: DryRunOutput,
 }
 
 impl HttpFilterConfig {
@@ -191,10 +323,12 @@ impl HttpFilterConfig {
         self.header_filter.is_some()
             || self.path_filter.is_some()
             || self.method_filter.is_some()
+            || self.methods.is_some()
             || self.all_of.is_some()
             || self.any_of.is_some()
             || self.body_filter.is_some()
             || self.header_filter_jq.is_some()
+            || self.not.is_some()
     }
 
     pub fn ensure_usable_with(
@@ -202,12 +336,22 @@ impl HttpFilterConfig {
         agent_protocol_version: Option<Version>,
     ) -> Result<(), ConfigError> {
         #![allow(clippy::type_complexity)]
-        static REQUIREMENTS: [(fn(&HttpFilterConfig) -> bool, &LazyLock<VersionReq>, &str); 4] = [
+        static REQUIREMENTS: [(fn(&HttpFilterConfig) -> bool, &LazyLock<VersionReq>, &str); 10] = [
             (
                 HttpFilterConfig::is_composite,
                 &HTTP_COMPOSITE_FILTER_VERSION,
                 "'any_of' or 'all_of' HTTP filter types",
             ),
+            (
+                HttpFilterConfig::has_nested_composite,
+                &HTTP_NESTED_COMPOSITE_FILTER_VERSION,
+                "nested 'any_of' or 'all_of' HTTP filters",
+            ),
+            (
+                HttpFilterConfig::has_not_filter,
+                &HTTP_NOT_FILTER_VERSION,
+                "'not' HTTP filters",
+            ),
             (
                 HttpFilterConfig::has_method_filter,
                 &HTTP_METHOD_FILTER_VERSION,
@@ -218,11 +362,31 @@ impl HttpFilterConfig {
                 &HTTP_BODY_JSON_FILTER_VERSION,
                 "JSON body filters",
             ),
+            (
+                HttpFilterConfig::has_jq_body_filter,
+                &HTTP_BODY_JQ_FILTER_VERSION,
+                "jq body filters",
+            ),
+            (
+                HttpFilterConfig::has_evaluate_fallback,
+                &HTTP_BODY_FILTER_EVALUATE_VERSION,
+                "the 'evaluate' jq body filter fallback",
+            ),
             (
                 HttpFilterConfig::has_header_jq_filter,
                 &HTTP_HEADER_JQ_FILTER_VERSION,
                 "JQ header filters",
             ),
+            (
+                HttpFilterConfig::has_grpc_decoder,
+                &HTTP_BODY_GRPC_FILTER_VERSION,
+                "gRPC body decoding",
+            ),
+            (
+                HttpFilterConfig::has_error_policy,
+                &HTTP_FILTER_ERROR_POLICY_VERSION,
+                "'on_filter_error' other than 'pass'",
+            ),
         ];
 
         for (validator, version, what) in REQUIREMENTS {
@@ -245,51 +409,119 @@ impl HttpFilterConfig {
     }
 
     fn is_composite(&self) -> bool {
-        self.all_of.is_some() || self.any_of.is_some()
+        self.all_of.is_some() || self.any_of.is_some() || self.methods.is_some()
+    }
+
+    /// All filters inside `all_of`, `any_of` and `not`, including the nested ones.
+    pub(crate) fn inner_filters(&self) -> Vec<&InnerFilter> {
+        let mut filters = Vec::new();
+        let mut pending = self
+            .all_of
+            .iter()
+            .chain(self.any_of.iter())
+            .flatten()
+            .chain(self.not.as_deref())
+            .collect::<Vec<_>>();
+
+        while let Some(filter) = pending.pop() {
+            match filter {
+                InnerFilter::AllOf { all_of: nested } | InnerFilter::AnyOf { any_of: nested } => {
+                    pending.extend(nested)
+                }
+                InnerFilter::Not { not } => pending.push(not),
+                _ => {}
+            }
+            filters.push(filter);
+        }
+
+        filters
+    }
+
+    fn has_nested_composite(&self) -> bool {
+        (self.methods.is_some() && (self.all_of.is_some() || self.any_of.is_some()))
+            || self
+                .inner_filters()
+                .into_iter()
+                .any(|f| f.nested().is_some())
+    }
+
+    fn has_not_filter(&self) -> bool {
+        self.not.is_some()
+            || self
+                .inner_filters()
+                .into_iter()
+                .any(|f| matches!(f, InnerFilter::Not { .. }))
     }
 
     fn has_method_filter(&self) -> bool {
         self.method_filter.is_some()
-            || self.all_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::Method { .. }))
-            })
-            || self.any_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::Method { .. }))
-            })
+            || self.methods.is_some()
+            || self
+                .inner_filters()
+                .into_iter()
+                .any(|f| matches!(f, InnerFilter::Method { .. }))
     }
 
     fn has_header_jq_filter(&self) -> bool {
         self.header_filter_jq.is_some()
-            || self.all_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::HeaderJq { .. }))
-            })
-            || self.any_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::HeaderJq { .. }))
-            })
+            || self
+                .inner_filters()
+                .into_iter()
+                .any(|f| matches!(f, InnerFilter::HeaderJq { .. }))
     }
 
     fn has_json_body_filter(&self) -> bool {
-        matches!(self.body_filter, Some(BodyFilter::Json { .. }))
-            || self.all_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::Body(BodyFilter::Json { .. })))
-            })
-            || self.any_of.as_ref().is_some_and(|composite| {
-                composite
-                    .iter()
-                    .any(|f| matches!(f, InnerFilter::Body(BodyFilter::Json { .. })))
+        matches!(
+            self.body_filter,
+            Some(BodyFilterOrQuery::Filter(BodyFilter::Json { .. }))
+        ) || self.inner_filters().into_iter().any(|f| {
+            matches!(
+                f,
+                InnerFilter::Body(BodyFilterOrQuery::Filter(BodyFilter::Json { .. }))
+            )
+        })
+    }
+
+    pub(crate) fn has_jq_body_filter(&self) -> bool {
+        matches!(
+            self.body_filter,
+            Some(BodyFilterOrQuery::Query(..) | BodyFilterOrQuery::Filter(BodyFilter::Jq { .. }))
+        ) || self.inner_filters().into_iter().any(|f| {
+            matches!(
+                f,
+                InnerFilter::Body(
+                    BodyFilterOrQuery::Query(..) | BodyFilterOrQuery::Filter(BodyFilter::Jq { .. })
+                )
+            )
+        })
+    }
+
+    fn has_evaluate_fallback(&self) -> bool {
+        let is_evaluate = |filter: &BodyFilterOrQuery| {
+            matches!(
+                filter,
+                BodyFilterOrQuery::Filter(BodyFilter::Jq {
+                    fallback: BodyFilterFallback::Evaluate,
+                    ..
+                })
+            )
+        };
+
+        self.body_filter.as_ref().is_some_and(is_evaluate)
+            || self.inner_filters().into_iter().any(|f| match f {
+                InnerFilter::Body(filter) => is_evaluate(filter),
+                _ => false,
             })
     }
 
+    fn has_grpc_decoder(&self) -> bool {
+        self.grpc.is_some()
+    }
+
+    fn has_error_policy(&self) -> bool {
+        self.on_filter_error != FilterErrorPolicy::Pass
+    }
+
     /// Returns the number of ports that get filtered.
     pub fn count_filtered_ports(&self) -> u16 {
         if self.is_filter_set().not() {
@@ -308,7 +540,83 @@ impl HttpFilterConfig {
     /// Returns an error if a filter expression is invalid. Panics if no filter is set
     /// (call [`is_filter_set`](Self::is_filter_set) first).
     pub fn as_protocol_http_filter(&self) -> Result<HttpFilter, HttpFilterParseError> {
-        match self {
+        let filter = match (self.methods_filter()?, self.single_filter()?) {
+            // Methods go first, so that the agent checks them before the other filter.
+            (Some(methods), Some(filter)) => HttpFilter::Composite {
+                all: true,
+                filters: vec![methods, filter],
+            },
+            (Some(filter), None) | (None, Some(filter)) => filter,
+            (None, None) => {
+                panic!("No HTTP filters specified, this should have been caught earlier")
+            }
+        };
+
+        let filter = match &self.grpc {
+            Some(grpc) => Self::with_grpc_decoder(filter, &grpc.as_protocol_grpc_decoder()?),
+            None => filter,
+        };
+
+        match self.on_filter_error {
+            // Default of the agent, not sent so that older agents can be used.
+            FilterErrorPolicy::Pass => Ok(filter),
+            policy => Ok(HttpFilter::OnError {
+                filter: Box::new(filter),
+                policy: policy.into(),
+            }),
+        }
+    }
+
+    /// Replaces the [`HttpBodyFilter::Jq`] filters in the given [`HttpFilter`] with
+    /// [`HttpBodyFilter::GrpcJq`] ones, that decode the body with the `decoder`.
+    fn with_grpc_decoder(filter: HttpFilter, decoder: &GrpcDecoder) -> HttpFilter {
+        match filter {
+            HttpFilter::Body(HttpBodyFilter::Jq { query, fallback }) => {
+                HttpFilter::Body(HttpBodyFilter::GrpcJq {
+                    query,
+                    fallback,
+                    decoder: decoder.clone(),
+                })
+            }
+            HttpFilter::Composite { all, filters } => HttpFilter::Composite {
+                all,
+                filters: filters
+                    .into_iter()
+                    .map(|filter| Self::with_grpc_decoder(filter, decoder))
+                    .collect(),
+            },
+            HttpFilter::Not(filter) => {
+                HttpFilter::Not(Box::new(Self::with_grpc_decoder(*filter, decoder)))
+            }
+            other => other,
+        }
+    }
+
+    /// Converts [`HttpFilterConfig::methods`] into the protocol-level [`HttpFilter`].
+    pub(crate) fn methods_filter(&self) -> Result<Option<HttpFilter>, HttpFilterParseError> {
+        let Some(methods) = &self.methods else {
+            return Ok(None);
+        };
+
+        let mut filters = methods
+            .iter()
+            .map(|method| parse_method(method).map(HttpFilter::Method))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if filters.len() == 1 {
+            Ok(filters.pop())
+        } else {
+            Ok(Some(HttpFilter::Composite {
+                all: false,
+                filters,
+            }))
+        }
+    }
+
+    /// Converts the filter set in one of the mutually exclusive fields (all except
+    /// [`HttpFilterConfig::methods`]) into the protocol-level [`HttpFilter`].
+    fn single_filter(&self) -> Result<Option<HttpFilter>, HttpFilterParseError> {
+        let filter = match self {
             HttpFilterConfig {
                 path_filter: Some(path),
                 header_filter: None,
@@ -317,8 +625,14 @@ impl HttpFilterConfig {
                 header_filter_jq: None,
                 all_of: None,
                 any_of: None,
+                not: None,
+                methods: _,
+                grpc: _,
+                on_filter_error: _,
                 ports: _,
-            } => Ok(HttpFilter::Path(Filter::new(path.into())?)),
+                dry_run: _,
+                dry_run_output: _,
+            } => HttpFilter::Path(Filter::new(path.into())?),
 
             HttpFilterConfig {
                 path_filter: None,
@@ -328,8 +642,14 @@ impl HttpFilterConfig {
                 header_filter_jq: None,
                 all_of: None,
                 any_of: None,
+                not: None,
+                methods: _,
+                grpc: _,
+                on_filter_error: _,
                 ports: _,
-            } => Ok(HttpFilter::Header(Filter::new(header.into())?)),
+                dry_run: _,
+                dry_run_output: _,
+            } => HttpFilter::Header(Filter::new(header.into())?),
 
             HttpFilterConfig {
                 path_filter: None,
@@ -339,8 +659,14 @@ impl HttpFilterConfig {
                 header_filter_jq: None,
                 all_of: None,
                 any_of: None,
+                not: None,
+                methods: _,
+                grpc: _,
+                on_filter_error: _,
                 ports: _,
-            } => Ok(HttpFilter::Method(HttpMethodFilter::from_str(method)?)),
+                dry_run: _,
+                dry_run_output: _,
+            } => HttpFilter::Method(HttpMethodFilter::from_str(method)?),
 
             HttpFilterConfig {
                 path_filter: None,
@@ -350,8 +676,14 @@ impl HttpFilterConfig {
                 header_filter_jq: None,
                 all_of: None,
                 any_of: None,
+                not: None,
+                methods: _,
+                grpc: _,
+                on_filter_error: _,
                 ports: _,
-            } => Ok(HttpFilter::Body(filter.as_protocol_http_body_filter()?)),
+                dry_run: _,
+                dry_run_output: _,
+            } => HttpFilter::Body(filter.to_body_filter().as_protocol_http_body_filter()?),
 
             HttpFilterConfig {
                 path_filter: None,
@@ -361,10 +693,14 @@ impl HttpFilterConfig {
                 header_filter_jq: Some(filter),
                 all_of: None,
                 any_of: None,
+                not: None,
+                methods: _,
+                grpc: _,
+                on_filter_error: _,
                 ports: _,
-            } => Ok(HttpFilter::HeaderJq(
-                JqQuery::new(filter).map_err(HttpFilterParseError::Jq)?,
-            )),
+                dry_run: _,
+                dry_run_output: _,
+            } => HttpFilter::HeaderJq(JqQuery::new(filter).map_err(HttpFilterParseError::Jq)?),
 
             HttpFilterConfig {
                 path_filter: None,
@@ -374,8 +710,14 @@ impl HttpFilterConfig {
                 header_filter_jq: None,
                 all_of: Some(filters),
                 any_of: None,
+                not: None,
+                methods: _,
+                grpc: _,
+                on_filter_error: _,
                 ports: _,
-            } => Self::make_composite_filter(true, filters),
+                dry_run: _,
+                dry_run_output: _,
+            } => Self::make_composite_filter(true, filters)?,
 
             HttpFilterConfig {
                 path_filter: None,
@@ -385,11 +727,53 @@ impl HttpFilterConfig {
                 header_filter_jq: None,
                 all_of: None,
                 any_of: Some(filters),
+                not: None,
+                methods: _,
+                grpc: _,
+                on_filter_error: _,
                 ports: _,
-            } => Self::make_composite_filter(false, filters),
+                dry_run: _,
+                dry_run_output: _,
+            } => Self::make_composite_filter(false, filters)?,
 
-            _ => panic!("No HTTP filters specified, this should have been caught earlier"),
-        }
+            HttpFilterConfig {
+                path_filter: None,
+                header_filter: None,
+                method_filter: None,
+                body_filter: None,
+                header_filter_jq: None,
+                all_of: None,
+                any_of: None,
+                not: Some(filter),
+                methods: _,
+                grpc: _,
+                on_filter_error: _,
+                ports: _,
+                dry_run: _,
+                dry_run_output: _,
+            } => HttpFilter::Not(Box::new(Self::make_inner_filter(filter)?)),
+
+            HttpFilterConfig {
+                path_filter: None,
+                header_filter: None,
+                method_filter: None,
+                body_filter: None,
+                header_filter_jq: None,
+                all_of: None,
+                any_of: None,
+                not: None,
+                methods: _,
+                grpc: _,
+                on_filter_error: _,
+                ports: _,
+                dry_run: _,
+                dry_run_output: _,
+            } => return Ok(None),
+
+            _ => panic!("Multiple HTTP filters specified, this should have been caught earlier"),
+        };
+
+        Ok(Some(filter))
     }
 
     fn make_composite_filter(
@@ -398,25 +782,34 @@ impl HttpFilterConfig {
     ) -> Result<HttpFilter, HttpFilterParseError> {
         let filters = filters
             .iter()
-            .map(|filter| match filter {
-                InnerFilter::Path { path } => Ok(HttpFilter::Path(Filter::new(path.clone())?)),
-                InnerFilter::Header { header } => {
-                    Ok(HttpFilter::Header(Filter::new(header.clone())?))
-                }
-                InnerFilter::Method { method } => {
-                    Ok(HttpFilter::Method(HttpMethodFilter::from_str(method)?))
-                }
-                InnerFilter::Body(body_filter) => Ok(HttpFilter::Body(
-                    body_filter.as_protocol_http_body_filter()?,
-                )),
-                InnerFilter::HeaderJq { query } => Ok(HttpFilter::HeaderJq(
-                    JqQuery::new(query).map_err(HttpFilterParseError::Jq)?,
-                )),
-            })
+            .map(Self::make_inner_filter)
             .collect::<Result<Vec<_>, HttpFilterParseError>>()?;
 
         Ok(HttpFilter::Composite { all, filters })
     }
+
+    fn make_inner_filter(filter: &InnerFilter) -> Result<HttpFilter, HttpFilterParseError> {
+        match filter {
+            InnerFilter::Path { path } => Ok(HttpFilter::Path(Filter::new(path.clone())?)),
+            InnerFilter::Header { header } => Ok(HttpFilter::Header(Filter::new(header.clone())?)),
+            InnerFilter::Method { method } => {
+                Ok(HttpFilter::Method(HttpMethodFilter::from_str(method)?))
+            }
+            InnerFilter::Body(body_filter) => Ok(HttpFilter::Body(
+                body_filter
+                    .to_body_filter()
+                    .as_protocol_http_body_filter()?,
+            )),
+            InnerFilter::HeaderJq { query } => Ok(HttpFilter::HeaderJq(
+                JqQuery::new(query).map_err(HttpFilterParseError::Jq)?,
+            )),
+            InnerFilter::AllOf { all_of } => Self::make_composite_filter(true, all_of),
+            InnerFilter::AnyOf { any_of } => Self::make_composite_filter(false, any_of),
+            InnerFilter::Not { not } => {
+                Ok(HttpFilter::Not(Box::new(Self::make_inner_filter(not)?)))
+            }
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
@@ -452,9 +845,13 @@ pub enum InnerFilter {
 
     /// ##### feature.network.incoming.inner_filter.body_filter {#feature-network-incoming-inner-body-filter}
     ///
-    /// Matches the request based on the contents of its body. Currently only JSON body filtering is
-    /// supported.
-    Body(BodyFilter),
+    /// Matches the request based on the contents of its body, see
+    /// [`body_filter.json`](#feature-network-incoming-inner-body-filter-json) and
+    /// [`body_filter.jq`](#feature-network-incoming-inner-body-filter-jq).
+    ///
+    /// Can also be just a jq expression evaluated against the JSON body, like
+    /// [`http_filter.body_filter`](#feature-network-incoming-http-body-filter).
+    Body(BodyFilterOrQuery),
 
     /// ##### feature.network.incoming.inner_filter.header_filter_jq
     /// ##### {#feature-network-incoming-inner-header-filter-jq}
@@ -465,9 +862,131 @@ pub enum InnerFilter {
     HeaderJq {
         query: String,
     },
+
+    /// ##### feature.network.incoming.inner_filter.all_of {#feature-network-incoming-inner-all-of}
+    ///
+    /// Matches when the request matches all of the filters in the list, see
+    /// [`http_filter.all_of`](#feature-network-incoming-http_filter-all_of).
+    ///
+    /// Can be nested up to 4 levels deep, and cannot be an empty list.
+    AllOf {
+        all_of: Vec<InnerFilter>,
+    },
+
+    /// ##### feature.network.incoming.inner_filter.any_of {#feature-network-incoming-inner-any-of}
+    ///
+    /// Matches when the request matches any of the filters in the list, see
+    /// [`http_filter.any_of`](#feature-network-incoming-http_filter-any_of).
+    ///
+    /// Can be nested up to 4 levels deep, and cannot be an empty list.
+    AnyOf {
+        any_of: Vec<InnerFilter>,
+    },
+
+    /// ##### feature.network.incoming.inner_filter.not {#feature-network-incoming-inner-not}
+    ///
+    /// Matches when the request does **not** match the inner filter, see
+    /// [`http_filter.not`](#feature-network-incoming-http_filter-not).
+    ///
+    /// Cannot directly contain another `not`.
+    Not {
+        not: Box<InnerFilter>,
+    },
 }
 
-/// Currently only JSON body filtering is supported.
+impl InnerFilter {
+    /// How many levels deep `all_of` and `any_of` can be nested in each other.
+    pub const MAX_NESTING: usize = 4;
+
+    /// Filters inside of this one, if this is a nested `all_of` or `any_of`.
+    pub(crate) fn nested(&self) -> Option<&[InnerFilter]> {
+        match self {
+            Self::AllOf { all_of } => Some(all_of),
+            Self::AnyOf { any_of } => Some(any_of),
+            _ => None,
+        }
+    }
+
+    /// Checks that this filter, found in the `field` of [`HttpFilterConfig`], follows the
+    /// nesting rules:
+    ///
+    /// 1. `all_of` and `any_of` are not empty, and are nested at most [`InnerFilter::MAX_NESTING`]
+    ///    levels deep;
+    /// 2. `not` does not directly contain another `not`.
+    ///
+    /// `depth` is the number of `all_of` and `any_of` this filter is in.
+    pub(crate) fn verify_nesting(&self, field: &str, depth: usize) -> Result<(), String> {
+        match self {
+            Self::AllOf { all_of: nested } | Self::AnyOf { any_of: nested } => {
+                if depth > Self::MAX_NESTING {
+                    return Err(format!(
+                        "'any_of' and 'all_of' HTTP filters in `{field}` can only be nested {} \
+                        levels deep",
+                        Self::MAX_NESTING
+                    ));
+                }
+
+                if nested.is_empty() {
+                    return Err(format!(
+                        "Nested 'any_of' or 'all_of' HTTP filter in `{field}` cannot be empty"
+                    ));
+                }
+
+                nested
+                    .iter()
+                    .try_for_each(|filter| filter.verify_nesting(field, depth + 1))
+            }
+            Self::Not { not } => Self::verify_negated(not, field, depth),
+            _ => Ok(()),
+        }
+    }
+
+    /// Same as [`InnerFilter::verify_nesting`], for the filter inside of a `not`.
+    pub(crate) fn verify_negated(
+        negated: &InnerFilter,
+        field: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        if matches!(negated, Self::Not { .. }) {
+            return Err(format!(
+                "Double negation in `{field}` HTTP filter, remove both of the 'not's"
+            ));
+        }
+
+        negated.verify_nesting(field, depth)
+    }
+}
+
+/// Value of [`http_filter.body_filter`](#feature-network-incoming-http-body-filter), either a
+/// [`BodyFilter`] or just a jq expression evaluated against the JSON body.
+#[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BodyFilterOrQuery {
+    Query(String),
+    Filter(BodyFilter),
+}
+
+impl BodyFilterOrQuery {
+    /// The jq expression of [`BodyFilterOrQuery::Query`] is turned into a [`BodyFilter::Jq`]
+    /// that evaluates it against the `body` of the request.
+    pub fn to_body_filter(&self) -> BodyFilter {
+        match self {
+            // Newlines, so that a comment at the end of the expression does not swallow the
+            // closing parenthesis.
+            Self::Query(query) => BodyFilter::Jq {
+                query: format!(".body | (\n{query}\n)"),
+                fallback: BodyFilterFallback::NoMatch,
+            },
+            Self::Filter(filter) => filter.clone(),
+        }
+    }
+}
+
+/// Filters that match the request based on the contents of its body.
+///
+/// The agent only buffers bodies up to
+/// [`agent.max_body_buffer_size`](#agent-max_body_buffer_size) bytes, bigger bodies are never
+/// matched by the JSON filter, and follow the `fallback` of the jq filter.
 #[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
 #[serde(tag = "body", rename_all = "lowercase")]
 pub enum BodyFilter {
@@ -565,16 +1084,177 @@ pub enum BodyFilter {
     /// }
     /// ```
     Json { query: String, matches: String },
+
+    /// ##### feature.network.incoming.inner_filter.body_filter.jq {#feature-network-incoming-inner-body-filter-jq}
+    ///
+    /// Evaluates the jq expression in `query` against a JSON view of the request. The request is
+    /// stolen only when the expression returns `true`.
+    ///
+    /// The expression gets the request in this form:
+    /// ```json
+    /// {
+    ///   "method": "POST",
+    ///   "path": "/api/v2/users",
+    ///   "query": { "page": ["1"], "tag": ["a", "b"] },
+    ///   "headers": { "content-type": "application/json", "x-tenant": "acme" },
+    ///   "body": { "user_id": "123" }
+    /// }
+    /// ```
+    ///
+    /// - `method` is uppercase;
+    /// - `path` does not include the query;
+    /// - `query` maps parameter names to all of their values;
+    /// - `headers` maps lowercase header names to their values. Repeated headers are joined with
+    ///   `", "`, and values are truncated to 4096 bytes;
+    /// - `body` is the parsed JSON body.
+    ///
+    /// `fallback` decides what happens with requests whose body is not JSON, or is bigger than
+    /// [`agent.max_body_buffer_size`](#agent-max_body_buffer_size): `"no_match"` (default),
+    /// `"match"`, or `"evaluate"` (evaluates the expression with `null` body).
+    ///
+    /// Example:
+    /// ```json
+    /// "http_filter": {
+    ///   "body_filter": {
+    ///     "body": "jq",
+    ///     "query": ".headers[\"x-tenant\"] == \"acme\" and (.path | test(\"^/api/v2/\")) and .body.user_id == \"123\"",
+    ///     "fallback": "no_match"
+    ///   }
+    /// }
+    /// ```
+    Jq {
+        query: String,
+        #[serde(default)]
+        fallback: BodyFilterFallback,
+    },
 }
 
 impl BodyFilter {
     /// Converts this config into the protocol-level [`HttpBodyFilter`].
-    pub fn as_protocol_http_body_filter(&self) -> Result<HttpBodyFilter, Box<fancy_regex::Error>> {
+    pub fn as_protocol_http_body_filter(&self) -> Result<HttpBodyFilter, HttpFilterParseError> {
         match self {
             BodyFilter::Json { query, matches } => Ok(HttpBodyFilter::Json {
                 query: JsonPathQuery::new_unchecked(query.clone()),
                 matches: Filter::new(matches.clone())?,
             }),
+            BodyFilter::Jq { query, fallback } => Ok(HttpBodyFilter::Jq {
+                query: JqQuery::new(query).map_err(HttpFilterParseError::Jq)?,
+                fallback: (*fallback).into(),
+            }),
+        }
+    }
+}
+
+/// What the agent does with a request when evaluating its HTTP filter fails, see
+/// [`http_filter.on_filter_error`](#feature-network-incoming-http_filter-on_filter_error).
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterErrorPolicy {
+    /// The request is not stolen.
+    #[default]
+    Pass,
+    /// The request is stolen.
+    Steal,
+    /// The agent responds with `503 Service Unavailable`.
+    Close,
+}
+
+impl From<FilterErrorPolicy> for mirrord_protocol::tcp::FilterErrorPolicy {
+    fn from(policy: FilterErrorPolicy) -> Self {
+        match policy {
+            FilterErrorPolicy::Pass => Self::Pass,
+            FilterErrorPolicy::Steal => Self::Steal,
+            FilterErrorPolicy::Close => Self::Close,
+        }
+    }
+}
+
+/// Format of the filter dry-run reports, see
+/// [`http_filter.dry_run_output`](#feature-network-incoming-http_filter-dry_run_output).
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DryRunOutput {
+    /// A human readable table.
+    #[default]
+    Table,
+    /// Newline delimited JSON.
+    Json,
+}
+
+impl FromStr for DryRunOutput {
+    type Err = DryRunOutputParseError;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            _ => Err(DryRunOutputParseError),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("could not parse DryRunOutput from string, values table/json")]
+pub struct DryRunOutputParseError;
+
+/// Protobuf descriptors used to decode gRPC request bodies, see
+/// [`http_filter.grpc`](#feature-network-incoming-http_filter-grpc).
+#[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcDecodeConfig {
+    /// Compiled protobuf descriptor set, in standard base64.
+    pub descriptor_set: Option<String>,
+
+    /// Path to the compiled protobuf descriptor set in the agent's file system.
+    pub descriptor_set_path: Option<String>,
+
+    /// Fully-qualified names of the request messages, by the prefix of the request path.
+    pub messages: BTreeMap<String, String>,
+}
+
+impl GrpcDecodeConfig {
+    /// Converts this config into the protocol-level [`GrpcDecoder`].
+    ///
+    /// The descriptors themselves are only decoded by the agent.
+    pub fn as_protocol_grpc_decoder(&self) -> Result<GrpcDecoder, HttpFilterParseError> {
+        let descriptor_set = match (&self.descriptor_set, &self.descriptor_set_path) {
+            (Some(encoded), None) => {
+                ProtoDescriptorSet::Inline(general_purpose::STANDARD.decode(encoded)?)
+            }
+            (None, Some(path)) => ProtoDescriptorSet::Path(path.clone()),
+            _ => return Err(HttpFilterParseError::GrpcDescriptorSource),
+        };
+
+        Ok(GrpcDecoder {
+            descriptor_set,
+            messages: self
+                .messages
+                .iter()
+                .map(|(prefix, message)| (prefix.clone(), message.clone()))
+                .collect(),
+        })
+    }
+}
+
+/// What [`BodyFilter::Jq`] does with requests whose body cannot be evaluated.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyFilterFallback {
+    /// The request is not stolen.
+    #[default]
+    NoMatch,
+    /// The request is stolen.
+    Match,
+    /// The expression is evaluated with `null` body.
+    Evaluate,
+}
+
+impl From<BodyFilterFallback> for mirrord_protocol::tcp::BodyFilterFallback {
+    fn from(fallback: BodyFilterFallback) -> Self {
+        match fallback {
+            BodyFilterFallback::NoMatch => Self::NoMatch,
+            BodyFilterFallback::Match => Self::Match,
+            BodyFilterFallback::Evaluate => Self::Evaluate,
         }
     }
 }
@@ -582,39 +1262,69 @@ impl BodyFilter {
 impl MirrordToggleableConfig for HttpFilterFileConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
         let header_filter = FromEnv::new("MIRRORD_HTTP_HEADER_FILTER")
+            .layer(|layer| Tracked::new("header_filter", layer))
             .source_value(context)
             .transpose()?;
 
         let path_filter = FromEnv::new("MIRRORD_HTTP_PATH_FILTER")
+            .layer(|layer| Tracked::new("path_filter", layer))
             .source_value(context)
             .transpose()?;
 
         let method_filter = FromEnv::new("MIRRORD_HTTP_METHOD_FILTER")
+            .layer(|layer| Tracked::new("method_filter", layer))
             .source_value(context)
             .transpose()?;
 
         let header_filter_jq = FromEnv::new("MIRRORD_HTTP_HEADER_FILTER_JQ")
+            .layer(|layer| Tracked::new("header_filter_jq", layer))
+            .source_value(context)
+            .transpose()?;
+
+        let methods = FromEnv::new("MIRRORD_HTTP_METHODS_FILTER")
+            .layer(|layer| Tracked::new("methods", layer))
             .source_value(context)
             .transpose()?;
 
         let all_of = None;
         let any_of = None;
+        let not = None;
 
         let body_filter = None;
+        let grpc = None;
+        let on_filter_error = Default::default();
 
         let ports = FromEnv::new("MIRRORD_HTTP_FILTER_PORTS")
+            .layer(|layer| Tracked::new("ports", layer))
             .source_value(context)
             .transpose()?;
 
+        let dry_run = FromEnv::new("MIRRORD_HTTP_FILTER_DRY_RUN")
+            .layer(|layer| Tracked::new("dry_run", layer))
+            .source_value(context)
+            .transpose()?
+            .unwrap_or_default();
+        let dry_run_output = FromEnv::new("MIRRORD_HTTP_FILTER_DRY_RUN_OUTPUT")
+            .layer(|layer| Tracked::new("dry_run_output", layer))
+            .source_value(context)
+            .transpose()?
+            .unwrap_or_default();
+
         Ok(Self::Generated {
             header_filter,
             path_filter,
             method_filter,
+            methods,
             body_filter,
             header_filter_jq,
             all_of,
             any_of,
+            not,
+            grpc,
+            on_filter_error,
             ports,
+            dry_run,
+            dry_run_output,
         })
     }
 }
@@ -623,7 +1333,13 @@ impl CollectAnalytics for &HttpFilterConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("header_filter", self.header_filter.is_some());
         analytics.add("path_filter", self.path_filter.is_some());
+        analytics.add(
+            "methods_count",
+            self.methods.as_ref().map_or(0, |methods| methods.len()),
+        );
+        analytics.add("grpc", self.grpc.is_some());
         analytics.add("ports", self.count_filtered_ports());
+        analytics.add("dry_run", self.dry_run);
     }
 }
 
@@ -638,4 +1354,30 @@ pub enum HttpFilterParseError {
 
     #[error("error while compiling jq expression: {0}")]
     Jq(String),
+
+    #[error("`{0}` is not a valid HTTP method")]
+    InvalidMethod(String),
+
+    #[error("gRPC descriptor set is not valid base64: {0}")]
+    GrpcDescriptorSet(#[from] base64::DecodeError),
+
+    #[error("exactly one of `grpc.descriptor_set` and `grpc.descriptor_set_path` must be set")]
+    GrpcDescriptorSource,
+}
+
+/// Parses an HTTP method from [`HttpFilterConfig::methods`].
+///
+/// Unlike [`HttpMethodFilter::from_str`], accepts non-standard methods, as long as they are valid
+/// HTTP tokens (RFC 9110, section 5.6.2).
+fn parse_method(method: &str) -> Result<HttpMethodFilter, HttpFilterParseError> {
+    let is_token = method.is_empty().not()
+        && method
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte));
+    if is_token.not() {
+        return Err(HttpFilterParseError::InvalidMethod(method.to_owned()));
+    }
+
+    Ok(HttpMethodFilter::from_str(method)
+        .unwrap_or_else(|_| HttpMethodFilter::Other(method.to_ascii_uppercase())))
 }
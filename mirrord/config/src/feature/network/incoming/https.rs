@@ -0,0 +1,221 @@
+use std::{
+    ops::Not,
+    path::{Path, PathBuf},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigContext, ConfigError};
+
+/// Allows for filtering stolen HTTPS traffic, when the target's TLS is terminated in the pod.
+///
+/// mirrord-agent terminates TLS on the stolen connections with the given certificate, applies the
+/// [`http_filter`](#feature-network-incoming-http_filter) to the decrypted requests, and
+/// encrypts the unmatched requests again when passing them to the original destination, keeping
+/// the SNI and ALPN sent by the client. Non-TLS traffic on these ports is passed through as it is.
+///
+/// The certificate chain and the private key can be taken either from a
+/// [TLS secret](https://kubernetes.io/docs/concepts/configuration/secret/#tls-secrets)
+/// (`namespace/name`, or just `name` for the target's namespace):
+///
+/// ```json
+/// {
+///   "enabled": true,
+///   "ports": [8443],
+///   "cert_secret": "ingress/api-tls"
+/// }
+/// ```
+///
+/// or from local PEM files:
+///
+/// ```json
+/// {
+///   "enabled": true,
+///   "ports": [8443],
+///   "cert_pem": "/path/to/cert.pem",
+///   "key_pem": "/path/to/key.pem"
+/// }
+/// ```
+///
+/// The certificate and the key are validated before the agent is created, and passed to the agent
+/// in its environment, so they don't have to be present in the target container. The agent does
+/// not verify the certificate of the original destination when passing requests through.
+///
+/// Not used when mirrord runs with the operator, where stealing TLS traffic is configured with
+/// the `MirrordTlsStealConfig` resources.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct HttpsConfig {
+    /// ##### feature.network.incoming.https.enabled {#feature-network-incoming-https-enabled}
+    ///
+    /// Enables terminating TLS on the stolen connections.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// ##### feature.network.incoming.https.ports {#feature-network-incoming-https-ports}
+    ///
+    /// Remote ports on which the TLS is terminated.
+    ///
+    /// Defaults to [`http_filter.ports`](#feature-network-incoming-http_filter-ports).
+    pub ports: Option<Vec<u16>>,
+
+    /// ##### feature.network.incoming.https.cert_secret {#feature-network-incoming-https-cert_secret}
+    ///
+    /// TLS secret holding the certificate chain (`tls.crt`) and the private key (`tls.key`), in
+    /// the `namespace/name` format. When the namespace is omitted, the target's namespace is
+    /// used.
+    ///
+    /// Mutually exclusive with [`cert_pem`](#feature-network-incoming-https-cert_pem) and
+    /// [`key_pem`](#feature-network-incoming-https-key_pem).
+    pub cert_secret: Option<String>,
+
+    /// ##### feature.network.incoming.https.cert_pem {#feature-network-incoming-https-cert_pem}
+    ///
+    /// Path to a local PEM file containing the certificate chain.
+    ///
+    /// This file must contain at least one certificate.
+    /// It can contain entries of other types, e.g private keys, which are ignored.
+    pub cert_pem: Option<PathBuf>,
+
+    /// ##### feature.network.incoming.https.key_pem {#feature-network-incoming-https-key_pem}
+    ///
+    /// Path to a local PEM file containing the private key matching the certificate chain.
+    ///
+    /// This file must contain exactly one private key.
+    /// It can contain entries of other types, e.g certificates, which are ignored.
+    pub key_pem: Option<PathBuf>,
+}
+
+/// Where the certificate chain and the private key of [`HttpsConfig`] are taken from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpsCertSource<'a> {
+    /// A TLS secret, the namespace defaults to the target's namespace.
+    Secret {
+        namespace: Option<&'a str>,
+        name: &'a str,
+    },
+    /// Local PEM files.
+    Files {
+        cert_pem: &'a Path,
+        key_pem: &'a Path,
+    },
+}
+
+impl HttpsConfig {
+    /// Returns the source of the certificate chain and the private key.
+    ///
+    /// Returns [`None`] if the config is invalid, see [`HttpsConfig::verify`].
+    pub fn cert_source(&self) -> Option<HttpsCertSource<'_>> {
+        match (
+            self.cert_secret.as_deref(),
+            self.cert_pem.as_deref(),
+            self.key_pem.as_deref(),
+        ) {
+            (Some(secret), None, None) => {
+                let (namespace, name) = match secret.split_once('/') {
+                    Some((namespace, name)) => (Some(namespace), name),
+                    None => (None, secret),
+                };
+
+                (namespace.is_none_or(|namespace| namespace.is_empty().not())
+                    && name.is_empty().not()
+                    && name.contains('/').not())
+                .then_some(HttpsCertSource::Secret { namespace, name })
+            }
+            (None, Some(cert_pem), Some(key_pem)) => {
+                Some(HttpsCertSource::Files { cert_pem, key_pem })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn verify(&self, _: &mut ConfigContext) -> Result<(), ConfigError> {
+        if self.enabled.not() {
+            return Ok(());
+        }
+
+        match (
+            self.cert_secret.as_deref(),
+            self.cert_pem.is_some(),
+            self.key_pem.is_some(),
+        ) {
+            (Some(..), false, false) if self.cert_source().is_none() => {
+                Err(ConfigError::InvalidValue {
+                    name: ".feature.network.incoming.https.cert_secret",
+                    provided: self.cert_secret.clone().unwrap_or_default(),
+                    error: "must be in the `namespace/name` or `name` format".into(),
+                })
+            }
+            (Some(..), false, false) | (None, true, true) => Ok(()),
+            (Some(..), ..) => Err(ConfigError::Conflict(
+                ".feature.network.incoming.https.cert_secret cannot be specified together with \
+                .feature.network.incoming.https.cert_pem or .feature.network.incoming.https.key_pem"
+                    .into(),
+            )),
+            (None, false, false) => Err(ConfigError::Conflict(
+                ".feature.network.incoming.https requires either `cert_secret`, \
+                or `cert_pem` and `key_pem`"
+                    .into(),
+            )),
+            (None, ..) => Err(ConfigError::Conflict(
+                ".feature.network.incoming.https.cert_pem and \
+                .feature.network.incoming.https.key_pem must be specified together"
+                    .into(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("api-tls", Some((None, "api-tls")))]
+    #[case("ingress/api-tls", Some((Some("ingress"), "api-tls")))]
+    #[case("/api-tls", None)]
+    #[case("ingress/", None)]
+    #[case("a/b/c", None)]
+    fn cert_secret_source(#[case] secret: &str, #[case] expected: Option<(Option<&str>, &str)>) {
+        let config = HttpsConfig {
+            enabled: true,
+            cert_secret: Some(secret.into()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.cert_source(),
+            expected.map(|(namespace, name)| HttpsCertSource::Secret { namespace, name })
+        );
+        assert_eq!(
+            config.verify(&mut ConfigContext::default()).is_ok(),
+            expected.is_some()
+        );
+    }
+
+    #[rstest]
+    #[case(Some("ns/name"), Some("cert.pem"), None)]
+    #[case(None, Some("cert.pem"), None)]
+    #[case(None, None, None)]
+    fn invalid_cert_source(
+        #[case] cert_secret: Option<&str>,
+        #[case] cert_pem: Option<&str>,
+        #[case] key_pem: Option<&str>,
+    ) {
+        let config = HttpsConfig {
+            enabled: true,
+            ports: None,
+            cert_secret: cert_secret.map(From::from),
+            cert_pem: cert_pem.map(From::from),
+            key_pem: key_pem.map(From::from),
+        };
+
+        assert!(config.cert_source().is_none());
+        config.verify(&mut ConfigContext::default()).unwrap_err();
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::Deref;
+use std::ops::RangeInclusive;
 
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
@@ -7,17 +7,23 @@ use serde::{Deserialize, Serialize};
 
 use super::filter::ProtocolAndAddressFilter;
 use crate::{
-    config::{ConfigContext, ConfigError, from_env::FromEnv, source::MirrordConfigSource},
+    config::{
+        ConfigContext, ConfigError, from_env::FromEnv, provenance::Tracked,
+        source::MirrordConfigSource,
+    },
     util::{MirrordToggleableConfig, VecOrSingle},
 };
 
 /// List of addresses/ports/subnets that should be sent through either the remote pod or local app,
-/// depending how you set this up with either `remote` or `local`.
+/// depending how you set this up with `remote` and `local`.
 ///
 /// You may use this option to specify when outgoing traffic is sent from the remote pod (which
 /// is the default behavior when you enable outgoing traffic), or from the local app (default when
 /// you have outgoing traffic disabled).
 ///
+/// When only `remote` is specified, traffic that matches no filter goes through the local app.
+/// Otherwise, it goes through the remote pod.
+///
 /// Takes a list of values, such as:
 ///
 /// - Only UDP traffic on subnet `1.1.1.0/24` on port 1337 will go through the remote pod.
@@ -52,17 +58,104 @@ use crate::{
 /// }
 /// ```
 ///
-/// Valid values follow this pattern: `[protocol]://[name|address|subnet/mask]:[port]`.
+/// - Traffic to the `10.0.0.0/8` subnet and to port `5432` of any `*.internal.corp` host will go
+///   through the local app, except for `10.1.2.3:443`, the rest will be emitted remotely.
+/// ```json
+/// {
+///   "local": ["10.0.0.0/8", "*.internal.corp:5432"],
+///   "remote": ["10.1.2.3:443"]
+/// }
+/// ```
+///
+/// Valid values follow this pattern: `[protocol]://[name|address|subnet/mask]:[port]`, a plain
+/// port number (e.g. `5432`) is also accepted. Names can be patterns starting with `*.`, which
+/// match any subdomain (e.g. `*.internal.corp` matches `db.internal.corp`).
+///
+/// Names are matched against the name your app passed to `getaddrinfo` when resolving the
+/// address it connects to. Plain names (not patterns) are also resolved and matched against the
+/// address itself.
+///
+/// When filters from both `local` and `remote` match a connection, the most specific one wins:
+///
+/// 1. A single host (IP address or name) is more specific than a name pattern (the one with more
+///    labels wins), which is more specific than a subnet (the one with the longer prefix wins),
+///    which is more specific than a filter without an address;
+/// 2. On the same address level, a filter with a port is more specific than one without;
+/// 3. Then a filter with a protocol is more specific than one without.
+///
+/// When the matching filters are equally specific, `local` wins.
+///
+/// Traffic to the [`local_ports`](#feature.network.outgoing.local_ports) never reaches these
+/// filters, it always goes through the local app.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OutgoingFilterConfig {
+    /// When filters are specified under `remote`, matching traffic will go through the remote pod.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<VecOrSingle<String>>,
+
+    /// When filters are specified under `local`, matching traffic will go through the local app.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local: Option<VecOrSingle<String>>,
+}
+
+impl OutgoingFilterConfig {
+    /// All filters, from both `remote` and `local`.
+    pub fn filters(&self) -> impl Iterator<Item = &String> {
+        self.remote
+            .iter()
+            .chain(self.local.iter())
+            .flat_map(|filters| filters.iter())
+    }
+
+    /// Whether traffic that matches no filter goes through the local app, which is the case when
+    /// only `remote` filters are specified.
+    pub fn unmatched_is_local(&self) -> bool {
+        self.remote.is_some() && self.local.is_none()
+    }
+}
+
+/// An entry of [`OutgoingConfig::local_ports`], either a single port, or an inclusive range of
+/// ports in the `"start-end"` format.
+///
+/// ```json
+/// [4140, "9000-9100"]
+/// ```
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, JsonSchema)]
-#[serde(deny_unknown_fields, rename_all = "lowercase")]
-pub enum OutgoingFilterConfig {
-    /// When filters are specified under `remote`, matching traffic will go through the remote pod,
-    /// everything else will go through local.
-    Remote(VecOrSingle<String>),
-
-    /// When filters are specified under `local`, matching traffic will go through the local app,
-    /// everything else will go through the remote pod.
-    Local(VecOrSingle<String>),
+#[serde(untagged)]
+pub enum OutgoingLocalPorts {
+    Port(u16),
+    Range(String),
+}
+
+impl OutgoingLocalPorts {
+    /// Returns the ports covered by this entry.
+    pub fn range(&self) -> Result<RangeInclusive<u16>, &'static str> {
+        let range = match self {
+            Self::Port(port) => return Ok(*port..=*port),
+            Self::Range(range) => range.trim(),
+        };
+
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let (Ok(start), Ok(end)) = (start.trim().parse::<u16>(), end.trim().parse::<u16>()) else {
+            return Err("expected a port number or a range of ports in the `start-end` format");
+        };
+
+        if start > end {
+            return Err("the start of the range must not be greater than its end");
+        }
+
+        Ok(start..=end)
+    }
+}
+
+impl std::fmt::Display for OutgoingLocalPorts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Port(port) => port.fmt(f),
+            Self::Range(range) => range.fmt(f),
+        }
+    }
 }
 
 /// Tunnel outgoing network operations through mirrord.
@@ -95,6 +188,7 @@ pub enum OutgoingFilterConfig {
 ///         "filter": {
 ///           "local": ["tcp://1.1.1.0/24:1337", "1.1.5.0/24", "google.com", ":53"]
 ///         },
+///         "local_ports": [4140, "9000-9100"],
 ///         "unix_streams": "bear.+"
 ///       }
 ///     }
@@ -130,6 +224,20 @@ pub struct OutgoingConfig {
     #[config(default)]
     pub filter: Option<OutgoingFilterConfig>,
 
+    /// ##### feature.network.outgoing.local_ports {#feature.network.outgoing.local_ports}
+    ///
+    /// Destination ports that are always connected to from the local app, e.g. a local dev proxy
+    /// or the callback port of a debugger.
+    ///
+    /// Takes a list of ports and inclusive ranges of ports, such as `[4140, "9000-9100"]`.
+    ///
+    /// TCP connections and UDP traffic to a matching port are not intercepted by mirrord at all,
+    /// whatever the destination address is. This is the rule with the highest precedence, it is
+    /// checked before [`filter`](#feature.network.outgoing.filter), so a `remote` filter that
+    /// matches the same port has no effect.
+    #[config(default)]
+    pub local_ports: Vec<OutgoingLocalPorts>,
+
     /// ##### feature.network.outgoing.unix_streams {#feature.network.outgoing.unix_streams}
     ///
     /// Connect to these unix streams remotely (and to all other paths locally).
@@ -143,6 +251,12 @@ pub struct OutgoingConfig {
     /// of regexes specified here. If there is a match, mirrord will connect your application with
     /// the target unix socket address on the target pod. Otherwise, it will leave the connection
     /// to happen locally on your machine.
+    ///
+    /// Abstract socket names (the ones that start with a NUL byte) are matched with a leading
+    /// `@` instead, e.g. `@sidecar` matches the abstract socket `\0sidecar`.
+    ///
+    /// Only stream sockets are supported, connecting a datagram unix socket that matches one of
+    /// the regexes fails with an error.
     #[config(unstable, env = "MIRRORD_OUTGOING_REMOTE_UNIX_STREAMS")]
     pub unix_streams: Option<VecOrSingle<String>>,
 }
@@ -151,12 +265,19 @@ impl MirrordToggleableConfig for OutgoingFileConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
         Ok(OutgoingConfig {
             tcp: FromEnv::new("MIRRORD_TCP_OUTGOING")
+                .or(Some(false))
+                .layer(|layer| Tracked::new("tcp", layer))
                 .source_value(context)
-                .unwrap_or(Ok(false))?,
+                .transpose()?
+                .unwrap_or_default(),
             udp: FromEnv::new("MIRRORD_UDP_OUTGOING")
+                .or(Some(false))
+                .layer(|layer| Tracked::new("udp", layer))
                 .source_value(context)
-                .unwrap_or(Ok(false))?,
+                .transpose()?
+                .unwrap_or_default(),
             unix_streams: FromEnv::new("MIRRORD_OUTGOING_REMOTE_UNIX_STREAMS")
+                .layer(|layer| Tracked::new("unix_streams", layer))
                 .source_value(context)
                 .transpose()?,
             ..Default::default()
@@ -169,6 +290,7 @@ impl CollectAnalytics for &OutgoingConfig {
         analytics.add("tcp", self.tcp);
         analytics.add("udp", self.udp);
         analytics.add("ignore_localhost", self.ignore_localhost);
+        analytics.add("local_ports", self.local_ports.len());
         analytics.add(
             "unix_streams",
             self.unix_streams
@@ -178,13 +300,11 @@ impl CollectAnalytics for &OutgoingConfig {
         );
 
         if let Some(filter) = self.filter.as_ref() {
-            match filter {
-                OutgoingFilterConfig::Remote(value) => {
-                    analytics.add("outgoing_filter_remote", value.len())
-                }
-                OutgoingFilterConfig::Local(value) => {
-                    analytics.add("outgoing_filter_local", value.len())
-                }
+            if let Some(value) = filter.remote.as_ref() {
+                analytics.add("outgoing_filter_remote", value.len())
+            }
+            if let Some(value) = filter.local.as_ref() {
+                analytics.add("outgoing_filter_local", value.len())
             }
         }
     }
@@ -192,13 +312,21 @@ impl CollectAnalytics for &OutgoingConfig {
 
 impl OutgoingConfig {
     pub fn verify(&self, _: &mut ConfigContext) -> Result<(), ConfigError> {
-        let filters = match self.filter.as_ref() {
-            None => return Ok(()),
-            Some(OutgoingFilterConfig::Local(filters)) => filters.deref(),
-            Some(OutgoingFilterConfig::Remote(filters)) => filters.deref(),
+        for ports in &self.local_ports {
+            if let Err(error) = ports.range() {
+                return Err(ConfigError::InvalidValue {
+                    name: "feature.network.outgoing.local_ports",
+                    provided: ports.to_string(),
+                    error: error.into(),
+                });
+            }
+        }
+
+        let Some(filters) = self.filter.as_ref() else {
+            return Ok(());
         };
 
-        for filter in filters {
+        for filter in filters.filters() {
             let Err(error) = filter.parse::<ProtocolAndAddressFilter>() else {
                 continue;
             };
@@ -220,7 +348,10 @@ mod tests {
 
     use crate::{
         config::{ConfigContext, MirrordConfig},
-        feature::network::OutgoingFileConfig,
+        feature::network::{
+            OutgoingFileConfig,
+            outgoing::{OutgoingConfig, OutgoingFilterConfig, OutgoingLocalPorts},
+        },
         util::ToggleableConfig,
     };
 
@@ -269,4 +400,70 @@ mod tests {
         assert_eq!(outgoing.tcp, tcp.1);
         assert_eq!(outgoing.udp, udp.1);
     }
+
+    #[rstest]
+    #[case::remote(r#"{ "remote": ["10.0.0.0/8"] }"#, true)]
+    #[case::local(r#"{ "local": "10.0.0.0/8" }"#, false)]
+    #[case::both(
+        r#"{ "local": ["10.0.0.0/8", "5432"], "remote": ["*.internal.corp:443"] }"#,
+        false
+    )]
+    fn filter_lists(#[case] filter: &str, #[case] unmatched_is_local: bool) {
+        let filter = serde_json::from_str::<OutgoingFilterConfig>(filter).unwrap();
+        assert_eq!(filter.unmatched_is_local(), unmatched_is_local);
+
+        let outgoing = OutgoingConfig {
+            filter: Some(filter),
+            ..Default::default()
+        };
+        outgoing.verify(&mut ConfigContext::default()).unwrap();
+    }
+
+    #[rstest]
+    #[case::port("4140", Some(4140..=4140))]
+    #[case::range(r#""9000-9100""#, Some(9000..=9100))]
+    #[case::single_port_range(r#""9000-9000""#, Some(9000..=9000))]
+    #[case::port_string(r#""4140""#, Some(4140..=4140))]
+    #[case::whitespace(r#"" 9000 - 9100 ""#, Some(9000..=9100))]
+    #[case::reversed(r#""9100-9000""#, None)]
+    #[case::out_of_range(r#""9000-70000""#, None)]
+    #[case::open_range(r#""9000-""#, None)]
+    #[case::too_many_parts(r#""1-2-3""#, None)]
+    #[case::not_a_port(r#""http""#, None)]
+    fn local_ports_range(
+        #[case] entry: &str,
+        #[case] expected: Option<std::ops::RangeInclusive<u16>>,
+    ) {
+        let entry = serde_json::from_str::<OutgoingLocalPorts>(entry).unwrap();
+        assert_eq!(entry.range().ok(), expected);
+
+        let outgoing = OutgoingConfig {
+            local_ports: vec![entry],
+            ..Default::default()
+        };
+        assert_eq!(
+            outgoing.verify(&mut ConfigContext::default()).is_ok(),
+            expected.is_some()
+        );
+    }
+
+    /// `local_ports` take precedence over the filter, so overlapping with it is not an error.
+    #[test]
+    fn local_ports_overlap_remote_filter() {
+        let outgoing = serde_json::from_str::<OutgoingFileConfig>(
+            r#"{ "filter": { "remote": [":4140", "tcp://10.0.0.0/8:9050"] }, "local_ports": [4140, "9000-9100"] }"#,
+        )
+        .unwrap()
+        .generate_config(&mut ConfigContext::default())
+        .unwrap();
+
+        outgoing.verify(&mut ConfigContext::default()).unwrap();
+        assert_eq!(
+            outgoing.local_ports,
+            vec![
+                OutgoingLocalPorts::Port(4140),
+                OutgoingLocalPorts::Range("9000-9100".into())
+            ]
+        );
+    }
 }
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -105,10 +107,35 @@ pub struct InternalProxyConfig {
     ///
     /// mirrord will auto create all parent directories.
     ///
-    /// Defaults to a randomized path inside the temporary directory.
+    /// The path can contain strftime-style placeholders, which are replaced with the local time
+    /// of the session start, e.g. `/tmp/mirrord/%Y-%m-%d/intproxy-%H%M%S.log`.
+    ///
+    /// Defaults to a randomized path inside the temporary directory, unique to each session.
     #[config(default, nested)]
     pub log_destination: LogDestinationConfig<Intproxy>,
 
+    /// ### internal_proxy.log_max_size {#internal_proxy-log_max_size}
+    ///
+    /// Size of the internal proxy log file in megabytes, after which the file is rotated.
+    ///
+    /// The rotated files get a numeric suffix, `.1` being the most recent one, and only
+    /// [`internal_proxy.log_max_files`](#internal_proxy-log_max_files) of them are kept.
+    ///
+    /// Set to `0` to disable the rotation.
+    ///
+    /// Defaults to `50`.
+    #[config(default = 50)]
+    pub log_max_size: u64,
+
+    /// ### internal_proxy.log_max_files {#internal_proxy-log_max_files}
+    ///
+    /// How many rotated internal proxy log files to keep, see
+    /// [`internal_proxy.log_max_size`](#internal_proxy-log_max_size).
+    ///
+    /// Defaults to `3`.
+    #[config(default = 3)]
+    pub log_max_files: usize,
+
     /// ### internal_proxy.json_log {#internal_proxy-json_log}
     ///
     /// Whether the proxy should output logs in JSON format. If false, logs are output in
@@ -134,4 +161,166 @@ pub struct InternalProxyConfig {
     /// ```
     #[config(default = 60)]
     pub process_logging_interval: u64,
+
+    /// ### internal_proxy.keepalive_interval {#internal_proxy-keepalive_interval}
+    ///
+    /// How often the internal proxy pings the agent to check that their connection is alive, in
+    /// seconds.
+    ///
+    /// Lower values detect connections silently dropped by a NAT or load balancer sooner. Values
+    /// below `1` are treated as `1`.
+    ///
+    /// ```json
+    /// {
+    ///   "internal_proxy": {
+    ///     "keepalive_interval": 10
+    ///   }
+    /// }
+    /// ```
+    #[config(default = 30)]
+    pub keepalive_interval: u64,
+
+    /// ### internal_proxy.keepalive_max_missed {#internal_proxy-keepalive_max_missed}
+    ///
+    /// After how many consecutive pings left without an answer the connection to the agent is
+    /// considered dead, see
+    /// [`internal_proxy.keepalive_interval`](#internal_proxy-keepalive_interval).
+    ///
+    /// Any message from the agent counts as an answer. When the connection is dead, mirrord
+    /// resumes the session with a new connection, see
+    /// [`internal_proxy.reconnect_max_attempts`](#internal_proxy-reconnect_max_attempts), and
+    /// exits with an error if it can't.
+    ///
+    /// Values below `1` are treated as `1`.
+    ///
+    /// Defaults to `1`.
+    #[config(default = 1)]
+    pub keepalive_max_missed: usize,
+
+    /// ### internal_proxy.reconnect_max_attempts {#internal_proxy-reconnect_max_attempts}
+    ///
+    /// How many times the internal proxy tries to reconnect to the agent when the connection
+    /// drops, before the session fails.
+    ///
+    /// While reconnecting, the user application is paused on its remote operations. Once the
+    /// connection is back, port subscriptions are restored, and remote files opened in read-only
+    /// mode are reopened at their previous offsets (as long as
+    /// [`feature.fs.readonly_file_buffer`](#feature-fs-readonly_file_buffer) is not `0`).
+    /// Operations that can't be resumed, e.g. on other remote files or outgoing connections,
+    /// fail with an IO error.
+    ///
+    /// Sessions that go through the operator are resumed only if the operator allows it, and
+    /// other sessions only with
+    /// [`internal_proxy.reconnect_without_operator`](#internal_proxy-reconnect_without_operator).
+    ///
+    /// Set to `0` to disable reconnecting.
+    ///
+    /// ```json
+    /// {
+    ///   "internal_proxy": {
+    ///     "reconnect_max_attempts": 10
+    ///   }
+    /// }
+    /// ```
+    #[config(default = 10)]
+    pub reconnect_max_attempts: u32,
+
+    /// ### internal_proxy.reconnect_max_downtime {#internal_proxy-reconnect_max_downtime}
+    ///
+    /// For how long the internal proxy tries to reconnect to the agent in total, in seconds, see
+    /// [`internal_proxy.reconnect_max_attempts`](#internal_proxy-reconnect_max_attempts).
+    ///
+    /// With
+    /// [`internal_proxy.reconnect_without_operator`](#internal_proxy-reconnect_without_operator),
+    /// the agent waits this long for the internal proxy to come back before it exits.
+    ///
+    /// ```json
+    /// {
+    ///   "internal_proxy": {
+    ///     "reconnect_max_downtime": 60
+    ///   }
+    /// }
+    /// ```
+    #[config(default = 60)]
+    pub reconnect_max_downtime: u64,
+
+    /// ### internal_proxy.reconnect_without_operator {#internal_proxy-reconnect_without_operator}
+    ///
+    /// Resume sessions that don't go through the operator when the agent connection drops, see
+    /// [`internal_proxy.reconnect_max_attempts`](#internal_proxy-reconnect_max_attempts).
+    ///
+    /// The agent is then kept alive for
+    /// [`internal_proxy.reconnect_max_downtime`](#internal_proxy-reconnect_max_downtime) after
+    /// the internal proxy disconnects, also when the session ends normally.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// ```json
+    /// {
+    ///   "internal_proxy": {
+    ///     "reconnect_without_operator": true
+    ///   }
+    /// }
+    /// ```
+    #[config(default = false)]
+    pub reconnect_without_operator: bool,
+
+    /// ### internal_proxy.protocol_capture {#internal_proxy-protocol_capture}
+    ///
+    /// Records all the messages the internal proxy exchanges with the agent, with their
+    /// timestamps and directions, to files starting with this path. Useful for debugging issues
+    /// like hanging file reads after the session is over.
+    ///
+    /// The capture of each internal proxy is written to `<path>-<pid>.capture`, and is rotated
+    /// once it grows past
+    /// [`internal_proxy.protocol_capture_max_size`](#internal_proxy-protocol_capture_max_size).
+    /// Use `mirrord capture inspect <file>` to print it.
+    ///
+    /// The capture contains the remote environment and the contents of the remote files read
+    /// by your application, unless
+    /// [`internal_proxy.protocol_capture_redact`](#internal_proxy-protocol_capture_redact) is
+    /// set.
+    ///
+    /// ```json
+    /// {
+    ///   "internal_proxy": {
+    ///     "protocol_capture": "/tmp/mirrord-capture"
+    ///   }
+    /// }
+    /// ```
+    #[config(env = "MIRRORD_PROTOCOL_CAPTURE")]
+    pub protocol_capture: Option<PathBuf>,
+
+    /// ### internal_proxy.protocol_capture_redact {#internal_proxy-protocol_capture_redact}
+    ///
+    /// Leave the values of the remote environment variables and the contents of the remote files
+    /// out of the [`internal_proxy.protocol_capture`](#internal_proxy-protocol_capture).
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_PROTOCOL_CAPTURE_REDACT", default = false)]
+    pub protocol_capture_redact: bool,
+
+    /// ### internal_proxy.protocol_capture_max_size {#internal_proxy-protocol_capture_max_size}
+    ///
+    /// Size of the [`internal_proxy.protocol_capture`](#internal_proxy-protocol_capture) file in
+    /// megabytes, after which the file is rotated.
+    ///
+    /// The rotated files get a numeric suffix, `.1` being the most recent one, and only
+    /// [`internal_proxy.protocol_capture_max_files`](#internal_proxy-protocol_capture_max_files)
+    /// of them are kept.
+    ///
+    /// Set to `0` to disable the rotation.
+    ///
+    /// Defaults to `100`.
+    #[config(default = 100)]
+    pub protocol_capture_max_size: u64,
+
+    /// ### internal_proxy.protocol_capture_max_files {#internal_proxy-protocol_capture_max_files}
+    ///
+    /// How many rotated capture files to keep, see
+    /// [`internal_proxy.protocol_capture_max_size`](#internal_proxy-protocol_capture_max_size).
+    ///
+    /// Defaults to `3`.
+    #[config(default = 3)]
+    pub protocol_capture_max_files: usize,
 }
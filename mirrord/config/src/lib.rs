@@ -23,17 +23,14 @@ pub mod retry;
 pub mod target;
 pub mod util;
 
-use std::{collections::HashMap, ffi::OsStr, path::Path};
+use std::{collections::HashMap, ops::Not, path::Path};
 
 use base64::prelude::*;
-use config::{ConfigContext, ConfigError, MirrordConfig};
+use config::{ConfigContext, ConfigError, MirrordConfig, provenance::ConfigSources};
 use experimental::ExperimentalConfig;
 use feature::{
-    env::mapper::EnvVarsRemapper,
-    network::{
-        incoming::http_filter::{BodyFilter, InnerFilter},
-        outgoing::OutgoingFilterConfig,
-    },
+    env::{filter::EnvVarsFilter, mapper::EnvVarsRemapper},
+    network::incoming::http_filter::{BodyFilter, HttpFilterConfig, InnerFilter},
 };
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
@@ -47,7 +44,12 @@ use tracing::warn;
 use crate::{
     agent::AgentConfig,
     ci::CiConfig,
-    config::{FromFileError, source::MirrordConfigSource},
+    config::{
+        FromFileError,
+        expand::{self, Expander},
+        format::ConfigFileFormat,
+        source::MirrordConfigSource,
+    },
     container::ContainerConfig,
     env_key::EnvKey,
     external_proxy::ExternalProxyConfig,
@@ -118,6 +120,37 @@ pub const MIRRORD_LAYER_WAIT_FOR_DEBUGGER: &str = "MIRRORD_LAYER_WAIT_FOR_DEBUGG
 /// }
 /// ```
 ///
+/// ### Basic `config.json` with environment variables {#root-basic-env-vars}
+///
+/// String values can reference environment variables with `${env:VAR}` or
+/// `${env:VAR:-default}` (the default is used when `VAR` is unset or empty), and the local
+/// username with `${user}`. Use `$${` for a literal `${`.
+///
+/// ```json
+/// {
+///   "target": {
+///     "path": "deployment/${env:SERVICE}",
+///     "namespace": "${env:TEAM_NAMESPACE:-staging}"
+///   },
+///   "feature": {
+///     "network": {
+///       "incoming": {
+///         "mode": "steal",
+///         "http_filter": {
+///           "header_filter": "x-user: ${user}"
+///         }
+///       }
+///     }
+///   }
+/// }
+/// ```
+///
+/// References that cannot be resolved are left as they are, with a warning. Pass
+/// `--strict-vars` (or set `MIRRORD_CONFIG_STRICT_VARS=true`) to fail instead.
+///
+/// Any value can also be overridden from the CLI with `--set key=value`, e.g.
+/// `--set agent.namespace=staging`, which wins over the config file and environment variables.
+///
 /// ### Complete `config.json` {#root-complete}
 ///
 ///  Don't use this example as a starting point, it's just here to show you all the available
@@ -354,6 +387,9 @@ pub struct LayerConfig {
     /// Kube context to use from the kubeconfig file.
     /// Will use current context if not specified.
     ///
+    /// The `--context` CLI flag takes precedence over this. mirrord fails right away if the
+    /// context does not exist in the kubeconfig.
+    ///
     /// ```json
     /// {
     ///   "kube_context": "mycluster"
@@ -473,6 +509,16 @@ impl LayerConfig {
     /// See [`LayerConfig::encode`] and [`LayerConfig::decode`].
     pub const RESOLVED_CONFIG_ENV: &str = "MIRRORD_RESOLVED_CONFIG";
 
+    /// Name of environment variable that holds the `--set key=value` overrides, one per line.
+    ///
+    /// Keys are dot-separated paths in the resolved config, as printed by
+    /// `mirrord config resolved`. Values are parsed as JSON, and taken as strings when they are
+    /// not valid JSON.
+    ///
+    /// The overrides are applied last in [`LayerConfig::resolve`], so they win over the config
+    /// file and all other environment variables.
+    pub const SET_ENV: &str = "MIRRORD_CONFIG_SET";
+
     /// Decodes an encoded [`LayerConfig`].
     ///
     /// You can encode the config with [`LayerConfig::encode`].
@@ -510,13 +556,86 @@ impl LayerConfig {
         } else {
             LayerFileConfig::default().generate_config(context)?
         };
-        config.apply_magic();
+        config.apply_magic(context);
+        config.apply_set(context)
+    }
+
+    /// Like [`LayerConfig::resolve`], but also records where the value of each field came from.
+    ///
+    /// Used by `mirrord config resolved --show-sources`.
+    pub fn resolve_with_sources(
+        context: &mut ConfigContext,
+    ) -> Result<(Self, ConfigSources), ConfigError> {
+        context.start_tracking();
+        let config = Self::resolve(context);
+        let sources = context.take_sources().unwrap_or_default();
+
+        Ok((config?, sources))
+    }
+
+    /// Applies the overrides from [`LayerConfig::SET_ENV`].
+    fn apply_set(self, context: &mut ConfigContext) -> Result<Self, ConfigError> {
+        let Ok(overrides) = context.get_env(Self::SET_ENV) else {
+            return Ok(self);
+        };
+
+        let invalid = |provided: &str, error: String| ConfigError::InvalidValue {
+            name: "--set",
+            provided: provided.to_owned(),
+            error: error.into(),
+        };
+        let encode = |error: serde_json::Error| ConfigError::EncodeError(error.to_string());
+
+        let mut config = serde_json::to_value(&self).map_err(encode)?;
+        let mut keys = Vec::new();
+        for entry in overrides.lines().filter(|entry| entry.is_empty().not()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| invalid(entry, "must be in the `key=value` format".into()))?;
+            let value = serde_json::from_str(value)
+                .unwrap_or_else(|_| serde_json::Value::String(value.to_owned()));
+
+            let field = key
+                .split('.')
+                .try_fold(&mut config, |parent, segment| {
+                    if parent.is_null() {
+                        *parent = serde_json::Value::Object(Default::default());
+                    }
+
+                    parent
+                        .as_object_mut()
+                        .map(|fields| fields.entry(segment).or_insert(serde_json::Value::Null))
+                })
+                .ok_or_else(|| invalid(entry, format!("`{key}` is not a config field")))?;
+            *field = value;
+
+            keys.push((key, entry));
+        }
+
+        let config: Self = serde_json::from_value(config)
+            .map_err(|error| invalid(&overrides, error.to_string()))?;
+
+        // Unknown fields are ignored when deserializing, so we check that the keys survived.
+        let applied = serde_json::to_value(&config).map_err(encode)?;
+        for (key, entry) in keys {
+            if key
+                .split('.')
+                .try_fold(&applied, |parent, segment| parent.get(segment))
+                .is_none()
+            {
+                return Err(invalid(entry, format!("`{key}` is not a config field")));
+            }
+
+            context.with_tracker(|tracker| tracker.note_set(key));
+        }
+
         Ok(config)
     }
 
     /// Applies the presets in `feature.magic` to the config, modifying it in-place.
-    fn apply_magic(&mut self) {
+    fn apply_magic(&mut self, context: &mut ConfigContext) {
         if self.feature.magic.aws {
+            context.with_tracker(|tracker| tracker.note_magic("feature.env.unset", "aws"));
             let mut unset: Vec<String> = self
                 .feature
                 .env
@@ -537,6 +656,7 @@ impl LayerConfig {
                     .get_or_insert_with(HashMap::new)
                     .entry(pattern)
                     .or_insert(replacement);
+                context.with_tracker(|tracker| tracker.note_magic("feature.fs.mapping", "aws"));
             }
         }
     }
@@ -551,10 +671,14 @@ impl LayerConfig {
             );
         }
 
-        if matches!(
-            self.feature.network.outgoing.filter,
-            Some(OutgoingFilterConfig::Remote(_))
-        ) && !self.feature.network.dns.enabled
+        if self
+            .feature
+            .network
+            .outgoing
+            .filter
+            .as_ref()
+            .is_some_and(|filter| filter.remote.is_some())
+            && !self.feature.network.dns.enabled
         {
             context.add_warning(
                 "The mirrord outgoing traffic filter includes host names to be connected remotely, \
@@ -563,66 +687,54 @@ impl LayerConfig {
             );
         }
 
-        let http_filter = &self.feature.network.incoming.http_filter;
-        let used_filters = [
-            http_filter.path_filter.is_some(),
-            http_filter.header_filter.is_some(),
-            http_filter.all_of.is_some(),
-            http_filter.any_of.is_some(),
-            http_filter.body_filter.is_some(),
-        ]
-        .into_iter()
-        .filter(|used| *used)
-        .count();
-        if used_filters > 1 {
-            Err(ConfigError::Conflict(
-                "Cannot use multiple types of HTTP filter at the same time, use 'any_of' or 'all_of' to combine filters".to_string(),
-            ))?
-        }
-
-        if [http_filter.all_of.as_ref(), http_filter.any_of.as_ref()]
-            .into_iter()
-            .flatten()
-            .any(Vec::is_empty)
-        {
-            Err(ConfigError::Conflict(
-                "Composite HTTP filter cannot be empty".to_string(),
-            ))?;
-        }
+        let incoming = &self.feature.network.incoming;
+        Self::verify_http_filter(&incoming.http_filter)?;
+        for (port, http_filter) in &incoming.port_http_filters {
+            if http_filter.ports.is_some() {
+                Err(ConfigError::Conflict(format!(
+                    "Cannot use `ports` in the HTTP filter of port `{port}` in `incoming.ports`, \
+                    the filter applies only to that port"
+                )))?
+            }
 
-        let verify_body_filter = |filter: &BodyFilter| match filter {
-            BodyFilter::Json { query, .. } => {
-                // Only need to verify `query` as `matches` is later
-                // verified by the layer. `query` CANNOT be modified by the layer, see
-                // `mirrord_protocol::tcp::JsonPathQuery::new_unchecked`
-                JsonPathQuery::new(query.clone()).map(|_| ()).map_err(|e| {
-                    ConfigError::InvalidValue {
-                        name: "feature.network.incoming.http_filter.body_filter.query",
-                        provided: query.to_string(),
-                        error: Box::new(e),
-                    }
-                })
+            if http_filter.dry_run {
+                Err(ConfigError::Conflict(format!(
+                    "Cannot use `dry_run` in the HTTP filter of port `{port}` in `incoming.ports`, \
+                    set it in `feature.network.incoming.http_filter` instead"
+                )))?
             }
-        };
 
-        if let Some(body) = &http_filter.body_filter {
-            verify_body_filter(body)?;
+            Self::verify_http_filter(http_filter)?;
         }
 
-        if let Some(all_of) = &http_filter.all_of {
-            for filter in all_of {
-                if let InnerFilter::Body(body) = filter {
-                    verify_body_filter(body)?
-                }
-            }
+        if incoming.http_filter.dry_run && incoming.uses_http_filter().not() {
+            context.add_warning(
+                "`feature.network.incoming.http_filter.dry_run` has no effect, \
+                as no HTTP filter is set."
+                    .to_string(),
+            );
         }
 
-        if let Some(any_of) = &http_filter.any_of {
-            for filter in any_of {
-                if let InnerFilter::Body(body) = filter {
-                    verify_body_filter(body)?
-                }
-            }
+        if incoming.mode.is_off()
+            && let Some(port) = incoming.port_modes.keys().min()
+        {
+            Err(ConfigError::Conflict(format!(
+                "Cannot set the mode of port `{port}` in `incoming.ports` when the incoming \
+                mode is `off`"
+            )))?
+        }
+
+        if let Some(port) = incoming
+            .port_modes
+            .iter()
+            .filter(|(port, mode)| mode.is_off() && incoming.port_http_filters.contains_key(port))
+            .map(|(port, _)| port)
+            .min()
+        {
+            Err(ConfigError::Conflict(format!(
+                "Cannot set an HTTP filter on port `{port}` in `incoming.ports`, \
+                its mode is `off`"
+            )))?
         }
 
         if !self.feature.network.incoming.ignore_ports.is_empty()
@@ -657,15 +769,54 @@ impl LayerConfig {
             (None, None) => {}
         }
 
-        if !self.feature.copy_target.enabled
-            && self
-                .target
-                .path
-                .as_ref()
-                .map(Target::requires_copy)
-                .unwrap_or_default()
-        {
-            Err(ConfigError::TargetJobWithoutCopyTarget)?
+        let https = &self.feature.network.incoming.https;
+        https.verify(context)?;
+        if https.enabled {
+            if self.feature.network.incoming.steals_any().not() {
+                Err(ConfigError::Conflict(
+                    "`feature.network.incoming.https` requires the steal mode".into(),
+                ))?
+            }
+
+            if self.feature.network.incoming.https_ports().is_empty() {
+                Err(ConfigError::Conflict(
+                    "`feature.network.incoming.https` requires either `https.ports` \
+                    or `http_filter.ports` to be set"
+                        .into(),
+                ))?
+            }
+        }
+
+        if incoming.all_replicas {
+            if incoming.steals_any() {
+                Err(ConfigError::Conflict(
+                    "`feature.network.incoming.all_replicas` can only be used with the mirror \
+                    mode, please either disable this option or mirror the traffic instead of \
+                    stealing it"
+                        .into(),
+                ))?
+            }
+
+            if incoming.max_replicas == Some(0) {
+                Err(ConfigError::InvalidValue {
+                    name: "feature.network.incoming.max_replicas",
+                    provided: "0".into(),
+                    error: "at least one pod must be mirrored".into(),
+                })?
+            }
+
+            if matches!(
+                self.target.path,
+                None | Some(Target::Deployment(..) | Target::Rollout(..) | Target::ReplicaSet(..))
+            )
+            .not()
+            {
+                context.add_warning(
+                    "`feature.network.incoming.all_replicas` has effect only with deployment, \
+                    rollout and replica set targets, only the targeted pod will be mirrored."
+                        .into(),
+                );
+            }
         }
 
         let is_targetless = match self.target.path.as_ref() {
@@ -675,7 +826,7 @@ impl LayerConfig {
         };
 
         if is_targetless {
-            if self.feature.network.incoming.is_steal() {
+            if self.feature.network.incoming.steals_any() {
                 Err(ConfigError::Conflict("Steal mode is not compatible with a targetless agent, please either disable this option or specify a target.".into()))?
             }
 
@@ -688,6 +839,14 @@ impl LayerConfig {
                 ))?
             }
 
+            if self.feature.env.container.is_some() {
+                Err(ConfigError::Conflict(
+                    "`feature.env.container` is not compatible with a targetless agent, \
+                    please either disable this option or specify a target."
+                        .into(),
+                ))?
+            }
+
             if self.agent.namespace.is_some() {
                 context.add_warning(
                     "Agent namespace is ignored in targetless runs. \
@@ -697,6 +856,27 @@ impl LayerConfig {
             }
         }
 
+        if self.target.create_job {
+            // Target may also be set later in the UI.
+            if is_targetless
+                || matches!(&self.target.path, Some(path) if !matches!(path, Target::CronJob(..)))
+            {
+                return Err(ConfigError::Conflict(
+                    "`target.create_job` can only be used with a cron job target, \
+                    please either disable this option or specify a cron job target."
+                        .into(),
+                ));
+            }
+
+            if self.feature.copy_target.enabled {
+                return Err(ConfigError::Conflict(
+                    "`target.create_job` is not compatible with the copy target feature, \
+                    please disable one of these options."
+                        .into(),
+                ));
+            }
+        }
+
         if self.feature.copy_target.enabled {
             if self.operator == Some(false) {
                 return Err(ConfigError::Conflict(
@@ -763,17 +943,46 @@ impl LayerConfig {
         }
 
         // Env vars
-        if self.feature.env.exclude.is_some() && self.feature.env.include.is_some() {
-            return Err(ConfigError::Conflict(
-                "cannot use both `include` and `exclude` filters for environment variables"
-                    .to_string(),
-            ));
+        EnvVarsFilter::new(&self.feature.env)?;
+
+        if self.feature.env.container.is_some() {
+            if self.feature.env.load_from_process.unwrap_or_default() {
+                Err(ConfigError::Conflict(
+                    "Cannot use both `feature.env.container` and `feature.env.load_from_process` \
+                    at the same time"
+                        .to_string(),
+                ))?
+            }
+
+            if self.agent.ephemeral {
+                Err(ConfigError::Conflict(
+                    "`feature.env.container` is not compatible with an ephemeral agent, \
+                    please either disable `agent.ephemeral` or remove this option."
+                        .to_string(),
+                ))?
+            }
+        }
+
+        if let Some(from) = &self.feature.env.from {
+            if from.is_empty().not() && self.feature.env.load_from_process.unwrap_or_default() {
+                Err(ConfigError::Conflict(
+                    "Cannot use both `feature.env.from` and `feature.env.load_from_process` at \
+                    the same time"
+                        .to_string(),
+                ))?
+            }
+
+            for (index, source) in from.iter().enumerate() {
+                source.verify(index)?;
+            }
         }
 
         if let Some(env_vars_mapping) = self.feature.env.mapping.clone() {
             EnvVarsRemapper::new(env_vars_mapping, HashMap::new())?;
         }
 
+        self.agent.verify(context)?;
+        self.feature.fs.verify(context)?;
         self.feature.network.dns.verify(context)?;
         self.feature.network.outgoing.verify(context)?;
         self.feature.split_queues.verify(context)?;
@@ -805,9 +1014,7 @@ impl LayerConfig {
             ));
         }
 
-        if self.feature.copy_target.enabled
-            && self.feature.network.incoming.http_filter.is_filter_set()
-        {
+        if self.feature.copy_target.enabled && self.feature.network.incoming.uses_http_filter() {
             context.add_warning(
                 "copy target is enabled and http filter is set, this means that all \
             unmatched HTTP requests are discarded"
@@ -838,6 +1045,128 @@ impl LayerConfig {
 
         Ok(())
     }
+
+    /// Verifies the HTTP filter, either the top-level one or one from `incoming.ports`.
+    fn verify_http_filter(http_filter: &HttpFilterConfig) -> Result<(), ConfigError> {
+        let used_filters = [
+            ("header_filter", http_filter.header_filter.is_some()),
+            ("path_filter", http_filter.path_filter.is_some()),
+            ("method_filter", http_filter.method_filter.is_some()),
+            ("body_filter", http_filter.body_filter.is_some()),
+            ("header_filter_jq", http_filter.header_filter_jq.is_some()),
+            ("all_of", http_filter.all_of.is_some()),
+            ("any_of", http_filter.any_of.is_some()),
+            ("not", http_filter.not.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, used)| used.then_some(name))
+        .collect::<Vec<_>>();
+        if used_filters.len() > 1 {
+            Err(ConfigError::Conflict(format!(
+                "Cannot use multiple types of HTTP filter at the same time (found `{}`), \
+                use 'any_of' or 'all_of' to combine filters",
+                used_filters.join("`, `")
+            )))?
+        }
+
+        if let Some(methods) = &http_filter.methods {
+            if methods.is_empty() {
+                Err(ConfigError::Conflict(
+                    "HTTP filter `methods` cannot be empty".to_owned(),
+                ))?
+            }
+
+            http_filter
+                .methods_filter()
+                .map_err(|error| ConfigError::InvalidValue {
+                    name: "feature.network.incoming.http_filter.methods",
+                    provided: methods.to_string(),
+                    error: Box::new(error),
+                })?;
+        }
+
+        let composite_filters = [
+            ("all_of", http_filter.all_of.as_deref()),
+            ("any_of", http_filter.any_of.as_deref()),
+        ];
+        for (name, filters) in composite_filters {
+            let Some(filters) = filters else {
+                continue;
+            };
+
+            if filters.is_empty() {
+                Err(ConfigError::Conflict(format!(
+                    "Composite HTTP filter `{name}` cannot be empty"
+                )))?
+            }
+
+            filters
+                .iter()
+                .try_for_each(|filter| filter.verify_nesting(name, 1))
+                .map_err(ConfigError::Conflict)?;
+        }
+
+        if let Some(not) = &http_filter.not {
+            InnerFilter::verify_negated(not, "not", 0).map_err(ConfigError::Conflict)?;
+        }
+
+        let verify_body_filter = |filter: &BodyFilter| match filter {
+            BodyFilter::Json { query, .. } => {
+                // Only need to verify `query` as `matches` is later
+                // verified by the layer. `query` CANNOT be modified by the layer, see
+                // `mirrord_protocol::tcp::JsonPathQuery::new_unchecked`
+                JsonPathQuery::new(query.clone()).map(|_| ()).map_err(|e| {
+                    ConfigError::InvalidValue {
+                        name: "feature.network.incoming.http_filter.body_filter.query",
+                        provided: query.to_string(),
+                        error: Box::new(e),
+                    }
+                })
+            }
+            BodyFilter::Jq { query, .. } => {
+                mirrord_jaq::compile_jq(query)
+                    .map(|_| ())
+                    .map_err(|e| ConfigError::InvalidValue {
+                        name: "feature.network.incoming.http_filter.body_filter.query",
+                        provided: query.to_string(),
+                        error: Box::new(e),
+                    })
+            }
+        };
+
+        let inner_body_filters = http_filter
+            .inner_filters()
+            .into_iter()
+            .filter_map(|filter| match filter {
+                InnerFilter::Body(body) => Some(body),
+                _ => None,
+            });
+        for body in http_filter.body_filter.iter().chain(inner_body_filters) {
+            verify_body_filter(&body.to_body_filter())?;
+        }
+
+        if let Some(grpc) = &http_filter.grpc {
+            if http_filter.has_jq_body_filter().not() {
+                Err(ConfigError::Conflict(
+                    "HTTP filter `grpc` only decodes bodies for jq body filters, \
+                    but the HTTP filter has none"
+                        .to_string(),
+                ))?
+            }
+
+            if grpc.messages.is_empty() {
+                Err(ConfigError::Conflict(
+                    "HTTP filter `grpc.messages` cannot be empty".to_owned(),
+                ))?
+            }
+
+            grpc.as_protocol_grpc_decoder().map_err(|error| {
+                ConfigError::Conflict(format!("Invalid HTTP filter `grpc`: {error}"))
+            })?;
+        }
+
+        Ok(())
+    }
 }
 
 impl CollectAnalytics for &LayerConfig {
@@ -856,7 +1185,8 @@ impl CollectAnalytics for &LayerConfig {
 }
 
 impl LayerFileConfig {
-    /// Parses a [`LayerFileConfig`] from a file path, rendering any Tera templates.
+    /// Parses a [`LayerFileConfig`] from a file path, rendering any Tera templates and expanding
+    /// the `${...}` references in string values (see [`config::expand`]).
     ///
     /// # Key Resolution for Template Rendering
     ///
@@ -896,13 +1226,26 @@ impl LayerFileConfig {
 
         let rendered = template_engine.render("main", &tera_context)?;
 
-        match path.as_ref().extension().and_then(OsStr::to_str) {
-            // No Extension? assume json
-            Some("json") | None => Ok(serde_json::from_str::<Self>(&rendered)?),
-            Some("toml") => Ok(toml::from_str::<Self>(&rendered)?),
-            Some("yaml" | "yml") => Ok(serde_yaml::from_str::<Self>(&rendered)?),
-            ext => Err(FromFileError::InvalidExtension(ext.map(String::from))),
+        let format = ConfigFileFormat::detect(path.as_ref(), &rendered)?;
+        context.with_tracker(|tracker| {
+            tracker.set_file(path.as_ref().to_owned(), format, rendered.clone())
+        });
+        let mut value = format.parse::<serde_json::Value>(&rendered)?;
+
+        let mut expander = Expander::new(context, expand::local_user());
+        expander.expand(&mut value)?;
+        for warning in expander.into_warnings() {
+            context.add_warning(warning);
         }
+
+        serde_json::from_value(value).map_err(|error| {
+            // Errors from the `Value` do not point to the line and column in the file, so we try
+            // to get the same error straight from the file.
+            format
+                .parse::<Self>(&rendered)
+                .err()
+                .unwrap_or(FromFileError::ParseJson(error))
+        })
     }
 
     /// Extracts just the `key` field from a config file without template rendering.
@@ -912,30 +1255,20 @@ impl LayerFileConfig {
     ///
     /// Returns `None` if the file doesn't contain a `key` field or if parsing fails.
     fn extract_key_from_file(path: &Path) -> Option<String> {
+        /// Just the `key` field, other fields are ignored.
+        #[derive(Deserialize)]
+        struct KeyOnly {
+            key: Option<String>,
+        }
+
         // Read the raw file content
         let content = std::fs::read_to_string(path).ok()?;
 
-        // Try to parse based on extension to extract just the key field
-        let extension = path.extension().and_then(OsStr::to_str);
-
-        match extension {
-            Some("json") | None => serde_json::from_str::<serde_json::Value>(&content)
-                .ok()?
-                .get("key")?
-                .as_str()
-                .map(String::from),
-            Some("toml") => toml::from_str::<toml::Value>(&content)
-                .ok()?
-                .get("key")?
-                .as_str()
-                .map(String::from),
-            Some("yaml" | "yml") => serde_yaml::from_str::<serde_yaml::Value>(&content)
-                .ok()?
-                .get("key")?
-                .as_str()
-                .map(String::from),
-            _ => None,
-        }
+        ConfigFileFormat::detect(path, &content)
+            .ok()?
+            .parse::<KeyOnly>(&content)
+            .ok()?
+            .key
     }
 }
 
@@ -944,8 +1277,10 @@ mod tests {
     use std::{
         fs::{File, OpenOptions},
         io::{Read, Write},
+        ops::Not,
     };
 
+    use mirrord_protocol::tcp::{BodyFilterFallback, HttpBodyFilter, HttpFilter, JqQuery};
     use rstest::*;
     use schemars::schema::RootSchema;
     use tempfile::NamedTempFile;
@@ -953,12 +1288,15 @@ mod tests {
     use super::*;
     use crate::{
         agent::AgentFileConfig,
+        config::provenance::ValueSource,
         feature::{
             FeatureFileConfig,
             fs::{FsModeConfig, FsUserConfig},
             network::{
                 NetworkFileConfig,
-                incoming::{IncomingAdvancedFileConfig, IncomingFileConfig, IncomingMode},
+                incoming::{
+                    IncomingAdvancedFileConfig, IncomingConfig, IncomingFileConfig, IncomingMode,
+                },
                 outgoing::OutgoingFileConfig,
             },
         },
@@ -966,7 +1304,7 @@ mod tests {
         util::ToggleableConfig,
     };
 
-    #[derive(Debug)]
+    #[derive(Clone, Copy, Debug)]
     enum ConfigType {
         Json,
         Toml,
@@ -1188,6 +1526,8 @@ mod tests {
                     container: None,
                 })),
                 namespace: Some("default".to_owned()),
+                pod_selection: Default::default(),
+                create_job: None,
             }),
             skip_processes: None,
             skip_extra_build_tools: None,
@@ -1225,12 +1565,16 @@ mod tests {
                             http_filter: None,
                             port_mapping: None,
                             ignore_localhost: None,
+                            ignore_health_checks: None,
                             ignore_ports: None,
                             listen_ports: None,
                             on_concurrent_steal: None,
                             ports: None,
                             https_delivery: Default::default(),
                             tls_delivery: Default::default(),
+                            https: None,
+                            all_replicas: None,
+                            max_replicas: None,
                         }),
                     ))),
                     outgoing: Some(ToggleableConfig::Config(OutgoingFileConfig {
@@ -1246,6 +1590,7 @@ mod tests {
                 db_branches: None,
                 magic: None,
                 preview: None,
+                target_logs: None,
             }),
             container: None,
             operator: None,
@@ -1266,6 +1611,43 @@ mod tests {
         assert_eq!(config, expect);
     }
 
+    /// The same config resolves identically from a file in each format, also when the format is
+    /// detected from the content.
+    #[rstest]
+    fn from_path_resolves_identically(
+        #[values(ConfigType::Json, ConfigType::Toml, ConfigType::Yaml)] config_type: ConfigType,
+        #[values(true, false)] with_extension: bool,
+    ) {
+        let resolve = |config_type: ConfigType, with_extension: bool| {
+            let suffix = match (config_type, with_extension) {
+                (_, false) => "",
+                (ConfigType::Json, true) => ".json",
+                (ConfigType::Toml, true) => ".toml",
+                (ConfigType::Yaml, true) => ".yaml",
+            };
+            let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+            file.write_all(config_type.full().as_bytes()).unwrap();
+
+            let mut ctx = ConfigContext::default()
+                .override_env(env_key::MIRRORD_ENV_KEY, "test-key")
+                .strict_env(true);
+            let mut config = LayerFileConfig::from_path(file.path(), &mut ctx)
+                .unwrap()
+                .generate_config(&mut ctx)
+                .unwrap();
+
+            // The default log destinations are randomized.
+            config.internal_proxy.log_destination.path = Default::default();
+            config.external_proxy.log_destination.path = Default::default();
+            config
+        };
+
+        assert_eq!(
+            resolve(config_type, with_extension),
+            resolve(ConfigType::Json, true)
+        );
+    }
+
     /// <!--${internal}-->
     /// Helper for printing the config schema.
     ///
@@ -1498,4 +1880,556 @@ mod tests {
 
         assert_eq!(pod_target.pod, "test-my-session");
     }
+
+    #[rstest]
+    #[case::valid_nested(
+        r#"{ "all_of": [{ "method": "post" }, { "any_of": [{ "path": "^/a" }, { "path": "^/b" }] }] }"#,
+        None
+    )]
+    #[case::legacy_and_composite(
+        r#"{ "method_filter": "post", "all_of": [{ "path": "^/a" }] }"#,
+        Some("found `method_filter`, `all_of`")
+    )]
+    #[case::empty(r#"{ "any_of": [] }"#, Some("`any_of` cannot be empty"))]
+    #[case::empty_nested(
+        r#"{ "any_of": [{ "all_of": [] }] }"#,
+        Some("Nested 'any_of' or 'all_of' HTTP filter in `any_of` cannot be empty")
+    )]
+    #[case::methods_with_path(r#"{ "methods": ["post", "PURGE"], "path_filter": "^/a" }"#, None)]
+    #[case::invalid_method(
+        r#"{ "methods": ["POST", "GET /"] }"#,
+        Some("`GET /` is not a valid HTTP method")
+    )]
+    #[case::empty_methods(r#"{ "methods": [] }"#, Some("`methods` cannot be empty"))]
+    #[case::not_in_any_of(
+        r#"{ "any_of": [{ "not": { "header": "^x-probe: true$" } }, { "not": { "all_of": [{ "path": "^/a" }] } }] }"#,
+        None
+    )]
+    #[case::not_with_methods(
+        r#"{ "not": { "header": "^x-probe: true$" }, "methods": "post" }"#,
+        None
+    )]
+    #[case::double_negation(
+        r#"{ "not": { "not": { "path": "^/a" } } }"#,
+        Some("Double negation in `not` HTTP filter")
+    )]
+    #[case::nested_double_negation(
+        r#"{ "all_of": [{ "path": "^/a" }, { "not": { "not": { "path": "^/b" } } }] }"#,
+        Some("Double negation in `all_of` HTTP filter")
+    )]
+    #[case::nested(
+        r#"{ "all_of": [{ "path": "^/api" }, { "any_of": [{ "header": "^x-user: foo$" }, { "all_of": [{ "header": "^x-team: foo$" }, { "not": { "path": "^/api/admin" } }] }] }, { "not": { "method": "OPTIONS" } }] }"#,
+        None
+    )]
+    #[case::too_deep(
+        r#"{ "all_of": [{ "any_of": [{ "all_of": [{ "any_of": [{ "all_of": [{ "not": { "any_of": [{ "path": "^/a" }] } }] }] }] }] }] }"#,
+        Some("can only be nested 4 levels deep")
+    )]
+    #[case::body_query(r#"{ "body_filter": ".tenant_id == \"my-team\" # tenant" }"#, None)]
+    #[case::invalid_body_query(
+        r#"{ "body_filter": ".tenant_id ==" }"#,
+        Some("jq filter could not be parsed")
+    )]
+    #[case::nested_body_query(
+        r#"{ "all_of": [{ "path": "^/api" }, { "not": ".tenant_id == \"my-team\"" }] }"#,
+        None
+    )]
+    #[case::nested_invalid_body_query(
+        r#"{ "any_of": [{ "path": "^/api" }, ".tenant_id =="] }"#,
+        Some("jq filter could not be parsed")
+    )]
+    #[case::grpc(
+        r#"{ "body_filter": { "body": "jq", "query": ".body.id == 1" }, "grpc": { "descriptor_set": "CgA=", "messages": { "/acme.Users/": "acme.GetUserRequest" } } }"#,
+        None
+    )]
+    #[case::grpc_without_jq(
+        r#"{ "path_filter": "^/acme", "grpc": { "descriptor_set_path": "/protos.pb", "messages": { "/": "acme.Request" } } }"#,
+        Some("`grpc` only decodes bodies for jq body filters")
+    )]
+    #[case::grpc_both_descriptor_sets(
+        r#"{ "all_of": [{ "body": "jq", "query": ".body.id == 1" }], "grpc": { "descriptor_set": "CgA=", "descriptor_set_path": "/protos.pb", "messages": { "/": "acme.Request" } } }"#,
+        Some("exactly one of `grpc.descriptor_set` and `grpc.descriptor_set_path`")
+    )]
+    #[case::grpc_invalid_base64(
+        r#"{ "body_filter": { "body": "jq", "query": ".body.id == 1" }, "grpc": { "descriptor_set": "not base64!", "messages": { "/": "acme.Request" } } }"#,
+        Some("not valid base64")
+    )]
+    fn verify_http_filter(#[case] http_filter: &str, #[case] expected_error: Option<&str>) {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(
+            temp_file,
+            r#"{{
+  "target": "pod/test",
+  "feature": {{ "network": {{ "incoming": {{ "mode": "steal", "http_filter": {http_filter} }} }} }}
+}}"#
+        )
+        .unwrap();
+
+        let mut ctx = ConfigContext::default().strict_env(true);
+        let config = LayerFileConfig::from_path(temp_file.path(), &mut ctx)
+            .unwrap()
+            .generate_config(&mut ctx)
+            .unwrap();
+
+        match (config.verify(&mut ctx), expected_error) {
+            (Ok(()), None) => {}
+            (Err(ConfigError::Conflict(error)), Some(expected)) => {
+                assert!(error.contains(expected), "unexpected error: {error}")
+            }
+            (Err(ConfigError::InvalidValue { error, .. }), Some(expected)) => {
+                assert!(
+                    error.to_string().contains(expected),
+                    "unexpected error: {error}"
+                )
+            }
+            (result, expected) => panic!("expected {expected:?}, got {result:?}"),
+        }
+    }
+
+    /// The jq expression in `body_filter` is evaluated against the body, also when it is nested.
+    #[test]
+    fn body_filter_query() {
+        let parse = |config: &str| {
+            serde_json::from_str::<feature::network::incoming::http_filter::HttpFilterFileConfig>(
+                config,
+            )
+            .unwrap()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap()
+            .as_protocol_http_filter()
+            .unwrap()
+        };
+        let body_query = HttpFilter::Body(HttpBodyFilter::Jq {
+            query: JqQuery::new(".body | (\n.tenant_id == \"my-team\"\n)").unwrap(),
+            fallback: BodyFilterFallback::NoMatch,
+        });
+
+        assert_eq!(
+            parse(r#"{ "body_filter": ".tenant_id == \"my-team\"" }"#),
+            body_query
+        );
+        assert_eq!(
+            parse(r#"{ "any_of": [{ "path": "^/api" }, ".tenant_id == \"my-team\""] }"#),
+            HttpFilter::Composite {
+                all: false,
+                filters: vec![
+                    HttpFilter::Path(mirrord_protocol::tcp::Filter::new("^/api".into()).unwrap()),
+                    body_query.clone(),
+                ],
+            }
+        );
+        assert_eq!(
+            parse(r#"{ "not": ".tenant_id == \"my-team\"" }"#),
+            HttpFilter::Not(Box::new(body_query))
+        );
+    }
+
+    /// The `evaluate` fallback is only sent to agents that can decode it.
+    #[rstest]
+    #[case::top_level(
+        r#"{ "body_filter": { "body": "jq", "query": ".body", "fallback": "evaluate" } }"#
+    )]
+    #[case::nested(
+        r#"{ "any_of": [{ "path": "^/api" }, { "body": "jq", "query": ".body", "fallback": "evaluate" }] }"#
+    )]
+    fn body_filter_evaluate_version(#[case] config: &str) {
+        let config = serde_json::from_str::<
+            feature::network::incoming::http_filter::HttpFilterFileConfig,
+        >(config)
+        .unwrap()
+        .generate_config(&mut ConfigContext::default())
+        .unwrap();
+
+        assert!(
+            config
+                .ensure_usable_with(Some("1.38.0".parse().unwrap()))
+                .is_err()
+        );
+        assert!(config.ensure_usable_with(None).is_err());
+        config
+            .ensure_usable_with(Some("1.39.0".parse().unwrap()))
+            .unwrap();
+    }
+
+    #[rstest]
+    #[case::target_container(r#""pod/test""#, r#"{ "container": "sidecar" }"#, None)]
+    #[case::targetless(r#""targetless""#, r#"{ "container": "sidecar" }"#, Some("targetless"))]
+    #[case::load_from_process(
+        r#""pod/test""#,
+        r#"{ "container": "sidecar", "load_from_process": true }"#,
+        Some("load_from_process")
+    )]
+    fn verify_env_container(
+        #[case] target: &str,
+        #[case] env: &str,
+        #[case] expected_error: Option<&str>,
+    ) {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(
+            temp_file,
+            r#"{{ "target": {target}, "feature": {{ "env": {env} }} }}"#
+        )
+        .unwrap();
+
+        let mut ctx = ConfigContext::default().strict_env(true);
+        let config = LayerFileConfig::from_path(temp_file.path(), &mut ctx)
+            .unwrap()
+            .generate_config(&mut ctx)
+            .unwrap();
+
+        match (config.verify(&mut ctx), expected_error) {
+            (Ok(()), None) => {}
+            (Err(ConfigError::Conflict(error)), Some(expected)) => {
+                assert!(error.contains(expected), "unexpected error: {error}")
+            }
+            (result, expected) => panic!("expected {expected:?}, got {result:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case::job(r#""job/test""#, "{}", None)]
+    #[case::cron_job(r#""cronjob/test""#, "{}", None)]
+    #[case::create_job(r#"{ "path": "cronjob/test", "create_job": true }"#, "{}", None)]
+    #[case::create_job_not_cron_job(
+        r#"{ "path": "job/test", "create_job": true }"#,
+        "{}",
+        Some("can only be used with a cron job target")
+    )]
+    #[case::create_job_copy_target(
+        r#"{ "path": "cronjob/test", "create_job": true }"#,
+        r#"{ "copy_target": true, "network": { "incoming": "steal" } }"#,
+        Some("not compatible with the copy target feature")
+    )]
+    fn verify_job_targets(
+        #[case] target: &str,
+        #[case] feature: &str,
+        #[case] expected_error: Option<&str>,
+    ) {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(
+            temp_file,
+            r#"{{ "target": {target}, "feature": {feature} }}"#
+        )
+        .unwrap();
+
+        let mut ctx = ConfigContext::default().strict_env(true);
+        let config = LayerFileConfig::from_path(temp_file.path(), &mut ctx)
+            .unwrap()
+            .generate_config(&mut ctx)
+            .unwrap();
+
+        match (config.verify(&mut ctx), expected_error) {
+            (Ok(()), None) => {}
+            (Err(ConfigError::Conflict(error)), Some(expected)) => {
+                assert!(error.contains(expected), "unexpected error: {error}")
+            }
+            (result, expected) => panic!("expected {expected:?}, got {result:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case::mirror(r#""deployment/test""#, r#""mode": "mirror""#, None)]
+    #[case::steal(
+        r#""deployment/test""#,
+        r#""mode": "steal""#,
+        Some("can only be used with the mirror mode")
+    )]
+    #[case::steal_port(
+        r#""rollout/test""#,
+        r#""ports": [8080, { "port": 9090, "mode": "steal" }]"#,
+        Some("can only be used with the mirror mode")
+    )]
+    fn verify_all_replicas(
+        #[case] target: &str,
+        #[case] incoming: &str,
+        #[case] expected_error: Option<&str>,
+    ) {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(
+            temp_file,
+            r#"{{
+  "target": {target},
+  "feature": {{ "network": {{ "incoming": {{ "all_replicas": true, {incoming} }} }} }}
+}}"#
+        )
+        .unwrap();
+
+        let mut ctx = ConfigContext::default().strict_env(true);
+        let config = LayerFileConfig::from_path(temp_file.path(), &mut ctx)
+            .unwrap()
+            .generate_config(&mut ctx)
+            .unwrap();
+
+        match (config.verify(&mut ctx), expected_error) {
+            (Ok(()), None) => {
+                assert!(config.feature.network.incoming.all_replicas);
+                assert_eq!(
+                    config.feature.network.incoming.max_replicas(),
+                    IncomingConfig::DEFAULT_MAX_REPLICAS
+                );
+            }
+            (Err(ConfigError::Conflict(error)), Some(expected)) => {
+                assert!(error.contains(expected), "unexpected error: {error}")
+            }
+            (result, expected) => panic!("expected {expected:?}, got {result:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case::distinct(r#"[[9333, 80], [9444, 443]]"#, None)]
+    #[case::repeated(r#"[[9333, 80], [9333, 80]]"#, None)]
+    #[case::same_local(
+        r#"[[8080, 80], [8080, 443]]"#,
+        Some("Entries `[8080, 80]` and `[8080, 443]` of `incoming.port_mapping` conflict")
+    )]
+    #[case::same_remote(
+        r#"[[8080, 80], [9090, 80]]"#,
+        Some("Entries `[8080, 80]` and `[9090, 80]` of `incoming.port_mapping` conflict")
+    )]
+    fn port_mapping_conflicts(#[case] port_mapping: &str, #[case] expected_error: Option<&str>) {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(
+            temp_file,
+            r#"{{
+  "feature": {{ "network": {{ "incoming": {{
+    "mode": "steal",
+    "port_mapping": {port_mapping},
+    "listen_ports": [[9333, 4433]]
+  }} }} }}
+}}"#
+        )
+        .unwrap();
+
+        let mut ctx = ConfigContext::default().strict_env(true);
+        let result = LayerFileConfig::from_path(temp_file.path(), &mut ctx)
+            .unwrap()
+            .generate_config(&mut ctx);
+
+        match (result, expected_error) {
+            (Ok(config), None) => {
+                let incoming = &config.feature.network.incoming;
+                assert_eq!(incoming.port_mapping.get_by_left(&9333), Some(&80));
+                assert_eq!(incoming.listen_ports.get_by_left(&9333), Some(&4433));
+            }
+            (Err(ConfigError::Conflict(error)), Some(expected)) => {
+                assert!(error.contains(expected), "unexpected error: {error}")
+            }
+            (result, expected) => panic!("expected {expected:?}, got {result:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case::plain(r#"[8080, 9090, 8080]"#, None)]
+    #[case::per_port(
+        r#"[{ "port": 8080, "http_filter": { "path_filter": "^/webhooks/" } }, 9090]"#,
+        None
+    )]
+    #[case::empty_per_port(r#"[{ "port": 9090, "http_filter": {} }]"#, None)]
+    #[case::duplicate_with_filter(
+        r#"[8080, { "port": 8080, "http_filter": { "path_filter": "^/webhooks/" } }]"#,
+        Some("Port `8080` is listed more than once")
+    )]
+    #[case::per_port_modes(
+        r#"[8080, { "port": 9090, "mode": "mirror" }, { "port": 5432, "mode": "off" }]"#,
+        None
+    )]
+    #[case::duplicate_with_mode(
+        r#"[{ "port": 9090, "mode": "mirror" }, 9090]"#,
+        Some("Port `9090` is listed more than once")
+    )]
+    #[case::filter_on_off_port(
+        r#"[{ "port": 5432, "mode": "off", "http_filter": { "path_filter": "^/a" } }]"#,
+        Some("Cannot set an HTTP filter on port `5432`")
+    )]
+    #[case::ports_in_per_port(
+        r#"[{ "port": 8080, "http_filter": { "path_filter": "^/a", "ports": [8080] } }]"#,
+        Some("Cannot use `ports` in the HTTP filter of port `8080`")
+    )]
+    #[case::dry_run_in_per_port(
+        r#"[{ "port": 8080, "http_filter": { "path_filter": "^/a", "dry_run": true } }]"#,
+        Some("Cannot use `dry_run` in the HTTP filter of port `8080`")
+    )]
+    #[case::invalid_per_port(
+        r#"[{ "port": 8080, "http_filter": { "path_filter": "^/a", "header_filter": "^b" } }]"#,
+        Some("found `header_filter`, `path_filter`")
+    )]
+    fn verify_port_http_filters(#[case] ports: &str, #[case] expected_error: Option<&str>) {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        write!(
+            temp_file,
+            r#"{{
+  "target": "pod/test",
+  "feature": {{ "network": {{ "incoming": {{
+    "mode": "steal",
+    "http_filter": {{ "header_filter": "^x-user: me$" }},
+    "ports": {ports}
+  }} }} }}
+}}"#
+        )
+        .unwrap();
+
+        let mut ctx = ConfigContext::default().strict_env(true);
+        let result = LayerFileConfig::from_path(temp_file.path(), &mut ctx)
+            .unwrap()
+            .generate_config(&mut ctx)
+            .and_then(|config| config.verify(&mut ctx).map(|()| config));
+
+        match (result, expected_error) {
+            (Ok(config), None) => {
+                let incoming = &config.feature.network.incoming;
+                for (port, http_filter) in &incoming.port_http_filters {
+                    assert!(incoming.ports.as_ref().unwrap().contains(port));
+                    assert_eq!(
+                        incoming.steals_port_without_filter(*port),
+                        http_filter.is_filter_set().not()
+                    );
+                }
+                for (port, mode) in &incoming.port_modes {
+                    assert!(incoming.ports.as_ref().unwrap().contains(port));
+                    assert_eq!(incoming.port_mode(*port), *mode);
+                    assert!(incoming.steals_port_without_filter(*port).not());
+                }
+            }
+            (Err(ConfigError::Conflict(error)), Some(expected)) => {
+                assert!(error.contains(expected), "unexpected error: {error}")
+            }
+            (result, expected) => panic!("expected {expected:?}, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn resolve_with_sources_reports_winner() {
+        let mut temp_file = tempfile::Builder::new().suffix(".yaml").tempfile().unwrap();
+        temp_file
+            .write_all(
+                br#"agent:
+  startup_timeout: 120
+  ttl: 30
+feature:
+  network:
+    incoming:
+      mode: steal
+  fs:
+    mode: read
+"#,
+            )
+            .unwrap();
+
+        let mut ctx = ConfigContext::default()
+            .override_env(LayerConfig::FILE_PATH_ENV, temp_file.path())
+            .override_env("MIRRORD_AGENT_STARTUP_TIMEOUT", "15")
+            .strict_env(true);
+        let (config, sources) = LayerConfig::resolve_with_sources(&mut ctx).unwrap();
+        let file = |line| ValueSource::File {
+            path: temp_file.path().to_path_buf(),
+            line: Some(line),
+        };
+
+        assert_eq!(config.agent.startup_timeout, 15);
+        assert_eq!(
+            sources.field("agent.startup_timeout"),
+            Some(&ValueSource::Env("MIRRORD_AGENT_STARTUP_TIMEOUT"))
+        );
+        assert_eq!(config.agent.ttl, 30);
+        assert_eq!(sources.field("agent.ttl"), Some(&file(3)));
+        assert_eq!(
+            sources.field("feature.network.incoming.mode"),
+            Some(&file(7))
+        );
+        assert_eq!(sources.source_of("feature.fs.mode"), &file(9));
+        assert_eq!(
+            sources.field("agent.namespace"),
+            Some(&ValueSource::Default)
+        );
+        assert_eq!(
+            sources.source_of("feature.copy_target.scale_down"),
+            &ValueSource::Default
+        );
+        assert!(ctx.take_sources().is_none());
+    }
+
+    #[test]
+    fn resolve_expands_variables() {
+        let mut temp_file = tempfile::Builder::new().suffix(".yaml").tempfile().unwrap();
+        temp_file
+            .write_all(
+                br#"
+target:
+  path: "deployment/${env:SERVICE}"
+  namespace: "${env:TEAM_NAMESPACE:-default}"
+feature:
+  env:
+    override:
+      GREETING: "$${not expanded}"
+"#,
+            )
+            .unwrap();
+
+        let mut ctx = ConfigContext::default()
+            .override_env(LayerConfig::FILE_PATH_ENV, temp_file.path())
+            .override_env("SERVICE", "checkout")
+            .strict_env(true);
+        let config = LayerConfig::resolve(&mut ctx).unwrap();
+
+        assert_eq!(
+            config.target.path.map(|target| target.to_string()),
+            Some("deployment/checkout".to_owned())
+        );
+        assert_eq!(config.target.namespace.as_deref(), Some("default"));
+        assert_eq!(
+            config.feature.env.r#override.unwrap().get("GREETING"),
+            Some(&"${not expanded}".to_owned())
+        );
+
+        let mut ctx = ConfigContext::default()
+            .override_env(LayerConfig::FILE_PATH_ENV, temp_file.path())
+            .override_env(expand::STRICT_VARS_ENV, "true")
+            .strict_env(true);
+        let error = LayerConfig::resolve(&mut ctx).unwrap_err().to_string();
+        assert!(error.contains("${env:SERVICE}"), "{error}");
+        assert!(error.contains("target.path"), "{error}");
+    }
+
+    #[test]
+    fn set_overrides_win() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file
+            .write_all(br#"{ "agent": { "namespace": "from-file", "ttl": 30 } }"#)
+            .unwrap();
+
+        let mut ctx = ConfigContext::default()
+            .override_env(LayerConfig::FILE_PATH_ENV, temp_file.path())
+            .override_env("MIRRORD_AGENT_NAMESPACE", "from-env")
+            .override_env(
+                LayerConfig::SET_ENV,
+                "agent.namespace=from-set\nagent.ttl=5\nfeature.env.override.A=one",
+            )
+            .strict_env(true);
+        let (config, sources) = LayerConfig::resolve_with_sources(&mut ctx).unwrap();
+
+        assert_eq!(config.agent.namespace.as_deref(), Some("from-set"));
+        assert_eq!(config.agent.ttl, 5);
+        assert_eq!(sources.field("agent.namespace"), Some(&ValueSource::Set));
+        assert_eq!(
+            config.feature.env.r#override.unwrap().get("A"),
+            Some(&"one".to_owned())
+        );
+
+        for invalid in [
+            "agent.namespaces=typo",
+            "agent.ttl.value=5",
+            "agent.ttl=five",
+        ] {
+            let mut ctx = ConfigContext::default()
+                .override_env(LayerConfig::SET_ENV, invalid)
+                .strict_env(true);
+            assert!(
+                matches!(
+                    LayerConfig::resolve(&mut ctx),
+                    Err(ConfigError::InvalidValue { name: "--set", .. })
+                ),
+                "{invalid}"
+            );
+        }
+    }
 }
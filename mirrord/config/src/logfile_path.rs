@@ -4,11 +4,15 @@ use std::{
     hash::{Hash, Hasher},
     io,
     marker::PhantomData,
-    ops::Deref,
+    ops::{Deref, Not},
     path::{Path, PathBuf},
     time::SystemTime,
 };
 
+use chrono::{
+    DateTime, Local,
+    format::{Item, StrftimeItems},
+};
 use rand::distr::{Alphanumeric, SampleString};
 use schemars::{JsonSchema, SchemaGenerator, schema::Schema};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -58,6 +62,9 @@ impl LogSource for Extproxy {
 /// 2. Otherwise, if the path exists, check if it's a directory or not.
 /// 3. Otherwise, assume it points to a file.
 ///
+/// Before that, strftime-style placeholders (e.g. `%Y-%m-%d`) in the path are replaced with the
+/// current local time.
+///
 /// During generation ([`MirrordConfig::generate_config`]), the provided value is always resolved to
 /// the final file.
 ///
@@ -127,10 +134,33 @@ impl<S> JsonSchema for LogDestinationConfig<S> {
     }
 }
 
+/// Replaces strftime-style placeholders in the given path with the given time.
+///
+/// Paths without any `%` are returned as they are.
+fn expand_placeholders(path: PathBuf, now: DateTime<Local>) -> Result<PathBuf, ConfigError> {
+    let raw = path.to_string_lossy();
+    if raw.contains('%').not() {
+        return Ok(path);
+    }
+
+    let items = StrftimeItems::new(&raw).collect::<Vec<_>>();
+    if items.contains(&Item::Error) {
+        return Err(ConfigError::InvalidValue {
+            name: "log_destination",
+            provided: raw.into_owned(),
+            error: "contains an invalid strftime placeholder".into(),
+        });
+    }
+
+    Ok(now.format_with_items(items.into_iter()).to_string().into())
+}
+
 impl<S: LogSource> MirrordConfig for LogDestinationConfig<S> {
     type Generated = LogDestinationConfig<S>;
 
     fn generate_config(mut self, _: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
+        self.path = expand_placeholders(self.path, Local::now())?;
+
         let exists = match self.path.try_exists() {
             Ok(exists) => exists,
             Err(error) => {
@@ -208,6 +238,7 @@ impl<S> AsRef<Path> for LogDestinationConfig<S> {
 
 #[cfg(all(test, any(target_os = "linux", target_os = "macos")))]
 mod test {
+    use chrono::{Local, TimeZone};
     use mirrord_config_derive::MirrordConfig;
     use rstest::rstest;
     use schemars::JsonSchema;
@@ -216,7 +247,7 @@ mod test {
 
     use crate::{
         config::MirrordConfig,
-        logfile_path::{LogDestinationConfig, LogSource},
+        logfile_path::{LogDestinationConfig, LogSource, expand_placeholders},
     };
 
     struct TestSource;
@@ -304,4 +335,22 @@ mod test {
             (Err(..), None) => {}
         }
     }
+
+    #[rstest]
+    #[case::no_placeholders("/tmp/mirrord/intproxy.log", Some("/tmp/mirrord/intproxy.log"))]
+    #[case::date_dir(
+        "/tmp/mirrord/%Y-%m-%d/intproxy-%H%M%S.log",
+        Some("/tmp/mirrord/2025-03-07/intproxy-091502.log")
+    )]
+    #[case::escaped_percent("/tmp/100%%/intproxy.log", Some("/tmp/100%/intproxy.log"))]
+    #[case::invalid("/tmp/%Q/intproxy.log", None)]
+    fn placeholders_are_expanded(#[case] input: &str, #[case] expected: Option<&str>) {
+        let now = Local.with_ymd_and_hms(2025, 3, 7, 9, 15, 2).unwrap();
+        let expanded = expand_placeholders(input.into(), now);
+
+        assert_eq!(
+            expanded.ok().as_deref().and_then(|path| path.to_str()),
+            expected
+        );
+    }
 }
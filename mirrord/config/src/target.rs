@@ -8,13 +8,15 @@ use serde::{Deserialize, Serialize};
 use strum_macros::{EnumDiscriminants, EnumString};
 
 use self::{
-    deployment::DeploymentTarget, job::JobTarget, pod::PodTarget, rollout::RolloutTarget,
-    service::ServiceTarget, stateful_set::StatefulSetTarget,
+    deployment::DeploymentTarget, job::JobTarget, pod::PodTarget,
+    pod_selection::PodSelectionConfig, rollout::RolloutTarget, service::ServiceTarget,
+    stateful_set::StatefulSetTarget,
 };
 use crate::{
     config::{
         ConfigContext, ConfigError, FromMirrordConfig, MirrordConfig, Result,
         from_env::{FromEnv, FromEnvWithError},
+        provenance::Tracked,
         source::MirrordConfigSource,
     },
     feature::FeatureConfig,
@@ -25,6 +27,7 @@ pub mod cron_job;
 pub mod deployment;
 pub mod job;
 pub mod pod;
+pub mod pod_selection;
 pub mod replica_set;
 pub mod rollout;
 pub mod service;
@@ -49,6 +52,9 @@ pub enum TargetFileConfig {
         #[schemars(schema_with = "make_simple_target_custom_schema")]
         path: Option<Target>,
         namespace: Option<String>,
+        #[serde(default)]
+        pod_selection: PodSelectionConfig,
+        create_job: Option<bool>,
     },
 }
 
@@ -82,13 +88,18 @@ fn make_simple_target_custom_schema(r#gen: &mut SchemaGenerator) -> schemars::sc
 /// - `rollout/{rollout-name}[/container/{container-name}]`;
 /// - `job/{job-name}[/container/{container-name}]`;
 /// - `cronjob/{cronjob-name}[/container/{container-name}]`;
-/// - `statefulset/{statefulset-name}[/container/{container-name}]`;
+/// - `statefulset/{statefulset-name}[/pod/{ordinal}][/container/{container-name}]`;
 /// - `service/{service-name}[/container/{container-name}]`;
 ///
 /// Please note that:
 ///
-/// - `job`, `cronjob`, `statefulset` and `service` targets require the mirrord Operator
-/// - `job` and `cronjob` targets require the [`copy_target`](#feature-copy_target) feature
+/// - `service` targets require the mirrord Operator
+/// - `statefulset` targets attach to the pod with the given ordinal, or to the one picked with
+///   [`target.pod_selection.ordinal`](#target-pod_selection-ordinal)
+/// - `job` targets wait for a pod of the job to start, up to
+///   [`agent.startup_timeout`](#agent-startup_timeout)
+/// - `cronjob` targets attach to a pod of the most recent active job, or to a new job created from
+///   the job template (see [`target.create_job`](#target-create_job))
 ///
 /// Shortened setup with a target:
 ///
@@ -152,20 +163,17 @@ pub struct TargetConfig {
     /// If not given, defaults to `targetless`.
     ///
     /// Note: targeting services and whole workloads is available only in mirrord for Teams.
-    /// If you target a workload without the mirrord Operator, it will choose a random pod replica
-    /// to work with.
+    /// If you target a workload without the mirrord Operator, it will choose one pod replica
+    /// to work with (see [`target.pod_selection`](#target-pod_selection)).
     ///
     /// Supports:
     /// - `targetless`
     /// - `pod/{pod-name}[/container/{container-name}]`;
     /// - `deployment/{deployment-name}[/container/{container-name}]`;
     /// - `rollout/{rollout-name}[/container/{container-name}]`;
-    /// - `job/{job-name}[/container/{container-name}]`; (requires mirrord Operator and the
-    ///   [`copy_target`](#feature-copy_target) feature)
-    /// - `cronjob/{cronjob-name}[/container/{container-name}]`; (requires mirrord Operator and the
-    ///   [`copy_target`](#feature-copy_target) feature)
-    /// - `statefulset/{statefulset-name}[/container/{container-name}]`; (requires mirrord
-    ///   Operator)
+    /// - `job/{job-name}[/container/{container-name}]`;
+    /// - `cronjob/{cronjob-name}[/container/{container-name}]`;
+    /// - `statefulset/{statefulset-name}[/pod/{ordinal}][/container/{container-name}]`;
     /// - `service/{service-name}[/container/{container-name}]`; (requires mirrord Operator)
     /// - `replicaset/{replicaset-name}[/container/{container-name}]`; (requires mirrord Operator)
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -180,6 +188,27 @@ pub struct TargetConfig {
     /// Defaults to the Kubernetes user's default namespace (defined in Kubernetes context).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
+
+    /// ### target.pod_selection {#target-pod_selection}
+    ///
+    /// See [`PodSelectionConfig`].
+    #[serde(default)]
+    pub pod_selection: PodSelectionConfig,
+
+    /// ### target.create_job {#target-create_job}
+    ///
+    /// When targeting a cron job, create a new job from its job template and target that,
+    /// instead of attaching to the most recent active job.
+    ///
+    /// The job is labeled so that it gets cleaned up after the session, and it is owned by the
+    /// cron job. Useful for debugging a cron job that is not running at the moment.
+    ///
+    /// Can only be used with a `cronjob` target, and is not compatible with
+    /// [`copy_target`](#feature-copy_target).
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub create_job: bool,
 }
 
 impl Default for TargetFileConfig {
@@ -193,19 +222,43 @@ impl FromMirrordConfig for TargetConfig {
 }
 
 impl TargetFileConfig {
-    /// Get the target path from the env var, `Ok(None)` if not set, `Err` if invalid value.
-    fn get_target_path_from_env(context: &mut ConfigContext) -> Result<Option<Target>> {
+    /// Get the target path from the env var, falling back to the config file. `Err` if invalid
+    /// value.
+    fn get_target_path(
+        context: &mut ConfigContext,
+        from_file: Option<Target>,
+    ) -> Result<Option<Target>> {
         FromEnvWithError::new("MIRRORD_IMPERSONATED_TARGET")
+            .or(from_file)
+            .layer(|inner| Tracked::new("path", inner))
             .source_value(context)
             .transpose()
     }
 
-    /// Get the target namespace from the env var, `Ok(None)` if not set, `Err` if invalid value.
-    fn get_target_namespace_from_env(context: &mut ConfigContext) -> Result<Option<String>> {
+    /// Get the target namespace from the env var, falling back to the config file. `Err` if
+    /// invalid value.
+    fn get_target_namespace(
+        context: &mut ConfigContext,
+        from_file: Option<String>,
+    ) -> Result<Option<String>> {
         FromEnv::new("MIRRORD_TARGET_NAMESPACE")
+            .or(from_file)
+            .layer(|inner| Tracked::new("namespace", inner))
             .source_value(context)
             .transpose()
     }
+
+    /// Get the `create_job` flag from the env var, falling back to the config file. `Err` if
+    /// invalid value.
+    fn get_create_job(context: &mut ConfigContext, from_file: Option<bool>) -> Result<bool> {
+        let create_job = FromEnv::new("MIRRORD_TARGET_CREATE_JOB")
+            .or(from_file)
+            .layer(|inner| Tracked::new("create_job", inner))
+            .source_value(context)
+            .transpose()?;
+
+        Ok(create_job.unwrap_or_default())
+    }
 }
 
 impl MirrordConfig for TargetFileConfig {
@@ -214,15 +267,27 @@ impl MirrordConfig for TargetFileConfig {
     /// Generate the final config object, out of the configuration parsed from a configuration file,
     /// factoring in environment variables (which are also set by the front end - CLI/IDE-plugin).
     fn generate_config(self, context: &mut ConfigContext) -> Result<Self::Generated> {
-        let (path_from_conf_file, namespace_from_conf_file) = match self {
-            TargetFileConfig::Simple(path) => (path, None),
-            TargetFileConfig::Advanced { path, namespace } => (path, namespace),
+        let (path_from_conf_file, namespace_from_conf_file, pod_selection, create_job) = match self
+        {
+            TargetFileConfig::Simple(path) => (path, None, Default::default(), None),
+            TargetFileConfig::Advanced {
+                path,
+                namespace,
+                pod_selection,
+                create_job,
+            } => (path, namespace, pod_selection, create_job),
         };
 
         // Env overrides configuration if both there.
-        let path = Self::get_target_path_from_env(context)?.or(path_from_conf_file);
-        let namespace = Self::get_target_namespace_from_env(context)?.or(namespace_from_conf_file);
-        Ok(TargetConfig { path, namespace })
+        let path = Self::get_target_path(context, path_from_conf_file)?;
+        let namespace = Self::get_target_namespace(context, namespace_from_conf_file)?;
+        let create_job = Self::get_create_job(context, create_job)?;
+        Ok(TargetConfig {
+            path,
+            namespace,
+            pod_selection,
+            create_job,
+        })
     }
 }
 
@@ -242,13 +307,13 @@ mirrord-layer failed to parse the provided target!
     >> `rollout/{rollout-name}[/container/{container-name}]`;
     >> `job/{job-name}[/container/{container-name}]`;
     >> `cronjob/{cronjob-name}[/container/{container-name}]`;
-    >> `statefulset/{statefulset-name}[/container/{container-name}]`;
+    >> `statefulset/{statefulset-name}[/pod/{ordinal}][/container/{container-name}]`;
     >> `service/{service-name}[/container/{container-name}]`;
     >> `replicaset/{replicaset-name}[/container/{container-name}]`;
 
 - Note:
     >> specifying container name is optional, defaults to a container chosen by mirrord
-    >> targeting a workload without the mirrord Operator results in a session targeting a random pod replica
+    >> targeting a workload without the mirrord Operator results in a session targeting a single pod replica, chosen with `target.pod_selection`
 
 - Suggestions:
     >> check for typos in the provided target.
@@ -268,7 +333,7 @@ mirrord-layer failed to parse the provided target!
 /// - `rollout/{rollout-name}[/container/{container-name}]`;
 /// - `job/{job-name}[/container/{container-name}]`;
 /// - `cronjob/{cronjob-name}[/container/{container-name}]`;
-/// - `statefulset/{statefulset-name}[/container/{container-name}]`;
+/// - `statefulset/{statefulset-name}[/pod/{ordinal}][/container/{container-name}]`;
 /// - `service/{service-name}[/container/{container-name}]`;
 /// - `replicaset/{replicaset-name}[/container/{container-name}]`;
 ///
@@ -295,14 +360,10 @@ pub enum Target {
 
     /// <!--${internal}-->
     /// [Job](https://kubernetes.io/docs/concepts/workloads/controllers/job/).
-    ///
-    /// Only supported when `copy_target` is enabled.
     Job(job::JobTarget),
 
     /// <!--${internal}-->
     /// [CronJob](https://kubernetes.io/docs/concepts/workloads/controllers/cron-jobs/).
-    ///
-    /// Only supported when `copy_target` is enabled.
     CronJob(cron_job::CronJobTarget),
 
     /// <!--${internal}-->
@@ -380,11 +441,6 @@ impl FromStr for Target {
 }
 
 impl Target {
-    /// `true` if this [`Target`] is only supported when the copy target feature is enabled.
-    pub(super) fn requires_copy(&self) -> bool {
-        matches!(self, Target::Job(_) | Target::CronJob(_))
-    }
-
     /// Set the container on this target. No-op for [`Target::Targetless`].
     pub fn set_container(&mut self, container: String) {
         match self {
@@ -402,14 +458,7 @@ impl Target {
 
     /// `true` if this [`Target`] is only supported when the operator is enabled.
     pub fn requires_operator(&self) -> bool {
-        matches!(
-            self,
-            Target::Job(_)
-                | Target::CronJob(_)
-                | Target::StatefulSet(_)
-                | Target::Service(_)
-                | Target::ReplicaSet(_)
-        )
+        matches!(self, Target::Service(_) | Target::ReplicaSet(_))
     }
 }
 
@@ -451,9 +500,10 @@ impl TargetType {
         match self {
             Self::Targetless | Self::Rollout => !config.copy_target.enabled,
             Self::Pod => !(config.copy_target.enabled && config.copy_target.scale_down),
-            Self::Job | Self::CronJob => config.copy_target.enabled,
             Self::Service => !config.copy_target.enabled,
-            Self::Deployment | Self::StatefulSet | Self::ReplicaSet => true,
+            Self::Deployment | Self::StatefulSet | Self::ReplicaSet | Self::Job | Self::CronJob => {
+                true
+            }
         }
     }
 }
@@ -512,10 +562,40 @@ impl_target_display!(DeploymentTarget, deployment, "deployment");
 impl_target_display!(RolloutTarget, rollout, "rollout");
 impl_target_display!(JobTarget, job, "job");
 impl_target_display!(CronJobTarget, cron_job, "cronjob");
-impl_target_display!(StatefulSetTarget, stateful_set, "statefulset");
 impl_target_display!(ServiceTarget, service, "service");
 impl_target_display!(ReplicaSetTarget, replica_set, "replicaset");
 
+impl TargetDisplay for StatefulSetTarget {
+    fn type_(&self) -> &str {
+        "statefulset"
+    }
+
+    fn name(&self) -> &str {
+        self.stateful_set.as_str()
+    }
+
+    fn container(&self) -> Option<&String> {
+        self.container.as_ref()
+    }
+}
+
+/// Like the `impl_target_display` ones, but with the optional `/pod/{ordinal}` segment.
+impl fmt::Display for StatefulSetTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.type_(), self.name())?;
+
+        if let Some(ordinal) = self.ordinal {
+            write!(f, "/pod/{ordinal}")?;
+        }
+
+        if let Some(container) = self.container() {
+            write!(f, "/container/{container}")?;
+        }
+
+        Ok(())
+    }
+}
+
 impl fmt::Display for Target {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -669,7 +749,9 @@ mod tests {
     #[case(None, None,
         TargetConfig {
             path: None,
-            namespace: None
+            namespace: None,
+            pod_selection: Default::default(),
+            create_job: false,
         }
     )] // Nothing specified - no target config (targetless mode).
     #[case(
@@ -677,7 +759,9 @@ mod tests {
         Some("ns"),
         TargetConfig{
             path: None,
-            namespace: Some("ns".to_string())
+            namespace: Some("ns".to_string()),
+            pod_selection: Default::default(),
+            create_job: false,
         }
     )] // Namespace without target - error.
     #[case(
@@ -685,7 +769,9 @@ mod tests {
         None,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "foo".to_string(), container: None})),
-            namespace: None
+            namespace: None,
+            pod_selection: Default::default(),
+            create_job: false,
         }
     )] // Only pod specified
     #[case(
@@ -696,7 +782,9 @@ mod tests {
                 pod: "foo".to_string(),
                 container: Some("bar".to_string())
             })),
-            namespace: None
+            namespace: None,
+            pod_selection: Default::default(),
+            create_job: false,
         }
     )] // Pod and container specified.
     #[case(
@@ -704,7 +792,9 @@ mod tests {
         Some("baz"),
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "foo".to_string(), container: None})),
-            namespace: Some("baz".to_string())
+            namespace: Some("baz".to_string()),
+            pod_selection: Default::default(),
+            create_job: false,
         }
     )] // Pod and namespace specified.
     #[case(
@@ -715,7 +805,9 @@ mod tests {
                 rollout: "foo".to_string(),
                 container: None
             })),
-            namespace: None
+            namespace: None,
+            pod_selection: Default::default(),
+            create_job: false,
         }
     )] // Rollout specified.
     fn default(
@@ -741,7 +833,9 @@ mod tests {
         r#"{ "namespace": "my-test-namespace" }"#,
         TargetConfig {
             path: None,
-            namespace: Some("my-test-namespace".to_string())
+            namespace: Some("my-test-namespace".to_string()),
+            pod_selection: Default::default(),
+            create_job: false,
         }
     )]
     // simple variant of file config - path string, not an object.
@@ -749,7 +843,9 @@ mod tests {
         r#""pod/my-cool-pod""#,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_string(), container: None})),
-            namespace: None
+            namespace: None,
+            pod_selection: Default::default(),
+            create_job: false,
         }
     )]
     // advanced variant of file config.
@@ -757,7 +853,9 @@ mod tests {
         r#"{ "path": "pod/my-cool-pod" }"#,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_string(), container: None})),
-            namespace: None
+            namespace: None,
+            pod_selection: Default::default(),
+            create_job: false,
         }
     )]
     // advanced variant of file config, with object as path.
@@ -769,7 +867,50 @@ mod tests {
         }"#,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_string(), container: None})),
-            namespace: None
+            namespace: None,
+            pod_selection: Default::default(),
+            create_job: false,
+        }
+    )]
+    // advanced variant of file config, with pod selection.
+    #[case(
+        r#"{
+            "path": "deployment/my-cool-deploy",
+            "pod_selection": {
+                "strategy": "by-name-prefix:my-cool-deploy-7c",
+                "allow_not_ready": true
+            }
+        }"#,
+        TargetConfig{
+            path: Some(Target::Deployment(DeploymentTarget {
+                deployment: "my-cool-deploy".to_string(),
+                container: None
+            })),
+            namespace: None,
+            pod_selection: PodSelectionConfig {
+                strategy: pod_selection::PodSelectionStrategy::ByNamePrefix(
+                    "my-cool-deploy-7c".to_string()
+                ),
+                allow_not_ready: true,
+                ordinal: None,
+            },
+            create_job: false,
+        }
+    )]
+    // advanced variant of file config, creating a job from a cron job.
+    #[case(
+        r#"{
+            "path": "cronjob/my-cool-cronjob",
+            "create_job": true
+        }"#,
+        TargetConfig{
+            path: Some(Target::CronJob(CronJobTarget {
+                cron_job: "my-cool-cronjob".to_string(),
+                container: None
+            })),
+            namespace: None,
+            pod_selection: Default::default(),
+            create_job: true,
         }
     )]
     fn parse_target_config_from_json(
@@ -0,0 +1,34 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{FAIL_PARSE_DEPLOYMENT_OR_POD, FromSplit};
+use crate::config::{self, ConfigError};
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CronJobTarget {
+    pub cron_job: String,
+    pub container: Option<String>,
+}
+
+impl FromSplit for CronJobTarget {
+    fn from_split(split: &mut std::str::Split<char>) -> config::Result<Self> {
+        let cron_job = split
+            .next()
+            .ok_or_else(|| ConfigError::InvalidTarget(FAIL_PARSE_DEPLOYMENT_OR_POD.to_string()))?;
+
+        match (split.next(), split.next()) {
+            (Some("container"), Some(container)) => Ok(Self {
+                cron_job: cron_job.to_string(),
+                container: Some(container.to_string()),
+            }),
+            (None, None) => Ok(Self {
+                cron_job: cron_job.to_string(),
+                container: None,
+            }),
+            _ => Err(ConfigError::InvalidTarget(
+                FAIL_PARSE_DEPLOYMENT_OR_POD.to_string(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::{fmt, str::FromStr};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// ### target.pod_selection {#target-pod_selection}
+///
+/// Controls which pod is picked when targeting a workload (e.g. a deployment or a rollout)
+/// without the mirrord Operator.
+///
+/// Stateful sets are the exception, their pod is picked by its ordinal, see
+/// [`target.pod_selection.ordinal`](#target-pod_selection-ordinal).
+///
+/// By default, the newest pod that is ready and not terminating is picked.
+///
+/// ```json
+/// {
+///   "target": {
+///     "path": "deployment/bear-deployment",
+///     "pod_selection": {
+///       "strategy": "by-name-prefix:bear-deployment-7c9f",
+///       "allow_not_ready": false
+///     }
+///   }
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PodSelectionConfig {
+    /// #### target.pod_selection.strategy {#target-pod_selection-strategy}
+    ///
+    /// How to pick one of the workload's pods.
+    ///
+    /// - `newest` (default): the pod with the most recent creation timestamp;
+    /// - `oldest`: the pod with the earliest creation timestamp;
+    /// - `random`: a random pod;
+    /// - `by-name-prefix:{prefix}`: the newest pod whose name starts with `{prefix}`.
+    #[serde(default)]
+    #[schemars(with = "String")]
+    pub strategy: PodSelectionStrategy,
+
+    /// #### target.pod_selection.allow_not_ready {#target-pod_selection-allow_not_ready}
+    ///
+    /// Also consider pods that are not Ready or are terminating.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub allow_not_ready: bool,
+
+    /// #### target.pod_selection.ordinal {#target-pod_selection-ordinal}
+    ///
+    /// Ordinal of the pod picked when targeting a stateful set without `/pod/{ordinal}` in the
+    /// target path, e.g. `1` for `{statefulset-name}-1`.
+    ///
+    /// Ignored for other targets. Defaults to `0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ordinal: Option<u32>,
+}
+
+/// <!--${internal}-->
+/// See [`PodSelectionConfig::strategy`].
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[serde(try_from = "String", into = "String")]
+pub enum PodSelectionStrategy {
+    #[default]
+    Newest,
+    Oldest,
+    Random,
+    ByNamePrefix(String),
+}
+
+impl PodSelectionStrategy {
+    const BY_NAME_PREFIX: &str = "by-name-prefix:";
+}
+
+#[derive(Error, Debug)]
+#[error(
+    "could not parse pod selection strategy `{0}`, values must be newest/oldest/random/by-name-prefix:<prefix>"
+)]
+pub struct PodSelectionStrategyParseError(String);
+
+impl FromStr for PodSelectionStrategy {
+    type Err = PodSelectionStrategyParseError;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val {
+            "newest" => Ok(Self::Newest),
+            "oldest" => Ok(Self::Oldest),
+            "random" => Ok(Self::Random),
+            other => match other.strip_prefix(Self::BY_NAME_PREFIX) {
+                Some(prefix) if !prefix.is_empty() => Ok(Self::ByNamePrefix(prefix.to_string())),
+                _ => Err(PodSelectionStrategyParseError(other.to_string())),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for PodSelectionStrategy {
+    type Error = PodSelectionStrategyParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<PodSelectionStrategy> for String {
+    fn from(value: PodSelectionStrategy) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for PodSelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Newest => write!(f, "newest"),
+            Self::Oldest => write!(f, "oldest"),
+            Self::Random => write!(f, "random"),
+            Self::ByNamePrefix(prefix) => write!(f, "{}{prefix}", Self::BY_NAME_PREFIX),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::PodSelectionStrategy;
+
+    #[rstest]
+    #[case("newest", PodSelectionStrategy::Newest)]
+    #[case("oldest", PodSelectionStrategy::Oldest)]
+    #[case("random", PodSelectionStrategy::Random)]
+    #[case("by-name-prefix:api-7c", PodSelectionStrategy::ByNamePrefix("api-7c".into()))]
+    fn strategy_round_trip(#[case] raw: &str, #[case] expected: PodSelectionStrategy) {
+        let parsed = raw.parse::<PodSelectionStrategy>().unwrap();
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.to_string(), raw);
+    }
+
+    #[rstest]
+    #[case("by-name-prefix:")]
+    #[case("first")]
+    fn strategy_invalid(#[case] raw: &str) {
+        raw.parse::<PodSelectionStrategy>().unwrap_err();
+    }
+}
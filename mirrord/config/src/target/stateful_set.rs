@@ -0,0 +1,59 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{FAIL_PARSE_DEPLOYMENT_OR_POD, FromSplit};
+use crate::config::{self, ConfigError};
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Debug, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct StatefulSetTarget {
+    pub stateful_set: String,
+    /// Ordinal of the targeted pod, e.g. `2` for `{stateful_set}-2`.
+    ///
+    /// When not given, `target.pod_selection.ordinal` is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ordinal: Option<u32>,
+    pub container: Option<String>,
+}
+
+impl StatefulSetTarget {
+    /// Name of the pod with the given `ordinal`, as assigned by the stateful set controller.
+    pub fn pod_name(&self, ordinal: u32) -> String {
+        format!("{}-{ordinal}", self.stateful_set)
+    }
+}
+
+impl FromSplit for StatefulSetTarget {
+    fn from_split(split: &mut std::str::Split<char>) -> config::Result<Self> {
+        let invalid = || ConfigError::InvalidTarget(FAIL_PARSE_DEPLOYMENT_OR_POD.to_string());
+
+        let stateful_set = split.next().ok_or_else(invalid)?.to_string();
+
+        let mut ordinal = None;
+        let mut container = None;
+        match (split.next(), split.next()) {
+            (Some("pod"), Some(raw)) => {
+                ordinal = Some(raw.parse::<u32>().map_err(|_| invalid())?);
+
+                match (split.next(), split.next()) {
+                    (Some("container"), Some(name)) => container = Some(name.to_string()),
+                    (None, None) => {}
+                    _ => return Err(invalid()),
+                }
+            }
+            (Some("container"), Some(name)) => container = Some(name.to_string()),
+            (None, None) => {}
+            _ => return Err(invalid()),
+        }
+
+        if split.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            stateful_set,
+            ordinal,
+            container,
+        })
+    }
+}
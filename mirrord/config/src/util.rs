@@ -5,7 +5,10 @@ use serde::{Deserialize, Deserializer, Serialize, de};
 
 use crate::{
     LayerConfig,
-    config::{ConfigContext, ConfigError, FromMirrordConfig, MirrordConfig, Result},
+    config::{
+        ConfigContext, ConfigError, FromMirrordConfig, MirrordConfig, Result,
+        provenance::SourceTracker,
+    },
 };
 
 pub trait MirrordToggleableConfig: MirrordConfig + Default {
@@ -37,8 +40,11 @@ where
 
     fn generate_config(self, context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
         match self {
-            ToggleableConfig::Enabled(true) => T::enabled_config(context),
-            ToggleableConfig::Enabled(false) => T::disabled_config(context),
+            ToggleableConfig::Enabled(true) => context.defaults(T::enabled_config),
+            ToggleableConfig::Enabled(false) => {
+                context.with_tracker(SourceTracker::note_disabled);
+                T::disabled_config(context)
+            }
             ToggleableConfig::Config(inner) => inner.generate_config(context),
         }
     }
@@ -588,3 +588,17 @@ impl_request!(
     req_path = LayerToProxyMessage::File => FileRequest::Fchmod,
     res_path = ProxyToLayerMessage::File => FileResponse::Fchmod,
 );
+
+impl_request!(
+    req = GetXattrRequest,
+    res = RemoteResult<GetXattrResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::GetXattr,
+    res_path = ProxyToLayerMessage::File => FileResponse::GetXattr,
+);
+
+impl_request!(
+    req = ListXattrRequest,
+    res = RemoteResult<ListXattrResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::ListXattr,
+    res_path = ProxyToLayerMessage::File => FileResponse::ListXattr,
+);
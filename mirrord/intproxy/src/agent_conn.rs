@@ -2,9 +2,16 @@
 //! created in different mirrord crates.
 
 use std::{
-    error::Report, fmt, io, net::SocketAddr, ops::ControlFlow, path::PathBuf, time::Duration,
+    error::Report,
+    fmt, io,
+    net::SocketAddr,
+    ops::ControlFlow,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+use hyper::StatusCode;
 use mirrord_analytics::{NullReporter, Reporter};
 use mirrord_config::LayerConfig;
 use mirrord_kube::{api::kubernetes::AgentKubernetesConnectInfo, error::KubeApiError, kube};
@@ -13,13 +20,13 @@ use mirrord_operator::{
         OperatorApi, OperatorSession,
         error::{OperatorApiError, OperatorOperation},
     },
-    types::{RECONNECT_NOT_POSSIBLE_CODE, RECONNECT_NOT_POSSIBLE_REASON},
+    types::{ReconnectError, ReconnectPolicy, is_reconnect_not_possible},
 };
 #[cfg(test)]
 use mirrord_protocol::DaemonMessage;
 #[cfg(test)]
 use mirrord_protocol_io::ConnectionOutput;
-use mirrord_protocol_io::{Client, Connection, ProtocolError};
+use mirrord_protocol_io::{Client, Connection, MessageTap, ProtocolError};
 #[cfg(not(test))]
 use serde::Deserialize;
 use serde::Serialize;
@@ -30,7 +37,6 @@ pub use tls::ConnectionTlsError;
 use tokio::net::{TcpSocket, TcpStream};
 #[cfg(test)]
 use tokio::sync::mpsc;
-use tokio_retry::{RetryIf, strategy::ExponentialBackoff};
 use tracing::Level;
 
 use crate::{
@@ -61,6 +67,36 @@ pub enum AgentConnectionError {
     ProtocolError(#[from] ProtocolError),
 }
 
+impl AgentConnectionError {
+    /// Returns the [`ReconnectError`] if the operator responded with an explicit 410, meaning that
+    /// the session is permanently gone.
+    fn session_gone(&self) -> Option<ReconnectError> {
+        match self {
+            Self::Operator(OperatorApiError::KubeError {
+                error: kube::Error::Api(error),
+                operation: OperatorOperation::WebsocketConnection,
+            }) if StatusCode::from_u16(error.code)
+                .is_ok_and(|status| is_reconnect_not_possible(status, &error.reason)) =>
+            {
+                Some(ReconnectError::new(error.message.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the status of the error response, if the operator responded with one when we tried
+    /// to connect to the session.
+    fn response_status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Operator(OperatorApiError::KubeError {
+                error: kube::Error::Api(error),
+                operation: OperatorOperation::WebsocketConnection,
+            }) => StatusCode::from_u16(error.code).ok(),
+            _ => None,
+        }
+    }
+}
+
 /// Directive for the proxy on how to connect to the agent.
 #[derive(Debug, Clone, Serialize, EnumDiscriminants)]
 #[cfg_attr(not(test), derive(Deserialize))]
@@ -153,6 +189,8 @@ pub enum AgentConnectionMessage {
 pub struct AgentConnection {
     pub connection: Connection<Client>,
     pub reconnect: ReconnectFlow,
+    /// Set on the new [`Connection`] after a reconnect, see [`AgentConnection::with_tap`].
+    pub tap: Option<Arc<dyn MessageTap<Client>>>,
 }
 
 impl AgentConnection {
@@ -165,6 +203,17 @@ impl AgentConnection {
         analytics: &mut R,
     ) -> Result<Self, AgentConnectionError> {
         let kind = connect_info.discriminant();
+        let resume_enabled = config.internal_proxy.reconnect_max_attempts > 0;
+        let reconnect_flow = |connect_info| {
+            if resume_enabled {
+                ReconnectFlow::ConnectInfo {
+                    config: Box::new(config.clone()),
+                    connect_info,
+                }
+            } else {
+                ReconnectFlow::Break(kind)
+            }
+        };
 
         let (connection, reconnect) = match connect_info {
             AgentConnectInfo::Operator(session) => {
@@ -174,10 +223,7 @@ impl AgentConnection {
                 (
                     connection.conn,
                     if session.allow_reconnect {
-                        ReconnectFlow::ConnectInfo {
-                            config: Box::new(config.clone()),
-                            connect_info: AgentConnectInfo::Operator(session),
-                        }
+                        reconnect_flow(AgentConnectInfo::Operator(session))
                     } else {
                         ReconnectFlow::Break(kind)
                     },
@@ -204,48 +250,86 @@ impl AgentConnection {
 
             AgentConnectInfo::DirectKubernetes(connect_info) => {
                 let conn = portforward::create_connection(config, connect_info.clone()).await?;
-                (conn, ReconnectFlow::Break(kind))
+                (
+                    conn,
+                    // The agent waits for us only when it was started with an idle TTL.
+                    if config.internal_proxy.reconnect_without_operator {
+                        reconnect_flow(AgentConnectInfo::DirectKubernetes(connect_info))
+                    } else {
+                        ReconnectFlow::Break(kind)
+                    },
+                )
             }
 
             #[cfg(test)]
             AgentConnectInfo::Dummy(sender) => {
                 let (conn, tx, rx) = Connection::dummy();
-                sender.send((tx, rx)).await.unwrap();
+                sender
+                    .send((tx, rx))
+                    .await
+                    .map_err(|_| io::Error::from(io::ErrorKind::ConnectionRefused))?;
 
-                let reconnect = ReconnectFlow::ConnectInfo {
-                    config: Box::new(config.clone()),
-                    connect_info: AgentConnectInfo::Dummy(sender),
-                };
-
-                (conn, reconnect)
+                (conn, reconnect_flow(AgentConnectInfo::Dummy(sender)))
             }
         };
 
         Ok(Self {
             connection,
             reconnect,
+            tap: None,
         })
     }
 
-    pub async fn new_for_raw_address(address: SocketAddr) -> Result<Self, AgentConnectionError> {
+    /// Connects to the agent listening on the given `address`.
+    ///
+    /// The session is resumed like the ones made with [`AgentConnectInfo::DirectKubernetes`],
+    /// by connecting to the same `address` again.
+    pub async fn new_for_raw_address(
+        address: SocketAddr,
+        config: &LayerConfig,
+    ) -> Result<Self, AgentConnectionError> {
         let stream = TcpStream::connect(address).await?;
         let connection = Connection::<Client>::from_stream(stream).await?;
 
+        let reconnect = if config.internal_proxy.reconnect_without_operator
+            && config.internal_proxy.reconnect_max_attempts > 0
+        {
+            ReconnectFlow::ConnectInfo {
+                config: Box::new(config.clone()),
+                connect_info: AgentConnectInfo::ExternalProxy {
+                    proxy_addr: address,
+                    tls_pem: None,
+                },
+            }
+        } else {
+            ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes)
+        };
+
         Ok(Self {
             connection,
-            reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes),
+            reconnect,
+            tap: None,
         })
     }
 
     pub fn reconnectable(&self) -> bool {
         self.reconnect.reconnectable()
     }
+
+    /// Makes the given [`MessageTap`] observe all the messages exchanged with the agent, also
+    /// through the connections made when reconnecting.
+    pub fn with_tap(mut self, tap: Arc<dyn MessageTap<Client>>) -> Self {
+        self.connection.set_tap(tap.clone());
+        self.tap = Some(tap);
+        self
+    }
 }
 
 impl fmt::Debug for AgentConnection {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("AgentConnection")
             .field("reconnect", &self.reconnect)
+            .field("tap", &self.tap.is_some())
             .finish()
     }
 }
@@ -320,50 +404,76 @@ impl RestartableBackgroundTask for AgentConnection {
                     .send(ProxyMessage::ConnectionRefresh(ConnectionRefresh::Start))
                     .await;
 
-                // 1s, 2s, 4s, 8s, 8s, ...
-                let retry_strategy = ExponentialBackoff::from_millis(2)
-                    .factor(500)
-                    .max_delay(Duration::from_secs(8))
-                    .take(10);
-                // Unless the operator responded with explicit 410 (meaning that the session is
-                // permanently gone), we can still retry.
-                let can_retry = |error: &AgentConnectionError| match error {
-                    AgentConnectionError::Operator(OperatorApiError::KubeError {
-                        error: kube::Error::Api(error),
-                        operation: OperatorOperation::WebsocketConnection,
-                    }) => {
-                        error.code != RECONNECT_NOT_POSSIBLE_CODE
-                            || error.reason != RECONNECT_NOT_POSSIBLE_REASON
-                    }
-                    _ => true,
+                let policy = ReconnectPolicy {
+                    // The first attempt is made right away, without consulting the policy.
+                    max_attempts: config
+                        .internal_proxy
+                        .reconnect_max_attempts
+                        .saturating_sub(1),
+                    ..Default::default()
                 };
-
-                let connection = RetryIf::spawn(
-                    retry_strategy,
-                    || async {
-                        message_bus
-                            .closed_token()
-                            .run_until_cancelled(AgentConnection::new(
-                                config,
-                                connect_info.clone(),
-                                &mut NullReporter::default(),
-                            ))
-                            .await
-                            .transpose()
-                            .inspect_err(|error| {
-                                tracing::error!(
-                                    error = %Report::new(error),
-                                    "Failed to reconnect to the {}",
-                                    connect_info.discriminant(),
-                                );
+                let deadline = Instant::now()
+                    + Duration::from_secs(config.internal_proxy.reconnect_max_downtime);
+                let mut attempt = 0;
+
+                let connection = loop {
+                    let new_connection = tokio::time::timeout_at(
+                        deadline.into(),
+                        AgentConnection::new(
+                            config,
+                            connect_info.clone(),
+                            &mut NullReporter::default(),
+                        ),
+                    );
+                    let error = match message_bus
+                        .closed_token()
+                        .run_until_cancelled(new_connection)
+                        .await
+                        .map(|result| {
+                            result.unwrap_or_else(|_| {
+                                Err(io::Error::from(io::ErrorKind::TimedOut).into())
                             })
-                    },
-                    can_retry,
-                )
-                .await;
+                        })
+                        .transpose()
+                    {
+                        Err(error) => error,
+                        result => break result,
+                    };
+
+                    tracing::error!(
+                        error = %Report::new(&error),
+                        "Failed to reconnect to the {}",
+                        connect_info.discriminant(),
+                    );
+
+                    // Unless the operator responded with explicit 410 (meaning that the session is
+                    // permanently gone), we can still retry.
+                    let Some(delay) = policy
+                        .next_delay(attempt, error.response_status())
+                        .filter(|delay| Instant::now() + *delay < deadline)
+                    else {
+                        break Err(error);
+                    };
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                };
+
+                if let Err(error) = &connection
+                    && let Some(gone) = error.session_gone()
+                {
+                    tracing::error!("{gone}");
+                }
 
                 match connection {
-                    Ok(Some(connection)) => {
+                    Ok(Some(mut connection)) => {
+                        if let Some(tap) = self.tap.take() {
+                            connection = connection.with_tap(tap);
+                        }
+                        // The session is resumed the same way the next time.
+                        connection.reconnect = std::mem::replace(
+                            &mut self.reconnect,
+                            ReconnectFlow::Break(connection.reconnect.kind()),
+                        );
                         *self = connection;
                         message_bus
                             .send(ProxyMessage::ConnectionRefresh(ConnectionRefresh::End(
@@ -38,11 +38,13 @@ impl<MessageIn, MessageOut> MessageBusInner<MessageIn, MessageOut> {
 
     /// Receives a message from this task's parent.
     ///
-    /// [`None`] means that the channel is closed and there will be no more messages.
+    /// [`None`] means that the channel is closed and there will be no more messages. Messages
+    /// sent before the parent went away are still received.
     pub async fn recv(&mut self) -> Option<MessageIn> {
         tokio::select! {
-            _ = self.tx.closed() => None,
+            biased;
             msg = self.rx.recv() => msg,
+            _ = self.tx.closed() => None,
         }
     }
 
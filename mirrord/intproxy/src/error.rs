@@ -1,3 +1,5 @@
+use std::convert::Infallible;
+
 use mirrord_intproxy_protocol::{LayerToProxyMessage, codec::CodecError};
 use mirrord_protocol::{DaemonMessage, ErrorKindInternal, RemoteIOError, ResponseError};
 use thiserror::Error;
@@ -34,6 +36,8 @@ pub(crate) enum ProxyRuntimeError {
     AgentFailed(String),
     #[error(transparent)]
     UnexpectedAgentMessage(#[from] UnexpectedAgentMessage),
+    #[error("failed to decompress an agent message: {0}")]
+    Decompression(std::io::Error),
 
     #[error("background task {0} exited unexpectedly")]
     TaskExit(MainTaskId),
@@ -58,6 +62,12 @@ pub(crate) enum ProxyRuntimeError {
     FilesProxy(#[from] FilesProxyError),
 }
 
+impl From<Infallible> for ProxyRuntimeError {
+    fn from(value: Infallible) -> Self {
+        match value {}
+    }
+}
+
 /// This kind of error causes a total failure of the proxy, meaning that for these errors doesn't
 /// exist a failover strategy, so facing this error the proxy stops working.
 #[derive(Error, Debug)]
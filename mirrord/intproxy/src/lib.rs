@@ -13,16 +13,23 @@ use error::UnexpectedAgentMessage;
 use layer_conn::LayerConnection;
 use layer_initializer::LayerInitializer;
 use main_tasks::{FromLayer, LayerForked, MainTaskId, ProxyMessage, ToLayer};
+use mirrord_auth::credential_store::UserIdentity;
 use mirrord_config::{
-    experimental::ExperimentalConfig, feature::network::incoming::tls_delivery::LocalTlsDelivery,
+    experimental::ExperimentalConfig,
+    feature::network::{dns::DnsCacheConfig, incoming::tls_delivery::LocalTlsDelivery},
 };
 use mirrord_intproxy_protocol::{
     IncomingRequest, LayerId, LayerToProxyMessage, LocalMessage, MessageId, ProcessInfo,
 };
 use mirrord_protocol::{
-    CLIENT_READY_FOR_LOGS, ClientMessage, DaemonMessage, FileRequest, LogLevel,
+    CLIENT_IDENTITY_VERSION, CLIENT_READY_FOR_LOGS, ClientIdentity, ClientMessage, DaemonMessage,
+    FileRequest, LogLevel,
+    compression::{COMPRESSION_VERSION, CompressionCodec},
+    dns::GetAddrInfoResponseV2,
+    tcp::HttpFilterReport,
 };
 use mirrord_protocol_io::{Client, TxHandle};
+pub use ping_pong::PingPongConfig;
 use ping_pong::{PingPong, PingPongMessage};
 use proxies::{
     files::{FilesProxy, FilesProxyMessage},
@@ -30,9 +37,11 @@ use proxies::{
     outgoing::{OutgoingProxy, OutgoingProxyMessage},
     simple::{SimpleProxy, SimpleProxyMessage},
 };
+use replicas::{ReplicaMirrors, ReplicaMirrorsMessage};
 use semver::Version;
 use tokio::{
     net::TcpListener,
+    sync::mpsc,
     time,
     time::{Interval, MissedTickBehavior},
 };
@@ -55,6 +64,7 @@ pub mod main_tasks;
 mod ping_pong;
 pub mod proxies;
 mod remote_resources;
+pub mod replicas;
 mod request_queue;
 
 /// [`TaskSender`]s for main background tasks. See [`MainTaskId`].
@@ -67,6 +77,8 @@ struct TaskTxs {
     outgoing: TaskSender<OutgoingProxy>,
     incoming: TaskSender<IncomingProxy>,
     files: TaskSender<FilesProxy>,
+    /// Present only when the other replicas of the target are mirrored.
+    replicas: Option<TaskSender<ReplicaMirrors>>,
 }
 
 /// This struct contains logic for proxying between multiple layer instances and one agent.
@@ -90,7 +102,7 @@ pub struct IntProxy {
     reconnect_task_queue: Option<VecDeque<ProxyMessage>>,
 
     // Simple ping preset state-machine to debounce ping-pong resets (from agent activity) to at
-    // most every 10/th of `PING_INTERVAL`
+    // most every 10/th of `PingPongConfig::interval`
     ping_pong_update_debounce: Interval,
     ping_pong_update_allowed: bool,
 
@@ -102,29 +114,35 @@ pub struct IntProxy {
 
     /// Send handle for the agent connection
     agent_tx: TxHandle<Client>,
+
+    /// Where to send the results of the HTTP filter dry run, see
+    /// [`IntProxy::with_filter_reports`].
+    filter_reports: Option<mpsc::UnboundedSender<HttpFilterReport>>,
 }
 
 impl IntProxy {
     /// Size of channels used to communicate with main tasks (see [`MainTaskId`]).
     const CHANNEL_SIZE: usize = 512;
-    /// How long can the agent connection remain silent.
-    #[cfg(not(test))]
-    const PING_INTERVAL: Duration = Duration::from_secs(30);
-    #[cfg(test)]
-    const PING_INTERVAL: Duration = Duration::from_secs(1);
     /// How many sequential reconnects should PingPong task attepmt to perform before giving up.
     const PING_PONG_MAX_RECONNECTS: usize = 5;
 
     /// Creates a new [`IntProxy`] using existing [`AgentConnection`].
     /// The returned instance will accept connections from the layers using the given
     /// [`TcpListener`].
+    ///
+    /// When given [`ReplicaMirrors`], the incoming traffic of the other replicas of the target is
+    /// mirrored as well.
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_connection(
         agent_conn: AgentConnection,
         listener: TcpListener,
         file_buffer_size: u64,
         https_delivery: LocalTlsDelivery,
         process_logging_interval: Duration,
+        ping_pong: PingPongConfig,
+        dns_cache: &DnsCacheConfig,
         experimental: &ExperimentalConfig,
+        replica_mirrors: Option<ReplicaMirrors>,
     ) -> Self {
         let mut background_tasks: BackgroundTasks<MainTaskId, ProxyMessage, ProxyRuntimeError> =
             BackgroundTasks::new(agent_conn.connection.tx_handle());
@@ -145,7 +163,7 @@ impl IntProxy {
         background_tasks.suspend_messages(MainTaskId::LayerInitializer);
         let ping_pong = background_tasks.register_restartable(
             PingPong::new(
-                Self::PING_INTERVAL,
+                ping_pong,
                 if agent_conn_reconnectable {
                     Self::PING_PONG_MAX_RECONNECTS
                 } else {
@@ -156,7 +174,7 @@ impl IntProxy {
             Self::CHANNEL_SIZE,
         );
         let simple = background_tasks.register(
-            SimpleProxy::new(experimental.dns_permission_error_fatal),
+            SimpleProxy::new(experimental.dns_permission_error_fatal, dns_cache),
             MainTaskId::SimpleProxy,
             Self::CHANNEL_SIZE,
         );
@@ -173,10 +191,18 @@ impl IntProxy {
             IncomingProxy::new(
                 Duration::from_millis(experimental.idle_local_http_connection_timeout),
                 https_delivery,
-            ),
+            )
+            .with_replica_mirrors(replica_mirrors.is_some()),
             MainTaskId::IncomingProxy,
             Self::CHANNEL_SIZE,
         );
+        let replicas = replica_mirrors.map(|replica_mirrors| {
+            background_tasks.register(
+                replica_mirrors,
+                MainTaskId::ReplicaMirrors,
+                Self::CHANNEL_SIZE,
+            )
+        });
         let files = background_tasks.register(
             FilesProxy::new(file_buffer_size),
             MainTaskId::FilesProxy,
@@ -191,7 +217,7 @@ impl IntProxy {
             Self::CHANNEL_SIZE,
         );
 
-        let mut ping_pong_update_debounce = time::interval(Self::PING_INTERVAL / 10);
+        let mut ping_pong_update_debounce = time::interval(ping_pong.interval / 10);
         ping_pong_update_debounce.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
         let mut process_logging_interval = time::interval(process_logging_interval);
@@ -209,6 +235,7 @@ impl IntProxy {
                 incoming,
                 ping_pong,
                 files,
+                replicas,
             },
             pending_layers: Default::default(),
             protocol_version: None,
@@ -218,9 +245,17 @@ impl IntProxy {
             connected_layers: HashMap::new(),
             process_logging_interval,
             agent_tx,
+            filter_reports: None,
         }
     }
 
+    /// Makes this proxy send the [`HttpFilterReport`]s received from the agent to the given
+    /// channel, instead of dropping them.
+    pub fn with_filter_reports(mut self, tx: mpsc::UnboundedSender<HttpFilterReport>) -> Self {
+        self.filter_reports = Some(tx);
+        self
+    }
+
     /// Check if any layer connections are still alive
     fn has_layer_connections(&self) -> bool {
         !self.task_txs.layers.is_empty()
@@ -398,6 +433,23 @@ impl IntProxy {
                 }
             }
             ProxyMessage::ConnectionRefresh(kind) => self.handle_connection_refresh(kind).await?,
+            ProxyMessage::FromReplica(msg) => {
+                self.task_txs
+                    .incoming
+                    .send(IncomingProxyMessage::AgentMirror(msg))
+                    .await
+            }
+            ProxyMessage::ToReplicas(msg) => {
+                if let Some(replicas) = &self.task_txs.replicas {
+                    replicas.send(ReplicaMirrorsMessage::Mirror(msg)).await;
+                }
+            }
+            ProxyMessage::HttpFilterReport(report) => {
+                if let Some(tx) = &self.filter_reports {
+                    // The receiver is gone only when the CLI is shutting down.
+                    let _ = tx.send(report);
+                }
+            }
         }
 
         Ok(())
@@ -478,6 +530,13 @@ impl IntProxy {
             self.ping_pong_update_allowed = false;
         }
 
+        let message = match message {
+            DaemonMessage::Compressed(compressed) => compressed
+                .decompress()
+                .map_err(ProxyRuntimeError::Decompression)?,
+            message => message,
+        };
+
         match message {
             DaemonMessage::Pong => {
                 self.task_txs
@@ -510,7 +569,13 @@ impl IntProxy {
             DaemonMessage::GetAddrInfoResponse(msg) => {
                 self.task_txs
                     .simple
-                    .send(SimpleProxyMessage::AddrInfoRes(msg))
+                    .send(SimpleProxyMessage::AddrInfoRes(msg, None))
+                    .await
+            }
+            DaemonMessage::GetAddrInfoResponseV2(GetAddrInfoResponseV2 { response, ttl }) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::AddrInfoRes(response, ttl))
                     .await
             }
             DaemonMessage::Tcp(msg) => {
@@ -537,6 +602,24 @@ impl IntProxy {
                     self.agent_tx.send(ClientMessage::ReadyForLogs).await;
                 }
 
+                if COMPRESSION_VERSION.matches(&protocol_version) {
+                    self.agent_tx
+                        .send(ClientMessage::SwitchCompression(
+                            CompressionCodec::SUPPORTED.to_vec(),
+                        ))
+                        .await;
+                }
+
+                if CLIENT_IDENTITY_VERSION.matches(&protocol_version) {
+                    let UserIdentity { name, hostname } = UserIdentity::load();
+                    self.agent_tx
+                        .send(ClientMessage::ClientIdentity(ClientIdentity {
+                            name,
+                            hostname,
+                        }))
+                        .await;
+                }
+
                 self.task_txs
                     .files
                     .send(FilesProxyMessage::ProtocolVersion(protocol_version.clone()))
@@ -561,6 +644,9 @@ impl IntProxy {
                     .send(OutgoingProxyMessage::AgentProtocolVersion(protocol_version))
                     .await;
             }
+            DaemonMessage::SwitchCompressionResponse(codec) => {
+                tracing::debug!(?codec, "Agent compression negotiated");
+            }
             DaemonMessage::LogMessage(log) => match log.level {
                 LogLevel::Error => tracing::error!(
                     message = log.message,
@@ -583,7 +669,8 @@ impl IntProxy {
             }
             message @ DaemonMessage::PauseTarget(_)
             | message @ DaemonMessage::Vpn(_)
-            | message @ DaemonMessage::ReverseDnsLookup(_) => {
+            | message @ DaemonMessage::ReverseDnsLookup(_)
+            | message @ DaemonMessage::Compressed(_) => {
                 Err(ProxyRuntimeError::UnexpectedAgentMessage(
                     UnexpectedAgentMessage(message.into()),
                 ))?;
@@ -727,12 +814,12 @@ impl IntProxy {
 
 #[cfg(test)]
 mod test {
-    use std::{net::SocketAddr, path::PathBuf, time::Duration};
+    use std::{net::SocketAddr, ops::Not, path::PathBuf, time::Duration};
 
     use hyper::{HeaderMap, Method, StatusCode, Uri, Version};
     use mirrord_analytics::NullReporter;
     use mirrord_config::{
-        LayerFileConfig, config::MirrordConfig, experimental::ExperimentalFileConfig,
+        LayerConfig, LayerFileConfig, config::MirrordConfig, experimental::ExperimentalFileConfig,
     };
     use mirrord_intproxy_protocol::{
         IncomingRequest, LayerToProxyMessage, LocalMessage, NetProtocol, NewSessionRequest,
@@ -743,8 +830,12 @@ mod test {
     use mirrord_protocol::{
         ClientMessage, DaemonMessage, ErrorKindInternal, FileRequest, FileResponse, RemoteIOError,
         ResponseError, VERSION,
+        compression::{CompressedMessage, CompressionCodec},
         dns::{AddressFamily, GetAddrInfoRequestV2, GetAddrInfoResponse, SockType},
-        file::{OpenFileRequest, StatFsRequestV2},
+        file::{
+            OpenFileRequest, OpenFileResponse, OpenOptionsInternal, ReadFileRequest,
+            ReadFileResponse, ReadLimitedFileRequest, StatFsRequestV2,
+        },
         outgoing::{LayerConnectV2, SocketAddress, tcp::LayerTcpOutgoing},
         tcp::{
             ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, DaemonTcp,
@@ -764,12 +855,18 @@ mod test {
     };
 
     use crate::{
-        IntProxy,
+        IntProxy, PingPongConfig,
         agent_conn::{
             AgentConnectInfo, AgentConnectInfoDiscriminants, AgentConnection, ReconnectFlow,
         },
     };
 
+    /// Pings the agent often, so that the tests don't wait long for the keepalive.
+    const TEST_PING_PONG: PingPongConfig = PingPongConfig {
+        interval: Duration::from_secs(1),
+        max_missed_pongs: 1,
+    };
+
     /// Verifies that [`IntProxy`] waits with processing layers' requests
     /// until [`mirrord_protocol`] version is negotiated.
     ///
@@ -789,6 +886,7 @@ mod test {
         let agent_conn = AgentConnection {
             connection,
             reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes),
+            tap: None,
         };
         let proxy = IntProxy::new_with_connection(
             agent_conn,
@@ -796,9 +894,12 @@ mod test {
             4096,
             Default::default(),
             Duration::from_secs(60),
+            TEST_PING_PONG,
+            &Default::default(),
             &ExperimentalFileConfig::default()
                 .generate_config(&mut Default::default())
                 .unwrap(),
+            None,
         );
         let proxy_handle = tokio::spawn(proxy.run(Duration::from_secs(60), Duration::ZERO));
 
@@ -880,7 +981,9 @@ mod test {
                 ClientMessage::Ping => {
                     proxy_tx.send(DaemonMessage::Pong).await.unwrap();
                 }
-                ClientMessage::ReadyForLogs => {}
+                ClientMessage::ReadyForLogs
+                | ClientMessage::SwitchCompression(..)
+                | ClientMessage::ClientIdentity(..) => {}
                 ClientMessage::FileRequest(FileRequest::StatFsV2(StatFsRequestV2 { path })) => {
                     assert_eq!(path, PathBuf::from("/some/path"));
                     break;
@@ -906,6 +1009,7 @@ mod test {
         let agent_conn = AgentConnection {
             connection,
             reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes),
+            tap: None,
         };
 
         let proxy = IntProxy::new_with_connection(
@@ -914,9 +1018,12 @@ mod test {
             4096,
             Default::default(),
             Duration::from_secs(60),
+            TEST_PING_PONG,
+            &Default::default(),
             &ExperimentalFileConfig::default()
                 .generate_config(&mut Default::default())
                 .unwrap(),
+            None,
         );
         let proxy_handle = tokio::spawn(proxy.run(Duration::from_secs(60), Duration::ZERO));
 
@@ -999,6 +1106,7 @@ mod test {
         let agent_conn = AgentConnection {
             connection,
             reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes),
+            tap: None,
         };
 
         let proxy = IntProxy::new_with_connection(
@@ -1007,9 +1115,12 @@ mod test {
             4096,
             Default::default(),
             Duration::from_secs(60),
+            TEST_PING_PONG,
+            &Default::default(),
             &ExperimentalFileConfig::default()
                 .generate_config(&mut Default::default())
                 .unwrap(),
+            None,
         );
         tokio::time::timeout(
             Duration::from_millis(200),
@@ -1026,17 +1137,24 @@ mod test {
         to_layer: AsyncDecoder<LocalMessage<ProxyToLayerMessage>, OwnedReadHalf>,
     }
 
-    async fn setup_reconnect_test() -> ReconnectTestSetup {
+    async fn setup_reconnect_test(ping_pong: PingPongConfig) -> ReconnectTestSetup {
+        let config = LayerFileConfig::default()
+            .generate_config(&mut Default::default())
+            .unwrap();
+
+        setup_reconnect_test_with_config(ping_pong, config).await
+    }
+
+    async fn setup_reconnect_test_with_config(
+        ping_pong: PingPongConfig,
+        config: LayerConfig,
+    ) -> ReconnectTestSetup {
         let listener = TcpListener::bind("127.0.0.1:0".parse::<SocketAddr>().unwrap())
             .await
             .unwrap();
 
         let (conn_tx, conn_rx) = mpsc::channel(1);
 
-        let config = LayerFileConfig::default()
-            .generate_config(&mut Default::default())
-            .unwrap();
-
         let agent_conn = AgentConnection::new(
             &config,
             AgentConnectInfo::Dummy(conn_tx),
@@ -1078,9 +1196,12 @@ mod test {
             4096,
             Default::default(),
             Duration::from_secs(60),
+            ping_pong,
+            &Default::default(),
             &ExperimentalFileConfig::default()
                 .generate_config(&mut Default::default())
                 .unwrap(),
+            None,
         );
         tokio::spawn(proxy.run(Duration::from_millis(100), Duration::ZERO));
 
@@ -1106,7 +1227,9 @@ mod test {
         loop {
             match from_proxy.next().await.unwrap() {
                 ClientMessage::Ping => to_proxy.send(DaemonMessage::Pong).await.unwrap(),
-                ClientMessage::ReadyForLogs => (),
+                ClientMessage::ReadyForLogs
+                | ClientMessage::SwitchCompression(..)
+                | ClientMessage::ClientIdentity(..) => (),
                 other => return other,
             }
         }
@@ -1138,7 +1261,7 @@ mod test {
             mut conn_rx,
             mut from_layer,
             mut to_layer,
-        } = setup_reconnect_test().await;
+        } = setup_reconnect_test(TEST_PING_PONG).await;
 
         let (mut to_proxy, mut from_proxy) = conn_rx.recv().await.unwrap();
 
@@ -1217,13 +1340,23 @@ mod test {
             // Keep the connection so intproxy doesn't exit
             from_layer: _from_layer,
             to_layer: _,
-        } = setup_reconnect_test().await;
+        } = setup_reconnect_test(TEST_PING_PONG).await;
 
         let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
 
         switch_protocol_version(&to_proxy, &from_proxy).await;
 
         assert_eq!(from_proxy.next().await, Some(ClientMessage::ReadyForLogs));
+        assert_eq!(
+            from_proxy.next().await,
+            Some(ClientMessage::SwitchCompression(
+                CompressionCodec::SUPPORTED.to_vec()
+            ))
+        );
+        assert!(matches!(
+            from_proxy.next().await,
+            Some(ClientMessage::ClientIdentity(..))
+        ));
         assert_eq!(from_proxy.next().await, Some(ClientMessage::Ping));
 
         // Don't respond to pings.
@@ -1238,7 +1371,73 @@ mod test {
         switch_protocol_version(&to_proxy, &from_proxy).await;
 
         assert_eq!(from_proxy.next().await, Some(ClientMessage::ReadyForLogs));
+        assert_eq!(
+            from_proxy.next().await,
+            Some(ClientMessage::SwitchCompression(
+                CompressionCodec::SUPPORTED.to_vec()
+            ))
+        );
+        assert!(matches!(
+            from_proxy.next().await,
+            Some(ClientMessage::ClientIdentity(..))
+        ));
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::Ping));
+    }
+
+    /// Verifies that [`IntProxy`] keeps pinging the agent until the configured amount of pongs is
+    /// missed, and only then reconnects.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn reconnect_after_missed_pongs() {
+        let ReconnectTestSetup {
+            mut conn_rx,
+            // Keep the connection so intproxy doesn't exit
+            from_layer: _from_layer,
+            to_layer: _,
+        } = setup_reconnect_test(PingPongConfig {
+            max_missed_pongs: 2,
+            ..TEST_PING_PONG
+        })
+        .await;
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::ReadyForLogs));
+        assert_eq!(
+            from_proxy.next().await,
+            Some(ClientMessage::SwitchCompression(
+                CompressionCodec::SUPPORTED.to_vec()
+            ))
+        );
+        assert!(matches!(
+            from_proxy.next().await,
+            Some(ClientMessage::ClientIdentity(..))
+        ));
+
+        // Don't respond to pings, the first one is missed but tolerated.
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::Ping));
         assert_eq!(from_proxy.next().await, Some(ClientMessage::Ping));
+
+        // We should get a reconnect after the second one is missed.
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::ReadyForLogs));
+        assert_eq!(
+            from_proxy.next().await,
+            Some(ClientMessage::SwitchCompression(
+                CompressionCodec::SUPPORTED.to_vec()
+            ))
+        );
+        assert!(matches!(
+            from_proxy.next().await,
+            Some(ClientMessage::ClientIdentity(..))
+        ));
     }
 
     /// Verifies that [`IntProxy`] reconnects correctly while waiting for a fileops response.
@@ -1251,7 +1450,7 @@ mod test {
             // Keep the connection so intproxy doesn't exit
             mut from_layer,
             mut to_layer,
-        } = setup_reconnect_test().await;
+        } = setup_reconnect_test(TEST_PING_PONG).await;
 
         let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
 
@@ -1296,6 +1495,248 @@ mod test {
         ));
     }
 
+    /// Verifies that [`IntProxy`] reopens buffered read-only files after a reconnect, and keeps
+    /// reading them from the same offset.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn reconnect_reopens_files() {
+        let ReconnectTestSetup {
+            mut conn_rx,
+            mut from_layer,
+            mut to_layer,
+        } = setup_reconnect_test(TEST_PING_PONG).await;
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        let open_request = OpenFileRequest {
+            path: "/some/file".into(),
+            open_options: OpenOptionsInternal {
+                read: true,
+                ..Default::default()
+            },
+        };
+        from_layer
+            .send(&LocalMessage {
+                message_id: 0,
+                inner: LayerToProxyMessage::File(FileRequest::Open(open_request.clone())),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            ClientMessage::FileRequest(FileRequest::Open(open_request.clone()))
+        );
+        to_proxy
+            .send(DaemonMessage::File(FileResponse::Open(Ok(
+                OpenFileResponse { fd: 7 },
+            ))))
+            .await
+            .unwrap();
+        assert!(matches!(
+            to_layer.receive().await,
+            Ok(Some(LocalMessage {
+                message_id: 0,
+                inner: ProxyToLayerMessage::File(FileResponse::Open(Ok(OpenFileResponse {
+                    fd: 7
+                }))),
+            }))
+        ));
+
+        let read_request = FileRequest::Read(ReadFileRequest {
+            remote_fd: 7,
+            buffer_size: 4,
+        });
+        from_layer
+            .send(&LocalMessage {
+                message_id: 1,
+                inner: LayerToProxyMessage::File(read_request.clone()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            ClientMessage::FileRequest(FileRequest::ReadLimited(ReadLimitedFileRequest {
+                remote_fd: 7,
+                buffer_size: 4096,
+                start_from: 0,
+            }))
+        );
+        to_proxy
+            .send(DaemonMessage::File(FileResponse::ReadLimited(Ok(
+                ReadFileResponse {
+                    bytes: b"abcdefgh".to_vec().into(),
+                    read_amount: 8,
+                },
+            ))))
+            .await
+            .unwrap();
+        assert!(matches!(
+            to_layer.receive().await,
+            Ok(Some(LocalMessage {
+                message_id: 1,
+                inner: ProxyToLayerMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+                    read_amount: 4,
+                    ..
+                }))),
+            }))
+        ));
+
+        drop(to_proxy);
+
+        // We should get a reconnect, and the file should be reopened.
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+
+        // The files proxy and the main task send their messages independently.
+        let mut reopened = false;
+        let mut switched = false;
+        while (reopened && switched).not() {
+            match next_proxy_msg(&to_proxy, &from_proxy).await {
+                ClientMessage::SwitchProtocolVersion(..) => {
+                    to_proxy
+                        .send(DaemonMessage::SwitchProtocolVersionResponse(
+                            mirrord_protocol::VERSION.clone(),
+                        ))
+                        .await
+                        .unwrap();
+                    switched = true;
+                }
+                ClientMessage::FileRequest(FileRequest::Open(request)) => {
+                    assert_eq!(request, open_request);
+                    to_proxy
+                        .send(DaemonMessage::File(FileResponse::Open(Ok(
+                            OpenFileResponse { fd: 3 },
+                        ))))
+                        .await
+                        .unwrap();
+                    reopened = true;
+                }
+                other => panic!("unexpected message from the proxy: {other:?}"),
+            }
+        }
+
+        // The layer keeps using the old descriptor.
+        from_layer
+            .send(&LocalMessage {
+                message_id: 2,
+                inner: LayerToProxyMessage::File(read_request),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            ClientMessage::FileRequest(FileRequest::ReadLimited(ReadLimitedFileRequest {
+                remote_fd: 3,
+                buffer_size: 4096,
+                start_from: 4,
+            }))
+        );
+    }
+
+    /// Verifies that [`IntProxy`] gives up reconnecting once it runs out of attempts or time.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[case::attempts(1, 60)]
+    #[case::downtime(10, 0)]
+    #[timeout(Duration::from_secs(5))]
+    async fn reconnect_gives_up(#[case] max_attempts: u32, #[case] max_downtime: u64) {
+        let mut config = LayerFileConfig::default()
+            .generate_config(&mut Default::default())
+            .unwrap();
+        config.internal_proxy.reconnect_max_attempts = max_attempts;
+        config.internal_proxy.reconnect_max_downtime = max_downtime;
+
+        let ReconnectTestSetup {
+            mut conn_rx,
+            from_layer: _from_layer,
+            mut to_layer,
+        } = setup_reconnect_test_with_config(TEST_PING_PONG, config).await;
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        // Every reconnect attempt fails.
+        drop(conn_rx);
+        drop(to_proxy);
+
+        // The proxy exits and closes the layer connection.
+        assert!(matches!(to_layer.receive().await, Ok(None) | Err(..)));
+    }
+
+    /// Verifies that [`IntProxy`] negotiates compression with the agent, and unwraps
+    /// [`DaemonMessage::Compressed`] responses before passing them to the layer.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn decompress_agent_messages() {
+        let ReconnectTestSetup {
+            mut conn_rx,
+            mut from_layer,
+            mut to_layer,
+        } = setup_reconnect_test(TEST_PING_PONG).await;
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::ReadyForLogs));
+        assert_eq!(
+            from_proxy.next().await,
+            Some(ClientMessage::SwitchCompression(
+                CompressionCodec::SUPPORTED.to_vec()
+            ))
+        );
+        assert!(matches!(
+            from_proxy.next().await,
+            Some(ClientMessage::ClientIdentity(..))
+        ));
+        to_proxy
+            .send(DaemonMessage::SwitchCompressionResponse(
+                CompressionCodec::Zstd,
+            ))
+            .await
+            .unwrap();
+
+        let file_request = FileRequest::Open(OpenFileRequest {
+            path: "/some/file".into(),
+            open_options: Default::default(),
+        });
+
+        from_layer
+            .send(&LocalMessage {
+                message_id: 0,
+                inner: LayerToProxyMessage::File(file_request.clone()),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            ClientMessage::FileRequest(file_request)
+        );
+
+        let response = DaemonMessage::File(FileResponse::Open(Ok(OpenFileResponse { fd: 1 })));
+        to_proxy
+            .send(DaemonMessage::Compressed(
+                CompressedMessage::new(&response, CompressionCodec::Zstd).unwrap(),
+            ))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            to_layer.receive().await,
+            Ok(Some(LocalMessage {
+                message_id: 0,
+                inner: ProxyToLayerMessage::File(FileResponse::Open(Ok(OpenFileResponse {
+                    fd: 1
+                }))),
+            }))
+        ));
+    }
+
     /// Verifies that [`IntProxy`] reconnects correctly while waiting for a response to a
     /// [`ClientMessage::TcpOutgoing`].
     #[tokio::test]
@@ -1307,7 +1748,7 @@ mod test {
             // Keep the connection so intproxy doesn't exit
             mut from_layer,
             mut to_layer,
-        } = setup_reconnect_test().await;
+        } = setup_reconnect_test(TEST_PING_PONG).await;
 
         let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
 
@@ -1367,7 +1808,7 @@ mod test {
             // Keep the connection so intproxy doesn't exit
             mut from_layer,
             mut to_layer,
-        } = setup_reconnect_test().await;
+        } = setup_reconnect_test(TEST_PING_PONG).await;
 
         let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
 
@@ -1424,7 +1865,7 @@ mod test {
             // Keep the connection so intproxy doesn't exit
             mut from_layer,
             mut to_layer,
-        } = setup_reconnect_test().await;
+        } = setup_reconnect_test(TEST_PING_PONG).await;
 
         let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
         let addr = server.local_addr().unwrap();
@@ -3,7 +3,10 @@ use std::fmt;
 use mirrord_intproxy_protocol::{
     LayerId, LayerToProxyMessage, MessageId, ProcessInfo, ProxyToLayerMessage,
 };
-use mirrord_protocol::DaemonMessage;
+use mirrord_protocol::{
+    DaemonMessage,
+    tcp::{DaemonTcp, HttpFilterReport, LayerTcp},
+};
 use mirrord_protocol_io::{Client, TxHandle};
 use tokio::net::TcpStream;
 
@@ -23,6 +26,14 @@ pub enum ProxyMessage {
     NewLayer(NewLayer),
     /// Connection to agent was dropped and needs reload.
     ConnectionRefresh(ConnectionRefresh),
+    /// Mirrored traffic received from the agent of another replica of the target, see
+    /// [`ReplicaMirrors`](crate::replicas::ReplicaMirrors).
+    FromReplica(DaemonTcp),
+    /// Mirror message to be sent to the agents of the other replicas of the target, see
+    /// [`ReplicaMirrors`](crate::replicas::ReplicaMirrors).
+    ToReplicas(LayerTcp),
+    /// Result of a dry run of the HTTP filter, see [`LayerTcp::PortSubscribeDryRun`].
+    HttpFilterReport(HttpFilterReport),
 }
 
 #[cfg(test)]
@@ -106,6 +117,7 @@ pub enum MainTaskId {
     PingPong,
     AgentConnection,
     FilesProxy,
+    ReplicaMirrors,
     LayerConnection(LayerId),
 }
 
@@ -120,6 +132,7 @@ impl fmt::Display for MainTaskId {
             Self::LayerConnection(id) => write!(f, "LAYER_CONNECTION_{}", id.0),
             Self::IncomingProxy => f.write_str("INCOMING_PROXY"),
             Self::FilesProxy => f.write_str("FILES_PROXY"),
+            Self::ReplicaMirrors => f.write_str("REPLICA_MIRRORS"),
         }
     }
 }
@@ -3,10 +3,17 @@
 //! from the layer.
 //!
 //! Realized using the [`DaemonMessage::Pong`](mirrord_protocol::codec::DaemonMessage::Pong) and
-//! [`ClientMessage::Ping`] messages.
+//! [`ClientMessage::Ping`] messages, which are supported by every agent version.
+//!
+//! It also detects dead connections (e.g. silently dropped by a NAT or a load balancer): after
+//! [`PingPongConfig::max_missed_pongs`] pings are left without an answer, the connection is
+//! refreshed, or the proxy exits with [`PingPongError::PongTimeout`]. Any message from the agent
+//! counts as an answer, so an agent busy sending other messages (e.g. large file reads) is not
+//! considered dead because its pongs are queued behind them.
 
 use std::{ops::ControlFlow, time::Duration};
 
+use mirrord_config::internal_proxy::InternalProxyConfig;
 use mirrord_protocol::ClientMessage;
 use thiserror::Error;
 use tokio::time::{self, Instant, Interval, MissedTickBehavior};
@@ -24,9 +31,31 @@ pub enum PingPongError {
     /// Agent sent pong but the proxy was not expecting one.
     #[error("received an unexpected pong from the agent")]
     UnmatchedPong,
-    /// Agent did not send ping in time.
-    #[error("agent did not respond to ping in time")]
-    PongTimeout,
+    /// Agent did not answer the given number of consecutive pings.
+    #[error(
+        "keepalive failed, the agent did not respond to {0} consecutive ping(s), \
+        the connection to the agent is probably lost"
+    )]
+    PongTimeout(usize),
+}
+
+/// Configuration of the [`PingPong`] keepalive, usually taken from the
+/// [`InternalProxyConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingPongConfig {
+    /// How often the task should send pings.
+    pub interval: Duration,
+    /// After how many consecutive unanswered pings the connection is considered dead.
+    pub max_missed_pongs: usize,
+}
+
+impl From<&InternalProxyConfig> for PingPongConfig {
+    fn from(config: &InternalProxyConfig) -> Self {
+        Self {
+            interval: Duration::from_secs(config.keepalive_interval.max(1)),
+            max_missed_pongs: config.keepalive_max_missed,
+        }
+    }
 }
 
 /// Notification about a [`DeamonMessage::Pong`](mirrord_protocol::DaemonMessage::Pong) received
@@ -44,6 +73,10 @@ pub struct PingPong {
     ticker: Interval,
     /// How many pong are expected from the agent.
     awaiting_pongs: usize,
+    /// How many consecutive pings were left without an answer from the agent.
+    missed_pongs: usize,
+    /// After how many consecutive unanswered pings the connection is considered dead.
+    max_missed_pongs: usize,
 
     reconnecting: bool,
     reconnects: usize,
@@ -57,14 +90,17 @@ impl PingPong {
     ///
     /// # Arguments
     ///
-    /// * frequency - how often the task should send pings
-    pub fn new(frequency: Duration, max_reconnects: usize) -> Self {
-        let mut ticker = time::interval_at(Instant::now() + frequency, frequency);
+    /// * config - how often the task should send pings, and how many can be missed
+    /// * max_reconnects - how many sequential reconnects to attempt before giving up
+    pub fn new(config: PingPongConfig, max_reconnects: usize) -> Self {
+        let mut ticker = time::interval_at(Instant::now() + config.interval, config.interval);
         ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
         Self {
             ticker,
             awaiting_pongs: 0,
+            missed_pongs: 0,
+            max_missed_pongs: config.max_missed_pongs.max(1),
             reconnecting: false,
             reconnects: 0,
             max_reconnects,
@@ -80,8 +116,9 @@ impl BackgroundTask for PingPong {
 
     /// Pings the agent with a frequency configured in [`PingPong::new`].
     ///
-    /// When the time comes to ping the agent and the previous ping was not answered, this task
-    /// exits with an error.
+    /// When the time comes to ping the agent and the previous ping was not answered, counts a
+    /// missed pong. Exits with an error when [`PingPongConfig::max_missed_pongs`] are missed in a
+    /// row.
     #[tracing::instrument(level = Level::INFO, name = "ping_pong_main_loop", skip_all, ret, err)]
     async fn run(&mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
         loop {
@@ -91,13 +128,20 @@ impl BackgroundTask for PingPong {
                         last_agent_message >= Instant::now() - self.ticker.period()
                     }).unwrap_or_default();
 
-                    if self.awaiting_pongs > 0 && !other_messages_in_last_period {
-                        break Err(PingPongError::PongTimeout);
-                    } else {
-                        tracing::debug!("Sending ping to the agent");
-                        message_bus.send_agent(ClientMessage::Ping).await;
-                        self.awaiting_pongs += 1;
+                    if other_messages_in_last_period {
+                        self.missed_pongs = 0;
+                    } else if self.awaiting_pongs > 0 {
+                        self.missed_pongs += 1;
+                        tracing::debug!(missed_pongs = self.missed_pongs, "Agent did not respond to ping");
                     }
+
+                    if self.missed_pongs >= self.max_missed_pongs {
+                        break Err(PingPongError::PongTimeout(self.missed_pongs));
+                    }
+
+                    tracing::debug!("Sending ping to the agent");
+                    message_bus.send_agent(ClientMessage::Ping).await;
+                    self.awaiting_pongs += 1;
                 },
 
                 msg = message_bus.recv() => match (msg, self.awaiting_pongs) {
@@ -118,6 +162,7 @@ impl BackgroundTask for PingPong {
                         }
 
                         self.awaiting_pongs = self.awaiting_pongs.saturating_sub(1);
+                        self.missed_pongs = 0;
                     },
                     (Some(PingPongMessage::AgentSentPong), 0) => {
                         break Err(PingPongError::UnmatchedPong)
@@ -127,6 +172,7 @@ impl BackgroundTask for PingPong {
                         match refresh {
                             ConnectionRefresh::Start => {
                                 self.awaiting_pongs = 0;
+                                self.missed_pongs = 0;
                                 self.reconnecting = true;
                             }
                             ConnectionRefresh::End(new_agent_tx) => {
@@ -152,7 +198,7 @@ impl RestartableBackgroundTask for PingPong {
         message_bus: &mut MessageBus<Self>,
     ) -> ControlFlow<Self::Error> {
         match error {
-            PingPongError::PongTimeout if self.reconnects < self.max_reconnects => {
+            PingPongError::PongTimeout(..) if self.reconnects < self.max_reconnects => {
                 message_bus
                     .send(ProxyMessage::ConnectionRefresh(ConnectionRefresh::Request))
                     .await;
@@ -164,7 +210,7 @@ impl RestartableBackgroundTask for PingPong {
             _ => {
                 if self.max_reconnects == 0 {
                     tracing::warn!(
-                        "no reconnect will be performed because agent is not connected via operator"
+                        "no reconnect will be performed because the agent connection cannot be resumed"
                     );
                 } else {
                     tracing::warn!(max_reconnects = %self.max_reconnects, "reached maximum amount of reconnects due to PongTimeout");
@@ -31,6 +31,7 @@ macro_rules! dummy_file_response {
 
 /// Lightweight (no allocations) [`FileResponse`] to be returned when connection with the
 /// mirrord-agent is lost. Must be converted into real [`FileResponse`] via [`From`].
+#[derive(Debug)]
 pub struct AgentLostFileResponse(LayerId, MessageId, FileResponse);
 
 impl From<AgentLostFileResponse> for ToLayer {
@@ -62,6 +63,8 @@ impl From<AgentLostFileResponse> for ToLayer {
             FileResponse::Futimens(..) => FileResponse::Futimens(Err(error)),
             FileResponse::Fchown(..) => FileResponse::Fchown(Err(error)),
             FileResponse::Fchmod(..) => FileResponse::Fchmod(Err(error)),
+            FileResponse::GetXattr(..) => FileResponse::GetXattr(Err(error)),
+            FileResponse::ListXattr(..) => FileResponse::ListXattr(Err(error)),
         };
 
         debug_assert_eq!(
@@ -86,6 +89,9 @@ trait FileRequestExt: Sized {
         layer_id: LayerId,
         message_id: MessageId,
     ) -> Option<AgentLostFileResponse>;
+
+    /// If this [`FileRequest`] refers to an open remote descriptor, return it.
+    fn remote_fd_mut(&mut self) -> Option<&mut u64>;
 }
 
 impl FileRequestExt for FileRequest {
@@ -124,10 +130,71 @@ impl FileRequestExt for FileRequest {
             Self::Futimens(..) => dummy_file_response!(Futimens),
             Self::Fchown(..) => dummy_file_response!(Fchown),
             Self::Fchmod(..) => dummy_file_response!(Fchmod),
+            Self::GetXattr(..) => dummy_file_response!(GetXattr),
+            Self::ListXattr(..) => dummy_file_response!(ListXattr),
         };
 
         Some(AgentLostFileResponse(layer_id, message_id, response))
     }
+
+    fn remote_fd_mut(&mut self) -> Option<&mut u64> {
+        match self {
+            Self::Open(..)
+            | Self::Access(..)
+            | Self::Xstat(XstatRequest { fd: None, .. })
+            | Self::ReadLink(..)
+            | Self::MakeDir(..)
+            | Self::Unlink(..)
+            | Self::RemoveDir(..)
+            | Self::StatFs(..)
+            | Self::StatFsV2(..)
+            | Self::Rename(..)
+            | Self::UnlinkAt(UnlinkAtRequest { dirfd: None, .. })
+            | Self::GetXattr(GetXattrRequest { fd: None, .. })
+            | Self::ListXattr(ListXattrRequest { fd: None, .. }) => None,
+
+            Self::Close(CloseFileRequest { fd: remote_fd })
+            | Self::CloseDir(CloseDirRequest { remote_fd })
+            | Self::FdOpenDir(FdOpenDirRequest { remote_fd })
+            | Self::GetDEnts64(GetDEnts64Request { remote_fd, .. })
+            | Self::OpenRelative(OpenRelativeFileRequest {
+                relative_fd: remote_fd,
+                ..
+            })
+            | Self::Read(ReadFileRequest { remote_fd, .. })
+            | Self::ReadDir(ReadDirRequest { remote_fd, .. })
+            | Self::ReadDirBatch(ReadDirBatchRequest { remote_fd, .. })
+            | Self::ReadLimited(ReadLimitedFileRequest { remote_fd, .. })
+            | Self::Seek(SeekFileRequest { fd: remote_fd, .. })
+            | Self::Write(WriteFileRequest { fd: remote_fd, .. })
+            | Self::WriteLimited(WriteLimitedFileRequest { remote_fd, .. })
+            | Self::Xstat(XstatRequest {
+                fd: Some(remote_fd),
+                ..
+            })
+            | Self::XstatFs(XstatFsRequest { fd: remote_fd })
+            | Self::XstatFsV2(XstatFsRequestV2 { fd: remote_fd })
+            | Self::MakeDirAt(MakeDirAtRequest {
+                dirfd: remote_fd, ..
+            })
+            | Self::UnlinkAt(UnlinkAtRequest {
+                dirfd: Some(remote_fd),
+                ..
+            })
+            | Self::Ftruncate(FtruncateRequest { fd: remote_fd, .. })
+            | Self::Futimens(FutimensRequest { fd: remote_fd, .. })
+            | Self::Fchown(FchownRequest { fd: remote_fd, .. })
+            | Self::Fchmod(FchmodRequest { fd: remote_fd, .. })
+            | Self::GetXattr(GetXattrRequest {
+                fd: Some(remote_fd),
+                ..
+            })
+            | Self::ListXattr(ListXattrRequest {
+                fd: Some(remote_fd),
+                ..
+            }) => Some(remote_fd),
+        }
+    }
 }
 
 /// Messages handled by [`FilesProxy`].
@@ -207,7 +274,11 @@ impl fmt::Debug for BufferedDirData {
 #[derive(Debug, Default)]
 enum AdditionalRequestData {
     /// Open file that will be buffered.
-    OpenBuffered,
+    OpenBuffered {
+        /// Original request, if the file can be reopened after the connection with the agent is
+        /// refreshed.
+        reopen: Option<OpenFileRequest>,
+    },
 
     /// Read file that is buffered.
     ReadBuffered {
@@ -232,21 +303,32 @@ enum AdditionalRequestData {
     Other,
 }
 
+/// Request saved by [`FilesProxy`] in its [`RequestQueue`].
+#[derive(Debug)]
+struct QueuedRequest {
+    data: AdditionalRequestData,
+    /// Response to be sent to the layer if the connection with the agent is lost before the agent
+    /// responds.
+    agent_lost: Option<AgentLostFileResponse>,
+}
+
 /// Manages state of file operations. Remaps remote file descriptors and returns early
 /// [`ResponseError`]s for [`FileRequest`]s related to invalidated (agent lost) descriptors.
-/// Tracks state of outstanding [`FileRequest`]s to respond with errors in case the agent is lost.
+///
+/// The layer only ever sees user-facing descriptors, and [`FilesProxy`] keeps its state in terms
+/// of them as well. They are translated to the agent's descriptors right before sending the
+/// requests, see [`RouterFileOps::send`].
 #[derive(Default)]
 pub struct RouterFileOps {
     /// Highest file fd we've returned to the client (after remapping).
     highest_user_facing_fd: Option<u64>,
     /// Offset we need to add to every fd we receive from the mirrord-agent.
     /// All lesser fds received from the clients are invalid (probably lost with previous
-    /// mirrord-agent responsible for file ops).
+    /// mirrord-agent responsible for file ops), unless they were reopened.
     current_fd_offset: u64,
-    /// Prepared error responses to outstanding [`FileRequest`]s.
-    /// We must flush these when connection to the mirrord-agent is lost, otherwise the layer will
-    /// hang.
-    queued_error_responses: VecDeque<AgentLostFileResponse>,
+    /// User-facing fds from before the last reconnect that were reopened in the current
+    /// mirrord-agent, mapped to the agent's fds.
+    reopened: HashMap<u64, u64>,
 }
 
 impl fmt::Debug for RouterFileOps {
@@ -254,97 +336,56 @@ impl fmt::Debug for RouterFileOps {
         f.debug_struct("RouterFileOps")
             .field("highest_user_facing_fd", &self.highest_user_facing_fd)
             .field("current_fd_offset", &self.current_fd_offset)
+            .field("reopened", &self.reopened.len())
             .finish()
     }
 }
 
 impl RouterFileOps {
-    /// Return a request to be sent to the agent ([`Ok`] variant) or
+    /// Returns whether the given user-facing fd is valid in the current mirrord-agent.
+    fn is_valid(&self, fd: u64) -> bool {
+        fd >= self.current_fd_offset || self.reopened.contains_key(&fd)
+    }
+
+    /// Return a request to be handled ([`Ok`] variant) or
     /// a response to be sent to the user ([`Err`] variant).
     ///
+    /// Requests that refer to invalid descriptors and do not require a response are dropped.
+    ///
     /// [`Err`] variant is boxed due to large size difference.
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE, Debug))]
-    pub fn map_request(
-        &mut self,
+    pub fn check_request(
+        &self,
         layer_id: LayerId,
         message_id: MessageId,
         mut request: FileRequest,
     ) -> Result<Option<FileRequest>, Box<ToLayer>> {
-        match &mut request {
-            // These requests do not refer to any open remote fd.
-            // It's safe to pass them as they are.
-            FileRequest::Open(..)
-            | FileRequest::Access(..)
-            | FileRequest::Xstat(XstatRequest { fd: None, .. })
-            | FileRequest::ReadLink(..)
-            | FileRequest::MakeDir(..)
-            | FileRequest::Unlink(..)
-            | FileRequest::RemoveDir(..)
-            | FileRequest::StatFs(..)
-            | FileRequest::StatFsV2(..)
-            | FileRequest::Rename(..)
-            | FileRequest::UnlinkAt(UnlinkAtRequest { dirfd: None, .. }) => {}
-
-            // These requests do not require any response from the agent.
-            // We need to remap the fd, but if the fd is invalid we simply drop them.
-            FileRequest::Close(CloseFileRequest { fd: remote_fd })
-            | FileRequest::CloseDir(CloseDirRequest { remote_fd }) => {
-                if *remote_fd < self.current_fd_offset {
-                    return Ok(None);
-                }
-
-                *remote_fd -= self.current_fd_offset;
-            }
-
-            // These requests refer to an open remote fd and require a response from the agent.
-            // We need to remap the fd and respond with an error if the fd is invalid.
-            FileRequest::FdOpenDir(FdOpenDirRequest { remote_fd })
-            | FileRequest::GetDEnts64(GetDEnts64Request { remote_fd, .. })
-            | FileRequest::OpenRelative(OpenRelativeFileRequest {
-                relative_fd: remote_fd,
-                ..
-            })
-            | FileRequest::Read(ReadFileRequest { remote_fd, .. })
-            | FileRequest::ReadDir(ReadDirRequest { remote_fd, .. })
-            | FileRequest::ReadDirBatch(ReadDirBatchRequest { remote_fd, .. })
-            | FileRequest::ReadLimited(ReadLimitedFileRequest { remote_fd, .. })
-            | FileRequest::Seek(SeekFileRequest { fd: remote_fd, .. })
-            | FileRequest::Write(WriteFileRequest { fd: remote_fd, .. })
-            | FileRequest::WriteLimited(WriteLimitedFileRequest { remote_fd, .. })
-            | FileRequest::Xstat(XstatRequest {
-                fd: Some(remote_fd),
-                ..
-            })
-            | FileRequest::XstatFs(XstatFsRequest { fd: remote_fd })
-            | FileRequest::XstatFsV2(XstatFsRequestV2 { fd: remote_fd })
-            | FileRequest::MakeDirAt(MakeDirAtRequest {
-                dirfd: remote_fd, ..
-            })
-            | FileRequest::UnlinkAt(UnlinkAtRequest {
-                dirfd: Some(remote_fd),
-                ..
-            })
-            | FileRequest::Ftruncate(FtruncateRequest { fd: remote_fd, .. })
-            | FileRequest::Futimens(FutimensRequest { fd: remote_fd, .. })
-            | FileRequest::Fchown(FchownRequest { fd: remote_fd, .. })
-            | FileRequest::Fchmod(FchmodRequest { fd: remote_fd, .. }) => {
-                if *remote_fd < self.current_fd_offset {
-                    let error_response = request
-                        .agent_lost_response(layer_id, message_id)
-                        .expect("these requests require responses")
-                        .into();
-                    return Err(Box::new(error_response));
-                }
+        let stale = request
+            .remote_fd_mut()
+            .is_some_and(|fd| self.is_valid(*fd).not());
+        if stale.not() {
+            return Ok(Some(request));
+        }
 
-                *remote_fd -= self.current_fd_offset;
-            }
-        };
+        // Requests that do not require a response are simply dropped.
+        match request.agent_lost_response(layer_id, message_id) {
+            Some(response) => Err(Box::new(response.into())),
+            None => Ok(None),
+        }
+    }
 
-        if let Some(response) = request.agent_lost_response(layer_id, message_id) {
-            self.queued_error_responses.push_back(response);
+    /// Sends the request to the agent, translating the user-facing fd it refers to.
+    async fn send(&self, mut request: FileRequest, message_bus: &mut MessageBus<FilesProxy>) {
+        if let Some(fd) = request.remote_fd_mut() {
+            *fd = match self.reopened.get(fd) {
+                Some(agent_fd) => *agent_fd,
+                None => *fd - self.current_fd_offset,
+            };
         }
 
-        Ok(Some(request))
+        message_bus
+            .send_agent(ClientMessage::FileRequest(request))
+            .await;
     }
 
     /// Return a response to be sent to the client.
@@ -374,7 +415,9 @@ impl RouterFileOps {
             | FileResponse::Ftruncate(..)
             | FileResponse::Futimens(..)
             | FileResponse::Fchown(..)
-            | FileResponse::Fchmod(..) => {}
+            | FileResponse::Fchmod(..)
+            | FileResponse::GetXattr(..)
+            | FileResponse::ListXattr(..) => {}
 
             FileResponse::GetDEnts64(Ok(GetDEnts64Response { fd: remote_fd, .. }))
             | FileResponse::Open(Ok(OpenFileResponse { fd: remote_fd }))
@@ -387,17 +430,28 @@ impl RouterFileOps {
             }
         }
 
-        self.queued_error_responses.pop_front();
-
         response
     }
 
+    /// Notify this manager that the user-facing fd was reopened in the current mirrord-agent as
+    /// `agent_fd`.
+    #[tracing::instrument(level = Level::TRACE)]
+    pub fn reopened(&mut self, fd: u64, agent_fd: u64) {
+        self.reopened.insert(fd, agent_fd);
+    }
+
+    /// Notify this manager that the user-facing fd was closed.
+    #[tracing::instrument(level = Level::TRACE)]
+    pub fn closed(&mut self, fd: u64) {
+        self.reopened.remove(&fd);
+    }
+
     /// Notify this manager that the agent was lost.
-    /// Return messages to be sent to the user.
+    /// All fds received so far become invalid.
     #[tracing::instrument(level = Level::TRACE)]
-    pub fn agent_lost(&mut self) -> VecDeque<AgentLostFileResponse> {
+    pub fn agent_lost(&mut self) {
         self.current_fd_offset = self.highest_user_facing_fd.map(|fd| fd + 1).unwrap_or(0);
-        std::mem::take(&mut self.queued_error_responses)
+        self.reopened.clear();
     }
 }
 
@@ -425,6 +479,14 @@ impl RouterFileOps {
 ///    buffer. If it's not possible, we proceed as in point 1
 /// 4. To solve problems with descriptor offset, we only use [`FileRequest::ReadLimited`] to read
 ///    buffered files. Descriptor offset value is maintained in this proxy.
+///
+/// # Reconnects
+///
+/// When the connection with the agent is refreshed, outstanding requests fail with
+/// [`agent_lost_io_error`], and so do the requests that refer to descriptors opened in the previous
+/// agent. The exception are buffered files opened with [`FileRequest::Open`]: these are reopened
+/// in the new agent, and keep their descriptor offsets, as they are maintained in this proxy.
+/// Layer requests are held until all of them are reopened.
 pub struct FilesProxy {
     /// [`mirrord_protocol`] version negotiated with the agent.
     /// Determines whether we can use some messages, like [`FileRequest::ReadDirBatch`] or
@@ -436,7 +498,7 @@ pub struct FilesProxy {
     file_buffer_size: u64,
 
     /// Stores metadata of outstanding requests.
-    request_queue: RequestQueue<AdditionalRequestData>,
+    request_queue: RequestQueue<QueuedRequest>,
 
     /// For tracking remote file descriptors across layer instances (forks).
     remote_files: RemoteResources<u64>,
@@ -449,6 +511,13 @@ pub struct FilesProxy {
     buffered_dirs: HashMap<u64, BufferedDirData>,
 
     reconnect_tracker: RouterFileOps,
+    /// Buffered files that can be reopened after the connection with the agent is refreshed,
+    /// with their original requests.
+    reopenable_files: HashMap<u64, OpenFileRequest>,
+    /// Files that are being reopened in the new agent, in the order of the sent requests.
+    reopening_files: VecDeque<u64>,
+    /// Layer requests received while [`Self::reopening_files`] is not empty.
+    held_requests: VecDeque<(MessageId, LayerId, FileRequest)>,
 }
 
 impl fmt::Debug for FilesProxy {
@@ -461,6 +530,9 @@ impl fmt::Debug for FilesProxy {
             .field("protocol_version", &self.protocol_version)
             .field("request_queue", &self.request_queue)
             .field("reconnect_tracker", &self.reconnect_tracker)
+            .field("reopenable_files", &self.reopenable_files.len())
+            .field("reopening_files", &self.reopening_files)
+            .field("held_requests", &self.held_requests.len())
             .finish()
     }
 }
@@ -489,6 +561,9 @@ impl FilesProxy {
             buffered_dirs: Default::default(),
 
             reconnect_tracker: Default::default(),
+            reopenable_files: Default::default(),
+            reopening_files: Default::default(),
+            held_requests: Default::default(),
         }
     }
 
@@ -512,25 +587,54 @@ impl FilesProxy {
 
     #[tracing::instrument(level = Level::TRACE, skip(message_bus))]
     async fn layer_closed(&mut self, closed: LayerClosed, message_bus: &mut MessageBus<Self>) {
-        for fd in self.remote_files.remove_all(closed.id) {
-            self.buffered_files.remove(&fd);
-            message_bus
-                .send_agent(ClientMessage::FileRequest(FileRequest::Close(
-                    CloseFileRequest { fd },
-                )))
-                .await;
+        let files = self.remote_files.remove_all(closed.id).collect::<Vec<_>>();
+        for fd in files {
+            self.file_closed(fd);
+            // Files that are still being reopened are closed in `Self::reopen_response`.
+            if self.reconnect_tracker.is_valid(fd) {
+                self.reconnect_tracker
+                    .send(FileRequest::Close(CloseFileRequest { fd }), message_bus)
+                    .await;
+            }
         }
 
-        for remote_fd in self.remote_dirs.remove_all(closed.id) {
+        let dirs = self.remote_dirs.remove_all(closed.id).collect::<Vec<_>>();
+        for remote_fd in dirs {
             self.buffered_dirs.remove(&remote_fd);
-            message_bus
-                .send_agent(ClientMessage::FileRequest(FileRequest::CloseDir(
-                    CloseDirRequest { remote_fd },
-                )))
+            self.reconnect_tracker
+                .send(
+                    FileRequest::CloseDir(CloseDirRequest { remote_fd }),
+                    message_bus,
+                )
                 .await;
         }
     }
 
+    /// Removes local state of a file that was closed in all layer instances.
+    fn file_closed(&mut self, fd: u64) {
+        self.buffered_files.remove(&fd);
+        self.reopenable_files.remove(&fd);
+        self.reconnect_tracker.closed(fd);
+    }
+
+    /// Saves the request in [`Self::request_queue`] and sends it to the agent.
+    async fn send_request(
+        &mut self,
+        message_id: MessageId,
+        layer_id: LayerId,
+        data: AdditionalRequestData,
+        request: FileRequest,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let agent_lost = request.agent_lost_response(layer_id, message_id);
+        self.request_queue.push_back_with_data(
+            message_id,
+            layer_id,
+            QueuedRequest { data, agent_lost },
+        );
+        self.reconnect_tracker.send(request, message_bus).await;
+    }
+
     #[tracing::instrument(level = Level::TRACE)]
     fn protocol_version(&mut self, version: Version) {
         self.protocol_version.replace(version);
@@ -579,6 +683,18 @@ impl FilesProxy {
             {
                 Err(FileResponse::Rename(Err(ResponseError::NotImplemented)))
             }
+            FileRequest::GetXattr(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| XATTR_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::GetXattr(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::ListXattr(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| XATTR_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::ListXattr(Err(ResponseError::NotImplemented)))
+            }
             _ => Ok(()),
         }
     }
@@ -614,9 +730,9 @@ impl FilesProxy {
             // Should trigger remote close only when the fd is closed in all layer instances.
             FileRequest::Close(close) => {
                 if self.remote_files.remove(layer_id, close.fd) {
-                    self.buffered_files.remove(&close.fd);
-                    message_bus
-                        .send_agent(ClientMessage::FileRequest(FileRequest::Close(close)))
+                    self.file_closed(close.fd);
+                    self.reconnect_tracker
+                        .send(FileRequest::Close(close), message_bus)
                         .await;
                 }
             }
@@ -625,8 +741,8 @@ impl FilesProxy {
             FileRequest::CloseDir(close) => {
                 if self.remote_dirs.remove(layer_id, close.remote_fd) {
                     self.buffered_dirs.remove(&close.remote_fd);
-                    message_bus
-                        .send_agent(ClientMessage::FileRequest(FileRequest::CloseDir(close)))
+                    self.reconnect_tracker
+                        .send(FileRequest::CloseDir(close), message_bus)
                         .await;
                 }
             }
@@ -634,29 +750,38 @@ impl FilesProxy {
             // May require storing additional data in the request queue.
             FileRequest::Open(open) => {
                 let additional_data = if self.buffer_reads() && open.open_options.is_read_only() {
-                    AdditionalRequestData::OpenBuffered
+                    AdditionalRequestData::OpenBuffered {
+                        reopen: Some(open.clone()),
+                    }
                 } else {
                     Default::default()
                 };
-                self.request_queue
-                    .push_back_with_data(message_id, layer_id, additional_data);
-                message_bus
-                    .send_agent(ClientMessage::FileRequest(FileRequest::Open(open)))
-                    .await;
+                self.send_request(
+                    message_id,
+                    layer_id,
+                    additional_data,
+                    FileRequest::Open(open),
+                    message_bus,
+                )
+                .await;
             }
 
             // May require storing additional data in the request queue.
+            // Relative opens are not reopened, their directory descriptors are lost.
             FileRequest::OpenRelative(open) => {
                 let additional_data = if self.buffer_reads() && open.open_options.is_read_only() {
-                    AdditionalRequestData::OpenBuffered
+                    AdditionalRequestData::OpenBuffered { reopen: None }
                 } else {
                     Default::default()
                 };
-                self.request_queue
-                    .push_back_with_data(message_id, layer_id, additional_data);
-                message_bus
-                    .send_agent(ClientMessage::FileRequest(FileRequest::OpenRelative(open)))
-                    .await;
+                self.send_request(
+                    message_id,
+                    layer_id,
+                    additional_data,
+                    FileRequest::OpenRelative(open),
+                    message_bus,
+                )
+                .await;
             }
 
             // Try to use local buffer if possible.
@@ -685,32 +810,32 @@ impl FilesProxy {
                             requested_amount: read.buffer_size,
                             update_fd_position: true,
                         };
-                        self.request_queue.push_back_with_data(
+                        let request = FileRequest::ReadLimited(ReadLimitedFileRequest {
+                            remote_fd: read.remote_fd,
+                            buffer_size: std::cmp::max(read.buffer_size, self.file_buffer_size),
+                            start_from: data.fd_position,
+                        });
+                        self.send_request(
                             message_id,
                             layer_id,
                             additional_data,
-                        );
-                        message_bus
-                            .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(
-                                ReadLimitedFileRequest {
-                                    remote_fd: read.remote_fd,
-                                    buffer_size: std::cmp::max(
-                                        read.buffer_size,
-                                        self.file_buffer_size,
-                                    ),
-                                    start_from: data.fd_position,
-                                },
-                            )))
-                            .await;
+                            request,
+                            message_bus,
+                        )
+                        .await;
                     }
                 }
 
                 // File is not buffered.
                 None => {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send_agent(ClientMessage::FileRequest(FileRequest::Read(read)))
-                        .await;
+                    self.send_request(
+                        message_id,
+                        layer_id,
+                        Default::default(),
+                        FileRequest::Read(read),
+                        message_bus,
+                    )
+                    .await;
                 }
             },
 
@@ -739,32 +864,32 @@ impl FilesProxy {
                             requested_amount: read.buffer_size,
                             update_fd_position: false,
                         };
-                        self.request_queue.push_back_with_data(
+                        let request = FileRequest::ReadLimited(ReadLimitedFileRequest {
+                            remote_fd: read.remote_fd,
+                            buffer_size: std::cmp::max(read.buffer_size, self.file_buffer_size),
+                            start_from: read.start_from,
+                        });
+                        self.send_request(
                             message_id,
                             layer_id,
                             additional_data,
-                        );
-                        message_bus
-                            .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(
-                                ReadLimitedFileRequest {
-                                    remote_fd: read.remote_fd,
-                                    buffer_size: std::cmp::max(
-                                        read.buffer_size,
-                                        self.file_buffer_size,
-                                    ),
-                                    start_from: read.start_from,
-                                },
-                            )))
-                            .await;
+                            request,
+                            message_bus,
+                        )
+                        .await;
                     }
                 }
 
                 // File is not buffered.
                 None => {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(read)))
-                        .await;
+                    self.send_request(
+                        message_id,
+                        layer_id,
+                        Default::default(),
+                        FileRequest::ReadLimited(read),
+                        message_bus,
+                    )
+                    .await;
                 }
             },
 
@@ -786,24 +911,30 @@ impl FilesProxy {
                             })
                             .await;
                     } else {
-                        self.request_queue.push_back(message_id, layer_id);
-                        message_bus
-                            .send_agent(ClientMessage::FileRequest(FileRequest::ReadDirBatch(
-                                ReadDirBatchRequest {
-                                    remote_fd: read_dir.remote_fd,
-                                    amount: Self::READDIR_BATCH_SIZE,
-                                },
-                            )))
-                            .await;
+                        self.send_request(
+                            message_id,
+                            layer_id,
+                            Default::default(),
+                            FileRequest::ReadDirBatch(ReadDirBatchRequest {
+                                remote_fd: read_dir.remote_fd,
+                                amount: Self::READDIR_BATCH_SIZE,
+                            }),
+                            message_bus,
+                        )
+                        .await;
                     }
                 }
 
                 // Directory is not buffered.
                 None => {
-                    self.request_queue.push_back(message_id, layer_id);
-                    message_bus
-                        .send_agent(ClientMessage::FileRequest(FileRequest::ReadDir(read_dir)))
-                        .await;
+                    self.send_request(
+                        message_id,
+                        layer_id,
+                        Default::default(),
+                        FileRequest::ReadDir(read_dir),
+                        message_bus,
+                    )
+                    .await;
                 }
             },
 
@@ -843,11 +974,14 @@ impl FilesProxy {
                         _ => AdditionalRequestData::Other,
                     };
 
-                self.request_queue
-                    .push_back_with_data(message_id, layer_id, additional_data);
-                message_bus
-                    .send_agent(ClientMessage::FileRequest(FileRequest::Seek(seek)))
-                    .await;
+                self.send_request(
+                    message_id,
+                    layer_id,
+                    additional_data,
+                    FileRequest::Seek(seek),
+                    message_bus,
+                )
+                .await;
             }
             FileRequest::StatFsV2(statfs_v2)
                 if self
@@ -855,12 +989,14 @@ impl FilesProxy {
                     .as_ref()
                     .is_none_or(|version| !STATFS_V2_VERSION.matches(version)) =>
             {
-                self.request_queue.push_back(message_id, layer_id);
-                message_bus
-                    .send_agent(ClientMessage::FileRequest(FileRequest::StatFs(
-                        statfs_v2.into(),
-                    )))
-                    .await;
+                self.send_request(
+                    message_id,
+                    layer_id,
+                    Default::default(),
+                    FileRequest::StatFs(statfs_v2.into()),
+                    message_bus,
+                )
+                .await;
             }
             FileRequest::XstatFsV2(xstatfs_v2)
                 if self
@@ -868,19 +1004,19 @@ impl FilesProxy {
                     .as_ref()
                     .is_none_or(|version| !STATFS_V2_VERSION.matches(version)) =>
             {
-                self.request_queue.push_back(message_id, layer_id);
-                message_bus
-                    .send_agent(ClientMessage::FileRequest(FileRequest::XstatFs(
-                        xstatfs_v2.into(),
-                    )))
-                    .await;
+                self.send_request(
+                    message_id,
+                    layer_id,
+                    Default::default(),
+                    FileRequest::XstatFs(xstatfs_v2.into()),
+                    message_bus,
+                )
+                .await;
             }
 
             // Doesn't require any special logic.
             other => {
-                self.request_queue.push_back(message_id, layer_id);
-                message_bus
-                    .send_agent(ClientMessage::FileRequest(other))
+                self.send_request(message_id, layer_id, Default::default(), other, message_bus)
                     .await;
             }
         }
@@ -895,7 +1031,7 @@ impl FilesProxy {
         match response {
             // Update file maps.
             FileResponse::Open(Ok(open)) => {
-                let (message_id, layer_id, additional_data) =
+                let (message_id, layer_id, QueuedRequest { data, .. }) =
                     self.request_queue.pop_front_with_data().ok_or_else(|| {
                         UnexpectedAgentMessage(
                             DaemonMessage::File(FileResponse::Open(Ok(open.clone()))).into(),
@@ -904,8 +1040,11 @@ impl FilesProxy {
 
                 self.remote_files.add(layer_id, open.fd);
 
-                if matches!(additional_data, AdditionalRequestData::OpenBuffered) {
+                if let AdditionalRequestData::OpenBuffered { reopen } = data {
                     self.buffered_files.insert(open.fd, Default::default());
+                    if let Some(reopen) = reopen {
+                        self.reopenable_files.insert(open.fd, reopen);
+                    }
                 }
 
                 message_bus
@@ -942,7 +1081,7 @@ impl FilesProxy {
 
             // If the file is buffered, update `files_data`.
             FileResponse::ReadLimited(Ok(read)) => {
-                let (message_id, layer_id, additional_data) =
+                let (message_id, layer_id, QueuedRequest { data, .. }) =
                     self.request_queue.pop_front_with_data().ok_or_else(|| {
                         UnexpectedAgentMessage(
                             DaemonMessage::File(FileResponse::ReadLimited(Ok(read.clone()))).into(),
@@ -953,7 +1092,7 @@ impl FilesProxy {
                     fd,
                     requested_amount,
                     update_fd_position,
-                } = additional_data
+                } = data
                 else {
                     // This file is not buffered.
                     message_bus
@@ -1014,7 +1153,7 @@ impl FilesProxy {
             FileResponse::ReadLimited(Err(error)) => {
                 // need to ensure that if a Read request was sent by layer, a Read response is
                 // returned containing the error rather than a ReadLimited
-                let (message_id, layer_id, additional_data) =
+                let (message_id, layer_id, QueuedRequest { data, .. }) =
                     self.request_queue.pop_front_with_data().ok_or_else(|| {
                         UnexpectedAgentMessage(
                             DaemonMessage::File(FileResponse::ReadLimited(Err(error.clone())))
@@ -1022,7 +1161,7 @@ impl FilesProxy {
                         )
                     })?;
 
-                let message = match additional_data {
+                let message = match data {
                     AdditionalRequestData::ReadBuffered {
                         update_fd_position, ..
                     } if update_fd_position => FileResponse::Read(Err(error)),
@@ -1040,14 +1179,14 @@ impl FilesProxy {
 
             // If the file is buffered, update `files_data`.
             FileResponse::Seek(Ok(seek)) => {
-                let (message_id, layer_id, additional_data) =
+                let (message_id, layer_id, QueuedRequest { data, .. }) =
                     self.request_queue.pop_front_with_data().ok_or_else(|| {
                         UnexpectedAgentMessage(
                             DaemonMessage::File(FileResponse::Seek(Ok(seek.clone()))).into(),
                         )
                     })?;
 
-                if let AdditionalRequestData::SeekBuffered { fd } = additional_data {
+                if let AdditionalRequestData::SeekBuffered { fd } = data {
                     let Some(data) = self.buffered_files.get_mut(&fd) else {
                         // File must have been closed from other thread in user application.
                         message_bus
@@ -1146,6 +1285,84 @@ impl FilesProxy {
         Ok(())
     }
 
+    /// Handles the response to a [`FileRequest::Open`] sent to reopen a file after the
+    /// connection with the agent was refreshed.
+    ///
+    /// Handles the held layer requests once all files are reopened.
+    #[tracing::instrument(level = Level::DEBUG, skip(message_bus), ret, err)]
+    async fn reopen_response(
+        &mut self,
+        response: FileResponse,
+        message_bus: &mut MessageBus<Self>,
+    ) -> Result<(), FilesProxyError> {
+        let Some(fd) = self.reopening_files.pop_front() else {
+            return Err(UnexpectedAgentMessage(DaemonMessage::File(response).into()).into());
+        };
+
+        match response {
+            FileResponse::Open(Ok(open)) if self.reopenable_files.contains_key(&fd) => {
+                tracing::debug!(fd, agent_fd = open.fd, "Reopened a remote file");
+                self.reconnect_tracker.reopened(fd, open.fd);
+            }
+
+            // The file was closed in the meantime.
+            FileResponse::Open(Ok(open)) => {
+                message_bus
+                    .send_agent(ClientMessage::FileRequest(FileRequest::Close(
+                        CloseFileRequest { fd: open.fd },
+                    )))
+                    .await;
+            }
+
+            FileResponse::Open(Err(error)) => {
+                tracing::warn!(
+                    fd,
+                    ?error,
+                    "Failed to reopen a remote file after a reconnect"
+                );
+                self.file_closed(fd);
+                self.remote_files.retain(|remote_fd| *remote_fd != fd);
+            }
+
+            other => {
+                return Err(UnexpectedAgentMessage(DaemonMessage::File(other).into()).into());
+            }
+        }
+
+        if self.reopening_files.is_empty() {
+            while let Some((message_id, layer_id, request)) = self.held_requests.pop_front() {
+                self.layer_request(request, layer_id, message_id, message_bus)
+                    .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a [`FileRequest`] from the layer, unless it refers to a descriptor that was lost
+    /// with the previous agent.
+    async fn layer_request(
+        &mut self,
+        request: FileRequest,
+        layer_id: LayerId,
+        message_id: MessageId,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        match self
+            .reconnect_tracker
+            .check_request(layer_id, message_id, request)
+        {
+            Ok(None) => {}
+            Err(response) => {
+                message_bus.send(*response).await;
+            }
+            Ok(Some(request)) => {
+                self.file_request(request, layer_id, message_id, message_bus)
+                    .await
+            }
+        };
+    }
+
     #[tracing::instrument(level = Level::INFO, skip(message_bus), ret)]
     async fn handle_reconnect(
         &mut self,
@@ -1154,14 +1371,39 @@ impl FilesProxy {
     ) {
         match refresh {
             ConnectionRefresh::Start => {
+                self.reconnect_tracker.agent_lost();
+
+                let responses = self
+                    .request_queue
+                    .drain()
+                    .filter_map(|(_, _, request)| request.agent_lost)
+                    .chain(self.held_requests.drain(..).filter_map(
+                        |(message_id, layer_id, request)| {
+                            request.agent_lost_response(layer_id, message_id)
+                        },
+                    ))
+                    .collect::<Vec<_>>();
+                tracing::debug!(
+                    num_responses = responses.len(),
+                    "Flushing error responses to file requests"
+                );
+                for response in responses {
+                    message_bus.send(ToLayer::from(response)).await;
+                }
+
+                // Files that were being reopened are reopened again with the next agent.
+                self.reopening_files.clear();
                 let files_to_drop = self
                     .remote_files
-                    .drain()
-                    .flat_map(|(_, fds)| fds)
-                    .collect::<HashSet<_>>();
+                    .retain(|fd| self.reopenable_files.contains_key(fd));
                 tracing::debug!(?files_to_drop, "Dropping remote files");
                 for fd in files_to_drop {
-                    self.buffered_files.remove(&fd);
+                    self.file_closed(fd);
+                }
+                // Remaining buffered files will be reopened, their contents are fetched again.
+                for data in self.buffered_files.values_mut() {
+                    data.buffer = Default::default();
+                    data.buffer_position = 0;
                 }
 
                 let directories_to_drop = self
@@ -1174,20 +1416,23 @@ impl FilesProxy {
                     self.buffered_dirs.remove(&fd);
                 }
 
-                let responses = self.reconnect_tracker.agent_lost();
-                tracing::debug!(
-                    num_responses = responses.len(),
-                    "Flushing error responses to file requests"
-                );
-                for response in responses {
-                    message_bus.send(ToLayer::from(response)).await;
-                }
                 // Reset protocol version since we'll need another negotiation
                 // round for the new connection.
                 self.protocol_version = None;
             }
             ConnectionRefresh::End(tx_handle) => {
                 message_bus.set_agent_tx(tx_handle);
+
+                tracing::debug!(
+                    files_to_reopen = self.reopenable_files.len(),
+                    "Reopening remote files"
+                );
+                for (fd, open) in &self.reopenable_files {
+                    self.reopening_files.push_back(*fd);
+                    message_bus
+                        .send_agent(ClientMessage::FileRequest(FileRequest::Open(open.clone())))
+                        .await;
+                }
             }
             ConnectionRefresh::Request => {}
         }
@@ -1203,20 +1448,18 @@ impl BackgroundTask for FilesProxy {
     async fn run(&mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
         while let Some(message) = message_bus.recv().await {
             match message {
+                FilesProxyMessage::FileReq(message_id, layer_id, request)
+                    if self.reopening_files.is_empty().not() =>
+                {
+                    self.held_requests
+                        .push_back((message_id, layer_id, request));
+                }
                 FilesProxyMessage::FileReq(message_id, layer_id, request) => {
-                    match self
-                        .reconnect_tracker
-                        .map_request(layer_id, message_id, request)
-                    {
-                        Ok(None) => {}
-                        Err(response) => {
-                            message_bus.send(*response).await;
-                        }
-                        Ok(Some(request)) => {
-                            self.file_request(request, layer_id, message_id, message_bus)
-                                .await
-                        }
-                    };
+                    self.layer_request(request, layer_id, message_id, message_bus)
+                        .await;
+                }
+                FilesProxyMessage::FileRes(response) if self.reopening_files.is_empty().not() => {
+                    self.reopen_response(response, message_bus).await?;
                 }
                 FilesProxyMessage::FileRes(response) => {
                     let response = self.reconnect_tracker.map_response(response);
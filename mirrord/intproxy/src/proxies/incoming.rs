@@ -49,6 +49,7 @@ use crate::{
         BackgroundTask, BackgroundTasks, MessageBus, TaskError, TaskSender, TaskUpdate,
     },
     main_tasks::{ConnectionRefresh, LayerClosed, LayerForked, ToLayer},
+    replicas::is_replica_connection,
 };
 
 mod bound_socket;
@@ -205,6 +206,10 @@ pub struct IncomingProxy {
     protocol_version: Option<Version>,
 
     restore_subscriptions_on_protocol_version_switch: bool,
+
+    /// Whether mirror messages should also be sent to the agents of the other replicas of the
+    /// target, see [`ReplicaMirrors`](crate::replicas::ReplicaMirrors).
+    mirror_replicas: bool,
 }
 
 impl IncomingProxy {
@@ -230,6 +235,39 @@ impl IncomingProxy {
             tasks: None,
             protocol_version: None,
             restore_subscriptions_on_protocol_version_switch: false,
+            mirror_replicas: false,
+        }
+    }
+
+    /// Makes this proxy send its mirror messages to the agents of the other replicas of the
+    /// target as well, see [`ReplicaMirrors`](crate::replicas::ReplicaMirrors).
+    pub fn with_replica_mirrors(mut self, enabled: bool) -> Self {
+        self.mirror_replicas = enabled;
+        self
+    }
+
+    /// Sends the given message to the agent.
+    ///
+    /// If [`Self::mirror_replicas`] is set, mirror messages are passed to the
+    /// [`ReplicaMirrors`](crate::replicas::ReplicaMirrors) as well. Connections mirrored from the
+    /// other replicas are unsubscribed only there.
+    async fn send_agent(&self, message: ClientMessage, message_bus: &MessageBus<Self>) {
+        match message {
+            ClientMessage::Tcp(message) if self.mirror_replicas => {
+                let replica_only = matches!(
+                    message,
+                    LayerTcp::ConnectionUnsubscribe(connection_id)
+                        if is_replica_connection(connection_id)
+                );
+                if replica_only.not() {
+                    message_bus
+                        .send_agent(ClientMessage::Tcp(message.clone()))
+                        .await;
+                }
+
+                message_bus.send(ProxyMessage::ToReplicas(message)).await;
+            }
+            message => message_bus.send_agent(message).await,
         }
     }
 
@@ -363,7 +401,7 @@ impl IncomingProxy {
             } else {
                 ClientMessage::Tcp(LayerTcp::ConnectionUnsubscribe(connection_id))
             };
-            message_bus.send_agent(message).await;
+            self.send_agent(message, message_bus).await;
 
             return Ok(());
         };
@@ -640,6 +678,12 @@ impl IncomingProxy {
                     message_bus.send(msg).await;
                 }
             }
+
+            DaemonTcp::HttpFilterReport(report) => {
+                message_bus
+                    .send(ProxyMessage::HttpFilterReport(report))
+                    .await;
+            }
         }
 
         Ok(())
@@ -663,7 +707,7 @@ impl IncomingProxy {
                     );
                     match msg {
                         Some(Either::Left(m)) => message_bus.send(m).await,
-                        Some(Either::Right(m)) => message_bus.send_agent(m).await,
+                        Some(Either::Right(m)) => self.send_agent(m, message_bus).await,
                         None => (),
                     };
                 }
@@ -672,7 +716,7 @@ impl IncomingProxy {
                     let msg = self.subscriptions.layer_unsubscribed(layer_id, unsubscribe);
 
                     if let Some(msg) = msg {
-                        message_bus.send_agent(msg).await;
+                        self.send_agent(msg, message_bus).await;
                     }
                 }
                 IncomingRequest::ConnMetadata(req) => {
@@ -701,7 +745,7 @@ impl IncomingProxy {
                 let msgs = self.subscriptions.layer_closed(msg.id);
 
                 for msg in msgs {
-                    message_bus.send_agent(msg).await;
+                    self.send_agent(msg, message_bus).await;
                 }
             }
 
@@ -714,14 +758,17 @@ impl IncomingProxy {
                 self.protocol_version.replace(protocol_version);
 
                 if self.restore_subscriptions_on_protocol_version_switch {
-                    for subscription in self.subscriptions.iter_mut() {
-                        tracing::info!(?subscription, "Resubscribing after connection refresh");
-
-                        message_bus
-                            .send_agent(
-                                subscription.resubscribe_message(self.protocol_version.as_ref()),
-                            )
-                            .await
+                    let messages = self
+                        .subscriptions
+                        .iter_mut()
+                        .map(|subscription| {
+                            tracing::info!(?subscription, "Resubscribing after connection refresh");
+                            subscription.resubscribe_message(self.protocol_version.as_ref())
+                        })
+                        .collect::<Vec<_>>();
+
+                    for message in messages {
+                        self.send_agent(message, message_bus).await;
                     }
                     self.restore_subscriptions_on_protocol_version_switch = false;
                 }
@@ -791,11 +838,11 @@ impl IncomingProxy {
                         ))
                         .await;
                 } else if send_close {
-                    message_bus
-                        .send_agent(ClientMessage::Tcp(LayerTcp::ConnectionUnsubscribe(
-                            connection_id,
-                        )))
-                        .await;
+                    self.send_agent(
+                        ClientMessage::Tcp(LayerTcp::ConnectionUnsubscribe(connection_id)),
+                        message_bus,
+                    )
+                    .await;
                 }
             }
 
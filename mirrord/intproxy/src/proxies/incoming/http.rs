@@ -1,7 +1,7 @@
 use std::{fmt, io, net::SocketAddr, ops::Not};
 
 use hyper::{
-    Request, Response, StatusCode, Version,
+    Method, Request, Response, StatusCode, Version,
     body::Incoming,
     client::conn::{http1, http2},
 };
@@ -123,6 +123,15 @@ impl LocalHttpError {
     }
 }
 
+/// Checks whether the local application's response upgrades the connection: `101 Switching
+/// Protocols`, or a `2xx` response to a `CONNECT` request.
+///
+/// After an upgrade, the connection no longer carries HTTP, and its data is proxied as it is.
+pub fn is_upgrade(request_method: &Method, response_status: StatusCode) -> bool {
+    response_status == StatusCode::SWITCHING_PROTOCOLS
+        || (*request_method == Method::CONNECT && response_status.is_success())
+}
+
 /// Produces a mirrord-specific [`StatusCode::BAD_GATEWAY`] response.
 pub fn mirrord_error_response<M: fmt::Display>(
     message: M,
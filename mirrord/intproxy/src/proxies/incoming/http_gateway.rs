@@ -9,7 +9,7 @@ use std::{
 };
 
 use http_body_util::BodyExt;
-use hyper::{StatusCode, body::Incoming, http::response::Parts};
+use hyper::{body::Incoming, http::response::Parts};
 use mirrord_protocol::{
     ClientMessage, Payload,
     batched_body::BatchedBody,
@@ -24,7 +24,10 @@ use tokio_retry::strategy::ExponentialBackoff;
 use tracing::Level;
 
 use super::{
-    http::{ClientStore, LocalHttpError, ResponseMode, StreamingBody, mirrord_error_response},
+    http::{
+        ClientStore, LocalHttpError, ResponseMode, StreamingBody, is_upgrade,
+        mirrord_error_response,
+    },
     tasks::{HttpOut, InProxyTaskMessage},
 };
 use crate::background_tasks::{BackgroundTask, MessageBus};
@@ -237,10 +240,11 @@ impl HttpGatewayTask {
             )
             .await?;
         let mut response = client.send_request(self.request.clone()).await?;
-        let on_upgrade = (response.status() == StatusCode::SWITCHING_PROTOCOLS).then(|| {
-            tracing::debug!("Detected an HTTP upgrade");
-            hyper::upgrade::on(&mut response)
-        });
+        let on_upgrade =
+            is_upgrade(&self.request.internal_request.method, response.status()).then(|| {
+                tracing::debug!("Detected an HTTP upgrade");
+                hyper::upgrade::on(&mut response)
+            });
         let (parts, mut body) = response.into_parts();
 
         let flow = match self.response_mode {
@@ -490,7 +494,8 @@ mod test {
 
     const INITIAL_MESSAGE: &[u8] = &[0x4a, 0x50, 0x32, 0x47, 0x4d, 0x44];
 
-    /// Handles requests upgrading to the [`TEST_PROTO`] protocol.
+    /// Handles requests upgrading to the [`TEST_PROTO`] protocol, and `CONNECT` requests, which
+    /// also get the [`TEST_PROTO`] protocol.
     async fn upgrade_req_handler(
         mut req: Request<Incoming>,
     ) -> hyper::Result<Response<Empty<Bytes>>> {
@@ -515,12 +520,13 @@ mod test {
 
         let mut res = Response::new(Empty::new());
 
+        let is_connect = req.method() == Method::CONNECT;
         let contains_expected_upgrade = req
             .headers()
             .get(UPGRADE)
             .filter(|proto| *proto == TEST_PROTO)
             .is_some();
-        if !contains_expected_upgrade {
+        if !is_connect && !contains_expected_upgrade {
             *res.status_mut() = StatusCode::BAD_REQUEST;
             return Ok(res);
         }
@@ -536,6 +542,10 @@ mod test {
             }
         });
 
+        if is_connect {
+            return Ok(res);
+        }
+
         *res.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
         res.headers_mut()
             .insert(UPGRADE, HeaderValue::from_static(TEST_PROTO));
@@ -610,14 +620,18 @@ mod test {
     }
 
     /// Verifies that [`HttpGatewayTask`] and [`TcpProxyTask`] together correctly handle HTTP
-    /// upgrades.
+    /// upgrades, and `CONNECT` requests.
     #[rstest]
     #[case::with_tls_mirror(true, false)]
     #[case::with_tls_steal(true, true)]
     #[case::without_tls_mirror(false, false)]
     #[case::without_tls_steal(false, true)]
     #[tokio::test]
-    async fn handles_http_upgrades(#[case] use_tls: bool, #[case] is_steal: bool) {
+    async fn handles_http_upgrades(
+        #[case] use_tls: bool,
+        #[case] is_steal: bool,
+        #[values(false, true)] connect: bool,
+    ) {
         let _ = rustls::crypto::CryptoProvider::install_default(
             rustls::crypto::aws_lc_rs::default_provider(),
         );
@@ -652,17 +666,27 @@ mod test {
                 connection_id: 0,
                 request_id: 0,
                 port: 80,
-                internal_request: InternalHttpRequest {
-                    method: Method::GET,
-                    uri: "dummyecho://metalbear.com/mirrord/".parse().unwrap(),
-                    headers: [
-                        (CONNECTION, HeaderValue::from_static("upgrade")),
-                        (UPGRADE, HeaderValue::from_static(TEST_PROTO)),
-                    ]
-                    .into_iter()
-                    .collect(),
-                    version: Version::HTTP_11,
-                    body: Default::default(),
+                internal_request: if connect {
+                    InternalHttpRequest {
+                        method: Method::CONNECT,
+                        uri: "metalbear.com:443".parse().unwrap(),
+                        headers: Default::default(),
+                        version: Version::HTTP_11,
+                        body: Default::default(),
+                    }
+                } else {
+                    InternalHttpRequest {
+                        method: Method::GET,
+                        uri: "dummyecho://metalbear.com/mirrord/".parse().unwrap(),
+                        headers: [
+                            (CONNECTION, HeaderValue::from_static("upgrade")),
+                            (UPGRADE, HeaderValue::from_static(TEST_PROTO)),
+                        ]
+                        .into_iter()
+                        .collect(),
+                        version: Version::HTTP_11,
+                        body: Default::default(),
+                    }
                 },
             };
             let gateway = HttpGatewayTask::new(
@@ -688,6 +712,9 @@ mod test {
         if is_steal {
             let message = proxy_rx.next().await.expect("no task result");
             match message {
+                ClientMessage::TcpSteal(LayerTcpSteal::HttpResponse(res)) if connect => {
+                    assert_eq!(res.internal_response.status, StatusCode::OK);
+                }
                 ClientMessage::TcpSteal(LayerTcpSteal::HttpResponse(res)) => {
                     assert_eq!(
                         res.internal_response.status,
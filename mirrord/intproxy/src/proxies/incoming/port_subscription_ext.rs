@@ -3,7 +3,10 @@
 use mirrord_intproxy_protocol::PortSubscription;
 use mirrord_protocol::{
     ClientMessage, Port,
-    tcp::{LayerTcp, LayerTcpSteal, MIRROR_HTTP_FILTER_VERSION, MirrorType, StealType},
+    tcp::{
+        HTTP_FILTER_DRY_RUN_VERSION, LayerTcp, LayerTcpSteal, MIRROR_HTTP_FILTER_VERSION,
+        MirrorType, StealType,
+    },
 };
 
 /// Retrieves subscribed port from the given [`StealType`].
@@ -36,8 +39,8 @@ impl PortSubscriptionExt for PortSubscription {
         }
     }
 
-    /// [`LayerTcp::PortSubscribe`], [`LayerTcp::PortSubscribeFilteredHttp`], or
-    /// [`LayerTcpSteal::PortSubscribe`].
+    /// [`LayerTcp::PortSubscribe`], [`LayerTcp::PortSubscribeFilteredHttp`],
+    /// [`LayerTcp::PortSubscribeDryRun`], or [`LayerTcpSteal::PortSubscribe`].
     fn agent_subscribe(&self, protocol_version: Option<&semver::Version>) -> ClientMessage {
         match self {
             Self::Mirror(mirror_type) => match mirror_type {
@@ -61,6 +64,22 @@ impl PortSubscriptionExt for PortSubscription {
                         ClientMessage::Tcp(LayerTcp::PortSubscribe(*port))
                     }
                 }
+                MirrorType::DryRun(port, filter) => {
+                    if protocol_version
+                        .is_some_and(|version| HTTP_FILTER_DRY_RUN_VERSION.matches(version))
+                    {
+                        ClientMessage::Tcp(LayerTcp::PortSubscribeDryRun(*port, filter.clone()))
+                    } else {
+                        // The requests matching the filter are at least mirrored.
+                        tracing::warn!(
+                            ?protocol_version,
+                            "Negotiated mirrord-protocol version does not allow for the HTTP filter dry run. \
+                            The requests matching the filter will be mirrored instead."
+                        );
+                        Self::Mirror(MirrorType::FilteredHttp(*port, filter.clone()))
+                            .agent_subscribe(protocol_version)
+                    }
+                }
                 MirrorType::All(_) => {
                     ClientMessage::Tcp(LayerTcp::PortSubscribe(mirror_type.get_port()))
                 }
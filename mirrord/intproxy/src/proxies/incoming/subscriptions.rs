@@ -188,6 +188,12 @@ impl SubscriptionsManager {
     /// Subsequent subscriptions of the same port will take precedence over previous ones, meaning
     /// that new connections will be routed to the listener from the most recent [`PortSubscribe`]
     /// request.
+    ///
+    /// Subsequent subscriptions must use the same [`PortSubscription`] (e.g. the same HTTP filter),
+    /// as the agent holds only one subscription per port for this session. Conflicting ones are
+    /// rejected with [`ResponseError::ConflictingPortSubscription`].
+    ///
+    /// [`PortSubscription`]: mirrord_intproxy_protocol::PortSubscription
     #[tracing::instrument(level = Level::INFO, skip(self), ret)]
     pub fn layer_subscribed(
         &mut self,
@@ -196,12 +202,29 @@ impl SubscriptionsManager {
         request: PortSubscribe,
         protocol_version: Option<&Version>,
     ) -> Option<Either<ProxyMessage, ClientMessage>> {
-        self.remote_ports.add(
-            layer_id,
-            (request.subscription.port(), request.listening_on),
-        );
-
         let port = request.subscription.port();
+
+        if let Some(subscription) = self.subscriptions.get(&port)
+            && subscription.active_source.request.subscription != request.subscription
+        {
+            tracing::warn!(
+                active = ?subscription.active_source.request.subscription,
+                requested = ?request.subscription,
+                "Rejecting a port subscription that conflicts with the active one",
+            );
+
+            return Some(Either::Left(ProxyMessage::ToLayer(ToLayer {
+                layer_id,
+                message_id,
+                message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Err(
+                    ResponseError::ConflictingPortSubscription(port),
+                ))),
+            })));
+        }
+
+        self.remote_ports
+            .add(layer_id, (port, request.listening_on));
+
         let source = Source {
             layer: layer_id,
             message: message_id,
@@ -263,12 +286,16 @@ impl SubscriptionsManager {
                 Ok(subscription.confirm())
             }
 
-            Err(ResponseError::PortAlreadyStolen(port)) => {
+            Err(
+                ref response_error @ (ResponseError::PortAlreadyStolen(port)
+                | ResponseError::PortStealConflict { port, .. }
+                | ResponseError::StealTlsSetup { port, .. }),
+            ) => {
                 let Some(subscription) = self.subscriptions.remove(&port) else {
                     return Ok(vec![]);
                 };
 
-                match subscription.reject(ResponseError::PortAlreadyStolen(port)) {
+                match subscription.reject(response_error.clone()) {
                     Ok(responses) => Ok(responses),
                     Err(subscription) => {
                         self.subscriptions.insert(port, *subscription);
@@ -346,7 +373,7 @@ impl SubscriptionsManager {
 #[cfg(test)]
 mod test {
     use mirrord_intproxy_protocol::PortSubscription;
-    use mirrord_protocol::tcp::{LayerTcp, MirrorType};
+    use mirrord_protocol::tcp::{Filter, HttpFilter, LayerTcp, MirrorType, StealType};
 
     use super::*;
 
@@ -432,6 +459,86 @@ mod test {
         assert!(manager.get(80).is_none());
     }
 
+    #[test]
+    fn with_conflicting_subscribe() {
+        let mut manager = SubscriptionsManager::default();
+
+        let response = manager.layer_subscribed(
+            LayerId(0),
+            0,
+            PortSubscribe {
+                listening_on: "127.0.0.1:1111".parse().unwrap(),
+                subscription: PortSubscription::Mirror(MirrorType::All(80)),
+            },
+            None,
+        );
+        assert!(matches!(response, Some(Either::Right(..))), "{response:?}");
+
+        let response = manager.layer_subscribed(
+            LayerId(0),
+            1,
+            PortSubscribe {
+                listening_on: "127.0.0.1:2222".parse().unwrap(),
+                subscription: PortSubscription::Mirror(MirrorType::FilteredHttp(
+                    80,
+                    HttpFilter::Path(Filter::new("^/webhooks/".into()).unwrap()),
+                )),
+            },
+            None,
+        );
+        assert!(
+            matches!(
+                response,
+                Some(Either::Left(ProxyMessage::ToLayer(ToLayer {
+                    layer_id: LayerId(0),
+                    message_id: 1,
+                    message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Err(
+                        ResponseError::ConflictingPortSubscription(80)
+                    ))),
+                })))
+            ),
+            "{response:?}"
+        );
+
+        let responses = manager.agent_responded(Ok(80)).unwrap();
+        assert_eq!(responses.len(), 1, "{responses:?}");
+        assert_eq!(
+            manager.get(80).unwrap().listening_on,
+            "127.0.0.1:1111".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn with_steal_conflict() {
+        let mut manager = SubscriptionsManager::default();
+
+        let response = manager.layer_subscribed(
+            LayerId(0),
+            0,
+            PortSubscribe {
+                listening_on: "127.0.0.1:1111".parse().unwrap(),
+                subscription: PortSubscription::Steal(StealType::All(80)),
+            },
+            None,
+        );
+        assert!(matches!(response, Some(Either::Right(..))), "{response:?}");
+
+        let error = ResponseError::PortStealConflict {
+            port: 80,
+            holder: Some("alice@laptop".into()),
+        };
+        let responses = manager.agent_responded(Err(error.clone())).unwrap();
+        assert_eq!(
+            responses,
+            [ToLayer {
+                layer_id: LayerId(0),
+                message_id: 0,
+                message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Err(error))),
+            }]
+        );
+        assert!(manager.get(80).is_none());
+    }
+
     #[test]
     fn with_fork() {
         let listening_on = "127.0.0.1:1111".parse().unwrap();
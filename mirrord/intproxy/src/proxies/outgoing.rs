@@ -19,7 +19,7 @@ use semver::Version;
 use thiserror::Error;
 use tracing::Level;
 
-use self::interceptor::Interceptor;
+use self::interceptor::{Interceptor, InterceptorMessage};
 use crate::{
     ProxyMessage,
     background_tasks::{
@@ -279,7 +279,7 @@ impl OutgoingProxy {
             tokio::time::sleep(std::time::Duration::from_millis(self.receive_delay_ms)).await;
         }
 
-        interceptor.send(bytes.0).await;
+        interceptor.send(InterceptorMessage::Bytes(bytes.0)).await;
 
         Ok(())
     }
@@ -507,8 +507,10 @@ impl OutgoingProxy {
     ) {
         match refresh {
             ConnectionRefresh::Start => {
-                tracing::debug!("Closing all local connections");
-                self.txs.clear();
+                tracing::debug!("Resetting all local connections");
+                for interceptor in std::mem::take(&mut self.txs).into_values() {
+                    interceptor.send(InterceptorMessage::Reset).await;
+                }
                 self.background_tasks.as_mut().unwrap().clear();
                 self.protocol_version = None;
 
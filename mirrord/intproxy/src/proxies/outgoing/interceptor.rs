@@ -12,6 +12,15 @@ use crate::{
     proxies::outgoing::net_protocol_ext::PreparedSocket,
 };
 
+/// Messages consumed by the [`Interceptor`].
+#[derive(Debug)]
+pub enum InterceptorMessage {
+    /// Data from the agent, a 0-sized read is a shutdown.
+    Bytes(Bytes),
+    /// The connection with the agent was lost, the connection with the layer is reset.
+    Reset,
+}
+
 /// Manages a single intercepted connection.
 /// Multiple instances are run as [`BackgroundTask`]s by one [`OutgoingProxy`](super::OutgoingProxy)
 /// to manage individual connections.
@@ -33,7 +42,7 @@ impl Interceptor {
 
 impl BackgroundTask for Interceptor {
     type Error = io::Error;
-    type MessageIn = Bytes;
+    type MessageIn = InterceptorMessage;
     type MessageOut = Bytes;
 
     /// Accepts one connection the owned [`PreparedSocket`] and transparently proxies bytes between
@@ -48,8 +57,11 @@ impl BackgroundTask for Interceptor {
     /// 2. A 0-sized read received from the [`MessageBus`] is treated as a shutdown on the agent
     ///    side. Connection with the peer is shut down as well.
     ///
-    /// 3. This implementation exits only when an error is encountered or the [`MessageBus`] is
-    ///    closed.
+    /// 3. [`InterceptorMessage::Reset`] resets the connection with the peer, see
+    ///    [`ConnectedSocket::reset`](crate::proxies::outgoing::net_protocol_ext::ConnectedSocket::reset).
+    ///
+    /// 4. This implementation exits only when an error is encountered, the connection is reset, or
+    ///    the [`MessageBus`] is closed.
     #[tracing::instrument(
         level = Level::DEBUG,
         name = "outgoing_interceptor_main_loop"
@@ -91,7 +103,7 @@ impl BackgroundTask for Interceptor {
                 },
 
                 msg = message_bus.recv() => match msg {
-                    Some(bytes) => {
+                    Some(InterceptorMessage::Bytes(bytes)) => {
                         if bytes.is_empty() {
                             tracing::trace!("Agent shutdown, shutting down connection with layer");
                             connected_socket.shutdown().await?;
@@ -101,6 +113,11 @@ impl BackgroundTask for Interceptor {
                         }
                     }
 
+                    Some(InterceptorMessage::Reset) => {
+                        tracing::trace!("Agent connection lost, resetting connection with layer");
+                        break connected_socket.reset();
+                    }
+
                     None => {
                         tracing::trace!("Connection closed from the outgoing_proxy side, exiting");
                         break Ok(())
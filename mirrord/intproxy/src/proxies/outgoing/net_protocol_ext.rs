@@ -6,6 +6,7 @@ use std::{env, path::PathBuf};
 use std::{
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
 };
 
 #[cfg(not(target_os = "windows"))]
@@ -281,6 +282,23 @@ impl ConnectedSocket {
         }
     }
 
+    /// Makes the connection reset when this socket is dropped, so that the layer gets
+    /// [`io::ErrorKind::ConnectionReset`] instead of a clean shutdown.
+    ///
+    /// # Note
+    ///
+    /// This is a no-op for UDP and unix sockets, which are just closed.
+    pub fn reset(&self) -> io::Result<()> {
+        match &self.inner {
+            InnerConnectedSocket::TcpStream(stream) => {
+                socket2::SockRef::from(stream).set_linger(Some(Duration::ZERO))
+            }
+            #[cfg(not(target_os = "windows"))]
+            InnerConnectedSocket::UnixStream(..) => Ok(()),
+            InnerConnectedSocket::UdpSocket(..) => Ok(()),
+        }
+    }
+
     /// Shuts the connection down. See [`AsyncWriteExt::shutdown`].
     ///
     /// # Note
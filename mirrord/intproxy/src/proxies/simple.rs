@@ -1,8 +1,10 @@
 //! The most basic proxying logic. Handles cases when the only job to do in the internal proxy is to
 //! pass requests and responses between the layer and the agent.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Instant};
 
+use dns_cache::{DnsCache, DnsCacheKey};
+use mirrord_config::feature::network::dns::DnsCacheConfig;
 use mirrord_intproxy_protocol::{LayerId, MessageId, ProxyToLayerMessage};
 use mirrord_protocol::{
     ClientMessage, DaemonMessage, DnsLookupError, GetEnvVarsRequest, RemoteResult,
@@ -13,6 +15,8 @@ use semver::Version;
 use thiserror::Error;
 use tracing::Level;
 
+mod dns_cache;
+
 use crate::{
     ProxyMessage,
     background_tasks::{BackgroundTask, MessageBus},
@@ -24,7 +28,8 @@ use crate::{
 #[derive(Debug)]
 pub enum SimpleProxyMessage {
     AddrInfoReq(MessageId, LayerId, GetAddrInfoRequestV2),
-    AddrInfoRes(GetAddrInfoResponse),
+    /// Response from the agent, with the TTL of the lookup in seconds, if the agent sent it.
+    AddrInfoRes(GetAddrInfoResponse, Option<u32>),
     GetEnvReq(MessageId, LayerId, GetEnvVarsRequest),
     GetEnvRes(RemoteResult<HashMap<String, String>>),
     /// Protocol version was negotiated with the agent.
@@ -88,8 +93,10 @@ impl From<AgentLostSimpleResponse> for ToLayer {
 /// For passing messages between the layer and the agent without custom internal logic.
 /// Run as a [`BackgroundTask`].
 pub struct SimpleProxy {
-    /// For [`GetAddrInfoRequestV2`]s.
-    addr_info_reqs: RequestQueue,
+    /// For [`GetAddrInfoRequestV2`]s, with the [`DnsCacheKey`]s of the requests to be cached.
+    addr_info_reqs: RequestQueue<Option<DnsCacheKey>>,
+    /// Responses to [`GetAddrInfoRequestV2`]s.
+    dns_cache: DnsCache,
     /// For [`GetEnvVarsRequest`]s.
     get_env_reqs: RequestQueue,
     /// [`mirrord_protocol`] version negotiated with the agent.
@@ -100,9 +107,10 @@ pub struct SimpleProxy {
 }
 
 impl SimpleProxy {
-    pub fn new(dns_permission_error_fatal: bool) -> Self {
+    pub fn new(dns_permission_error_fatal: bool, dns_cache: &DnsCacheConfig) -> Self {
         Self {
             addr_info_reqs: Default::default(),
+            dns_cache: DnsCache::new(dns_cache),
             get_env_reqs: Default::default(),
             protocol_version: Default::default(),
             dns_permission_error_fatal,
@@ -155,6 +163,9 @@ impl SimpleProxy {
                 // Reset protocol version since we'll need another negotiation
                 // round for the new connection.
                 self.protocol_version = None;
+
+                // The new connection may be to a different agent.
+                self.dns_cache.clear();
             }
             ConnectionRefresh::End(tx_handle) => message_bus.set_agent_tx(tx_handle),
             ConnectionRefresh::Request => {}
@@ -171,8 +182,23 @@ impl BackgroundTask for SimpleProxy {
     async fn run(&mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
         while let Some(msg) = message_bus.recv().await {
             match msg {
-                SimpleProxyMessage::AddrInfoReq(message_id, session_id, req) => {
-                    self.addr_info_reqs.push_back(message_id, session_id);
+                SimpleProxyMessage::AddrInfoReq(message_id, layer_id, req) => {
+                    let cache_key = match self.dns_cache.get(&req, Instant::now()) {
+                        Ok(res) => {
+                            message_bus
+                                .send(ToLayer {
+                                    message_id,
+                                    message: ProxyToLayerMessage::GetAddrInfo(res),
+                                    layer_id,
+                                })
+                                .await;
+                            continue;
+                        }
+                        Err(cache_key) => cache_key,
+                    };
+
+                    self.addr_info_reqs
+                        .push_back_with_data(message_id, layer_id, cache_key);
                     if self.addr_info_v2() {
                         message_bus
                             .send_agent(ClientMessage::GetAddrInfoRequestV2(req))
@@ -191,20 +217,24 @@ impl BackgroundTask for SimpleProxy {
                             .await;
                     }
                 }
-                SimpleProxyMessage::AddrInfoRes(GetAddrInfoResponse(Err(
-                    ResponseError::DnsLookup(DnsLookupError {
+                SimpleProxyMessage::AddrInfoRes(
+                    GetAddrInfoResponse(Err(ResponseError::DnsLookup(DnsLookupError {
                         kind: ResolveErrorKindInternal::PermissionDenied,
-                    }),
-                ))) if self.dns_permission_error_fatal => {
+                    }))),
+                    _,
+                ) if self.dns_permission_error_fatal => {
                     return Err(SimpleProxyError::DnsPermissionDenied);
                 }
-                SimpleProxyMessage::AddrInfoRes(res) => {
-                    let (message_id, layer_id) =
-                        self.addr_info_reqs.pop_front().ok_or_else(|| {
+                SimpleProxyMessage::AddrInfoRes(res, ttl) => {
+                    let (message_id, layer_id, cache_key) =
+                        self.addr_info_reqs.pop_front_with_data().ok_or_else(|| {
                             UnexpectedAgentMessage(
                                 DaemonMessage::GetAddrInfoResponse(res.clone()).into(),
                             )
                         })?;
+                    if let Some(cache_key) = cache_key {
+                        self.dns_cache.insert(cache_key, &res, ttl, Instant::now());
+                    }
                     message_bus
                         .send(ToLayer {
                             message_id,
@@ -243,7 +273,12 @@ impl BackgroundTask for SimpleProxy {
             }
         }
 
-        tracing::debug!("Message bus closed, exiting");
+        let (hits, misses) = self.dns_cache.stats();
+        tracing::debug!(
+            dns_cache_hits = hits,
+            dns_cache_misses = misses,
+            "Message bus closed, exiting"
+        );
 
         Ok(())
     }
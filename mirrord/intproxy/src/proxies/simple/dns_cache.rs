@@ -0,0 +1,335 @@
+//! Cache of the [`GetAddrInfoResponse`]s received from the agent, see [`DnsCache`].
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use mirrord_config::feature::network::dns::DnsCacheConfig;
+use mirrord_protocol::{
+    DnsLookupError, ResolveErrorKindInternal, ResponseError,
+    dns::{AddressFamily, GetAddrInfoRequestV2, GetAddrInfoResponse},
+};
+
+/// DNS response code of a lookup for a hostname that does not exist.
+const NXDOMAIN: u16 = 3;
+
+/// Identifies cached lookups.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DnsCacheKey {
+    node: String,
+    family: AddressFamily,
+}
+
+impl From<&GetAddrInfoRequestV2> for DnsCacheKey {
+    fn from(request: &GetAddrInfoRequestV2) -> Self {
+        Self {
+            node: request.node.to_ascii_lowercase(),
+            family: request.family,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DnsCacheEntry {
+    response: GetAddrInfoResponse,
+    expires_at: Instant,
+    /// Value of [`DnsCache::clock`] when this entry was last used, for evicting the least recently
+    /// used entry.
+    last_used: u64,
+}
+
+/// Bounded cache of [`GetAddrInfoResponse`]s, keyed by the hostname and the [`AddressFamily`].
+///
+/// Successful lookups are cached for the TTL returned by the agent, clamped to
+/// [`DnsCacheConfig::max_ttl`]. Lookups of hostnames that do not exist are cached for
+/// [`DnsCacheConfig::negative_ttl`]. Other errors and lookups without a TTL (from agents that don't
+/// send it) are never cached.
+#[derive(Debug)]
+pub struct DnsCache {
+    enabled: bool,
+    max_ttl: Duration,
+    negative_ttl: Duration,
+    max_entries: usize,
+    entries: HashMap<DnsCacheKey, DnsCacheEntry>,
+    /// Incremented on every access, used instead of timestamps for the LRU order.
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl DnsCache {
+    pub fn new(config: &DnsCacheConfig) -> Self {
+        Self {
+            enabled: config.enabled && config.max_entries > 0,
+            max_ttl: Duration::from_secs(config.max_ttl),
+            negative_ttl: Duration::from_secs(config.negative_ttl),
+            max_entries: config.max_entries,
+            entries: Default::default(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the cached response for the given request, if there is one that has not expired.
+    ///
+    /// Returns the [`DnsCacheKey`] to be used with [`DnsCache::insert`] on a miss, or [`None`]
+    /// when the cache is disabled.
+    pub fn get(
+        &mut self,
+        request: &GetAddrInfoRequestV2,
+        now: Instant,
+    ) -> Result<GetAddrInfoResponse, Option<DnsCacheKey>> {
+        if !self.enabled {
+            return Err(None);
+        }
+
+        let key = DnsCacheKey::from(request);
+        self.clock += 1;
+
+        match self.entries.get_mut(&key) {
+            Some(entry) if entry.expires_at > now => {
+                entry.last_used = self.clock;
+                self.hits += 1;
+                tracing::debug!(
+                    node = %key.node,
+                    family = ?key.family,
+                    hits = self.hits,
+                    misses = self.misses,
+                    "DNS cache hit",
+                );
+                Ok(entry.response.clone())
+            }
+            expired => {
+                if expired.is_some() {
+                    self.entries.remove(&key);
+                }
+                self.misses += 1;
+                tracing::debug!(
+                    node = %key.node,
+                    family = ?key.family,
+                    hits = self.hits,
+                    misses = self.misses,
+                    "DNS cache miss",
+                );
+                Err(Some(key))
+            }
+        }
+    }
+
+    /// Caches the response received from the agent, if it's cacheable.
+    ///
+    /// `ttl` is the TTL of the lookup in seconds, as returned by the agent.
+    pub fn insert(
+        &mut self,
+        key: DnsCacheKey,
+        response: &GetAddrInfoResponse,
+        ttl: Option<u32>,
+        now: Instant,
+    ) {
+        let ttl = match (&response.0, ttl) {
+            (Ok(..), Some(ttl)) => Duration::from_secs(ttl.into()).min(self.max_ttl),
+            (
+                Err(ResponseError::DnsLookup(DnsLookupError {
+                    kind: ResolveErrorKindInternal::NoRecordsFound(NXDOMAIN),
+                })),
+                _,
+            ) => self.negative_ttl,
+            _ => return,
+        };
+        if ttl.is_zero() {
+            return;
+        }
+
+        if self.entries.len() >= self.max_entries && !self.entries.contains_key(&key) {
+            self.evict(now);
+        }
+
+        self.clock += 1;
+        self.entries.insert(
+            key,
+            DnsCacheEntry {
+                response: response.clone(),
+                expires_at: now + ttl,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// Removes the expired entries, or the least recently used one if none has expired.
+    fn evict(&mut self, now: Instant) {
+        let len = self.entries.len();
+        self.entries.retain(|_, entry| entry.expires_at > now);
+        if self.entries.len() < len {
+            return;
+        }
+
+        let lru = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = lru {
+            self.entries.remove(&key);
+        }
+    }
+
+    /// Removes all entries, e.g. when the connection with the agent is refreshed.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the number of cache hits and misses so far.
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::{Duration, Instant},
+    };
+
+    use mirrord_config::feature::network::dns::DnsCacheConfig;
+    use mirrord_protocol::{
+        DnsLookupError, ResolveErrorKindInternal, ResponseError,
+        dns::{
+            AddressFamily, DnsLookup, GetAddrInfoRequestV2, GetAddrInfoResponse, LookupRecord,
+            SockType,
+        },
+    };
+    use rstest::rstest;
+
+    use super::DnsCache;
+
+    fn request(node: &str, family: AddressFamily) -> GetAddrInfoRequestV2 {
+        GetAddrInfoRequestV2 {
+            node: node.into(),
+            service_port: 0,
+            family,
+            socktype: SockType::Any,
+            flags: 0,
+            protocol: 0,
+        }
+    }
+
+    fn resolved(node: &str) -> GetAddrInfoResponse {
+        GetAddrInfoResponse(Ok(DnsLookup(vec![LookupRecord {
+            name: node.into(),
+            ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        }])))
+    }
+
+    fn failed(kind: ResolveErrorKindInternal) -> GetAddrInfoResponse {
+        GetAddrInfoResponse(Err(ResponseError::DnsLookup(DnsLookupError { kind })))
+    }
+
+    fn config(max_entries: usize) -> DnsCacheConfig {
+        DnsCacheConfig {
+            enabled: true,
+            max_ttl: 30,
+            negative_ttl: 5,
+            max_entries,
+        }
+    }
+
+    #[rstest]
+    #[case::short_ttl(resolved("db"), Some(10), Some(10))]
+    #[case::clamped_ttl(resolved("db"), Some(3600), Some(30))]
+    #[case::zero_ttl(resolved("db"), Some(0), None)]
+    #[case::no_ttl(resolved("db"), None, None)]
+    #[case::nxdomain(failed(ResolveErrorKindInternal::NoRecordsFound(3)), None, Some(5))]
+    #[case::servfail(failed(ResolveErrorKindInternal::NoRecordsFound(2)), None, None)]
+    #[case::timeout(failed(ResolveErrorKindInternal::Timeout), None, None)]
+    fn cached_for(
+        #[case] response: GetAddrInfoResponse,
+        #[case] ttl: Option<u32>,
+        #[case] expected_secs: Option<u64>,
+    ) {
+        let mut cache = DnsCache::new(&config(16));
+        let now = Instant::now();
+        let request = request("db", AddressFamily::Ipv4Only);
+
+        let key = cache.get(&request, now).unwrap_err().unwrap();
+        cache.insert(key, &response, ttl, now);
+
+        match expected_secs {
+            Some(secs) => {
+                let cached = Duration::from_secs(secs);
+                assert_eq!(
+                    cache.get(&request, now + cached - Duration::from_millis(1)),
+                    Ok(response)
+                );
+                assert!(cache.get(&request, now + cached).is_err());
+            }
+            None => assert!(cache.get(&request, now).is_err()),
+        }
+    }
+
+    #[test]
+    fn keyed_by_name_and_family() {
+        let mut cache = DnsCache::new(&config(16));
+        let now = Instant::now();
+
+        let key = cache
+            .get(&request("DB.default", AddressFamily::Ipv4Only), now)
+            .unwrap_err()
+            .unwrap();
+        cache.insert(key, &resolved("db.default"), Some(10), now);
+
+        assert!(
+            cache
+                .get(&request("db.default", AddressFamily::Ipv4Only), now)
+                .is_ok()
+        );
+        assert!(
+            cache
+                .get(&request("db.default", AddressFamily::Ipv6Only), now)
+                .is_err()
+        );
+        assert_eq!(cache.stats(), (1, 2));
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = DnsCache::new(&config(2));
+        let now = Instant::now();
+
+        for node in ["a", "b"] {
+            let key = cache
+                .get(&request(node, AddressFamily::Any), now)
+                .unwrap_err()
+                .unwrap();
+            cache.insert(key, &resolved(node), Some(10), now);
+        }
+
+        // `a` becomes the most recently used.
+        assert!(cache.get(&request("a", AddressFamily::Any), now).is_ok());
+
+        let key = cache
+            .get(&request("c", AddressFamily::Any), now)
+            .unwrap_err()
+            .unwrap();
+        cache.insert(key, &resolved("c"), Some(10), now);
+
+        assert!(cache.get(&request("a", AddressFamily::Any), now).is_ok());
+        assert!(cache.get(&request("b", AddressFamily::Any), now).is_err());
+        assert!(cache.get(&request("c", AddressFamily::Any), now).is_ok());
+    }
+
+    #[test]
+    fn disabled() {
+        let mut cache = DnsCache::new(&DnsCacheConfig {
+            enabled: false,
+            ..config(16)
+        });
+
+        assert_eq!(
+            cache.get(&request("db", AddressFamily::Any), Instant::now()),
+            Err(None)
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     collections::{HashMap, HashSet, hash_map::Entry},
     hash::Hash,
+    ops::Not,
 };
 
 use mirrord_intproxy_protocol::LayerId;
@@ -124,6 +125,29 @@ where
         }
     }
 
+    /// Removes all resources for which `keep` returns `false`, from all layer instances.
+    /// Returns the removed resources.
+    ///
+    /// Can be used when the remote is lost, and only some of the resources can be restored.
+    #[tracing::instrument(level = Level::TRACE, skip_all)]
+    pub(crate) fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F) -> Vec<T> {
+        let mut removed = Vec::new();
+        self.counts.retain(|resource, _| {
+            let retain = keep(resource);
+            if retain.not() {
+                removed.push(resource.clone());
+            }
+            retain
+        });
+
+        self.by_layer.retain(|_, resources| {
+            resources.retain(|resource| self.counts.contains_key(resource));
+            resources.is_empty().not()
+        });
+
+        removed
+    }
+
     /// Removes all resources held by all layers instances.
     /// Returns an [`Iterator`] of layers and remote files/folders that were removed.
     ///
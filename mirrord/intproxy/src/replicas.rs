@@ -0,0 +1,582 @@
+//! Mirroring the incoming traffic of all the replicas of the target workload, see
+//! [`ReplicaMirrors`].
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    convert::Infallible,
+    ops::Not,
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::{StreamExt, stream::BoxStream};
+use mirrord_config::{
+    LayerConfig,
+    target::{Target, TargetConfig, TargetDisplay, pod::PodTarget},
+};
+use mirrord_kube::{
+    api::{
+        container::ContainerConfig,
+        kubernetes::{AgentKubernetesConnectInfo, KubernetesAPI},
+        runtime::replicas::{watch_ready_pods, workload_pod_selector},
+    },
+    error::KubeApiError,
+    kube::runtime::watcher,
+};
+use mirrord_progress::NullProgress;
+use mirrord_protocol::{
+    ClientMessage, ConnectionId, DaemonMessage, LogLevel, Port,
+    tcp::{ChunkedRequest, DaemonTcp, LayerTcp},
+};
+use mirrord_protocol_io::{Client, Connection, ProtocolError, TxHandle};
+use thiserror::Error;
+use tokio::{
+    task::{self, JoinSet},
+    time::{self, MissedTickBehavior},
+};
+use tokio_stream::{StreamMap, StreamNotifyClose};
+use tracing::Level;
+
+use crate::{
+    agent_conn::AgentConnectInfo,
+    background_tasks::{BackgroundTask, MessageBus},
+    main_tasks::ProxyMessage,
+};
+
+/// The lower bits of a [`ConnectionId`] mirrored by one of the [`ReplicaMirrors`] agents hold the
+/// ID assigned by that agent, the higher bits hold the index of the agent.
+const REPLICA_INDEX_SHIFT: u32 = 48;
+
+/// Checks whether the given connection was mirrored by one of the [`ReplicaMirrors`] agents, and
+/// not by the agent of the session.
+pub fn is_replica_connection(connection_id: ConnectionId) -> bool {
+    connection_id >> REPLICA_INDEX_SHIFT != 0
+}
+
+/// Returns the [`ConnectionId`] in the given message, if the message has one.
+fn connection_id_mut(message: &mut DaemonTcp) -> Option<&mut ConnectionId> {
+    let connection_id = match message {
+        DaemonTcp::NewConnectionV1(connection) => &mut connection.connection_id,
+        DaemonTcp::NewConnectionV2(connection) => &mut connection.connection.connection_id,
+        DaemonTcp::Data(data) => &mut data.connection_id,
+        DaemonTcp::Close(close) => &mut close.connection_id,
+        DaemonTcp::HttpRequest(request) => &mut request.connection_id,
+        DaemonTcp::HttpRequestFramed(request) => &mut request.connection_id,
+        DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV1(request)) => {
+            &mut request.connection_id
+        }
+        DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(request)) => {
+            &mut request.connection_id
+        }
+        DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(body)) => &mut body.connection_id,
+        DaemonTcp::HttpRequestChunked(ChunkedRequest::ErrorV1(error)) => &mut error.connection_id,
+        DaemonTcp::HttpRequestChunked(ChunkedRequest::ErrorV2(error)) => &mut error.connection_id,
+        DaemonTcp::SubscribeResult(..) | DaemonTcp::HttpFilterReport(..) => return None,
+    };
+
+    Some(connection_id)
+}
+
+/// Errors that can occur when creating an agent for a replica.
+#[derive(Error, Debug)]
+enum ReplicaAgentError {
+    #[error(transparent)]
+    Kube(#[from] KubeApiError),
+    #[error("protocol error: {0}")]
+    Protocol(#[from] ProtocolError),
+}
+
+/// Messages consumed by [`ReplicaMirrors`] running as a [`BackgroundTask`].
+#[derive(Debug)]
+pub enum ReplicaMirrorsMessage {
+    /// Mirror message that the
+    /// [`IncomingProxy`](crate::proxies::incoming::IncomingProxy) sent to the agent of the
+    /// session.
+    Mirror(LayerTcp),
+}
+
+/// Agent created for one of the replicas.
+struct ReplicaAgent {
+    /// Index of this agent, see [`REPLICA_INDEX_SHIFT`].
+    index: u64,
+    tx: TxHandle<Client>,
+}
+
+/// Mirrors the incoming traffic of the other replicas of the target workload, see
+/// [`IncomingConfig::all_replicas`](mirrord_config::feature::network::incoming::IncomingConfig::all_replicas).
+///
+/// Watches the ready pods of the workload and creates a mirrord-agent for each of them, up to
+/// [`IncomingConfig::max_replicas`](mirrord_config::feature::network::incoming::IncomingConfig::max_replicas)
+/// including the agent of the session. The mirror subscriptions of the session are sent to all
+/// the agents, and the traffic they mirror is passed to the
+/// [`IncomingProxy`](crate::proxies::incoming::IncomingProxy) as if it came from the agent of the
+/// session, with the [`ConnectionId`]s moved out of the way (see [`REPLICA_INDEX_SHIFT`]).
+///
+/// Failures are only logged, as the agent of the session still mirrors the targeted pod.
+pub struct ReplicaMirrors {
+    config: Arc<LayerConfig>,
+    workload: Target,
+    /// Pod targeted by the agent of the session, its traffic is already mirrored.
+    target_pod: String,
+    /// Maximum number of agents created here.
+    max_agents: usize,
+
+    /// Mirror subscriptions of the session, sent to every new agent.
+    subscriptions: HashMap<Port, LayerTcp>,
+    /// Pods of the workload that are ready to be mirrored.
+    ready_pods: BTreeSet<String>,
+    /// Agents of the mirrored pods, by pod name.
+    agents: HashMap<String, ReplicaAgent>,
+    /// Messages from the agents in [`Self::agents`], by pod name.
+    agent_streams: StreamMap<String, StreamNotifyClose<BoxStream<'static, DaemonMessage>>>,
+    /// Pods whose agents are being created, by the ID of the creating task.
+    pending: HashMap<task::Id, String>,
+    creating: JoinSet<Result<Connection<Client>, ReplicaAgentError>>,
+    /// Pods whose agents failed or closed, not mirrored again until they stop being ready.
+    failed: HashSet<String>,
+    /// Index given to the next agent, see [`REPLICA_INDEX_SHIFT`].
+    next_index: u64,
+    /// Whether we've warned that not all the ready pods can be mirrored.
+    warned_about_limit: bool,
+}
+
+impl ReplicaMirrors {
+    /// How often the agents are pinged, keeps the port forwarding alive.
+    const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+    /// Returns [`None`] when the other replicas should not be mirrored: when
+    /// [`IncomingConfig::all_replicas`](mirrord_config::feature::network::incoming::IncomingConfig::all_replicas)
+    /// is not set, when the session does not use an agent created directly by the CLI (the
+    /// operator already mirrors all the pods), or when the target is not a deployment, rollout or
+    /// replica set.
+    pub fn new(config: &LayerConfig, connect_info: &AgentConnectInfo) -> Option<Self> {
+        let incoming = &config.feature.network.incoming;
+        if incoming.all_replicas.not() {
+            return None;
+        }
+
+        let AgentConnectInfo::DirectKubernetes(AgentKubernetesConnectInfo {
+            target_pod: Some(target_pod),
+            ..
+        }) = connect_info
+        else {
+            return None;
+        };
+
+        let workload = match config.target.path.as_ref()? {
+            workload @ (Target::Deployment(..) | Target::Rollout(..) | Target::ReplicaSet(..)) => {
+                workload.clone()
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            config: Arc::new(config.clone()),
+            workload,
+            target_pod: target_pod.clone(),
+            max_agents: incoming.max_replicas().saturating_sub(1),
+            subscriptions: Default::default(),
+            ready_pods: Default::default(),
+            agents: Default::default(),
+            agent_streams: Default::default(),
+            pending: Default::default(),
+            creating: Default::default(),
+            failed: Default::default(),
+            next_index: 1,
+            warned_about_limit: false,
+        })
+    }
+
+    /// Prepares the [`KubernetesAPI`] and starts watching the pods of [`Self::workload`].
+    async fn start(
+        &self,
+    ) -> Result<
+        (
+            Arc<KubernetesAPI>,
+            BoxStream<'static, Result<BTreeSet<String>, watcher::Error>>,
+        ),
+        KubeApiError,
+    > {
+        let api = KubernetesAPI::create(&self.config, &NullProgress).await?;
+        let namespace = self.config.target.namespace.as_deref();
+
+        let selector = workload_pod_selector(api.client(), &self.workload, namespace)
+            .await?
+            .ok_or(KubeApiError::MissingRuntimeData)?;
+        let pods = watch_ready_pods(
+            api.client(),
+            namespace,
+            &selector,
+            self.workload.container().cloned(),
+        )
+        .boxed();
+
+        Ok((Arc::new(api), pods))
+    }
+
+    /// Starts creating agents for the ready pods that are not mirrored yet, and drops the agents
+    /// of the pods that are no longer ready.
+    fn reconcile(&mut self, api: &Arc<KubernetesAPI>) {
+        let ready_pods = &self.ready_pods;
+
+        self.agents.retain(|pod, _| {
+            let ready = ready_pods.contains(pod);
+            if ready.not() {
+                tracing::info!(
+                    pod,
+                    "Stopped mirroring a replica, the pod is no longer ready"
+                );
+                self.agent_streams.remove(pod);
+            }
+            ready
+        });
+        self.failed.retain(|pod| ready_pods.contains(pod));
+
+        let candidates = ready_pods
+            .iter()
+            .filter(|pod| {
+                **pod != self.target_pod
+                    && self.agents.contains_key(*pod).not()
+                    && self.pending.values().any(|pending| pending == *pod).not()
+                    && self.failed.contains(*pod).not()
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let available = self
+            .max_agents
+            .saturating_sub(self.agents.len() + self.pending.len());
+
+        if candidates.len() > available && self.warned_about_limit.not() {
+            tracing::warn!(
+                ready = ready_pods.len(),
+                max_replicas = self.max_agents + 1,
+                "Not all the ready pods of the target workload can be mirrored, \
+                increase `feature.network.incoming.max_replicas` to mirror more of them",
+            );
+            self.warned_about_limit = true;
+        }
+
+        for pod in candidates.into_iter().take(available) {
+            tracing::debug!(pod, "Creating an agent for a replica");
+
+            let api = api.clone();
+            let config = self.config.clone();
+            let target = PodTarget {
+                pod: pod.clone(),
+                container: self.workload.container().cloned(),
+            };
+            let handle = self
+                .creating
+                .spawn(async move { Self::create_agent(&api, &config, target).await });
+            self.pending.insert(handle.id(), pod);
+        }
+    }
+
+    /// Creates an agent targeting the given pod and connects to it.
+    async fn create_agent(
+        api: &KubernetesAPI,
+        config: &LayerConfig,
+        target: PodTarget,
+    ) -> Result<Connection<Client>, ReplicaAgentError> {
+        let target = TargetConfig {
+            path: Some(Target::Pod(target)),
+            ..config.target.clone()
+        };
+        let container_config = ContainerConfig {
+            support_ipv6: config.feature.network.ipv6,
+            ignore_local_traffic: config.feature.network.incoming.ignore_localhost,
+            ..Default::default()
+        };
+
+        let connect_info = api
+            .create_agent(&mut NullProgress, &target, None, container_config)
+            .await?;
+        let stream = api.create_connection_portforward(connect_info).await?;
+
+        Ok(Connection::from_stream(stream).await?)
+    }
+
+    /// Registers the agent created for the given pod, and sends it the mirror subscriptions of
+    /// the session.
+    async fn agent_created(
+        &mut self,
+        pod: String,
+        result: Result<Connection<Client>, ReplicaAgentError>,
+    ) {
+        let connection = match result {
+            Ok(connection) => connection,
+            Err(error) => {
+                tracing::warn!(pod, %error, "Failed to create an agent for a replica");
+                self.failed.insert(pod);
+                return;
+            }
+        };
+
+        if self.ready_pods.contains(&pod).not() {
+            tracing::debug!(
+                pod,
+                "Dropping the agent of a replica, the pod is no longer ready"
+            );
+            return;
+        }
+
+        let index = self.next_index;
+        self.next_index += 1;
+
+        let tx = connection.tx_handle();
+        tx.send(ClientMessage::SwitchProtocolVersion(
+            mirrord_protocol::VERSION.clone(),
+        ))
+        .await;
+        for message in self.subscriptions.values() {
+            tx.send(ClientMessage::Tcp(message.clone())).await;
+        }
+
+        let messages = futures::stream::unfold(connection, |mut connection| async move {
+            let message = connection.recv().await?;
+            Some((message, connection))
+        })
+        .boxed();
+        self.agent_streams
+            .insert(pod.clone(), StreamNotifyClose::new(messages));
+        self.agents.insert(pod.clone(), ReplicaAgent { index, tx });
+
+        tracing::info!(pod, index, "Started mirroring a replica");
+    }
+
+    /// Handles a mirror message that was sent to the agent of the session.
+    async fn handle_mirror_message(&mut self, message: LayerTcp) {
+        match &message {
+            LayerTcp::PortSubscribe(port)
+            | LayerTcp::PortSubscribeFilteredHttp(port, _)
+            | LayerTcp::PortSubscribeDryRun(port, _) => {
+                self.subscriptions.insert(*port, message.clone());
+            }
+            LayerTcp::PortUnsubscribe(port) => {
+                self.subscriptions.remove(port);
+            }
+            LayerTcp::ConnectionUnsubscribe(connection_id) => {
+                let index = connection_id >> REPLICA_INDEX_SHIFT;
+                let original_id = connection_id & ((1 << REPLICA_INDEX_SHIFT) - 1);
+
+                if let Some(agent) = self.agents.values().find(|agent| agent.index == index) {
+                    agent
+                        .tx
+                        .send(ClientMessage::Tcp(LayerTcp::ConnectionUnsubscribe(
+                            original_id,
+                        )))
+                        .await;
+                }
+
+                return;
+            }
+        }
+
+        for agent in self.agents.values() {
+            agent.tx.send(ClientMessage::Tcp(message.clone())).await;
+        }
+    }
+
+    /// Handles a message from the agent of the given pod.
+    #[tracing::instrument(level = Level::TRACE, skip(self, message_bus))]
+    async fn handle_agent_message(
+        &mut self,
+        pod: String,
+        message: DaemonMessage,
+        message_bus: &MessageBus<Self>,
+    ) {
+        let Some(index) = self.agents.get(&pod).map(|agent| agent.index) else {
+            return;
+        };
+
+        match message {
+            DaemonMessage::Tcp(DaemonTcp::SubscribeResult(Ok(port))) => {
+                tracing::debug!(pod, port, "Subscribed to a port of a replica");
+            }
+            DaemonMessage::Tcp(DaemonTcp::SubscribeResult(Err(error))) => {
+                tracing::warn!(pod, %error, "Failed to subscribe to a port of a replica");
+            }
+            DaemonMessage::Tcp(mut message) => {
+                let is_new = matches!(
+                    message,
+                    DaemonTcp::NewConnectionV1(..)
+                        | DaemonTcp::NewConnectionV2(..)
+                        | DaemonTcp::HttpRequest(..)
+                        | DaemonTcp::HttpRequestFramed(..)
+                        | DaemonTcp::HttpRequestChunked(
+                            ChunkedRequest::StartV1(..) | ChunkedRequest::StartV2(..)
+                        )
+                );
+
+                if let Some(connection_id) = connection_id_mut(&mut message) {
+                    if is_new {
+                        tracing::debug!(
+                            pod,
+                            connection_id = *connection_id,
+                            "Mirrored a new connection or request from a replica",
+                        );
+                    }
+
+                    *connection_id |= index << REPLICA_INDEX_SHIFT;
+                }
+
+                message_bus.send(ProxyMessage::FromReplica(message)).await;
+            }
+            DaemonMessage::Close(reason) => {
+                tracing::warn!(pod, reason, "The agent of a replica closed the connection");
+                self.agent_closed(&pod);
+            }
+            DaemonMessage::LogMessage(log) => match log.level {
+                LogLevel::Error => tracing::error!(
+                    pod,
+                    message = log.message,
+                    "Received a log message from the agent of a replica"
+                ),
+                LogLevel::Warn => tracing::warn!(
+                    pod,
+                    message = log.message,
+                    "Received a log message from the agent of a replica"
+                ),
+                LogLevel::Info => tracing::info!(
+                    pod,
+                    message = log.message,
+                    "Received a log message from the agent of a replica"
+                ),
+            },
+            DaemonMessage::Pong | DaemonMessage::SwitchProtocolVersionResponse(..) => {}
+            other => {
+                tracing::debug!(
+                    pod,
+                    message = ?other,
+                    "Ignoring an unexpected message from the agent of a replica",
+                );
+            }
+        }
+    }
+
+    /// Drops the agent of the given pod, it won't be created again while the pod stays ready.
+    fn agent_closed(&mut self, pod: &str) {
+        if self.agents.remove(pod).is_some() {
+            tracing::info!(pod, "Stopped mirroring a replica, its agent is gone");
+            self.agent_streams.remove(pod);
+            self.failed.insert(pod.to_owned());
+        }
+    }
+}
+
+impl BackgroundTask for ReplicaMirrors {
+    type Error = Infallible;
+    type MessageIn = ReplicaMirrorsMessage;
+    type MessageOut = ProxyMessage;
+
+    #[tracing::instrument(level = Level::INFO, name = "replica_mirrors_main_loop", skip_all, fields(workload = %self.workload))]
+    async fn run(&mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
+        let (api, mut pods) = match self.start().await {
+            Ok(started) => started,
+            Err(error) => {
+                tracing::error!(
+                    %error,
+                    "Failed to watch the pods of the target workload, \
+                    only the targeted pod is mirrored",
+                );
+
+                while message_bus.recv().await.is_some() {}
+                return Ok(());
+            }
+        };
+
+        let mut ping = time::interval(Self::PING_INTERVAL);
+        ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                message = message_bus.recv() => match message {
+                    None => {
+                        tracing::debug!("Message bus closed, exiting");
+                        break Ok(());
+                    }
+                    Some(ReplicaMirrorsMessage::Mirror(message)) => {
+                        self.handle_mirror_message(message).await;
+                    }
+                },
+
+                Some(update) = pods.next() => match update {
+                    Ok(ready_pods) => {
+                        self.ready_pods = ready_pods;
+                        self.reconcile(&api);
+                    }
+                    Err(error) => {
+                        tracing::warn!(%error, "Watching the pods of the target workload failed, retrying");
+                    }
+                },
+
+                Some(created) = self.creating.join_next_with_id() => {
+                    let (id, result) = match created {
+                        Ok((id, result)) => (id, result),
+                        Err(error) => {
+                            tracing::error!(%error, "Task creating the agent of a replica failed");
+                            if let Some(pod) = self.pending.remove(&error.id()) {
+                                self.failed.insert(pod);
+                            }
+                            continue;
+                        }
+                    };
+
+                    if let Some(pod) = self.pending.remove(&id) {
+                        self.agent_created(pod, result).await;
+                        self.reconcile(&api);
+                    }
+                },
+
+                Some((pod, message)) = self.agent_streams.next() => match message {
+                    Some(message) => self.handle_agent_message(pod, message, message_bus).await,
+                    None => {
+                        self.agent_closed(&pod);
+                        self.reconcile(&api);
+                    }
+                },
+
+                _ = ping.tick() => {
+                    for agent in self.agents.values() {
+                        agent.tx.send(ClientMessage::Ping).await;
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use mirrord_protocol::tcp::{NewTcpConnectionV1, TcpClose};
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(DaemonTcp::Close(TcpClose { connection_id: 7 }), Some(7))]
+    #[case(
+        DaemonTcp::NewConnectionV1(NewTcpConnectionV1 {
+            connection_id: 3,
+            remote_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            destination_port: 80,
+            source_port: 41000,
+            local_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        }),
+        Some(3)
+    )]
+    #[case(DaemonTcp::SubscribeResult(Ok(80)), None)]
+    fn replica_connection_ids(#[case] mut message: DaemonTcp, #[case] expected: Option<u64>) {
+        let connection_id = connection_id_mut(&mut message).map(|id| *id);
+        assert_eq!(connection_id, expected);
+
+        if let Some(connection_id) = connection_id {
+            assert!(is_replica_connection(connection_id).not());
+            assert!(is_replica_connection(
+                connection_id | 2 << REPLICA_INDEX_SHIFT
+            ));
+        }
+    }
+}
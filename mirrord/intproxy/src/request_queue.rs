@@ -74,6 +74,12 @@ impl<T: fmt::Debug> RequestQueue<T> {
         Some((message_id, layer_id, data))
     }
 
+    /// Retrieve and remove all requests from this queue.
+    #[tracing::instrument(level = Level::TRACE)]
+    pub fn drain(&mut self) -> impl '_ + Iterator<Item = (MessageId, LayerId, T)> {
+        self.inner.drain(..)
+    }
+
     pub fn len(&self) -> usize {
         self.inner.len()
     }
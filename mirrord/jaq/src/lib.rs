@@ -87,7 +87,10 @@ fn compiler_error_to_string(error: CompileError) -> String {
     compile_error
 }
 
-pub fn compile_jq(code: &str) -> Result<jaq_core::Filter<jaq_core::Native<jaq_json::Val>>> {
+/// A compiled jq program, see [`compile_jq`].
+pub type JqFilter = jaq_core::Filter<jaq_core::Native<jaq_json::Val>>;
+
+pub fn compile_jq(code: &str) -> Result<JqFilter> {
     let file = jaq_core::load::File { code, path: () };
     let loader = jaq_core::load::Loader::new(jaq_std::defs().chain(jaq_json::defs()));
     let arena = jaq_core::load::Arena::default();
@@ -108,6 +111,28 @@ pub fn compile_jq(code: &str) -> Result<jaq_core::Filter<jaq_core::Native<jaq_js
     })
 }
 
+/// Runs the `filter` against the `input`, the first boolean output decides.
+///
+/// Errors are returned only when the filter outputs no boolean, and a filter without any boolean
+/// outputs evaluates to `false`.
+pub fn run_jq(filter: &JqFilter, input: jaq_json::Val) -> std::result::Result<bool, String> {
+    let inputs = jaq_core::RcIter::new(core::iter::empty());
+    let out = filter.run((jaq_core::Ctx::new([], &inputs), input));
+
+    let mut error = None;
+    for item in out {
+        match item {
+            Ok(jaq_json::Val::Bool(value)) => return Ok(value),
+            Ok(..) => {}
+            Err(fail) => {
+                error.get_or_insert_with(|| format!("{fail:?}"));
+            }
+        }
+    }
+
+    error.map_or(Ok(false), Err)
+}
+
 #[cfg(feature = "eval")]
 pub async fn evaluate_jq(
     jq_code: &str,
@@ -195,6 +220,7 @@ mod tests {
     use jaq_core::compile::Undefined;
     #[cfg(feature = "eval")]
     use mirrord_test_macros::background_shutdown_tokio_test;
+    use serde_json::json;
 
     use super::*;
 
@@ -213,6 +239,21 @@ mod tests {
         VerifiedJqString::try_from("idk | whatever").unwrap_err();
     }
 
+    #[test]
+    fn jq_run_first_boolean_wins() {
+        let run =
+            |code: &str, input: serde_json::Value| run_jq(&compile_jq(code).unwrap(), input.into());
+
+        assert_eq!(run(".snow > 25", json!({"snow": 30})), Ok(true));
+        assert_eq!(
+            run(".[] | .snow", json!([{"snow": 1}, {"snow": false}])),
+            Ok(false)
+        );
+        assert_eq!(run(".snow", json!({"snow": 30})), Ok(false));
+        assert_eq!(run("(.[] | tonumber), true", json!(["snow"])), Ok(true));
+        run(".snow | tonumber", json!({"snow": "deep"})).unwrap_err();
+    }
+
     #[test]
     fn test_estimate_string_len() {
         // This error doesn't make sens (complains about undefined symbols that don't appear in the
@@ -1,13 +1,14 @@
 use std::{collections::HashSet, net::IpAddr, sync::LazyLock, time::Duration};
 
 use k8s_openapi::api::core::v1::{ContainerStatus, Pod};
-use mirrord_agent_env::{mesh::MeshVendor, steal_tls::StealPortTlsConfig};
+use mirrord_agent_env::{mesh::MeshVendor, probes::HttpProbe, steal_tls::StealPortTlsConfig};
 use mirrord_config::agent::AgentConfig;
 use mirrord_progress::Progress;
 use rand::distr::{Alphanumeric, SampleString};
 
 use crate::{api::kubernetes::AgentKubernetesConnectInfo, error::Result};
 
+pub mod diagnostics;
 pub mod ephemeral;
 pub mod job;
 pub mod pod;
@@ -47,6 +48,11 @@ pub struct ContainerConfig {
     pub steal_tls_config: Vec<StealPortTlsConfig>,
     /// How long the agent should keep running after all client connections have been closed.
     pub idle_ttl: Duration,
+    /// Whether the agent should pass through stolen connections made from within the target pod.
+    pub ignore_local_traffic: bool,
+    /// Whether the agent should pass through stolen requests of the target pod's HTTP probes,
+    /// see [`ContainerParams::ignored_probes`].
+    pub ignore_health_checks: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -68,6 +74,10 @@ pub struct ContainerParams {
     pub steal_tls_config: Vec<StealPortTlsConfig>,
     /// How long the agent should keep running after all client connections have been closed.
     pub idle_ttl: Duration,
+    /// Whether the agent should pass through stolen connections made from within the target pod.
+    pub ignore_local_traffic: bool,
+    /// HTTP probes of the target pod, the agent passes their stolen requests through.
+    pub ignored_probes: Vec<HttpProbe>,
 }
 
 impl From<ContainerConfig> for ContainerParams {
@@ -93,6 +103,8 @@ impl From<ContainerConfig> for ContainerParams {
             support_ipv6: value.support_ipv6,
             steal_tls_config: value.steal_tls_config,
             idle_ttl: value.idle_ttl,
+            ignore_local_traffic: value.ignore_local_traffic,
+            ignored_probes: Default::default(),
         }
     }
 }
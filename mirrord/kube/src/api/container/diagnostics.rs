@@ -0,0 +1,332 @@
+//! State of the agent pod, reported when it fails to start within
+//! [`AgentConfig::startup_timeout`](mirrord_config::agent::AgentConfig::startup_timeout).
+
+use std::{fmt, time::Duration};
+
+use k8s_openapi::{
+    api::core::v1::{ContainerStatus, Event, Pod},
+    chrono::{DateTime, Utc},
+};
+use kube::{Api, Client, api::ListParams};
+
+use crate::error::KubeApiError;
+
+/// How many of the most recent events are included in [`AgentStartupDiagnostics`].
+const MAX_EVENTS: usize = 10;
+
+/// What we know about the agent pod at the moment we gave up waiting for it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AgentStartupDiagnostics {
+    /// `namespace/name` of the agent pod.
+    pub pod: String,
+    /// Phase of the pod, e.g. `Pending`.
+    pub phase: Option<String>,
+    /// One line per container, e.g. `mirrord-agent: waiting (ImagePullBackOff): ...`.
+    pub containers: Vec<String>,
+    /// Most recent events of the pod, oldest first.
+    pub events: Vec<String>,
+    /// Set when the agent job did not create the pod in time, [`Self::pod`] and [`Self::events`]
+    /// then refer to the job.
+    pub pending_job: bool,
+}
+
+impl AgentStartupDiagnostics {
+    /// Fetches the pod and its events. Failures are not reported, as this is best effort.
+    pub async fn collect(client: &Client, namespace: &str, pod_name: &str) -> Self {
+        let pod = Api::<Pod>::namespaced(client.clone(), namespace)
+            .get_opt(pod_name)
+            .await
+            .inspect_err(|error| tracing::debug!(%error, "Failed to fetch the agent pod"))
+            .ok()
+            .flatten();
+        let events = fetch_events(client, namespace, pod_name).await;
+
+        Self::from_resources(format!("{namespace}/{pod_name}"), pod.as_ref(), events)
+    }
+
+    /// Fetches the events of the agent job, which usually explain why it did not create the pod
+    /// (e.g. an exceeded resource quota).
+    pub async fn collect_job(client: &Client, namespace: &str, job_name: &str) -> Self {
+        let events = fetch_events(client, namespace, job_name).await;
+
+        Self {
+            pending_job: true,
+            ..Self::from_resources(format!("{namespace}/{job_name}"), None, events)
+        }
+    }
+
+    fn from_resources(pod_name: String, pod: Option<&Pod>, mut events: Vec<Event>) -> Self {
+        let status = pod.and_then(|pod| pod.status.as_ref());
+
+        let containers = status
+            .into_iter()
+            .flat_map(|status| {
+                let init = status
+                    .init_container_statuses
+                    .as_deref()
+                    .unwrap_or_default();
+                let regular = status.container_statuses.as_deref().unwrap_or_default();
+                let ephemeral = status
+                    .ephemeral_container_statuses
+                    .as_deref()
+                    .unwrap_or_default();
+
+                init.iter().chain(regular).chain(ephemeral)
+            })
+            .map(describe_container)
+            .collect();
+
+        events.sort_by_key(event_time);
+        let skip = events.len().saturating_sub(MAX_EVENTS);
+        let events = events.iter().skip(skip).map(describe_event).collect();
+
+        Self {
+            pod: pod_name,
+            phase: status.and_then(|status| status.phase.clone()),
+            containers,
+            events,
+            pending_job: false,
+        }
+    }
+}
+
+impl fmt::Display for AgentStartupDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.pending_job {
+            write!(f, "agent job {} did not create the agent pod", self.pod)?;
+        } else {
+            write!(
+                f,
+                "agent pod {} is in phase {}",
+                self.pod,
+                self.phase.as_deref().unwrap_or("<unknown>")
+            )?;
+        }
+
+        if !self.containers.is_empty() {
+            f.write_str("\ncontainers:")?;
+            for container in &self.containers {
+                write!(f, "\n  {container}")?;
+            }
+        }
+
+        if !self.events.is_empty() {
+            f.write_str("\nrecent events:")?;
+            for event in &self.events {
+                write!(f, "\n  {event}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds [`KubeApiError::AgentStartupTimeout`] with the current state of the agent pod.
+pub(super) async fn startup_timeout_error(
+    client: &Client,
+    namespace: &str,
+    pod_name: &str,
+    timeout: Duration,
+) -> KubeApiError {
+    let diagnostics = AgentStartupDiagnostics::collect(client, namespace, pod_name).await;
+
+    KubeApiError::AgentStartupTimeout {
+        timeout,
+        diagnostics: Box::new(diagnostics),
+    }
+}
+
+/// Builds [`KubeApiError::AgentStartupTimeout`] when the agent job did not create the pod in time.
+pub(super) async fn job_startup_timeout_error(
+    client: &Client,
+    namespace: &str,
+    job_name: &str,
+    timeout: Duration,
+) -> KubeApiError {
+    let diagnostics = AgentStartupDiagnostics::collect_job(client, namespace, job_name).await;
+
+    KubeApiError::AgentStartupTimeout {
+        timeout,
+        diagnostics: Box::new(diagnostics),
+    }
+}
+
+/// Lists the events of the given object. Failures are not reported, as this is best effort.
+async fn fetch_events(client: &Client, namespace: &str, name: &str) -> Vec<Event> {
+    let list_params = ListParams {
+        field_selector: Some(format!("involvedObject.name={name}")),
+        ..Default::default()
+    };
+
+    Api::<Event>::namespaced(client.clone(), namespace)
+        .list(&list_params)
+        .await
+        .inspect_err(|error| tracing::debug!(%error, "Failed to list agent events"))
+        .map(|list| list.items)
+        .unwrap_or_default()
+}
+
+fn describe_container(status: &ContainerStatus) -> String {
+    let state = status.state.as_ref();
+
+    let (state, reason, message) = if let Some(waiting) = state.and_then(|s| s.waiting.as_ref()) {
+        (
+            "waiting",
+            waiting.reason.as_deref(),
+            waiting.message.as_deref(),
+        )
+    } else if let Some(terminated) = state.and_then(|s| s.terminated.as_ref()) {
+        (
+            "terminated",
+            terminated.reason.as_deref(),
+            terminated.message.as_deref(),
+        )
+    } else if state.is_some_and(|s| s.running.is_some()) {
+        let state = if status.ready {
+            "running"
+        } else {
+            "running, not ready"
+        };
+        (state, None, None)
+    } else {
+        ("unknown", None, None)
+    };
+
+    let mut description = format!("{}: {state}", status.name);
+    if let Some(reason) = reason {
+        description.push_str(&format!(" ({reason})"));
+    }
+    if let Some(message) = message {
+        description.push_str(&format!(": {message}"));
+    }
+
+    description
+}
+
+/// Most precise timestamp available in the [`Event`], used only for ordering.
+fn event_time(event: &Event) -> Option<DateTime<Utc>> {
+    event
+        .event_time
+        .as_ref()
+        .map(|time| time.0)
+        .or_else(|| event.last_timestamp.as_ref().map(|time| time.0))
+        .or_else(|| {
+            event
+                .metadata
+                .creation_timestamp
+                .as_ref()
+                .map(|time| time.0)
+        })
+}
+
+fn describe_event(event: &Event) -> String {
+    let mut description = format!(
+        "{} {}: {}",
+        event.type_.as_deref().unwrap_or("Normal"),
+        event.reason.as_deref().unwrap_or("<no reason>"),
+        event.message.as_deref().unwrap_or("<no message>"),
+    );
+
+    if let Some(count) = event.count.filter(|count| *count > 1) {
+        description.push_str(&format!(" (x{count})"));
+    }
+
+    description
+}
+
+#[cfg(test)]
+mod test {
+    use k8s_openapi::{
+        api::core::v1::{
+            ContainerState, ContainerStateWaiting, ContainerStatus, Event, Pod, PodStatus,
+        },
+        apimachinery::pkg::apis::meta::v1::Time,
+        chrono::{TimeZone, Utc},
+    };
+
+    use super::{AgentStartupDiagnostics, MAX_EVENTS};
+
+    fn event(reason: &str, message: &str, timestamp: i64) -> Event {
+        Event {
+            type_: Some("Warning".into()),
+            reason: Some(reason.into()),
+            message: Some(message.into()),
+            last_timestamp: Some(Time(Utc.timestamp_opt(timestamp, 0).unwrap())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reports_image_pull_backoff() {
+        let pod = Pod {
+            status: Some(PodStatus {
+                phase: Some("Pending".into()),
+                container_statuses: Some(vec![ContainerStatus {
+                    name: "mirrord-agent".into(),
+                    state: Some(ContainerState {
+                        waiting: Some(ContainerStateWaiting {
+                            reason: Some("ImagePullBackOff".into()),
+                            message: Some("Back-off pulling image".into()),
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let events = vec![
+            event("Failed", "Failed to pull image", 200),
+            event("Scheduled", "Successfully assigned", 100),
+        ];
+
+        let diagnostics =
+            AgentStartupDiagnostics::from_resources("default/agent".into(), Some(&pod), events);
+
+        assert_eq!(
+            diagnostics.to_string(),
+            "agent pod default/agent is in phase Pending\n\
+             containers:\n  \
+             mirrord-agent: waiting (ImagePullBackOff): Back-off pulling image\n\
+             recent events:\n  \
+             Warning Scheduled: Successfully assigned\n  \
+             Warning Failed: Failed to pull image"
+        );
+    }
+
+    #[test]
+    fn reports_pending_job() {
+        let events = vec![event(
+            "FailedCreate",
+            "Error creating: pods is forbidden: exceeded quota",
+            100,
+        )];
+
+        let diagnostics = AgentStartupDiagnostics {
+            pending_job: true,
+            ..AgentStartupDiagnostics::from_resources("default/agent".into(), None, events)
+        };
+
+        assert_eq!(
+            diagnostics.to_string(),
+            "agent job default/agent did not create the agent pod\n\
+             recent events:\n  \
+             Warning FailedCreate: Error creating: pods is forbidden: exceeded quota"
+        );
+    }
+
+    #[test]
+    fn keeps_only_recent_events() {
+        let events = (0..MAX_EVENTS as i64 + 5)
+            .map(|index| event("Reason", &index.to_string(), index))
+            .collect();
+
+        let diagnostics =
+            AgentStartupDiagnostics::from_resources("default/agent".into(), None, events);
+
+        assert_eq!(diagnostics.phase, None);
+        assert_eq!(diagnostics.events.len(), MAX_EVENTS);
+        assert_eq!(diagnostics.events[0], "Warning Reason: 5");
+    }
+}
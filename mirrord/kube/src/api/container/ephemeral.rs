@@ -18,7 +18,8 @@ use crate::{
     api::{
         container::{
             ContainerParams, ContainerVariant,
-            util::{base_command_line, get_capabilities, wait_for_agent_startup},
+            diagnostics::startup_timeout_error,
+            util::{StartupDeadline, base_command_line, get_capabilities, wait_for_agent_startup},
         },
         kubernetes::AgentKubernetesConnectInfo,
         runtime::RuntimeData,
@@ -106,6 +107,7 @@ where
     // The target pod might have changed since we fetched it.
     ephemeral_containers_subresource.metadata.resource_version = None;
 
+    let deadline = StartupDeadline::start(variant.agent_config());
     pod_api
         .replace_subresource(
             "ephemeralcontainers",
@@ -133,17 +135,32 @@ where
     let stream = watcher(pod_api.clone(), watcher_config).applied_objects();
     pin!(stream);
 
-    while let Some(Ok(pod)) = stream.next().await {
-        if is_ephemeral_container_running(pod, &params.name) {
-            debug!("container ready");
-            break;
-        } else {
-            debug!("container not ready yet");
+    let wait_until_ready = async {
+        while let Some(Ok(pod)) = stream.next().await {
+            if is_ephemeral_container_running(pod, &params.name) {
+                debug!("container ready");
+                break;
+            } else {
+                debug!("container not ready yet");
+            }
         }
-    }
 
-    let version =
-        wait_for_agent_startup(&pod_api, &runtime_data.pod_name, params.name.clone()).await?;
+        wait_for_agent_startup(&pod_api, &runtime_data.pod_name, params.name.clone()).await
+    };
+
+    let version = match deadline.run(wait_until_ready).await {
+        Ok(version) => version?,
+        Err(timeout) => {
+            container_progress.failure(Some("agent container did not become ready in time"));
+            return Err(startup_timeout_error(
+                client,
+                &runtime_data.pod_namespace,
+                &runtime_data.pod_name,
+                timeout,
+            )
+            .await);
+        }
+    };
     match version.as_ref() {
         Some(version) if version != env!("CARGO_PKG_VERSION") => {
             let message = format!(
@@ -162,6 +179,8 @@ where
         pod_name: runtime_data.pod_name.to_string(),
         pod_namespace: runtime_data.pod_namespace.clone(),
         agent_port: params.port,
+        target_pod: None,
+        startup_deadline: deadline,
     })
 }
 
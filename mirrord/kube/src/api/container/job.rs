@@ -25,8 +25,9 @@ use crate::{
     api::{
         container::{
             ContainerParams, ContainerVariant,
+            diagnostics::{job_startup_timeout_error, startup_timeout_error},
             pod::{PodTargetedVariant, PodVariant},
-            util::wait_for_agent_startup,
+            util::{StartupDeadline, wait_for_agent_startup},
         },
         kubernetes::{AgentKubernetesConnectInfo, get_k8s_resource_api},
         runtime::RuntimeData,
@@ -55,6 +56,7 @@ where
 
     let job_api = get_k8s_resource_api(client, agent.namespace.as_deref());
 
+    let deadline = StartupDeadline::start(agent);
     job_api
         .create(&PostParams::default(), &agent_job)
         .await
@@ -69,15 +71,21 @@ where
     let stream = watcher(pod_api.clone(), watcher_config);
     pin!(stream);
 
-    let agent_pod = stream
-        .as_mut()
-        .applied_objects()
-        .next()
-        .await
-        .ok_or_else(|| {
-            KubeApiError::AgentPodStartError("watch stream unexpectedly finished".to_owned())
-        })?
-        .map_err(|err| KubeApiError::AgentPodStartError(format!("watch stream failed: {err}")))?;
+    let agent_pod = match deadline.run(stream.as_mut().applied_objects().next()).await {
+        Ok(agent_pod) => agent_pod,
+        Err(timeout) => {
+            pod_progress.failure(Some("agent pod was not created in time"));
+            let namespace = agent
+                .namespace
+                .as_deref()
+                .unwrap_or(client.default_namespace());
+            return Err(job_startup_timeout_error(client, namespace, &params.name, timeout).await);
+        }
+    }
+    .ok_or_else(|| {
+        KubeApiError::AgentPodStartError("watch stream unexpectedly finished".to_owned())
+    })?
+    .map_err(|err| KubeApiError::AgentPodStartError(format!("watch stream failed: {err}")))?;
 
     let (Name(pod_name), Namespace(pod_namespace)) = FromResource::from_resource(&agent_pod, &())?;
 
@@ -104,6 +112,10 @@ where
                     last_known_container_state
                 ));
             }
+            timeout = deadline.expired() => {
+                pod_progress.failure(Some("agent pod did not become ready in time"));
+                return Err(startup_timeout_error(client, pod_namespace, pod_name, timeout).await);
+            }
             event = stream.next() => {
                 match event {
                     Some(Ok(Event::Apply(pod) | Event::InitApply(pod))) => {
@@ -189,7 +201,20 @@ where
         }
     }
 
-    let version = wait_for_agent_startup(&pod_api, pod_name, "mirrord-agent".to_string()).await?;
+    let version = match deadline
+        .run(wait_for_agent_startup(
+            &pod_api,
+            pod_name,
+            "mirrord-agent".to_string(),
+        ))
+        .await
+    {
+        Ok(version) => version?,
+        Err(timeout) => {
+            pod_progress.failure(Some("agent did not report readiness in time"));
+            return Err(startup_timeout_error(client, pod_namespace, pod_name, timeout).await);
+        }
+    };
     match version.as_ref() {
         Some(version) if version != env!("CARGO_PKG_VERSION") => {
             let message = format!(
@@ -207,6 +232,8 @@ where
         pod_name: pod_name.to_owned(),
         pod_namespace: pod_namespace.to_owned(),
         agent_port: params.port,
+        target_pod: None,
+        startup_deadline: deadline,
     })
 }
 
@@ -380,6 +407,8 @@ mod test {
             support_ipv6,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            ignore_local_traffic: false,
+            ignored_probes: Default::default(),
         };
 
         let update = JobVariant::new(&agent, &params).as_update();
@@ -429,9 +458,11 @@ mod test {
                                     { "name": envs::JSON_LOG.name, "value": Some(agent.json_log.to_string()) },
                                     { "name": envs::IPV6_SUPPORT.name, "value": Some(support_ipv6.to_string()) },
                                     { "name": envs::PASSTHROUGH_MIRRORING.name, "value": "true" },
-                                    { "name": envs::MAX_BODY_BUFFER_SIZE.name, "value": "65535" },
+                                    { "name": envs::MAX_BODY_BUFFER_SIZE.name, "value": "262144" },
+                                    { "name": envs::MAX_BODY_BUFFER_TOTAL.name, "value": "67108864" },
                                     { "name": envs::MAX_BODY_BUFFER_TIMEOUT.name, "value": "1000" },
-                                    { "name": envs::JAQ_TIME_LIMIT.name, "value": "1" },
+                                    { "name": envs::JAQ_TIME_LIMIT_MS.name, "value": "500" },
+                                    { "name": envs::JAQ_MEMORY_LIMIT_BYTES.name, "value": "536870912" },
                                 ],
                                 "resources": // Add requests to avoid getting defaulted https://github.com/metalbear-co/mirrord/issues/579
                                 {
@@ -473,6 +504,8 @@ mod test {
             support_ipv6,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            ignore_local_traffic: false,
+            ignored_probes: Default::default(),
         };
 
         let update = JobTargetedVariant::new(
@@ -488,9 +521,11 @@ mod test {
                 container_id: "container".to_string(),
                 container_runtime: ContainerRuntime::Docker,
                 container_name: "foo".to_string(),
+                pod_containers: Default::default(),
                 guessed_container: false,
                 share_process_namespace: false,
                 containers_probe_ports: vec![],
+                containers_http_probes: vec![],
             },
         )
         .as_update();
@@ -574,9 +609,11 @@ mod test {
                                     { "name": envs::JSON_LOG.name, "value": Some(agent.json_log.to_string()) },
                                     { "name": envs::IPV6_SUPPORT.name, "value": Some(support_ipv6.to_string()) },
                                     { "name": envs::PASSTHROUGH_MIRRORING.name, "value": "true" },
-                                    { "name": envs::MAX_BODY_BUFFER_SIZE.name, "value": "65535" },
+                                    { "name": envs::MAX_BODY_BUFFER_SIZE.name, "value": "262144" },
+                                    { "name": envs::MAX_BODY_BUFFER_TOTAL.name, "value": "67108864" },
                                     { "name": envs::MAX_BODY_BUFFER_TIMEOUT.name, "value": "1000" },
-                                    { "name": envs::JAQ_TIME_LIMIT.name, "value": "1" },
+                                    { "name": envs::JAQ_TIME_LIMIT_MS.name, "value": "500" },
+                                    { "name": envs::JAQ_MEMORY_LIMIT_BYTES.name, "value": "536870912" },
                                     { "name": envs::NFTABLES.name, "value": "true" },
                                 ],
                                 "resources": // Add requests to avoid getting defaulted https://github.com/metalbear-co/mirrord/issues/579
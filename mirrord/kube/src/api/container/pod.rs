@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, ops::Not};
 
 use k8s_openapi::{
     DeepMerge,
@@ -195,7 +195,7 @@ impl ContainerVariant for PodTargetedVariant<'_> {
 
         let tolerations = agent.tolerations.as_ref().unwrap_or(&DEFAULT_TOLERATIONS);
 
-        let env = self.runtime_data.mesh.map(|mesh_vendor| {
+        let mut env = self.runtime_data.mesh.map(|mesh_vendor| {
             let mut env = vec![envs::IN_SERVICE_MESH.as_k8s_spec(&true)];
             if matches!(mesh_vendor, MeshVendor::IstioCni) {
                 env.push(envs::ISTIO_CNI.as_k8s_spec(&true));
@@ -203,6 +203,11 @@ impl ContainerVariant for PodTargetedVariant<'_> {
             env
         });
 
+        if runtime_data.pod_containers.is_empty().not() {
+            env.get_or_insert_default()
+                .push(envs::POD_CONTAINERS.as_k8s_spec(&runtime_data.pod_containers));
+        }
+
         let update = Pod {
             spec: Some(PodSpec {
                 restart_policy: Some("Never".to_string()),
@@ -309,6 +314,8 @@ mod test {
             support_ipv6: false,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            ignore_local_traffic: false,
+            ignored_probes: Default::default(),
         };
 
         let update = PodVariant::new(&agent, &params).as_update();
@@ -330,9 +337,11 @@ mod test {
                 container_id: "container".to_string(),
                 container_runtime: ContainerRuntime::Docker,
                 container_name: "some-container".to_string(),
+                pod_containers: Default::default(),
                 guessed_container: false,
                 share_process_namespace: false,
                 containers_probe_ports: vec![],
+                containers_http_probes: vec![],
             },
         )
         .as_update();
@@ -357,6 +366,8 @@ mod test {
             support_ipv6: false,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            ignore_local_traffic: false,
+            ignored_probes: Default::default(),
         };
 
         let update = PodTargetedVariant::new(
@@ -372,9 +383,11 @@ mod test {
                 container_id: "container".to_string(),
                 container_runtime: ContainerRuntime::Docker,
                 container_name: "some-container".to_string(),
+                pod_containers: Default::default(),
                 guessed_container: false,
                 share_process_namespace: false,
                 containers_probe_ports: vec![],
+                containers_http_probes: vec![],
             },
         )
         .as_update();
@@ -1,4 +1,4 @@
-use std::{ops::Not, sync::LazyLock};
+use std::{ops::Not, sync::LazyLock, time::Duration};
 
 use futures::{AsyncBufReadExt, TryStreamExt};
 use k8s_openapi::api::core::v1::{EnvVar, Pod, Toleration};
@@ -6,6 +6,7 @@ use kube::{Api, api::LogParams};
 use mirrord_agent_env::envs;
 use mirrord_config::agent::{AgentConfig, LinuxCapability};
 use regex::Regex;
+use tokio::time::Instant;
 use tracing::warn;
 
 use crate::{api::container::ContainerParams, error::Result};
@@ -49,8 +50,10 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &ContainerParams) -> Vec<En
         // Left for compatibility with older agents.
         envs::PASSTHROUGH_MIRRORING.as_k8s_spec(&true),
         envs::MAX_BODY_BUFFER_SIZE.as_k8s_spec(&agent.max_body_buffer_size),
+        envs::MAX_BODY_BUFFER_TOTAL.as_k8s_spec(&agent.max_body_buffer_total),
         envs::MAX_BODY_BUFFER_TIMEOUT.as_k8s_spec(&agent.max_body_buffer_timeout),
-        envs::JAQ_TIME_LIMIT.as_k8s_spec(&agent.jaq_time_limit),
+        envs::JAQ_TIME_LIMIT_MS.as_k8s_spec(&agent.jaq_time_limit),
+        envs::JAQ_MEMORY_LIMIT_BYTES.as_k8s_spec(&agent.jaq_memory_limit),
     ];
 
     if let Some(nftables) = agent.nftables {
@@ -81,6 +84,14 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &ContainerParams) -> Vec<En
         env.push(envs::STEAL_TLS_CONFIG.as_k8s_spec(&params.steal_tls_config));
     }
 
+    if params.ignore_local_traffic {
+        env.push(envs::STEAL_IGNORE_LOCAL.as_k8s_spec(&params.ignore_local_traffic));
+    }
+
+    if params.ignored_probes.is_empty().not() {
+        env.push(envs::STEAL_IGNORE_PROBES.as_k8s_spec(&params.ignored_probes));
+    }
+
     if params.idle_ttl.is_zero().not() {
         env.push(envs::IDDLE_TTL.as_k8s_spec(&params.idle_ttl.as_secs()))
     }
@@ -93,6 +104,23 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &ContainerParams) -> Vec<En
         env.push(envs::CLEAN_IPTABLES_ON_START.as_k8s_spec(&clean));
     }
 
+    if let Some(size) = agent.jaq_pool_size {
+        env.push(envs::JAQ_POOL_SIZE.as_k8s_spec(&size));
+
+        if let Some(max_evaluations) = agent.jaq_pool_max_evaluations {
+            env.push(envs::JAQ_POOL_MAX_EVALUATIONS.as_k8s_spec(&max_evaluations));
+        }
+    }
+
+    // Invalid values are rejected when the config is verified.
+    if let Ok(Some(limit)) = agent.mirror_bandwidth_limit_bytes() {
+        env.push(envs::MIRROR_BANDWIDTH_LIMIT.as_k8s_spec(&limit));
+
+        if let Ok(Some(burst)) = agent.mirror_bandwidth_burst_bytes() {
+            env.push(envs::MIRROR_BANDWIDTH_BURST.as_k8s_spec(&burst));
+        }
+    }
+
     env
 }
 
@@ -115,6 +143,49 @@ pub(super) fn base_command_line(agent: &AgentConfig, params: &ContainerParams) -
     command_line
 }
 
+/// Deadline for the agent startup, started when the agent resources are created.
+///
+/// Shared between all startup phases, up to the first handshake with the agent, so that
+/// [`AgentConfig::startup_timeout`] bounds the whole startup, and not each phase separately. See
+/// [`AgentKubernetesConnectInfo::startup_deadline`](crate::api::kubernetes::AgentKubernetesConnectInfo::startup_deadline).
+///
+/// The [`Default`] deadline never expires.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct StartupDeadline(Option<(Duration, Instant)>);
+
+impl StartupDeadline {
+    pub(super) fn start(agent: &AgentConfig) -> Self {
+        Self(
+            agent
+                .startup_timeout_duration()
+                .map(|timeout| (timeout, Instant::now() + timeout)),
+        )
+    }
+
+    /// Runs `future` until the deadline, returns the configured timeout if it expires first.
+    pub async fn run<F: Future>(self, future: F) -> Result<F::Output, Duration> {
+        match self.0 {
+            Some((timeout, deadline)) => tokio::time::timeout_at(deadline, future)
+                .await
+                .map_err(|_| timeout),
+            None => Ok(future.await),
+        }
+    }
+
+    /// Completes when the deadline expires, returning the configured timeout.
+    ///
+    /// Never completes if there is no deadline.
+    pub(super) async fn expired(self) -> Duration {
+        match self.0 {
+            Some((timeout, deadline)) => {
+                tokio::time::sleep_until(deadline).await;
+                timeout
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
 /**
  * Wait until the agent prints the "agent ready" message.
  * Return agent version extracted from the message (if found).
@@ -3,7 +3,7 @@ use std::{
     ops::{Deref, Not},
 };
 
-use k8s_openapi::NamespaceResourceScope;
+use k8s_openapi::{NamespaceResourceScope, api::batch::v1::CronJob};
 use kube::{
     Api, Client, Config, Discovery,
     client::ClientBuilder,
@@ -14,7 +14,7 @@ use mirrord_config::{
     LayerConfig,
     agent::AgentConfig,
     feature::network::NetworkConfig,
-    target::{Target, TargetConfig},
+    target::{Target, TargetConfig, job::JobTarget},
 };
 use mirrord_progress::Progress;
 use serde::{Deserialize, Serialize};
@@ -30,13 +30,18 @@ use crate::{
             job::{JobTargetedVariant, JobVariant},
             targeted::Targeted,
             targetless::Targetless,
+            util::StartupDeadline,
+        },
+        runtime::{
+            RuntimeData, RuntimeDataFromLabels, RuntimeDataProvider, cron_job::latest_active_job,
+            job::wait_for_job_runtime_data, stateful_set::stateful_set_runtime_data,
         },
-        runtime::{RuntimeData, RuntimeDataProvider},
     },
     error::{KubeApiError, Result},
     retry::RetryKube,
 };
 
+pub mod dry_run;
 #[cfg(feature = "portforward")]
 pub mod portforwarder;
 pub mod rollout;
@@ -124,7 +129,7 @@ impl KubernetesAPI {
             ..
         }: &AgentKubernetesConnectInfo,
     ) -> Result<tokio::net::TcpStream> {
-        use std::{net::IpAddr, time::Duration};
+        use std::net::IpAddr;
 
         use k8s_openapi::api::core::v1::Pod;
         use tokio::net::TcpStream;
@@ -137,30 +142,29 @@ impl KubernetesAPI {
             .status
             .as_ref()
             .and_then(|status| status.pod_ip.as_ref());
-        let conn = if let Some(pod_ip) = pod_ip {
-            // When pod_ip is available we directly create it as SocketAddr to prevent tokio from
-            // performing a DNS lookup.
-            let ip = pod_ip
-                .parse::<IpAddr>()
-                .map_err(|e| KubeApiError::invalid_value(&pod, "status.podIp", e))?;
-            tracing::trace!("connecting to pod {pod_ip}:{agent_port}");
-
-            tokio::time::timeout(
-                Duration::from_secs(self.agent.startup_timeout),
-                TcpStream::connect((ip, *agent_port)),
-            )
-            .await
-            .map_err(|_| KubeApiError::AgentReadyTimeout)??
-        } else {
-            let hostname = format!("{pod_name}.{pod_namespace}");
-            tracing::trace!("connecting to pod {hostname}:{agent_port}");
-
-            tokio::time::timeout(
-                Duration::from_secs(self.agent.startup_timeout),
-                TcpStream::connect((hostname.as_str(), *agent_port)),
-            )
-            .await
-            .map_err(|_| KubeApiError::AgentReadyTimeout)??
+        let connect = async {
+            if let Some(pod_ip) = pod_ip {
+                // When pod_ip is available we directly create it as SocketAddr to prevent tokio
+                // from performing a DNS lookup.
+                let ip = pod_ip
+                    .parse::<IpAddr>()
+                    .map_err(|e| KubeApiError::invalid_value(&pod, "status.podIp", e))?;
+                tracing::trace!("connecting to pod {pod_ip}:{agent_port}");
+
+                Ok::<_, KubeApiError>(TcpStream::connect((ip, *agent_port)).await?)
+            } else {
+                let hostname = format!("{pod_name}.{pod_namespace}");
+                tracing::trace!("connecting to pod {hostname}:{agent_port}");
+
+                Ok(TcpStream::connect((hostname.as_str(), *agent_port)).await?)
+            }
+        };
+
+        let conn = match self.agent.startup_timeout_duration() {
+            Some(timeout) => tokio::time::timeout(timeout, connect)
+                .await
+                .map_err(|_| KubeApiError::AgentReadyTimeout)??,
+            None => connect.await?,
         };
 
         Ok(conn)
@@ -183,19 +187,94 @@ impl KubernetesAPI {
     /// Prepares params to create an agent.
     ///
     /// Unless targetless, fetches [`RuntimeData`] for the given target and fills
-    /// [`ContainerConfig::pod_ips`].
-    #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
-    pub async fn create_agent_params(
+    /// [`ContainerConfig::pod_ips`], and [`ContainerParams::ignored_probes`] when
+    /// [`ContainerConfig::ignore_health_checks`] is set.
+    ///
+    /// For deployment and rollout targets, the pod is picked according to
+    /// [`TargetConfig::pod_selection`], and the choice is reported to the given `progress`.
+    ///
+    /// Job targets are resolved to one of the job's pods, waiting for one up to
+    /// [`AgentConfig::startup_timeout`]. Cron job targets are resolved the same way, through the
+    /// cron job's most recent active job.
+    #[tracing::instrument(level = Level::TRACE, skip(self, progress), ret, err)]
+    pub async fn create_agent_params<P>(
         &self,
+        progress: &P,
         target: &TargetConfig,
         mut config: ContainerConfig,
-    ) -> Result<(ContainerParams, Option<RuntimeData>), KubeApiError> {
-        let mut runtime_data = match target.path.as_ref().unwrap_or(&Target::Targetless) {
-            Target::Targetless => None,
-            path => path
-                .runtime_data(&self.client, target.namespace.as_deref())
+    ) -> Result<(ContainerParams, Option<RuntimeData>), KubeApiError>
+    where
+        P: Progress,
+    {
+        let namespace = target.namespace.as_deref();
+        let selected = match target.path.as_ref().unwrap_or(&Target::Targetless) {
+            Target::Deployment(deployment) => Some(
+                deployment
+                    .runtime_data_with_selection(&self.client, namespace, &target.pod_selection)
+                    .await?,
+            ),
+            Target::Rollout(rollout) => Some(
+                rollout
+                    .runtime_data_with_selection(&self.client, namespace, &target.pod_selection)
+                    .await?,
+            ),
+            _ => None,
+        };
+
+        let mut runtime_data = match selected {
+            Some((runtime_data, reason)) => {
+                progress.info(&format!(
+                    "picked pod `{}` ({reason})",
+                    runtime_data.pod_name
+                ));
+                Some(runtime_data)
+            }
+            None => match target.path.as_ref().unwrap_or(&Target::Targetless) {
+                Target::Targetless => None,
+                Target::Job(job) => wait_for_job_runtime_data(
+                    &self.client,
+                    namespace,
+                    job,
+                    self.agent.startup_timeout_duration(),
+                )
                 .await?
                 .into(),
+                Target::CronJob(cron_job) => {
+                    let cron_job_api: Api<CronJob> = get_k8s_resource_api(&self.client, namespace);
+                    let job = latest_active_job(
+                        &self.client,
+                        &cron_job_api.get(&cron_job.cron_job).await?,
+                    )
+                    .await?;
+                    let job = JobTarget {
+                        job: job.metadata.name.unwrap_or_default(),
+                        container: cron_job.container.clone(),
+                    };
+                    progress.info(&format!("picked job `{}`", job.job));
+
+                    wait_for_job_runtime_data(
+                        &self.client,
+                        namespace,
+                        &job,
+                        self.agent.startup_timeout_duration(),
+                    )
+                    .await?
+                    .into()
+                }
+                Target::StatefulSet(stateful_set) => {
+                    let runtime_data = stateful_set_runtime_data(
+                        &self.client,
+                        namespace,
+                        stateful_set,
+                        &target.pod_selection,
+                    )
+                    .await?;
+                    progress.info(&format!("picked pod `{}`", runtime_data.pod_name));
+
+                    Some(runtime_data)
+                }
+                path => path.runtime_data(&self.client, namespace).await?.into(),
+            },
         };
 
         if let Some(runtime_data) = runtime_data.as_mut() {
@@ -208,8 +287,14 @@ impl KubernetesAPI {
             .filter(|pod_ips| !pod_ips.is_empty());
 
         config.pod_ips = pod_ips;
+        let ignore_health_checks = config.ignore_health_checks;
+
+        let mut params = ContainerParams::from(config);
+        if ignore_health_checks && let Some(runtime_data) = runtime_data.as_ref() {
+            params.ignored_probes = runtime_data.containers_http_probes.clone();
+        }
 
-        Ok((config.into(), runtime_data))
+        Ok((params, runtime_data))
     }
 
     /// Creates an agent.
@@ -228,7 +313,7 @@ impl KubernetesAPI {
         P: Progress,
     {
         let (params, runtime_data) = self
-            .create_agent_params(target_config, container_config)
+            .create_agent_params(&*progress, target_config, container_config)
             .await?;
 
         if let Some(RuntimeData {
@@ -247,6 +332,13 @@ impl KubernetesAPI {
                     .iter()
                     .copied()
                     .filter(|port| network_config.incoming.steals_port_without_filter(*port))
+                    .filter(|port| {
+                        params
+                            .ignored_probes
+                            .iter()
+                            .any(|probe| probe.port == *port)
+                            .not()
+                    })
                     .map(|p| p.to_string())
                     .collect::<Vec<_>>()
                     .join(", ");
@@ -255,7 +347,7 @@ impl KubernetesAPI {
                     progress.warning(&format!(
                     "Your mirrord config may steal HTTP/gRPC health checks configured on ports [{}], \
                     causing Kubernetes to terminate containers on the targeted pod. \
-                    Use an HTTP filter to prevent this.",
+                    Use an HTTP filter or `ignore_health_checks` to prevent this.",
                     stolen_probes,
                 ));
                 }
@@ -276,7 +368,11 @@ impl KubernetesAPI {
 
         info!(?params, "Spawning new agent");
 
-        let agent_connect_info = match (runtime_data, self.agent.ephemeral) {
+        let target_pod = runtime_data
+            .as_ref()
+            .map(|runtime_data| runtime_data.pod_name.clone());
+
+        let mut agent_connect_info = match (runtime_data, self.agent.ephemeral) {
             (None, false) => {
                 let variant = JobVariant::new(&self.agent, &params);
 
@@ -301,6 +397,8 @@ impl KubernetesAPI {
             (None, true) => return Err(KubeApiError::MissingRuntimeData),
         };
 
+        agent_connect_info.target_pod = target_pod;
+
         info!(?agent_connect_info, "Created agent pod");
 
         Ok(agent_connect_info)
@@ -332,6 +430,14 @@ pub struct AgentKubernetesConnectInfo {
     pub pod_namespace: String,
     /// Port on which the agent accepts connections.
     pub agent_port: u16,
+    /// Name of the pod targeted by the agent, [`None`] if targetless.
+    #[serde(default)]
+    pub target_pod: Option<String>,
+    /// Deadline of the agent startup, which also bounds the first handshake with the agent.
+    ///
+    /// Not passed to the internal proxy, it's only relevant to the process that spawned the agent.
+    #[serde(skip)]
+    pub startup_deadline: StartupDeadline,
 }
 
 #[tracing::instrument(level = Level::TRACE, skip(kubeconfig), ret, err)]
@@ -348,33 +454,19 @@ where
         ..Default::default()
     };
 
-    // parse kubeconfig the same way as KUBECONFIG is parsed by `kube-client`, supporting
-    // colon-separated lists of paths. Borrowed affectionately & with love from
-    // https://docs.rs/kube/latest/kube/config/struct.Kubeconfig.html#method.from_env
-    let mut config = if let Some(kubeconfig) = kubeconfig
-        && let paths = std::env::split_paths(&kubeconfig)
-            .filter_map(|p| {
-                let path_str = p.as_os_str().to_string_lossy().into_owned();
-                path_str.is_empty().not().then_some(path_str)
-            })
-            .collect::<Vec<_>>()
-        && paths.is_empty().not()
-    {
-        let parsed_kube_config =
-            paths
-                .iter()
-                .try_fold(Kubeconfig::default(), |merged_kubeconfig, path_str| {
-                    let expanded = shellexpand::full(&path_str)
-                        .map_err(|e| KubeApiError::ConfigPathExpansionError(e.to_string()))?;
-
-                    Kubeconfig::read_from(expanded.deref())
-                        .and_then(|config| merged_kubeconfig.merge(config))
-                        .map_err(KubeApiError::from)
-                })?;
-        Config::from_custom_kubeconfig(parsed_kube_config, &kube_config_opts).await?
-    } else if kube_config_opts.context.is_some() {
+    let parsed_kube_config = match (read_kubeconfig(kubeconfig)?, &kube_config_opts.context) {
+        (Some(parsed_kube_config), _) => Some(parsed_kube_config),
         // if context is set, it's not in cluster so it has to be a kubeconfig.
-        Config::from_kubeconfig(&kube_config_opts).await?
+        (None, Some(_)) => Some(Kubeconfig::read()?),
+        (None, None) => None,
+    };
+
+    let mut config = if let Some(parsed_kube_config) = parsed_kube_config {
+        if let Some(context) = &kube_config_opts.context {
+            ensure_kube_context_exists(&parsed_kube_config, context)?;
+        }
+
+        Config::from_custom_kubeconfig(parsed_kube_config, &kube_config_opts).await?
     } else {
         // if context isn't set and user doesn't specify a kubeconfig, we infer which tries
         // local kube or in-cluster configuration.
@@ -388,6 +480,87 @@ where
     Ok(config)
 }
 
+/// Reads and merges the kubeconfig files from `kubeconfig`.
+///
+/// Returns [`None`] if no path was given.
+fn read_kubeconfig<P>(kubeconfig: Option<P>) -> Result<Option<Kubeconfig>>
+where
+    P: AsRef<OsStr>,
+{
+    // parse kubeconfig the same way as KUBECONFIG is parsed by `kube-client`, supporting
+    // colon-separated lists of paths. Borrowed affectionately & with love from
+    // https://docs.rs/kube/latest/kube/config/struct.Kubeconfig.html#method.from_env
+    let Some(kubeconfig) = kubeconfig else {
+        return Ok(None);
+    };
+
+    let paths = std::env::split_paths(&kubeconfig)
+        .filter_map(|p| {
+            let path_str = p.as_os_str().to_string_lossy().into_owned();
+            path_str.is_empty().not().then_some(path_str)
+        })
+        .collect::<Vec<_>>();
+
+    if paths.is_empty() {
+        return Ok(None);
+    }
+
+    paths
+        .iter()
+        .try_fold(Kubeconfig::default(), |merged_kubeconfig, path_str| {
+            let expanded = shellexpand::full(&path_str)
+                .map_err(|e| KubeApiError::ConfigPathExpansionError(e.to_string()))?;
+
+            Kubeconfig::read_from(expanded.deref())
+                .and_then(|config| merged_kubeconfig.merge(config))
+                .map_err(KubeApiError::from)
+        })
+        .map(Some)
+}
+
+/// Fails fast with [`KubeApiError::UnknownKubeContext`] if `context` is not defined in the
+/// kubeconfig, instead of the less helpful error from [`kube`].
+fn ensure_kube_context_exists(kubeconfig: &Kubeconfig, context: &str) -> Result<()> {
+    if kubeconfig
+        .contexts
+        .iter()
+        .any(|named| named.name == context)
+    {
+        return Ok(());
+    }
+
+    Err(KubeApiError::UnknownKubeContext {
+        context: context.to_owned(),
+        available: kubeconfig
+            .contexts
+            .iter()
+            .map(|named| named.name.clone())
+            .collect(),
+    })
+}
+
+/// Name of the kube context that mirrord uses: `kube_context` if set, otherwise the kubeconfig's
+/// `current-context`.
+///
+/// Best effort, used only for reporting. Returns [`None`] if there is no kubeconfig (e.g. when
+/// running in a cluster).
+pub fn selected_kube_context<P>(kubeconfig: Option<P>, kube_context: Option<&str>) -> Option<String>
+where
+    P: AsRef<OsStr>,
+{
+    if let Some(context) = kube_context {
+        return Some(context.to_owned());
+    }
+
+    let kubeconfig = match read_kubeconfig(kubeconfig) {
+        Ok(Some(kubeconfig)) => kubeconfig,
+        Ok(None) => Kubeconfig::read().ok()?,
+        Err(..) => return None,
+    };
+
+    kubeconfig.current_context
+}
+
 #[tracing::instrument(level = "trace", skip(client))]
 pub fn get_k8s_resource_api<K>(client: &Client, namespace: Option<&str>) -> Api<K>
 where
@@ -400,3 +573,38 @@ where
         Api::default_namespaced(client.clone())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use kube::config::{Kubeconfig, NamedContext};
+
+    use super::ensure_kube_context_exists;
+    use crate::error::KubeApiError;
+
+    #[test]
+    fn unknown_kube_context_lists_available() {
+        let kubeconfig = Kubeconfig {
+            contexts: ["staging", "production"]
+                .into_iter()
+                .map(|name| NamedContext {
+                    name: name.into(),
+                    context: None,
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        assert!(ensure_kube_context_exists(&kubeconfig, "staging").is_ok());
+
+        let error = ensure_kube_context_exists(&kubeconfig, "prod").unwrap_err();
+        assert!(matches!(
+            &error,
+            KubeApiError::UnknownKubeContext { context, available }
+                if context == "prod" && *available == ["staging", "production"]
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Kube context `prod` not found, available contexts: [staging, production]"
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Planning of the agent creation without touching the cluster, used by `mirrord exec --dry-run`.
+
+use std::fmt;
+
+use k8s_openapi::api::{batch::v1::Job, core::v1::EphemeralContainer as KubeEphemeralContainer};
+use mirrord_config::target::TargetConfig;
+use mirrord_progress::Progress;
+use serde::Serialize;
+use tracing::Level;
+
+use super::KubernetesAPI;
+use crate::{
+    api::container::{
+        ContainerConfig, ContainerVariant,
+        ephemeral::EphemeralTargetedVariant,
+        job::{JobTargetedVariant, JobVariant},
+    },
+    error::{KubeApiError, Result},
+};
+
+/// Agent name used in place of the randomly generated one, so that dry-run output is stable.
+pub const DRY_RUN_AGENT_NAME: &str = "mirrord-agent-dry-run";
+
+/// Agent port used when [`ContainerConfig::port`] is not set.
+pub const DRY_RUN_AGENT_PORT: u16 = 30000;
+
+/// Agent group id used in place of the randomly generated one.
+pub const DRY_RUN_AGENT_GID: u16 = 3000;
+
+/// How the agent would be spawned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AgentStrategy {
+    /// A [`Job`] not tied to any target.
+    TargetlessJob,
+    /// A [`Job`] scheduled on the target's node.
+    TargetedJob,
+    /// An ephemeral container added to the target pod.
+    EphemeralContainer,
+}
+
+impl fmt::Display for AgentStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let strategy = match self {
+            Self::TargetlessJob => "targetless job",
+            Self::TargetedJob => "targeted job",
+            Self::EphemeralContainer => "ephemeral container",
+        };
+
+        f.write_str(strategy)
+    }
+}
+
+/// A single Kubernetes API request that would be made when spawning the agent.
+#[derive(Clone, Debug)]
+pub struct PlannedOperation {
+    /// HTTP verb of the request.
+    pub verb: &'static str,
+    /// Path of the request, relative to the API server.
+    pub path: String,
+    /// Object sent in the request body, if any.
+    pub body: Option<serde_json::Value>,
+}
+
+impl PlannedOperation {
+    fn new(verb: &'static str, path: String) -> Self {
+        Self {
+            verb,
+            path,
+            body: None,
+        }
+    }
+
+    fn with_body<T: Serialize>(mut self, body: &T) -> Result<Self> {
+        self.body = Some(serde_json::to_value(body).map_err(KubeApiError::DryRunSerialize)?);
+        Ok(self)
+    }
+}
+
+/// Result of [`KubernetesAPI::plan_agent`].
+#[derive(Clone, Debug)]
+pub struct AgentPlan {
+    pub strategy: AgentStrategy,
+    /// Requests in the order in which they would be made.
+    pub operations: Vec<PlannedOperation>,
+}
+
+impl KubernetesAPI {
+    /// Resolves the target and renders the agent spec, without creating anything in the cluster.
+    ///
+    /// The only requests made here are reads required to resolve the target. The randomly
+    /// generated parts of [`ContainerParams`](crate::api::container::ContainerParams) are replaced
+    /// with fixed values, so that the output can be compared between runs.
+    #[tracing::instrument(level = Level::TRACE, skip(self, progress), ret, err)]
+    pub async fn plan_agent<P: Progress>(
+        &self,
+        progress: &P,
+        target_config: &TargetConfig,
+        mut container_config: ContainerConfig,
+    ) -> Result<AgentPlan> {
+        container_config.port.get_or_insert(DRY_RUN_AGENT_PORT);
+
+        let (mut params, runtime_data) = self
+            .create_agent_params(progress, target_config, container_config)
+            .await?;
+        params.name = DRY_RUN_AGENT_NAME.to_string();
+        params.gid = DRY_RUN_AGENT_GID;
+
+        let agent_namespace = self
+            .agent
+            .namespace
+            .as_deref()
+            .unwrap_or(self.client.default_namespace());
+        let jobs_path = format!("/apis/batch/v1/namespaces/{agent_namespace}/jobs");
+        let watch_pods = PlannedOperation::new(
+            "WATCH",
+            format!(
+                "/api/v1/namespaces/{agent_namespace}/pods?labelSelector=job-name={}",
+                params.name
+            ),
+        );
+
+        let (strategy, mut operations) = match (runtime_data, self.agent.ephemeral) {
+            (None, false) => {
+                let job: Job = JobVariant::new(&self.agent, &params).as_update();
+
+                (
+                    AgentStrategy::TargetlessJob,
+                    vec![
+                        PlannedOperation::new("POST", jobs_path).with_body(&job)?,
+                        watch_pods,
+                    ],
+                )
+            }
+            (Some(runtime_data), false) => {
+                let job: Job =
+                    JobTargetedVariant::new(&self.agent, &params, &runtime_data).as_update();
+
+                (
+                    AgentStrategy::TargetedJob,
+                    vec![
+                        PlannedOperation::new("POST", jobs_path).with_body(&job)?,
+                        watch_pods,
+                    ],
+                )
+            }
+            (Some(runtime_data), true) => {
+                let container: KubeEphemeralContainer =
+                    EphemeralTargetedVariant::new(&self.agent, &params, &runtime_data).as_update();
+                let pod_path = format!(
+                    "/api/v1/namespaces/{}/pods/{}",
+                    runtime_data.pod_namespace, runtime_data.pod_name
+                );
+
+                (
+                    AgentStrategy::EphemeralContainer,
+                    vec![
+                        PlannedOperation::new("GET", pod_path.clone()),
+                        PlannedOperation::new("GET", format!("{pod_path}/ephemeralcontainers")),
+                        PlannedOperation::new("PUT", format!("{pod_path}/ephemeralcontainers"))
+                            .with_body(&container)?,
+                        PlannedOperation::new("WATCH", pod_path),
+                    ],
+                )
+            }
+            (None, true) => return Err(KubeApiError::MissingRuntimeData),
+        };
+
+        operations.push(PlannedOperation::new(
+            "CONNECT",
+            format!(
+                "/api/v1/namespaces/{{agent-pod-namespace}}/pods/{{agent-pod}}/portforward?ports={}",
+                params.port
+            ),
+        ));
+
+        Ok(AgentPlan {
+            strategy,
+            operations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use kube::{Client, Config};
+    use mirrord_config::{
+        agent::AgentFileConfig,
+        config::{ConfigContext, MirrordConfig},
+        target::TargetFileConfig,
+    };
+    use mirrord_progress::NullProgress;
+    use rstest::rstest;
+
+    use super::{AgentStrategy, DRY_RUN_AGENT_NAME, KubernetesAPI};
+    use crate::{api::container::ContainerConfig, error::KubeApiError};
+
+    fn kubernetes_api(namespace: Option<&str>, ephemeral: bool) -> KubernetesAPI {
+        let mut context = ConfigContext::default().strict_env(true);
+        let mut agent = AgentFileConfig::default()
+            .generate_config(&mut context)
+            .unwrap();
+        agent.namespace = namespace.map(ToOwned::to_owned);
+        agent.ephemeral = ephemeral;
+
+        let config = Config::new("http://127.0.0.1:6443".parse().unwrap());
+
+        KubernetesAPI::new(Client::try_from(config).unwrap(), agent)
+    }
+
+    #[rstest]
+    #[case::default_namespace(None, "default")]
+    #[case::agent_namespace(Some("agents"), "agents")]
+    #[tokio::test]
+    async fn targetless_job(#[case] agent_namespace: Option<&str>, #[case] expected: &str) {
+        let target = TargetFileConfig::default()
+            .generate_config(&mut ConfigContext::default().strict_env(true))
+            .unwrap();
+
+        let plan = kubernetes_api(agent_namespace, false)
+            .plan_agent(&NullProgress, &target, ContainerConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(plan.strategy, AgentStrategy::TargetlessJob);
+        let jobs_path = format!("/apis/batch/v1/namespaces/{expected}/jobs");
+        let pods_path = format!(
+            "/api/v1/namespaces/{expected}/pods?labelSelector=job-name={DRY_RUN_AGENT_NAME}"
+        );
+        let operations = plan
+            .operations
+            .iter()
+            .map(|operation| (operation.verb, operation.path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            operations,
+            [
+                ("POST", jobs_path.as_str()),
+                ("WATCH", pods_path.as_str()),
+                (
+                    "CONNECT",
+                    "/api/v1/namespaces/{agent-pod-namespace}/pods/{agent-pod}/portforward?ports=30000"
+                ),
+            ]
+        );
+
+        let job_name = plan
+            .operations
+            .first()
+            .and_then(|operation| operation.body.as_ref())
+            .and_then(|body| body.pointer("/metadata/name"));
+        assert_eq!(job_name, Some(&serde_json::Value::from(DRY_RUN_AGENT_NAME)));
+    }
+
+    /// Ephemeral containers need a target pod to be added to.
+    #[tokio::test]
+    async fn targetless_ephemeral_fails() {
+        let target = TargetFileConfig::default()
+            .generate_config(&mut ConfigContext::default().strict_env(true))
+            .unwrap();
+
+        let result = kubernetes_api(None, true)
+            .plan_agent(&NullProgress, &target, ContainerConfig::default())
+            .await;
+
+        assert!(matches!(result, Err(KubeApiError::MissingRuntimeData)));
+    }
+}
@@ -27,18 +27,24 @@ pub struct KubeResourceSeeker<'a> {
 
 impl KubeResourceSeeker<'_> {
     /// Returns all resource types that don't require the operator to operate ie. [`Pod`],
-    /// [`Deployment`] and [`Rollout`]
+    /// [`Deployment`], [`Rollout`], [`StatefulSet`], [`CronJob`] and [`Job`]
     pub async fn all_open_source(&self) -> Result<Vec<String>> {
-        let (pods, deployments, rollouts) = tokio::try_join!(
+        let (pods, deployments, rollouts, statefulsets, cronjobs, jobs) = tokio::try_join!(
             self.pods(),
             self.deployments(),
-            self.simple_list_resource::<Rollout>("rollout")
+            self.simple_list_resource::<Rollout>("rollout"),
+            self.simple_list_resource::<StatefulSet>("statefulset"),
+            self.simple_list_resource::<CronJob>("cronjob"),
+            self.simple_list_resource::<Job>("job"),
         )?;
 
         Ok(pods
             .into_iter()
             .chain(deployments)
             .chain(rollouts)
+            .chain(statefulsets)
+            .chain(cronjobs)
+            .chain(jobs)
             .collect())
     }
 
@@ -106,11 +112,9 @@ impl KubeResourceSeeker<'_> {
             TargetType::Deployment => self.deployments().await,
             TargetType::Pod => self.pods().await,
             TargetType::Rollout => self.simple_list_resource::<Rollout>("rollout").await,
-            TargetType::Job if operator_active => self.simple_list_resource::<Job>("job").await,
-            TargetType::CronJob if operator_active => {
-                self.simple_list_resource::<CronJob>("cronjob").await
-            }
-            TargetType::StatefulSet if operator_active => {
+            TargetType::Job => self.simple_list_resource::<Job>("job").await,
+            TargetType::CronJob => self.simple_list_resource::<CronJob>("cronjob").await,
+            TargetType::StatefulSet => {
                 self.simple_list_resource::<StatefulSet>("statefulset")
                     .await
             }
@@ -15,8 +15,8 @@ use k8s_openapi::{
     apimachinery::pkg::util::intstr::IntOrString,
 };
 use kube::{Api, Client, Resource, api::ListParams};
-use mirrord_agent_env::mesh::MeshVendor;
-use mirrord_config::target::Target;
+use mirrord_agent_env::{mesh::MeshVendor, probes::HttpProbe};
+use mirrord_config::target::{Target, pod_selection::PodSelectionConfig};
 use serde::de::DeserializeOwned;
 use thiserror::Error;
 use tracing::Level;
@@ -25,6 +25,7 @@ use crate::{
     api::{
         container::{check_mesh_vendor, choose_container},
         kubernetes::get_k8s_resource_api,
+        runtime::pod_selection::{SelectedPod, SelectionReason, select_pod},
     },
     error::{KubeApiError, Result},
     extract::{
@@ -38,11 +39,16 @@ pub mod cron_job;
 pub mod deployment;
 pub mod job;
 pub mod pod;
+pub mod pod_selection;
 pub mod replica_set;
+pub mod replicas;
 pub mod rollout;
 pub mod service;
 pub mod stateful_set;
 
+/// The only pod phase accepted by [`RuntimeData::from_pod_checked`].
+const RUNNING_PHASE: &str = "Running";
+
 #[derive(Debug)]
 pub enum ContainerRuntime {
     Docker,
@@ -87,6 +93,8 @@ pub struct RuntimeData {
     pub container_id: String,
     pub container_runtime: ContainerRuntime,
     pub container_name: String,
+    /// Names and ids of all running containers in the pod, including the target container.
+    pub pod_containers: BTreeMap<String, String>,
     /// True when no container was specified by the user, but there are multiple containers,
     /// so mirrord chose one of them for the user.
     pub guessed_container: bool,
@@ -99,6 +107,9 @@ pub struct RuntimeData {
     /// Ports where HTTP/gRPC probes are configured
     /// in the target pod.
     pub containers_probe_ports: Vec<u16>,
+
+    /// HTTP probes configured in the target pod.
+    pub containers_http_probes: Vec<HttpProbe>,
 }
 
 impl RuntimeData {
@@ -113,6 +124,18 @@ impl RuntimeData {
     /// 2. pod is not in deletion,
     /// 3. target container is ready.
     pub fn from_pod(pod: &Pod, container_name: Option<&str>) -> Result<Self> {
+        Self::from_pod_checked(pod, container_name, true)
+    }
+
+    /// Works like [`RuntimeData::from_pod`], but when `require_ready` is not set, accepts a
+    /// [`Pod`] that is in deletion or whose target container is not ready.
+    ///
+    /// The [`Pod`] still has to be in "Running" phase.
+    pub fn from_pod_checked(
+        pod: &Pod,
+        container_name: Option<&str>,
+        require_ready: bool,
+    ) -> Result<Self> {
         let (Name(pod_name), Namespace(pod_namespace)) = FromResource::from_resource(pod, &())?;
 
         let phase = pod
@@ -120,11 +143,11 @@ impl RuntimeData {
             .as_ref()
             .and_then(|status| status.phase.as_ref())
             .ok_or_else(|| KubeApiError::missing_field(pod, ".status.phase"))?;
-        if phase != "Running" {
+        if phase != RUNNING_PHASE {
             return Err(KubeApiError::invalid_state(pod, "not in 'Running' phase"));
         }
 
-        if pod.metadata.deletion_timestamp.is_some() {
+        if require_ready && pod.metadata.deletion_timestamp.is_some() {
             return Err(KubeApiError::invalid_state(pod, "in deletion"));
         }
 
@@ -135,14 +158,14 @@ impl RuntimeData {
             .ok_or_else(|| KubeApiError::missing_field(pod, ".spec.nodeName"))?
             .to_owned();
 
-        let containers_probe_ports: Vec<u16> = pod
+        let containers = &pod
             .spec
             .as_ref()
             .ok_or_else(|| KubeApiError::missing_field(pod, ".spec"))?
-            .containers
-            .iter()
-            .flat_map(Self::probe_ports)
-            .collect();
+            .containers;
+        let containers_probe_ports: Vec<u16> =
+            containers.iter().flat_map(Self::probe_ports).collect();
+        let containers_http_probes = containers.iter().flat_map(Self::http_probes).collect();
 
         let pod_ips = pod
             .status
@@ -191,7 +214,7 @@ impl RuntimeData {
             None => KubeApiError::invalid_state(pod, "no viable target container found"),
         })?;
 
-        if !chosen_status.ready {
+        if require_ready && !chosen_status.ready {
             return Err(KubeApiError::invalid_state(
                 pod,
                 format_args!("target container `{}` is not ready", chosen_status.name),
@@ -221,6 +244,14 @@ impl RuntimeData {
             }
         };
 
+        let pod_containers = container_statuses
+            .iter()
+            .filter_map(|status| {
+                let (_, id) = status.container_id.as_deref()?.split_once("://")?;
+                Some((status.name.clone(), id.to_owned()))
+            })
+            .collect();
+
         let mesh = check_mesh_vendor(pod);
 
         Ok(RuntimeData {
@@ -232,6 +263,7 @@ impl RuntimeData {
             container_id,
             container_runtime,
             container_name,
+            pod_containers,
             guessed_container,
             mesh,
             share_process_namespace: pod
@@ -240,6 +272,7 @@ impl RuntimeData {
                 .and_then(|spec| spec.share_process_namespace)
                 .unwrap_or_default(),
             containers_probe_ports,
+            containers_http_probes,
         })
     }
 
@@ -314,28 +347,29 @@ impl RuntimeData {
         }
     }
 
+    /// Resolves the port of a probe, which can be given by the name of a container port.
+    fn probe_port(port: &IntOrString, container_ports: &[ContainerPort]) -> Option<u16> {
+        match port {
+            IntOrString::Int(p) => Some(*p as u16),
+            IntOrString::String(port_name) => container_ports
+                .iter()
+                .find(|p| p.name.as_ref() == Some(port_name))
+                .and_then(|p| p.container_port.try_into().ok()),
+        }
+    }
+
     /// Returns a list of ports used by the container's readiness and liveness probes.
     ///
     /// * Startup probes are ignored: target needs to be ready to start a new session anyway
     /// * TCP probes are ignored: the agent will accept the connection anyway
     fn probe_ports(container: &Container) -> Vec<u16> {
-        fn port_to_int(port: &IntOrString, container_ports: &[ContainerPort]) -> Option<u16> {
-            match port {
-                IntOrString::Int(p) => Some(*p as u16),
-                IntOrString::String(port_name) => container_ports
-                    .iter()
-                    .find(|p| p.name.as_ref() == Some(port_name))
-                    .and_then(|p| p.container_port.try_into().ok()),
-            }
-        }
-
         fn get_probe_ports(probe: &Probe, container_ports: &[ContainerPort]) -> Vec<u16> {
             let mut ports = Vec::new();
 
             if let Some(port) = probe
                 .http_get
                 .as_ref()
-                .and_then(|get| port_to_int(&get.port, container_ports))
+                .and_then(|get| RuntimeData::probe_port(&get.port, container_ports))
             {
                 ports.push(port);
             }
@@ -369,6 +403,28 @@ impl RuntimeData {
 
         ports
     }
+
+    /// Returns the HTTP `GET` readiness and liveness probes of the container, ignoring startup
+    /// probes like [`RuntimeData::probe_ports`].
+    fn http_probes(container: &Container) -> Vec<HttpProbe> {
+        let container_ports = container.ports.as_deref().unwrap_or_default();
+
+        [&container.liveness_probe, &container.readiness_probe]
+            .into_iter()
+            .flatten()
+            .filter_map(|probe| probe.http_get.as_ref())
+            .filter_map(|get| {
+                let port = Self::probe_port(&get.port, container_ports)?;
+                let path = get.path.as_deref().unwrap_or("/");
+                let path = path.split_once('?').map_or(path, |(path, _)| path);
+
+                Some(HttpProbe {
+                    port,
+                    path: path.to_owned(),
+                })
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -433,13 +489,8 @@ pub trait RuntimeDataFromLabels {
 
             let labels = Self::get_selector_match_labels(&resource)?;
 
-            let formatted_labels = labels
-                .iter()
-                .map(|(key, value)| format!("{key}={value}"))
-                .collect::<Vec<String>>()
-                .join(",");
             let list_params = ListParams {
-                label_selector: Some(formatted_labels),
+                label_selector: Some(format_label_selector(&labels)),
                 ..Default::default()
             };
 
@@ -454,6 +505,47 @@ pub trait RuntimeDataFromLabels {
     fn name(&self) -> Cow<'_, str>;
 
     fn container(&self) -> Option<&str>;
+
+    /// Resolves [`RuntimeData`] from the pod picked with [`select_pod`].
+    ///
+    /// Unlike [`RuntimeDataProvider::runtime_data`], which takes the first viable pod, this gives
+    /// the user control over which pod is targeted. Returns the [`SelectionReason`] along with the
+    /// data, so that the choice can be reported.
+    fn runtime_data_with_selection(
+        &self,
+        client: &Client,
+        namespace: Option<&str>,
+        selection: &PodSelectionConfig,
+    ) -> impl Future<Output = Result<(RuntimeData, SelectionReason)>> {
+        async move {
+            let api: Api<<Self as RuntimeDataFromLabels>::Resource> =
+                get_k8s_resource_api(client, namespace);
+            let resource = api.get(&self.name()).await?;
+            let pods = Self::get_pods(&resource, client).await?;
+
+            if pods.is_empty() {
+                return Err(KubeApiError::invalid_state(
+                    &resource,
+                    "no pods matching the labels were found",
+                ));
+            }
+
+            let SelectedPod { pod, reason } = select_pod(&pods, selection).ok_or_else(|| {
+                KubeApiError::invalid_state(
+                    &resource,
+                    format_args!(
+                        "no pod matching the labels can be selected with strategy `{}`",
+                        selection.strategy
+                    ),
+                )
+            })?;
+
+            let runtime_data =
+                RuntimeData::from_pod_checked(pod, self.container(), !selection.allow_not_ready)?;
+
+            Ok((runtime_data, reason))
+        }
+    }
 }
 
 impl<T> RuntimeDataProvider for T
@@ -517,10 +609,20 @@ impl RuntimeDataProvider for ResolvedTarget<true> {
     }
 }
 
+/// Formats the given labels as an equality-based label selector, e.g. `app=foo,tier=web`.
+pub fn format_label_selector(labels: &BTreeMap<String, String>) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
 #[cfg(test)]
 mod tests {
     use mirrord_config::target::{
         deployment::DeploymentTarget, job::JobTarget, pod::PodTarget, service::ServiceTarget,
+        stateful_set::StatefulSetTarget,
     };
     use rstest::rstest;
 
@@ -536,9 +638,14 @@ mod tests {
     #[case("job/foo/container/baz", Target::Job(JobTarget { job: "foo".to_string(), container: Some("baz".to_string()) }))]
     #[case("service/foo", Target::Service(ServiceTarget { service: "foo".into(), container: None }))]
     #[case("service/foo/container/baz", Target::Service(ServiceTarget { service: "foo".into(), container: Some("baz".into()) }))]
+    #[case("statefulset/db", Target::StatefulSet(StatefulSetTarget { stateful_set: "db".into(), ordinal: None, container: None }))]
+    #[case("statefulset/db/pod/2", Target::StatefulSet(StatefulSetTarget { stateful_set: "db".into(), ordinal: Some(2), container: None }))]
+    #[case("statefulset/db/pod/2/container/pg", Target::StatefulSet(StatefulSetTarget { stateful_set: "db".into(), ordinal: Some(2), container: Some("pg".into()) }))]
+    #[case("statefulset/db/container/pg", Target::StatefulSet(StatefulSetTarget { stateful_set: "db".into(), ordinal: None, container: Some("pg".into()) }))]
     fn target_parses(#[case] target: &str, #[case] expected: Target) {
-        let target = target.parse::<Target>().unwrap();
-        assert_eq!(target, expected)
+        let parsed = target.parse::<Target>().unwrap();
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.to_string(), target);
     }
 
     #[allow(clippy::duplicated_attributes)]
@@ -547,6 +654,10 @@ mod tests {
     #[case::panic("deployment/foobaz/blah")]
     #[should_panic(expected = "InvalidTarget")]
     #[case::panic("pod/foo/baz")]
+    #[should_panic(expected = "InvalidTarget")]
+    #[case::panic("statefulset/db/pod/first")]
+    #[should_panic(expected = "InvalidTarget")]
+    #[case::panic("statefulset/db/pod/2/pg")]
     fn target_parse_fails(#[case] target: &str) {
         let target = target.parse::<Target>().unwrap();
         assert_eq!(
@@ -557,4 +668,30 @@ mod tests {
             })
         )
     }
+
+    #[test]
+    fn container_http_probes() {
+        let container: Container = serde_json::from_value(serde_json::json!({
+            "name": "app",
+            "ports": [{ "name": "http", "containerPort": 8080 }],
+            "livenessProbe": { "httpGet": { "path": "/healthz?full=1", "port": "http" } },
+            "readinessProbe": { "httpGet": { "port": 8081 } },
+            "startupProbe": { "httpGet": { "path": "/started", "port": 8080 } },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            RuntimeData::http_probes(&container),
+            [
+                HttpProbe {
+                    port: 8080,
+                    path: "/healthz".into()
+                },
+                HttpProbe {
+                    port: 8081,
+                    path: "/".into()
+                },
+            ]
+        );
+    }
 }
@@ -1,40 +1,250 @@
-use std::{borrow::Cow, collections::BTreeMap};
+use std::collections::BTreeMap;
 
-use k8s_openapi::api::batch::v1::CronJob;
-use mirrord_config::target::cron_job::CronJobTarget;
+use k8s_openapi::{
+    api::batch::v1::{CronJob, Job},
+    apimachinery::pkg::apis::meta::v1::ObjectMeta,
+};
+use kube::{Api, Client, Resource, api::PostParams};
+use mirrord_config::target::{cron_job::CronJobTarget, job::JobTarget};
 
-use super::RuntimeDataFromLabels;
-use crate::error::{KubeApiError, Result};
+use super::{RuntimeData, RuntimeDataProvider, job::finished_condition};
+use crate::{
+    api::kubernetes::get_k8s_resource_api,
+    error::{KubeApiError, Result},
+};
 
-impl RuntimeDataFromLabels for CronJobTarget {
-    type Resource = CronJob;
+/// Annotation set by `kubectl create job --from=cronjob/<name>` on the [`Job`]s it instantiates
+/// from a [`CronJob`].
+pub const CRON_JOB_INSTANTIATE_ANNOTATION: &str = "cronjob.kubernetes.io/instantiate";
 
-    fn name(&self) -> Cow<'_, str> {
-        Cow::from(&self.cron_job)
+/// A [`CronJob`] has no pods of its own, so it is resolved to a pod of its most recent active
+/// [`Job`], see [`latest_active_job`].
+impl RuntimeDataProvider for CronJobTarget {
+    async fn runtime_data(&self, client: &Client, namespace: Option<&str>) -> Result<RuntimeData> {
+        let api: Api<CronJob> = get_k8s_resource_api(client, namespace);
+        let cron_job = api.get(&self.cron_job).await?;
+        let job = latest_active_job(client, &cron_job).await?;
+
+        JobTarget {
+            job: job.metadata.name.unwrap_or_default(),
+            container: self.container.clone(),
+        }
+        .runtime_data(client, job.metadata.namespace.as_deref())
+        .await
+    }
+}
+
+/// Fetches the most recent of the [`Job`]s listed as active in the [`CronJob`]'s status.
+///
+/// Fails if the [`CronJob`] has no active [`Job`]s, in which case one can be created with
+/// [`create_job_from_cron_job`].
+pub async fn latest_active_job(client: &Client, cron_job: &CronJob) -> Result<Job> {
+    let api: Api<Job> = get_k8s_resource_api(client, cron_job.metadata.namespace.as_deref());
+
+    let mut jobs = Vec::new();
+    for reference in cron_job
+        .status
+        .as_ref()
+        .and_then(|status| status.active.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        let Some(name) = reference.name.as_deref() else {
+            continue;
+        };
+
+        // The job may be gone by the time we get to it.
+        if let Some(job) = api.get_opt(name).await? {
+            jobs.push(job);
+        }
+    }
+
+    pick_latest_job(jobs).ok_or_else(|| {
+        KubeApiError::invalid_state(
+            cron_job,
+            "cron job has no active jobs, enable `target.create_job` to create one from its job \
+            template",
+        )
+    })
+}
+
+/// Picks the most recently started of the given [`Job`]s that are not finished yet.
+fn pick_latest_job(jobs: Vec<Job>) -> Option<Job> {
+    jobs.into_iter()
+        .filter(|job| finished_condition(job).is_none())
+        .max_by(|a, b| {
+            let started = |job: &Job| {
+                job.status
+                    .as_ref()
+                    .and_then(|status| status.start_time.clone())
+                    .or_else(|| job.metadata.creation_timestamp.clone())
+            };
+
+            started(a).cmp(&started(b))
+        })
+}
+
+/// Prepares a [`Job`] from the given [`CronJob`]'s job template, the same way
+/// `kubectl create job --from=cronjob/<name>` does.
+///
+/// The given `labels` are added to the ones from the template. The [`Job`] is owned by the
+/// [`CronJob`], and its name is generated by the cluster.
+pub fn job_from_cron_job(cron_job: &CronJob, labels: BTreeMap<String, String>) -> Result<Job> {
+    let cron_job_name = cron_job
+        .metadata
+        .name
+        .as_deref()
+        .ok_or_else(|| KubeApiError::missing_field(cron_job, ".metadata.name"))?;
+    let template = &cron_job
+        .spec
+        .as_ref()
+        .ok_or_else(|| KubeApiError::missing_field(cron_job, ".spec"))?
+        .job_template;
+    let template_metadata = template.metadata.clone().unwrap_or_default();
+
+    let mut job_labels = template_metadata.labels.unwrap_or_default();
+    job_labels.extend(labels);
+
+    let mut annotations = template_metadata.annotations.unwrap_or_default();
+    annotations.insert(
+        CRON_JOB_INSTANTIATE_ANNOTATION.to_owned(),
+        "manual".to_owned(),
+    );
+
+    Ok(Job {
+        metadata: ObjectMeta {
+            generate_name: Some(format!("{cron_job_name}-mirrord-")),
+            namespace: cron_job.metadata.namespace.clone(),
+            labels: Some(job_labels),
+            annotations: Some(annotations),
+            owner_references: cron_job.controller_owner_ref(&()).map(|owner| vec![owner]),
+            ..Default::default()
+        },
+        spec: template.spec.clone(),
+        status: None,
+    })
+}
+
+/// Creates a new [`Job`] from the job template of the [`CronJob`] with the given `name`, see
+/// [`job_from_cron_job`].
+pub async fn create_job_from_cron_job(
+    client: &Client,
+    namespace: Option<&str>,
+    name: &str,
+    labels: BTreeMap<String, String>,
+) -> Result<Job> {
+    let cron_job_api: Api<CronJob> = get_k8s_resource_api(client, namespace);
+    let cron_job = cron_job_api.get(name).await?;
+
+    let job = job_from_cron_job(&cron_job, labels)?;
+    let job_api: Api<Job> = get_k8s_resource_api(client, cron_job.metadata.namespace.as_deref());
+
+    Ok(job_api.create(&PostParams::default(), &job).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::{
+        api::batch::v1::{CronJobSpec, JobCondition, JobSpec, JobStatus, JobTemplateSpec},
+        apimachinery::pkg::apis::meta::v1::Time,
+        chrono::{TimeZone, Utc},
+    };
+
+    use super::*;
+
+    fn job(name: &str, started_at: i64, finished: bool) -> Job {
+        Job {
+            metadata: ObjectMeta {
+                name: Some(name.to_owned()),
+                ..Default::default()
+            },
+            spec: None,
+            status: Some(JobStatus {
+                start_time: Some(Time(Utc.timestamp_opt(started_at, 0).unwrap())),
+                conditions: finished.then(|| {
+                    vec![JobCondition {
+                        type_: "Complete".to_owned(),
+                        status: "True".to_owned(),
+                        ..Default::default()
+                    }]
+                }),
+                ..Default::default()
+            }),
+        }
     }
 
-    fn container(&self) -> Option<&str> {
-        self.container.as_deref()
+    #[test]
+    fn picks_latest_unfinished_job() {
+        let latest = pick_latest_job(vec![
+            job("first", 100, false),
+            job("second", 200, false),
+            job("third", 300, true),
+        ]);
+
+        assert_eq!(
+            latest.and_then(|job| job.metadata.name).as_deref(),
+            Some("second")
+        );
+        assert!(pick_latest_job(vec![job("done", 100, true)]).is_none());
     }
 
-    fn get_selector_match_labels(resource: &Self::Resource) -> Result<BTreeMap<String, String>> {
-        resource
-            .spec
-            .as_ref()
-            .and_then(|spec| {
-                spec.job_template
-                    .spec
-                    .as_ref()?
-                    .selector
-                    .as_ref()?
-                    .match_labels
-                    .clone()
-            })
-            .ok_or_else(|| {
-                KubeApiError::missing_field(
-                    resource,
-                    ".spec.selector or .spec.selector.matchLabels",
-                )
-            })
+    #[test]
+    fn job_from_cron_job_template() {
+        let cron_job = CronJob {
+            metadata: ObjectMeta {
+                name: Some("nightly".to_owned()),
+                namespace: Some("batch".to_owned()),
+                uid: Some("cron-job-uid".to_owned()),
+                ..Default::default()
+            },
+            spec: Some(CronJobSpec {
+                schedule: "0 0 * * *".to_owned(),
+                job_template: JobTemplateSpec {
+                    metadata: Some(ObjectMeta {
+                        labels: Some(BTreeMap::from([("app".to_owned(), "nightly".to_owned())])),
+                        ..Default::default()
+                    }),
+                    spec: Some(JobSpec {
+                        backoff_limit: Some(0),
+                        ..Default::default()
+                    }),
+                },
+                ..Default::default()
+            }),
+            status: None,
+        };
+
+        let job = job_from_cron_job(
+            &cron_job,
+            BTreeMap::from([("owner".to_owned(), "me".to_owned())]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            job.metadata.generate_name.as_deref(),
+            Some("nightly-mirrord-")
+        );
+        assert_eq!(job.metadata.namespace.as_deref(), Some("batch"));
+        assert_eq!(
+            job.metadata.labels,
+            Some(BTreeMap::from([
+                ("app".to_owned(), "nightly".to_owned()),
+                ("owner".to_owned(), "me".to_owned()),
+            ]))
+        );
+        assert_eq!(
+            job.metadata
+                .annotations
+                .as_ref()
+                .and_then(|annotations| annotations.get(CRON_JOB_INSTANTIATE_ANNOTATION))
+                .map(String::as_str),
+            Some("manual")
+        );
+
+        let owner = job.metadata.owner_references.unwrap().remove(0);
+        assert_eq!(owner.kind, "CronJob");
+        assert_eq!(owner.name, "nightly");
+        assert_eq!(owner.controller, Some(true));
+        assert_eq!(job.spec.and_then(|spec| spec.backoff_limit), Some(0));
     }
 }
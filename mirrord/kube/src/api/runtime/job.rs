@@ -1,10 +1,17 @@
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{borrow::Cow, collections::BTreeMap, time::Duration};
 
-use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::{batch::v1::Job, core::v1::Pod};
+use kube::{Api, Client, api::ListParams};
 use mirrord_config::target::job::JobTarget;
 
-use super::RuntimeDataFromLabels;
-use crate::error::{KubeApiError, Result};
+use super::{RuntimeData, RuntimeDataFromLabels};
+use crate::{
+    api::kubernetes::get_k8s_resource_api,
+    error::{KubeApiError, Result},
+};
+
+/// How often [`wait_for_job_runtime_data`] checks for a pod that can be targeted.
+const JOB_POD_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 impl RuntimeDataFromLabels for JobTarget {
     type Resource = Job;
@@ -30,3 +37,83 @@ impl RuntimeDataFromLabels for JobTarget {
             })
     }
 }
+
+/// Returns the type of the condition that marks the given [`Job`] as finished (`Complete` or
+/// `Failed`), if there is one.
+pub(crate) fn finished_condition(job: &Job) -> Option<&str> {
+    job.status
+        .as_ref()?
+        .conditions
+        .as_ref()?
+        .iter()
+        .find(|condition| {
+            matches!(condition.type_.as_str(), "Complete" | "Failed") && condition.status == "True"
+        })
+        .map(|condition| condition.type_.as_str())
+}
+
+/// Resolves [`RuntimeData`] from one of the pods of the [`Job`] targeted with the given
+/// [`JobTarget`].
+///
+/// Jobs are often targeted right after they're created, so when none of the pods can be targeted
+/// yet, this waits for one, up to the given `timeout` (indefinitely if it's [`None`]). Fails early
+/// if the [`Job`] finishes in the meantime.
+pub async fn wait_for_job_runtime_data(
+    client: &Client,
+    namespace: Option<&str>,
+    target: &JobTarget,
+    timeout: Option<Duration>,
+) -> Result<RuntimeData> {
+    let job_api: Api<Job> = get_k8s_resource_api(client, namespace);
+    let job = job_api.get(&target.job).await?;
+
+    let label_selector = JobTarget::get_selector_match_labels(&job)?
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<String>>()
+        .join(",");
+    let list_params = ListParams::default().labels(&label_selector);
+    let pod_api: Api<Pod> = get_k8s_resource_api(client, job.metadata.namespace.as_deref());
+
+    let wait = async {
+        let mut job = job.clone();
+
+        loop {
+            if let Some(condition) = finished_condition(&job) {
+                return Err(KubeApiError::invalid_state(
+                    &job,
+                    format_args!("job has already finished (`{condition}`)"),
+                ));
+            }
+
+            let pods = pod_api.list(&list_params).await?;
+            if let Some(runtime_data) = pods
+                .items
+                .iter()
+                .find_map(|pod| RuntimeData::from_pod(pod, target.container()).ok())
+            {
+                return Ok(runtime_data);
+            }
+
+            tracing::debug!(
+                job = %target.job,
+                "No pod of the job can be targeted yet, waiting"
+            );
+            tokio::time::sleep(JOB_POD_POLL_INTERVAL).await;
+            job = job_api.get(&target.job).await?;
+        }
+    };
+
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, wait).await.map_err(|_| {
+            KubeApiError::invalid_state(
+                &job,
+                format_args!(
+                    "no pod of the job was ready to be targeted within {}s",
+                    timeout.as_secs()
+                ),
+            )
+        })?,
+        None => wait.await,
+    }
+}
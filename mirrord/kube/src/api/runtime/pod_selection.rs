@@ -0,0 +1,250 @@
+//! Choosing a single pod out of a workload's pods, according to [`PodSelectionConfig`].
+
+use std::{cmp::Reverse, fmt};
+
+use k8s_openapi::api::core::v1::Pod;
+use mirrord_config::target::pod_selection::{PodSelectionConfig, PodSelectionStrategy};
+
+use super::RUNNING_PHASE;
+
+/// A pod picked by [`select_pod`], along with a human readable reason for the choice.
+#[derive(Debug)]
+pub struct SelectedPod<'a> {
+    pub pod: &'a Pod,
+    pub reason: SelectionReason,
+}
+
+/// Why [`select_pod`] picked the [`SelectedPod::pod`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionReason {
+    pub strategy: PodSelectionStrategy,
+    /// How many pods were considered.
+    pub candidates: usize,
+    /// How many pods were skipped because they were not ready or terminating.
+    pub skipped: usize,
+}
+
+impl fmt::Display for SelectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "strategy `{}` out of {} eligible pod(s)",
+            self.strategy, self.candidates
+        )?;
+
+        if self.skipped > 0 {
+            write!(f, ", skipped {} not ready or terminating", self.skipped)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns whether the pod is in the phase required by [`RuntimeData::from_pod_checked`], even
+/// when [`PodSelectionConfig::allow_not_ready`] is set.
+///
+/// [`RuntimeData::from_pod_checked`]: super::RuntimeData::from_pod_checked
+fn is_running(pod: &Pod) -> bool {
+    pod.status
+        .as_ref()
+        .and_then(|status| status.phase.as_deref())
+        == Some(RUNNING_PHASE)
+}
+
+/// Returns whether the pod has the `Ready` condition set and is not being deleted.
+fn is_ready(pod: &Pod) -> bool {
+    if pod.metadata.deletion_timestamp.is_some() {
+        return false;
+    }
+
+    pod.status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .is_some_and(|conditions| {
+            conditions
+                .iter()
+                .any(|condition| condition.type_ == "Ready" && condition.status == "True")
+        })
+}
+
+fn pod_name(pod: &Pod) -> &str {
+    pod.metadata.name.as_deref().unwrap_or_default()
+}
+
+/// Picks one pod out of `pods`, according to the given [`PodSelectionConfig`].
+///
+/// Pods outside of the `Running` phase are never picked. Unless
+/// [`PodSelectionConfig::allow_not_ready`] is set, neither are pods that are not ready or are
+/// terminating. Ties in creation timestamps are broken by pod name, so the
+/// result is deterministic for all strategies except [`PodSelectionStrategy::Random`].
+///
+/// Returns [`None`] if no pod is eligible.
+pub fn select_pod<'a>(pods: &'a [Pod], config: &PodSelectionConfig) -> Option<SelectedPod<'a>> {
+    let (eligible, skipped): (Vec<&Pod>, Vec<&Pod>) = pods
+        .iter()
+        .partition(|pod| is_running(pod) && (config.allow_not_ready || is_ready(pod)));
+
+    let candidates = match &config.strategy {
+        PodSelectionStrategy::ByNamePrefix(prefix) => eligible
+            .into_iter()
+            .filter(|pod| pod_name(pod).starts_with(prefix.as_str()))
+            .collect(),
+        _ => eligible,
+    };
+
+    let pod = match &config.strategy {
+        PodSelectionStrategy::Newest | PodSelectionStrategy::ByNamePrefix(..) => candidates
+            .iter()
+            .copied()
+            .max_by_key(|pod| (&pod.metadata.creation_timestamp, Reverse(pod_name(pod))))?,
+        PodSelectionStrategy::Oldest => candidates
+            .iter()
+            .copied()
+            .min_by_key(|pod| (&pod.metadata.creation_timestamp, pod_name(pod)))?,
+        PodSelectionStrategy::Random if candidates.is_empty() => return None,
+        PodSelectionStrategy::Random => candidates[rand::random_range(0..candidates.len())],
+    };
+
+    Some(SelectedPod {
+        pod,
+        reason: SelectionReason {
+            strategy: config.strategy.clone(),
+            candidates: candidates.len(),
+            skipped: skipped.len(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use k8s_openapi::{
+        api::core::v1::{Pod, PodCondition, PodStatus},
+        apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time},
+        chrono::{TimeZone, Utc},
+    };
+    use mirrord_config::target::pod_selection::{PodSelectionConfig, PodSelectionStrategy};
+    use rstest::rstest;
+
+    use super::select_pod;
+
+    /// Fabricates a pod created at `created` seconds since the epoch.
+    fn pod(name: &str, created: i64, ready: bool, terminating: bool) -> Pod {
+        let timestamp = |secs| Time(Utc.timestamp_opt(secs, 0).unwrap());
+
+        Pod {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                creation_timestamp: Some(timestamp(created)),
+                deletion_timestamp: terminating.then(|| timestamp(created + 1000)),
+                ..Default::default()
+            },
+            status: Some(PodStatus {
+                phase: Some("Running".to_string()),
+                conditions: Some(vec![PodCondition {
+                    type_: "Ready".to_string(),
+                    status: if ready { "True" } else { "False" }.to_string(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn pods() -> Vec<Pod> {
+        vec![
+            pod("api-old", 100, true, false),
+            pod("api-new-not-ready", 400, false, false),
+            pod("api-mid", 200, true, false),
+            pod("web-new", 300, true, false),
+            pod("api-newest-terminating", 500, true, true),
+        ]
+    }
+
+    fn config(strategy: PodSelectionStrategy, allow_not_ready: bool) -> PodSelectionConfig {
+        PodSelectionConfig {
+            strategy,
+            allow_not_ready,
+            ordinal: None,
+        }
+    }
+
+    #[rstest]
+    #[case(PodSelectionStrategy::Newest, false, "web-new")]
+    #[case(PodSelectionStrategy::Oldest, false, "api-old")]
+    #[case(PodSelectionStrategy::ByNamePrefix("api-".into()), false, "api-mid")]
+    #[case(PodSelectionStrategy::Newest, true, "api-newest-terminating")]
+    #[case(PodSelectionStrategy::ByNamePrefix("api-new".into()), true, "api-newest-terminating")]
+    #[case(PodSelectionStrategy::Oldest, true, "api-old")]
+    fn selects_expected_pod(
+        #[case] strategy: PodSelectionStrategy,
+        #[case] allow_not_ready: bool,
+        #[case] expected: &str,
+    ) {
+        let pods = pods();
+        let selected = select_pod(&pods, &config(strategy, allow_not_ready)).unwrap();
+
+        assert_eq!(selected.pod.metadata.name.as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn reason_counts_skipped_pods() {
+        let pods = pods();
+        let selected = select_pod(&pods, &config(PodSelectionStrategy::Newest, false)).unwrap();
+
+        assert_eq!(selected.reason.candidates, 3);
+        assert_eq!(selected.reason.skipped, 2);
+    }
+
+    #[test]
+    fn random_picks_only_ready_pods() {
+        let pods = pods();
+
+        for _ in 0..32 {
+            let selected = select_pod(&pods, &config(PodSelectionStrategy::Random, false)).unwrap();
+            let name = selected.pod.metadata.name.as_deref().unwrap();
+            assert!(["api-old", "api-mid", "web-new"].contains(&name), "{name}");
+        }
+    }
+
+    #[test]
+    fn same_creation_timestamp_is_deterministic() {
+        let pods = vec![pod("b", 100, true, false), pod("a", 100, true, false)];
+
+        let newest = select_pod(&pods, &config(PodSelectionStrategy::Newest, false)).unwrap();
+        let oldest = select_pod(&pods, &config(PodSelectionStrategy::Oldest, false)).unwrap();
+
+        assert_eq!(newest.pod.metadata.name.as_deref(), Some("a"));
+        assert_eq!(oldest.pod.metadata.name.as_deref(), Some("a"));
+    }
+
+    /// Pods outside of the `Running` phase are rejected by `RuntimeData::from_pod_checked`, even
+    /// when not ready pods are allowed.
+    #[test]
+    fn allow_not_ready_skips_pending_pods() {
+        let mut pending = pod("api-pending", 600, false, false);
+        if let Some(status) = pending.status.as_mut() {
+            status.phase = Some("Pending".to_string());
+        }
+        let mut pods = pods();
+        pods.push(pending);
+
+        let selected = select_pod(&pods, &config(PodSelectionStrategy::Newest, true)).unwrap();
+
+        assert_eq!(
+            selected.pod.metadata.name.as_deref(),
+            Some("api-newest-terminating")
+        );
+        assert_eq!(selected.reason.skipped, 1);
+    }
+
+    #[rstest]
+    #[case(PodSelectionStrategy::Newest)]
+    #[case(PodSelectionStrategy::Random)]
+    #[case(PodSelectionStrategy::ByNamePrefix("web-".into()))]
+    fn nothing_eligible(#[case] strategy: PodSelectionStrategy) {
+        let pods = vec![pod("api", 100, false, false), pod("web", 200, true, true)];
+
+        assert!(select_pod(&pods, &config(strategy, false)).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+//! Resolving all the pods of a workload target, used to mirror the traffic of all its replicas.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use futures::{Stream, StreamExt};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{
+    Api, Client,
+    runtime::{
+        WatchStreamExt,
+        watcher::{self, Event, watcher},
+    },
+};
+use mirrord_config::target::Target;
+
+use super::{RuntimeData, RuntimeDataFromLabels, format_label_selector};
+use crate::{
+    api::kubernetes::{get_k8s_resource_api, rollout::Rollout},
+    error::{KubeApiError, Result},
+};
+
+/// Returns the label selector of the pods that make up the given workload target.
+///
+/// Only deployments, rollouts and replica sets are resolved, [`None`] is returned for the other
+/// targets.
+pub async fn workload_pod_selector(
+    client: &Client,
+    target: &Target,
+    namespace: Option<&str>,
+) -> Result<Option<String>> {
+    async fn from_labels<T: RuntimeDataFromLabels>(
+        target: &T,
+        client: &Client,
+        namespace: Option<&str>,
+    ) -> Result<BTreeMap<String, String>> {
+        let api: Api<T::Resource> = get_k8s_resource_api(client, namespace);
+        let resource = api.get(&target.name()).await?;
+
+        T::get_selector_match_labels(&resource)
+    }
+
+    let labels = match target {
+        Target::Deployment(target) => from_labels(target, client, namespace).await?,
+        Target::ReplicaSet(target) => from_labels(target, client, namespace).await?,
+        Target::Rollout(target) => {
+            let api: Api<Rollout> = get_k8s_resource_api(client, namespace);
+            let rollout = api.get(&target.rollout).await?;
+
+            rollout
+                .get_match_labels(client)
+                .await?
+                .match_labels
+                .clone()
+                .ok_or_else(|| {
+                    KubeApiError::missing_field(&rollout, ".selector or .selector.match_labels")
+                })?
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(format_label_selector(&labels)))
+}
+
+/// Watches the pods matching the given label selector.
+///
+/// Yields the names of all the pods that are ready to be targeted with the given container (see
+/// [`RuntimeData::from_pod`]) every time the set may have changed.
+///
+/// Errors of the underlying [`watcher`] are yielded as they are, the watch is retried with a
+/// backoff.
+pub fn watch_ready_pods(
+    client: &Client,
+    namespace: Option<&str>,
+    selector: &str,
+    container: Option<String>,
+) -> impl Stream<Item = Result<BTreeSet<String>, watcher::Error>> + Send + 'static {
+    let api: Api<Pod> = get_k8s_resource_api(client, namespace);
+    let is_ready = move |pod: &Pod| RuntimeData::from_pod(pod, container.as_deref()).ok();
+
+    let mut ready = BTreeSet::new();
+    // Pods listed since the watch was (re)started, replaces `ready` when the listing is done.
+    let mut relisted: Option<BTreeSet<String>> = None;
+
+    watcher(api, watcher::Config::default().labels(selector))
+        .default_backoff()
+        .filter_map(move |event| {
+            let update = match event {
+                Err(error) => Some(Err(error)),
+                Ok(Event::Init) => {
+                    relisted = Some(Default::default());
+                    None
+                }
+                Ok(Event::InitApply(pod)) => {
+                    if let Some(runtime_data) = is_ready(&pod) {
+                        relisted
+                            .get_or_insert_default()
+                            .insert(runtime_data.pod_name);
+                    }
+                    None
+                }
+                Ok(Event::InitDone) => {
+                    ready = relisted.take().unwrap_or_default();
+                    Some(Ok(ready.clone()))
+                }
+                Ok(Event::Apply(pod)) => {
+                    match (is_ready(&pod), pod.metadata.name) {
+                        (Some(runtime_data), _) => ready.insert(runtime_data.pod_name),
+                        (None, Some(name)) => ready.remove(&name),
+                        (None, None) => false,
+                    };
+                    Some(Ok(ready.clone()))
+                }
+                Ok(Event::Delete(pod)) => {
+                    if let Some(name) = pod.metadata.name {
+                        ready.remove(&name);
+                    }
+                    Some(Ok(ready.clone()))
+                }
+            };
+
+            std::future::ready(update)
+        })
+}
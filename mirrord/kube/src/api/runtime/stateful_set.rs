@@ -1,27 +1,105 @@
-use std::{borrow::Cow, collections::BTreeMap};
+use std::ops::Range;
 
-use k8s_openapi::api::apps::v1::StatefulSet;
-use mirrord_config::target::stateful_set::StatefulSetTarget;
+use k8s_openapi::api::{apps::v1::StatefulSet, core::v1::Pod};
+use kube::{Api, Client};
+use mirrord_config::target::{pod_selection::PodSelectionConfig, stateful_set::StatefulSetTarget};
 
-use super::RuntimeDataFromLabels;
-use crate::error::{KubeApiError, Result};
+use super::{RuntimeData, RuntimeDataProvider};
+use crate::{
+    api::kubernetes::get_k8s_resource_api,
+    error::{KubeApiError, Result},
+};
 
-impl RuntimeDataFromLabels for StatefulSetTarget {
-    type Resource = StatefulSet;
-
-    fn name(&self) -> Cow<'_, str> {
-        Cow::from(&self.stateful_set)
+/// Pods of a [`StatefulSet`] have stable names, so the target resolves directly to the one with
+/// the ordinal given in [`StatefulSetTarget::ordinal`], or to `{stateful_set}-0`.
+impl RuntimeDataProvider for StatefulSetTarget {
+    async fn runtime_data(&self, client: &Client, namespace: Option<&str>) -> Result<RuntimeData> {
+        stateful_set_runtime_data(client, namespace, self, &PodSelectionConfig::default()).await
     }
+}
 
-    fn container(&self) -> Option<&str> {
-        self.container.as_deref()
-    }
+/// Returns the range of ordinals the [`StatefulSet`]'s pods are expected to have, based on its
+/// `.spec.replicas` and `.spec.ordinals.start`.
+fn valid_ordinals(stateful_set: &StatefulSet) -> Range<u32> {
+    let spec = stateful_set.spec.as_ref();
+    let start = spec
+        .and_then(|spec| spec.ordinals.as_ref()?.start)
+        .unwrap_or_default();
+    let replicas = spec.and_then(|spec| spec.replicas).unwrap_or(1);
+
+    let start = u32::try_from(start).unwrap_or_default();
+    start..start.saturating_add(u32::try_from(replicas).unwrap_or_default())
+}
+
+/// Resolves [`RuntimeData`] from the pod of the [`StatefulSet`] targeted with the given
+/// [`StatefulSetTarget`].
+///
+/// The pod is picked by its ordinal, taken from [`StatefulSetTarget::ordinal`], then
+/// [`PodSelectionConfig::ordinal`], and defaulting to `0`. Fails with the list of valid ordinals
+/// if the pod does not exist.
+pub async fn stateful_set_runtime_data(
+    client: &Client,
+    namespace: Option<&str>,
+    target: &StatefulSetTarget,
+    selection: &PodSelectionConfig,
+) -> Result<RuntimeData> {
+    let stateful_set_api: Api<StatefulSet> = get_k8s_resource_api(client, namespace);
+    let stateful_set = stateful_set_api.get(&target.stateful_set).await?;
+
+    let ordinal = target.ordinal.or(selection.ordinal).unwrap_or_default();
+    let pod_api: Api<Pod> =
+        get_k8s_resource_api(client, stateful_set.metadata.namespace.as_deref());
+
+    let Some(pod) = pod_api.get_opt(&target.pod_name(ordinal)).await? else {
+        let valid = valid_ordinals(&stateful_set);
+        let reason = if valid.is_empty() {
+            format!("stateful set has no pod with ordinal {ordinal}, it is scaled down to 0")
+        } else {
+            format!(
+                "stateful set has no pod with ordinal {ordinal}, valid ordinals are {}",
+                valid
+                    .map(|ordinal| ordinal.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+
+        return Err(KubeApiError::invalid_state(&stateful_set, reason));
+    };
+
+    RuntimeData::from_pod_checked(
+        &pod,
+        target.container.as_deref(),
+        !selection.allow_not_ready,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::apps::v1::{StatefulSetOrdinals, StatefulSetSpec};
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(None, None, 0..1)]
+    #[case(Some(3), None, 0..3)]
+    #[case(Some(3), Some(5), 5..8)]
+    #[case(Some(0), None, 0..0)]
+    fn stateful_set_valid_ordinals(
+        #[case] replicas: Option<i32>,
+        #[case] start: Option<i32>,
+        #[case] expected: Range<u32>,
+    ) {
+        let stateful_set = StatefulSet {
+            spec: Some(StatefulSetSpec {
+                replicas,
+                ordinals: start.map(|start| StatefulSetOrdinals { start: Some(start) }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
 
-    fn get_selector_match_labels(resource: &Self::Resource) -> Result<BTreeMap<String, String>> {
-        resource
-            .spec
-            .as_ref()
-            .and_then(|spec| spec.selector.match_labels.clone())
-            .ok_or_else(|| KubeApiError::missing_field(resource, ".spec.selector.matchLabels"))
+        assert_eq!(valid_ordinals(&stateful_set), expected);
     }
 }
@@ -1,10 +1,12 @@
-use std::{convert::Infallible, fmt};
+use std::{convert::Infallible, fmt, time::Duration};
 
 use kube::Resource;
 use mirrord_config::target::TargetType;
 use thiserror::Error;
 use tower::retry::backoff::InvalidBackoff;
 
+use crate::api::container::diagnostics::AgentStartupDiagnostics;
+
 pub type Result<T, E = KubeApiError> = std::result::Result<T, E>;
 
 #[derive(Debug, Error)]
@@ -24,6 +26,14 @@ pub enum KubeApiError {
     #[error("Timeout waiting for agent to be ready")]
     AgentReadyTimeout,
 
+    /// The agent pod did not become ready within
+    /// [`AgentConfig::startup_timeout`](mirrord_config::agent::AgentConfig::startup_timeout).
+    #[error("Timeout waiting for agent to be ready after {}s, {diagnostics}", .timeout.as_secs())]
+    AgentStartupTimeout {
+        timeout: Duration,
+        diagnostics: Box<AgentStartupDiagnostics>,
+    },
+
     #[error("Port not found in port forward")]
     PortForwardFailed,
 
@@ -37,6 +47,13 @@ pub enum KubeApiError {
     #[error("Path expansion for kubeconfig failed: {0}")]
     ConfigPathExpansionError(String),
 
+    /// The requested kube context does not exist in the kubeconfig.
+    #[error("Kube context `{context}` not found, available contexts: [{}]", .available.join(", "))]
+    UnknownKubeContext {
+        context: String,
+        available: Vec<String>,
+    },
+
     /// We fetched a malformed resource using [`kube`] (should not happen).
     /// Construct with [`Self::missing_field`] or [`Self::invalid_value`] for consistent error
     /// messages.
@@ -48,15 +65,6 @@ pub enum KubeApiError {
     #[error("Resource fetched from Kube API is invalid: {0}")]
     InvalidResourceState(String),
 
-    /// Attempted to create an `OperatorTarget` from a resource that cannot be an immediate target.
-    ///
-    /// Create this variant with the [`KubeApiError::requires_copy`] method.
-    #[error("targeting {0} requires the `copy_target` feature")]
-    RequiresCopy(
-        /// Should be plural name of the resource
-        String,
-    ),
-
     /// Attempted to list a specific resource type with `mirrord ls` but was unable to because the
     /// operator was required.
     #[error(
@@ -84,6 +92,10 @@ pub enum KubeApiError {
     /// Spawned agent pod was deleted during startup.
     #[error("Agent pod was unexpectedly deleted")]
     AgentPodDeleted,
+
+    /// Failed to render an agent resource when planning a dry run.
+    #[error("Failed to serialize the agent spec for dry run: {0}")]
+    DryRunSerialize(#[source] serde_json::Error),
 }
 
 impl KubeApiError {
@@ -149,10 +161,6 @@ impl KubeApiError {
 
         Self::InvalidResourceState(message)
     }
-
-    pub fn requires_copy<R: Resource<DynamicType = ()>>() -> Self {
-        Self::RequiresCopy(R::plural(&()).into_owned())
-    }
 }
 
 impl From<Infallible> for KubeApiError {
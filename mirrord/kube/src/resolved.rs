@@ -309,8 +309,8 @@ impl ResolvedTarget<false> {
     ///    [`ResolvedTarget::StatefulSet`] - the target container, if specified, is found in the
     ///    spec
     /// 2. [`ResolvedTarget::Pod`] - passes target-readiness check, see [`RuntimeData::from_pod`].
-    /// 3. [`ResolvedTarget::Job`] and [`ResolvedTarget::CronJob`] - error, as this is `copy_target`
-    ///    exclusive
+    /// 3. [`ResolvedTarget::Job`] and [`ResolvedTarget::CronJob`] - the target container, if
+    ///    specified, is found in the job's pod template
     /// 4. [`ResolvedTarget::Targetless`] - no check (not applicable)
     /// 5. [`ResolvedTarget::Service`] - the target container, if specified, is found in at least
     ///    one of the pods
@@ -378,12 +378,53 @@ impl ResolvedTarget<false> {
                 }))
             }
 
-            ResolvedTarget::Job(..) => {
-                return Err(KubeApiError::requires_copy::<Job>());
+            ResolvedTarget::Job(ResolvedResource {
+                resource,
+                container,
+            }) => {
+                if let Some(container) = &container {
+                    // verify that the container exists
+                    resource
+                        .spec
+                        .as_ref()
+                        .ok_or_else(|| KubeApiError::missing_field(resource.as_ref(), ".spec"))?
+                        .template
+                        .spec
+                        .as_ref()
+                        .ok_or_else(|| KubeApiError::missing_field(resource.as_ref(), ".spec.template.spec"))?
+                        .containers
+                        .iter()
+                        .find(|c| c.name == *container)
+                        .ok_or_else(|| KubeApiError::invalid_state(resource.as_ref(), format_args!("specified pod template does not contain target container `{container}`")))?;
+                }
+
+                Ok(ResolvedTarget::Job(ResolvedResource {
+                    resource,
+                    container,
+                }))
             }
 
-            ResolvedTarget::CronJob(..) => {
-                return Err(KubeApiError::requires_copy::<CronJob>());
+            ResolvedTarget::CronJob(ResolvedResource {
+                resource,
+                container,
+            }) => {
+                if let Some(container) = &container {
+                    // verify that the container exists
+                    resource
+                        .spec
+                        .as_ref()
+                        .and_then(|spec| spec.job_template.spec.as_ref()?.template.spec.as_ref())
+                        .ok_or_else(|| KubeApiError::missing_field(resource.as_ref(), ".spec.jobTemplate.spec.template.spec"))?
+                        .containers
+                        .iter()
+                        .find(|c| c.name == *container)
+                        .ok_or_else(|| KubeApiError::invalid_state(resource.as_ref(), format_args!("specified pod template does not contain target container `{container}`")))?;
+                }
+
+                Ok(ResolvedTarget::CronJob(ResolvedResource {
+                    resource,
+                    container,
+                }))
             }
 
             ResolvedTarget::StatefulSet(ResolvedResource {
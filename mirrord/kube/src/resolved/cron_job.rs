@@ -1,47 +1,24 @@
-use std::{borrow::Cow, collections::BTreeMap};
-
 use k8s_openapi::api::batch::v1::CronJob;
+use kube::Client;
+use mirrord_config::target::job::JobTarget;
 
 use super::ResolvedResource;
 use crate::{
-    api::runtime::RuntimeDataFromLabels,
-    error::{KubeApiError, Result},
+    api::runtime::{RuntimeData, RuntimeDataProvider, cron_job::latest_active_job},
+    error::Result,
 };
 
-impl RuntimeDataFromLabels for ResolvedResource<CronJob> {
-    type Resource = CronJob;
-
-    fn name(&self) -> Cow<'_, str> {
-        self.resource
-            .metadata
-            .name
-            .as_ref()
-            .map(Cow::from)
-            .unwrap_or_default()
-    }
-
-    fn container(&self) -> Option<&str> {
-        self.container.as_deref()
-    }
+/// A [`CronJob`] has no pods of its own, so it is resolved to a pod of its most recent active
+/// [`Job`](k8s_openapi::api::batch::v1::Job), see [`latest_active_job`].
+impl RuntimeDataProvider for ResolvedResource<CronJob> {
+    async fn runtime_data(&self, client: &Client, _: Option<&str>) -> Result<RuntimeData> {
+        let job = latest_active_job(client, &self.resource).await?;
 
-    fn get_selector_match_labels(resource: &Self::Resource) -> Result<BTreeMap<String, String>> {
-        resource
-            .spec
-            .as_ref()
-            .and_then(|spec| {
-                spec.job_template
-                    .spec
-                    .as_ref()?
-                    .selector
-                    .as_ref()?
-                    .match_labels
-                    .clone()
-            })
-            .ok_or_else(|| {
-                KubeApiError::missing_field(
-                    resource,
-                    ".spec.selector or .spec.selector.matchLabels",
-                )
-            })
+        JobTarget {
+            job: job.metadata.name.unwrap_or_default(),
+            container: self.container.clone(),
+        }
+        .runtime_data(client, job.metadata.namespace.as_deref())
+        .await
     }
 }
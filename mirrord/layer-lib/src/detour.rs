@@ -218,6 +218,10 @@ pub enum Bypass {
     /// Hooked a `connect` to a target that is disabled in the configuration.
     DisabledOutgoing,
 
+    /// Outgoing traffic to a port from `feature.network.outgoing.local_ports`, which is never
+    /// intercepted.
+    LocalOutgoingPort(SocketAddr),
+
     /// Incoming traffic is disabled, bypass.
     DisabledIncoming,
 
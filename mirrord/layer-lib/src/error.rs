@@ -174,6 +174,15 @@ pub enum HookError {
     )]
     UnsupportedSocketType,
 
+    /// Only unix streams can be connected remotely, see
+    /// `feature.network.outgoing.unix_streams`.
+    #[cfg(unix)]
+    #[error(
+        "mirrord-layer: datagram unix socket `{0}` matches `feature.network.outgoing.unix_streams`, \
+        but only stream unix sockets can be connected remotely."
+    )]
+    UnsupportedUnixDatagram(String),
+
     #[error("mirrord-layer: Pointer argument points to an invalid address")]
     BadPointer,
 
@@ -393,6 +402,10 @@ fn get_platform_errno(fail: HookError) -> i32 {
             // never appears as HookError::ResponseError(PortAlreadyStolen(_)).
             // this could be changed by waiting for the Subscribed response from agent.
             ResponseError::PortAlreadyStolen(_port) => libc::EINVAL,
+            ResponseError::ConflictingPortSubscription(_port) => libc::EADDRINUSE,
+            ResponseError::UnknownContainer { .. } => libc::EINVAL,
+            ResponseError::StealTlsSetup { .. } => libc::EINVAL,
+            ResponseError::PortStealConflict { .. } => libc::EINVAL,
             ResponseError::NotImplemented => libc::EINVAL,
             ResponseError::StripPrefix(_) => libc::EINVAL,
             err @ (ResponseError::Forbidden { .. } | ResponseError::ForbiddenWithReason { .. }) => {
@@ -409,6 +422,7 @@ fn get_platform_errno(fail: HookError) -> i32 {
         HookError::FailedSipPatch(_) => libc::EACCES,
         HookError::SocketUnsuportedIpv6 => libc::EAFNOSUPPORT,
         HookError::UnsupportedSocketType => libc::EAFNOSUPPORT,
+        HookError::UnsupportedUnixDatagram(_) => libc::EPROTOTYPE,
         HookError::BadPointer => libc::EFAULT,
         HookError::AddressAlreadyBound(_) => libc::EADDRINUSE,
         HookError::FileNotFound(_) => libc::ENOENT,
@@ -467,6 +481,10 @@ fn get_platform_errno(fail: HookError) -> u32 {
             // never appears as HookError::ResponseError(PortAlreadyStolen(_)).
             // this could be changed by waiting for the Subscribed response from agent.
             ResponseError::PortAlreadyStolen(_port) => WSAEINVAL,
+            ResponseError::ConflictingPortSubscription(_port) => WSAEADDRINUSE,
+            ResponseError::UnknownContainer { .. } => WSAEINVAL,
+            ResponseError::StealTlsSetup { .. } => WSAEINVAL,
+            ResponseError::PortStealConflict { .. } => WSAEINVAL,
             ResponseError::NotImplemented => WSAEINVAL,
             ResponseError::StripPrefix(_) => WSAEINVAL,
             err @ (ResponseError::Forbidden { .. } | ResponseError::ForbiddenWithReason { .. }) => {
@@ -562,6 +580,9 @@ impl From<HookError> for i64 {
             HookError::SocketUnsuportedIpv6 => {
                 info!("{fail}")
             }
+            HookError::ResponseError(ref err @ ResponseError::PortStealConflict { .. }) => {
+                error!("{err}")
+            }
             HookError::ResponseError(ResponseError::NotImplemented) => {
                 // this means we bypass, so we can just return to avoid setting libc.
                 return -1;
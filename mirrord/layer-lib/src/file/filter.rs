@@ -7,9 +7,9 @@ use std::path::Path;
 /// 1. no configuration (default): will bypass file operations for file paths and types that
 ///    match [`generate_local_set`];
 ///
-/// 2. Using the overrides for `read_only`, `read_write` and `local`.
+/// 2. Using the `rules`, or the overrides for `read_only`, `read_write` and `local`.
 use mirrord_config::{
-    feature::fs::{FsConfig, FsModeConfig},
+    feature::fs::{FsConfig, FsModeConfig, FsRuleMode},
     util::VecOrSingle,
 };
 use regex::{RegexSet, RegexSetBuilder};
@@ -76,10 +76,11 @@ pub enum FileMode {
 
 #[derive(Debug)]
 pub struct FileFilter {
-    pub read_only: RegexSet,
-    pub read_write: RegexSet,
-    pub local: RegexSet,
-    pub not_found: RegexSet,
+    /// Patterns of [`FsConfig::effective_rules`], in order.
+    pub rules: RegexSet,
+    /// Modes of [`FsConfig::effective_rules`], by the index of their pattern in
+    /// [`FileFilter::rules`].
+    pub rule_modes: Vec<FsRuleMode>,
     pub default_local: RegexSet,
     pub default_remote_ro: RegexSet,
     pub default_not_found: RegexSet,
@@ -95,59 +96,62 @@ impl FileFilter {
 
     /// Initializes a `FileFilter` based on the user configuration.
     ///
-    /// The filter first checks if the user specified any include/exclude regexes. (This will be
-    /// removed) If path matches include, it continues to check if the path has specific
-    /// behavior, if not, it checks if the path matches the default exclude list.
-    /// If not, it does the default behavior set by user (default is read only remote).
+    /// The filter first checks the user rules, including the ones converted from the
+    /// include/exclude regexes, and uses the first one that matches the path. If none does, it
+    /// checks if the path matches the default exclude lists. If not, it does the default behavior
+    /// set by user (default is read only remote).
     #[mirrord_layer_macro::instrument(level = "trace")]
     pub fn new(fs_config: FsConfig) -> Self {
-        let FsConfig {
-            read_write,
-            read_only,
-            local,
-            mode,
-            not_found,
-            ..
-        } = fs_config;
-
-        let read_write =
-            Self::make_regex_set(read_write).expect("building read-write regex set failed");
-        let read_only =
-            Self::make_regex_set(read_only).expect("building read-only regex set failed");
-        let local = Self::make_regex_set(local).expect("building local path regex set failed");
-        let not_found =
-            Self::make_regex_set(not_found).expect("building not-found regex set failed");
+        let (patterns, rule_modes): (Vec<_>, Vec<_>) = fs_config
+            .effective_rules()
+            .into_iter()
+            .map(|rule| (rule.pattern, rule.mode))
+            .unzip();
+
+        let rules = RegexSetBuilder::new(patterns)
+            .case_insensitive(true)
+            .build()
+            .expect("building rules regex set failed");
 
         let default_local = generate_local_set();
         let default_remote_ro = generate_remote_ro_set();
         let default_not_found = generate_not_found_set();
 
         Self {
-            read_only,
-            read_write,
-            local,
-            not_found,
+            rules,
+            rule_modes,
             default_local,
             default_remote_ro,
             default_not_found,
-            mode,
+            mode: fs_config.mode,
         }
     }
 
+    /// Returns the mode of the first rule that matches the given path.
+    ///
+    /// All the rules are evaluated in a single pass over the path. Operations on already opened
+    /// files reuse the decision made when the file was opened, and don't check the rules again.
+    pub fn rule(&self, path: &str) -> Option<FsRuleMode> {
+        self.rules
+            .matches(path)
+            .iter()
+            .next()
+            .map(|index| self.rule_modes[index])
+    }
+
     pub fn check<T: AsRef<str>>(&self, path: T) -> Option<FileMode> {
         let path = path.as_ref();
 
         match self.mode {
             FsModeConfig::Local => Some(FileMode::Local(false)),
             FsModeConfig::Read | FsModeConfig::Write | FsModeConfig::LocalWithOverrides => {
-                if self.not_found.is_match(path) {
-                    Some(FileMode::NotFound(false))
-                } else if self.read_write.is_match(path) {
-                    Some(FileMode::ReadWrite(false))
-                } else if self.read_only.is_match(path) {
-                    Some(FileMode::ReadOnly(false))
-                } else if self.local.is_match(path) {
-                    Some(FileMode::Local(false))
+                if let Some(mode) = self.rule(path) {
+                    Some(match mode {
+                        FsRuleMode::NotFound => FileMode::NotFound(false),
+                        FsRuleMode::Write => FileMode::ReadWrite(false),
+                        FsRuleMode::Read => FileMode::ReadOnly(false),
+                        FsRuleMode::Local => FileMode::Local(false),
+                    })
                 } else if self.default_not_found.is_match(path) {
                     Some(FileMode::NotFound(true))
                 } else if self.default_remote_ro.is_match(path) {
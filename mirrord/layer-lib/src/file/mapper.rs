@@ -1,30 +1,75 @@
 use std::{borrow::Cow, collections::HashMap, path::PathBuf};
 
-use regex::{Regex, RegexSet, RegexSetBuilder};
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
 
+/// Characters that make a `feature.fs.mapping` key a regex, keys without any of them are path
+/// prefixes.
+const REGEX_META_CHARACTERS: &[char] = &[
+    '\\', '^', '$', '*', '+', '?', '(', ')', '[', ']', '{', '}', '|',
+];
+
+/// Applies `feature.fs.mapping` to the paths used by the application.
+///
+/// Keys without regex meta characters (e.g. `/var/data`) are path prefixes, matched on whole path
+/// components, and the longest matching prefix wins. Other keys are case-insensitive regexes,
+/// used only when no prefix matches, and the one with the longest match wins (ties are broken by
+/// the order of the patterns).
+///
+/// The mapping is applied once, to the path as given by the application, so the replaced path is
+/// not mapped again and symlinks are resolved only after the mapping, by whichever side serves
+/// the replaced path.
 #[derive(Debug)]
 pub struct FileRemapper {
+    /// Sorted by the prefix length, longest first.
+    prefixes: Vec<(String, String)>,
     filter: RegexSet,
+    /// Sorted by the pattern, in the same order as [`Self::filter`].
     mapping: Vec<(Regex, String)>,
 }
 
 impl FileRemapper {
     pub fn new(mapping: HashMap<String, String>) -> Self {
-        let filter = RegexSetBuilder::new(mapping.keys())
+        let (mut prefixes, mut patterns): (Vec<_>, Vec<_>) = mapping
+            .into_iter()
+            .partition(|(pattern, _)| !pattern.contains(REGEX_META_CHARACTERS));
+        prefixes.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        patterns.sort();
+
+        let filter = RegexSetBuilder::new(patterns.iter().map(|(pattern, _)| pattern))
             .case_insensitive(true)
             .build()
             .expect("Building path mapping regex set failed");
-        let mapping = mapping
+        let mapping = patterns
             .into_iter()
             .map(|(pattern, value)| {
                 (
-                    Regex::new(&pattern).expect("Building path mapping regex failed"),
+                    RegexBuilder::new(&pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .expect("Building path mapping regex failed"),
                     value,
                 )
             })
             .collect();
 
-        FileRemapper { filter, mapping }
+        FileRemapper {
+            prefixes,
+            filter,
+            mapping,
+        }
+    }
+
+    /// Replaces the longest path prefix matching the `path_str`.
+    fn replace_prefix<'p>(&self, path_str: &'p str) -> Option<Cow<'p, str>> {
+        self.prefixes.iter().find_map(|(prefix, value)| {
+            let rest = path_str.strip_prefix(prefix.trim_end_matches('/'))?;
+
+            if rest.is_empty() || rest.starts_with('/') {
+                Some(Cow::Owned(format!("{}{rest}", value.trim_end_matches('/'))))
+            } else {
+                None
+            }
+        })
     }
 
     #[tracing::instrument(level = "trace", skip(self), ret)]
@@ -39,9 +84,22 @@ impl FileRemapper {
 
     // Don't instrument trace this or `change_path` because it spams a lot
     pub fn change_path_str<'p>(&self, path_str: &'p str) -> Cow<'p, str> {
-        let matches = self.filter.matches(path_str);
+        if let Some(replaced) = self.replace_prefix(path_str) {
+            return replaced;
+        }
+
+        let longest_match = self.filter.matches(path_str).iter().max_by_key(|&index| {
+            let match_len = self.mapping[index]
+                .0
+                .find(path_str)
+                .map(|found| found.len())
+                .unwrap_or_default();
+
+            // On equal lengths, `max_by_key` picks the last one, so prefer lower indices.
+            (match_len, std::cmp::Reverse(index))
+        });
 
-        if let Some(index) = matches.iter().next() {
+        if let Some(index) = longest_match {
             self.replace_path_str(index, path_str)
         } else {
             Cow::Borrowed(path_str)
@@ -91,4 +149,37 @@ mod tests {
 
         assert_eq!(remapper.change_path(input), expect);
     }
+
+    fn prefix_mapping() -> HashMap<String, String> {
+        [
+            ("/etc/app/config.yaml", "./config/dev.yaml"),
+            ("/var/data", "/tmp/mirrord-data"),
+            ("/var/data/cache/", "/tmp/mirrord-cache/"),
+            ("/tmp/mirrord-data", "/srv/data"),
+            ("^/var/(log|run)/", "/tmp/mirrord-var/"),
+            ("/var/log/app[0-9]+", "/tmp/mirrord-app-log"),
+        ]
+        .into_iter()
+        .map(|(pattern, value)| (pattern.to_string(), value.to_string()))
+        .collect()
+    }
+
+    #[rstest]
+    #[case::exact_file("/etc/app/config.yaml", "./config/dev.yaml")]
+    #[case::not_component_boundary("/etc/app/config.yaml.bak", "/etc/app/config.yaml.bak")]
+    #[case::directory("/var/data", "/tmp/mirrord-data")]
+    #[case::directory_trailing_slash("/var/data/", "/tmp/mirrord-data/")]
+    #[case::directory_entry("/var/data/users/1.json", "/tmp/mirrord-data/users/1.json")]
+    #[case::sibling_directory("/var/database", "/var/database")]
+    #[case::longest_prefix("/var/data/cache/index", "/tmp/mirrord-cache/index")]
+    #[case::longest_prefix_directory("/var/data/cache", "/tmp/mirrord-cache")]
+    #[case::prefix_case_sensitive("/VAR/data/x", "/VAR/data/x")]
+    #[case::not_applied_twice("/var/data/x", "/tmp/mirrord-data/x")]
+    #[case::regex("/var/run/app.pid", "/tmp/mirrord-var/app.pid")]
+    #[case::longest_regex_match("/var/log/app12/out.log", "/tmp/mirrord-app-log/out.log")]
+    fn prefix_mapping_precedence(#[case] input: PathBuf, #[case] expect: PathBuf) {
+        let remapper = FileRemapper::new(prefix_mapping());
+
+        assert_eq!(remapper.change_path(input), expect);
+    }
 }
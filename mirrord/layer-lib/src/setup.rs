@@ -1,4 +1,8 @@
-use std::{collections::HashSet, net::SocketAddr, ops::Not, sync::OnceLock};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::OnceLock,
+};
 
 use mirrord_config::{
     LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR,
@@ -24,7 +28,7 @@ use regex::RegexSet;
 use crate::{
     debugger_ports::DebuggerPorts,
     file::{filter::FileFilter, mapper::FileRemapper},
-    socket::{OutgoingSelector, dns_selector::DnsSelector},
+    socket::{LocalPorts, OutgoingSelector, dns_selector::DnsSelector},
     trace_only::{is_trace_only_mode, modify_config_for_trace_only},
 };
 
@@ -44,6 +48,7 @@ pub fn init_layer_setup(mut config: LayerConfig, sip_only: bool) {
         // we need to hook file access to patch path to our temp bin.
         config.feature.fs = FsConfig {
             mode: FsModeConfig::Local,
+            rules: Default::default(),
             read_write: None,
             read_only: None,
             local: None,
@@ -81,6 +86,7 @@ pub struct LayerSetup {
     debugger_ports: DebuggerPorts,
     remote_unix_streams: RegexSet,
     outgoing_selector: OutgoingSelector,
+    outgoing_local_ports: LocalPorts,
     dns_selector: DnsSelector,
     proxy_address: SocketAddr,
     incoming_mode: IncomingMode,
@@ -112,6 +118,7 @@ impl LayerSetup {
             .unwrap_or_default();
 
         let outgoing_selector = OutgoingSelector::new(&config.feature.network.outgoing);
+        let outgoing_local_ports = LocalPorts::new(&config.feature.network.outgoing);
 
         let dns_selector = DnsSelector::from(&config.feature.network.dns);
 
@@ -134,6 +141,7 @@ impl LayerSetup {
             debugger_ports,
             remote_unix_streams,
             outgoing_selector,
+            outgoing_local_ports,
             dns_selector,
             proxy_address,
             incoming_mode,
@@ -214,6 +222,11 @@ impl LayerSetup {
         &self.outgoing_selector
     }
 
+    /// Whether outgoing traffic to `addr` should bypass mirrord, see [`LocalPorts`].
+    pub fn is_outgoing_local_port(&self, addr: &SocketAddr) -> bool {
+        self.outgoing_local_ports.contains(addr)
+    }
+
     pub fn dns_selector(&self) -> &DnsSelector {
         &self.dns_selector
     }
@@ -279,6 +292,16 @@ pub struct HttpSettings {
 pub struct IncomingMode {
     pub steal: bool,
     pub http_settings: Option<HttpSettings>,
+    /// Filters of the ports that have their own, see [`IncomingConfig::port_http_filters`].
+    ///
+    /// [`None`] means that the port is not filtered.
+    pub port_filters: HashMap<Port, Option<HttpFilter>>,
+    /// Whether to steal from the ports that have their own mode, see
+    /// [`IncomingConfig::port_modes`].
+    pub port_steal: HashMap<Port, bool>,
+    /// Whether the filtered ports should only be subscribed in dry run mode, see
+    /// [`HttpFilterConfig::dry_run`](mirrord_config::feature::network::incoming::http_filter::HttpFilterConfig::dry_run).
+    pub dry_run: bool,
 }
 
 impl IncomingMode {
@@ -303,44 +326,71 @@ impl IncomingMode {
             HttpSettings { filter, ports }
         });
 
+        let port_filters = config
+            .protocol_port_http_filters()
+            .expect("invalid HTTP filter expression");
+
+        let port_steal = config
+            .port_modes
+            .iter()
+            .map(|(port, mode)| (*port, matches!(mode, ConfigIncomingMode::Steal)))
+            .collect();
+
         Self {
             steal: config.is_steal(),
             http_settings,
+            port_filters,
+            port_steal,
+            dry_run: config.http_filter.dry_run,
         }
     }
 
+    /// Returns whether traffic should be stolen from the given port, or mirrored.
+    ///
+    /// The port's own mode from [`IncomingConfig::port_modes`] takes precedence over the
+    /// top-level one.
+    fn steals(&self, port: Port) -> bool {
+        self.port_steal.get(&port).copied().unwrap_or(self.steal)
+    }
+
+    /// Returns the HTTP filter to be used for the given port, if any.
+    ///
+    /// The port's own filter from [`IncomingConfig::port_http_filters`] takes precedence over
+    /// the top-level one.
+    fn http_filter(&self, port: Port) -> Option<&HttpFilter> {
+        if let Some(filter) = self.port_filters.get(&port) {
+            return filter.as_ref();
+        }
+
+        self.http_settings
+            .as_ref()
+            .filter(|settings| {
+                settings
+                    .ports
+                    .as_ref()
+                    .is_none_or(|ports| ports.contains(&port))
+            })
+            .map(|settings| &settings.filter)
+    }
+
     /// Returns [`PortSubscription`] request to be used for the given port.
     pub fn subscription(&self, port: Port) -> PortSubscription {
-        if self.steal {
-            let steal_type = match &self.http_settings {
+        let filter = self.http_filter(port).cloned();
+
+        if let Some(filter) = filter.clone().filter(|_| self.dry_run) {
+            return PortSubscription::Mirror(MirrorType::DryRun(port, filter));
+        }
+
+        if self.steals(port) {
+            let steal_type = match filter {
+                Some(filter) => StealType::FilteredHttpEx(port, filter),
                 None => StealType::All(port),
-                Some(settings) => {
-                    if settings
-                        .ports
-                        .as_ref()
-                        .is_some_and(|p| p.contains(&port).not())
-                    {
-                        StealType::All(port)
-                    } else {
-                        StealType::FilteredHttpEx(port, settings.filter.clone())
-                    }
-                }
             };
             PortSubscription::Steal(steal_type)
         } else {
-            let mirror_type = match &self.http_settings {
+            let mirror_type = match filter {
+                Some(filter) => MirrorType::FilteredHttp(port, filter),
                 None => MirrorType::All(port),
-                Some(settings) => {
-                    if settings
-                        .ports
-                        .as_ref()
-                        .is_some_and(|p| p.contains(&port).not())
-                    {
-                        MirrorType::All(port)
-                    } else {
-                        MirrorType::FilteredHttp(port, settings.filter.clone())
-                    }
-                }
             };
             PortSubscription::Mirror(mirror_type)
         }
@@ -8,19 +8,22 @@ pub mod sockets;
 
 #[cfg(windows)]
 use std::mem;
-use std::{collections::HashSet, net::SocketAddr, str::FromStr};
+use std::{cmp::Reverse, collections::HashSet, net::SocketAddr, ops::RangeInclusive, str::FromStr};
 
 use bincode::{Decode, Encode};
 // Re-export dns module items
-pub use dns::reverse_dns::get_hostname_for_ip;
+pub use dns::reverse_dns::{get_hostname_for_ip, get_query_name_for_ip};
 use hickory_resolver::{Resolver, error::ResolveErrorKind};
 use libc::c_int;
 // Cross-platform socket constants
 #[cfg(unix)]
 pub use libc::{AF_INET, AF_INET6, AF_UNIX, SOCK_DGRAM, SOCK_STREAM, sockaddr, socklen_t};
-use mirrord_config::feature::network::{
-    filter::{AddressFilter, ProtocolAndAddressFilter, ProtocolFilter},
-    outgoing::{OutgoingConfig, OutgoingFilterConfig},
+use mirrord_config::{
+    feature::network::{
+        filter::{AddressFilter, FilterSpecificity, ProtocolAndAddressFilter, ProtocolFilter},
+        outgoing::{OutgoingConfig, OutgoingFilterConfig},
+    },
+    util::VecOrSingle,
 };
 use mirrord_intproxy_protocol::{NetProtocol, OutgoingConnCloseRequest, PortUnsubscribe};
 use mirrord_protocol::{
@@ -97,6 +100,29 @@ pub struct Connected {
     pub connection_id: Option<u128>,
 }
 
+/// A remote peer that an unconnected UDP socket sends datagrams to with `sendto`/`sendmsg`.
+///
+/// Each peer gets its own outgoing connection, so the internal proxy has a separate interceptor
+/// socket for it. This lets us tell which peer a datagram came from in `recvfrom`, based on the
+/// interceptor address it was received from.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct UdpPeer {
+    /// The address requested by the user, returned from `recvfrom` for datagrams coming from
+    /// [`UdpPeer::layer_address`].
+    pub remote_address: SocketAddr,
+
+    /// The address of the interceptor socket, this is where we really send the datagrams.
+    pub layer_address: SocketAddr,
+
+    /// Unique ID of the outgoing connection to this peer.
+    pub connection_id: u128,
+}
+
+impl UdpPeer {
+    /// Maximum number of [`UdpPeer`]s of one socket, see [`UserSocket::add_udp_peer`].
+    pub const MAX_PER_SOCKET: usize = 64;
+}
+
 /// Represents a [`SocketState`] where the user made a `bind` call, and we intercepted it.
 ///
 /// ## Details
@@ -182,6 +208,8 @@ pub struct UserSocket {
     pub protocol: c_int,
     pub state: SocketState,
     pub kind: SocketKind,
+    /// Peers of an unconnected UDP socket, see [`UdpPeer`].
+    pub udp_peers: Vec<UdpPeer>,
 }
 
 impl UserSocket {
@@ -198,12 +226,27 @@ impl UserSocket {
             protocol,
             state,
             kind,
+            udp_peers: Vec::new(),
         }
     }
 
+    /// Adds a new [`UdpPeer`] to this socket.
+    ///
+    /// When the socket already has [`UdpPeer::MAX_PER_SOCKET`] peers, the oldest one is removed
+    /// and returned, so that its outgoing connection can be closed. Datagrams that still come from
+    /// its interceptor are then no longer reported as coming from its `remote_address`.
+    pub fn add_udp_peer(&mut self, peer: UdpPeer) -> Option<UdpPeer> {
+        let evicted =
+            (self.udp_peers.len() >= UdpPeer::MAX_PER_SOCKET).then(|| self.udp_peers.remove(0));
+        self.udp_peers.push(peer);
+        evicted
+    }
+
     /// Closes the socket and performs necessary cleanup.
     /// If this socket was listening and bound to a port, notifies agent to stop
     /// mirroring/stealing that port by sending PortUnsubscribe.
+    /// Outgoing connections of this socket (including the ones of its [`UdpPeer`]s) are closed
+    /// with OutgoingConnCloseRequest.
     ///
     /// **Important**
     ///
@@ -233,6 +276,12 @@ impl UserSocket {
             }
             _ => {}
         }
+
+        for peer in &self.udp_peers {
+            let _ = make_proxy_request_no_response(OutgoingConnCloseRequest {
+                conn_id: peer.connection_id,
+            });
+        }
     }
 }
 
@@ -450,16 +499,48 @@ pub enum ConnectionThrough {
     Remote(SocketAddr),
 }
 
+/// Destination ports from [`OutgoingConfig::local_ports`], outgoing traffic to them is never
+/// intercepted, before the [`OutgoingSelector`] is even consulted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LocalPorts(Vec<RangeInclusive<u16>>);
+
+impl LocalPorts {
+    /// Builds a new instance from the user config, which must be already verified.
+    pub fn new(config: &OutgoingConfig) -> Self {
+        Self(
+            config
+                .local_ports
+                .iter()
+                .map(|ports| ports.range().expect("invalid outgoing local ports"))
+                .collect(),
+        )
+    }
+
+    /// Checks whether the port of `address` is one of the local ports, whatever the IP is.
+    pub fn contains(&self, address: &SocketAddr) -> bool {
+        self.0.iter().any(|ports| ports.contains(&address.port()))
+    }
+}
+
 /// Holds the [`ProtocolAndAddressFilter`]s set up by the user in the [`OutgoingFilterConfig`].
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub enum OutgoingSelector {
     #[default]
     Unfiltered,
-    /// If the address from `connect` matches this, then we send the connection through the
-    /// remote pod.
-    Remote(HashSet<ProtocolAndAddressFilter>),
-    /// If the address from `connect` matches this, then we send the connection from the local app.
-    Local(HashSet<ProtocolAndAddressFilter>),
+    Filtered {
+        /// If the address from `connect` matches these, then we send the connection through the
+        /// remote pod.
+        ///
+        /// Sorted from the most specific filter, see [`ProtocolAndAddressFilter::specificity`].
+        remote: Vec<ProtocolAndAddressFilter>,
+        /// If the address from `connect` matches these, then we send the connection from the
+        /// local app.
+        ///
+        /// Sorted from the most specific filter, see [`ProtocolAndAddressFilter::specificity`].
+        local: Vec<ProtocolAndAddressFilter>,
+        /// Whether connections that match no filter are sent from the local app.
+        unmatched_is_local: bool,
+    },
 }
 
 impl OutgoingSelector {
@@ -467,8 +548,8 @@ impl OutgoingSelector {
         filters: I,
         tcp_enabled: bool,
         udp_enabled: bool,
-    ) -> HashSet<ProtocolAndAddressFilter> {
-        filters
+    ) -> Vec<ProtocolAndAddressFilter> {
+        let mut filters = filters
             .map(|filter| {
                 ProtocolAndAddressFilter::from_str(filter).expect("invalid outgoing filter")
             })
@@ -479,7 +560,10 @@ impl OutgoingSelector {
                 ProtocolFilter::Tcp => tcp_enabled,
                 ProtocolFilter::Udp => udp_enabled,
             })
-            .collect::<HashSet<_>>()
+            .collect::<Vec<_>>();
+
+        filters.sort_by_key(|filter| Reverse(filter.specificity()));
+        filters
     }
 
     /// Builds a new instance from the user config, removing filters
@@ -488,23 +572,26 @@ impl OutgoingSelector {
     ///
     /// It also removes duplicated filters, by putting them into a [`HashSet`].
     pub fn new(config: &OutgoingConfig) -> Self {
-        match &config.filter {
-            None => Self::Unfiltered,
-            Some(OutgoingFilterConfig::Remote(list)) | Some(OutgoingFilterConfig::Local(list))
-                if list.is_empty() =>
-            {
-                panic!("outgoing traffic filter cannot be empty");
-            }
-            Some(OutgoingFilterConfig::Remote(list)) => Self::Remote(Self::build_selector(
-                list.iter().map(String::as_str),
-                config.tcp,
-                config.udp,
-            )),
-            Some(OutgoingFilterConfig::Local(list)) => Self::Local(Self::build_selector(
-                list.iter().map(String::as_str),
+        let Some(filter) = &config.filter else {
+            return Self::Unfiltered;
+        };
+
+        if filter.filters().next().is_none() {
+            panic!("outgoing traffic filter cannot be empty");
+        }
+
+        let build = |list: &Option<VecOrSingle<String>>| {
+            Self::build_selector(
+                list.iter().flat_map(|list| list.iter()).map(String::as_str),
                 config.tcp,
                 config.udp,
-            )),
+            )
+        };
+
+        Self::Filtered {
+            remote: build(&filter.remote),
+            local: build(&filter.local),
+            unmatched_is_local: filter.unmatched_is_local(),
         }
     }
 
@@ -525,12 +612,19 @@ impl OutgoingSelector {
     /// `address` matches something specified in `local = [":7777"]`, then we return a
     /// [`ConnectionThrough::Local`].
     ///
+    /// ## Both
+    ///
+    /// When both `local` and `remote` filters match, the most specific filter wins, and `local`
+    /// wins the ties, see [`prefer_local`].
+    ///
     /// ## Filter rules
     ///
     /// The filter comparison follows these rules:
     ///
     /// 1. `0.0.0.0` means any ip;
     /// 2. `:0` means any port;
+    /// 3. names are matched against the name that resolved to `address` in `getaddrinfo` (see
+    ///    [`get_query_name_for_ip`]), and plain names are also resolved.
     ///
     /// So if the user specified a selector with `0.0.0.0:0`, we're going to be always matching on
     /// it.
@@ -540,28 +634,33 @@ impl OutgoingSelector {
         address: SocketAddr,
         protocol: NetProtocol,
     ) -> HookResult<ConnectionThrough> {
-        let (filters, selector_is_local) = match self {
-            Self::Unfiltered => return Ok(ConnectionThrough::Remote(address)),
-            Self::Local(filters) => (filters, true),
-            Self::Remote(filters) => (filters, false),
+        let Self::Filtered {
+            remote,
+            local,
+            unmatched_is_local,
+        } = self
+        else {
+            return Ok(ConnectionThrough::Remote(address));
         };
 
-        for filter in filters {
-            if !filter.matches(address, protocol, selector_is_local)? {
-                continue;
+        let query_name = get_query_name_for_ip(address.ip());
+        let first_match = |filters: &[ProtocolAndAddressFilter], force_local_dns: bool| {
+            for filter in filters {
+                if filter.matches(address, protocol, query_name.as_deref(), force_local_dns)? {
+                    return Ok(Some(filter.specificity()));
+                }
             }
 
-            return if selector_is_local {
-                Self::get_local_address_to_connect(address).map(ConnectionThrough::Local)
-            } else {
-                Ok(ConnectionThrough::Remote(address))
-            };
-        }
+            HookResult::Ok(None)
+        };
 
-        if selector_is_local {
-            Ok(ConnectionThrough::Remote(address))
-        } else {
+        let local = first_match(local, true)?;
+        let remote = first_match(remote, false)?;
+
+        if prefer_local(local, remote, *unmatched_is_local) {
             Self::get_local_address_to_connect(address).map(ConnectionThrough::Local)
+        } else {
+            Ok(ConnectionThrough::Remote(address))
         }
     }
 
@@ -572,8 +671,8 @@ impl OutgoingSelector {
     ///
     /// - `address`: the [`SocketAddr`] that was passed to `connect`;
     ///
-    /// We only get here when the [`OutgoingSelector::Remote`] matched nothing, or when the
-    /// [`OutgoingSelector::Local`] matched on something.
+    /// We only get here when the connection should go through the local app, see
+    /// [`OutgoingSelector::get_connection_through`].
     ///
     /// Returns 1 of 2 possibilities:
     ///
@@ -610,28 +709,23 @@ impl OutgoingSelector {
             .map(|resolved| SocketAddr::new(resolved, address.port()))
             .ok_or(HookError::DNSNoName)
     }
+}
 
-    /// Returns whether this selector is configured for remote traffic
-    pub fn is_remote(&self) -> bool {
-        matches!(self, Self::Remote(_))
-    }
-
-    /// Returns whether this selector is configured for local traffic
-    pub fn is_local(&self) -> bool {
-        matches!(self, Self::Local(_))
-    }
-
-    /// Returns whether this selector is unfiltered
-    pub fn is_unfiltered(&self) -> bool {
-        matches!(self, Self::Unfiltered)
-    }
-
-    /// Gets the filters for the selector, if any
-    pub fn filters(&self) -> Option<&HashSet<ProtocolAndAddressFilter>> {
-        match self {
-            Self::Remote(filters) | Self::Local(filters) => Some(filters),
-            Self::Unfiltered => None,
-        }
+/// Decides whether an outgoing connection goes through the local app, based on the
+/// [`FilterSpecificity`] of the most specific `local` and `remote` filters that match it.
+///
+/// The more specific filter wins, and `local` wins the ties. When no filter matches, uses
+/// `unmatched_is_local`.
+fn prefer_local(
+    local: Option<FilterSpecificity>,
+    remote: Option<FilterSpecificity>,
+    unmatched_is_local: bool,
+) -> bool {
+    match (local, remote) {
+        (None, None) => unmatched_is_local,
+        (Some(_), None) => true,
+        (None, Some(_)) => false,
+        (Some(local), Some(remote)) => local >= remote,
     }
 }
 
@@ -640,6 +734,9 @@ trait ProtocolAndAddressFilterExt {
     /// Matches the outgoing connection request (given as [[`SocketAddr`], [`NetProtocol`]] pair)
     /// against this filter.
     ///
+    /// [`AddressFilter::Name`]s are first matched against the `query_name` (the name passed to
+    /// `getaddrinfo` that resolved to `address`). Name patterns are never resolved.
+    ///
     /// # Note on DNS resolution
     ///
     /// This method may require a DNS resolution (when [`ProtocolAndAddressFilter::address`] is
@@ -649,6 +746,7 @@ trait ProtocolAndAddressFilterExt {
         &self,
         address: SocketAddr,
         protocol: NetProtocol,
+        query_name: Option<&str>,
         force_local_dns: bool,
     ) -> HookResult<bool>;
 }
@@ -658,6 +756,7 @@ impl ProtocolAndAddressFilterExt for ProtocolAndAddressFilter {
         &self,
         address: SocketAddr,
         protocol: NetProtocol,
+        query_name: Option<&str>,
         force_local_dns: bool,
     ) -> HookResult<bool> {
         if let (ProtocolFilter::Tcp, NetProtocol::Datagrams)
@@ -680,6 +779,12 @@ impl ProtocolAndAddressFilterExt for ProtocolAndAddressFilter {
         };
 
         match &self.address {
+            AddressFilter::Name(..)
+                if query_name.is_some_and(|name| self.address.matches_name(name)) =>
+            {
+                Ok(true)
+            }
+            AddressFilter::Name(..) if self.address.is_name_pattern() => Ok(false),
             AddressFilter::Name(name, port) => {
                 let resolved_ips = if setup().remote_dns_enabled() && !force_local_dns {
                     match remote_getaddrinfo(name.to_string(), *port, 0, family, 0, addr_protocol) {
@@ -722,3 +827,128 @@ impl ProtocolAndAddressFilterExt for ProtocolAndAddressFilter {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use mirrord_config::feature::network::{
+        filter::ProtocolAndAddressFilter,
+        outgoing::{OutgoingConfig, OutgoingFilterConfig, OutgoingLocalPorts},
+    };
+    use mirrord_intproxy_protocol::NetProtocol;
+    use rstest::rstest;
+
+    use super::{
+        LocalPorts, ProtocolAndAddressFilterExt, SocketKind, SocketState, UdpPeer, UserSocket,
+        prefer_local,
+    };
+
+    #[rstest]
+    #[case::only_local(Some("10.0.0.0/8"), None, false, true)]
+    #[case::only_remote(None, Some("10.0.0.0/8"), true, false)]
+    #[case::unmatched_local(None, None, true, true)]
+    #[case::unmatched_remote(None, None, false, false)]
+    #[case::longer_prefix(Some("10.0.0.0/8"), Some("10.1.0.0/16"), false, false)]
+    #[case::host_over_subnet(Some("10.1.2.3:443"), Some("10.0.0.0/8"), false, true)]
+    #[case::host_over_pattern(Some("*.internal.corp"), Some("db.internal.corp"), false, false)]
+    #[case::pattern_over_subnet(Some("*.internal.corp:5432"), Some("10.0.0.0/8"), false, true)]
+    #[case::port_over_any_port(Some("10.0.0.0/8"), Some("10.0.0.0/8:5432"), true, false)]
+    #[case::protocol_over_any(Some("tcp://:5432"), Some(":5432"), false, true)]
+    #[case::local_wins_ties(Some("*.internal.corp:5432"), Some("*.other.corp:5432"), false, true)]
+    fn outgoing_filter_precedence(
+        #[case] local: Option<&str>,
+        #[case] remote: Option<&str>,
+        #[case] unmatched_is_local: bool,
+        #[case] expected: bool,
+    ) {
+        let specificity = |filter: Option<&str>| {
+            filter.map(|filter| {
+                ProtocolAndAddressFilter::from_str(filter)
+                    .unwrap()
+                    .specificity()
+            })
+        };
+
+        assert_eq!(
+            prefer_local(specificity(local), specificity(remote), unmatched_is_local),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::port_v4("127.0.0.1:4140", true)]
+    #[case::port_v6("[::1]:4140", true)]
+    #[case::range_start_v4("10.1.2.3:9000", true)]
+    #[case::range_end_v6("[fd00::1]:9100", true)]
+    #[case::below_range("10.1.2.3:8999", false)]
+    #[case::above_range_v6("[fd00::1]:9101", false)]
+    #[case::other_port_v6("[::1]:4141", false)]
+    fn outgoing_local_ports(#[case] address: SocketAddr, #[case] expected: bool) {
+        let config = OutgoingConfig {
+            local_ports: vec![
+                OutgoingLocalPorts::Port(4140),
+                OutgoingLocalPorts::Range("9000-9100".into()),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(LocalPorts::new(&config).contains(&address), expected);
+    }
+
+    /// The layer checks [`LocalPorts`] before the outgoing filter, so the connection stays local
+    /// even though a `remote` filter matches it.
+    #[rstest]
+    #[case::v4("10.1.2.3:4140", "10.0.0.0/8")]
+    #[case::v6("[fd00::1]:4140", ":4140")]
+    fn outgoing_local_ports_overlap_remote_filter(
+        #[case] address: SocketAddr,
+        #[case] remote: &str,
+    ) {
+        let config = OutgoingConfig {
+            filter: Some(OutgoingFilterConfig {
+                remote: Some(vec![remote.to_string()].into()),
+                local: None,
+            }),
+            local_ports: vec![OutgoingLocalPorts::Port(4140)],
+            ..Default::default()
+        };
+
+        let remote = ProtocolAndAddressFilter::from_str(remote).unwrap();
+        assert!(
+            remote
+                .matches(address, NetProtocol::Stream, None, false)
+                .unwrap()
+        );
+        assert!(LocalPorts::new(&config).contains(&address));
+    }
+
+    /// The oldest [`UdpPeer`] is evicted once the socket has too many.
+    #[test]
+    fn udp_peers_are_capped() {
+        let mut socket = UserSocket::new(
+            libc::AF_INET,
+            libc::SOCK_DGRAM,
+            0,
+            SocketState::Initialized,
+            SocketKind::Udp(libc::SOCK_DGRAM),
+        );
+        let peer = |connection_id: u128| UdpPeer {
+            remote_address: SocketAddr::from(([10, 0, 0, 1], connection_id as u16)),
+            layer_address: SocketAddr::from(([127, 0, 0, 1], connection_id as u16)),
+            connection_id,
+        };
+
+        for connection_id in 0..UdpPeer::MAX_PER_SOCKET as u128 {
+            assert!(socket.add_udp_peer(peer(connection_id)).is_none());
+        }
+
+        let evicted = socket.add_udp_peer(peer(UdpPeer::MAX_PER_SOCKET as u128));
+        assert_eq!(evicted.map(|peer| peer.connection_id), Some(0));
+        assert_eq!(socket.udp_peers.len(), UdpPeer::MAX_PER_SOCKET);
+        assert_eq!(
+            socket.udp_peers.first().map(|peer| peer.connection_id),
+            Some(1)
+        );
+    }
+}
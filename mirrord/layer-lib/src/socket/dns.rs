@@ -15,7 +15,7 @@ use crate::{
     proxy_connection::make_proxy_request_with_response,
     socket::{
         AF_INET, AF_INET6, SOCK_DGRAM, SOCK_STREAM,
-        dns::reverse_dns::update_dns_reverse_mapping_bulk,
+        dns::reverse_dns::{update_dns_query_names, update_dns_reverse_mapping_bulk},
     },
 };
 
@@ -24,7 +24,8 @@ use crate::{
 ///
 /// # Note
 ///
-/// This function updates the mapping in [`reverse_dns::REMOTE_DNS_REVERSE_MAPPING`].
+/// This function updates the mappings in [`reverse_dns::REMOTE_DNS_REVERSE_MAPPING`] and
+/// [`reverse_dns::DNS_QUERY_NAMES`].
 #[mirrord_layer_macro::instrument(level = Level::TRACE, ret, err)]
 pub fn remote_getaddrinfo(
     node: String,
@@ -45,7 +46,7 @@ pub fn remote_getaddrinfo(
         _ => SockType::Any,
     };
     let addr_info_list = make_proxy_request_with_response(GetAddrInfoRequestV2 {
-        node,
+        node: node.clone(),
         service_port,
         flags,
         family,
@@ -60,6 +61,7 @@ pub fn remote_getaddrinfo(
         .collect();
 
     update_dns_reverse_mapping_bulk(&result);
+    update_dns_query_names(&node, result.iter().map(|(_, ip)| *ip));
 
     Ok(result)
 }
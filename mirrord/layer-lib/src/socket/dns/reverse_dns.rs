@@ -21,6 +21,15 @@ use crate::mutex::Mutex;
 pub static REMOTE_DNS_REVERSE_MAPPING: LazyLock<Mutex<HashMap<IpAddr, String>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
+/// Holds the pair of [`IpAddr`] with the names passed to `getaddrinfo` that resolved to them,
+/// either remotely or locally.
+///
+/// Unlike [`REMOTE_DNS_REVERSE_MAPPING`], which holds the names from the DNS records, these are
+/// the names the user's app asked for. Used by the outgoing filter to match host names and
+/// patterns (e.g. `*.internal.corp`) against the address the user called `connect` with.
+pub static DNS_QUERY_NAMES: LazyLock<Mutex<HashMap<IpAddr, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
 /// Maximum number of entries in the DNS cache to prevent memory exhaustion
 const MAX_DNS_CACHE_SIZE: usize = 1000;
 
@@ -37,34 +46,64 @@ pub fn update_dns_reverse_mapping_bulk(lookups: &Vec<(String, IpAddr)>) {
     }
 
     if let Ok(mut mapping) = REMOTE_DNS_REVERSE_MAPPING.lock() {
-        // Count how many *new* IPs we'll be adding so we can evict up-front.
-        let mut new_ips = HashSet::new();
-        for (_, ip) in lookups {
-            if !mapping.contains_key(ip) {
-                new_ips.insert(*ip);
-            }
+        insert_bounded(
+            &mut mapping,
+            lookups
+                .iter()
+                .map(|(hostname, ip)| (hostname.as_str(), *ip)),
+        );
+    }
+}
+
+/// Records the addresses that the `name` passed to `getaddrinfo` resolved to in
+/// [`DNS_QUERY_NAMES`].
+pub fn update_dns_query_names<I: IntoIterator<Item = IpAddr>>(name: &str, ips: I) {
+    let lookups = ips.into_iter().map(|ip| (name, ip)).collect::<Vec<_>>();
+    if lookups.is_empty() {
+        return;
+    }
+
+    if let Ok(mut mapping) = DNS_QUERY_NAMES.lock() {
+        insert_bounded(&mut mapping, lookups);
+    }
+}
+
+/// Inserts the `lookups` into the `mapping`, evicting old entries so that it does not grow over
+/// [`MAX_DNS_CACHE_SIZE`].
+fn insert_bounded<'a, I>(mapping: &mut HashMap<IpAddr, String>, lookups: I)
+where
+    I: IntoIterator<Item = (&'a str, IpAddr)>,
+    I::IntoIter: Clone,
+{
+    let lookups = lookups.into_iter();
+
+    // Count how many *new* IPs we'll be adding so we can evict up-front.
+    let mut new_ips = HashSet::new();
+    for (_, ip) in lookups.clone() {
+        if !mapping.contains_key(&ip) {
+            new_ips.insert(ip);
         }
+    }
 
-        if !new_ips.is_empty() {
-            // Figure out how much space is missing.
-            let available_slots = MAX_DNS_CACHE_SIZE.saturating_sub(mapping.len());
-            let mut to_evict = new_ips.len().saturating_sub(available_slots);
+    if !new_ips.is_empty() {
+        // Figure out how much space is missing.
+        let available_slots = MAX_DNS_CACHE_SIZE.saturating_sub(mapping.len());
+        let mut to_evict = new_ips.len().saturating_sub(available_slots);
 
-            // Evict entries ahead of time so new inserts are safe.
-            while to_evict > 0 && !mapping.is_empty() {
-                if let Some(key) = mapping.keys().next().cloned() {
-                    mapping.remove(&key);
-                    to_evict -= 1;
-                } else {
-                    break;
-                }
+        // Evict entries ahead of time so new inserts are safe.
+        while to_evict > 0 && !mapping.is_empty() {
+            if let Some(key) = mapping.keys().next().cloned() {
+                mapping.remove(&key);
+                to_evict -= 1;
+            } else {
+                break;
             }
         }
+    }
 
-        // Apply the batch once enough space is guaranteed.
-        for (hostname, ip) in lookups {
-            mapping.insert(*ip, hostname.clone());
-        }
+    // Apply the batch once enough space is guaranteed.
+    for (hostname, ip) in lookups {
+        mapping.insert(ip, hostname.to_owned());
     }
 }
 
@@ -80,3 +119,9 @@ pub fn update_dns_reverse_mapping_bulk(lookups: &Vec<(String, IpAddr)>) {
 pub fn get_hostname_for_ip(ip: IpAddr) -> Option<String> {
     REMOTE_DNS_REVERSE_MAPPING.lock().ok()?.get(&ip).cloned()
 }
+
+/// Get the name passed to `getaddrinfo` that resolved to an IP address, see [`DNS_QUERY_NAMES`].
+/// Returns None if the IP was not found in the mapping.
+pub fn get_query_name_for_ip(ip: IpAddr) -> Option<String> {
+    DNS_QUERY_NAMES.lock().ok()?.get(&ip).cloned()
+}
@@ -1,70 +1,253 @@
-use std::{net::IpAddr, ops::Deref};
+use std::{borrow::Cow, net::IpAddr, ops::Deref, sync::OnceLock};
 
 use mirrord_config::feature::network::{
-    dns::{DnsConfig, DnsFilterConfig},
+    dns::{DnsConfig, DnsFilterConfig, DnsResolution},
     filter::AddressFilter,
 };
+use regex::Regex;
 use tracing::Level;
 
-use crate::detour::{Bypass, Detour};
+use crate::{
+    detour::{Bypass, Detour, OptionExt},
+    socket::{get_hostname_for_ip, hostname::read_remote_file_via_proxy},
+};
 
 /// Generated from [`DnsConfig`] provided in the [`LayerConfig`](mirrord_config::LayerConfig).
 /// Decides whether DNS queries are done locally or remotely.
 #[derive(Debug)]
 pub struct DnsSelector {
-    /// Filters provided in the config.
-    filters: Vec<AddressFilter>,
-    /// Whether a query matching one of [`Self::filters`] should be done locally.
-    filter_is_local: bool,
+    filter: DnsFilter,
+}
+
+/// Filter of a [`DnsSelector`], see [`DnsFilterConfig`].
+#[derive(Debug)]
+enum DnsFilter {
+    /// From [`DnsFilterConfig::Remote`] and [`DnsFilterConfig::Local`].
+    Addresses {
+        /// Filters provided in the config.
+        filters: Vec<AddressFilter>,
+        /// Whether a query matching one of `filters` should be done locally.
+        filter_is_local: bool,
+    },
+    /// From [`DnsFilterConfig::Rules`].
+    Rules(DnsRules),
+}
+
+/// Compiled [`DnsFilterConfig::Rules`].
+#[derive(Debug)]
+struct DnsRules {
+    /// In the config order, the first match wins.
+    rules: Vec<(Regex, DnsResolution)>,
+    /// Used for names that don't match any of the [`Self::rules`].
+    default: DnsResolution,
+    /// Search config of the remote pod, read on the first query.
+    search: OnceLock<SearchConfig>,
+}
+
+impl DnsRules {
+    /// Returns where the query for the `name` should go.
+    ///
+    /// The `name` is expanded with the remote search domains, and the candidates are checked in
+    /// the order the resolver would try them, except that the `name` as given comes first. The
+    /// first rule matching a candidate wins.
+    fn resolution(&self, name: &str) -> DnsResolution {
+        self.search
+            .get_or_init(SearchConfig::from_remote)
+            .expand(name)
+            .iter()
+            .find_map(|candidate| {
+                self.rules
+                    .iter()
+                    .find(|(regex, _)| regex.is_match(candidate))
+                    .map(|(_, resolution)| *resolution)
+            })
+            .unwrap_or(self.default)
+    }
+}
+
+/// The parts of the remote `/etc/resolv.conf` that decide how names are expanded before being
+/// resolved, see `resolv.conf(5)`.
+#[derive(Debug, PartialEq, Eq)]
+struct SearchConfig {
+    /// From the `search` (or `domain`) line.
+    domains: Vec<String>,
+    /// From the `ndots` option, names with fewer dots are expanded with [`Self::domains`].
+    ndots: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            domains: Default::default(),
+            ndots: 1,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Max size of the remote `/etc/resolv.conf` we read.
+    const MAX_RESOLV_CONF_SIZE: u64 = 4096;
+
+    /// Reads the search config from the remote `/etc/resolv.conf`, falls back to no search
+    /// domains if that fails.
+    fn from_remote() -> Self {
+        match read_remote_file_via_proxy("/etc/resolv.conf", Self::MAX_RESOLV_CONF_SIZE) {
+            Ok(bytes) => Self::parse(&String::from_utf8_lossy(&bytes)),
+            Err(error) => {
+                tracing::warn!(
+                    %error,
+                    "Failed to read the remote /etc/resolv.conf, DNS filter rules will be \
+                    matched without the search domains"
+                );
+                Default::default()
+            }
+        }
+    }
+
+    fn parse(resolv_conf: &str) -> Self {
+        let mut config = Self::default();
+
+        for line in resolv_conf.lines() {
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                // The last `search` or `domain` line wins.
+                Some("search" | "domain") => {
+                    config.domains = words
+                        .map(|domain| domain.trim_end_matches('.').to_owned())
+                        .collect();
+                }
+                Some("options") => {
+                    if let Some(ndots) = words
+                        .filter_map(|option| option.strip_prefix("ndots:"))
+                        .filter_map(|ndots| ndots.parse().ok())
+                        .next_back()
+                    {
+                        config.ndots = ndots;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        config
+    }
+
+    /// Returns the `name` (without the trailing `.`), followed by the names it would be expanded
+    /// to by the resolver.
+    ///
+    /// Fully qualified names (with the trailing `.`), and names with at least [`Self::ndots`]
+    /// dots are not expanded.
+    fn expand<'a>(&self, name: &'a str) -> Vec<Cow<'a, str>> {
+        if let Some(name) = name.strip_suffix('.') {
+            return vec![Cow::Borrowed(name)];
+        }
+
+        let mut names = vec![Cow::Borrowed(name)];
+        if name.matches('.').count() < self.ndots {
+            names.extend(
+                self.domains
+                    .iter()
+                    .map(|domain| Cow::Owned(format!("{name}.{domain}"))),
+            );
+        }
+
+        names
+    }
 }
 
 impl DnsSelector {
     /// Bypasses queries that should be done locally.
+    ///
+    /// With [`DnsFilterConfig::Rules`], IP addresses are always bypassed.
     #[tracing::instrument(level = Level::DEBUG, ret)]
     pub fn check_query(&self, node: &str, port: u16) -> Detour<()> {
-        let matched = self
-            .filters
-            .iter()
-            .filter(|filter| {
-                let filter_port = filter.port();
-                filter_port == 0 || filter_port == port
-            })
-            .any(|filter| match filter {
-                AddressFilter::Port(..) => true,
-                AddressFilter::Name(filter_name, _) => filter_name == node,
-                AddressFilter::Socket(filter_socket) => {
-                    filter_socket.ip().is_unspecified()
-                        || Some(filter_socket.ip()) == node.parse().ok()
-                }
-                AddressFilter::Subnet(filter_subnet, _) => {
-                    let Ok(ip) = node.parse::<IpAddr>() else {
-                        return false;
-                    };
+        let is_local = match &self.filter {
+            DnsFilter::Addresses {
+                filters,
+                filter_is_local,
+            } => {
+                let matched = filters
+                    .iter()
+                    .filter(|filter| {
+                        let filter_port = filter.port();
+                        filter_port == 0 || filter_port == port
+                    })
+                    .any(|filter| match filter {
+                        AddressFilter::Port(..) => true,
+                        AddressFilter::Name(..) => filter.matches_name(node),
+                        AddressFilter::Socket(filter_socket) => {
+                            filter_socket.ip().is_unspecified()
+                                || Some(filter_socket.ip()) == node.parse().ok()
+                        }
+                        AddressFilter::Subnet(filter_subnet, _) => {
+                            let Ok(ip) = node.parse::<IpAddr>() else {
+                                return false;
+                            };
 
-                    filter_subnet.contains(&ip)
-                }
-            });
+                            filter_subnet.contains(&ip)
+                        }
+                    });
+
+                matched == *filter_is_local
+            }
+            DnsFilter::Rules(..) if node.parse::<IpAddr>().is_ok() => true,
+            DnsFilter::Rules(rules) => rules.resolution(node) == DnsResolution::Local,
+        };
 
-        if matched == self.filter_is_local {
+        if is_local {
             Detour::Bypass(Bypass::LocalDns)
         } else {
             Detour::Success(())
         }
     }
+
+    /// Checks a reverse lookup of the `ip`, returns the name the `ip` was resolved from through
+    /// the remote pod, if the query for that name also goes through the remote pod.
+    ///
+    /// Bypasses the lookup when the `ip` was not resolved remotely.
+    #[tracing::instrument(level = Level::DEBUG, ret)]
+    pub fn check_reverse_query(&self, ip: IpAddr) -> Detour<String> {
+        let name = get_hostname_for_ip(ip).bypass(Bypass::LocalDns)?;
+        self.check_query(&name, 0)?;
+
+        Detour::Success(name)
+    }
 }
 
 impl From<&DnsConfig> for DnsSelector {
     fn from(value: &DnsConfig) -> Self {
         if !value.enabled {
             return Self {
-                filters: Default::default(),
-                filter_is_local: false,
+                filter: DnsFilter::Addresses {
+                    filters: Default::default(),
+                    filter_is_local: false,
+                },
             };
         }
 
         let (filters, filter_is_local) = match &value.filter {
-            Some(DnsFilterConfig::Local(filters)) => (Some(filters.deref()), true),
-            Some(DnsFilterConfig::Remote(filters)) => (Some(filters.deref()), false),
+            Some(DnsFilterConfig::Local { local }) => (Some(local.deref()), true),
+            Some(DnsFilterConfig::Remote { remote }) => (Some(remote.deref()), false),
+            Some(DnsFilterConfig::Rules { rules, default }) => {
+                let rules = rules
+                    .iter()
+                    .map(|rule| {
+                        let regex = rule
+                            .to_regex()
+                            .expect("bad DNS filter rule, should be verified in the CLI");
+                        (regex, rule.resolve)
+                    })
+                    .collect();
+
+                return Self {
+                    filter: DnsFilter::Rules(DnsRules {
+                        rules,
+                        default: *default,
+                        search: Default::default(),
+                    }),
+                };
+            }
             None => (None, true),
         };
 
@@ -79,8 +262,116 @@ impl From<&DnsConfig> for DnsSelector {
             .collect();
 
         Self {
-            filters,
-            filter_is_local,
+            filter: DnsFilter::Addresses {
+                filters,
+                filter_is_local,
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use mirrord_config::feature::network::dns::{
+        DnsConfig, DnsFilterConfig, DnsFilterRule, DnsResolution,
+    };
+    use rstest::rstest;
+
+    use super::{DnsFilter, DnsSelector, SearchConfig};
+    use crate::detour::Detour;
+
+    fn rule(pattern: &str, resolve: DnsResolution) -> DnsFilterRule {
+        let (pattern, regex) = match pattern.strip_prefix("regex:") {
+            Some(regex) => (None, Some(regex.to_owned())),
+            None => (Some(pattern.to_owned()), None),
+        };
+
+        DnsFilterRule {
+            pattern,
+            regex,
+            resolve,
+        }
+    }
+
+    /// Selector with overlapping rules, and the search config of a pod in the `default`
+    /// namespace.
+    fn selector(default: DnsResolution) -> DnsSelector {
+        let selector = DnsSelector::from(&DnsConfig {
+            enabled: true,
+            filter: Some(DnsFilterConfig::Rules {
+                rules: vec![
+                    rule("db.default.svc.cluster.local", DnsResolution::Local),
+                    rule("*.svc.cluster.local", DnsResolution::Remote),
+                    rule(r"regex:\.corp\.example\.com$", DnsResolution::Local),
+                    rule("*.example.com", DnsResolution::Remote),
+                ],
+                default,
+            }),
+            cache: Default::default(),
+        });
+
+        let DnsFilter::Rules(rules) = &selector.filter else {
+            panic!("expected DNS filter rules");
+        };
+        rules
+            .search
+            .set(SearchConfig::parse(
+                "nameserver 10.96.0.10\n\
+                search default.svc.cluster.local svc.cluster.local cluster.local\n",
+            ))
+            .unwrap();
+
+        selector
+    }
+
+    #[rstest]
+    #[case::first_match_wins("db.default.svc.cluster.local", true)]
+    #[case::cluster_name("other.default.svc.cluster.local", false)]
+    #[case::case_insensitive("OTHER.Default.SVC.cluster.local.", false)]
+    #[case::search_domain("my-service", false)]
+    #[case::search_domain_first_match_wins("db", true)]
+    #[case::not_expanded("db.internal", true)]
+    #[case::regex_before_glob("vpn.corp.example.com", true)]
+    #[case::glob_after_regex("www.example.com", false)]
+    #[case::default("crates.io", true)]
+    #[case::ip_literal("10.0.0.1", true)]
+    #[case::ipv6_literal("::1", true)]
+    fn rules_precedence(#[case] node: &str, #[case] local: bool) {
+        let result = selector(DnsResolution::Local).check_query(node, 0);
+        assert_eq!(matches!(result, Detour::Bypass(..)), local, "{result:?}");
+    }
+
+    #[test]
+    fn rules_default() {
+        let selector = selector(DnsResolution::Remote);
+        assert!(matches!(
+            selector.check_query("crates.io", 0),
+            Detour::Success(())
+        ));
+        assert!(matches!(
+            selector.check_query("vpn.corp.example.com", 0),
+            Detour::Bypass(..)
+        ));
+    }
+
+    #[test]
+    fn search_expansion() {
+        let search = SearchConfig::parse(
+            "domain ignored.local\nsearch ns.svc.cluster.local. cluster.local\noptions ndots:2 rotate\n",
+        );
+        assert_eq!(
+            search,
+            SearchConfig {
+                domains: vec!["ns.svc.cluster.local".into(), "cluster.local".into()],
+                ndots: 2,
+            }
+        );
+
+        assert_eq!(
+            search.expand("db.ns"),
+            ["db.ns", "db.ns.ns.svc.cluster.local", "db.ns.cluster.local"]
+        );
+        assert_eq!(search.expand("a.b.c"), ["a.b.c"]);
+        assert_eq!(search.expand("db."), ["db"]);
+    }
+}
@@ -109,7 +109,7 @@ pub fn remote_hostname_string(check_is_enabled: bool) -> HookResult<Option<Strin
 }
 
 /// Generic helper to read a file from the remote target via ProxyConnection
-fn read_remote_file_via_proxy(file_path: &str, max_size: u64) -> HookResult<Vec<u8>> {
+pub(crate) fn read_remote_file_via_proxy(file_path: &str, max_size: u64) -> HookResult<Vec<u8>> {
     ManagedRemoteFile::open(file_path)?.read_all(max_size)
 }
 
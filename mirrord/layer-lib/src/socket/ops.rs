@@ -11,6 +11,8 @@ use std::{
 };
 
 use libc::c_int;
+#[cfg(unix)]
+use mirrord_intproxy_protocol::OutgoingConnCloseRequest;
 use mirrord_intproxy_protocol::{NetProtocol, OutgoingConnectRequest, OutgoingConnectResponse};
 use mirrord_protocol::outgoing::SocketAddress;
 #[cfg(unix)]
@@ -37,6 +39,8 @@ use crate::{
 use crate::{error::ConnectError, socket::dns::windows::check_address_reachability};
 #[cfg(windows)]
 use crate::{error::windows::WindowsError, socket::sockets::find_listener_address_by_port};
+#[cfg(unix)]
+use crate::{proxy_connection::make_proxy_request_no_response, socket::UdpPeer};
 
 /// Result type for connect operations that preserves errno information
 #[derive(Debug)]
@@ -214,6 +218,24 @@ where
     }
 }
 
+/// Converts a unix socket address into the string that is matched against
+/// `feature.network.outgoing.unix_streams`.
+///
+/// Pathnames are used as they are, while abstract names (which start with a NUL byte) get a
+/// leading `@` instead, so that patterns can tell them apart. Non-utf8 bytes are replaced with
+/// [`char::REPLACEMENT_CHARACTER`]. Unnamed addresses have no string.
+#[cfg(unix)]
+pub fn unix_address_pattern_input(address: &SockAddr) -> Option<String> {
+    address
+        .as_pathname()
+        .map(|path| path.to_string_lossy().into_owned())
+        .or_else(|| {
+            address
+                .as_abstract_namespace()
+                .map(|name| format!("@{}", String::from_utf8_lossy(name)))
+        })
+}
+
 /// Helper function to check if a port should be ignored (port 0)
 #[inline]
 pub fn is_ignored_port(addr: &SocketAddr) -> bool {
@@ -259,6 +281,11 @@ where
             return Detour::Success(ConnectResult::from(0));
         }
 
+        // Takes precedence over everything else, including connecting to our own listeners.
+        if setup().is_outgoing_local_port(&ip_address) {
+            return Detour::Bypass(Bypass::LocalOutgoingPort(ip_address));
+        }
+
         // Handle localhost/unspecified addresses first -
         //  if applicable, connect locally without proxy
         let ip = ip_address.ip();
@@ -301,13 +328,7 @@ where
     } else if remote_address.is_unix() {
         #[cfg(unix)]
         {
-            let address = remote_address
-                .as_pathname()
-                .map(|path| path.to_string_lossy())
-                .or(remote_address
-                    .as_abstract_namespace()
-                    .map(String::from_utf8_lossy))
-                .map(String::from);
+            let address = unix_address_pattern_input(&remote_address);
 
             let handle_remotely = address
                 .as_ref()
@@ -316,6 +337,13 @@ where
             if !handle_remotely {
                 return Detour::Bypass(Bypass::UnixSocket(address));
             }
+
+            // The internal proxy can only intercept unix streams.
+            if user_socket_info.kind.is_udp() {
+                return Detour::Error(HookError::UnsupportedUnixDatagram(
+                    address.unwrap_or_default(),
+                ));
+            }
         }
         #[cfg(windows)]
         {
@@ -413,29 +441,7 @@ where
         } = make_proxy_request_with_response(request)??;
 
         if let SocketAddress::Ip(interceptor_addr) = &mut layer_address {
-            // Our socket can be bound to any local interface,
-            // so the interceptor listens on an unspecified IP address, e.g. 0.0.0.0
-            // We need to fill the exact IP here.
-            match &user_socket_info.state {
-                SocketState::Bound {
-                    bound: Bound { address, .. },
-                    ..
-                } => {
-                    if interceptor_addr.ip().is_unspecified() {
-                        if interceptor_addr.is_ipv4() {
-                            interceptor_addr.set_ip(Ipv4Addr::LOCALHOST.into())
-                        } else {
-                            interceptor_addr.set_ip(Ipv6Addr::LOCALHOST.into())
-                        }
-                    } else {
-                        interceptor_addr.set_ip(address.ip());
-                    }
-                }
-                _ if interceptor_addr.is_ipv4() => {
-                    interceptor_addr.set_ip(Ipv4Addr::LOCALHOST.into())
-                }
-                _ => interceptor_addr.set_ip(Ipv6Addr::LOCALHOST.into()),
-            }
+            fill_interceptor_ip(interceptor_addr, &user_socket_info.state);
         }
 
         // Connect to the socket prepared by the internal proxy.
@@ -522,6 +528,118 @@ where
     Detour::Success(connect_result)
 }
 
+/// Our socket can be bound to any local interface, so the interceptor listens on an unspecified IP
+/// address, e.g. 0.0.0.0. We need to fill the exact IP here.
+fn fill_interceptor_ip(interceptor_addr: &mut SocketAddr, state: &SocketState) {
+    match state {
+        SocketState::Bound {
+            bound: Bound { address, .. },
+            ..
+        } => {
+            if interceptor_addr.ip().is_unspecified() {
+                if interceptor_addr.is_ipv4() {
+                    interceptor_addr.set_ip(Ipv4Addr::LOCALHOST.into())
+                } else {
+                    interceptor_addr.set_ip(Ipv6Addr::LOCALHOST.into())
+                }
+            } else {
+                interceptor_addr.set_ip(address.ip());
+            }
+        }
+        _ if interceptor_addr.is_ipv4() => interceptor_addr.set_ip(Ipv4Addr::LOCALHOST.into()),
+        _ => interceptor_addr.set_ip(Ipv6Addr::LOCALHOST.into()),
+    }
+}
+
+/// Returns the address that a datagram sent from the unconnected UDP socket `sockfd` to
+/// `destination` should really be sent to.
+///
+/// When the outgoing filter says that `destination` should be reached through the cluster, the
+/// first datagram sent to it requests a new outgoing connection from the internal proxy, and the
+/// socket gets a new [`UdpPeer`]. The following datagrams go to the same interceptor socket, so the
+/// datagram boundaries and the order are kept. Otherwise, the (possibly resolved) local address is
+/// returned.
+///
+/// Bypasses when outgoing UDP traffic is disabled, when `destination` is ignored or its port is
+/// one of the `outgoing.local_ports` (see [`connect_common`]), or when the socket is already
+/// connected.
+#[cfg(unix)]
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub fn udp_peer_address(sockfd: SocketDescriptor, destination: SocketAddr) -> Detour<SockAddr> {
+    if !setup().outgoing_config().udp {
+        return Detour::Bypass(Bypass::DisabledOutgoing);
+    }
+
+    if setup().is_outgoing_local_port(&destination) {
+        return Detour::Bypass(Bypass::LocalOutgoingPort(destination));
+    }
+
+    let ip = destination.ip();
+    if is_ignored_port(&destination)
+        || setup().is_debugger_port(&destination)
+        || ((ip.is_loopback() || ip.is_unspecified()) && setup().outgoing_config().ignore_localhost)
+    {
+        return Detour::Bypass(Bypass::IgnoredInIncoming(destination));
+    }
+
+    let user_socket_info = SOCKETS
+        .lock()?
+        .get(&sockfd)
+        .cloned()
+        .ok_or(Bypass::LocalFdNotFound(sockfd))?;
+
+    if !user_socket_info.kind.is_udp()
+        || matches!(user_socket_info.state, SocketState::Connected(_))
+    {
+        return Detour::Bypass(Bypass::InvalidState(sockfd));
+    }
+
+    if let Some(peer) = user_socket_info
+        .udp_peers
+        .iter()
+        .find(|peer| peer.remote_address == destination)
+    {
+        return Detour::Success(peer.layer_address.into());
+    }
+
+    let remote_address = match setup()
+        .outgoing_selector()
+        .get_connection_through(destination, NetProtocol::Datagrams)?
+    {
+        ConnectionThrough::Remote(addr) => addr,
+        ConnectionThrough::Local(addr) => return Detour::Success(addr.into()),
+    };
+
+    let OutgoingConnectResponse {
+        connection_id,
+        layer_address,
+        ..
+    } = make_proxy_request_with_response(create_outgoing_request(
+        remote_address,
+        NetProtocol::Datagrams,
+    ))??;
+
+    let SocketAddress::Ip(mut layer_address) = layer_address else {
+        return Detour::Bypass(Bypass::AddressConversion);
+    };
+    fill_interceptor_ip(&mut layer_address, &user_socket_info.state);
+
+    let evicted = SOCKETS.lock()?.get_mut(&sockfd).and_then(|socket| {
+        Arc::make_mut(socket).add_udp_peer(UdpPeer {
+            remote_address: destination,
+            layer_address,
+            connection_id,
+        })
+    });
+    if let Some(evicted) = evicted {
+        let _ = make_proxy_request_no_response(OutgoingConnCloseRequest {
+            conn_id: evicted.connection_id,
+        });
+    }
+
+    Detour::Success(layer_address.into())
+}
+
 /// Creates an outgoing connection request for the specified address and protocol
 pub fn create_outgoing_request(
     remote_address: SocketAddr,
@@ -764,4 +882,46 @@ mod tests {
         ));
         assert!(!is_unix_address(&addr));
     }
+
+    #[test]
+    fn test_fill_interceptor_ip() {
+        let mut interceptor = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 4000);
+        fill_interceptor_ip(&mut interceptor, &SocketState::Initialized);
+        assert_eq!(interceptor.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        let mut interceptor = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 4000);
+        fill_interceptor_ip(&mut interceptor, &SocketState::Initialized);
+        assert_eq!(interceptor.ip(), IpAddr::V6(Ipv6Addr::LOCALHOST));
+
+        let bound = SocketState::Bound {
+            bound: Bound {
+                requested_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 9000),
+                address: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)), 9000),
+            },
+            is_only_bound: false,
+        };
+        let mut interceptor = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 4000);
+        fill_interceptor_ip(&mut interceptor, &bound);
+        assert_eq!(interceptor.ip(), IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)));
+        assert_eq!(interceptor.port(), 4000);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_unix_address_pattern_input() {
+        let path = SockAddr::unix("/var/run/app/sidecar.sock").unwrap();
+        assert_eq!(
+            unix_address_pattern_input(&path).as_deref(),
+            Some("/var/run/app/sidecar.sock")
+        );
+
+        let abstract_name = SockAddr::unix("\0sidecar").unwrap();
+        assert_eq!(
+            unix_address_pattern_input(&abstract_name).as_deref(),
+            Some("@sidecar")
+        );
+
+        let unnamed = SockAddr::unix("").unwrap();
+        assert_eq!(unix_address_pattern_input(&unnamed), None);
+    }
 }
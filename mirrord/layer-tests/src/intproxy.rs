@@ -11,11 +11,11 @@ use futures::{SinkExt, StreamExt};
 use mirrord_config::{
     LayerFileConfig,
     config::{ConfigContext, MirrordConfig},
-    experimental::ExperimentalFileConfig,
 };
-use mirrord_intproxy::{IntProxy, agent_conn::AgentConnection};
+use mirrord_intproxy::{IntProxy, PingPongConfig, agent_conn::AgentConnection};
 use mirrord_protocol::{
     ClientMessage, ConnectionId, DaemonCodec, DaemonMessage, FileRequest, FileResponse, ToPayload,
+    compression::CompressionCodec,
     file::{
         AccessFileRequest, AccessFileResponse, MetadataInternal, OpenFileRequest,
         OpenOptionsInternal, ReadFileRequest, SeekFromInternal, XstatFsResponseV2, XstatRequest,
@@ -33,6 +33,9 @@ use tokio::net::{TcpListener, TcpStream};
 
 pub struct TestIntProxy {
     codec: Framed<TcpStream, DaemonCodec>,
+    /// Accepts the connections the intproxy makes to resume the session, see
+    /// [`Self::reconnect`].
+    fake_agent_listener: TcpListener,
     num_connections: u64,
 }
 
@@ -41,21 +44,15 @@ impl TestIntProxy {
         let fake_agent_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
         let fake_agent_address = fake_agent_listener.local_addr().unwrap();
         let mut context = ConfigContext::default();
-        let experimental_config = match config {
-            Some(path) => {
-                LayerFileConfig::from_path(path, &mut context)
-                    .unwrap()
-                    .generate_config(&mut context)
-                    .unwrap()
-                    .experimental
-            }
-            None => ExperimentalFileConfig::default()
-                .generate_config(&mut context)
-                .unwrap(),
-        };
+        let config = match config {
+            Some(path) => LayerFileConfig::from_path(path, &mut context).unwrap(),
+            None => LayerFileConfig::default(),
+        }
+        .generate_config(&mut context)
+        .unwrap();
 
         tokio::spawn(async move {
-            let agent_conn = AgentConnection::new_for_raw_address(fake_agent_address)
+            let agent_conn = AgentConnection::new_for_raw_address(fake_agent_address, &config)
                 .await
                 .unwrap();
             let intproxy = IntProxy::new_with_connection(
@@ -64,7 +61,13 @@ impl TestIntProxy {
                 0,
                 Default::default(),
                 Duration::from_secs(60),
-                &experimental_config,
+                PingPongConfig {
+                    interval: Duration::from_secs(30),
+                    max_missed_pongs: 1,
+                },
+                &Default::default(),
+                &config.experimental,
+                None,
             );
             intproxy
                 .run(Duration::from_secs(15), Duration::from_secs(5))
@@ -78,10 +81,23 @@ impl TestIntProxy {
 
         Self {
             codec,
+            fake_agent_listener,
             num_connections: 0,
         }
     }
 
+    /// Closes the connection with the intproxy, and accepts the one it makes to resume the
+    /// session.
+    ///
+    /// The intproxy resumes sessions only with `internal_proxy.reconnect_without_operator`.
+    pub async fn reconnect(&mut self) {
+        self.codec.close().await.unwrap();
+
+        let (stream, _) = self.fake_agent_listener.accept().await.unwrap();
+        self.codec = Framed::new(stream, DaemonCodec::default());
+        println!("intproxy reconnected");
+    }
+
     pub async fn recv(&mut self) -> ClientMessage {
         self.try_recv().await.expect("intproxy connection closed")
     }
@@ -164,7 +180,13 @@ impl TestIntProxy {
                     self.send(DaemonMessage::SwitchProtocolVersionResponse(version))
                         .await;
                 }
-                ClientMessage::ReadyForLogs => {}
+                ClientMessage::ReadyForLogs | ClientMessage::ClientIdentity(..) => {}
+                ClientMessage::SwitchCompression(..) => {
+                    self.send(DaemonMessage::SwitchCompressionResponse(
+                        CompressionCodec::None,
+                    ))
+                    .await;
+                }
                 other => break Some(other),
             }
         }
@@ -20,7 +20,11 @@ pub const RUST_OUTGOING_LOCAL: &str = "4.4.4.4:4444";
 /// Various applications used by integration tests.
 #[derive(Debug)]
 pub enum Application {
-    RustOutgoingTcp { non_blocking: bool },
+    RustOutgoingTcp {
+        non_blocking: bool,
+    },
+    /// Reads the ports to listen on from the `APP_PORTS` environment variable.
+    RustListenPorts,
 }
 
 impl Application {
@@ -31,6 +35,11 @@ impl Application {
                 env!("CARGO_MANIFEST_DIR"),
                 "../../target/debug/outgoing",
             ),
+            Application::RustListenPorts => format!(
+                "{}/{}",
+                env!("CARGO_MANIFEST_DIR"),
+                "../../target/debug/listen_ports",
+            ),
         }
     }
 
@@ -54,12 +63,13 @@ impl Application {
             .into_iter()
             .map(Into::into)
             .collect(),
+            Application::RustListenPorts => vec![],
         }
     }
 
     pub fn get_app_port(&self) -> u16 {
         match self {
-            Application::RustOutgoingTcp { .. } => {
+            Application::RustOutgoingTcp { .. } | Application::RustListenPorts => {
                 unimplemented!("shouldn't get here")
             }
         }
@@ -0,0 +1,127 @@
+#![warn(clippy::indexing_slicing)]
+
+use core::assert_matches;
+use std::{
+    io::Write,
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage,
+    outgoing::{LayerWrite, tcp::LayerTcpOutgoing},
+    tcp::{DaemonTcp, LayerTcp},
+};
+use rstest::rstest;
+use tempfile::NamedTempFile;
+use tokio::net::TcpListener;
+
+mod common;
+
+pub use common::*;
+
+/// Writes a config file that enables resuming the session after the agent connection drops, see
+/// [`TestIntProxy::reconnect`](mirrord_layer_tests::intproxy::TestIntProxy::reconnect).
+fn reconnect_config(incoming: serde_json::Value) -> NamedTempFile {
+    let config = serde_json::json!({
+        "feature": {
+            "network": {
+                "incoming": incoming,
+            },
+            "fs": "local"
+        },
+        "internal_proxy": {
+            "reconnect_without_operator": true
+        }
+    });
+
+    let mut config_file = NamedTempFile::with_suffix(".json").unwrap();
+    config_file
+        .as_file_mut()
+        .write_all(serde_json::to_string(&config).unwrap().as_bytes())
+        .unwrap();
+    config_file
+}
+
+/// Verifies that the port subscriptions of the application are restored after the agent
+/// connection drops, and that the mirrored traffic keeps reaching the application.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(30))]
+async fn reconnect_resubscribes_ports() {
+    let port = TcpListener::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0))
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config_file = reconnect_config(serde_json::json!("mirror"));
+
+    let (mut test_process, mut intproxy) = Application::RustListenPorts
+        .start_process(
+            vec![("APP_PORTS", &port.to_string())],
+            Some(config_file.path()),
+        )
+        .await;
+
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::Tcp(LayerTcp::PortSubscribe(port))
+    );
+    intproxy
+        .send(DaemonMessage::Tcp(DaemonTcp::SubscribeResult(Ok(port))))
+        .await;
+
+    intproxy.reconnect().await;
+
+    // The intproxy subscribes again on its own.
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::Tcp(LayerTcp::PortSubscribe(port))
+    );
+    intproxy
+        .send(DaemonMessage::Tcp(DaemonTcp::SubscribeResult(Ok(port))))
+        .await;
+
+    intproxy.send_connection_then_data("HELLO", port).await;
+
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::Tcp(LayerTcp::PortUnsubscribe(port))
+    );
+
+    test_process.wait_assert_success().await;
+}
+
+/// Verifies that outgoing connections open when the agent connection drops are reset, so that the
+/// application does not mistake the drop for a clean shutdown of the remote peer.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(30))]
+async fn reconnect_resets_outgoing_connections() {
+    let config_file = reconnect_config(serde_json::json!("mirror"));
+
+    let (mut test_process, mut intproxy) = Application::RustOutgoingTcp {
+        non_blocking: false,
+    }
+    .start_process(vec![], Some(config_file.path()))
+    .await;
+
+    let (uid, addr) = intproxy.recv_tcp_connect().await;
+    intproxy
+        .send_tcp_connect_ok(uid, 0, addr, RUST_OUTGOING_LOCAL.parse().unwrap())
+        .await;
+    assert_matches!(
+        intproxy.recv().await,
+        ClientMessage::TcpOutgoing(LayerTcpOutgoing::Write(LayerWrite {
+            connection_id: 0,
+            ..
+        }))
+    );
+
+    // The application is now waiting for the response.
+    intproxy.reconnect().await;
+
+    test_process.wait_assert_fail().await;
+    test_process.assert_stderr_contains("ConnectionReset").await;
+}
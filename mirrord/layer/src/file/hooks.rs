@@ -1233,6 +1233,151 @@ pub(crate) unsafe extern "C" fn statfs64_detour(
     }
 }
 
+/// Reads the name of an extended attribute passed to the `*xattr` functions.
+#[cfg(target_os = "linux")]
+unsafe fn xattr_name(name: *const c_char) -> Detour<Vec<u8>> {
+    if name.is_null() {
+        return Detour::Error(HookError::BadPointer);
+    }
+
+    Detour::Success(
+        unsafe { std::ffi::CStr::from_ptr(name) }
+            .to_bytes()
+            .to_vec(),
+    )
+}
+
+/// Copies the result of a `*getxattr` or `*listxattr` call into the user's buffer.
+///
+/// When `size` is 0, only returns the size of the result, so the caller can allocate a big enough
+/// buffer.
+#[cfg(target_os = "linux")]
+unsafe fn copy_xattr(result: &[u8], buffer: *mut c_void, size: size_t) -> Detour<ssize_t> {
+    if size > 0 {
+        if result.len() > size {
+            return Detour::Error(HookError::IO(std::io::Error::from_raw_os_error(
+                libc::ERANGE,
+            )));
+        }
+
+        if buffer.is_null() {
+            return Detour::Error(HookError::BadPointer);
+        }
+
+        unsafe { ptr::copy_nonoverlapping(result.as_ptr(), buffer.cast(), result.len()) };
+    }
+
+    Detour::Success(ssize_t::try_from(result.len()).unwrap())
+}
+
+/// Hook for `libc::getxattr`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn getxattr_detour(
+    raw_path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        xattr_name(name)
+            .and_then(|name| getxattr(Some(raw_path.checked_into()), None, true, name))
+            .and_then(|result| copy_xattr(&result, value, size))
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_GETXATTR(raw_path, name, value, size)
+            })
+    }
+}
+
+/// Hook for `libc::lgetxattr`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn lgetxattr_detour(
+    raw_path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        xattr_name(name)
+            .and_then(|name| getxattr(Some(raw_path.checked_into()), None, false, name))
+            .and_then(|result| copy_xattr(&result, value, size))
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_LGETXATTR(raw_path, name, value, size)
+            })
+    }
+}
+
+/// Hook for `libc::fgetxattr`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn fgetxattr_detour(
+    fd: c_int,
+    name: *const c_char,
+    value: *mut c_void,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        xattr_name(name)
+            .and_then(|name| getxattr(None, Some(fd), true, name))
+            .and_then(|result| copy_xattr(&result, value, size))
+            .unwrap_or_bypass_with(|_| FN_FGETXATTR(fd, name, value, size))
+    }
+}
+
+/// Hook for `libc::listxattr`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn listxattr_detour(
+    raw_path: *const c_char,
+    list: *mut c_char,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        listxattr(Some(raw_path.checked_into()), None, true)
+            .and_then(|result| copy_xattr(&result, list.cast(), size))
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_LISTXATTR(raw_path, list, size)
+            })
+    }
+}
+
+/// Hook for `libc::llistxattr`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn llistxattr_detour(
+    raw_path: *const c_char,
+    list: *mut c_char,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        listxattr(Some(raw_path.checked_into()), None, false)
+            .and_then(|result| copy_xattr(&result, list.cast(), size))
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_LLISTXATTR(raw_path, list, size)
+            })
+    }
+}
+
+/// Hook for `libc::flistxattr`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn flistxattr_detour(
+    fd: c_int,
+    list: *mut c_char,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        listxattr(None, Some(fd), true)
+            .and_then(|result| copy_xattr(&result, list.cast(), size))
+            .unwrap_or_bypass_with(|_| FN_FLISTXATTR(fd, list, size))
+    }
+}
+
 unsafe fn realpath_logic(
     source_path: *const c_char,
     output_path: *mut c_char,
@@ -1746,6 +1891,48 @@ pub(crate) unsafe fn enable_file_hooks(hook_manager: &mut HookManager, state: &L
                 FnStatfs64,
                 FN_STATFS64
             );
+            replace!(
+                hook_manager,
+                "getxattr",
+                getxattr_detour,
+                FnGetxattr,
+                FN_GETXATTR
+            );
+            replace!(
+                hook_manager,
+                "lgetxattr",
+                lgetxattr_detour,
+                FnLgetxattr,
+                FN_LGETXATTR
+            );
+            replace!(
+                hook_manager,
+                "fgetxattr",
+                fgetxattr_detour,
+                FnFgetxattr,
+                FN_FGETXATTR
+            );
+            replace!(
+                hook_manager,
+                "listxattr",
+                listxattr_detour,
+                FnListxattr,
+                FN_LISTXATTR
+            );
+            replace!(
+                hook_manager,
+                "llistxattr",
+                llistxattr_detour,
+                FnLlistxattr,
+                FN_LLISTXATTR
+            );
+            replace!(
+                hook_manager,
+                "flistxattr",
+                flistxattr_detour,
+                FnFlistxattr,
+                FN_FLISTXATTR
+            );
         }
 
         #[cfg(not(all(target_os = "macos", target_arch = "x86_64")))]
@@ -16,7 +16,7 @@ use std::{
 use libc::{AT_FDCWD, c_int, iovec};
 #[cfg(target_os = "linux")]
 use libc::{c_char, statx, statx_timestamp};
-use mirrord_config::feature::fs::FsModeConfig;
+use mirrord_config::feature::fs::{FsModeConfig, FsRuleMode};
 use mirrord_layer_lib::{
     detour::{Bypass, Detour},
     error::{HookError, HookResult as Result},
@@ -25,11 +25,12 @@ use mirrord_layer_lib::{
 use mirrord_protocol::{
     Payload, ResponseError,
     file::{
-        FchmodRequest, FchownRequest, FtruncateRequest, FutimensRequest, MakeDirAtRequest,
-        MakeDirRequest, OpenFileRequest, OpenFileResponse, OpenOptionsInternal, ReadFileResponse,
-        ReadLinkFileRequest, ReadLinkFileResponse, RemoveDirRequest, RenameRequest,
-        SeekFileResponse, StatFsRequestV2, Timespec, UnlinkAtRequest, UnlinkRequest,
-        WriteFileResponse, XstatFsRequestV2, XstatFsResponseV2, XstatResponse,
+        FchmodRequest, FchownRequest, FtruncateRequest, FutimensRequest, GetXattrRequest,
+        ListXattrRequest, MakeDirAtRequest, MakeDirRequest, OpenFileRequest, OpenFileResponse,
+        OpenOptionsInternal, ReadFileResponse, ReadLinkFileRequest, ReadLinkFileResponse,
+        RemoveDirRequest, RenameRequest, SeekFileResponse, StatFsRequestV2, Timespec,
+        UnlinkAtRequest, UnlinkRequest, WriteFileResponse, XstatFsRequestV2, XstatFsResponseV2,
+        XstatResponse,
     },
 };
 use nix::errno::Errno;
@@ -68,49 +69,60 @@ impl PathExt for Path {
 }
 
 /// Checks whether the given [`Path`] should be accessed remotely.
+///
+/// The first matching rule of the `fs` config wins, see [`FileFilter::rule`].
 pub fn ensure_remote(file_filter: &FileFilter, path: &Path, write: bool) -> Detour<()> {
     // TODO(gabriela): rewrite this using `FileFilter::check`!
 
     let text = path.to_str().unwrap_or_default();
 
-    match file_filter.mode {
-        FsModeConfig::Local => Detour::Bypass(Bypass::ignored_file(text)),
-        _ if file_filter.not_found.is_match(text) => {
-            Detour::Error(HookError::FileNotFound(text.to_string()))
-        }
-        _ if file_filter.read_write.is_match(text) => Detour::Success(()),
-        _ if file_filter.read_only.is_match(text) => {
-            if write {
-                Detour::Bypass(Bypass::ignored_file(text))
-            } else {
-                Detour::Success(())
-            }
-        }
-        _ if file_filter.local.is_match(text) => Detour::Bypass(Bypass::ignored_file(text)),
+    let rule = match file_filter.mode {
+        FsModeConfig::Local => None,
+        _ => file_filter.rule(text),
+    };
+
+    match (file_filter.mode, rule) {
+        (FsModeConfig::Local, _) => Detour::Bypass(Bypass::ignored_file(text)),
+        (_, Some(FsRuleMode::NotFound)) => Detour::Error(HookError::FileNotFound(text.to_string())),
+        (_, Some(FsRuleMode::Write)) => Detour::Success(()),
+        (_, Some(FsRuleMode::Read)) if write => Detour::Bypass(Bypass::ignored_file(text)),
+        (_, Some(FsRuleMode::Read)) => Detour::Success(()),
+        (_, Some(FsRuleMode::Local)) => Detour::Bypass(Bypass::ignored_file(text)),
         _ if file_filter.default_not_found.is_match(text) => {
             Detour::Error(HookError::FileNotFound(text.to_string()))
         }
         _ if file_filter.default_remote_ro.is_match(text) && !write => Detour::Success(()),
         _ if file_filter.default_local.is_match(text) => Detour::Bypass(Bypass::ignored_file(text)),
-        FsModeConfig::LocalWithOverrides => Detour::Bypass(Bypass::ignored_file(text)),
-        FsModeConfig::Write => Detour::Success(()),
-        FsModeConfig::Read if write => Detour::Bypass(Bypass::ReadOnly(text.into())),
-        FsModeConfig::Read => Detour::Success(()),
+        (FsModeConfig::LocalWithOverrides, None) => Detour::Bypass(Bypass::ignored_file(text)),
+        (FsModeConfig::Write, None) => Detour::Success(()),
+        (FsModeConfig::Read, None) if write => Detour::Bypass(Bypass::ReadOnly(text.into())),
+        (FsModeConfig::Read, None) => Detour::Success(()),
     }
 }
 
+/// Remaps the absolute `path` according to the `fs.mapping` config.
+///
+/// Bypasses if the path is mapped to a relative path, which is then accessed locally (relative to
+/// the working directory of the user application).
+fn remap_path(path: PathBuf) -> Detour<PathBuf> {
+    let path = crate::setup().file_remapper().change_path(path);
+    path.ensure_not_relative_or_not_found()?;
+
+    Detour::Success(path)
+}
+
 /// Performs standard verification of paths accessed by the user application.
 ///
 /// Operations in order:
-/// 1. Bypass if the path is not relative and not present in the `fs.not_found` filters.
-/// 2. Remap the file according to the config.
+/// 1. Bypass if the path is relative and not present in the `fs.not_found` filters.
+/// 2. Remap the file according to the config, bypass if it's remapped to a relative path.
 /// 3. Bypass if the new path should be accessed locally.
 ///
 /// Returns the remapped path.
 fn common_path_check(path: PathBuf, write: bool) -> Detour<PathBuf> {
     path.ensure_not_relative_or_not_found()?;
 
-    let path = crate::setup().file_remapper().change_path(path);
+    let path = remap_path(path)?;
     ensure_remote(crate::setup().file_filter(), &path, write)?;
     Detour::Success(path)
 }
@@ -467,7 +479,7 @@ pub(crate) fn unlinkat(dirfd: RawFd, path: Detour<PathBuf>, flags: u32) -> Detou
     }
 
     if path.is_absolute() {
-        path = crate::setup().file_remapper().change_path(path);
+        path = remap_path(path)?;
         ensure_remote(crate::setup().file_filter(), &path, true)?;
     }
 
@@ -602,7 +614,7 @@ pub(crate) fn xstat(
                     path = common_path_check(path, false)?;
                     None
                 } else if path.is_absolute() {
-                    path = crate::setup().file_remapper().change_path(path);
+                    path = remap_path(path)?;
                     ensure_remote(crate::setup().file_filter(), &path, true)?;
                     None
                 } else {
@@ -801,6 +813,81 @@ pub(crate) fn statfs(path: Detour<PathBuf>) -> Detour<XstatFsResponseV2> {
     Detour::Success(response)
 }
 
+/// Returns the remote path or fd that an extended attributes operation refers to.
+#[cfg(target_os = "linux")]
+fn xattr_target(
+    rawish_path: Option<Detour<PathBuf>>,
+    fd: Option<RawFd>,
+) -> Detour<(Option<PathBuf>, Option<u64>)> {
+    match (rawish_path, fd) {
+        (Some(path), None) => Detour::Success((Some(common_path_check(path?, false)?), None)),
+        (None, Some(fd)) => Detour::Success((None, Some(get_remote_fd(fd)?))),
+        _ => Detour::Error(HookError::NullPointer),
+    }
+}
+
+/// Reads the whole value of the extended attribute `name`, for `getxattr`, `lgetxattr` and
+/// `fgetxattr`.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(crate) fn getxattr(
+    rawish_path: Option<Detour<PathBuf>>,
+    fd: Option<RawFd>,
+    follow_symlink: bool,
+    name: Vec<u8>,
+) -> Detour<Payload> {
+    let (path, fd) = xattr_target(rawish_path, fd)?;
+
+    let request = GetXattrRequest {
+        path,
+        fd,
+        follow_symlink,
+        name,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(request)? {
+        Ok(response) => Detour::Success(response.value),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// Lists the names of the extended attributes, for `listxattr`, `llistxattr` and `flistxattr`.
+///
+/// Returns the names in the `libc` format, each followed by a null terminator.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(crate) fn listxattr(
+    rawish_path: Option<Detour<PathBuf>>,
+    fd: Option<RawFd>,
+    follow_symlink: bool,
+) -> Detour<Vec<u8>> {
+    let (path, fd) = xattr_target(rawish_path, fd)?;
+
+    let request = ListXattrRequest {
+        path,
+        fd,
+        follow_symlink,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(request)? {
+        Ok(response) => Detour::Success(
+            response
+                .names
+                .into_iter()
+                .flat_map(|mut name| {
+                    name.push(0);
+                    name
+                })
+                .collect(),
+        ),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
 #[cfg(target_os = "linux")]
 #[mirrord_layer_macro::instrument(level = "trace")]
 pub(crate) fn getdents64(fd: RawFd, buffer_size: u64) -> Detour<GetDEnts64Response> {
@@ -920,7 +1007,10 @@ pub(crate) fn fchmod(fd: RawFd, mode: u32) -> Detour<()> {
 mod test {
     use std::path::PathBuf;
 
-    use mirrord_config::{feature::fs::FsConfig, util::VecOrSingle};
+    use mirrord_config::{
+        feature::fs::{FsConfig, FsRule, READONLY_FILE_BUFFER_DEFAULT},
+        util::VecOrSingle,
+    };
     use mirrord_layer_lib::detour::Detour;
     use rstest::*;
 
@@ -1157,8 +1247,6 @@ mod test {
         #[case] write: bool,
         #[case] expected: DetourKind,
     ) {
-        use mirrord_config::feature::fs::READONLY_FILE_BUFFER_DEFAULT;
-
         let read_write = Some(VecOrSingle::Multiple(vec![
             r"/pain/read_write.*\.a".to_string(),
         ]));
@@ -1168,6 +1256,7 @@ mod test {
         let local = Some(VecOrSingle::Multiple(vec![r"/pain/local.*\.a".to_string()]));
         let not_found = Some(VecOrSingle::Single(r"/pain/not_found.*\.a".to_string()));
         let fs_config = FsConfig {
+            rules: vec![],
             read_write,
             read_only,
             local,
@@ -1180,7 +1269,6 @@ mod test {
         let file_filter = FileFilter::new(fs_config);
 
         let res = ensure_remote(&file_filter, Path::new(path), write);
-        println!("filter result: {res:?}");
         assert_eq!(DetourKind::from(&res), expected);
     }
 
@@ -1207,8 +1295,6 @@ mod test {
         #[case] write: bool,
         #[case] expected: DetourKind,
     ) {
-        use mirrord_config::feature::fs::READONLY_FILE_BUFFER_DEFAULT;
-
         let fs_config = FsConfig {
             mode,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
@@ -1218,8 +1304,38 @@ mod test {
         let file_filter = FileFilter::new(fs_config);
 
         let res = ensure_remote(&file_filter, Path::new(path), write);
-        println!("filter result: {res:?}");
+        assert_eq!(DetourKind::from(&res), expected);
+    }
+
+    /// The first matching rule wins, and the rules come before the legacy lists.
+    #[rstest]
+    #[case("/var/app/config.yaml", false, DetourKind::Success)]
+    #[case("/var/app/config.yaml", true, DetourKind::Bypass)]
+    #[case("/var/app/uploads/image.png", true, DetourKind::Success)]
+    #[case("/var/app/uploads/tmp/image.png", true, DetourKind::Bypass)]
+    #[case("/tmp/cache", false, DetourKind::Bypass)]
+    #[case("/home/user/app/.venv/lib/site.py", false, DetourKind::Bypass)]
+    #[case("/etc/app/optional.yaml", false, DetourKind::Error)]
+    #[case("/etc/app/legacy.yaml", false, DetourKind::Bypass)]
+    fn rules_order(#[case] path: &str, #[case] write: bool, #[case] expected: DetourKind) {
+        let fs_config = FsConfig {
+            mode: FsModeConfig::Read,
+            rules: vec![
+                FsRule::new("^/var/app/uploads/tmp(/|$)", FsRuleMode::Local),
+                FsRule::new("^/var/app/uploads(/|$)", FsRuleMode::Write),
+                FsRule::new("^/tmp(/|$)", FsRuleMode::Local),
+                FsRule::new(r"/\.venv(/|$)", FsRuleMode::Local),
+                FsRule::new(r"^/etc/app/optional\.yaml$", FsRuleMode::NotFound),
+                FsRule::new(r"^/etc/app/legacy\.yaml$", FsRuleMode::Local),
+            ],
+            not_found: Some(VecOrSingle::Single(r"^/etc/app/.*\.yaml$".to_string())),
+            readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            ..Default::default()
+        };
 
+        let file_filter = FileFilter::new(fs_config);
+
+        let res = ensure_remote(&file_filter, Path::new(path), write);
         assert_eq!(DetourKind::from(&res), expected);
     }
 
@@ -1242,8 +1358,6 @@ mod test {
     fn not_found_set(#[case] path: &str, #[case] expected: DetourKind) {
         let filter = FileFilter::new(Default::default());
         let res = ensure_remote(&filter, Path::new(path), false);
-        println!("filter result: {res:?}");
-
         assert_eq!(DetourKind::from(&res), expected);
     }
 }
@@ -73,7 +73,7 @@ extern crate core;
 use std::ffi::c_void;
 use std::{
     cmp::Ordering,
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     fs::File,
     io::Read,
     net::SocketAddr,
@@ -96,7 +96,8 @@ use load::ExecuteArgs;
 #[cfg(doc)]
 use mirrord_config::feature::fs::FsConfig;
 use mirrord_config::{
-    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR, feature::env::mapper::EnvVarsRemapper,
+    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR,
+    feature::env::{filter::EnvVarsFilter, mapper::EnvVarsRemapper},
 };
 use mirrord_intproxy_protocol::NewSessionRequest;
 #[cfg(doc)]
@@ -111,7 +112,7 @@ use mirrord_layer_lib::{
     trace_only::is_trace_only_mode,
 };
 use mirrord_layer_macro::{hook_fn, hook_guard_fn};
-use mirrord_protocol::{EnvVars, GetEnvVarsRequest};
+use mirrord_protocol::GetEnvVarsRequest;
 use nix::errno::Errno;
 use socket::SOCKETS;
 
@@ -438,35 +439,19 @@ const REMOTE_ENV_FETCHED: &str = "MIRRORD_REMOTE_ENV_FETCHED";
 /// Fetches remote environment from the agent.
 /// Uses [`SETUP`] and [`PROXY_CONNECTION`] globals.
 fn fetch_env_vars() -> HashMap<String, String> {
-    let (env_vars_exclude, env_vars_include) = match (
-        setup()
-            .env_config()
-            .exclude
-            .clone()
-            .map(|exclude| exclude.join(";")),
-        setup()
-            .env_config()
-            .include
-            .clone()
-            .map(|include| include.join(";")),
-    ) {
-        (Some(..), Some(..)) => {
-            panic!("invalid env config");
-        }
-        (Some(exclude), None) => (HashSet::from(EnvVars(exclude)), HashSet::new()),
-        (None, Some(include)) => (HashSet::new(), HashSet::from(EnvVars(include))),
-        (None, None) => (HashSet::new(), HashSet::from(EnvVars("*".to_owned()))),
-    };
+    let env_vars_filter = EnvVarsFilter::new(setup().env_config())
+        .expect("invalid env filters, should've been caught when verifying config!");
+    let (env_vars_exclude, env_vars_include) = env_vars_filter.remote_selectors();
 
     let mut env_vars = if !env_vars_exclude.is_empty() || !env_vars_include.is_empty() {
-        {
-            make_proxy_request_with_response(GetEnvVarsRequest {
-                env_vars_filter: env_vars_exclude,
-                env_vars_select: env_vars_include,
-            })
-            .expect("failed to make request to proxy")
-            .expect("failed to fetch remote env")
-        }
+        let remote_env = make_proxy_request_with_response(GetEnvVarsRequest {
+            env_vars_filter: env_vars_exclude,
+            env_vars_select: env_vars_include,
+        })
+        .expect("failed to make request to proxy")
+        .expect("failed to fetch remote env");
+
+        env_vars_filter.filter(remote_env)
     } else {
         Default::default()
     };
@@ -532,8 +517,9 @@ fn sip_only_layer_start(
 /// - `enabled_file_ops`: replaces [`libc`] file-ish calls with our own from [`file::hooks`], see
 ///   `FsConfig::is_active`, and [`hooks::enable_file_hooks`](file::hooks::enable_file_hooks);
 ///
-/// - `enabled_remote_dns`: replaces [`libc::getaddrinfo`] and [`libc::freeaddrinfo`] when this is
-///   `true`, see [`NetworkConfig`](mirrord_config::feature::network::NetworkConfig), and
+/// - `enabled_remote_dns`: replaces [`libc::getaddrinfo`], [`libc::freeaddrinfo`] and
+///   [`libc::getnameinfo`] when this is `true`, see
+///   [`NetworkConfig`](mirrord_config::feature::network::NetworkConfig), and
 ///   [`hooks::enable_socket_hooks`](socket::hooks::enable_socket_hooks).
 #[mirrord_layer_macro::instrument(level = tracing::Level::TRACE)]
 fn enable_hooks(state: &LayerSetup) {
@@ -1,6 +1,6 @@
 use alloc::ffi::CString;
 use core::{cmp, ffi::CStr};
-use std::{collections::HashSet, os::unix::io::RawFd, sync::LazyLock};
+use std::{collections::HashSet, net::SocketAddr, os::unix::io::RawFd, sync::LazyLock};
 
 use libc::{c_char, c_int, c_void, hostent, size_t, sockaddr, socklen_t, ssize_t};
 #[cfg(target_os = "macos")]
@@ -11,7 +11,7 @@ use mirrord_layer_lib::socket::apple_dnsinfo::*;
 use mirrord_layer_lib::{
     detour::{Detour, DetourGuard},
     mutex::Mutex,
-    socket::ops::socket,
+    socket::{SocketAddrExt, dns::reverse_dns::update_dns_query_names, ops::socket},
 };
 use mirrord_layer_macro::{hook_fn, hook_guard_fn};
 use nix::errno::Errno;
@@ -154,6 +154,63 @@ unsafe extern "C" fn gethostbyname_detour(raw_name: *const c_char) -> *mut hoste
     }
 }
 
+/// Hook for `libc::getnameinfo`.
+///
+/// Answers reverse lookups of addresses resolved through the remote pod with the name they were
+/// resolved from, see [`ops::getnameinfo`](super::ops::getnameinfo). The service is always filled
+/// by the original function.
+#[hook_guard_fn]
+unsafe extern "C" fn getnameinfo_detour(
+    raw_address: *const sockaddr,
+    address_length: socklen_t,
+    host: *mut c_char,
+    host_length: socklen_t,
+    service: *mut c_char,
+    service_length: socklen_t,
+    flags: c_int,
+) -> c_int {
+    unsafe {
+        let host_requested = !host.is_null() && host_length > 0;
+
+        getnameinfo(raw_address, address_length, host_requested, flags)
+            .map(|name| {
+                let name = name.as_bytes_with_nul();
+                if name.len() > host_length as usize {
+                    return libc::EAI_OVERFLOW;
+                }
+
+                if !service.is_null() && service_length > 0 {
+                    let result = FN_GETNAMEINFO(
+                        raw_address,
+                        address_length,
+                        std::ptr::null_mut(),
+                        0,
+                        service,
+                        service_length,
+                        flags,
+                    );
+                    if result != 0 {
+                        return result;
+                    }
+                }
+
+                host.copy_from_nonoverlapping(name.as_ptr().cast(), name.len());
+                0
+            })
+            .unwrap_or_bypass_with(|_| {
+                FN_GETNAMEINFO(
+                    raw_address,
+                    address_length,
+                    host,
+                    host_length,
+                    service,
+                    service_length,
+                    flags,
+                )
+            })
+    }
+}
+
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn accept_detour(
     sockfd: c_int,
@@ -351,11 +408,40 @@ unsafe extern "C" fn getaddrinfo_detour(
                 0
             })
             .unwrap_or_bypass_with(|_| {
-                FN_GETADDRINFO(raw_node, raw_service, raw_hints, out_addr_info)
+                let result = FN_GETADDRINFO(raw_node, raw_service, raw_hints, out_addr_info);
+                if result == 0
+                    && let Some(node) = rawish_node
+                {
+                    record_local_addrinfo(node, *out_addr_info);
+                }
+                result
             })
     }
 }
 
+/// Records the addresses resolved locally for `node` in
+/// [`DNS_QUERY_NAMES`](mirrord_layer_lib::socket::dns::reverse_dns::DNS_QUERY_NAMES), so that
+/// the outgoing filter can match them by name.
+///
+/// # Safety
+///
+/// `addr_info` must be a list returned from the original `getaddrinfo`.
+unsafe fn record_local_addrinfo(node: &CStr, mut addr_info: *const libc::addrinfo) {
+    let Ok(node) = node.to_str() else {
+        return;
+    };
+
+    let mut ips = vec![];
+    while let Some(info) = unsafe { addr_info.as_ref() } {
+        if let Detour::Success(address) = SocketAddr::try_from_raw(info.ai_addr, info.ai_addrlen) {
+            ips.push(address.ip());
+        }
+        addr_info = info.ai_next;
+    }
+
+    update_dns_query_names(node, ips);
+}
+
 /// Deallocates a `*mut libc::addrinfo` that was previously allocated with `Box::new` in
 /// `getaddrinfo_detour` and converted into a raw pointer by `Box::into_raw`. Same thing must also
 /// be done for `addrinfo.ai_addr`.
@@ -825,6 +911,14 @@ pub(crate) unsafe fn enable_socket_hooks(
                 FnFreeaddrinfo,
                 FN_FREEADDRINFO
             );
+
+            replace!(
+                hook_manager,
+                "getnameinfo",
+                getnameinfo_detour,
+                FnGetnameinfo,
+                FN_GETNAMEINFO
+            );
             #[cfg(target_os = "macos")]
             {
                 replace!(
@@ -33,7 +33,10 @@ use mirrord_layer_lib::{
     socket::{
         Bound, Connected, SocketAddrExt, SocketKind, SocketState,
         dns::{remote_getaddrinfo, unix::getaddrinfo as getaddrinfo_lib},
-        ops::{ConnectResult, connect_common, connect_outgoing_common, nop_connect_fn},
+        ops::{
+            ConnectResult, connect_common, connect_outgoing_common, nop_connect_fn,
+            udp_peer_address,
+        },
     },
 };
 use mirrord_protocol::{
@@ -162,7 +165,9 @@ fn is_ignored_tcp_port(addr: &SocketAddr, config: &IncomingConfig) -> bool {
         .as_ref()
         .is_some_and(|ports| ports.contains(&mapped_port).not());
 
-    is_ignored_port(addr) || have_whitelist_and_port_is_not_whitelisted
+    is_ignored_port(addr)
+        || have_whitelist_and_port_is_not_whitelisted
+        || config.port_mode(mapped_port).is_off()
 }
 
 /// If the socket is not found in [`SOCKETS`], bypass.
@@ -874,6 +879,31 @@ pub(super) fn gethostbyname(raw_name: Option<&CStr>) -> Detour<*mut hostent> {
     Detour::Success(std::ptr::addr_of!(GETHOSTBYNAME_HOSTENT) as _)
 }
 
+/// Answers the reverse lookup of the `address`, when it was resolved through the remote pod and
+/// the [`DnsSelector`](mirrord_layer_lib::socket::dns_selector::DnsSelector) sends the query for
+/// its name to the remote pod too.
+///
+/// Lookups that don't ask for the host name, or ask for the numeric host
+/// ([`libc::NI_NUMERICHOST`]), are bypassed.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(super) fn getnameinfo(
+    raw_address: *const sockaddr,
+    address_length: socklen_t,
+    host_requested: bool,
+    flags: c_int,
+) -> Detour<CString> {
+    if host_requested.not() || flags & libc::NI_NUMERICHOST != 0 {
+        return Detour::Bypass(Bypass::LocalDns);
+    }
+
+    let address = SocketAddr::try_from_raw(raw_address, address_length)?;
+    let name = crate::setup()
+        .dns_selector()
+        .check_reverse_query(address.ip())?;
+
+    Detour::Success(CString::new(name)?)
+}
+
 /// Resolve hostname from remote host with caching for the result
 #[mirrord_layer_macro::instrument(level = "trace")]
 pub(super) fn gethostname() -> Detour<&'static CString> {
@@ -934,6 +964,10 @@ pub(super) fn read_remote_resolv_conf() -> Detour<Vec<u8>> {
 /// When the socket is in a [`Connected`] state, we call [`fill_address`] with its `remote_address`,
 /// instead of letting whatever came in `raw_source` through.
 ///
+/// When the packet came from the interceptor of one of the socket's
+/// [`UdpPeer`](mirrord_layer_lib::socket::UdpPeer)s, we call [`fill_address`] with the peer's
+/// `remote_address`.
+///
 /// See [`send_to`] for more information.
 #[mirrord_layer_macro::instrument(level = "trace", ret, skip(raw_source, source_length))]
 pub(super) fn recv_from(
@@ -949,6 +983,22 @@ pub(super) fn recv_from(
             SocketState::Connected(Connected { remote_address, .. }) => {
                 Some(remote_address.clone())
             }
+            // The datagram might have come from the interceptor of one of the socket's peers.
+            SocketState::Bound { .. } | SocketState::Initialized
+                if !raw_source.is_null() && !socket.udp_peers.is_empty() =>
+            {
+                let Detour::Success(source) =
+                    SocketAddr::try_from_raw(raw_source, unsafe { *source_length })
+                else {
+                    return None;
+                };
+
+                socket
+                    .udp_peers
+                    .iter()
+                    .find(|peer| peer.layer_address == source)
+                    .map(|peer| peer.remote_address.into())
+            }
             SocketState::Bound { .. } | SocketState::Initialized | SocketState::Listening(_) => {
                 None
             }
@@ -1030,6 +1080,10 @@ fn send_dns_patch(
 /// If we find `destination` as the `requested_address` of one of our [`Bound`] sockets, then we
 /// [`libc::sendto`] to the bound `address`. A similar logic applies to a [`Connected`] socket.
 ///
+/// Otherwise, when outgoing UDP traffic is enabled and the outgoing filter allows it, the packet is
+/// sent to the cluster, see [`udp_peer_address`]. Each distinct `destination` gets its own
+/// interceptor socket, so [`recv_from`] can tell the peers apart.
+///
 /// ## Destination is `0.0.0.0:{not 53}`
 ///
 /// No special care is taken here, sending a packet to this address behaves the same with or without
@@ -1064,19 +1118,21 @@ pub(super) fn send_to(
         return Detour::Bypass(Bypass::Domain(AF_UNIX));
     }
 
-    // Currently this flow only handles DNS resolution.
-    // So here we have to check for 2 things:
+    // So here we have to check for 3 things:
     //
     // 1. Are we sending something port 53? Then we use mirrord flow;
     // 2. Is the destination a socket that we have bound? Then we send it to the real address that
-    // we've bound the destination socket.
+    // we've bound the destination socket;
+    // 3. Should the destination be reached through the cluster? Then we send it to the interceptor
+    // of the respective `UdpPeer`.
     //
     // If none of the above are true, then the destination is some real address outside our scope.
     let sent_result = if let Some(destination) = destination
         .as_socket()
         .filter(|destination| destination.port() != 53)
     {
-        let rawish_true_destination = send_dns_patch(sockfd, user_socket_info, destination)?;
+        let rawish_true_destination = send_dns_patch(sockfd, user_socket_info, destination)
+            .or_bypass(|_| udp_peer_address(sockfd, destination))?;
 
         unsafe {
             FN_SEND_TO(
@@ -1157,19 +1213,21 @@ pub(super) fn sendmsg(
         return Detour::Bypass(Bypass::Domain(AF_UNIX));
     }
 
-    // Currently this flow only handles DNS resolution.
-    // So here we have to check for 2 things:
+    // So here we have to check for 3 things:
     //
     // 1. Are we sending something port 53? Then we use mirrord flow;
     // 2. Is the destination a socket that we have bound? Then we send it to the real address that
-    // we've bound the destination socket.
+    // we've bound the destination socket;
+    // 3. Should the destination be reached through the cluster? Then we send it to the interceptor
+    // of the respective `UdpPeer`.
     //
     // If none of the above are true, then the destination is some real address outside our scope.
     let sent_result = if let Some(destination) = destination
         .as_socket()
         .filter(|destination| destination.port() != 53)
     {
-        let rawish_true_destination = send_dns_patch(sockfd, user_socket_info, destination)?;
+        let rawish_true_destination = send_dns_patch(sockfd, user_socket_info, destination)
+            .or_bypass(|_| udp_peer_address(sockfd, destination))?;
 
         let mut true_message_header = Box::new(unsafe { *raw_message_header });
 
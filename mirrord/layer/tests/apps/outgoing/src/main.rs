@@ -1,6 +1,7 @@
 #![warn(clippy::indexing_slicing)]
 
 use std::{
+    collections::HashMap,
     env,
     io::{Read, Write},
     net::{SocketAddr, TcpStream, UdpSocket},
@@ -13,8 +14,12 @@ use tokio::{
 
 const MESSAGE: &[u8] = "FOO BAR HAM".as_bytes();
 
+/// Sent after [`MESSAGE`] to every peer by [`test_udp_unconnected`].
+const SECOND_MESSAGE: &[u8] = "SPAM".as_bytes();
+
 struct Args {
     tcp: bool,
+    unconnected: bool,
     expected_local_addr: SocketAddr,
     peers: Vec<SocketAddr>,
     non_blocking: bool,
@@ -23,9 +28,10 @@ struct Args {
 fn parse_args() -> Option<Args> {
     let args = env::args().collect::<Vec<_>>();
 
-    let tcp = match args.get(1)?.as_str() {
-        "--tcp" => true,
-        "--udp" => false,
+    let (tcp, unconnected) = match args.get(1)?.as_str() {
+        "--tcp" => (true, false),
+        "--udp" => (false, false),
+        "--udp-unconnected" => (false, true),
         _ => None?,
     };
     let expected_local_addr = args.get(2)?.parse::<SocketAddr>().ok()?;
@@ -43,6 +49,7 @@ fn parse_args() -> Option<Args> {
 
     Some(Args {
         tcp,
+        unconnected,
         expected_local_addr,
         peers,
         non_blocking,
@@ -107,6 +114,42 @@ fn test_udp(socket: SocketAddr, peer: SocketAddr) {
     }
 }
 
+/// Sends [`MESSAGE`] and [`SECOND_MESSAGE`] to every peer from one unconnected socket, and expects
+/// every peer to send both back, each in its own datagram.
+fn test_udp_unconnected(peers: Vec<SocketAddr>) {
+    let udp_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+
+    for peer in &peers {
+        for message in [MESSAGE, SECOND_MESSAGE] {
+            let sent = udp_socket.send_to(message, peer).unwrap();
+            if sent != message.len() {
+                panic!("Partial send to {peer}: {sent} bytes.");
+            }
+        }
+    }
+
+    let mut responses: HashMap<SocketAddr, Vec<Vec<u8>>> = HashMap::new();
+    for _ in 0..peers.len() * 2 {
+        let mut response = [0; 64];
+        let (res_len, remote) = udp_socket.recv_from(&mut response).unwrap();
+        let response = response
+            .get(..res_len)
+            .expect("returned response length out of bounds");
+        responses.entry(remote).or_default().push(response.to_vec());
+    }
+
+    for peer in peers {
+        match responses.remove(&peer) {
+            Some(received) if received == [MESSAGE, SECOND_MESSAGE] => {}
+            other => panic!("Invalid responses received from {peer}: {other:?}."),
+        }
+    }
+
+    if !responses.is_empty() {
+        panic!("Responses received from unexpected peers: {responses:?}.");
+    }
+}
+
 async fn test_tcp_non_blocking(socket: SocketAddr, peers: Vec<SocketAddr>) {
     let mut tasks = JoinSet::new();
 
@@ -142,11 +185,20 @@ async fn test_tcp_non_blocking(socket: SocketAddr, peers: Vec<SocketAddr>) {
 fn main() {
     let Some(args) = parse_args() else {
         panic!(
-            "USAGE: {} --tcp/--udp <local socket> <peer sockets> [--non-blocking]",
+            "USAGE: {} --tcp/--udp/--udp-unconnected <local socket> <peer sockets> [--non-blocking]",
             env::args().next().unwrap()
         );
     };
 
+    if args.unconnected {
+        if args.non_blocking {
+            panic!("--non-blocking flag is not supported with --udp-unconnected")
+        }
+
+        test_udp_unconnected(args.peers);
+        return;
+    }
+
     match (args.tcp, args.non_blocking) {
         (true, true) => {
             // The runtime **must** be single-threaded, otherwise this app will not verify what it's
@@ -140,6 +140,10 @@ pub enum Application {
     GoFAccessAt(GoVersion),
     GoSelfOpen(GoVersion),
     RustOutgoingUdp,
+    /// Sends datagrams to [`RUST_OUTGOING_PEERS`] and `local_peer` from one unconnected socket.
+    RustOutgoingUdpUnconnected {
+        local_peer: SocketAddr,
+    },
     RustOutgoingTcp {
         non_blocking: bool,
     },
@@ -276,7 +280,9 @@ impl Application {
             Application::RustIssue1458PortNot53 => {
                 String::from("tests/apps/issue1458portnot53/target/issue1458portnot53")
             }
-            Application::RustOutgoingUdp | Application::RustOutgoingTcp { .. } => format!(
+            Application::RustOutgoingUdp
+            | Application::RustOutgoingUdpUnconnected { .. }
+            | Application::RustOutgoingTcp { .. } => format!(
                 "{}/{}",
                 env!("CARGO_MANIFEST_DIR"),
                 "../../target/debug/outgoing",
@@ -516,6 +522,11 @@ impl Application {
                 .into_iter()
                 .map(Into::into)
                 .collect(),
+            Application::RustOutgoingUdpUnconnected { local_peer } => vec![
+                "--udp-unconnected".into(),
+                RUST_OUTGOING_LOCAL.into(),
+                format!("{RUST_OUTGOING_PEERS},{local_peer}"),
+            ],
             Application::RustOutgoingTcp {
                 non_blocking: false,
             } => ["--tcp", RUST_OUTGOING_LOCAL, RUST_OUTGOING_PEERS]
@@ -584,6 +595,7 @@ impl Application {
             | Application::GoSelfOpen(..)
             | Application::GoDir(..)
             | Application::RustOutgoingUdp
+            | Application::RustOutgoingUdpUnconnected { .. }
             | Application::RustOutgoingTcp { .. }
             | Application::RustIssue1458
             | Application::RustIssue1458PortNot53
@@ -83,6 +83,18 @@ fn expected_behavior(port: u16, incoming: &IncomingConfig) -> BindMode {
         return BindMode::Local;
     }
 
+    if incoming.port_mode(port).is_off() {
+        return BindMode::Local;
+    }
+
+    if let Some(http_filter) = incoming.port_http_filters.get(&port) {
+        return if http_filter.is_filter_set() {
+            BindMode::Filtered
+        } else {
+            BindMode::Unfiltered
+        };
+    }
+
     if incoming.http_filter.is_filter_set().not() {
         return BindMode::Unfiltered;
     };
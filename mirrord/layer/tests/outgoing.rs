@@ -2,6 +2,8 @@
 #![warn(clippy::indexing_slicing)]
 
 use std::{
+    collections::HashMap,
+    io::Write,
     net::{Ipv4Addr, SocketAddr},
     path::Path,
     time::Duration,
@@ -20,7 +22,7 @@ use rstest::rstest;
 mod common;
 
 pub use common::*;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, UdpSocket};
 
 // TODO: add a test for when DNS lookup is unsuccessful, to make sure the layer returns a valid
 //      error to the user application.
@@ -88,6 +90,112 @@ async fn outgoing_udp(dylib_path: &Path) {
     test_process.wait_assert_success().await;
 }
 
+/// Test outgoing UDP on an unconnected socket.
+/// Application, from one socket that is never connected:
+/// 1. Sends two datagrams to each remote peer in [`RUST_OUTGOING_PEERS`] and to a local peer
+/// 2. Expects each peer to send the same datagrams back
+/// 3. Expects `recvfrom` to report the peer that each datagram came from
+///
+/// The local peer is excluded with the outgoing filter, so its datagrams must not reach the
+/// cluster.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(15))]
+async fn outgoing_udp_unconnected(dylib_path: &Path) {
+    let local_peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let local_peer_address = local_peer.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buffer = [0; 64];
+        loop {
+            let (len, source) = local_peer.recv_from(&mut buffer).await.unwrap();
+            local_peer
+                .send_to(buffer.get(..len).unwrap(), source)
+                .await
+                .unwrap();
+        }
+    });
+
+    let config = serde_json::json!({
+        "feature": {
+            "network": {
+                "outgoing": {
+                    "filter": {
+                        "local": [local_peer_address.to_string()]
+                    }
+                }
+            }
+        }
+    });
+    let mut config_file = tempfile::NamedTempFile::with_suffix(".json").unwrap();
+    config_file
+        .as_file_mut()
+        .write_all(serde_json::to_string(&config).unwrap().as_bytes())
+        .unwrap();
+
+    let (mut test_process, mut intproxy) = Application::RustOutgoingUdpUnconnected {
+        local_peer: local_peer_address,
+    }
+    .start_process_with_layer(dylib_path, vec![], Some(config_file.path()))
+    .await;
+
+    let peers = RUST_OUTGOING_PEERS
+        .split(',')
+        .map(|s| s.parse::<SocketAddr>().unwrap())
+        .collect::<Vec<_>>();
+    let mut writes = HashMap::<u64, Vec<Vec<u8>>>::new();
+
+    // Every peer gets its own connection, the datagrams are not merged nor split.
+    while writes.values().map(Vec::len).sum::<usize>() < peers.len() * 2 {
+        match intproxy.recv().await {
+            ClientMessage::UdpOutgoing(LayerUdpOutgoing::ConnectV2(LayerConnectV2 {
+                uid,
+                remote_address: SocketAddress::Ip(addr),
+            })) => {
+                let connection_id = writes.len() as u64;
+                assert_eq!(peers.get(connection_id as usize), Some(&addr));
+                writes.insert(connection_id, vec![]);
+                intproxy
+                    .send_udp_connect_ok(
+                        uid,
+                        connection_id,
+                        addr,
+                        RUST_OUTGOING_LOCAL.parse().unwrap(),
+                    )
+                    .await;
+            }
+            ClientMessage::UdpOutgoing(LayerUdpOutgoing::Write(LayerWrite {
+                connection_id,
+                bytes,
+            })) => {
+                writes
+                    .get_mut(&connection_id)
+                    .expect("write before connect")
+                    .push(bytes.to_vec());
+                intproxy
+                    .send(DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::Read(Ok(
+                        DaemonRead {
+                            connection_id,
+                            bytes,
+                        },
+                    ))))
+                    .await;
+            }
+            other => panic!("Invalid message received from layer: {other:?}"),
+        }
+    }
+
+    for (connection_id, datagrams) in writes {
+        assert_eq!(
+            datagrams,
+            [b"FOO BAR HAM".as_slice(), b"SPAM".as_slice()],
+            "invalid datagrams sent to {:?}",
+            peers.get(connection_id as usize),
+        );
+    }
+
+    test_process.wait_assert_success().await;
+}
+
 /// Test outgoing TCP.
 /// Application, for each remote peer in [`RUST_OUTGOING_PEERS`]:
 /// 1. Opens a TCP port at [`RUST_OUTGOING_LOCAL`]
@@ -1,14 +1,14 @@
 use std::{fmt, ops::Not, time::Duration};
 
 use base64::{Engine, engine::general_purpose};
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use connect_params::ConnectParams;
 use error::{OperatorApiError, OperatorApiResult, OperatorOperation};
 use futures::{SinkExt, StreamExt, future::Either};
-use http::{HeaderName, HeaderValue, request::Request};
-use k8s_openapi::api::apps::v1::Deployment;
+use http::{HeaderValue, request::Request};
+use k8s_openapi::api::{apps::v1::Deployment, autoscaling::v2::HorizontalPodAutoscaler};
 use kube::{
-    Api, Client, Config, Resource,
+    Api, Client, Config, Resource, ResourceExt,
     api::{ListParams, PostParams},
     client::ClientBuilder,
 };
@@ -16,7 +16,7 @@ use mirrord_analytics::{AnalyticsHash, AnalyticsOperatorProperties, Reporter};
 use mirrord_auth::{
     certificate::Certificate,
     credential_store::{CredentialStoreSync, UserIdentity},
-    credentials::{CiApiKey, Credentials, LicenseValidity},
+    credentials::{CiApiKey, Credentials},
 };
 use mirrord_config::{
     LayerConfig, feature::database_branches::default_creation_timeout_secs, target::Target,
@@ -57,8 +57,9 @@ use crate::{
         session::SessionCiInfo,
     },
     types::{
-        CLIENT_CERT_HEADER, CLIENT_HOSTNAME_HEADER, CLIENT_NAME_HEADER, MIRRORD_CLI_VERSION_HEADER,
-        SESSION_ID_HEADER,
+        HeaderSet, OperatorHeader, RedactedLicenseInfo, SessionId, TargetNamespace, TraceId,
+        client_cert_expiry_warning, client_cert_not_after, client_platform_value,
+        decode_client_cert, encode_client_cert, header,
     },
 };
 
@@ -66,6 +67,7 @@ pub mod connect_params;
 mod credentials;
 pub mod database_branches;
 mod discovery;
+pub mod dry_run;
 pub mod error;
 mod upgrade;
 
@@ -76,9 +78,11 @@ pub trait ClientCertificateState: fmt::Debug {}
 #[derive(Debug)]
 pub struct NoClientCert {
     /// [`Config::headers`] here contain some extra entries:
-    /// 1. [`CLIENT_HOSTNAME_HEADER`] (if available)
-    /// 2. [`CLIENT_NAME_HEADER`] (if available)
-    /// 3. [`MIRRORD_CLI_VERSION_HEADER`]
+    /// 1. [`CLIENT_HOSTNAME_HEADER`](crate::types::CLIENT_HOSTNAME_HEADER) (if available)
+    /// 2. [`CLIENT_NAME_HEADER`](crate::types::CLIENT_NAME_HEADER) (if available)
+    /// 3. [`MIRRORD_CLI_VERSION_HEADER`](crate::types::MIRRORD_CLI_VERSION_HEADER)
+    /// 4. [`CLIENT_PLATFORM_HEADER`](crate::types::CLIENT_PLATFORM_HEADER) (if available)
+    /// 5. [`MIRRORD_TRACE_ID_HEADER`](crate::types::MIRRORD_TRACE_ID_HEADER)
     ///
     /// Can be used to create a certified [`Client`] when the [`Certificate`] is available.
     base_config: Config,
@@ -128,9 +132,16 @@ impl ClientCertificateState for MaybeClientCert {}
 #[derive(Clone, Serialize, Deserialize)]
 pub struct OperatorSession {
     /// Random session id, generated locally.
-    id: u64,
+    id: SessionId,
     /// URL where websocket connection request should be sent.
     connect_url: String,
+    /// Namespace of the target, sent in the
+    /// [`X_MIRRORD_TARGET_NAMESPACE_HEADER`](crate::types::X_MIRRORD_TARGET_NAMESPACE_HEADER) of
+    /// the websocket connection request.
+    ///
+    /// Missing in sessions serialized by older CLIs.
+    #[serde(default)]
+    target_namespace: Option<TargetNamespace>,
     /// Client certificate, should be included as header in the websocket connection request.
     client_cert: Certificate,
     /// Operator license fingerprint, right now only for setting [`Reporter`] properties.
@@ -154,8 +165,9 @@ impl fmt::Debug for OperatorSession {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut debug_struct = f.debug_struct("OperatorSession");
         debug_struct
-            .field("id", &format!("{:X}", self.id))
+            .field("id", &self.id.to_hex())
             .field("connect_url", &self.connect_url)
+            .field("target_namespace", &self.target_namespace)
             .field("cert_public_key_data", &self.client_cert.public_key_data())
             .field(
                 "operator_license_fingerprint",
@@ -189,8 +201,8 @@ impl fmt::Debug for OperatorSessionConnection {
 pub struct OperatorApi<C> {
     /// For making requests to kubernetes API server.
     client: Client,
-    /// Prepared client certificate. If present, [`Self::client`] sends [`CLIENT_CERT_HEADER`] with
-    /// each request.
+    /// Prepared client certificate. If present, [`Self::client`] sends
+    /// [`CLIENT_CERT_HEADER`](crate::types::CLIENT_CERT_HEADER) with each request.
     client_cert: C,
     /// Fetched operator resource.
     operator: MirrordOperatorCrd,
@@ -336,11 +348,10 @@ impl OperatorApi<NoClientCert> {
 
         let result = try {
             let header = Self::make_client_cert_header(certificate)?;
+            Self::warn_client_cert_expiry(progress, &header);
 
             let mut config = self.client_cert.base_config;
-            config
-                .headers
-                .push((HeaderName::from_static(CLIENT_CERT_HEADER), header));
+            config.headers.push((header::CLIENT_CERT, header));
 
             let client = progress
                 .suspend(|| ClientBuilder::try_from(config))
@@ -374,6 +385,28 @@ impl OperatorApi<NoClientCert> {
         }
     }
 
+    /// Warns the user when the client certificate carried in the `header` is about to expire, so
+    /// that it does not expire mid-session unnoticed, see [`client_cert_expiry_warning`].
+    fn warn_client_cert_expiry<P: Progress>(progress: &P, header: &HeaderValue) {
+        let not_after = header
+            .to_str()
+            .map_err(|error| error.to_string())
+            .and_then(|value| decode_client_cert(value).map_err(|error| error.to_string()))
+            .and_then(|der| client_cert_not_after(&der).map_err(|error| error.to_string()));
+
+        match not_after {
+            Ok(not_after) => {
+                if let Some(warning) = client_cert_expiry_warning(not_after, Utc::now()) {
+                    progress.warning(&warning);
+                    tracing::warn!("{warning}");
+                }
+            }
+            Err(error) => {
+                tracing::debug!(%error, "Failed to read the client certificate expiry");
+            }
+        }
+    }
+
     /// Prepares client [`Certificate`] to be sent in all subsequent requests to the operator.
     /// In case of failure, state of this API instance does not change.
     #[tracing::instrument(level = Level::TRACE, skip(reporter, progress))]
@@ -447,34 +480,33 @@ where
     where
         P: Progress,
     {
-        let Some(days_until_expiration) =
-            self.operator.spec.license.expire_at.days_until_expiration()
-        else {
-            let no_license_message = "No valid license found for mirrord for Teams. Visit https://app.metalbear.com to purchase or renew your license";
-            progress.warning(no_license_message);
-            tracing::warn!(no_license_message);
-
-            return Err(OperatorApiError::NoLicense);
-        };
+        let license = &self.operator.spec.license;
+        let now = Utc::now();
+        tracing::debug!(
+            license = ?RedactedLicenseInfo::from(license),
+            "Checking the operator license"
+        );
 
-        let expires_soon =
-            days_until_expiration <= <DateTime<Utc> as LicenseValidity>::CLOSE_TO_EXPIRATION_DAYS;
-        let is_trial = self.operator.spec.license.name.contains("(Trial)");
+        if let Err(error) = license.check_expiry(now) {
+            let no_license_message = format!("{error}. {}", error.remediation());
+            progress.warning(&no_license_message);
+            tracing::warn!("{no_license_message}");
 
-        if is_trial && expires_soon {
-            let expiring_soon = if days_until_expiration > 0 {
-                format!(
-                    "soon, in {days_until_expiration} day{}",
-                    if days_until_expiration > 1 { "s" } else { "" }
-                )
-            } else {
-                "today".to_string()
-            };
-            let message = format!("Operator license will expire {expiring_soon}!",);
-            progress.warning(&message);
-        } else if is_trial {
-            let message =
-                format!("Operator license is valid for {days_until_expiration} more days.");
+            return Err(error.into());
+        }
+
+        if let Some(warning) = license.newer_version_warning() {
+            progress.warning(&warning);
+            tracing::warn!("{warning}");
+        }
+
+        if let Some(warning) = license.expiry_warning(now.date_naive()) {
+            progress.warning(&warning);
+        } else if let Some(days_left) = license.trial_days_left(now.date_naive()) {
+            let message = format!(
+                "Your mirrord trial is valid for {days_left} more days. Visit \
+                https://app.metalbear.com to upgrade your license."
+            );
             progress.info(&message);
         }
 
@@ -532,9 +564,12 @@ where
 
     /// Creates a base [`Config`] for creating kube [`Client`]s.
     /// Adds extra headers that we send to the operator with each request:
-    /// 1. [`MIRRORD_CLI_VERSION_HEADER`]
-    /// 2. [`CLIENT_NAME_HEADER`]
-    /// 3. [`CLIENT_HOSTNAME_HEADER`]
+    /// 1. [`MIRRORD_CLI_VERSION_HEADER`](crate::types::MIRRORD_CLI_VERSION_HEADER)
+    /// 2. [`CLIENT_NAME_HEADER`](crate::types::CLIENT_NAME_HEADER)
+    /// 3. [`CLIENT_HOSTNAME_HEADER`](crate::types::CLIENT_HOSTNAME_HEADER)
+    /// 4. [`CLIENT_PLATFORM_HEADER`](crate::types::CLIENT_PLATFORM_HEADER)
+    /// 5. [`MIRRORD_TRACE_ID_HEADER`](crate::types::MIRRORD_TRACE_ID_HEADER), generated once per
+    ///    [`OperatorApi`]
     async fn base_client_config(layer_config: &LayerConfig) -> OperatorApiResult<Config> {
         let mut client_config = create_kube_config(
             layer_config.accept_invalid_certificates,
@@ -544,37 +579,25 @@ where
         .await
         .map_err(OperatorApiError::CreateKubeClient)?;
 
-        client_config.headers.push((
-            HeaderName::from_static(MIRRORD_CLI_VERSION_HEADER),
-            HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
-        ));
-
         let UserIdentity { name, hostname } = UserIdentity::load();
-
-        let headers = [
-            (CLIENT_NAME_HEADER, name),
-            (CLIENT_HOSTNAME_HEADER, hostname),
-        ];
-        for (name, raw_value) in headers {
-            let Some(raw_value) = raw_value else {
-                continue;
-            };
-
-            // Replace non-ascii (not supported in headers) chars and trim.
-            let cleaned = raw_value
-                .replace(|c: char| !c.is_ascii(), "")
-                .trim()
-                .to_string();
-            let value = HeaderValue::from_str(&cleaned);
-            match value {
-                Ok(value) => client_config
-                    .headers
-                    .push((HeaderName::from_static(name), value)),
-                Err(error) => {
-                    tracing::debug!(%error, %name, raw_value = raw_value, cleaned, "Invalid header value");
-                }
-            }
-        }
+        let platform = client_platform_value(env!("CARGO_PKG_VERSION"));
+        let trace_id = TraceId::generate();
+        let headers = HeaderSet::new(env!("CARGO_PKG_VERSION"))
+            .name(name.as_deref())
+            .hostname(hostname.as_deref())
+            .platform(platform.as_deref())
+            .trace_id(trace_id)
+            .build();
+        tracing::debug!(
+            %trace_id,
+            headers = ?OperatorHeader::present_in(&headers).collect::<Vec<_>>(),
+            "Attaching headers to operator requests",
+        );
+        client_config.headers.extend(
+            headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
 
         Ok(client_config)
     }
@@ -675,8 +698,7 @@ where
                 "failed to encode client certificate: {error}"
             ))
         })?;
-        let as_base64 = general_purpose::STANDARD.encode(as_der);
-        HeaderValue::try_from(as_base64)
+        HeaderValue::try_from(encode_client_cert(&as_der))
             .map_err(|error| OperatorApiError::ClientCertError(error.to_string()))
     }
 }
@@ -785,6 +807,7 @@ impl OperatorApi<PreparedClientCert> {
             let session = self.make_operator_session(
                 id,
                 connect_url,
+                copied.meta().namespace.as_deref(),
                 layer_config.traceparent.clone(),
                 layer_config.baggage.clone(),
             )?;
@@ -849,6 +872,11 @@ impl OperatorApi<PreparedClientCert> {
             let session = self.make_operator_session(
                 None,
                 connect_url,
+                Some(
+                    target
+                        .namespace()
+                        .unwrap_or(self.client.default_namespace()),
+                ),
                 layer_config.traceparent.clone(),
                 layer_config.baggage.clone(),
             )?;
@@ -887,6 +915,7 @@ impl OperatorApi<PreparedClientCert> {
                 let session = self.make_operator_session(
                     session_id,
                     connect_url,
+                    copied.meta().namespace.as_deref(),
                     layer_config.traceparent.clone(),
                     layer_config.baggage.clone(),
                 )?;
@@ -985,6 +1014,7 @@ impl OperatorApi<PreparedClientCert> {
         let session = self.make_operator_session(
             None,
             connect_url,
+            Some(namespace),
             layer_config.traceparent.clone(),
             layer_config.baggage.clone(),
         )?;
@@ -1131,7 +1161,8 @@ impl OperatorApi<PreparedClientCert> {
         Ok((true, Some("empty deployment")))
     }
 
-    /// Creates a new [`OperatorSession`] with the given `id` and `connect_url`.
+    /// Creates a new [`OperatorSession`] with the given `id`, `connect_url` and
+    /// `target_namespace`.
     ///
     /// If `id` is not passed, a random one is generated.
     #[tracing::instrument(level = Level::DEBUG, err, ret)]
@@ -1139,13 +1170,17 @@ impl OperatorApi<PreparedClientCert> {
         &self,
         id: Option<&str>,
         connect_url: String,
+        target_namespace: Option<&str>,
         traceparent: Option<String>,
         baggage: Option<String>,
     ) -> OperatorApiResult<OperatorSession> {
         let id = id
-            .map(|id| u64::from_str_radix(id, 16))
+            .map(SessionId::from_hex)
             .transpose()?
-            .unwrap_or_else(rand::random);
+            .unwrap_or_else(|| SessionId(rand::random()));
+        let target_namespace = target_namespace
+            .map(str::parse::<TargetNamespace>)
+            .transpose()?;
         let operator_protocol_version = self
             .operator
             .spec
@@ -1162,6 +1197,7 @@ impl OperatorApi<PreparedClientCert> {
         Ok(OperatorSession {
             id,
             connect_url,
+            target_namespace,
             client_cert: self.client_cert.cert.clone(),
             operator_license_fingerprint: self.operator.spec.license.fingerprint.clone(),
             operator_protocol_version,
@@ -1286,6 +1322,7 @@ impl OperatorApi<PreparedClientCert> {
             is_default_cluster: None, // Only used in multi-cluster
             sqs_output_queues: Default::default(), // Only used in multi-cluster
             key: Some(key),
+            ordinal: None,
         };
 
         if use_proxy {
@@ -1349,6 +1386,10 @@ impl OperatorApi<PreparedClientCert> {
             .exclude_init_containers
             .clone();
 
+        if scale_down && !layer_config.feature.copy_target.force_scale_down {
+            self.check_scale_down(&target, namespace, progress).await?;
+        }
+
         let copy_target_api: Api<CopyTargetCrd> = Api::namespaced(self.client.clone(), namespace);
 
         let copy_target_name = TargetCrd::urlfied_name(&target);
@@ -1376,6 +1417,48 @@ impl OperatorApi<PreparedClientCert> {
         self.wait_for_copy_ready(copied, progress).await
     }
 
+    /// Fails with [`OperatorApiError::ScaleDownWithHpa`] if the given [`Target`] is managed by a
+    /// [`HorizontalPodAutoscaler`], which would scale it back up while the copy is alive.
+    ///
+    /// If the user is not allowed to list the autoscalers, only warns that the check was skipped.
+    async fn check_scale_down<P: Progress>(
+        &self,
+        target: &Target,
+        namespace: &str,
+        progress: &P,
+    ) -> OperatorApiResult<()> {
+        let hpa_api: Api<HorizontalPodAutoscaler> = Api::namespaced(self.client.clone(), namespace);
+
+        let autoscalers = match hpa_api.list(&ListParams::default()).await {
+            Ok(autoscalers) => autoscalers.items,
+            Err(kube::Error::Api(response)) if response.code == 403 => {
+                progress.warning(&format!(
+                    "Could not check whether {target} is managed by a HorizontalPodAutoscaler \
+                    before scaling it down: {}",
+                    response.message
+                ));
+                return Ok(());
+            }
+            Err(error) => {
+                return Err(OperatorApiError::KubeError {
+                    error,
+                    operation: OperatorOperation::CopyingTarget,
+                });
+            }
+        };
+
+        match autoscalers
+            .iter()
+            .find(|autoscaler| hpa_scales_target(autoscaler, target))
+        {
+            Some(autoscaler) => Err(OperatorApiError::ScaleDownWithHpa {
+                target: target.to_string(),
+                autoscaler: autoscaler.name_any(),
+            }),
+            None => Ok(()),
+        }
+    }
+
     async fn try_reuse_copy_target<P: Progress>(
         &self,
         layer_config: &LayerConfig,
@@ -1536,9 +1619,7 @@ impl OperatorApi<PreparedClientCert> {
 
         let mut config = Self::base_client_config(layer_config).await?;
         let cert_header = Self::make_client_cert_header(&session.client_cert)?;
-        config
-            .headers
-            .push((HeaderName::from_static(CLIENT_CERT_HEADER), cert_header));
+        config.headers.push((header::CLIENT_CERT, cert_header));
 
         let client = ClientBuilder::try_from(config)
             .map_err(KubeApiError::from)
@@ -1562,7 +1643,12 @@ impl OperatorApi<PreparedClientCert> {
     ) -> OperatorApiResult<Connection<ProtocolClient>> {
         let request_builder = Request::builder()
             .uri(&session.connect_url)
-            .header(SESSION_ID_HEADER, session.id.to_string());
+            .header(header::SESSION_ID, session.id.to_string());
+        let request_builder = if let Some(target_namespace) = &session.target_namespace {
+            request_builder.header(header::TARGET_NAMESPACE, target_namespace.header_value())
+        } else {
+            request_builder
+        };
         let request_builder = if let Some(traceparent) = &session.traceparent {
             request_builder.header("traceparent", traceparent.clone())
         } else {
@@ -1930,13 +2016,34 @@ impl OperatorApi<PreparedClientCert> {
     }
 }
 
+/// Returns whether the given [`HorizontalPodAutoscaler`] scales the workload of the given
+/// [`Target`].
+fn hpa_scales_target(autoscaler: &HorizontalPodAutoscaler, target: &Target) -> bool {
+    let (kind, name) = match target {
+        Target::Deployment(target) => ("Deployment", &target.deployment),
+        Target::Rollout(target) => ("Rollout", &target.rollout),
+        Target::StatefulSet(target) => ("StatefulSet", &target.stateful_set),
+        Target::ReplicaSet(target) => ("ReplicaSet", &target.replica_set),
+        _ => return false,
+    };
+
+    autoscaler.spec.as_ref().is_some_and(|spec| {
+        spec.scale_target_ref.kind == kind && &spec.scale_target_ref.name == name
+    })
+}
+
 #[cfg(test)]
 mod test {
     use std::collections::{BTreeMap, HashMap};
 
-    use k8s_openapi::api::apps::v1::Deployment;
+    use k8s_openapi::api::{
+        apps::v1::Deployment,
+        autoscaling::v2::{
+            CrossVersionObjectReference, HorizontalPodAutoscaler, HorizontalPodAutoscalerSpec,
+        },
+    };
     use kube::api::ObjectMeta;
-    use mirrord_config::feature::network::incoming::ConcurrentSteal;
+    use mirrord_config::{feature::network::incoming::ConcurrentSteal, target::Target};
     use mirrord_kube::resolved::{ResolvedResource, ResolvedTarget};
     use rstest::rstest;
 
@@ -2223,6 +2330,7 @@ mod test {
             is_default_cluster: None, // Only used in multi-cluster
             sqs_output_queues: Default::default(), // Only used in multi-cluster
             key,
+            ordinal: None,
         };
 
         let produced = OperatorApi::target_connect_url(use_proxy, &target, &params);
@@ -2340,9 +2448,38 @@ mod test {
             is_default_cluster: None,
             sqs_output_queues: Default::default(),
             key,
+            ordinal: None,
         };
         let produced =
             OperatorApi::target_connect_url_from_config(use_proxy, &target, namespace, &params);
         assert_eq!(produced, expected)
     }
+
+    #[rstest]
+    #[case::deployment("deployment/py-serv", "Deployment", "py-serv", true)]
+    #[case::other_name("deployment/py-serv", "Deployment", "other", false)]
+    #[case::other_kind("deployment/py-serv", "StatefulSet", "py-serv", false)]
+    #[case::rollout("rollout/py-serv", "Rollout", "py-serv", true)]
+    #[case::pod("pod/py-serv", "Pod", "py-serv", false)]
+    fn hpa_scales_target(
+        #[case] target: &str,
+        #[case] kind: &str,
+        #[case] name: &str,
+        #[case] expected: bool,
+    ) {
+        let autoscaler = HorizontalPodAutoscaler {
+            spec: Some(HorizontalPodAutoscalerSpec {
+                scale_target_ref: CrossVersionObjectReference {
+                    kind: kind.into(),
+                    name: name.into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let target = target.parse::<Target>().unwrap();
+
+        assert_eq!(super::hpa_scales_target(&autoscaler, &target), expected);
+    }
 }
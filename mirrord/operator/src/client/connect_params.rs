@@ -3,7 +3,7 @@ use std::{
     fmt,
 };
 
-use mirrord_config::{LayerConfig, feature::network::incoming::ConcurrentSteal};
+use mirrord_config::{LayerConfig, feature::network::incoming::ConcurrentSteal, target::Target};
 use serde::Serialize;
 
 use crate::crd::session::SessionCiInfo;
@@ -72,6 +72,12 @@ pub struct ConnectParams<'a> {
     /// Key for this session
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<&'a str>,
+
+    /// Ordinal of the targeted pod, only when targeting a stateful set.
+    ///
+    /// Taken from the target path, or from `target.pod_selection.ordinal`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ordinal: Option<u32>,
 }
 
 impl<'a> ConnectParams<'a> {
@@ -99,6 +105,12 @@ impl<'a> ConnectParams<'a> {
             is_default_cluster: None,          // Only used in multi-cluster
             sqs_output_queues: HashMap::new(), // Only used in multi-cluster
             key: Some(key),
+            ordinal: match config.target.path.as_ref() {
+                Some(Target::StatefulSet(target)) => {
+                    target.ordinal.or(config.target.pod_selection.ordinal)
+                }
+                _ => None,
+            },
         }
     }
 }
@@ -0,0 +1,310 @@
+//! Planning of an operator session without starting it, used by `mirrord exec --dry-run`.
+
+use kube::Resource;
+use mirrord_config::{LayerConfig, target::Target};
+
+use super::{
+    ClientCertificateState, OperatorApi, PreparedClientCert, connect_params::ConnectParams,
+};
+use crate::{
+    crd::{
+        MirrordClusterOperatorUserCredential, MirrordOperatorCrd, NewOperatorFeature,
+        copy_target::CopyTargetCrd,
+    },
+    types::{
+        CLIENT_CERT_HEADER, CLIENT_HOSTNAME_HEADER, CLIENT_NAME_HEADER, CLIENT_PLATFORM_HEADER,
+        MIRRORD_CLI_VERSION_HEADER, MIRRORD_TRACE_ID_HEADER, SESSION_ID_HEADER,
+    },
+};
+
+/// Placeholder for the name of the copied target, which is only known once the operator creates
+/// it.
+const COPY_TARGET_NAME_PLACEHOLDER: &str = "{copy-target-name}";
+
+/// Printed in place of [`LayerConfig::key`], which identifies the session.
+const KEY_PLACEHOLDER: &str = "redacted";
+
+/// A single request that would be made to the operator when starting a session.
+///
+/// Only header names are kept, values (e.g. the client certificate) are never exposed.
+#[derive(Clone, Debug)]
+pub struct PlannedOperatorRequest {
+    /// HTTP verb of the request, or `WEBSOCKET` for the session connection.
+    pub verb: &'static str,
+    /// Path of the request, relative to the API server.
+    pub url: String,
+    /// Names of the extra headers attached to the request.
+    pub headers: Vec<&'static str>,
+    /// Condition under which the request is made.
+    pub note: Option<&'static str>,
+}
+
+impl<C> OperatorApi<C>
+where
+    C: ClientCertificateState,
+{
+    /// Lists the requests [`OperatorApi::connect_in_new_session`] would make for the given
+    /// config, without making them.
+    ///
+    /// Database branching requests are not included.
+    pub fn plan_session(&self, layer_config: &LayerConfig) -> Vec<PlannedOperatorRequest> {
+        let base_headers = vec![
+            MIRRORD_CLI_VERSION_HEADER,
+            CLIENT_NAME_HEADER,
+            CLIENT_HOSTNAME_HEADER,
+            CLIENT_PLATFORM_HEADER,
+            MIRRORD_TRACE_ID_HEADER,
+        ];
+        let mut certified_headers = base_headers.clone();
+        certified_headers.push(CLIENT_CERT_HEADER);
+
+        let mut requests = Vec::new();
+
+        let certificate_url = if self
+            .operator
+            .spec
+            .supported_features()
+            .contains(&NewOperatorFeature::ExtendableUserCredentials)
+        {
+            MirrordClusterOperatorUserCredential::url_path(&(), None)
+        } else {
+            format!(
+                "{}/operator/certificate",
+                MirrordOperatorCrd::url_path(&(), None)
+            )
+        };
+        requests.push(PlannedOperatorRequest {
+            verb: "POST",
+            url: certificate_url,
+            headers: base_headers,
+            note: Some("only when no valid user certificate is stored locally"),
+        });
+
+        let use_proxy = self
+            .operator
+            .spec
+            .supported_features()
+            .contains(&NewOperatorFeature::ProxyApi);
+        let target = layer_config
+            .target
+            .path
+            .clone()
+            .unwrap_or(Target::Targetless);
+        let namespace = layer_config
+            .target
+            .namespace
+            .as_deref()
+            .unwrap_or(self.client.default_namespace());
+        let connect_params = ConnectParams::new(
+            layer_config,
+            None,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            None,
+            KEY_PLACEHOLDER,
+        );
+
+        let connect_url = if layer_config.feature.copy_target.enabled {
+            let copy_url = CopyTargetCrd::url_path(&(), Some(namespace));
+            requests.push(PlannedOperatorRequest {
+                verb: "POST",
+                url: copy_url.clone(),
+                headers: certified_headers.clone(),
+                note: Some("only when there is no reusable copy of the target"),
+            });
+            requests.push(PlannedOperatorRequest {
+                verb: "WATCH",
+                url: copy_url.clone(),
+                headers: certified_headers.clone(),
+                note: None,
+            });
+
+            if use_proxy {
+                format!(
+                    "/apis/{}/proxy/namespaces/{namespace}/{}/{COPY_TARGET_NAME_PLACEHOLDER}?{connect_params}",
+                    CopyTargetCrd::api_version(&()),
+                    CopyTargetCrd::plural(&()),
+                )
+            } else {
+                format!("{copy_url}/{COPY_TARGET_NAME_PLACEHOLDER}?{connect_params}")
+            }
+        } else {
+            OperatorApi::<PreparedClientCert>::target_connect_url_from_config(
+                use_proxy,
+                &target,
+                namespace,
+                &connect_params,
+            )
+        };
+
+        let mut connect_headers = certified_headers;
+        connect_headers.push(SESSION_ID_HEADER);
+        if layer_config.traceparent.is_some() {
+            connect_headers.push("traceparent");
+        }
+        if layer_config.baggage.is_some() {
+            connect_headers.push("baggage");
+        }
+        requests.push(PlannedOperatorRequest {
+            verb: "WEBSOCKET",
+            url: connect_url,
+            headers: connect_headers,
+            note: None,
+        });
+
+        requests
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use kube::{Client, Config};
+    use mirrord_config::{
+        LayerConfig,
+        config::ConfigContext,
+        target::{Target, deployment::DeploymentTarget},
+    };
+    use rstest::rstest;
+    use semver::Version;
+
+    use super::PlannedOperatorRequest;
+    use crate::{
+        client::{NoClientCert, OperatorApi},
+        crd::{MirrordOperatorCrd, MirrordOperatorSpec, NewOperatorFeature},
+        types::LicenseInfoOwned,
+    };
+
+    fn operator_api(features: Vec<NewOperatorFeature>) -> OperatorApi<NoClientCert> {
+        let config = Config::new("http://127.0.0.1:6443".parse().unwrap());
+        let license = LicenseInfoOwned {
+            version: 0,
+            name: "team".to_owned(),
+            organization: "metalbear".to_owned(),
+            expire_at: NaiveDate::from_ymd_opt(2030, 1, 1).unwrap(),
+            fingerprint: None,
+            subscription_id: None,
+            features: Default::default(),
+            max_concurrent_sessions: None,
+            is_trial: false,
+        };
+
+        OperatorApi {
+            client: Client::try_from(config.clone()).unwrap(),
+            client_cert: NoClientCert {
+                base_config: config,
+            },
+            operator: MirrordOperatorCrd::new(
+                "operator",
+                MirrordOperatorSpec::new(
+                    Version::new(3, 100, 0),
+                    "default".to_owned(),
+                    features,
+                    license,
+                    None,
+                    None,
+                ),
+            ),
+        }
+    }
+
+    fn layer_config() -> LayerConfig {
+        let mut context = ConfigContext::default()
+            .strict_env(true)
+            .override_env("MIRRORD_ENV_KEY", "secret-key");
+
+        LayerConfig::resolve(&mut context).unwrap()
+    }
+
+    /// Returns the verb and the path (without the query) of each request.
+    fn summary(requests: &[PlannedOperatorRequest]) -> Vec<(&'static str, &str)> {
+        requests
+            .iter()
+            .map(|request| {
+                let path = request
+                    .url
+                    .split_once('?')
+                    .map_or(request.url.as_str(), |(path, _)| path);
+                (request.verb, path)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn targetless_session() {
+        let requests = operator_api(vec![]).plan_session(&layer_config());
+
+        assert_eq!(
+            summary(&requests),
+            [
+                (
+                    "POST",
+                    "/apis/operator.metalbear.co/v1/mirrordoperators/operator/certificate"
+                ),
+                (
+                    "WEBSOCKET",
+                    "/apis/operator.metalbear.co/v1/namespaces/default/targets/targetless"
+                ),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::direct(
+        vec![],
+        "/apis/operator.metalbear.co/v1/namespaces/staging/copytargets/{copy-target-name}"
+    )]
+    #[case::proxy(
+        vec![NewOperatorFeature::ProxyApi],
+        "/apis/operator.metalbear.co/v1/proxy/namespaces/staging/copytargets/{copy-target-name}"
+    )]
+    #[tokio::test]
+    async fn copy_target_session(
+        #[case] features: Vec<NewOperatorFeature>,
+        #[case] connect_path: &str,
+    ) {
+        let mut config = layer_config();
+        config.target.path = Some(Target::Deployment(DeploymentTarget {
+            deployment: "app".to_owned(),
+            container: None,
+        }));
+        config.target.namespace = Some("staging".to_owned());
+        config.feature.copy_target.enabled = true;
+
+        let requests = operator_api(features).plan_session(&config);
+
+        assert_eq!(
+            summary(&requests),
+            [
+                (
+                    "POST",
+                    "/apis/operator.metalbear.co/v1/mirrordoperators/operator/certificate"
+                ),
+                (
+                    "POST",
+                    "/apis/operator.metalbear.co/v1/namespaces/staging/copytargets"
+                ),
+                (
+                    "WATCH",
+                    "/apis/operator.metalbear.co/v1/namespaces/staging/copytargets"
+                ),
+                ("WEBSOCKET", connect_path),
+            ]
+        );
+    }
+
+    /// The session key must not be printed with the plan.
+    #[tokio::test]
+    async fn session_key_is_redacted() {
+        let requests = operator_api(vec![]).plan_session(&layer_config());
+
+        let connect_url = &requests.last().unwrap().url;
+        assert!(connect_url.contains("key=redacted"), "{connect_url}");
+        assert!(
+            requests
+                .iter()
+                .all(|request| !request.url.contains("secret-key"))
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fmt, num::ParseIntError};
+use std::fmt;
 
 pub use http::Error as HttpError;
 use mirrord_auth::error::ApiKeyError;
@@ -7,7 +7,10 @@ use mirrord_protocol_io::ProtocolError;
 use thiserror::Error;
 use tower::retry::backoff::InvalidBackoff;
 
-use crate::crd::{NewOperatorFeature, kube_target::UnknownTargetType};
+use crate::{
+    crd::{NewOperatorFeature, kube_target::UnknownTargetType},
+    types::{LicenseError, SessionIdError, TargetNamespaceError},
+};
 
 /// Operations performed on the operator via [`kube`] API.
 #[derive(Debug)]
@@ -69,8 +72,8 @@ pub enum OperatorApiError {
         status: Box<kube::core::Status>,
     },
 
-    #[error("mirrord operator license expired")]
-    NoLicense,
+    #[error(transparent)]
+    License(#[from] LicenseError),
 
     #[error("failed to prepare client certificate: {0}")]
     ClientCertError(String),
@@ -82,11 +85,20 @@ pub enum OperatorApiError {
     KubeApi(#[from] KubeApiError),
 
     #[error(transparent)]
-    ParseInt(#[from] ParseIntError),
+    InvalidSessionId(#[from] SessionIdError),
+
+    #[error(transparent)]
+    InvalidTargetNamespace(#[from] TargetNamespaceError),
 
     #[error("copied target failed: {}", message.as_deref().unwrap_or("reason unknown"))]
     CopiedTargetFailed { message: Option<String> },
 
+    #[error(
+        "refusing to scale down {target}, it is managed by the HorizontalPodAutoscaler \
+        `{autoscaler}`, which would scale it back up"
+    )]
+    ScaleDownWithHpa { target: String, autoscaler: String },
+
     #[error("operation timed out: {}", operation)]
     OperationTimeout { operation: OperatorOperation },
 
@@ -19,7 +19,7 @@ use serde::{Deserialize, Serialize};
 use crate::client::error::OperatorApiError;
 use crate::{
     crd::{copy_target::CopyTargetCrd, kafka::MirrordKafkaEphemeralTopicSpec},
-    types::LicenseInfoOwned,
+    types::{Capability, LicenseInfoOwned, MIRRORD_PROTOCOL, OperatorCapabilities},
 };
 
 pub mod copy_target;
@@ -111,6 +111,8 @@ impl TryFrom<TargetCrd> for TargetConfig {
         Ok(TargetConfig {
             path: Some(Target::try_from(crd.spec.target)?),
             namespace: crd.metadata.namespace,
+            pod_selection: Default::default(),
+            create_job: false,
         })
     }
 }
@@ -160,6 +162,12 @@ pub struct MirrordOperatorSpec {
     /// Used by CLI in multi-cluster management-only mode to create CRDs
     /// in the operator's namespace with a target-namespace annotation.
     pub operator_namespace: Option<String>,
+    /// Features and protocol versions the operator supports.
+    ///
+    /// Access this info only via `capabilities()`.
+    /// Optional for backwards compatibility (new clients can talk to old operators that don't send
+    /// this field).
+    capabilities: Option<OperatorCapabilities>,
 }
 
 impl MirrordOperatorSpec {
@@ -186,6 +194,44 @@ impl MirrordOperatorSpec {
             features,
             copy_target_enabled,
             operator_namespace,
+            capabilities: None,
+        }
+    }
+
+    /// Advertises the given [`OperatorCapabilities`] to the clients.
+    pub fn with_capabilities(mut self, capabilities: OperatorCapabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// Get the [`OperatorCapabilities`] advertised by the operator.
+    ///
+    /// Older operators don't advertise them, in which case they're built from
+    /// [`MirrordOperatorSpec::supported_features`] and [`MirrordOperatorSpec::protocol_version`],
+    /// and include only what can be told from these.
+    pub fn capabilities(&self) -> OperatorCapabilities {
+        if let Some(capabilities) = &self.capabilities {
+            return capabilities.clone();
+        }
+
+        let features = self
+            .supported_features()
+            .contains(&NewOperatorFeature::CopyTarget)
+            .then_some(Capability::CopyTarget)
+            .into_iter()
+            .collect();
+
+        let protocol_versions = self
+            .protocol_version
+            .as_deref()
+            .and_then(|version| version.parse::<Version>().ok())
+            .map(|version| (MIRRORD_PROTOCOL.to_owned(), version))
+            .into_iter()
+            .collect();
+
+        OperatorCapabilities {
+            features,
+            protocol_versions,
         }
     }
 
@@ -424,6 +470,12 @@ pub struct Session {
     pub user_id: Option<String>,
     pub sqs: Option<Vec<MirrordSqsSession>>,
     pub kafka: Option<Vec<MirrordKafkaEphemeralTopicSpec>>,
+
+    /// Ports of the target mirrored by the session.
+    ///
+    /// Option because added later.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirrored_ports: Option<Vec<u16>>,
 }
 
 /// Resource used to access the operator's session management routes.
@@ -901,27 +953,68 @@ pub enum UserCredentialKind {
 
 #[cfg(test)]
 mod tests {
-    use std::{fs, path::PathBuf};
+    use std::{fs, ops::Not, path::PathBuf};
 
     use kube::CustomResourceExt;
-
-    use crate::crd::{
-        MirrordClusterOperatorUserCredential, MirrordOperatorCrd, MirrordSqsSession,
-        MirrordWorkloadQueueRegistry, SessionCrd,
-        db_branching::{
-            mongodb::MongodbBranchDatabase, mysql::MysqlBranchDatabase, pg::PgBranchDatabase,
+    use semver::Version;
+
+    use crate::{
+        crd::{
+            MirrordClusterOperatorUserCredential, MirrordOperatorCrd, MirrordOperatorSpec,
+            MirrordSqsSession, MirrordWorkloadQueueRegistry, SessionCrd,
+            db_branching::{
+                mongodb::MongodbBranchDatabase, mysql::MysqlBranchDatabase, pg::PgBranchDatabase,
+            },
+            external::MirrordClusterExternalResource,
+            kafka::{
+                MirrordKafkaClientConfig, MirrordKafkaEphemeralTopic, MirrordKafkaTopicsConsumer,
+            },
+            multi_cluster::MirrordMultiClusterSession,
+            patch::{MirrordClusterWorkloadPatch, MirrordClusterWorkloadPatchRequest},
+            policy::{MirrordClusterPolicy, MirrordPolicy},
+            preview::PreviewSession,
+            profile::{MirrordClusterProfile, MirrordProfile},
+            session::MirrordClusterSession,
+            steal_tls::{MirrordClusterTlsStealConfig, MirrordTlsStealConfig},
         },
-        external::MirrordClusterExternalResource,
-        kafka::{MirrordKafkaClientConfig, MirrordKafkaEphemeralTopic, MirrordKafkaTopicsConsumer},
-        multi_cluster::MirrordMultiClusterSession,
-        patch::{MirrordClusterWorkloadPatch, MirrordClusterWorkloadPatchRequest},
-        policy::{MirrordClusterPolicy, MirrordPolicy},
-        preview::PreviewSession,
-        profile::{MirrordClusterProfile, MirrordProfile},
-        session::MirrordClusterSession,
-        steal_tls::{MirrordClusterTlsStealConfig, MirrordTlsStealConfig},
+        types::{Capability, MIRRORD_PROTOCOL, OperatorCapabilities},
     };
 
+    #[test]
+    fn capabilities_from_old_operator() {
+        let spec: MirrordOperatorSpec = serde_json::from_value(serde_json::json!({
+            "operator_version": "3.100.0",
+            "default_namespace": "default",
+            "supported_features": ["ProxyApi", "CopyTarget"],
+            "license": {
+                "name": "team",
+                "organization": "metalbear",
+                "expire_at": "2030-01-01",
+                "fingerprint": null,
+                "subscription_id": null
+            },
+            "protocol_version": "1.20.0"
+        }))
+        .unwrap();
+
+        let capabilities = spec.capabilities();
+        assert!(capabilities.supports(Capability::CopyTarget));
+        assert!(capabilities.supports(Capability::StealHttpFilters).not());
+        assert_eq!(
+            capabilities.protocol_version(MIRRORD_PROTOCOL),
+            Some(&Version::new(1, 20, 0))
+        );
+
+        let advertised = OperatorCapabilities {
+            features: [Capability::StealHttpFilters].into(),
+            protocol_versions: Default::default(),
+        };
+        assert_eq!(
+            spec.with_capabilities(advertised.clone()).capabilities(),
+            advertised
+        );
+    }
+
     fn write_crd_yaml<T: CustomResourceExt>() {
         let crd = T::crd();
         let file_name = crd
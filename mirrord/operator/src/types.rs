@@ -1,42 +1,1673 @@
-use chrono::NaiveDate;
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+    fmt,
+    ops::Not,
+    str::FromStr,
+    time::Duration,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+use base64::{Engine, engine::general_purpose};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeDelta, Utc};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use schemars::{
+    JsonSchema,
+    r#gen::SchemaGenerator,
+    schema::{InstanceType, Metadata, Schema, SchemaObject},
+};
+use semver::Version;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// How many days before [`LicenseInfoOwned::expire_at`] users start getting warned about the
+/// license expiring, see [`LicenseInfoOwned::expiry_warning`].
+pub const LICENSE_EXPIRY_WARNING_DAYS: i64 = 7;
+
+/// Current version of the [`LicenseInfoOwned`] format, see [`LicenseInfoOwned::version`].
+pub const LICENSE_INFO_VERSION: u32 = 1;
+
+/// Magic bytes at the start of [`LicenseInfoOwned::to_cache_bytes`].
+pub const LICENSE_CACHE_MAGIC: &[u8; 4] = b"MLIC";
+
+/// Current version of the [`LicenseInfoOwned::to_cache_bytes`] encoding.
+///
+/// Only bumped for changes that older versions cannot skip over, new fields just get new record
+/// tags. Caches in a newer format are ignored by [`LicenseInfoOwned::from_cache_bytes`].
+pub const LICENSE_CACHE_FORMAT: u8 = 1;
+
+/// License of the operator, as reported in the operator resource.
+///
+/// Unknown fields are ignored when deserializing (`deny_unknown_fields` must stay off), so that
+/// licenses in a newer format can still be read. New fields should be optional or have defaults,
+/// for the same reason in reverse.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct LicenseInfoOwned {
+    /// Version of this format, bumped (see [`LICENSE_INFO_VERSION`]) when the meaning of the
+    /// fields changes.
+    ///
+    /// Missing (`0`) in licenses issued before the version was introduced.
+    #[serde(default)]
+    pub version: u32,
     pub name: String,
     pub organization: String,
+    /// Last day of the license.
+    ///
+    /// Older licenses encode it as a full RFC 3339 datetime, which is truncated to its date when
+    /// deserializing. Always serialized as `YYYY-MM-DD`.
+    #[serde(deserialize_with = "deserialize_expire_at")]
     pub expire_at: NaiveDate,
-    /// Fingerprint of the operator license.
+    /// Fingerprint of the operator license, see [`LicenseInfoOwned::expected_fingerprint`].
     pub fingerprint: Option<String>,
     /// Subscription id encoded in the operator license extension.
     pub subscription_id: Option<String>,
+    /// Operator capabilities unlocked by this license.
+    ///
+    /// Missing in licenses issued before feature flags were introduced, in which case it's empty.
+    #[serde(default)]
+    #[schemars(with = "Vec<LicenseFeature>")]
+    pub features: BTreeSet<LicenseFeature>,
+    /// Max number of mirrord sessions that can run at the same time under this license, see
+    /// [`LicenseInfoOwned::allows_new_session`].
+    ///
+    /// Missing when the number of sessions is unlimited.
+    #[serde(default)]
+    pub max_concurrent_sessions: Option<u32>,
+    /// Whether this is a trial license, see [`LicenseInfoOwned::is_trial_license`].
+    ///
+    /// Missing in licenses issued before it was introduced, which are only recognized as trials by
+    /// their [`name`](Self::name).
+    #[serde(default)]
+    pub is_trial: bool,
+}
+
+/// Deserializes [`LicenseInfoOwned::expire_at`] from either a plain `YYYY-MM-DD` date, or an
+/// RFC 3339 datetime (used by older licenses), in which case the time part is dropped.
+fn deserialize_expire_at<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<NaiveDate, D::Error> {
+    let value = String::deserialize(deserializer)?;
+
+    parse_expire_at(&value).map_err(|_| {
+        serde::de::Error::invalid_value(
+            serde::de::Unexpected::Str(&value),
+            &"a `YYYY-MM-DD` date or an RFC 3339 datetime",
+        )
+    })
+}
+
+/// Parses a license expiration date, see [`LicenseInfoOwned::expire_at`].
+///
+/// Accepts either a plain `YYYY-MM-DD` date, or an RFC 3339 datetime, in which case the time part
+/// is dropped.
+pub fn parse_expire_at(value: &str) -> Result<NaiveDate, LicenseError> {
+    value
+        .parse::<NaiveDate>()
+        .or_else(|_| DateTime::parse_from_rfc3339(value).map(|datetime| datetime.date_naive()))
+        .map_err(|error| LicenseError::Parse(format!("invalid expiration date `{value}`: {error}")))
+}
+
+/// State of a license on a given day, see [`LicenseInfoOwned::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LicenseStatus {
+    /// The expiration is further away than the warning window.
+    Valid,
+    /// The expiration is within the warning window, the expiration day included.
+    ExpiringSoon,
+    /// The license has expired, but is still within the grace period.
+    GracePeriod,
+    /// The license has expired, and the grace period is over.
+    Expired,
+}
+
+impl LicenseInfoOwned {
+    /// Returns whether this license unlocks the given [`LicenseFeature`].
+    pub fn allows(&self, feature: &LicenseFeature) -> bool {
+        self.features.contains(feature)
+    }
+
+    /// Fails with [`LicenseError::FeatureNotLicensed`] if this license does not unlock the given
+    /// [`LicenseFeature`].
+    pub fn require(&self, feature: &LicenseFeature) -> Result<(), LicenseError> {
+        if self.allows(feature) {
+            Ok(())
+        } else {
+            Err(LicenseError::FeatureNotLicensed(feature.clone()))
+        }
+    }
+
+    /// Returns whether another session can start, given the number of `active` sessions.
+    pub fn allows_new_session(&self, active: u32) -> bool {
+        self.max_concurrent_sessions
+            .is_none_or(|max_concurrent_sessions| active < max_concurrent_sessions)
+    }
+
+    /// Returns whether this is a trial license.
+    ///
+    /// Operators that don't send [`is_trial`](Self::is_trial) yet mark trials with `(Trial)` in
+    /// the license [`name`](Self::name).
+    pub fn is_trial_license(&self) -> bool {
+        self.is_trial || self.name.contains("(Trial)")
+    }
+
+    /// Returns the number of days left in the trial as of `today`, or [`None`] if this is not a
+    /// trial license, see [`LicenseInfoOwned::is_trial_license`].
+    ///
+    /// The expiration day counts as the last day of the trial, so this is `0` on that day, and
+    /// stays `0` once the trial is over.
+    pub fn trial_days_left(&self, today: NaiveDate) -> Option<i64> {
+        self.is_trial_license().then(|| {
+            self.expire_at
+                .signed_duration_since(today)
+                .num_days()
+                .max(0)
+        })
+    }
+
+    /// Returns a warning if this license comes in a format newer than
+    /// [`LICENSE_INFO_VERSION`], in which case some of its information may be missed.
+    pub fn newer_version_warning(&self) -> Option<String> {
+        (self.version > LICENSE_INFO_VERSION).then(|| {
+            format!(
+                "The operator license uses format version {}, but this mirrord version only \
+                supports up to {LICENSE_INFO_VERSION}, some license information may be missing. \
+                Consider updating mirrord",
+                self.version
+            )
+        })
+    }
+
+    /// The instant when this license expires: the end of the [`expire_at`](Self::expire_at) day
+    /// (23:59:59) in UTC.
+    ///
+    /// The license is valid for the whole expiration day in UTC, regardless of the user's
+    /// timezone, so it does not appear expired early for users ahead of UTC.
+    pub fn expires_at_utc(&self) -> DateTime<Utc> {
+        let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).expect("23:59:59 is a valid time");
+        self.expire_at.and_time(end_of_day).and_utc()
+    }
+
+    /// Returns whether this license is expired at the given instant, see
+    /// [`expires_at_utc`](Self::expires_at_utc).
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        now > self.expires_at_utc()
+    }
+
+    /// Fails with [`LicenseError::Expired`] if this license is expired at the given instant, see
+    /// [`is_expired_at`](Self::is_expired_at).
+    pub fn check_expiry(&self, now: DateTime<Utc>) -> Result<(), LicenseError> {
+        if self.is_expired_at(now) {
+            Err(LicenseError::Expired {
+                expire_at: self.expire_at,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Verifies this license at the given instant: it must not be expired (see
+    /// [`check_expiry`](Self::check_expiry)), and its fingerprint must match (see
+    /// [`verify_fingerprint`](Self::verify_fingerprint)).
+    ///
+    /// Licenses without a fingerprint (issued before fingerprints were introduced) pass the
+    /// fingerprint check.
+    pub fn verify(&self, now: DateTime<Utc>) -> Result<(), LicenseError> {
+        self.check_expiry(now)?;
+
+        match self.verify_fingerprint() {
+            Ok(()) | Err(FingerprintError::Unverifiable) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Computes the fingerprint of this license from its canonical fields.
+    ///
+    /// The fingerprint is the lowercase hex SHA-256 digest of the [`name`](Self::name),
+    /// [`organization`](Self::organization), [`expire_at`](Self::expire_at) (as `YYYY-MM-DD`) and
+    /// [`subscription_id`](Self::subscription_id) (empty when missing), in this order. Each field
+    /// is hashed as its UTF-8 length (big-endian `u64`) followed by its UTF-8 bytes, so that
+    /// different field values can never produce the same input.
+    ///
+    /// The license-generation side must use the same scheme.
+    pub fn expected_fingerprint(&self) -> String {
+        let expire_at = self.expire_at.format("%Y-%m-%d").to_string();
+        let fields = [
+            self.name.as_str(),
+            self.organization.as_str(),
+            expire_at.as_str(),
+            self.subscription_id.as_deref().unwrap_or_default(),
+        ];
+
+        let mut hasher = Sha256::new();
+        for field in fields {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field.as_bytes());
+        }
+
+        hex::encode(hasher.finalize())
+    }
+
+    /// Checks the [`fingerprint`](Self::fingerprint) against the
+    /// [`expected_fingerprint`](Self::expected_fingerprint), to detect corrupted or tampered
+    /// licenses.
+    ///
+    /// Licenses without a fingerprint cannot be verified, which results in
+    /// [`FingerprintError::Unverifiable`].
+    pub fn verify_fingerprint(&self) -> Result<(), FingerprintError> {
+        let found = self
+            .fingerprint
+            .as_deref()
+            .ok_or(FingerprintError::Unverifiable)?;
+        let expected = self.expected_fingerprint();
+
+        if found.eq_ignore_ascii_case(&expected) {
+            Ok(())
+        } else {
+            Err(FingerprintError::Mismatch {
+                expected,
+                found: found.to_owned(),
+            })
+        }
+    }
+
+    /// Renders this license for the user, as of `today`:
+    ///
+    /// ```text
+    /// Operator License
+    ///     name: team
+    ///     organization: metalbear
+    ///     expire at: 1-Jan-2030 (expires in 3 days)
+    ///     subscription id: <none>
+    ///     fingerprint: present
+    /// ```
+    ///
+    /// Should be used wherever the license is displayed, so that all commands show it the same
+    /// way.
+    pub fn summary(&self, today: NaiveDate) -> String {
+        let days = self.expire_at.signed_duration_since(today).num_days();
+        let plural = |days: i64| if days == 1 { "day" } else { "days" };
+        let expiry = match days {
+            0 => "expires today".to_owned(),
+            1.. => format!("expires in {days} {}", plural(days)),
+            _ => format!("expired {} {} ago", -days, plural(-days)),
+        };
+
+        format!(
+            "Operator License\n    \
+            name: {}\n    \
+            organization: {}\n    \
+            expire at: {} ({expiry})\n    \
+            subscription id: {}\n    \
+            fingerprint: {}",
+            self.name,
+            self.organization,
+            self.expire_at.format("%-d-%b-%Y"),
+            self.subscription_id.as_deref().unwrap_or("<none>"),
+            if self.fingerprint.is_some() {
+                "present"
+            } else {
+                "missing"
+            },
+        )
+    }
+
+    /// Returns a user-facing warning if `today` is within [`LICENSE_EXPIRY_WARNING_DAYS`] of the
+    /// license expiration date.
+    ///
+    /// Returns [`None`] when the expiration is further away, or when the license has already
+    /// expired (which is reported as an error elsewhere).
+    pub fn expiry_warning(&self, today: NaiveDate) -> Option<String> {
+        let days = self.expire_at.signed_duration_since(today).num_days();
+
+        let expires = match days {
+            0 => "today".to_owned(),
+            1 => "in 1 day".to_owned(),
+            2..=LICENSE_EXPIRY_WARNING_DAYS => format!("in {days} days"),
+            _ => return None,
+        };
+
+        Some(format!(
+            "Your mirrord license `{}` (org {}) expires {expires}",
+            self.name, self.organization
+        ))
+    }
+
+    /// Returns the [`LicenseStatus`] of this license on `today`.
+    ///
+    /// The license is [`LicenseStatus::ExpiringSoon`] from `warn_window` days before
+    /// [`expire_at`](Self::expire_at) until the expiration day, and in
+    /// [`LicenseStatus::GracePeriod`] for `grace` days after it.
+    pub fn status(&self, today: NaiveDate, warn_window: i64, grace: i64) -> LicenseStatus {
+        let days = self.expire_at.signed_duration_since(today).num_days();
+
+        if days > warn_window {
+            LicenseStatus::Valid
+        } else if days >= 0 {
+            LicenseStatus::ExpiringSoon
+        } else if -days <= grace {
+            LicenseStatus::GracePeriod
+        } else {
+            LicenseStatus::Expired
+        }
+    }
+
+    /// Returns the new [`LicenseStatus`] of this license if it changed between `yesterday` and
+    /// `today`, see [`status`](Self::status).
+    ///
+    /// Meant for periodic checks, so that the user is notified once, on the day the status
+    /// changes.
+    pub fn transition_between(
+        &self,
+        yesterday: NaiveDate,
+        today: NaiveDate,
+        warn_window: i64,
+        grace: i64,
+    ) -> Option<LicenseStatus> {
+        let status = self.status(today, warn_window, grace);
+
+        (self.status(yesterday, warn_window, grace) != status).then_some(status)
+    }
+
+    /// Encodes this license for the local license cache, see [`LICENSE_CACHE_FORMAT`].
+    ///
+    /// The encoding starts with [`LICENSE_CACHE_MAGIC`] and the format version byte, followed
+    /// by one record per present field. Each record is a one byte tag, the length of the value as
+    /// a little-endian `u32`, and the value itself. Strings are UTF-8, numbers are little-endian,
+    /// and [`expire_at`](Self::expire_at) is the number of days since the common era. Every
+    /// feature gets its own record.
+    ///
+    /// New fields must get new tags, so that older versions can skip them.
+    pub fn to_cache_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(*LICENSE_CACHE_MAGIC);
+        bytes.push(LICENSE_CACHE_FORMAT);
+
+        let mut record = |tag: u8, value: &[u8]| {
+            bytes.push(tag);
+            bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(value);
+        };
+
+        record(cache_tag::VERSION, &self.version.to_le_bytes());
+        record(cache_tag::NAME, self.name.as_bytes());
+        record(cache_tag::ORGANIZATION, self.organization.as_bytes());
+        record(
+            cache_tag::EXPIRE_AT,
+            &self.expire_at.num_days_from_ce().to_le_bytes(),
+        );
+        if let Some(fingerprint) = &self.fingerprint {
+            record(cache_tag::FINGERPRINT, fingerprint.as_bytes());
+        }
+        if let Some(subscription_id) = &self.subscription_id {
+            record(cache_tag::SUBSCRIPTION_ID, subscription_id.as_bytes());
+        }
+        for feature in &self.features {
+            record(cache_tag::FEATURE, feature.as_str().as_bytes());
+        }
+        if let Some(max_concurrent_sessions) = self.max_concurrent_sessions {
+            record(
+                cache_tag::MAX_CONCURRENT_SESSIONS,
+                &max_concurrent_sessions.to_le_bytes(),
+            );
+        }
+        if self.is_trial {
+            record(cache_tag::IS_TRIAL, &[]);
+        }
+
+        bytes
+    }
+
+    /// Decodes a license encoded with [`to_cache_bytes`](Self::to_cache_bytes).
+    ///
+    /// Returns [`None`] for caches written in a [`LICENSE_CACHE_FORMAT`] newer than this one,
+    /// which should be treated as a cache miss. Records with unknown tags (fields added by newer
+    /// versions) are skipped.
+    pub fn from_cache_bytes(bytes: &[u8]) -> Result<Option<Self>, LicenseError> {
+        let parse_error =
+            |reason: &str| LicenseError::Parse(format!("invalid license cache, {reason}"));
+
+        let rest = bytes
+            .strip_prefix(LICENSE_CACHE_MAGIC.as_slice())
+            .ok_or_else(|| parse_error("missing header"))?;
+        let (&format, mut rest) = rest
+            .split_first()
+            .ok_or_else(|| parse_error("missing format version"))?;
+        if format > LICENSE_CACHE_FORMAT {
+            return Ok(None);
+        }
+
+        let string = |value: &[u8]| {
+            String::from_utf8(value.to_vec()).map_err(|_| parse_error("string is not UTF-8"))
+        };
+        let number = |value: &[u8]| {
+            <[u8; 4]>::try_from(value).map_err(|_| parse_error("number is not 4 bytes long"))
+        };
+
+        let mut version = None;
+        let mut name = None;
+        let mut organization = None;
+        let mut expire_at = None;
+        let mut fingerprint = None;
+        let mut subscription_id = None;
+        let mut features = BTreeSet::new();
+        let mut max_concurrent_sessions = None;
+        let mut is_trial = false;
+
+        while let Some((&tag, remaining)) = rest.split_first() {
+            let (len, remaining) = remaining
+                .split_first_chunk::<4>()
+                .ok_or_else(|| parse_error("truncated record"))?;
+            let (value, remaining) = remaining
+                .split_at_checked(u32::from_le_bytes(*len) as usize)
+                .ok_or_else(|| parse_error("truncated record"))?;
+            rest = remaining;
+
+            match tag {
+                cache_tag::VERSION => version = Some(u32::from_le_bytes(number(value)?)),
+                cache_tag::NAME => name = Some(string(value)?),
+                cache_tag::ORGANIZATION => organization = Some(string(value)?),
+                cache_tag::EXPIRE_AT => {
+                    let days = i32::from_le_bytes(number(value)?);
+                    expire_at = Some(
+                        NaiveDate::from_num_days_from_ce_opt(days)
+                            .ok_or_else(|| parse_error("expiration date is out of range"))?,
+                    );
+                }
+                cache_tag::FINGERPRINT => fingerprint = Some(string(value)?),
+                cache_tag::SUBSCRIPTION_ID => subscription_id = Some(string(value)?),
+                cache_tag::FEATURE => {
+                    let Ok(feature) = string(value)?.parse();
+                    features.insert(feature);
+                }
+                cache_tag::MAX_CONCURRENT_SESSIONS => {
+                    max_concurrent_sessions = Some(u32::from_le_bytes(number(value)?))
+                }
+                cache_tag::IS_TRIAL => is_trial = true,
+                _ => {}
+            }
+        }
+
+        Ok(Some(Self {
+            version: version.unwrap_or_default(),
+            name: name.ok_or_else(|| parse_error("missing name"))?,
+            organization: organization.ok_or_else(|| parse_error("missing organization"))?,
+            expire_at: expire_at.ok_or_else(|| parse_error("missing expiration date"))?,
+            fingerprint,
+            subscription_id,
+            features,
+            max_concurrent_sessions,
+            is_trial,
+        }))
+    }
+}
+
+/// Tags of the [`LicenseInfoOwned::to_cache_bytes`] records, one per field.
+///
+/// Tags must never be reused for a different field.
+mod cache_tag {
+    pub const VERSION: u8 = 1;
+    pub const NAME: u8 = 2;
+    pub const ORGANIZATION: u8 = 3;
+    pub const EXPIRE_AT: u8 = 4;
+    pub const FINGERPRINT: u8 = 5;
+    pub const SUBSCRIPTION_ID: u8 = 6;
+    pub const FEATURE: u8 = 7;
+    pub const MAX_CONCURRENT_SESSIONS: u8 = 8;
+    /// Present only for trial licenses, with no value.
+    pub const IS_TRIAL: u8 = 9;
+}
+
+/// View of a [`LicenseInfoOwned`] that is safe to log, display and send in telemetry.
+///
+/// The [`subscription_id`](LicenseInfoOwned::subscription_id) and
+/// [`fingerprint`](LicenseInfoOwned::fingerprint) are replaced with a short hash (see
+/// [`RedactedLicenseInfo::REDACTED_LEN`]), which is enough to tell licenses apart without
+/// revealing the values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RedactedLicenseInfo {
+    pub name: String,
+    pub organization: String,
+    pub expire_at: NaiveDate,
+    /// Hash of the [`LicenseInfoOwned::subscription_id`].
+    pub subscription_id: Option<String>,
+    /// Hash of the [`LicenseInfoOwned::fingerprint`].
+    pub fingerprint: Option<String>,
+}
+
+impl RedactedLicenseInfo {
+    /// Number of hex characters of the SHA-256 digest kept in the redacted fields.
+    pub const REDACTED_LEN: usize = 8;
+
+    fn redact(value: &str) -> String {
+        let mut digest = hex::encode(Sha256::digest(value.as_bytes()));
+        digest.truncate(Self::REDACTED_LEN);
+        format!("sha256:{digest}")
+    }
+}
+
+impl From<&LicenseInfoOwned> for RedactedLicenseInfo {
+    fn from(license: &LicenseInfoOwned) -> Self {
+        Self {
+            name: license.name.clone(),
+            organization: license.organization.clone(),
+            expire_at: license.expire_at,
+            subscription_id: license.subscription_id.as_deref().map(Self::redact),
+            fingerprint: license.fingerprint.as_deref().map(Self::redact),
+        }
+    }
+}
+
+/// Errors of [`LicenseInfoOwned::verify_fingerprint`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FingerprintError {
+    #[error("license has no fingerprint, it cannot be verified")]
+    Unverifiable,
+
+    #[error("license fingerprint `{found}` does not match the expected `{expected}`")]
+    Mismatch { expected: String, found: String },
+}
+
+/// Errors of the operator license parsing and verification, e.g. [`LicenseInfoOwned::verify`].
+///
+/// The messages are user-facing, and each variant comes with a [`LicenseError::remediation`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LicenseError {
+    #[error("mirrord operator license expired on {expire_at}")]
+    Expired { expire_at: NaiveDate },
+
+    #[error("mirrord operator license is corrupted: {0}")]
+    FingerprintMismatch(#[from] FingerprintError),
+
+    #[error("failed to parse the mirrord operator license: {0}")]
+    Parse(String),
+
+    /// The license was not signed by MetalBear.
+    #[error("mirrord operator license signature is invalid: {0}")]
+    Signature(String),
+
+    #[error("`{0}` is not included in the mirrord operator license")]
+    FeatureNotLicensed(LicenseFeature),
+}
+
+impl LicenseError {
+    /// What the user can do about this error.
+    pub fn remediation(&self) -> &'static str {
+        match self {
+            Self::Expired { .. } => "Visit https://app.metalbear.com to renew your license",
+            Self::FeatureNotLicensed(..) => {
+                "Visit https://app.metalbear.com to upgrade your license"
+            }
+            Self::FingerprintMismatch(..) | Self::Parse(..) | Self::Signature(..) => {
+                "Download the license again from https://app.metalbear.com and reinstall the \
+                operator with it"
+            }
+        }
+    }
+}
+
+/// Operator capability gated by the license tier, carried in [`LicenseInfoOwned::features`].
+///
+/// Serialized as a kebab-case string. Values not known to this version of mirrord are
+/// deserialized into [`LicenseFeature::Unknown`] instead of failing, so that licenses issued for
+/// newer operators can still be read.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LicenseFeature {
+    /// Copying the target with `feature.copy_target`.
+    CopyTarget,
+    /// Stealing traffic with HTTP filters.
+    StealFilters,
+    /// Targeting multiple pods of a workload at once.
+    MultiPod,
+    /// SQS and Kafka queue splitting.
+    QueueSplitting,
+    /// Database branching.
+    DbBranching,
+    /// Feature introduced in a version newer than this one.
+    Unknown(String),
+}
+
+impl LicenseFeature {
+    /// All variants of this enum, except for [`LicenseFeature::Unknown`].
+    pub const KNOWN: [Self; 5] = [
+        Self::CopyTarget,
+        Self::StealFilters,
+        Self::MultiPod,
+        Self::QueueSplitting,
+        Self::DbBranching,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::CopyTarget => "copy-target",
+            Self::StealFilters => "steal-filters",
+            Self::MultiPod => "multi-pod",
+            Self::QueueSplitting => "queue-splitting",
+            Self::DbBranching => "db-branching",
+            Self::Unknown(name) => name,
+        }
+    }
+}
+
+impl FromStr for LicenseFeature {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let feature = Self::KNOWN
+            .into_iter()
+            .find(|known| known.as_str() == s)
+            .unwrap_or_else(|| Self::Unknown(s.to_owned()));
+
+        Ok(feature)
+    }
+}
+
+impl fmt::Display for LicenseFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for LicenseFeature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for LicenseFeature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Ok(feature) = String::deserialize(deserializer)?.parse();
+        Ok(feature)
+    }
+}
+
+/// Plain string schema, so that unknown values pass validation. Known values are listed in the
+/// description.
+impl JsonSchema for LicenseFeature {
+    fn schema_name() -> String {
+        "LicenseFeature".to_owned()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        Cow::Borrowed(concat!(module_path!(), "::LicenseFeature"))
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let known = Self::KNOWN
+            .iter()
+            .map(|feature| format!("`{feature}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            metadata: Some(Box::new(Metadata {
+                description: Some(format!(
+                    "Operator capability unlocked by the license. Known values: {known}."
+                )),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Feature of the operator that the client may depend on, advertised in
+/// [`OperatorCapabilities::features`].
+///
+/// Serialized as a kebab-case string. Values not known to this version of mirrord are
+/// deserialized into [`Capability::Unknown`] instead of failing, so that capabilities of newer
+/// operators can still be read.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Capability {
+    /// Stealing HTTP traffic with `feature.network.incoming.http_filter`.
+    StealHttpFilters,
+    /// HTTP filters on the request body, evaluated with `jaq`.
+    JaqFilters,
+    /// Copying the target with `feature.copy_target`.
+    CopyTarget,
+    /// Capability introduced in a version newer than this one.
+    Unknown(String),
+}
+
+impl Capability {
+    /// All variants of this enum, except for [`Capability::Unknown`].
+    pub const KNOWN: [Self; 3] = [Self::StealHttpFilters, Self::JaqFilters, Self::CopyTarget];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::StealHttpFilters => "steal-http-filters",
+            Self::JaqFilters => "jaq-filters",
+            Self::CopyTarget => "copy-target",
+            Self::Unknown(name) => name,
+        }
+    }
+}
+
+impl FromStr for Capability {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let capability = Self::KNOWN
+            .into_iter()
+            .find(|known| known.as_str() == s)
+            .unwrap_or_else(|| Self::Unknown(s.to_owned()));
+
+        Ok(capability)
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Capability {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Capability {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Ok(capability) = String::deserialize(deserializer)?.parse();
+        Ok(capability)
+    }
+}
+
+/// Plain string schema, so that unknown values pass validation. Known values are listed in the
+/// description.
+impl JsonSchema for Capability {
+    fn schema_name() -> String {
+        "Capability".to_owned()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        Cow::Borrowed(concat!(module_path!(), "::Capability"))
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let known = Self::KNOWN
+            .iter()
+            .map(|capability| format!("`{capability}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            metadata: Some(Box::new(Metadata {
+                description: Some(format!(
+                    "Feature supported by the operator. Known values: {known}."
+                )),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Name of the `mirrord-protocol` in [`OperatorCapabilities::protocol_versions`].
+pub const MIRRORD_PROTOCOL: &str = "mirrord-protocol";
+
+/// What the operator supports, as advertised by the operator itself.
+///
+/// Lets the client enable or disable behavior based on what the connected operator actually
+/// supports, instead of guessing from the operator version.
+///
+/// Unknown fields are ignored when deserializing, and all fields have defaults, so that
+/// capabilities of older and newer operators can be read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OperatorCapabilities {
+    /// Features supported by the operator.
+    #[serde(default)]
+    #[schemars(with = "Vec<Capability>")]
+    pub features: BTreeSet<Capability>,
+    /// Versions of the protocols spoken by the operator, by protocol name, e.g.
+    /// [`MIRRORD_PROTOCOL`].
+    #[serde(default)]
+    #[schemars(with = "BTreeMap<String, String>")]
+    pub protocol_versions: BTreeMap<String, Version>,
+}
+
+impl OperatorCapabilities {
+    /// Whether the operator advertised the given [`Capability`].
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.features.contains(&capability)
+    }
+
+    /// Version of the given protocol spoken by the operator, if advertised.
+    pub fn protocol_version(&self, protocol: &str) -> Option<&Version> {
+        self.protocol_versions.get(protocol)
+    }
 }
 
 /// Name of HTTP header containing CLI version.
 /// Sent with each request to the mirrord operator.
 pub const MIRRORD_CLI_VERSION_HEADER: &str = "x-mirrord-cli-version";
 
+/// Version of the mirrord CLI, as sent in the [`MIRRORD_CLI_VERSION_HEADER`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CliVersion(pub Version);
+
+/// Result of [`CliVersion::is_compatible_with_operator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    Ok,
+    /// The user should upgrade the mirrord CLI.
+    ClientTooOld,
+    /// The user should downgrade the mirrord CLI, or ask their admin to upgrade the operator.
+    ClientTooNew,
+}
+
+impl CliVersion {
+    /// How many minor versions the CLI can lag behind the operator before it's considered too
+    /// old.
+    ///
+    /// Derived from the release cadence: a minor version is released about every week (3.142.0
+    /// to 3.192.0 took ten months), so users are asked to upgrade a CLI that is most of a year
+    /// older than the operator. This is not a guarantee that older CLIs work, the features they
+    /// need are still checked against the operator's supported features.
+    pub const MAX_MINOR_VERSIONS_BEHIND: u64 = 50;
+
+    /// Checks whether this CLI can work with the operator of the given version.
+    ///
+    /// Only major and minor versions are considered. The CLI is too new only when its major version
+    /// is ahead of the operator's, since newer CLIs check the operator's features on their own.
+    pub fn is_compatible_with_operator(&self, operator_version: &Version) -> Compatibility {
+        let cli = &self.0;
+
+        match cli.major.cmp(&operator_version.major) {
+            Ordering::Less => Compatibility::ClientTooOld,
+            Ordering::Greater => Compatibility::ClientTooNew,
+            Ordering::Equal
+                if cli.minor + Self::MAX_MINOR_VERSIONS_BEHIND < operator_version.minor =>
+            {
+                Compatibility::ClientTooOld
+            }
+            Ordering::Equal => Compatibility::Ok,
+        }
+    }
+}
+
+impl FromStr for CliVersion {
+    type Err = semver::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Version::parse(s.trim()).map(Self)
+    }
+}
+
+impl fmt::Display for CliVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 /// Name of HTTP header containing client certificate.
 /// Sent with each request to the mirrord operator (if available) except:
 /// 1. Initial GET on the operator resource
 /// 2. User certificate request
 ///
 /// Required for making the target connection request.
+///
+/// The value is the DER-encoded certificate in standard base64, see [`encode_client_cert`] and
+/// [`decode_client_cert`].
 pub const CLIENT_CERT_HEADER: &str = "x-client-der";
 
+/// Errors that can occur when decoding the value of the [`CLIENT_CERT_HEADER`].
+#[derive(Debug, Error)]
+pub enum CertHeaderError {
+    #[error("client certificate header is empty")]
+    Empty,
+
+    #[error("client certificate header is not valid base64: {0}")]
+    InvalidBase64(#[from] base64::DecodeError),
+}
+
+/// Encodes DER certificate bytes into the value of the [`CLIENT_CERT_HEADER`].
+pub fn encode_client_cert(der: &[u8]) -> String {
+    general_purpose::STANDARD.encode(der)
+}
+
+/// Decodes the value of the [`CLIENT_CERT_HEADER`] back into DER certificate bytes.
+///
+/// Surrounding whitespace is ignored.
+pub fn decode_client_cert(header_value: &str) -> Result<Vec<u8>, CertHeaderError> {
+    let header_value = header_value.trim();
+    if header_value.is_empty() {
+        return Err(CertHeaderError::Empty);
+    }
+
+    Ok(general_purpose::STANDARD.decode(header_value)?)
+}
+
+/// How many hours before the client certificate expires users start getting warned about it, see
+/// [`client_cert_expiry_warning`].
+pub const CLIENT_CERT_EXPIRY_WARNING_HOURS: i64 = 24;
+
+/// Errors that can occur when reading the client certificate carried in the
+/// [`CLIENT_CERT_HEADER`], see [`client_cert_not_after`].
+#[derive(Debug, Error)]
+pub enum CertError {
+    #[error("failed to parse the client certificate: {0}")]
+    Parse(#[from] x509_parser::nom::Err<x509_parser::error::X509Error>),
+
+    #[error("client certificate is followed by {0} unexpected bytes")]
+    TrailingData(usize),
+
+    #[error("client certificate expiry timestamp `{0}` is out of range")]
+    NotAfterOutOfRange(i64),
+}
+
+/// Returns the end of the validity period (`notAfter`) of the DER-encoded client certificate,
+/// e.g. from [`decode_client_cert`].
+pub fn client_cert_not_after(der: &[u8]) -> Result<DateTime<Utc>, CertError> {
+    let (rest, certificate) = X509Certificate::from_der(der)?;
+    if !rest.is_empty() {
+        return Err(CertError::TrailingData(rest.len()));
+    }
+
+    let not_after = certificate.validity().not_after.timestamp();
+    DateTime::from_timestamp(not_after, 0).ok_or(CertError::NotAfterOutOfRange(not_after))
+}
+
+/// Returns a warning about the client certificate expiring soon, if it expires within
+/// [`CLIENT_CERT_EXPIRY_WARNING_HOURS`] from `now`.
+///
+/// The session fails to authenticate with the operator once the certificate expires.
+pub fn client_cert_expiry_warning(not_after: DateTime<Utc>, now: DateTime<Utc>) -> Option<String> {
+    let left = not_after.signed_duration_since(now);
+    if left > TimeDelta::hours(CLIENT_CERT_EXPIRY_WARNING_HOURS) {
+        return None;
+    }
+
+    let when = if left <= TimeDelta::zero() {
+        "has expired".to_owned()
+    } else if left.num_hours() > 0 {
+        format!("expires in {} hours", left.num_hours())
+    } else {
+        "expires in less than an hour".to_owned()
+    };
+
+    Some(format!(
+        "Your mirrord operator client certificate {when} (at {not_after}), requests to the \
+        operator will fail to authenticate after that, until a new certificate is requested by \
+        the next mirrord run."
+    ))
+}
+
 /// Name of HTTP header containing client hostname.
 /// Sent with each request to the mirrord operator (if available).
+///
+/// Unless overridden, the value is produced by [`default_client_hostname`].
 pub const CLIENT_HOSTNAME_HEADER: &str = "x-client-hostname";
 
 /// Name of HTTP header containing client name.
 /// Sent with each request to the mirrord operator (if available).
+///
+/// Unless overridden, the value is produced by [`default_client_name`].
 pub const CLIENT_NAME_HEADER: &str = "x-client-name";
 
+/// Returns the first of the given values that is not blank, trimmed.
+fn first_non_blank(values: impl IntoIterator<Item = String>) -> Option<String> {
+    values
+        .into_iter()
+        .map(|value| value.trim().to_owned())
+        .find(|value| value.is_empty().not())
+}
+
+/// Detects the hostname of the client machine, the default value of the
+/// [`CLIENT_HOSTNAME_HEADER`].
+///
+/// Checks the `HOSTNAME` and `COMPUTERNAME` (Windows) environment variables, and then the
+/// `/proc/sys/kernel/hostname` and `/etc/hostname` files. Returns [`None`] if none of them is
+/// available.
+pub fn default_client_hostname() -> Option<String> {
+    let from_env = ["HOSTNAME", "COMPUTERNAME"]
+        .into_iter()
+        .filter_map(|variable| std::env::var(variable).ok());
+    let from_files = ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .into_iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok());
+
+    first_non_blank(from_env.chain(from_files))
+}
+
+/// Detects the name of the user running the client, the default value of the
+/// [`CLIENT_NAME_HEADER`].
+///
+/// Checks the `USER`, `USERNAME` (Windows) and `LOGNAME` environment variables. Returns [`None`]
+/// if none of them is set.
+pub fn default_client_name() -> Option<String> {
+    first_non_blank(
+        ["USER", "USERNAME", "LOGNAME"]
+            .into_iter()
+            .filter_map(|variable| std::env::var(variable).ok()),
+    )
+}
+
+/// Name of HTTP header containing client platform.
+/// Sent with each request to the mirrord operator (if available).
+///
+/// The value is produced by [`client_platform_value`].
+pub const CLIENT_PLATFORM_HEADER: &str = "x-client-platform";
+
+/// Produces the value of the [`CLIENT_PLATFORM_HEADER`], e.g. `linux/x86_64; cli=3.150.0`.
+///
+/// Returns [`None`] when the OS or the architecture of the client is unknown.
+pub fn client_platform_value(cli_version: &str) -> Option<String> {
+    let (os, arch) = (std::env::consts::OS, std::env::consts::ARCH);
+    if os.is_empty() || arch.is_empty() {
+        return None;
+    }
+
+    Some(format!("{os}/{arch}; cli={cli_version}"))
+}
+
 /// Name of HTTP header containing operator session id.
 /// Sent with target connection request.
+///
+/// The value is a [`SessionId`] in decimal.
 pub const SESSION_ID_HEADER: &str = "x-session-id";
 
+/// Name of HTTP header containing the trace id of the mirrord session.
+/// Sent with each request to the mirrord operator, so that the operator can include it in its logs.
+///
+/// The value is a [`TraceId`].
+pub const MIRRORD_TRACE_ID_HEADER: &str = "x-mirrord-trace-id";
+
+/// Id used to correlate the logs of a mirrord session across the CLI, the operator and the agent.
+///
+/// Generated randomly by the client with [`TraceId::generate`], and sent in the
+/// [`MIRRORD_TRACE_ID_HEADER`] as a hyphenated UUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceId(pub Uuid);
+
+/// Errors that can occur when reading a [`TraceId`] from the [`MIRRORD_TRACE_ID_HEADER`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TraceIdError {
+    #[error("trace id is not a valid UUID: {0}")]
+    InvalidUuid(#[from] uuid::Error),
+
+    #[error("trace id header value is not visible ASCII")]
+    InvalidHeaderValue,
+}
+
+impl TraceId {
+    /// Generates a new random id, should be done once per session.
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4())
+    }
+
+    /// Reads the id from the [`MIRRORD_TRACE_ID_HEADER`], returns [`None`] if the header is
+    /// missing.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, TraceIdError> {
+        headers
+            .get(header::MIRRORD_TRACE_ID)
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_| TraceIdError::InvalidHeaderValue)?
+                    .parse()
+            })
+            .transpose()
+    }
+
+    /// Value of the [`MIRRORD_TRACE_ID_HEADER`].
+    pub fn header_value(self) -> HeaderValue {
+        let mut buffer = Uuid::encode_buffer();
+        let hyphenated = self.0.hyphenated().encode_lower(&mut buffer);
+
+        HeaderValue::from_str(hyphenated).expect("hyphenated UUID is a valid header value")
+    }
+}
+
+impl FromStr for TraceId {
+    type Err = TraceIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(Uuid::try_parse(s.trim())?))
+    }
+}
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.hyphenated().fmt(f)
+    }
+}
+
+/// Id of an operator session, generated randomly by the client.
+///
+/// Sent in the [`SESSION_ID_HEADER`] as a decimal number. Note that CRDs store it in hex instead,
+/// see [`SessionId::from_hex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionId(pub u64);
+
+/// Errors that can occur when parsing a [`SessionId`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SessionIdError {
+    #[error("session id is empty")]
+    Empty,
+
+    #[error("session id is {0} characters long, at most {max} are allowed", max = SessionId::MAX_LEN)]
+    TooLong(usize),
+
+    #[error("session id contains invalid character {0:?}")]
+    InvalidCharacter(char),
+
+    #[error("session id does not fit in 64 bits")]
+    OutOfRange,
+
+    #[error("session id header value is not visible ASCII")]
+    InvalidHeaderValue,
+}
+
+impl SessionId {
+    /// Length of [`u64::MAX`] in decimal.
+    pub const MAX_LEN: usize = 20;
+
+    /// Parses the hex representation used in CRDs, e.g. in
+    /// [`CopyTargetCrd`](crate::crd::copy_target::CopyTargetCrd).
+    pub fn from_hex(s: &str) -> Result<Self, SessionIdError> {
+        Self::parse(s, 16)
+    }
+
+    /// Formats the id the way CRDs store it, see [`SessionId::from_hex`].
+    pub fn to_hex(self) -> String {
+        format!("{:X}", self.0)
+    }
+
+    fn parse(s: &str, radix: u32) -> Result<Self, SessionIdError> {
+        if s.is_empty() {
+            return Err(SessionIdError::Empty);
+        }
+
+        if let Some(invalid) = s.chars().find(|c| !c.is_digit(radix)) {
+            return Err(SessionIdError::InvalidCharacter(invalid));
+        }
+
+        if radix == 10 && s.len() > Self::MAX_LEN {
+            return Err(SessionIdError::TooLong(s.len()));
+        }
+
+        u64::from_str_radix(s, radix)
+            .map(Self)
+            .map_err(|_| SessionIdError::OutOfRange)
+    }
+
+    /// Reads the id from the [`SESSION_ID_HEADER`], returns [`None`] if the header is missing.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, SessionIdError> {
+        headers
+            .get(header::SESSION_ID)
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_| SessionIdError::InvalidHeaderValue)?
+                    .parse()
+            })
+            .transpose()
+    }
+
+    /// Sets the [`SESSION_ID_HEADER`] to this id, replacing any previous value.
+    pub fn insert_into(self, headers: &mut HeaderMap) {
+        headers.insert(header::SESSION_ID, HeaderValue::from(self.0));
+    }
+}
+
+impl FromStr for SessionId {
+    type Err = SessionIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, 10)
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for SessionId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Name of HTTP header containing the namespace of the target.
+/// Sent with target connection request.
+///
+/// The value is a [`TargetNamespace`].
+pub const X_MIRRORD_TARGET_NAMESPACE_HEADER: &str = "x-mirrord-target-namespace";
+
+/// Namespace of the target the client connects to, a valid DNS-1123 label.
+///
+/// Sent in the [`X_MIRRORD_TARGET_NAMESPACE_HEADER`], so that the operator does not have to dig
+/// it out of the request path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TargetNamespace(String);
+
+/// Errors that can occur when parsing a [`TargetNamespace`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TargetNamespaceError {
+    #[error("target namespace is empty")]
+    Empty,
+
+    #[error("target namespace is {0} characters long, at most {max} are allowed", max = TargetNamespace::MAX_LEN)]
+    TooLong(usize),
+
+    #[error(
+        "target namespace contains invalid character {0:?}, only lowercase alphanumeric \
+        characters and '-' are allowed"
+    )]
+    InvalidCharacter(char),
+
+    #[error("target namespace must start and end with an alphanumeric character")]
+    InvalidBoundary,
+
+    #[error("target namespace header value is not visible ASCII")]
+    InvalidHeaderValue,
+}
+
+impl TargetNamespace {
+    /// Max length of a DNS-1123 label.
+    pub const MAX_LEN: usize = 63;
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Reads the namespace from the [`X_MIRRORD_TARGET_NAMESPACE_HEADER`], returns [`None`] if
+    /// the header is missing.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, TargetNamespaceError> {
+        headers
+            .get(header::TARGET_NAMESPACE)
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_| TargetNamespaceError::InvalidHeaderValue)?
+                    .parse()
+            })
+            .transpose()
+    }
+
+    /// Sets the [`X_MIRRORD_TARGET_NAMESPACE_HEADER`] to this namespace, replacing any previous
+    /// value.
+    pub fn insert_into(&self, headers: &mut HeaderMap) {
+        headers.insert(header::TARGET_NAMESPACE, self.header_value());
+    }
+
+    /// Value of the [`X_MIRRORD_TARGET_NAMESPACE_HEADER`].
+    pub fn header_value(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.0).expect("DNS-1123 label is a valid header value")
+    }
+}
+
+impl FromStr for TargetNamespace {
+    type Err = TargetNamespaceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(TargetNamespaceError::Empty);
+        }
+
+        if s.len() > Self::MAX_LEN {
+            return Err(TargetNamespaceError::TooLong(s.len()));
+        }
+
+        if let Some(invalid) = s
+            .chars()
+            .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-'))
+        {
+            return Err(TargetNamespaceError::InvalidCharacter(invalid));
+        }
+
+        if s.starts_with('-') || s.ends_with('-') {
+            return Err(TargetNamespaceError::InvalidBoundary);
+        }
+
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl fmt::Display for TargetNamespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for TargetNamespace {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for TargetNamespace {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// [`HeaderName`] counterparts of the `*_HEADER` constants, so that call sites don't have to parse
+/// the names themselves.
+pub mod header {
+    use http::HeaderName;
+
+    /// See [`MIRRORD_CLI_VERSION_HEADER`](super::MIRRORD_CLI_VERSION_HEADER).
+    pub const MIRRORD_CLI_VERSION: HeaderName =
+        HeaderName::from_static(super::MIRRORD_CLI_VERSION_HEADER);
+
+    /// See [`CLIENT_CERT_HEADER`](super::CLIENT_CERT_HEADER).
+    pub const CLIENT_CERT: HeaderName = HeaderName::from_static(super::CLIENT_CERT_HEADER);
+
+    /// See [`CLIENT_HOSTNAME_HEADER`](super::CLIENT_HOSTNAME_HEADER).
+    pub const CLIENT_HOSTNAME: HeaderName = HeaderName::from_static(super::CLIENT_HOSTNAME_HEADER);
+
+    /// See [`CLIENT_NAME_HEADER`](super::CLIENT_NAME_HEADER).
+    pub const CLIENT_NAME: HeaderName = HeaderName::from_static(super::CLIENT_NAME_HEADER);
+
+    /// See [`CLIENT_PLATFORM_HEADER`](super::CLIENT_PLATFORM_HEADER).
+    pub const CLIENT_PLATFORM: HeaderName = HeaderName::from_static(super::CLIENT_PLATFORM_HEADER);
+
+    /// See [`SESSION_ID_HEADER`](super::SESSION_ID_HEADER).
+    pub const SESSION_ID: HeaderName = HeaderName::from_static(super::SESSION_ID_HEADER);
+
+    /// See [`MIRRORD_TRACE_ID_HEADER`](super::MIRRORD_TRACE_ID_HEADER).
+    pub const MIRRORD_TRACE_ID: HeaderName =
+        HeaderName::from_static(super::MIRRORD_TRACE_ID_HEADER);
+
+    /// See [`X_MIRRORD_TARGET_NAMESPACE_HEADER`](super::X_MIRRORD_TARGET_NAMESPACE_HEADER).
+    pub const TARGET_NAMESPACE: HeaderName =
+        HeaderName::from_static(super::X_MIRRORD_TARGET_NAMESPACE_HEADER);
+}
+
+/// Headers that the mirrord CLI sends to the mirrord operator.
+///
+/// Unlike the `*_HEADER` constants (which are the [`OperatorHeader::as_str`] results), can be
+/// iterated with [`OperatorHeader::all`], e.g. to log which of the headers are present in a
+/// request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperatorHeader {
+    /// [`MIRRORD_CLI_VERSION_HEADER`]
+    CliVersion,
+    /// [`CLIENT_CERT_HEADER`]
+    ClientCert,
+    /// [`CLIENT_HOSTNAME_HEADER`]
+    ClientHostname,
+    /// [`CLIENT_NAME_HEADER`]
+    ClientName,
+    /// [`CLIENT_PLATFORM_HEADER`]
+    ClientPlatform,
+    /// [`SESSION_ID_HEADER`]
+    SessionId,
+    /// [`MIRRORD_TRACE_ID_HEADER`]
+    TraceId,
+    /// [`X_MIRRORD_TARGET_NAMESPACE_HEADER`]
+    TargetNamespace,
+}
+
+impl OperatorHeader {
+    const ALL: [Self; 8] = [
+        Self::CliVersion,
+        Self::ClientCert,
+        Self::ClientHostname,
+        Self::ClientName,
+        Self::ClientPlatform,
+        Self::SessionId,
+        Self::TraceId,
+        Self::TargetNamespace,
+    ];
+
+    /// All the headers, in declaration order.
+    pub fn all() -> impl Iterator<Item = Self> {
+        Self::ALL.into_iter()
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::CliVersion => MIRRORD_CLI_VERSION_HEADER,
+            Self::ClientCert => CLIENT_CERT_HEADER,
+            Self::ClientHostname => CLIENT_HOSTNAME_HEADER,
+            Self::ClientName => CLIENT_NAME_HEADER,
+            Self::ClientPlatform => CLIENT_PLATFORM_HEADER,
+            Self::SessionId => SESSION_ID_HEADER,
+            Self::TraceId => MIRRORD_TRACE_ID_HEADER,
+            Self::TargetNamespace => X_MIRRORD_TARGET_NAMESPACE_HEADER,
+        }
+    }
+
+    /// Name of this header as sent to the operator, and as it should appear in logs.
+    ///
+    /// Header names are case-insensitive, but all of ours are declared as lowercase tokens, which
+    /// is checked at compile time (see [`is_lowercase_token`]).
+    pub const fn normalized_name(self) -> &'static str {
+        let name = self.as_str();
+        debug_assert!(is_lowercase_token(name));
+        name
+    }
+
+    /// [`HeaderName`] of this header, see the [`header`] module.
+    pub const fn header_name(self) -> HeaderName {
+        match self {
+            Self::CliVersion => header::MIRRORD_CLI_VERSION,
+            Self::ClientCert => header::CLIENT_CERT,
+            Self::ClientHostname => header::CLIENT_HOSTNAME,
+            Self::ClientName => header::CLIENT_NAME,
+            Self::ClientPlatform => header::CLIENT_PLATFORM,
+            Self::SessionId => header::SESSION_ID,
+            Self::TraceId => header::MIRRORD_TRACE_ID,
+            Self::TargetNamespace => header::TARGET_NAMESPACE,
+        }
+    }
+
+    /// The headers present in the given [`HeaderMap`].
+    pub fn present_in(headers: &HeaderMap) -> impl Iterator<Item = Self> + '_ {
+        Self::all().filter(|header| headers.contains_key(header.as_str()))
+    }
+}
+
+impl fmt::Display for OperatorHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.normalized_name())
+    }
+}
+
+/// Fails the build if any of the [`OperatorHeader`] names is not a lowercase token.
+const _: () = {
+    let mut i = 0;
+    while i < OperatorHeader::ALL.len() {
+        assert!(
+            is_lowercase_token(OperatorHeader::ALL[i].as_str()),
+            "operator header names must be lowercase tokens"
+        );
+        i += 1;
+    }
+};
+
+/// Returns whether `name` is a valid header name token
+/// ([RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#name-tokens)) without uppercase letters.
+const fn is_lowercase_token(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    if bytes.is_empty() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if !matches!(
+            bytes[i],
+            b'a'..=b'z'
+                | b'0'..=b'9'
+                | b'!'
+                | b'#'
+                | b'$'
+                | b'%'
+                | b'&'
+                | b'\''
+                | b'*'
+                | b'+'
+                | b'-'
+                | b'.'
+                | b'^'
+                | b'_'
+                | b'`'
+                | b'|'
+                | b'~'
+        ) {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// Builds the [`HeaderMap`] sent with requests to the mirrord operator.
+///
+/// Client name and hostname default to [`default_client_name`] and [`default_client_hostname`].
+///
+/// Client name, hostname and platform come from the user's machine, so instead of failing the
+/// request, they are sanitized (see [`HeaderSet::clean_value`]) and skipped entirely if nothing is
+/// left of them.
+#[derive(Debug, Clone)]
+pub struct HeaderSet<'a> {
+    cli_version: &'a str,
+    client_cert: Option<HeaderValue>,
+    hostname: Option<Cow<'a, str>>,
+    name: Option<Cow<'a, str>>,
+    platform: Option<&'a str>,
+    trace_id: Option<TraceId>,
+    target_namespace: Option<&'a TargetNamespace>,
+}
+
+impl<'a> HeaderSet<'a> {
+    /// Starts a set with the [`MIRRORD_CLI_VERSION_HEADER`], and the default
+    /// [`CLIENT_HOSTNAME_HEADER`] and [`CLIENT_NAME_HEADER`] (if they can be detected).
+    pub fn new(cli_version: &'a str) -> Self {
+        Self {
+            cli_version,
+            client_cert: None,
+            hostname: default_client_hostname().map(Cow::Owned),
+            name: default_client_name().map(Cow::Owned),
+            platform: None,
+            trace_id: None,
+            target_namespace: None,
+        }
+    }
+
+    /// Adds the [`CLIENT_CERT_HEADER`] with a certificate already encoded with
+    /// [`encode_client_cert`].
+    pub fn client_cert(mut self, client_cert: HeaderValue) -> Self {
+        self.client_cert = Some(client_cert);
+        self
+    }
+
+    /// Overrides the [`default_client_hostname`] in the [`CLIENT_HOSTNAME_HEADER`], if the
+    /// hostname is known.
+    pub fn hostname(mut self, hostname: Option<&'a str>) -> Self {
+        if let Some(hostname) = hostname {
+            self.hostname = Some(Cow::Borrowed(hostname));
+        }
+        self
+    }
+
+    /// Overrides the [`default_client_name`] in the [`CLIENT_NAME_HEADER`], if the name is known.
+    pub fn name(mut self, name: Option<&'a str>) -> Self {
+        if let Some(name) = name {
+            self.name = Some(Cow::Borrowed(name));
+        }
+        self
+    }
+
+    /// Adds the [`CLIENT_PLATFORM_HEADER`], if the platform is known, see
+    /// [`client_platform_value`].
+    pub fn platform(mut self, platform: Option<&'a str>) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Adds the [`MIRRORD_TRACE_ID_HEADER`].
+    pub fn trace_id(mut self, trace_id: TraceId) -> Self {
+        self.trace_id = Some(trace_id);
+        self
+    }
+
+    /// Adds the [`X_MIRRORD_TARGET_NAMESPACE_HEADER`], if the request targets a namespace.
+    pub fn target_namespace(mut self, target_namespace: Option<&'a TargetNamespace>) -> Self {
+        self.target_namespace = target_namespace;
+        self
+    }
+
+    /// Makes the given value safe to send in a header: non-ASCII characters are dropped, control
+    /// characters (e.g. a newline, which would otherwise break the request) are replaced with
+    /// spaces, and the result is trimmed.
+    ///
+    /// Returns [`None`] if the result is empty or still not a valid [`HeaderValue`].
+    fn clean_value(raw: &str) -> Option<HeaderValue> {
+        let cleaned = raw
+            .chars()
+            .filter(char::is_ascii)
+            .map(|c| if c.is_ascii_control() { ' ' } else { c })
+            .collect::<String>();
+        let cleaned = cleaned.trim();
+
+        if cleaned.is_empty() {
+            return None;
+        }
+
+        HeaderValue::from_str(cleaned).ok()
+    }
+
+    pub fn build(self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Ok(version) = HeaderValue::from_str(self.cli_version) {
+            headers.insert(header::MIRRORD_CLI_VERSION, version);
+        }
+
+        if let Some(name) = self.name.as_deref().and_then(Self::clean_value) {
+            headers.insert(header::CLIENT_NAME, name);
+        }
+
+        if let Some(hostname) = self.hostname.as_deref().and_then(Self::clean_value) {
+            headers.insert(header::CLIENT_HOSTNAME, hostname);
+        }
+
+        if let Some(platform) = self.platform.and_then(Self::clean_value) {
+            headers.insert(header::CLIENT_PLATFORM, platform);
+        }
+
+        if let Some(trace_id) = self.trace_id {
+            headers.insert(header::MIRRORD_TRACE_ID, trace_id.header_value());
+        }
+
+        if let Some(target_namespace) = self.target_namespace {
+            target_namespace.insert_into(&mut headers);
+        }
+
+        if let Some(client_cert) = self.client_cert {
+            headers.insert(header::CLIENT_CERT, client_cert);
+        }
+
+        headers
+    }
+}
+
 /// Code returned in error responses from the operator, when reconnecting to a session is no longer
 /// possible.
 ///
@@ -47,6 +1678,110 @@ pub const RECONNECT_NOT_POSSIBLE_CODE: u16 = 410;
 /// longer possible.
 pub const RECONNECT_NOT_POSSIBLE_REASON: &str = "ReconnectNotPossible";
 
+/// Returns whether an error response with the given status and reason means that reconnecting to
+/// the session is no longer possible, see [`ReconnectError`].
+pub fn is_reconnect_not_possible(status: StatusCode, reason: &str) -> bool {
+    status.as_u16() == RECONNECT_NOT_POSSIBLE_CODE && reason == RECONNECT_NOT_POSSIBLE_REASON
+}
+
+/// Body of the error response returned by the operator when reconnecting to a session is no longer
+/// possible, e.g. because the session has expired.
+///
+/// Has the shape of a Kubernetes `Status`, so that clients can read it as a regular API error.
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+#[error("your mirrord session has expired ({message}), please restart mirrord")]
+pub struct ReconnectError {
+    /// Always `Failure`.
+    pub status: String,
+    pub message: String,
+    /// Always [`RECONNECT_NOT_POSSIBLE_REASON`].
+    pub reason: String,
+    /// Always [`RECONNECT_NOT_POSSIBLE_CODE`].
+    pub code: u16,
+}
+
+impl ReconnectError {
+    /// Builds the error response, `message` explains why the session is gone.
+    pub fn new<M: Into<String>>(message: M) -> Self {
+        Self {
+            status: "Failure".to_owned(),
+            message: message.into(),
+            reason: RECONNECT_NOT_POSSIBLE_REASON.to_owned(),
+            code: RECONNECT_NOT_POSSIBLE_CODE,
+        }
+    }
+
+    /// Status of the HTTP response carrying this error.
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::GONE
+    }
+}
+
+/// How the client retries connecting to a session after the connection drops.
+///
+/// Delays grow exponentially from [`Self::base_delay`], up to [`Self::max_delay`], and each one is
+/// shortened by a random fraction of up to [`Self::jitter`], so that clients disconnected at the
+/// same time don't all reconnect at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt.
+    pub base_delay: Duration,
+    /// Upper bound for the delay before any attempt.
+    pub max_delay: Duration,
+    /// Fraction of each delay (`0.0..=1.0`) that may be randomly cut off.
+    pub jitter: f64,
+    /// Number of attempts after which the client gives up.
+    pub max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+    /// 1s, 2s, 4s, 8s, 8s, ... for 10 attempts, with 20% jitter.
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(8),
+            jitter: 0.2,
+            max_attempts: 10,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns how long to wait before the given `attempt` (counted from `0`), or [`None`] if the
+    /// client should give up.
+    ///
+    /// `status` is the status of the error response that failed the previous attempt, if there
+    /// was one. [`RECONNECT_NOT_POSSIBLE_CODE`] means that the session is permanently gone, so
+    /// there is no point in retrying.
+    #[cfg(feature = "client")]
+    pub fn next_delay(&self, attempt: u32, status: Option<StatusCode>) -> Option<Duration> {
+        self.jittered_delay(attempt, status, rand::random::<f64>())
+    }
+
+    /// Works like [`Self::next_delay`], but takes the random `sample` (`0.0..=1.0`) that decides
+    /// how much of the delay is cut off.
+    pub fn jittered_delay(
+        &self,
+        attempt: u32,
+        status: Option<StatusCode>,
+        sample: f64,
+    ) -> Option<Duration> {
+        if status.is_some_and(|status| status.as_u16() == RECONNECT_NOT_POSSIBLE_CODE)
+            || attempt >= self.max_attempts
+        {
+            return None;
+        }
+
+        let delay = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let cut_off = self.jitter.clamp(0.0, 1.0) * sample.clamp(0.0, 1.0);
+
+        Some(delay.mul_f64(1.0 - cut_off))
+    }
+}
+
 /// Kubernetes label key identifying resources owned by the mirrord operator.
 pub const OPERATOR_OWNERSHIP_LABEL: &str = "operator.metalbear.co/owner";
 
@@ -56,3 +1791,947 @@ pub const OPERATOR_ISOLATION_MARKER_ENV: &str = "OPERATOR_ISOLATION_MARKER";
 /// Default value for the [`OPERATOR_OWNERSHIP_LABEL`] when
 /// [`OPERATOR_ISOLATION_MARKER_ENV`] is not set.
 pub const DEFAULT_OPERATOR_ISOLATION_MARKER: &str = "mirrord-operator";
+
+/// Returns the operator isolation marker: the value of [`OPERATOR_ISOLATION_MARKER_ENV`] if set,
+/// otherwise [`DEFAULT_OPERATOR_ISOLATION_MARKER`].
+///
+/// Both the CLI and the operator must use this, so that they agree on the marker.
+pub fn resolve_isolation_marker() -> String {
+    resolve_isolation_marker_with(|name| std::env::var(name).ok())
+}
+
+/// Like [`resolve_isolation_marker`], but reads the environment with the given `env` function.
+///
+/// An empty marker is treated as unset.
+pub fn resolve_isolation_marker_with<F>(env: F) -> String
+where
+    F: FnOnce(&str) -> Option<String>,
+{
+    env(OPERATOR_ISOLATION_MARKER_ENV)
+        .filter(|marker| !marker.is_empty())
+        .unwrap_or_else(|| DEFAULT_OPERATOR_ISOLATION_MARKER.to_owned())
+}
+
+/// Qualifies the isolation `marker` with the `namespace` of the operator, e.g.
+/// `mirrord-operator.staging`.
+///
+/// Lets operators installed with the same marker in different namespaces of one cluster (e.g. dev
+/// and staging) tell their resources apart. Pass the result wherever a marker is expected, e.g.
+/// to [`ownership_labels`] and [`matches_owner`]. The result is used as a label value, so it
+/// should not be longer than 63 characters.
+pub fn namespaced_isolation_marker(marker: &str, namespace: &str) -> String {
+    format!("{marker}.{namespace}")
+}
+
+/// Returns the [`OPERATOR_OWNERSHIP_LABEL`] key along with its value, the resolved isolation
+/// marker (see [`resolve_isolation_marker`]).
+pub fn ownership_label_value() -> (&'static str, String) {
+    (OPERATOR_OWNERSHIP_LABEL, resolve_isolation_marker())
+}
+
+/// Kubernetes label selector matching resources owned by the operator with the given isolation
+/// `marker`, e.g. `operator.metalbear.co/owner=mirrord-operator`.
+pub fn ownership_label_selector(marker: &str) -> String {
+    format!("{OPERATOR_OWNERSHIP_LABEL}={marker}")
+}
+
+/// Labels marking a new resource as owned by the operator with the given isolation `marker`,
+/// which can be qualified with [`namespaced_isolation_marker`].
+///
+/// Resources labeled with these are matched by [`ownership_label_selector`] and
+/// [`matches_owner`].
+pub fn ownership_labels(marker: &str) -> BTreeMap<String, String> {
+    BTreeMap::from([(OPERATOR_OWNERSHIP_LABEL.to_owned(), marker.to_owned())])
+}
+
+/// Checks whether a resource with the given `labels` is owned by the operator with the given
+/// isolation `marker`.
+///
+/// The marker must match exactly, so a resource labeled with a
+/// [`namespaced_isolation_marker`] is not owned by an operator using the plain marker, and the
+/// other way around. Resources without the [`OPERATOR_OWNERSHIP_LABEL`] are not owned by any
+/// operator.
+pub fn matches_owner(labels: &BTreeMap<String, String>, marker: &str) -> bool {
+    labels
+        .get(OPERATOR_OWNERSHIP_LABEL)
+        .is_some_and(|owner| owner == marker)
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Not;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    fn license_json(features: &str) -> String {
+        format!(
+            r#"{{
+                "name": "team",
+                "organization": "metalbear",
+                "expire_at": "2030-01-01",
+                "fingerprint": null,
+                "subscription_id": null{features}
+            }}"#
+        )
+    }
+
+    #[test]
+    fn license_features_known_and_unknown() {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json(
+            r#", "features": ["copy-target", "time-travel"]"#,
+        ))
+        .unwrap();
+
+        assert!(license.allows(&LicenseFeature::CopyTarget));
+        assert!(license.allows(&LicenseFeature::Unknown("time-travel".into())));
+        assert!(license.allows(&LicenseFeature::MultiPod).not());
+
+        let serialized = serde_json::to_value(&license).unwrap();
+        assert_eq!(
+            serialized["features"],
+            serde_json::json!(["copy-target", "time-travel"])
+        );
+    }
+
+    /// Self-signed DER certificate valid until the end of 2029.
+    fn client_cert_der() -> Vec<u8> {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["user".to_string()]).unwrap();
+        params.not_after = rcgen::date_time_ymd(2030, 1, 1);
+        params.self_signed(&key_pair).unwrap().der().to_vec()
+    }
+
+    #[test]
+    fn client_cert_not_after_from_header() {
+        let der = client_cert_der();
+        let decoded = decode_client_cert(&encode_client_cert(&der)).unwrap();
+
+        assert_eq!(
+            client_cert_not_after(&decoded).unwrap(),
+            "2030-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn client_cert_not_after_malformed() {
+        assert!(matches!(
+            client_cert_not_after(b"not a certificate"),
+            Err(CertError::Parse(..))
+        ));
+
+        let mut der = client_cert_der();
+        der.truncate(der.len() / 2);
+        assert!(matches!(
+            client_cert_not_after(&der),
+            Err(CertError::Parse(..))
+        ));
+
+        let mut der = client_cert_der();
+        der.extend_from_slice(b"junk");
+        assert!(matches!(
+            client_cert_not_after(&der),
+            Err(CertError::TrailingData(4))
+        ));
+    }
+
+    #[rstest]
+    #[case::far_away("2029-12-30T00:00:00Z", None)]
+    #[case::window_start("2029-12-31T00:00:00Z", Some("expires in 24 hours"))]
+    #[case::last_hour("2029-12-31T23:30:00Z", Some("expires in less than an hour"))]
+    #[case::expired("2030-01-01T00:00:01Z", Some("has expired"))]
+    fn client_cert_expiry(#[case] now: &str, #[case] expected: Option<&str>) {
+        let not_after = "2030-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let warning = client_cert_expiry_warning(not_after, now.parse().unwrap());
+
+        match expected {
+            None => assert_eq!(warning, None),
+            Some(expected) => assert!(warning.unwrap().contains(expected)),
+        }
+    }
+
+    #[rstest]
+    #[case::date("2030-01-01")]
+    #[case::datetime_utc("2030-01-01T12:30:00Z")]
+    #[case::datetime_offset("2030-01-01T23:59:59.999+05:00")]
+    fn license_expire_at_date_or_datetime(#[case] expire_at: &str) {
+        let license = license_json("").replace("2030-01-01", expire_at);
+        let license: LicenseInfoOwned = serde_json::from_str(&license).unwrap();
+
+        assert_eq!(
+            license.expire_at,
+            NaiveDate::from_ymd_opt(2030, 1, 1).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&license).unwrap()["expire_at"],
+            "2030-01-01"
+        );
+    }
+
+    #[test]
+    fn license_expire_at_invalid() {
+        let license = license_json("").replace("2030-01-01", "01/01/2030");
+        assert!(serde_json::from_str::<LicenseInfoOwned>(&license).is_err());
+    }
+
+    #[rstest]
+    #[case::far_away("2029-12-01", None)]
+    #[case::window_start("2029-12-25", Some("in 7 days"))]
+    #[case::tomorrow("2029-12-31", Some("in 1 day"))]
+    #[case::today("2030-01-01", Some("today"))]
+    #[case::expired("2030-01-02", None)]
+    fn license_expiry_warning(#[case] today: NaiveDate, #[case] expected: Option<&str>) {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+
+        assert_eq!(
+            license.expiry_warning(today),
+            expected.map(|expires| format!(
+                "Your mirrord license `team` (org metalbear) expires {expires}"
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case::valid("2029-12-24", LicenseStatus::Valid)]
+    #[case::window_start("2029-12-25", LicenseStatus::ExpiringSoon)]
+    #[case::last_day("2030-01-01", LicenseStatus::ExpiringSoon)]
+    #[case::grace_start("2030-01-02", LicenseStatus::GracePeriod)]
+    #[case::grace_end("2030-01-04", LicenseStatus::GracePeriod)]
+    #[case::expired("2030-01-05", LicenseStatus::Expired)]
+    fn license_status(#[case] today: NaiveDate, #[case] expected: LicenseStatus) {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+
+        assert_eq!(
+            license.status(today, LICENSE_EXPIRY_WARNING_DAYS, 3),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::valid("2029-12-23", None)]
+    #[case::window_start("2029-12-25", Some(LicenseStatus::ExpiringSoon))]
+    #[case::expiring("2029-12-28", None)]
+    #[case::grace_start("2030-01-02", Some(LicenseStatus::GracePeriod))]
+    #[case::expired("2030-01-05", Some(LicenseStatus::Expired))]
+    #[case::after_expired("2030-01-06", None)]
+    fn license_transition_between(
+        #[case] today: NaiveDate,
+        #[case] expected: Option<LicenseStatus>,
+    ) {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+        let yesterday = today.pred_opt().unwrap();
+
+        assert_eq!(
+            license.transition_between(yesterday, today, LICENSE_EXPIRY_WARNING_DAYS, 3),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::paid("", "2029-12-01", None)]
+    #[case::trial(r#", "is_trial": true"#, "2029-12-01", Some(31))]
+    #[case::last_day(r#", "is_trial": true"#, "2030-01-01", Some(0))]
+    #[case::over(r#", "is_trial": true"#, "2030-01-05", Some(0))]
+    fn license_trial_days_left(
+        #[case] fields: &str,
+        #[case] today: NaiveDate,
+        #[case] expected: Option<i64>,
+    ) {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json(fields)).unwrap();
+
+        assert_eq!(license.trial_days_left(today), expected);
+    }
+
+    /// Licenses without `is_trial` are still recognized as trials by their name.
+    #[test]
+    fn license_trial_days_left_old_format() {
+        let mut license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+        license.name = "team (Trial)".to_owned();
+
+        assert!(license.is_trial.not());
+        assert!(license.is_trial_license());
+        assert_eq!(
+            license.trial_days_left("2029-12-01".parse().unwrap()),
+            Some(31)
+        );
+    }
+
+    #[rstest]
+    #[case::day_before("2029-12-31T23:59:59Z", false)]
+    #[case::end_of_day("2030-01-01T23:59:59Z", false)]
+    #[case::next_day("2030-01-02T00:00:00Z", true)]
+    fn license_expires_at_end_of_day_utc(#[case] now: DateTime<Utc>, #[case] expired: bool) {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+
+        assert_eq!(
+            license.expires_at_utc().to_rfc3339(),
+            "2030-01-01T23:59:59+00:00"
+        );
+        assert_eq!(license.is_expired_at(now), expired);
+    }
+
+    #[test]
+    fn license_fingerprint() {
+        let mut license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+        assert_eq!(
+            license.verify_fingerprint(),
+            Err(FingerprintError::Unverifiable)
+        );
+
+        let expected = license.expected_fingerprint();
+        assert_eq!(expected.len(), 64);
+
+        license.fingerprint = Some(expected.to_uppercase());
+        assert_eq!(license.verify_fingerprint(), Ok(()));
+
+        license.subscription_id = Some("sub".to_owned());
+        assert_eq!(
+            license.verify_fingerprint(),
+            Err(FingerprintError::Mismatch {
+                expected: license.expected_fingerprint(),
+                found: expected.to_uppercase(),
+            })
+        );
+    }
+
+    #[test]
+    fn license_errors() {
+        let mut license: LicenseInfoOwned =
+            serde_json::from_str(&license_json(r#", "features": ["copy-target"]"#)).unwrap();
+        let before_expiry = "2030-01-01T23:00:00Z".parse().unwrap();
+        let after_expiry = "2030-01-02T00:00:00Z".parse().unwrap();
+
+        assert_eq!(license.verify(before_expiry), Ok(()));
+        assert_eq!(
+            license.verify(after_expiry),
+            Err(LicenseError::Expired {
+                expire_at: NaiveDate::from_ymd_opt(2030, 1, 1).unwrap()
+            })
+        );
+
+        license.fingerprint = Some("bad".to_owned());
+        assert!(matches!(
+            license.verify(before_expiry),
+            Err(LicenseError::FingerprintMismatch(
+                FingerprintError::Mismatch { .. }
+            ))
+        ));
+
+        assert_eq!(license.require(&LicenseFeature::CopyTarget), Ok(()));
+        assert_eq!(
+            license.require(&LicenseFeature::MultiPod),
+            Err(LicenseError::FeatureNotLicensed(LicenseFeature::MultiPod))
+        );
+
+        assert_eq!(
+            parse_expire_at("2030-01-01T10:00:00+02:00"),
+            Ok(NaiveDate::from_ymd_opt(2030, 1, 1).unwrap())
+        );
+        assert!(matches!(
+            parse_expire_at("1st of January"),
+            Err(LicenseError::Parse(..))
+        ));
+    }
+
+    #[test]
+    fn license_cache_round_trip() {
+        let full = LicenseInfoOwned {
+            version: LICENSE_INFO_VERSION,
+            name: "team".to_owned(),
+            organization: "metalbear".to_owned(),
+            expire_at: NaiveDate::from_ymd_opt(2030, 1, 1).unwrap(),
+            fingerprint: Some("abcd".to_owned()),
+            subscription_id: Some("sub".to_owned()),
+            features: BTreeSet::from([
+                LicenseFeature::CopyTarget,
+                LicenseFeature::Unknown("time-travel".to_owned()),
+            ]),
+            max_concurrent_sessions: Some(5),
+            is_trial: true,
+        };
+        let minimal: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+
+        for license in [full, minimal] {
+            assert_eq!(
+                LicenseInfoOwned::from_cache_bytes(&license.to_cache_bytes()),
+                Ok(Some(license))
+            );
+        }
+    }
+
+    #[test]
+    fn license_cache_compatibility() {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+
+        // Records added by a newer version are skipped.
+        let mut bytes = license.to_cache_bytes();
+        bytes.extend_from_slice(&[u8::MAX, 2, 0, 0, 0, 1, 2]);
+        assert_eq!(
+            LicenseInfoOwned::from_cache_bytes(&bytes),
+            Ok(Some(license.clone()))
+        );
+
+        // Caches in a newer format are a miss.
+        let mut bytes = license.to_cache_bytes();
+        bytes[LICENSE_CACHE_MAGIC.len()] = LICENSE_CACHE_FORMAT + 1;
+        assert_eq!(LicenseInfoOwned::from_cache_bytes(&bytes), Ok(None));
+
+        let bytes = license.to_cache_bytes();
+        for corrupted in [&bytes[..bytes.len() - 1], &bytes[1..], &[]] {
+            assert!(matches!(
+                LicenseInfoOwned::from_cache_bytes(corrupted),
+                Err(LicenseError::Parse(..))
+            ));
+        }
+    }
+
+    #[rstest]
+    #[case::unlimited("", 1000, true)]
+    #[case::below_limit(r#", "max_concurrent_sessions": 5"#, 4, true)]
+    #[case::at_limit(r#", "max_concurrent_sessions": 5"#, 5, false)]
+    #[case::no_sessions_allowed(r#", "max_concurrent_sessions": 0"#, 0, false)]
+    fn license_concurrent_sessions(
+        #[case] extra: &str,
+        #[case] active: u32,
+        #[case] allowed: bool,
+    ) {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json(extra)).unwrap();
+
+        assert_eq!(license.allows_new_session(active), allowed);
+    }
+
+    #[rstest]
+    #[case::in_days("2029-12-29", "expires in 3 days")]
+    #[case::tomorrow("2029-12-31", "expires in 1 day")]
+    #[case::today("2030-01-01", "expires today")]
+    #[case::yesterday("2030-01-02", "expired 1 day ago")]
+    #[case::expired("2030-01-11", "expired 10 days ago")]
+    fn license_summary(#[case] today: NaiveDate, #[case] expiry: &str) {
+        let mut license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+
+        assert_eq!(
+            license.summary(today),
+            format!(
+                "Operator License\n    \
+                name: team\n    \
+                organization: metalbear\n    \
+                expire at: 1-Jan-2030 ({expiry})\n    \
+                subscription id: <none>\n    \
+                fingerprint: missing"
+            )
+        );
+
+        license.subscription_id = Some("sub_123".to_owned());
+        license.fingerprint = Some("abc".to_owned());
+        let summary = license.summary(today);
+        assert!(summary.contains("subscription id: sub_123"));
+        assert!(summary.contains("fingerprint: present"));
+    }
+
+    #[test]
+    fn redacted_license_info() {
+        let mut license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+        license.subscription_id = Some("sub_1PqR5tUvWxYz".to_owned());
+        license.fingerprint = Some(license.expected_fingerprint());
+
+        let redacted = RedactedLicenseInfo::from(&license);
+        assert_eq!(redacted.name, "team");
+        assert_eq!(redacted.organization, "metalbear");
+        assert_eq!(redacted.expire_at, license.expire_at);
+
+        let serialized = serde_json::to_string(&redacted).unwrap();
+        assert!(
+            serialized.contains("sub_1PqR5tUvWxYz").not(),
+            "{serialized}"
+        );
+        assert!(
+            serialized
+                .contains(license.fingerprint.as_deref().unwrap())
+                .not(),
+            "{serialized}"
+        );
+        assert!(serialized.contains("2030-01-01"), "{serialized}");
+
+        let subscription_id = redacted.subscription_id.unwrap();
+        assert_eq!(
+            subscription_id.len(),
+            "sha256:".len() + RedactedLicenseInfo::REDACTED_LEN
+        );
+        assert_eq!(
+            RedactedLicenseInfo::from(&license).subscription_id,
+            Some(subscription_id)
+        );
+
+        license.subscription_id = None;
+        assert_eq!(RedactedLicenseInfo::from(&license).subscription_id, None);
+    }
+
+    #[rstest]
+    #[case::missing("", 0, false)]
+    #[case::current(r#", "version": 1"#, 1, false)]
+    #[case::newer(r#", "version": 2, "seats": 10"#, 2, true)]
+    fn license_version(#[case] extra: &str, #[case] version: u32, #[case] warns: bool) {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json(extra)).unwrap();
+
+        assert_eq!(license.version, version);
+        assert_eq!(license.newer_version_warning().is_some(), warns);
+    }
+
+    #[test]
+    fn license_features_missing() {
+        let license: LicenseInfoOwned = serde_json::from_str(&license_json("")).unwrap();
+        assert!(license.features.is_empty());
+    }
+
+    #[test]
+    fn operator_capabilities_known_and_unknown() {
+        let capabilities: OperatorCapabilities = serde_json::from_str(
+            r#"{
+                "features": ["steal-http-filters", "time-travel"],
+                "protocol_versions": { "mirrord-protocol": "1.32.0" },
+                "added_later": true
+            }"#,
+        )
+        .unwrap();
+
+        assert!(capabilities.supports(Capability::StealHttpFilters));
+        assert!(capabilities.supports(Capability::Unknown("time-travel".into())));
+        assert!(capabilities.supports(Capability::JaqFilters).not());
+        assert_eq!(
+            capabilities.protocol_version(MIRRORD_PROTOCOL),
+            Some(&Version::new(1, 32, 0))
+        );
+
+        let serialized = serde_json::to_value(&capabilities).unwrap();
+        assert_eq!(
+            serialized["features"],
+            serde_json::json!(["steal-http-filters", "time-travel"])
+        );
+        assert_eq!(
+            serde_json::from_value::<OperatorCapabilities>(serialized).unwrap(),
+            capabilities
+        );
+    }
+
+    #[test]
+    fn operator_capabilities_missing() {
+        let capabilities: OperatorCapabilities = serde_json::from_str("{}").unwrap();
+        assert_eq!(capabilities, OperatorCapabilities::default());
+        assert!(capabilities.supports(Capability::CopyTarget).not());
+        assert_eq!(capabilities.protocol_version(MIRRORD_PROTOCOL), None);
+    }
+
+    #[test]
+    fn license_feature_schema_lists_known_values() {
+        let schema = serde_json::to_string(&schemars::schema_for!(LicenseFeature)).unwrap();
+
+        for feature in LicenseFeature::KNOWN {
+            assert!(
+                schema.contains(feature.as_str()),
+                "{feature} missing in {schema}"
+            );
+        }
+    }
+
+    #[test]
+    fn header_set_cleans_and_skips_values() {
+        let headers = HeaderSet::new("3.150.0")
+            .name(Some(" Zoë Bear "))
+            .hostname(Some("bear\r\nx-injected: yes"))
+            .platform(Some("linux/x86_64; cli=3.150.0"))
+            .client_cert(HeaderValue::from_static("Y2VydA=="))
+            .build();
+
+        assert_eq!(headers[header::MIRRORD_CLI_VERSION], "3.150.0");
+        assert_eq!(headers[header::CLIENT_NAME], "Zo Bear");
+        assert_eq!(headers[header::CLIENT_HOSTNAME], "bear  x-injected: yes");
+        assert!(headers.contains_key("x-injected").not());
+        assert_eq!(
+            headers[header::CLIENT_PLATFORM],
+            "linux/x86_64; cli=3.150.0"
+        );
+        assert_eq!(headers[header::CLIENT_CERT], "Y2VydA==");
+        assert_eq!(headers.len(), 5);
+
+        let headers = HeaderSet::new("3.150.0")
+            .name(Some("\n\t "))
+            .hostname(Some("żółć"))
+            .build();
+        assert_eq!(
+            OperatorHeader::present_in(&headers).collect::<Vec<_>>(),
+            [OperatorHeader::CliVersion]
+        );
+    }
+
+    #[test]
+    fn header_set_default_identity() {
+        let headers = HeaderSet::new("3.150.0").build();
+        assert_eq!(
+            headers
+                .get(header::CLIENT_HOSTNAME)
+                .and_then(|value| value.to_str().ok()),
+            default_client_hostname().as_deref()
+        );
+
+        let headers = HeaderSet::new("3.150.0").name(Some("alice")).build();
+        assert_eq!(headers[header::CLIENT_NAME], "alice");
+
+        assert_eq!(
+            first_non_blank(["".into(), " \n".into(), " bear-box\n".into()]),
+            Some("bear-box".to_owned())
+        );
+        assert_eq!(first_non_blank([]), None);
+    }
+
+    #[test]
+    fn client_platform_value_format() {
+        let value = client_platform_value("3.150.0").unwrap();
+
+        assert_eq!(
+            value,
+            format!(
+                "{}/{}; cli=3.150.0",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            )
+        );
+        HeaderValue::from_str(&value).unwrap();
+    }
+
+    #[rstest]
+    #[case::empty(&[])]
+    #[case::short(&[0x30])]
+    #[case::der_prefix(&[0x30, 0x82, 0x01, 0x0a, 0x02, 0x82, 0x01, 0x01, 0x00, 0xff])]
+    fn client_cert_round_trip(#[case] der: &[u8]) {
+        let encoded = encode_client_cert(der);
+        HeaderValue::from_str(&encoded).unwrap();
+
+        if der.is_empty() {
+            assert!(matches!(
+                decode_client_cert(&encoded),
+                Err(CertHeaderError::Empty)
+            ));
+        } else {
+            assert_eq!(decode_client_cert(&encoded).unwrap(), der);
+        }
+    }
+
+    #[rstest]
+    #[case::not_base64("not base64!")]
+    #[case::url_safe("_-8=")]
+    #[case::bad_padding("MII")]
+    fn client_cert_malformed(#[case] header_value: &str) {
+        assert!(matches!(
+            decode_client_cert(header_value),
+            Err(CertHeaderError::InvalidBase64(..))
+        ));
+    }
+
+    #[rstest]
+    #[case::same("3.150.0", "3.150.0", Compatibility::Ok)]
+    #[case::older_patch("3.150.0", "3.150.7", Compatibility::Ok)]
+    #[case::newer_minor("3.160.0", "3.150.0", Compatibility::Ok)]
+    #[case::within_window("3.100.0", "3.150.0", Compatibility::Ok)]
+    #[case::outside_window("3.99.0", "3.150.0", Compatibility::ClientTooOld)]
+    #[case::older_major("2.200.0", "3.1.0", Compatibility::ClientTooOld)]
+    #[case::newer_major("4.0.0", "3.150.0", Compatibility::ClientTooNew)]
+    #[case::prerelease("3.150.0-rc.1", "3.150.0", Compatibility::Ok)]
+    fn cli_version_compatibility(
+        #[case] cli: &str,
+        #[case] operator: &str,
+        #[case] expected: Compatibility,
+    ) {
+        let cli = cli.parse::<CliVersion>().unwrap();
+        let operator = Version::parse(operator).unwrap();
+
+        assert_eq!(cli.is_compatible_with_operator(&operator), expected);
+    }
+
+    #[rstest]
+    #[case(" 3.150.0 ", Some("3.150.0"))]
+    #[case("3.150", None)]
+    #[case("latest", None)]
+    fn cli_version_parse(#[case] raw: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            raw.parse::<CliVersion>()
+                .ok()
+                .map(|version| version.to_string()),
+            expected.map(str::to_string)
+        );
+    }
+
+    #[test]
+    fn reconnect_error_round_trip() {
+        let error = ReconnectError::new("session `abc` expired");
+
+        assert!(is_reconnect_not_possible(
+            error.status_code(),
+            &error.reason
+        ));
+
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "status": "Failure",
+                "message": "session `abc` expired",
+                "reason": "ReconnectNotPossible",
+                "code": 410,
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<ReconnectError>(json).unwrap(),
+            error
+        );
+    }
+
+    #[rstest]
+    #[case::first(0, None, 0.0, Some(Duration::from_secs(1)))]
+    #[case::doubled(2, None, 0.0, Some(Duration::from_secs(4)))]
+    #[case::capped(5, None, 0.0, Some(Duration::from_secs(8)))]
+    #[case::max_jitter(1, None, 1.0, Some(Duration::from_millis(1600)))]
+    #[case::retryable_status(
+        1,
+        Some(StatusCode::SERVICE_UNAVAILABLE),
+        0.0,
+        Some(Duration::from_secs(2))
+    )]
+    #[case::exhausted(10, None, 0.0, None)]
+    #[case::gone(0, Some(StatusCode::GONE), 0.0, None)]
+    fn reconnect_policy(
+        #[case] attempt: u32,
+        #[case] status: Option<StatusCode>,
+        #[case] sample: f64,
+        #[case] expected: Option<Duration>,
+    ) {
+        assert_eq!(
+            ReconnectPolicy::default().jittered_delay(attempt, status, sample),
+            expected
+        );
+    }
+
+    #[test]
+    fn reconnect_policy_jitter_bounds() {
+        let policy = ReconnectPolicy::default();
+
+        for attempt in 0..policy.max_attempts {
+            let max = policy.jittered_delay(attempt, None, 0.0).unwrap();
+            for sample in [0.25, 0.5, 1.0, 2.0] {
+                let delay = policy.jittered_delay(attempt, None, sample).unwrap();
+                assert!(delay <= max && delay >= max.mul_f64(0.8), "{delay:?}");
+            }
+        }
+        assert!(
+            policy
+                .jittered_delay(policy.max_attempts, None, 0.0)
+                .is_none()
+        );
+    }
+
+    #[rstest]
+    #[case(StatusCode::GONE, "Expired")]
+    #[case(StatusCode::NOT_FOUND, RECONNECT_NOT_POSSIBLE_REASON)]
+    fn reconnect_possible(#[case] status: StatusCode, #[case] reason: &str) {
+        assert!(is_reconnect_not_possible(status, reason).not());
+    }
+
+    #[rstest]
+    #[case::zero("0", Ok(SessionId(0)))]
+    #[case::max("18446744073709551615", Ok(SessionId(u64::MAX)))]
+    #[case::empty("", Err(SessionIdError::Empty))]
+    #[case::hex("1F", Err(SessionIdError::InvalidCharacter('F')))]
+    #[case::sign("+1", Err(SessionIdError::InvalidCharacter('+')))]
+    #[case::whitespace(" 1", Err(SessionIdError::InvalidCharacter(' ')))]
+    #[case::overflow("18446744073709551616", Err(SessionIdError::OutOfRange))]
+    #[case::too_long("000000000000000000001", Err(SessionIdError::TooLong(21)))]
+    fn session_id_parse(#[case] raw: &str, #[case] expected: Result<SessionId, SessionIdError>) {
+        assert_eq!(raw.parse::<SessionId>(), expected);
+    }
+
+    #[test]
+    fn session_id_round_trip() {
+        let id = SessionId(0xDEAD_BEEF);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(SessionId::from_headers(&headers), Ok(None));
+        id.insert_into(&mut headers);
+        assert_eq!(headers[SESSION_ID_HEADER], "3735928559");
+        assert_eq!(SessionId::from_headers(&headers), Ok(Some(id)));
+
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, r#""3735928559""#);
+        assert_eq!(serde_json::from_str::<SessionId>(&json).unwrap(), id);
+
+        assert_eq!(id.to_hex(), "DEADBEEF");
+        assert_eq!(SessionId::from_hex("deadbeef"), Ok(id));
+    }
+
+    #[test]
+    fn session_id_malformed_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::SESSION_ID, HeaderValue::from_static("abc"));
+
+        assert_eq!(
+            SessionId::from_headers(&headers),
+            Err(SessionIdError::InvalidCharacter('a'))
+        );
+    }
+
+    #[test]
+    fn target_namespace_round_trip() {
+        let namespace = "staging-2".parse::<TargetNamespace>().unwrap();
+        let headers = HeaderSet::new("3.150.0")
+            .target_namespace(Some(&namespace))
+            .build();
+
+        assert_eq!(headers[header::TARGET_NAMESPACE], "staging-2");
+        assert_eq!(
+            TargetNamespace::from_headers(&headers),
+            Ok(Some(namespace.clone()))
+        );
+        assert_eq!(TargetNamespace::from_headers(&HeaderMap::new()), Ok(None));
+
+        let json = serde_json::to_string(&namespace).unwrap();
+        assert_eq!(json, r#""staging-2""#);
+        assert_eq!(
+            serde_json::from_str::<TargetNamespace>(&json).unwrap(),
+            namespace
+        );
+    }
+
+    #[rstest]
+    #[case::empty("", TargetNamespaceError::Empty)]
+    #[case::too_long(&"a".repeat(64), TargetNamespaceError::TooLong(64))]
+    #[case::uppercase("Staging", TargetNamespaceError::InvalidCharacter('S'))]
+    #[case::dot("kube.system", TargetNamespaceError::InvalidCharacter('.'))]
+    #[case::path("default/pods", TargetNamespaceError::InvalidCharacter('/'))]
+    #[case::leading_dash("-staging", TargetNamespaceError::InvalidBoundary)]
+    #[case::trailing_dash("staging-", TargetNamespaceError::InvalidBoundary)]
+    fn target_namespace_invalid(#[case] namespace: &str, #[case] expected: TargetNamespaceError) {
+        assert_eq!(namespace.parse::<TargetNamespace>(), Err(expected));
+    }
+
+    #[test]
+    fn target_namespace_malformed_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::TARGET_NAMESPACE,
+            HeaderValue::from_static("../kube-system"),
+        );
+
+        assert_eq!(
+            TargetNamespace::from_headers(&headers),
+            Err(TargetNamespaceError::InvalidCharacter('.'))
+        );
+        assert!(serde_json::from_str::<TargetNamespace>(r#""Default""#).is_err());
+    }
+
+    #[test]
+    fn trace_id_round_trip() {
+        let trace_id = TraceId::generate();
+        let headers = HeaderSet::new("3.150.0").trace_id(trace_id).build();
+
+        assert_eq!(
+            headers[header::MIRRORD_TRACE_ID],
+            trace_id.to_string().as_str()
+        );
+        assert_eq!(TraceId::from_headers(&headers), Ok(Some(trace_id)));
+        assert_eq!(TraceId::from_headers(&HeaderMap::new()), Ok(None));
+    }
+
+    #[rstest]
+    #[case::not_uuid("abc")]
+    #[case::too_short("67e55044-10b1-426f-9247-bb680e5fe0c")]
+    #[case::empty("")]
+    fn trace_id_malformed_header(#[case] value: &'static str) {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::MIRRORD_TRACE_ID, HeaderValue::from_static(value));
+
+        assert!(matches!(
+            TraceId::from_headers(&headers),
+            Err(TraceIdError::InvalidUuid(..))
+        ));
+    }
+
+    #[rstest]
+    #[case(None, DEFAULT_OPERATOR_ISOLATION_MARKER)]
+    #[case(Some(""), DEFAULT_OPERATOR_ISOLATION_MARKER)]
+    #[case(Some("dev-operator"), "dev-operator")]
+    fn isolation_marker_precedence(#[case] env: Option<&str>, #[case] expected: &str) {
+        let marker = resolve_isolation_marker_with(|name| {
+            assert_eq!(name, OPERATOR_ISOLATION_MARKER_ENV);
+            env.map(ToOwned::to_owned)
+        });
+
+        assert_eq!(marker, expected);
+    }
+
+    #[test]
+    fn ownership_selector_matches_labels() {
+        let selector = ownership_label_selector("dev-operator");
+        assert_eq!(selector, "operator.metalbear.co/owner=dev-operator");
+
+        let labels = ownership_labels("dev-operator");
+        let (key, value) = selector.split_once('=').unwrap();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels.get(key).map(String::as_str), Some(value));
+    }
+
+    #[test]
+    fn ownership_matches_namespaced_marker() {
+        let staging = namespaced_isolation_marker(DEFAULT_OPERATOR_ISOLATION_MARKER, "staging");
+        assert_eq!(staging, "mirrord-operator.staging");
+
+        let labels = ownership_labels(&staging);
+        assert!(matches_owner(&labels, &staging));
+        assert!(matches_owner(&labels, DEFAULT_OPERATOR_ISOLATION_MARKER).not());
+        assert!(
+            matches_owner(
+                &labels,
+                &namespaced_isolation_marker(DEFAULT_OPERATOR_ISOLATION_MARKER, "dev")
+            )
+            .not()
+        );
+
+        let labels = ownership_labels(DEFAULT_OPERATOR_ISOLATION_MARKER);
+        assert!(matches_owner(&labels, DEFAULT_OPERATOR_ISOLATION_MARKER));
+        assert!(matches_owner(&labels, &staging).not());
+        assert!(matches_owner(&BTreeMap::new(), DEFAULT_OPERATOR_ISOLATION_MARKER).not());
+    }
+
+    #[test]
+    fn operator_headers_are_distinct_and_valid() {
+        let names = OperatorHeader::all()
+            .map(OperatorHeader::as_str)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(names.len(), OperatorHeader::all().count());
+
+        for header in OperatorHeader::all() {
+            let name = header.normalized_name();
+            assert_eq!(name, name.to_ascii_lowercase());
+            assert!(is_lowercase_token(name));
+            assert_eq!(HeaderName::from_static(name), header.header_name());
+            assert_eq!(header.header_name().as_str(), name);
+        }
+
+        assert!(is_lowercase_token("x-mirrord-cli-version"));
+        assert!(is_lowercase_token("X-Mirrord-Cli-Version").not());
+        assert!(is_lowercase_token("x mirrord").not());
+        assert!(is_lowercase_token("x-mirrord:").not());
+        assert!(is_lowercase_token("").not());
+
+        let headers = HeaderSet::new("3.0.0")
+            .name(Some("alice"))
+            .hostname(Some("bear-box"))
+            .build();
+        assert_eq!(
+            OperatorHeader::present_in(&headers).collect::<Vec<_>>(),
+            [
+                OperatorHeader::CliVersion,
+                OperatorHeader::ClientHostname,
+                OperatorHeader::ClientName
+            ]
+        );
+    }
+}
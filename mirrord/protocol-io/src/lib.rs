@@ -6,7 +6,7 @@ use std::{
     io::{self},
     marker::PhantomData,
     sync::{
-        Arc, Mutex,
+        Arc, Mutex, OnceLock,
         atomic::{AtomicUsize, Ordering},
     },
     task::{Context, Poll},
@@ -47,6 +47,15 @@ pub trait ProtocolEndpoint: 'static + Sized + Clone {
     type OutMsg: bincode::Encode + Send + fmt::Debug;
 }
 
+/// Observes all the messages going through a [`Connection`], see [`Connection::set_tap`].
+pub trait MessageTap<Type: ProtocolEndpoint>: Send + Sync + 'static {
+    /// Called with every outbound message, when it is queued for sending.
+    fn sent(&self, message: &Type::OutMsg);
+
+    /// Called with every inbound message, before it is handed out from the [`Connection`].
+    fn received(&self, message: &Type::InMsg);
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ProtocolError {
     #[error("IO error: {0}")]
@@ -227,6 +236,16 @@ impl<Type: ProtocolEndpoint> Connection<Type> {
     pub fn is_closed(&self) -> bool {
         self.rx.is_closed()
     }
+
+    /// Makes the given [`MessageTap`] observe all the messages going through this connection,
+    /// including the ones sent with the [`TxHandle`]s.
+    ///
+    /// Only the first tap set on a connection is used.
+    pub fn set_tap(&self, tap: Arc<dyn MessageTap<Type>>) {
+        if self.shared_state.tap.set(tap).is_err() {
+            tracing::warn!("A message tap was already set on this connection, ignoring");
+        }
+    }
 }
 
 impl<Type: ProtocolEndpoint> Drop for Connection<Type> {
@@ -293,6 +312,10 @@ async fn io_task<Channel, Type>(
                         break;
                     }
                     Some(Ok(msg)) => {
+                        if let Some(tap) = queues.tap.get() {
+                            tap.received(&msg);
+                        }
+
                         if let Err(error) = tx.send(msg).await {
                             tracing::info!(?error, "io task channel closed");
                             break;
@@ -345,6 +368,9 @@ struct SharedState<Type: ProtocolEndpoint> {
     /// (left), or enqueued for sending (right).
     out_filter: Option<Box<FilterFn<Type::InMsg, Type::OutMsg>>>,
 
+    /// See [`Connection::set_tap`].
+    tap: OnceLock<Arc<dyn MessageTap<Type>>>,
+
     next_queue_id: AtomicUsize,
 
     /// Used for telling the io task to shut down.
@@ -375,6 +401,7 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
             nonempty: Arc::new(Notify::new()),
             in_tx,
             out_filter,
+            tap: OnceLock::new(),
             // 0 is reserved for the Connection struct
             next_queue_id: 1.into(),
             cancel: CancellationToken::new(),
@@ -428,6 +455,10 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
             match filter(msg) {
                 Either::Left(out) => msg = out,
                 Either::Right(inj) => {
+                    if let Some(tap) = self.tap.get() {
+                        tap.received(&inj);
+                    }
+
                     match self.in_tx.upgrade() {
                         Some(tx) => {
                             let _ = tx.send(inj).await;
@@ -443,6 +474,10 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
             }
         }
 
+        if let Some(tap) = self.tap.get() {
+            tap.sent(&msg);
+        }
+
         let mut encoded = bincode::encode_to_vec(msg, bincode::config::standard()).unwrap();
 
         loop {
@@ -646,4 +681,38 @@ mod tests {
             assert_eq!(seq.next(), None);
         }
     }
+
+    #[derive(Default)]
+    struct RecordingTap {
+        sent: Mutex<Vec<Message>>,
+    }
+
+    impl MessageTap<Test> for RecordingTap {
+        fn sent(&self, message: &Message) {
+            self.sent.lock().unwrap().push(message.clone());
+        }
+
+        fn received(&self, _: &Message) {}
+    }
+
+    #[tokio::test]
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn tap_sees_sent_messages() {
+        let (connection, _inbound_tx, output) = Connection::<Test>::dummy();
+        let tap = Arc::new(RecordingTap::default());
+        connection.set_tap(tap.clone());
+
+        let messages = [Message::new(0), Message::new(1)];
+        connection.send(messages[0].clone()).await;
+        connection.tx_handle().send(messages[1].clone()).await;
+
+        for _ in &messages {
+            output.next().await.unwrap();
+        }
+
+        let mut sent = tap.sent.lock().unwrap().clone();
+        sent.sort_by_key(|message| message.from);
+        assert_eq!(sent, messages);
+    }
 }
@@ -0,0 +1,90 @@
+//! Throughput of sending a file read response from the agent to the client, with and without
+//! compression.
+//!
+//! Run with `cargo bench -p mirrord-protocol`.
+
+#![feature(test)]
+
+extern crate test;
+
+use actix_codec::{Decoder, Encoder};
+use bytes::BytesMut;
+use mirrord_protocol::{
+    ClientCodec, DaemonCodec, DaemonMessage, FileResponse, compression::CompressionCodec,
+    file::ReadFileResponse,
+};
+use test::Bencher;
+
+/// Size of the file chunk sent in a single response.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Log-like text, compresses well.
+fn compressible() -> Vec<u8> {
+    (0..)
+        .flat_map(|line: u64| {
+            format!(
+                "2024-01-01T00:00:{:02}Z INFO request {line} handled in 3ms\n",
+                line % 60
+            )
+            .into_bytes()
+        })
+        .take(CHUNK_SIZE)
+        .collect()
+}
+
+/// Pseudo-random bytes (xorshift), do not compress at all.
+fn incompressible() -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    (0..CHUNK_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+/// Sends the bytes as a [`FileResponse::Read`] from [`DaemonCodec`] to [`ClientCodec`], compressing
+/// on the agent side and decompressing on the client side.
+fn round_trip(b: &mut Bencher, bytes: Vec<u8>, codec: CompressionCodec) {
+    let message = DaemonMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+        read_amount: bytes.len() as u64,
+        bytes: bytes.into(),
+    })));
+    let mut daemon_codec = DaemonCodec::default();
+    let mut client_codec = ClientCodec::default();
+    let mut buffer = BytesMut::new();
+
+    b.bytes = CHUNK_SIZE as u64;
+    b.iter(|| {
+        daemon_codec
+            .encode(message.clone().compress(codec), &mut buffer)
+            .unwrap();
+
+        match client_codec.decode(&mut buffer).unwrap().unwrap() {
+            DaemonMessage::Compressed(compressed) => compressed.decompress().unwrap(),
+            message => message,
+        }
+    });
+}
+
+#[bench]
+fn compressible_none(b: &mut Bencher) {
+    round_trip(b, compressible(), CompressionCodec::None);
+}
+
+#[bench]
+fn compressible_zstd(b: &mut Bencher) {
+    round_trip(b, compressible(), CompressionCodec::Zstd);
+}
+
+#[bench]
+fn incompressible_none(b: &mut Bencher) {
+    round_trip(b, incompressible(), CompressionCodec::None);
+}
+
+#[bench]
+fn incompressible_zstd(b: &mut Bencher) {
+    round_trip(b, incompressible(), CompressionCodec::Zstd);
+}
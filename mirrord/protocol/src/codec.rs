@@ -1,6 +1,6 @@
 use std::{
     collections::{HashMap, HashSet},
-    io,
+    fmt, io,
     marker::PhantomData,
     sync::LazyLock,
 };
@@ -21,9 +21,10 @@ use semver::VersionReq;
 
 use crate::{
     ResponseError,
+    compression::{CompressedMessage, CompressionCodec},
     dns::{
-        GetAddrInfoRequest, GetAddrInfoRequestV2, GetAddrInfoResponse, ReverseDnsLookupRequest,
-        ReverseDnsLookupResponse,
+        GetAddrInfoRequest, GetAddrInfoRequestV2, GetAddrInfoResponse, GetAddrInfoResponseV2,
+        ReverseDnsLookupRequest, ReverseDnsLookupResponse,
     },
     file::*,
     outgoing::{
@@ -74,6 +75,23 @@ pub struct GetEnvVarsRequest {
     pub env_vars_select: HashSet<String>,
 }
 
+/// Minimal mirrord-protocol version that allows [`ClientMessage::GetContainerEnvVarsRequest`].
+pub static GET_CONTAINER_ENV_VARS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.32.0".parse().expect("Bad Identifier"));
+
+/// Like [`GetEnvVarsRequest`], but reads the environment of the given container of the target
+/// pod, instead of the target container.
+///
+/// Supported from [`GET_CONTAINER_ENV_VARS_VERSION`]. The agent responds with
+/// [`DaemonMessage::GetEnvVarsResponse`], or [`ResponseError::UnknownContainer`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetContainerEnvVarsRequest {
+    /// Name of the container in the target pod.
+    pub container: String,
+    pub env_vars_filter: HashSet<String>,
+    pub env_vars_select: HashSet<String>,
+}
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub enum FileRequest {
     Open(OpenFileRequest),
@@ -116,6 +134,12 @@ pub enum FileRequest {
     Futimens(FutimensRequest),
     Fchown(FchownRequest),
     Fchmod(FchmodRequest),
+
+    /// Supported from [`XATTR_VERSION`].
+    GetXattr(GetXattrRequest),
+
+    /// Supported from [`XATTR_VERSION`].
+    ListXattr(ListXattrRequest),
 }
 
 /// Minimal mirrord-protocol version that allows `ClientMessage::ReadyForLogs` message.
@@ -166,6 +190,41 @@ pub enum ClientMessage {
     ///
     /// Sent by the operator when enforcing hostname-based outgoing network policies.
     ReverseDnsLookup(ReverseDnsLookupRequest),
+    /// Supported from [`GET_CONTAINER_ENV_VARS_VERSION`].
+    GetContainerEnvVarsRequest(GetContainerEnvVarsRequest),
+    /// Advertises the [`CompressionCodec`]s supported by the client.
+    ///
+    /// Supported from [`COMPRESSION_VERSION`](crate::compression::COMPRESSION_VERSION). The agent
+    /// responds with [`DaemonMessage::SwitchCompressionResponse`].
+    SwitchCompression(Vec<CompressionCodec>),
+    /// Identifies the user running this client, so that other clients can be told who holds a
+    /// port, see [`ResponseError::PortStealConflict`].
+    ///
+    /// Supported from [`CLIENT_IDENTITY_VERSION`]. The agent does not respond.
+    ClientIdentity(ClientIdentity),
+}
+
+/// Minimal mirrord-protocol version that allows [`ClientMessage::ClientIdentity`].
+pub static CLIENT_IDENTITY_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.35.0".parse().expect("Bad Identifier"));
+
+/// User running a mirrord client, sent in [`ClientMessage::ClientIdentity`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Default)]
+pub struct ClientIdentity {
+    pub name: Option<String>,
+    pub hostname: Option<String>,
+}
+
+/// Displayed as `name@hostname`, e.g. in [`ResponseError::PortStealConflict`].
+impl fmt::Display for ClientIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}@{}",
+            self.name.as_deref().unwrap_or("unknown"),
+            self.hostname.as_deref().unwrap_or("unknown")
+        )
+    }
 }
 
 /// Type alias for `Result`s that should be returned from mirrord-agent to mirrord-layer.
@@ -196,6 +255,8 @@ pub enum FileResponse {
     Futimens(RemoteResult<()>),
     Fchown(RemoteResult<()>),
     Fchmod(RemoteResult<()>),
+    GetXattr(RemoteResult<GetXattrResponse>),
+    ListXattr(RemoteResult<ListXattrResponse>),
 }
 
 /// `-agent` --> `-layer` messages.
@@ -230,6 +291,17 @@ pub enum DaemonMessage {
     ///
     /// Sent by the agent in response to [`ClientMessage::ReverseDnsLookup`].
     ReverseDnsLookup(RemoteResult<ReverseDnsLookupResponse>),
+    /// The [`CompressionCodec`] picked by the agent in response to
+    /// [`ClientMessage::SwitchCompression`].
+    SwitchCompressionResponse(CompressionCodec),
+    /// A bulk message compressed with the codec from [`DaemonMessage::SwitchCompressionResponse`].
+    ///
+    /// Never sent before the compression is negotiated, see [`DaemonMessage::compress`].
+    Compressed(CompressedMessage),
+    /// Sent by the agent in response to [`ClientMessage::GetAddrInfoRequest`] and
+    /// [`ClientMessage::GetAddrInfoRequestV2`] instead of [`DaemonMessage::GetAddrInfoResponse`],
+    /// when the client supports [`ADDRINFO_TTL_VERSION`](crate::dns::ADDRINFO_TTL_VERSION).
+    GetAddrInfoResponseV2(GetAddrInfoResponseV2),
 }
 
 #[derive(Encode, Decode, PartialEq, Eq, Clone, From, Into, Deref)]
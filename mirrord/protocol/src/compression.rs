@@ -0,0 +1,275 @@
+//! Optional compression of bulk [`DaemonMessage`]s.
+//!
+//! After the protocol version is negotiated, the client may send
+//! [`ClientMessage::SwitchCompression`](crate::ClientMessage::SwitchCompression) with the
+//! [`CompressionCodec`]s it supports. The agent picks one with [`CompressionCodec::negotiate`],
+//! and responds with [`DaemonMessage::SwitchCompressionResponse`]. From then on, bulk messages
+//! (see [`DaemonMessage::is_bulk`]) larger than [`COMPRESSION_THRESHOLD`] are sent wrapped in
+//! [`DaemonMessage::Compressed`].
+//!
+//! Older peers never go through this exchange, and keep using [`CompressionCodec::None`].
+
+use std::{
+    io::{self, Read},
+    sync::LazyLock,
+};
+
+use bincode::{Decode, Encode};
+use bytes::Bytes;
+use semver::VersionReq;
+
+use crate::{DaemonMessage, FileResponse, Payload};
+
+/// Minimal mirrord-protocol version that allows
+/// [`ClientMessage::SwitchCompression`](crate::ClientMessage::SwitchCompression).
+pub static COMPRESSION_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.33.0".parse().expect("Bad Identifier"));
+
+/// Encoded size (in bytes) above which bulk messages are compressed.
+///
+/// Smaller messages are always sent as they are, compressing them is not worth the latency.
+pub const COMPRESSION_THRESHOLD: usize = 4 * 1024;
+
+/// zstd level used when compressing messages, favors speed over ratio.
+const ZSTD_LEVEL: i32 = 1;
+
+/// Maximum size (in bytes) of a decompressed message.
+///
+/// [`CompressionCodec::decompress`] fails past this, so that a small malicious frame cannot
+/// make the peer allocate without limit.
+pub const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Compression algorithm used for bulk [`DaemonMessage`]s.
+///
+/// lz4 is deliberately not offered: it compresses worse than zstd at level [`ZSTD_LEVEL`], for a
+/// speed difference that does not matter next to the network latency, and it would be another
+/// native dependency of the layer and the agent.
+///
+/// New codecs must be added as the last variant.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy, Default, Hash)]
+pub enum CompressionCodec {
+    /// Messages are not compressed.
+    #[default]
+    None,
+    Zstd,
+}
+
+impl CompressionCodec {
+    /// All the codecs supported by this version of mirrord-protocol, from the most preferred.
+    pub const SUPPORTED: &[Self] = &[Self::Zstd, Self::None];
+
+    /// Picks the most preferred of our [`Self::SUPPORTED`] codecs that is also supported by the
+    /// peer, falling back to [`CompressionCodec::None`].
+    pub fn negotiate(peer: &[Self]) -> Self {
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|codec| peer.contains(codec))
+            .unwrap_or_default()
+    }
+
+    /// Compresses the given bytes.
+    pub fn compress(self, bytes: &[u8]) -> io::Result<Bytes> {
+        match self {
+            Self::None => Ok(Bytes::copy_from_slice(bytes)),
+            Self::Zstd => zstd::bulk::compress(bytes, ZSTD_LEVEL).map(Bytes::from),
+        }
+    }
+
+    /// Decompresses the given bytes, compressed with [`CompressionCodec::compress`].
+    ///
+    /// Fails if the decompressed bytes would exceed [`MAX_DECOMPRESSED_SIZE`].
+    pub fn decompress(self, bytes: &[u8]) -> io::Result<Bytes> {
+        match self {
+            Self::None => Ok(Bytes::copy_from_slice(bytes)),
+            Self::Zstd => {
+                let mut decompressed = Vec::new();
+                zstd::stream::read::Decoder::new(bytes)?
+                    .take(MAX_DECOMPRESSED_SIZE as u64 + 1)
+                    .read_to_end(&mut decompressed)?;
+
+                if decompressed.len() > MAX_DECOMPRESSED_SIZE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "decompressed message exceeds the limit of {MAX_DECOMPRESSED_SIZE} bytes"
+                        ),
+                    ));
+                }
+
+                Ok(decompressed.into())
+            }
+        }
+    }
+}
+
+/// A bincode encoded [`DaemonMessage`], compressed with [`CompressedMessage::codec`].
+///
+/// Sent as [`DaemonMessage::Compressed`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct CompressedMessage {
+    pub codec: CompressionCodec,
+    pub payload: Payload,
+}
+
+impl CompressedMessage {
+    /// Encodes and compresses the given [`DaemonMessage`], regardless of its size.
+    pub fn new(message: &DaemonMessage, codec: CompressionCodec) -> io::Result<Self> {
+        let encoded = bincode::encode_to_vec(message, bincode::config::standard())
+            .map_err(io::Error::other)?;
+
+        Ok(Self {
+            codec,
+            payload: Payload(codec.compress(&encoded)?),
+        })
+    }
+
+    /// Decompresses and decodes the inner [`DaemonMessage`].
+    pub fn decompress(&self) -> io::Result<DaemonMessage> {
+        let bytes = self.codec.decompress(&self.payload)?;
+        let (message, _) = bincode::decode_from_slice(&bytes, bincode::config::standard())
+            .map_err(io::Error::other)?;
+
+        Ok(message)
+    }
+}
+
+impl DaemonMessage {
+    /// Whether this message carries bulk data that may be compressed, i.e. file contents,
+    /// directory listings or environment variables.
+    pub fn is_bulk(&self) -> bool {
+        matches!(
+            self,
+            Self::File(
+                FileResponse::Read(..)
+                    | FileResponse::ReadLimited(..)
+                    | FileResponse::ReadDir(..)
+                    | FileResponse::ReadDirBatch(..)
+                    | FileResponse::GetDEnts64(..)
+            ) | Self::GetEnvVarsResponse(..)
+        )
+    }
+
+    /// Wraps this message in [`DaemonMessage::Compressed`], if it's a bulk message (see
+    /// [`DaemonMessage::is_bulk`]) larger than [`COMPRESSION_THRESHOLD`].
+    ///
+    /// The message is returned as it is if compression does not make it any smaller, or fails.
+    pub fn compress(self, codec: CompressionCodec) -> Self {
+        if codec == CompressionCodec::None || !self.is_bulk() {
+            return self;
+        }
+
+        let Ok(encoded) = bincode::encode_to_vec(&self, bincode::config::standard()) else {
+            return self;
+        };
+        if encoded.len() <= COMPRESSION_THRESHOLD {
+            return self;
+        }
+
+        match codec.compress(&encoded) {
+            Ok(compressed) if compressed.len() < encoded.len() => {
+                Self::Compressed(CompressedMessage {
+                    codec,
+                    payload: Payload(compressed),
+                })
+            }
+            _ => self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RemoteEnvVars, file::ReadFileResponse};
+
+    fn read_response(bytes: Vec<u8>) -> DaemonMessage {
+        DaemonMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+            read_amount: bytes.len() as u64,
+            bytes: bytes.into(),
+        })))
+    }
+
+    #[test]
+    fn negotiate_falls_back_to_none() {
+        assert_eq!(
+            CompressionCodec::negotiate(CompressionCodec::SUPPORTED),
+            CompressionCodec::Zstd
+        );
+        assert_eq!(
+            CompressionCodec::negotiate(&[CompressionCodec::None]),
+            CompressionCodec::None
+        );
+        assert_eq!(CompressionCodec::negotiate(&[]), CompressionCodec::None);
+    }
+
+    #[test]
+    fn compress_bulk_round_trip() {
+        let message = read_response(b"mirrord ".repeat(4096));
+        let compressed = message.clone().compress(CompressionCodec::Zstd);
+
+        let DaemonMessage::Compressed(inner) = &compressed else {
+            panic!("expected a compressed message, got {compressed:?}");
+        };
+        assert!(inner.payload.len() < COMPRESSION_THRESHOLD);
+        assert_eq!(inner.decompress().unwrap(), message);
+
+        let env = DaemonMessage::GetEnvVarsResponse(Ok(RemoteEnvVars(
+            (0..1024)
+                .map(|i| (format!("VAR_{i}"), "value".to_owned()))
+                .collect(),
+        )));
+        let DaemonMessage::Compressed(inner) = env.clone().compress(CompressionCodec::Zstd) else {
+            panic!("expected a compressed env response");
+        };
+        assert_eq!(inner.decompress().unwrap(), env);
+    }
+
+    #[test]
+    fn compress_skips_small_and_non_bulk() {
+        let small = read_response(vec![0; COMPRESSION_THRESHOLD / 2]);
+        assert_eq!(small.clone().compress(CompressionCodec::Zstd), small);
+
+        let large = read_response(vec![0; COMPRESSION_THRESHOLD * 4]);
+        assert_eq!(large.clone().compress(CompressionCodec::None), large);
+
+        let close = DaemonMessage::Close("x".repeat(COMPRESSION_THRESHOLD * 4));
+        assert_eq!(close.clone().compress(CompressionCodec::Zstd), close);
+    }
+
+    #[test]
+    fn decompress_rejects_bombs() {
+        let bomb = CompressionCodec::Zstd
+            .compress(&vec![0; MAX_DECOMPRESSED_SIZE + 1])
+            .unwrap();
+        assert!(bomb.len() < COMPRESSION_THRESHOLD);
+
+        let error = CompressionCodec::Zstd.decompress(&bomb).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let limit = CompressionCodec::Zstd
+            .compress(&vec![0; MAX_DECOMPRESSED_SIZE])
+            .unwrap();
+        assert_eq!(
+            CompressionCodec::Zstd.decompress(&limit).unwrap().len(),
+            MAX_DECOMPRESSED_SIZE
+        );
+    }
+
+    #[test]
+    fn compress_skips_incompressible() {
+        // xorshift, so the bytes don't compress.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let bytes = (0..COMPRESSION_THRESHOLD * 4)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+
+        let message = read_response(bytes);
+        assert_eq!(message.clone().compress(CompressionCodec::Zstd), message);
+    }
+}
@@ -38,9 +38,26 @@ impl IntoIterator for DnsLookup {
     }
 }
 
+/// Minimal mirrord-protocol version that allows [`GetAddrInfoResponseV2`].
+pub static ADDRINFO_TTL_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.36.0".parse().expect("Bad Identifier"));
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct GetAddrInfoResponse(pub RemoteResult<DnsLookup>);
 
+/// [`GetAddrInfoResponse`] with the time to live of the lookup, so that the client can cache it.
+///
+/// Sent by the agent instead of [`GetAddrInfoResponse`] to clients that support
+/// [`ADDRINFO_TTL_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetAddrInfoResponseV2 {
+    pub response: GetAddrInfoResponse,
+    /// Lowest TTL of the resolved records, in seconds.
+    ///
+    /// [`None`] when the lookup failed or returned no records.
+    pub ttl: Option<u32>,
+}
+
 impl Deref for GetAddrInfoResponse {
     type Target = RemoteResult<DnsLookup>;
 
@@ -63,7 +80,7 @@ impl From<GetAddrInfoRequestV2> for GetAddrInfoRequest {
 }
 
 #[derive(
-    serde::Serialize, serde::Deserialize, Encode, Decode, Debug, PartialEq, Eq, Copy, Clone,
+    serde::Serialize, serde::Deserialize, Encode, Decode, Debug, PartialEq, Eq, Hash, Copy, Clone,
 )]
 pub enum AddressFamily {
     Ipv4Only,
@@ -69,6 +69,30 @@ pub enum ResponseError {
         policy_name: Option<String>,
         reason: String,
     },
+
+    #[error(
+        "Could not subscribe to port `{0}`, as this mirrord session is already subscribed to it \
+        with a different HTTP filter."
+    )]
+    ConflictingPortSubscription(Port),
+
+    #[error(
+        "Container `{name}` was not found in the target pod, available containers: [{}].",
+        available.join(", ")
+    )]
+    UnknownContainer {
+        name: String,
+        available: Vec<String>,
+    },
+
+    #[error("Could not subscribe to port `{port}`, failed to set up TLS stealing: {reason}")]
+    StealTlsSetup { port: Port, reason: String },
+
+    #[error(
+        "port {port} is already stolen by {}",
+        .holder.as_deref().unwrap_or("another mirrord client")
+    )]
+    PortStealConflict { port: Port, holder: Option<String> },
 }
 
 impl From<StripPrefixError> for ResponseError {
@@ -95,6 +119,14 @@ pub static MIRROR_BLOCK_VERSION: LazyLock<VersionReq> =
 pub static MIRROR_POLICY_REASON_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.17.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows [`ResponseError::StealTlsSetup`].
+pub static STEAL_TLS_SETUP_ERROR_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.34.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`ResponseError::PortStealConflict`].
+pub static PORT_STEAL_CONFLICT_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.35.0".parse().expect("Bad Identifier"));
+
 /// All the actions that can be blocked by the operator, to identify the blocked feature in a
 /// [`ResponseError::Forbidden`] or [`ResponseError::ForbiddenWithReason`] message.
 #[derive(Encode, Decode, Debug, PartialEq, Clone, Eq, Error)]
@@ -49,6 +49,10 @@ pub static RENAME_VERSION: LazyLock<VersionReq> =
 pub static COPYFILE_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.24.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows [`GetXattrRequest`] and [`ListXattrRequest`].
+pub static XATTR_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.37.0".parse().expect("Bad Identifier"));
+
 /// Internal version of Metadata across operating system (macOS, Linux)
 /// Only mutual attributes
 #[derive(Encode, Decode, Debug, PartialEq, Clone, Copy, Eq, Default)]
@@ -687,3 +691,37 @@ pub struct FchmodRequest {
     pub fd: u64,
     pub mode: u32,
 }
+
+/// Reads the value of an extended attribute, like `getxattr`, `lgetxattr` and `fgetxattr`.
+///
+/// Either `path` or `fd` is set.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetXattrRequest {
+    pub path: Option<PathBuf>,
+    pub fd: Option<u64>,
+    pub follow_symlink: bool,
+    /// Name of the attribute, e.g. `user.checksum`, without the null terminator.
+    pub name: Vec<u8>,
+}
+
+/// The whole value of the attribute, the client handles the size of its own buffer.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetXattrResponse {
+    pub value: Payload,
+}
+
+/// Lists the names of the extended attributes, like `listxattr`, `llistxattr` and `flistxattr`.
+///
+/// Either `path` or `fd` is set.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ListXattrRequest {
+    pub path: Option<PathBuf>,
+    pub fd: Option<u64>,
+    pub follow_symlink: bool,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ListXattrResponse {
+    /// Names of the attributes, without the null terminators.
+    pub names: Vec<Vec<u8>>,
+}
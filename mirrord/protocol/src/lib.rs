@@ -63,6 +63,7 @@
 
 pub mod batched_body;
 pub mod codec;
+pub mod compression;
 pub mod dns;
 pub mod error;
 pub mod file;
@@ -92,6 +92,14 @@ pub enum LayerTcp {
     /// User is interested in mirroring traffic on this `Port`, so add it to the list of
     /// ports that the sniffer is filtering.
     PortSubscribeFilteredHttp(Port, HttpFilter),
+
+    /// User wants to see which HTTP requests to this `Port` would be matched by the filter.
+    ///
+    /// The agent evaluates the filter on the mirrored requests and responds with a
+    /// [`DaemonTcp::HttpFilterReport`] for each of them. No traffic is sent to the user.
+    ///
+    /// Supported from [`HTTP_FILTER_DRY_RUN_VERSION`].
+    PortSubscribeDryRun(Port, HttpFilter),
 }
 
 /// Messages related to Tcp handler from server.
@@ -110,6 +118,31 @@ pub enum DaemonTcp {
     HttpRequestFramed(HttpRequest<InternalHttpBody>),
     HttpRequestChunked(ChunkedRequest),
     NewConnectionV2(NewTcpConnectionV2),
+    /// Result of evaluating the filter of a [`LayerTcp::PortSubscribeDryRun`] subscription on a
+    /// mirrored HTTP request.
+    HttpFilterReport(HttpFilterReport),
+}
+
+/// Result of evaluating an [`HttpFilter`] on an HTTP request, without acting on it.
+///
+/// Sent for the [`LayerTcp::PortSubscribeDryRun`] subscriptions.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct HttpFilterReport {
+    /// Destination port of the request.
+    pub port: Port,
+    pub method: String,
+    /// Path and query of the request.
+    pub path: String,
+    /// Whether the request would have been stolen, according to the [`FilterErrorPolicy`] when
+    /// the evaluation failed.
+    pub matched: bool,
+    /// The filter that matched the request: the first matching filter of a top level `any_of`
+    /// composite, or the whole filter otherwise.
+    pub matched_filter: Option<String>,
+    /// Why the evaluation failed.
+    pub error: Option<String>,
+    /// How long the evaluation took, in microseconds.
+    pub duration_us: u64,
 }
 
 /// Contents of a chunked message from server.
@@ -365,6 +398,59 @@ pub enum HttpBodyFilter {
         query: JsonPathQuery,
         matches: Filter,
     },
+    /// Evaluates the jq expression against a JSON view of the request (method, path, query,
+    /// headers and the JSON body), matching only when it returns `true`.
+    Jq {
+        query: JqQuery,
+        /// Used when the body is not JSON or was too big to be buffered.
+        fallback: BodyFilterFallback,
+    },
+    /// Same as [`HttpBodyFilter::Jq`], but the body of a gRPC request is first decoded from
+    /// protobuf into JSON with the [`GrpcDecoder`].
+    GrpcJq {
+        query: JqQuery,
+        /// Used when the body cannot be decoded or was too big to be buffered.
+        fallback: BodyFilterFallback,
+        decoder: GrpcDecoder,
+    },
+}
+
+/// Decodes the protobuf messages in gRPC request bodies, see [`HttpBodyFilter::GrpcJq`].
+///
+/// Only the first message of the body is decoded, so streaming RPCs are matched based on their
+/// first request message.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GrpcDecoder {
+    /// Compiled protobuf descriptors of the messages.
+    pub descriptor_set: ProtoDescriptorSet,
+    /// Fully-qualified names of the request messages (e.g. `acme.users.v1.GetUserRequest`), by
+    /// the prefix of the request path (e.g. `/acme.users.v1.Users/GetUser`).
+    ///
+    /// The longest matching prefix wins.
+    pub messages: Vec<(String, String)>,
+}
+
+/// Encoded protobuf `FileDescriptorSet` (e.g. from `protoc --descriptor_set_out`).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum ProtoDescriptorSet {
+    /// The descriptor set itself.
+    Inline(Vec<u8>),
+    /// Path to the descriptor set in the agent's file system, e.g. mounted into the agent pod.
+    Path(String),
+}
+
+/// Result of [`HttpBodyFilter::Jq`] when the body cannot be evaluated.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum BodyFilterFallback {
+    /// The request is not stolen.
+    #[default]
+    NoMatch,
+    /// The request is stolen.
+    Match,
+    /// The expression is evaluated with a `null` body.
+    ///
+    /// Requires [`HTTP_BODY_FILTER_EVALUATE_VERSION`].
+    Evaluate,
 }
 
 /// Describes different types of HTTP filtering available
@@ -389,6 +475,30 @@ pub enum HttpFilter {
 
     /// Filter by header using JQ
     HeaderJq(JqQuery),
+
+    /// Matches when the inner filter does not match
+    Not(Box<HttpFilter>),
+
+    /// Applies the [`FilterErrorPolicy`] when the inner filter fails to evaluate, e.g. when a jq
+    /// expression errors. Used only at the top level.
+    OnError {
+        filter: Box<HttpFilter>,
+        policy: FilterErrorPolicy,
+    },
+}
+
+/// What the agent does with a request when evaluating its [`HttpFilter`] fails, e.g. when a jq
+/// expression errors or runs over its limits.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy, Default, strum_macros::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum FilterErrorPolicy {
+    /// The request is not stolen, and goes to its original destination.
+    #[default]
+    Pass,
+    /// The request is stolen.
+    Steal,
+    /// The agent responds to the request with `503 Service Unavailable`.
+    Close,
 }
 
 impl Display for HttpFilter {
@@ -427,6 +537,8 @@ impl Display for HttpFilter {
             },
             HttpFilter::Body(filter) => write!(f, "body={filter}"),
             HttpFilter::HeaderJq(filter) => write!(f, "header_jq={filter}"),
+            HttpFilter::Not(filter) => write!(f, "not ({filter})"),
+            HttpFilter::OnError { filter, policy } => write!(f, "{filter} (on error {policy})"),
         }
     }
 }
@@ -461,11 +573,16 @@ pub enum MirrorType {
     All(Port),
     /// Mirror HTTP traffic matching a given filter - supporting more than once kind of filter
     FilteredHttp(Port, HttpFilter),
+    /// Only report which HTTP requests match the given filter, see
+    /// [`LayerTcp::PortSubscribeDryRun`].
+    DryRun(Port, HttpFilter),
 }
 
 impl MirrorType {
     pub fn get_port(&self) -> Port {
-        let (MirrorType::All(port) | MirrorType::FilteredHttp(port, ..)) = self;
+        let (MirrorType::All(port)
+        | MirrorType::FilteredHttp(port, ..)
+        | MirrorType::DryRun(port, ..)) = self;
         *port
     }
 }
@@ -643,6 +760,43 @@ pub static HTTP_BODY_JSON_FILTER_VERSION: LazyLock<VersionReq> =
 pub static HTTP_HEADER_JQ_FILTER_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.26.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows HTTP body filtering with jq
+/// ([`HttpBodyFilter::Jq`]).
+pub static HTTP_BODY_JQ_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.27.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`HttpFilter::Composite`] filters nested in
+/// another [`HttpFilter::Composite`].
+pub static HTTP_NESTED_COMPOSITE_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.28.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`HttpFilter::Not`].
+///
+/// Older agents fail to decode subscriptions with this filter, so clients must not send it to
+/// them.
+pub static HTTP_NOT_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.29.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`HttpBodyFilter::GrpcJq`].
+pub static HTTP_BODY_GRPC_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.30.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`HttpFilter::OnError`].
+pub static HTTP_FILTER_ERROR_POLICY_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.31.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`LayerTcp::PortSubscribeDryRun`] and
+/// [`DaemonTcp::HttpFilterReport`].
+pub static HTTP_FILTER_DRY_RUN_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.38.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`BodyFilterFallback::Evaluate`].
+///
+/// Older agents fail to decode subscriptions with this fallback, so clients must not send it to
+/// them.
+pub static HTTP_BODY_FILTER_EVALUATE_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.39.0".parse().expect("Bad Identifier"));
+
 /// Protocol break - on version 2, please add source port, dest/src IP to the message
 /// so we can avoid losing this information.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -1,4 +1,4 @@
-use std::{boxed::Box, process::ExitStatus};
+use std::{boxed::Box, path::PathBuf, process::ExitStatus};
 
 use thiserror::Error;
 
@@ -48,6 +48,15 @@ pub enum SipError {
 
     #[error("Code sign failed with {0}, stderr: `{1}`")]
     Sign(ExitStatus, String),
+
+    #[error("Failed to SIP-patch `{}`: {source}", path.display())]
+    PatchFailed {
+        path: PathBuf,
+        source: Box<SipError>,
+    },
+
+    #[error("Too many nested shebangs when resolving the interpreter of `{0}`")]
+    ShebangTooDeep(String),
 }
 
 /// NOTE(gabriela): this was introduced in https://github.com/metalbear-co/mirrord/pull/3687
@@ -21,7 +21,10 @@ mod rpath;
 /// SIP and executes as usual.
 ///
 /// - If the process is a **script**, the shebang must be changed to point to the patched version of
-///   the interpreter.
+///   the interpreter. When the interpreter is itself a script, the whole chain of scripts is
+///   patched, and when it is `env`, the command it runs is patched as well.
+/// - If the process is a **universal (fat) binary**, only the slice matching the current
+///   architecture is patched, the other slices are kept as they are.
 ///
 /// ### Usage
 ///
@@ -37,6 +40,11 @@ mod rpath;
 /// - The directory where patched files exist is not randomly named, but uses the const
 ///   [`main::MIRRORD_PATCH_DIR`] due to an issue with the temp dir changing between executions.
 /// - A shebang is added to scripts without one in order to point it to the patched binary.
+/// - Patched files keep the path of the original file under the patch dir, as the layer strips that
+///   prefix to get back the original path. So a patched binary is reused only when the
+///   `.mirrord-cache-key` file next to it matches the path, modification time and architecture of
+///   the original binary.
+/// - Shebangs are followed up to a depth limit, to not loop forever on cyclic shebangs.
 /// - When checking a script, only the first line of the file is checked for a shebang, in case the
 ///   script is encoded unusually.
 /// - Some errors may prevent logs from printing out, making it harder to diagnose the issue. In
@@ -47,7 +55,11 @@ mod main {
         ffi::{OsStr, OsString},
         fs::{File, OpenOptions},
         io::{self, BufRead, ErrorKind, Write},
-        os::{macos::fs::MetadataExt, unix::fs::PermissionsExt},
+        ops::Range,
+        os::{
+            macos::fs::MetadataExt,
+            unix::{ffi::OsStrExt, fs::PermissionsExt},
+        },
         path::{Path, PathBuf},
         str::from_utf8,
         time::{SystemTime, UNIX_EPOCH},
@@ -157,11 +169,171 @@ mod main {
     struct BinaryInfo {
         offset: usize,
         size: usize,
+        /// Set when the binary is a slice of a universal (fat) binary.
+        fat: Option<FatBinary>,
+    }
+
+    /// A slice of a universal (fat) binary, as described in its header.
+    #[derive(Clone, Copy, Debug)]
+    struct FatSlice {
+        cputype: u32,
+        cpusubtype: u32,
+        offset: u64,
+        size: u64,
+        /// Alignment of the slice in the file, as a power of 2.
+        align: u32,
+    }
+
+    impl FatSlice {
+        fn new(arch: &impl FatArch) -> Self {
+            let (offset, size) = arch.file_range();
+            Self {
+                cputype: arch.cputype(),
+                cpusubtype: arch.cpusubtype(),
+                offset,
+                size,
+                align: arch.align(),
+            }
+        }
+
+        /// Whether the slice is kept next to the patched one in the patched universal binary.
+        ///
+        /// On apple chips arm64e slices are dropped, because the kernel would prefer them to the
+        /// patched slice.
+        fn is_kept(&self) -> bool {
+            cfg!(target_arch = "x86_64")
+                || !(self.cputype == macho::CPU_TYPE_ARM64
+                    && is_cpu_subtype_arm64e(self.cpusubtype))
+        }
+    }
+
+    /// Layout of a universal (fat) binary, of which only one slice is patched.
+    struct FatBinary {
+        is_64: bool,
+        slices: Vec<FatSlice>,
+        /// Index of the patched slice in [`FatBinary::slices`].
+        patched: usize,
+    }
+
+    impl FatBinary {
+        /// Returns a universal binary made of the `patched` slice, and the other slices of the
+        /// `original` file that are kept (see [`FatSlice::is_kept`]).
+        fn assemble(&self, original: &[u8], patched: &[u8]) -> Result<Vec<u8>> {
+            let slices = self
+                .slices
+                .iter()
+                .enumerate()
+                .filter(|(index, slice)| *index == self.patched || slice.is_kept())
+                .map(|(index, slice)| {
+                    if index == self.patched {
+                        return Ok((*slice, patched));
+                    }
+
+                    let bytes = usize::try_from(slice.offset)
+                        .ok()
+                        .zip(usize::try_from(slice.offset + slice.size).ok())
+                        .and_then(|(start, end)| original.get(start..end))
+                        .ok_or_else(|| {
+                            SipError::UnsupportedFileFormat(
+                                "Fat Mach-O slice out of the file bounds".to_owned(),
+                            )
+                        })?;
+                    Ok((*slice, bytes))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            build_fat_binary(self.is_64, &slices)
+        }
+    }
+
+    /// Writes a universal (fat) binary with the given slices, in order, each aligned as its
+    /// [`FatSlice::align`] requires. The offsets and sizes of the [`FatSlice`]s are ignored.
+    fn build_fat_binary(is_64: bool, slices: &[(FatSlice, &[u8])]) -> Result<Vec<u8>> {
+        let unsupported = |reason: &str| SipError::UnsupportedFileFormat(reason.to_string());
+
+        // The fat header is the magic and the number of slices, then a fixed size entry per slice.
+        let entry_len = if is_64 { 32 } else { 20 };
+        let mut end = (8 + entry_len * slices.len()) as u64;
+        let mut offsets = Vec::with_capacity(slices.len());
+        for (slice, bytes) in slices {
+            let alignment = 1u64
+                .checked_shl(slice.align)
+                .ok_or_else(|| unsupported("Fat Mach-O slice alignment too big"))?;
+            let offset = end.next_multiple_of(alignment);
+            offsets.push(offset);
+            end = offset + bytes.len() as u64;
+        }
+
+        let mut fat = Vec::with_capacity(end as usize);
+        let magic = if is_64 {
+            macho::FAT_MAGIC_64
+        } else {
+            macho::FAT_MAGIC
+        };
+        fat.extend_from_slice(&magic.to_be_bytes());
+        fat.extend_from_slice(&(slices.len() as u32).to_be_bytes());
+
+        for ((slice, bytes), offset) in slices.iter().zip(&offsets) {
+            fat.extend_from_slice(&slice.cputype.to_be_bytes());
+            fat.extend_from_slice(&slice.cpusubtype.to_be_bytes());
+            if is_64 {
+                fat.extend_from_slice(&offset.to_be_bytes());
+                fat.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+                fat.extend_from_slice(&slice.align.to_be_bytes());
+                // reserved
+                fat.extend_from_slice(&0u32.to_be_bytes());
+            } else {
+                let too_big = |_| unsupported("32-bit fat Mach-O slices too big");
+                fat.extend_from_slice(&u32::try_from(*offset).map_err(too_big)?.to_be_bytes());
+                fat.extend_from_slice(&u32::try_from(bytes.len()).map_err(too_big)?.to_be_bytes());
+                fat.extend_from_slice(&slice.align.to_be_bytes());
+            }
+        }
+
+        for ((_, bytes), offset) in slices.iter().zip(offsets) {
+            fat.resize(offset as usize, 0);
+            fat.extend_from_slice(bytes);
+        }
+
+        Ok(fat)
     }
 
     impl BinaryInfo {
         fn new(offset: usize, size: usize) -> Self {
-            Self { offset, size }
+            Self {
+                offset,
+                size,
+                fat: None,
+            }
+        }
+
+        /// Picks the slice to patch out of the `arches` of a fat binary: arm64 (not arm64e) if
+        /// there is one on apple chips, x64 otherwise.
+        fn from_fat_arches<A: FatArch>(arches: &[A], is_64: bool) -> Result<Self> {
+            #[cfg(target_arch = "aarch64")]
+            let found_arch = arches
+                .iter()
+                .position(|arch| is_fat_arm64_arch(&arch))
+                .or_else(|| arches.iter().position(|arch| is_fat_x64_arch(&arch)));
+
+            #[cfg(target_arch = "x86_64")]
+            let found_arch = arches.iter().position(|arch| is_fat_x64_arch(&arch));
+
+            let patched = found_arch.ok_or(SipError::NoSupportedArchitecture)?;
+            let slices = arches.iter().map(FatSlice::new).collect::<Vec<_>>();
+            let slice = slices
+                .get(patched)
+                .ok_or_else(|| UnlikelyError("Fat Mach-O slice disappeared".to_owned()))?;
+
+            Ok(Self {
+                offset: slice.offset as usize,
+                size: slice.size as usize,
+                fat: Some(FatBinary {
+                    is_64,
+                    slices,
+                    patched,
+                }),
+            })
         }
 
         /// Takes the cpu type and subtype and the bytes of a file that is a non-fat Mach-O, and
@@ -187,7 +359,8 @@ mod main {
         ///
         /// If the file is a fat binary, then if it contains an arm64 binary (not arm64e) the info
         /// of that binary is returned. If there is no arm64 but there is an x64 binary, then the
-        /// info of that binary is returned.
+        /// info of that binary is returned. The layout of the fat binary is kept in
+        /// [`BinaryInfo::fat`], so that the other slices can be preserved.
         ///
         /// # Errors
         ///
@@ -216,19 +389,7 @@ mod main {
                     let fat_slice = read::macho::MachOFatFile32::parse(bytes).map_err(|_| {
                         SipError::UnsupportedFileFormat("FatMach-O 32-bit".to_string())
                     })?;
-                    #[cfg(target_arch = "aarch64")]
-                    let found_arch = fat_slice
-                        .arches()
-                        .iter()
-                        .find(is_fat_arm64_arch)
-                        .or_else(|| fat_slice.arches().iter().find(is_fat_x64_arch));
-
-                    #[cfg(target_arch = "x86_64")]
-                    let found_arch = fat_slice.arches().iter().find(is_fat_x64_arch);
-
-                    found_arch
-                        .map(|arch| Self::new(arch.offset() as usize, arch.size() as usize))
-                        .ok_or(SipError::NoSupportedArchitecture)
+                    Self::from_fat_arches(fat_slice.arches(), false)
                 }
 
                 // It seems like 64 bit fat Mach-Os are only used (if at all) when one of the
@@ -238,20 +399,7 @@ mod main {
                     let fat_slice = read::macho::MachOFatFile64::parse(bytes).map_err(|_| {
                         SipError::UnsupportedFileFormat("Mach-O 32-bit".to_string())
                     })?;
-
-                    #[cfg(target_arch = "aarch64")]
-                    let found_arch = fat_slice
-                        .arches()
-                        .iter()
-                        .find(is_fat_arm64_arch)
-                        .or_else(|| fat_slice.arches().iter().find(is_fat_x64_arch));
-
-                    #[cfg(target_arch = "x86_64")]
-                    let found_arch = fat_slice.arches().iter().find(is_fat_x64_arch);
-
-                    found_arch
-                        .map(|arch| Self::new(arch.offset() as usize, arch.size() as usize))
-                        .ok_or(SipError::NoSupportedArchitecture)
+                    Self::from_fat_arches(fat_slice.arches(), true)
                 }
                 other => Err(SipError::UnsupportedFileFormat(format!("{other:?}"))),
             }
@@ -321,21 +469,71 @@ mod main {
         rpath::add_rpaths(output_path, new_entries)
     }
 
-    /// Read the contents (or just the x86_64 section in case of a fat file) from the SIP binary at
-    /// `path`, write it into `output`, give it the same permissions, and sign the new binary.
+    /// Identifies the version of the binary at `path` that is patched: its path, modification time
+    /// and the architecture of the slice that is patched.
+    fn patch_cache_key(path: &Path) -> Result<String> {
+        let modified = std::fs::metadata(path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok(format!(
+            "{}\n{}\n{}",
+            path.display(),
+            modified.as_nanos(),
+            env::consts::ARCH
+        ))
+    }
+
+    /// Where the [`patch_cache_key`] of the binary patched at `output` is stored.
+    fn patch_cache_key_path(output: &Path) -> PathBuf {
+        let mut key_path = output.as_os_str().to_owned();
+        key_path.push(".mirrord-cache-key");
+        PathBuf::from(key_path)
+    }
+
+    /// Patch the SIP binary at `path`, unless the patched version at the output path was created
+    /// from the same version of the binary (see [`patch_cache_key`]), in which case it is reused
+    /// without signing again.
+    ///
+    /// Errors are wrapped in [`SipError::PatchFailed`], to name the binary that couldn't be
+    /// patched.
     fn patch_binary(path: &Path) -> Result<PathBuf> {
         set_fallback_frameworks_path_if_mac_app(path);
 
-        let output = get_output_path(path)?;
+        let patch = || -> Result<PathBuf> {
+            let output = get_output_path(path)?;
+            let cache_key = patch_cache_key(path)?;
+            let cache_key_path = patch_cache_key_path(&output);
 
-        if output.exists() {
-            trace!(
-                "Using existing SIP-patched version of {:?}: {:?}",
-                path, output
-            );
-            return Ok(output);
-        }
+            if output.exists()
+                && std::fs::read_to_string(&cache_key_path).is_ok_and(|key| key == cache_key)
+            {
+                trace!(
+                    "Using existing SIP-patched version of {:?}: {:?}",
+                    path, output
+                );
+                return Ok(output);
+            }
+
+            sign_patched_binary(path, &output)?;
+            std::fs::write(&cache_key_path, cache_key)?;
 
+            Ok(output)
+        };
+
+        patch().map_err(|error| SipError::PatchFailed {
+            path: path.to_path_buf(),
+            source: Box::new(error),
+        })
+    }
+
+    /// Read the contents (or just the supported slice in case of a fat file) from the SIP binary
+    /// at `path`, sign it, give it the same permissions, and move it to `output`.
+    ///
+    /// The other slices of a fat file are put back around the signed slice, so that the patched
+    /// binary can still run on the architectures the original binary supports.
+    fn sign_patched_binary(path: &Path, output: &Path) -> Result<()> {
         // If the same file is executed in parallel, parallel signing could fail. So do the work on
         // a temp file, and then move it to its final destination once ready and signed.
         let temp_binary = tempfile::NamedTempFile::new()?;
@@ -367,27 +565,50 @@ mod main {
         let signed_temp_file = tempfile::NamedTempFile::new()?;
         codesign::sign(&temp_binary, &signed_temp_file, path)?;
 
+        if let Some(fat) = &binary_info.fat {
+            let signed = std::fs::read(&signed_temp_file)?;
+            std::fs::write(&signed_temp_file, fat.assemble(&data, &signed)?)?;
+        }
+
         // Give the new file the same permissions as the old file.
         // This needs to happen after the sign because it might change the permissions.
         trace!("Setting permissions for {temp_binary:?}");
         std::fs::set_permissions(&signed_temp_file, std::fs::metadata(path)?.permissions())?;
 
-        // Move the temp binary into its final location if no other process/thread already did.
-        if let Err(err) = signed_temp_file.persist_noclobber(&output)
-            && err.error.kind() != std::io::ErrorKind::AlreadyExists
-        {
-            return Err(SipError::BinaryMoveFailed(err.error));
-        }
+        // Move the temp binary into its final location, replacing a patched version of an older
+        // version of the binary. Processes that already run the replaced file are not affected.
+        signed_temp_file
+            .persist(output)
+            .map_err(|err| SipError::BinaryMoveFailed(err.error))?;
 
-        Ok(output)
+        Ok(())
     }
 
     /// Create a new file at `patched_path` with the same contents as `original_path` except for
-    /// the shebang which is `new_shebang`.
+    /// the shebang, which points to `new_interpreter`, and to `new_env_command` instead of the
+    /// [`ScriptShebang::env_command`], if given.
+    ///
+    /// Errors are wrapped in [`SipError::PatchFailed`], to name the script that couldn't be
+    /// patched.
     fn patch_script(
         original_path: &Path,
         shebang: ScriptShebang,
-        new_shebang: &str,
+        new_interpreter: &Path,
+        new_env_command: Option<&Path>,
+    ) -> Result<PathBuf> {
+        write_patched_script(original_path, shebang, new_interpreter, new_env_command).map_err(
+            |error| SipError::PatchFailed {
+                path: original_path.to_path_buf(),
+                source: Box::new(error),
+            },
+        )
+    }
+
+    fn write_patched_script(
+        original_path: &Path,
+        shebang: ScriptShebang,
+        new_interpreter: &Path,
+        new_env_command: Option<&Path>,
     ) -> Result<PathBuf> {
         let patched_path = get_output_path(original_path)?;
 
@@ -400,13 +621,27 @@ mod main {
         let contents = data
             .get(shebang.start_of_rest_of_file..)
             .expect("original shebang size exceeds file size");
-        let mut new_contents = String::from("#!") + new_shebang;
+        let mut new_contents = String::from("#!") + new_interpreter.to_string_lossy().as_ref();
         if shebang.start_of_rest_of_file == 0 {
             // trailing newline is needed for scripts without an original shebang
             new_contents.push('\n');
         }
         let mut bytes = new_contents.into_bytes();
-        bytes.extend_from_slice(contents);
+        match (shebang.env_command, new_env_command) {
+            (Some(EnvCommand { range, .. }), Some(new_env_command)) => {
+                let (Some(before), Some(after)) =
+                    (contents.get(..range.start), contents.get(range.end..))
+                else {
+                    return Err(UnlikelyError(
+                        "env command out of the shebang bounds".to_owned(),
+                    ));
+                };
+                bytes.extend_from_slice(before);
+                bytes.extend_from_slice(new_env_command.as_os_str().as_bytes());
+                bytes.extend_from_slice(after);
+            }
+            _ => bytes.extend_from_slice(contents),
+        }
 
         std::fs::write(&patched_path, bytes)?;
 
@@ -440,12 +675,57 @@ mod main {
                 // magic, whitespace and path.
                 (first_line.get(start_of_path + 2..)?, first_line.len())
             };
+        let interpreter_path = PathBuf::from(interpreter);
+        let env_command = if interpreter_path.file_name() == Some(OsStr::new("env")) {
+            get_env_command(first_line.get(len_with_whitespace..)?)
+        } else {
+            None
+        };
+
         Some(ScriptShebang {
-            interpreter_path: PathBuf::from(interpreter),
+            interpreter_path,
             start_of_rest_of_file: len_with_whitespace,
+            env_command,
         })
     }
 
+    /// Finds the command in the arguments of an `env` shebang, skipping the options (including
+    /// `-S`) and the variable assignments.
+    ///
+    /// Returns [`None`] if there's no command, or when an option changes how the command is found
+    /// (`-P`) or is unknown.
+    fn get_env_command(args: &str) -> Option<EnvCommand> {
+        let mut words = args
+            .split_whitespace()
+            .map(|word| (word.as_ptr() as usize - args.as_ptr() as usize, word));
+
+        while let Some((start, word)) = words.next() {
+            match word {
+                "-S"
+                | "--split-string"
+                | "-i"
+                | "--ignore-environment"
+                | "-"
+                | "-v"
+                | "-0"
+                | "--null" => {}
+                "-u" | "--unset" | "-C" | "--chdir" => {
+                    words.next()?;
+                }
+                option if option.starts_with('-') => return None,
+                assignment if assignment.contains('=') => {}
+                command => {
+                    return Some(EnvCommand {
+                        name: command.to_string(),
+                        range: start..start + command.len(),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
     /// Including '#!', just until whitespace, no arguments.
     /// will not be called on object files
     fn read_shebang_from_file<P: AsRef<Path>>(path: P) -> Result<Option<ScriptShebang>> {
@@ -478,14 +758,36 @@ mod main {
         /// !# /usr/bin/env bash
         ///                ^-- Rest of the file starts at index 15.
         start_of_rest_of_file: usize,
+
+        /// Set when the interpreter is `env`, e.g. `bash` in `#!/usr/bin/env -S bash -e`.
+        env_command: Option<EnvCommand>,
+    }
+
+    /// The command run by an `env` interpreter, see [`get_env_command`].
+    #[derive(Debug)]
+    struct EnvCommand {
+        name: String,
+        /// Where the command is, relative to [`ScriptShebang::start_of_rest_of_file`].
+        range: Range<usize>,
+    }
+
+    /// An executable a shebang leads to, with its complete path.
+    #[derive(Debug)]
+    struct ShebangTarget {
+        path: PathBuf,
+        status: SipStatus,
     }
 
     #[derive(Debug)]
     enum SipStatus {
-        /// The executable is a script with a shebang that points to a SIP-protected binary.
+        /// The executable is a script with a shebang that leads to a SIP-protected binary: either
+        /// its interpreter, the command run by an `env` interpreter, or a binary further down a
+        /// chain of scripts.
         SipScript {
             path: PathBuf,
             shebang: ScriptShebang,
+            interpreter: Box<ShebangTarget>,
+            env_command: Option<Box<ShebangTarget>>,
         },
         /// The executable is a SIP-protected binary.
         SipBinary(PathBuf),
@@ -493,6 +795,12 @@ mod main {
         NoSip,
     }
 
+    impl SipStatus {
+        fn is_sip(&self) -> bool {
+            !matches!(self, NoSip)
+        }
+    }
+
     /// Checks if binary is signed with either `RUNTIME` or `RESTRICTED` flags.
     /// The code ignores error to allow smoother fallbacks.
     fn is_code_signed(data: &[u8]) -> bool {
@@ -543,12 +851,36 @@ mod main {
             .unwrap_or_default())
     }
 
+    /// Resolves the interpreter of a shebang like the kernel does: a relative path is relative to
+    /// the current directory, and is not looked up in `PATH`.
+    fn get_interpreter_complete_path(interpreter: &Path) -> Result<PathBuf> {
+        let complete_path = env::current_dir()?.join(interpreter);
+        if !complete_path.exists() {
+            return Err(FileNotFound(complete_path.to_string_lossy().to_string()));
+        }
+        Ok(complete_path)
+    }
+
+    /// How many shebangs are followed from the executed file, before giving up with
+    /// [`SipError::ShebangTooDeep`].
+    const MAX_SHEBANG_DEPTH: usize = 4;
+
     /// Checks the SF_RESTRICTED flags on a file (there might be a better check, feel free to
     /// suggest)
     /// If file is a script with shebang, the SipStatus is derived from the SipStatus of the
-    /// file the shebang points to.
+    /// files the shebang points to, recursively.
     fn get_sip_status(path: &str, opts: SipPatchOptions) -> Result<SipStatus> {
         let complete_path = get_complete_path(path)?;
+        get_file_sip_status(complete_path, opts, 0)
+    }
+
+    /// [`get_sip_status`] of the file at `complete_path`, which was reached by following `depth`
+    /// shebangs.
+    fn get_file_sip_status(
+        complete_path: PathBuf,
+        opts: SipPatchOptions,
+        depth: usize,
+    ) -> Result<SipStatus> {
         // If the binary is in our temp bin dir, it's not SIP protected.
         if is_in_mirrord_tmp_dir(&complete_path)? {
             return Ok(NoSip);
@@ -558,43 +890,97 @@ mod main {
         let data = std::fs::read(&complete_path)?;
         if MachFile::parse(data.as_ref()).is_ok() {
             // file is an object file
-            is_binary_sip(&complete_path, &data, opts).map(|is_sip| {
+            return is_binary_sip(&complete_path, &data, opts).map(|is_sip| {
                 if is_sip {
                     SipBinary(complete_path)
                 } else {
                     NoSip
                 }
-            })
-        } else {
-            let shebang = read_shebang_from_file(&complete_path)?.unwrap_or_else(|| {
+            });
+        }
+
+        if depth >= MAX_SHEBANG_DEPTH {
+            return Err(SipError::ShebangTooDeep(
+                complete_path.to_string_lossy().to_string(),
+            ));
+        }
+
+        let shebang = match read_shebang_from_file(&complete_path)? {
+            Some(shebang) => shebang,
+            // Only the executed file is run by the shell, an interpreter has to be executable.
+            None if depth > 0 => return Ok(NoSip),
+            None => {
                 trace!("Did not find a shebang, defaulting to $SHELL.");
                 ScriptShebang {
                     interpreter_path: PathBuf::from(
                         env::var("SHELL").expect("$SHELL should be present"),
                     ),
                     start_of_rest_of_file: 0,
+                    env_command: None,
                 }
-            });
-
-            let interpreter_complete_path = get_complete_path(&shebang.interpreter_path)?;
-            if is_in_mirrord_tmp_dir(&interpreter_complete_path)? {
-                return Ok(NoSip);
             }
-            let data = std::fs::read(&interpreter_complete_path)?;
-            is_binary_sip(&interpreter_complete_path, &data, opts).map(|is_sip| {
-                if is_sip {
-                    SipScript {
-                        path: complete_path,
-                        shebang,
-                    }
-                } else {
-                    // The interpreter the shebang points to is not protected.
-                    NoSip
-                }
+        };
+
+        let interpreter_path = get_interpreter_complete_path(&shebang.interpreter_path)?;
+        let interpreter = ShebangTarget {
+            status: get_file_sip_status(interpreter_path.clone(), opts, depth + 1)?,
+            path: interpreter_path,
+        };
+
+        // `env` looks the command up in `PATH`, the command failing to resolve is left for `env`
+        // to report when the script runs.
+        let env_command = shebang.env_command.as_ref().and_then(|command| {
+            let path = get_complete_path(command.name.as_str()).ok()?;
+            let status = get_file_sip_status(path.clone(), opts, depth + 1).ok()?;
+            Some(ShebangTarget { path, status })
+        });
+
+        if interpreter.status.is_sip()
+            || env_command
+                .as_ref()
+                .is_some_and(|command| command.status.is_sip())
+        {
+            Ok(SipScript {
+                path: complete_path,
+                shebang,
+                interpreter: Box::new(interpreter),
+                env_command: env_command.map(Box::new),
             })
+        } else {
+            // Nothing the shebang leads to is protected.
+            Ok(NoSip)
         }
     }
 
+    /// Patches the SIP-protected binaries the status leads to, and the scripts that lead to them,
+    /// bottom-up, so that the shebang of each patched script points to the patched version of its
+    /// interpreter.
+    ///
+    /// Returns the patched version of the executable, [`None`] if it's not SIP protected.
+    fn patch_sip_status(status: SipStatus) -> Result<Option<PathBuf>> {
+        match status {
+            SipScript {
+                path,
+                shebang,
+                interpreter,
+                env_command,
+            } => {
+                let interpreter = patch_shebang_target(*interpreter)?;
+                let env_command = env_command
+                    .map(|command| patch_shebang_target(*command))
+                    .transpose()?;
+                patch_script(&path, shebang, &interpreter, env_command.as_deref()).map(Some)
+            }
+            SipBinary(binary) => patch_binary(&binary).map(Some),
+            NoSip => Ok(None),
+        }
+    }
+
+    /// The patched version of the target, or the target itself if it's not SIP protected.
+    fn patch_shebang_target(target: ShebangTarget) -> Result<PathBuf> {
+        Ok(patch_sip_status(target.status)?.unwrap_or(target.path))
+    }
+
     /// When patching a bundled mac application, it try to load libraries from its frameworks
     /// directory. The patch might cause it to search under the `mirrord-bin` temp dir.
     ///
@@ -628,9 +1014,6 @@ mod main {
     /// Get new path for patched version, both as PathBuf and as a string, and make the dir
     /// of the path, recursively.
     fn get_output_path(path: &Path) -> Result<PathBuf> {
-        // TODO: Change output to be with hash of the contents, so that old versions of changed
-        //       files do not get used. (Also change back existing file logic to always use.)
-
         let output = MIRRORD_TEMP_BIN_DIR_PATH_BUF.join(
             // Strip root path from binary path, as when joined it will clear the previous.
             path.strip_prefix("/").unwrap_or(path), // No prefix - no problem.
@@ -734,21 +1117,8 @@ mod main {
         // DO NOT INTRODUCE NEW TRACING LOGS OR CHANGE THE LEVEL OF EXISTING LOGS - tracing logs are
         // NOT fork safe, and have been suspected to cause issues.
         let patch_result = match status {
-            Ok(SipScript { path, shebang }) => {
-                let patched_interpreter = patch_binary(&shebang.interpreter_path)?;
-                let patched_script = patch_script(
-                    &path,
-                    shebang,
-                    patched_interpreter.to_string_lossy().as_ref(),
-                )
-                .map(|path| path.to_string_lossy().to_string());
-                Some(patched_script).transpose()
-            }
-            Ok(SipBinary(binary)) => {
-                let patched_binary =
-                    patch_binary(&binary).map(|path| path.to_string_lossy().to_string());
-                Some(patched_binary).transpose()
-            }
+            Ok(status @ (SipScript { .. } | SipBinary(..))) => patch_sip_status(status)
+                .map(|patched| patched.map(|path| path.to_string_lossy().to_string())),
             Ok(NoSip) => {
                 trace!("No SIP detected on {:?}", binary_path);
                 Ok(None)
@@ -919,9 +1289,9 @@ mod main {
         }
 
         /// Test that when a fat binary contains an arm64 binary, that binary is used and patching
-        /// works.
+        /// works, and that the slices of the other architectures are kept.
         ///
-        /// This assumes `/usr/bin/file` is present and contains an arm64 binary.
+        /// This assumes `/usr/bin/file` is present and contains an arm64 and an x86_64 binary.
         #[test]
         fn patch_binary_fat_with_arm64() {
             let path = "/usr/bin/file";
@@ -938,16 +1308,120 @@ mod main {
                 .unwrap();
             assert!(String::from_utf8_lossy(&output.stderr).contains("libsystem_kernel.dylib"));
 
-            // Check that the binary was chosen according to the architecture.
+            // Check that the binary was chosen according to the architecture, and that the other
+            // slices are still there.
+            let original = std::fs::read(path).unwrap();
             let data = std::fs::read(patched_path).unwrap();
-            let file_kind = FileKind::parse(&data[..]).unwrap();
-            assert_eq!(file_kind, FileKind::MachO64);
-            let header: &MachHeader64<Endianness> = MachHeader::parse(&data[..], 0).unwrap();
+            let BinaryInfo {
+                fat: Some(original_fat),
+                ..
+            } = BinaryInfo::from_object_bytes(&original).unwrap()
+            else {
+                panic!("{path} is not a universal binary");
+            };
+            let BinaryInfo {
+                offset,
+                fat: Some(patched_fat),
+                ..
+            } = BinaryInfo::from_object_bytes(&data).unwrap()
+            else {
+                panic!("patched {path} is not a universal binary");
+            };
+
+            let header: &MachHeader64<Endianness> =
+                MachHeader::parse(&data[..], offset as u64).unwrap();
             let cpu_type = header.cputype(Endianness::default());
             #[cfg(target_arch = "aarch64")]
             assert_eq!(cpu_type, macho::CPU_TYPE_ARM64);
             #[cfg(target_arch = "x86_64")]
             assert_eq!(cpu_type, macho::CPU_TYPE_X86_64);
+
+            let kept_cpu_types = |fat: &FatBinary| {
+                fat.slices
+                    .iter()
+                    .filter(|slice| slice.is_kept())
+                    .map(|slice| slice.cputype)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(kept_cpu_types(&original_fat), kept_cpu_types(&patched_fat));
+            assert!(patched_fat.slices.len() > 1);
+        }
+
+        #[test]
+        fn fat_binary_keeps_slices() {
+            let slice = |cputype, cpusubtype, align| FatSlice {
+                cputype,
+                cpusubtype,
+                offset: 0,
+                size: 0,
+                align,
+            };
+            let x64 = slice(macho::CPU_TYPE_X86_64, macho::CPU_SUBTYPE_X86_64_ALL, 12);
+            let arm64 = slice(macho::CPU_TYPE_ARM64, macho::CPU_SUBTYPE_ARM64_ALL, 14);
+
+            let fat = build_fat_binary(false, &[(x64, b"x86_64 slice"), (arm64, b"arm64 slice")])
+                .unwrap();
+
+            let parsed = read::macho::MachOFatFile32::parse(&fat[..]).unwrap();
+            let arches = parsed.arches();
+            assert_eq!(arches.len(), 2);
+            for (arch, (expected, bytes)) in arches
+                .iter()
+                .zip([(x64, &b"x86_64 slice"[..]), (arm64, &b"arm64 slice"[..])])
+            {
+                assert_eq!(arch.cputype(), expected.cputype);
+                assert_eq!(arch.align(), expected.align);
+                let (offset, _) = arch.file_range();
+                assert_eq!(offset % (1 << expected.align), 0);
+                assert_eq!(arch.data(&fat[..]).unwrap(), bytes);
+            }
+        }
+
+        /// The patched file is reused as long as the original binary doesn't change, and is patched
+        /// again once it does.
+        #[test]
+        fn patched_binary_reused_until_changed() {
+            let binary = tempfile::NamedTempFile::new().unwrap();
+            std::fs::copy("/bin/ls", binary.path()).unwrap();
+
+            let patched_path = patch_binary(binary.path()).unwrap();
+            let patched_at = std::fs::metadata(&patched_path)
+                .unwrap()
+                .modified()
+                .unwrap();
+
+            assert_eq!(patch_binary(binary.path()).unwrap(), patched_path);
+            let reused_at = std::fs::metadata(&patched_path)
+                .unwrap()
+                .modified()
+                .unwrap();
+            assert_eq!(patched_at, reused_at);
+
+            binary
+                .as_file()
+                .set_modified(SystemTime::now() + std::time::Duration::from_secs(60))
+                .unwrap();
+            assert_eq!(patch_binary(binary.path()).unwrap(), patched_path);
+            let repatched_at = std::fs::metadata(&patched_path)
+                .unwrap()
+                .modified()
+                .unwrap();
+            assert_ne!(patched_at, repatched_at);
+        }
+
+        #[test]
+        fn patch_failure_names_binary() {
+            let mut not_binary = tempfile::NamedTempFile::new().unwrap();
+            not_binary.write_all(b"not a binary").unwrap();
+            not_binary.flush().unwrap();
+
+            let error = patch_binary(not_binary.path()).unwrap_err();
+            assert!(matches!(error, SipError::PatchFailed { .. }));
+            assert!(
+                error
+                    .to_string()
+                    .contains(not_binary.path().to_str().unwrap())
+            );
         }
 
         fn test_patch_script(script_contents: &str) {
@@ -1079,6 +1553,124 @@ mod main {
                 .unwrap();
         }
 
+        /// Write an executable script with the given contents.
+        fn write_script(contents: &str) -> tempfile::NamedTempFile {
+            let mut script = tempfile::NamedTempFile::new().unwrap();
+            script.write_all(contents.as_ref()).unwrap();
+            script.flush().unwrap();
+            std::fs::set_permissions(script.path(), std::fs::Permissions::from_mode(0o700))
+                .unwrap();
+            script
+        }
+
+        /// A script whose interpreter is another script is patched along with that script, and the
+        /// binary at the end of the chain.
+        #[test]
+        fn patch_shebang_chain() {
+            let inner = write_script("#!/bin/bash\nexec bash \"$@\"\n");
+            let outer = write_script(&format!(
+                "#!{}\necho hello\n",
+                inner.path().to_str().unwrap()
+            ));
+
+            let patched_outer = sip_patch(
+                outer.path().to_str().unwrap(),
+                SipPatchOptions::default(),
+                None,
+            )
+            .unwrap()
+            .unwrap();
+
+            let patched_inner = read_shebang_from_file(patched_outer)
+                .unwrap()
+                .unwrap()
+                .interpreter_path;
+            assert_eq!(patched_inner, get_output_path(inner.path()).unwrap());
+
+            let patched_bash = read_shebang_from_file(patched_inner)
+                .unwrap()
+                .unwrap()
+                .interpreter_path;
+            assert_eq!(patched_bash, get_output_path("/bin/bash".as_ref()).unwrap());
+        }
+
+        /// A relative interpreter is resolved from the current directory, like the kernel does.
+        #[test]
+        fn patch_relative_shebang() {
+            let current_dir = env::current_dir().unwrap();
+            let relative_bash = std::iter::repeat_n("..", current_dir.components().count() - 1)
+                .collect::<PathBuf>()
+                .join("bin/bash");
+
+            test_patch_script(&format!(
+                "#!{}\necho hello\n",
+                relative_bash.to_str().unwrap()
+            ));
+        }
+
+        /// The command run by `env -S` is patched, and its arguments are kept.
+        #[test]
+        fn patch_env_split_string_shebang() {
+            let script = write_script("#!/usr/bin/env -S bash -e\necho hello\n");
+
+            let patched_script = sip_patch(
+                script.path().to_str().unwrap(),
+                SipPatchOptions::default(),
+                None,
+            )
+            .unwrap()
+            .unwrap();
+
+            let first_line = std::fs::read_to_string(patched_script)
+                .unwrap()
+                .lines()
+                .next()
+                .unwrap()
+                .to_string();
+            assert_eq!(
+                first_line,
+                format!(
+                    "#!{} -S {} -e",
+                    get_output_path("/usr/bin/env".as_ref()).unwrap().display(),
+                    get_output_path("/bin/bash".as_ref()).unwrap().display(),
+                )
+            );
+        }
+
+        #[test]
+        fn env_command_from_shebang() {
+            let contents = "#!/usr/bin/env -S PYTHONPATH=. python3 -u\n";
+            let shebang = get_shebang_from_string(contents).unwrap();
+            let command = shebang.env_command.unwrap();
+            assert_eq!(command.name, "python3");
+            assert_eq!(
+                contents
+                    .get(shebang.start_of_rest_of_file..)
+                    .unwrap()
+                    .get(command.range),
+                Some("python3")
+            );
+
+            let command = get_shebang_from_string("#!/usr/bin/env -i -u HOME bash")
+                .unwrap()
+                .env_command
+                .unwrap();
+            assert_eq!(command.name, "bash");
+
+            assert!(
+                get_shebang_from_string("#!/usr/bin/env -P /opt/bin bash")
+                    .unwrap()
+                    .env_command
+                    .is_none()
+            );
+            assert!(
+                get_shebang_from_string("#!/bin/bash -e")
+                    .unwrap()
+                    .env_command
+                    .is_none()
+            );
+        }
+
         /// Run `sip_patch` on a file that has a shebang that points to itself and verify that we
         /// don't get stuck in a recursion until the stack overflows.
         #[test]
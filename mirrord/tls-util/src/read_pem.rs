@@ -1,4 +1,9 @@
-use std::{fs::File, io::BufReader, ops::Not, path::PathBuf};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    ops::Not,
+    path::PathBuf,
+};
 
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use rustls_pemfile::Item;
@@ -21,13 +26,7 @@ pub async fn read_cert_chain(path: PathBuf) -> Result<Vec<CertificateDer<'static
             Err(error) => return Err(FromPemError::OpenFileError { error, path }),
         };
 
-        let cert_chain = rustls_pemfile::certs(&mut file).collect::<Result<Vec<_>, _>>();
-
-        match cert_chain {
-            Ok(cert_chain) if cert_chain.is_empty().not() => Ok(cert_chain),
-            Ok(..) => Err(FromPemError::NoCertFound(path)),
-            Err(error) => Err(FromPemError::ParseFileError { error, path }),
-        }
+        cert_chain_from_reader(&mut file, path)
     })
     .await?
 }
@@ -46,23 +45,70 @@ pub async fn read_key_der(path: PathBuf) -> Result<PrivateKeyDer<'static>, FromP
             Err(error) => return Err(FromPemError::OpenFileError { error, path }),
         };
 
-        let mut found_key = None;
+        key_der_from_reader(&mut file, path)
+    })
+    .await?
+}
+
+/// Parses a certificate chain from the given PEM data, following the same rules as
+/// [`read_cert_chain`].
+///
+/// `source` describes where the data comes from, and is only used in errors.
+///
+/// All logic is done in a blocking task. See this crate's doc for rationale.
+#[tracing::instrument(level = Level::DEBUG, skip(pem), ret, err(level = Level::DEBUG))]
+pub async fn parse_cert_chain(
+    pem: Vec<u8>,
+    source: PathBuf,
+) -> Result<Vec<CertificateDer<'static>>, FromPemError> {
+    tokio::task::spawn_blocking(move || cert_chain_from_reader(&mut pem.as_slice(), source)).await?
+}
+
+/// Parses a private key from the given PEM data, following the same rules as [`read_key_der`].
+///
+/// `source` describes where the data comes from, and is only used in errors.
+///
+/// All logic is done in a blocking task. See this crate's doc for rationale.
+#[tracing::instrument(level = Level::DEBUG, skip(pem), ret, err(level = Level::DEBUG))]
+pub async fn parse_key_der(
+    pem: Vec<u8>,
+    source: PathBuf,
+) -> Result<PrivateKeyDer<'static>, FromPemError> {
+    tokio::task::spawn_blocking(move || key_der_from_reader(&mut pem.as_slice(), source)).await?
+}
+
+fn cert_chain_from_reader(
+    reader: &mut dyn BufRead,
+    path: PathBuf,
+) -> Result<Vec<CertificateDer<'static>>, FromPemError> {
+    let cert_chain = rustls_pemfile::certs(reader).collect::<Result<Vec<_>, _>>();
 
-        for entry in rustls_pemfile::read_all(&mut file) {
-            let key = match entry {
-                Ok(Item::Pkcs1Key(key)) => PrivateKeyDer::Pkcs1(key),
-                Ok(Item::Pkcs8Key(key)) => PrivateKeyDer::Pkcs8(key),
-                Ok(Item::Sec1Key(key)) => PrivateKeyDer::Sec1(key),
-                Ok(..) => continue,
-                Err(error) => return Err(FromPemError::ParseFileError { error, path }),
-            };
+    match cert_chain {
+        Ok(cert_chain) if cert_chain.is_empty().not() => Ok(cert_chain),
+        Ok(..) => Err(FromPemError::NoCertFound(path)),
+        Err(error) => Err(FromPemError::ParseFileError { error, path }),
+    }
+}
 
-            if found_key.replace(key).is_some() {
-                return Err(FromPemError::MultipleKeysFound(path));
-            }
+fn key_der_from_reader(
+    reader: &mut dyn BufRead,
+    path: PathBuf,
+) -> Result<PrivateKeyDer<'static>, FromPemError> {
+    let mut found_key = None;
+
+    for entry in rustls_pemfile::read_all(reader) {
+        let key = match entry {
+            Ok(Item::Pkcs1Key(key)) => PrivateKeyDer::Pkcs1(key),
+            Ok(Item::Pkcs8Key(key)) => PrivateKeyDer::Pkcs8(key),
+            Ok(Item::Sec1Key(key)) => PrivateKeyDer::Sec1(key),
+            Ok(..) => continue,
+            Err(error) => return Err(FromPemError::ParseFileError { error, path }),
+        };
+
+        if found_key.replace(key).is_some() {
+            return Err(FromPemError::MultipleKeysFound(path));
         }
+    }
 
-        found_key.ok_or(FromPemError::NoKeyFound(path))
-    })
-    .await?
+    found_key.ok_or(FromPemError::NoKeyFound(path))
 }
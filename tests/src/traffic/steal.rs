@@ -6,7 +6,7 @@ mod steal_tests {
     use hyper::StatusCode;
     use k8s_openapi::api::core::v1::Pod;
     use kube::{Api, Client};
-    use reqwest::{header::HeaderMap, Url};
+    use reqwest::{Url, header::HeaderMap};
     use rstest::*;
     use tokio::{
         io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
@@ -15,7 +15,7 @@ mod steal_tests {
     };
     use tokio_tungstenite::{
         connect_async,
-        tungstenite::{client::IntoClientRequest, Message},
+        tungstenite::{Message, client::IntoClientRequest},
     };
 
     use crate::utils::{
@@ -237,7 +237,9 @@ mod steal_tests {
 
             let response = match client.get(&url).send().await {
                 Ok(response) if response.status() == StatusCode::BAD_GATEWAY => {
-                    println!("Got a BAD_GATEWAY response, probably meaning that the agent has just processed port unsubscribe");
+                    println!(
+                        "Got a BAD_GATEWAY response, probably meaning that the agent has just processed port unsubscribe"
+                    );
                     sleep(Duration::from_secs(1)).await;
                     continue;
                 }
@@ -246,7 +248,9 @@ mod steal_tests {
                     response
                 }
                 Err(error) => {
-                    println!("Failed to send the request, agent still didn't process port unsubscribe, error: {error}");
+                    println!(
+                        "Failed to send the request, agent still didn't process port unsubscribe, error: {error}"
+                    );
                     sleep(Duration::from_secs(1)).await;
                     continue;
                 }
@@ -564,6 +568,81 @@ mod steal_tests {
         application.assert(&client).await;
     }
 
+    /// Steals only the mutating requests with the `methods` HTTP filter, while GETs sent
+    /// concurrently on the same port keep going to the remote app.
+    #[cfg_attr(not(feature = "job"), ignore)]
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[timeout(Duration::from_secs(120))]
+    async fn filter_with_single_client_requests_by_methods(
+        config_dir: &Path,
+        #[future] basic_service: KubeService,
+        #[future] kube_client: Client,
+        #[values(Application::NodeHTTP)] application: Application,
+    ) {
+        let service = basic_service.await;
+        let kube_client = kube_client.await;
+        let portforwarder = PortForwarder::new(
+            kube_client.clone(),
+            &service.pod_name,
+            &service.namespace,
+            80,
+        )
+        .await;
+        let url = format!("http://{}", portforwarder.address());
+
+        let mut config_path = config_dir.to_path_buf();
+        config_path.push("http_filter_methods.json");
+
+        let client = application
+            .run(
+                &service.pod_container_target(),
+                Some(&service.namespace),
+                None,
+                Some(vec![("MIRRORD_CONFIG_FILE", config_path.to_str().unwrap())]),
+            )
+            .await;
+
+        #[cfg(target_os = "windows")]
+        application.wait_until_listening(&client).await;
+
+        #[cfg(not(target_os = "windows"))]
+        client
+            .wait_for_line(Duration::from_secs(40), "daemon subscribed")
+            .await;
+
+        // Wait until the remote app responds, same as in
+        // `filter_with_single_client_requests_by_path`.
+        let req_client = reqwest::ClientBuilder::new()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        loop {
+            let response = req_client.get(&url).send().await.unwrap();
+            if response.status() == StatusCode::BAD_GATEWAY {
+                sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+
+            break;
+        }
+
+        // GETs go through to the remote app, POSTs are stolen by the local app.
+        let requests = (0..4).flat_map(|_| {
+            [
+                send_request(
+                    req_client.get(&url),
+                    Some("OK - GET: Request completed\n"),
+                    Default::default(),
+                ),
+                send_request(req_client.post(&url), Some("POST"), Default::default()),
+            ]
+        });
+        futures_util::future::join_all(requests).await;
+
+        application.assert(&client).await;
+    }
+
     #[cfg_attr(not(feature = "job"), ignore)]
     #[rstest]
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]